@@ -0,0 +1,150 @@
+//! Generates the gRPC stubs for the `TimeSeries` service in `proto/renewable.proto`. Only the
+//! service is generated, its messages are derived in `src/grpc.rs` so no `protoc` is needed.
+//!
+//! Also compiles in the build details reported by `GET /version`, see `src/build_info.rs`,
+//! and the migrations the schema drift check replays, see `src/schema_check.rs`.
+use std::{
+    env, fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::grpc::{input}"))
+        .output_type(format!("crate::grpc::{output}"))
+        .codec_path("tonic_prost::ProstCodec")
+        .build()
+}
+
+/// `GIT_SHA` when set, e.g. by a Docker build without `.git`, otherwise the checked out commit
+fn git_sha() -> String {
+    if let Ok(sha) = env::var("GIT_SHA") {
+        return sha;
+    }
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |sha| sha.trim().to_string())
+}
+
+/// Seconds since the epoch, honouring `SOURCE_DATE_EPOCH` for reproducible builds
+fn build_timestamp() -> u64 {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        })
+}
+
+/// Cargo features the crate is compiled with, comma separated
+fn enabled_features() -> String {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    features.join(",")
+}
+
+/// Newest directory in `migrations`, the schema level this build migrates to
+fn schema_migration() -> String {
+    fs::read_dir("migrations")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .max()
+        .unwrap_or_default()
+}
+
+/// Writes `migrations.rs` to `OUT_DIR`, a slice of every migration's name and `up.sql`
+/// in the order they are applied
+fn emit_migrations() {
+    let mut names: Vec<String> = fs::read_dir("migrations")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().join("up.sql").is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let entries: String = names
+        .iter()
+        .map(|name| {
+            let up = Path::new(&manifest_dir)
+                .join("migrations")
+                .join(name)
+                .join("up.sql");
+            format!("    ({name:?}, include_str!({:?})),\n", up.display())
+        })
+        .collect();
+    let out_dir = env::var("OUT_DIR").unwrap_or_default();
+    fs::write(
+        Path::new(&out_dir).join("migrations.rs"),
+        format!("&[\n{entries}]\n"),
+    )
+    .unwrap_or_else(|e| panic!("unable to write migrations.rs: {e}"));
+}
+
+fn emit_build_info() {
+    println!("cargo::rustc-env=BUILD_GIT_SHA={}", git_sha());
+    println!("cargo::rustc-env=BUILD_TIMESTAMP={}", build_timestamp());
+    println!("cargo::rustc-env=BUILD_FEATURES={}", enabled_features());
+    println!(
+        "cargo::rustc-env=BUILD_SCHEMA_MIGRATION={}",
+        schema_migration()
+    );
+    println!("cargo::rerun-if-env-changed=GIT_SHA");
+    println!("cargo::rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo::rerun-if-changed=migrations");
+    for git_path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(git_path).exists() {
+            println!("cargo::rerun-if-changed={git_path}");
+        }
+    }
+}
+
+fn main() {
+    let service = Service::builder()
+        .name("TimeSeries")
+        .package("renewable.v1")
+        .method(method(
+            "query_aggregation",
+            "QueryAggregation",
+            "AggregationRequest",
+            "AggregationResponse",
+        ))
+        .method(method(
+            "query_history",
+            "QueryHistory",
+            "HistoryRequest",
+            "HistoryResponse",
+        ))
+        .method(method(
+            "ingest",
+            "Ingest",
+            "IngestRequest",
+            "IngestResponse",
+        ))
+        .build();
+    Builder::new().compile(&[service]);
+    emit_build_info();
+    emit_migrations();
+    println!("cargo::rerun-if-changed=build.rs");
+}
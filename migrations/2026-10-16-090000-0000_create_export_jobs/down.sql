@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS renewable.export_jobs;
+DROP TYPE IF EXISTS renewable.job_status;
@@ -0,0 +1,16 @@
+CREATE TYPE renewable.job_status AS ENUM ('Pending', 'Running', 'Complete', 'Failed');
+
+CREATE TABLE renewable.export_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    status renewable.job_status NOT NULL DEFAULT 'Pending',
+    aggregation renewable.aggregation_kind NOT NULL,
+    from_date TIMESTAMPTZ,
+    to_date TIMESTAMPTZ,
+    file_path TEXT,
+    row_count BIGINT,
+    error TEXT,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_export_jobs_created_at ON renewable.export_jobs(created_at);
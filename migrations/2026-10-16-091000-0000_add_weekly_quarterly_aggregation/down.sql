@@ -0,0 +1,15 @@
+-- Postgres cannot drop enum values, so the type is rebuilt without them
+DELETE FROM renewable.query_history WHERE aggregation IN ('Weekly', 'Quarterly');
+DELETE FROM renewable.export_jobs WHERE aggregation IN ('Weekly', 'Quarterly');
+
+ALTER TYPE renewable.aggregation_kind RENAME TO aggregation_kind_old;
+CREATE TYPE renewable.aggregation_kind AS ENUM ('Hourly', 'DayInMonth', 'Monthly', 'Yearly');
+
+ALTER TABLE renewable.query_history
+    ALTER COLUMN aggregation TYPE renewable.aggregation_kind
+    USING aggregation::text::renewable.aggregation_kind;
+ALTER TABLE renewable.export_jobs
+    ALTER COLUMN aggregation TYPE renewable.aggregation_kind
+    USING aggregation::text::renewable.aggregation_kind;
+
+DROP TYPE renewable.aggregation_kind_old;
@@ -0,0 +1,2 @@
+ALTER TYPE renewable.aggregation_kind ADD VALUE 'Weekly' AFTER 'DayInMonth';
+ALTER TYPE renewable.aggregation_kind ADD VALUE 'Quarterly' AFTER 'Monthly';
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS renewable.idx_ts_store_datetime_recorded_at;
+ALTER TABLE renewable.ts_store DROP COLUMN IF EXISTS recorded_at;
@@ -0,0 +1,9 @@
+ALTER TABLE renewable.ts_store ADD COLUMN recorded_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Existing readings were recorded when their ingestion ran
+UPDATE renewable.ts_store s
+SET recorded_at = m.ingestion_datetime
+FROM renewable.ts_metadata m
+WHERE s.ingestion_id = m.ingestion_id;
+
+CREATE INDEX idx_ts_store_datetime_recorded_at ON renewable.ts_store(datetime, recorded_at);
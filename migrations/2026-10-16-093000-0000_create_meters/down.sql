@@ -0,0 +1,2 @@
+DROP TABLE renewable.meter_series;
+DROP TABLE renewable.meters;
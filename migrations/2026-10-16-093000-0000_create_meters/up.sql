@@ -0,0 +1,16 @@
+CREATE TABLE renewable.meters (
+    id BIGSERIAL PRIMARY KEY,
+    meter_code TEXT NOT NULL UNIQUE,
+    site_name TEXT NOT NULL,
+    capacity_kw NUMERIC,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE renewable.meter_series (
+    id BIGSERIAL PRIMARY KEY,
+    meter_id BIGINT NOT NULL REFERENCES renewable.meters(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    unit TEXT NOT NULL DEFAULT 'kWh',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (meter_id, name)
+);
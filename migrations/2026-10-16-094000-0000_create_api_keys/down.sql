@@ -0,0 +1,3 @@
+ALTER TABLE renewable.query_history DROP COLUMN api_key_id;
+
+DROP TABLE renewable.api_keys;
@@ -0,0 +1,10 @@
+CREATE TABLE renewable.api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ
+);
+
+ALTER TABLE renewable.query_history
+    ADD COLUMN api_key_id BIGINT REFERENCES renewable.api_keys(id) ON DELETE SET NULL;
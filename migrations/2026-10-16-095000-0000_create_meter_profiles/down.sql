@@ -0,0 +1 @@
+DROP TABLE renewable.meter_profiles;
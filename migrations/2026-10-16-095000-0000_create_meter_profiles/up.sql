@@ -0,0 +1,8 @@
+CREATE TABLE renewable.meter_profiles (
+    meter_id BIGINT NOT NULL REFERENCES renewable.meters(id) ON DELETE CASCADE,
+    month SMALLINT NOT NULL CHECK (month BETWEEN 1 AND 12),
+    p50_kwh NUMERIC NOT NULL,
+    p90_kwh NUMERIC NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (meter_id, month)
+);
@@ -0,0 +1 @@
+ALTER TABLE renewable.ts_store ALTER COLUMN amount TYPE NUMERIC(20, 6);
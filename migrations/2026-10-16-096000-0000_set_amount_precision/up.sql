@@ -0,0 +1,3 @@
+-- Precision and scale must match AMOUNT_PRECISION and AMOUNT_SCALE in src/model/mod.rs,
+-- which reject out of range readings before they reach the insert
+ALTER TABLE renewable.ts_store ALTER COLUMN amount TYPE NUMERIC(28, 6);
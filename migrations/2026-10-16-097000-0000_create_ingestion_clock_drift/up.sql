@@ -0,0 +1,9 @@
+CREATE TABLE renewable.ingestion_clock_drift (
+    ingestion_id BIGINT PRIMARY KEY REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    interval_secs INT NOT NULL CHECK (interval_secs > 0),
+    readings BIGINT NOT NULL,
+    drifted_readings BIGINT NOT NULL,
+    max_offset_secs BIGINT NOT NULL,
+    snapped BOOLEAN NOT NULL,
+    collisions BIGINT NOT NULL
+);
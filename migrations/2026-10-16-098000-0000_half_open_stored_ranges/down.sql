@@ -0,0 +1,2 @@
+UPDATE renewable.export_jobs SET to_date = to_date - INTERVAL '1 microsecond' WHERE to_date IS NOT NULL;
+UPDATE renewable.query_history SET to_date = to_date - INTERVAL '1 microsecond' WHERE to_date IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Stored ranges were inclusive of to_date, they are now exclusive of it. Timestamps
+-- have microsecond resolution so the next instant keeps the same readings in range.
+UPDATE renewable.export_jobs SET to_date = to_date + INTERVAL '1 microsecond' WHERE to_date IS NOT NULL;
+UPDATE renewable.query_history SET to_date = to_date + INTERVAL '1 microsecond' WHERE to_date IS NOT NULL;
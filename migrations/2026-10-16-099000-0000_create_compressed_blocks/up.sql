@@ -0,0 +1,15 @@
+-- Research layout for large archives, one delta-of-delta encoded block per ingestion
+-- and UTC day. Only written by the compressed-storage build, ts_store stays the source
+-- of truth so the two layouts can be compared.
+CREATE TABLE renewable.ts_compressed_blocks (
+    ingestion_id BIGINT NOT NULL REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    first_datetime TIMESTAMPTZ NOT NULL,
+    last_datetime TIMESTAMPTZ NOT NULL,
+    readings INT NOT NULL CHECK (readings > 0),
+    recorded_at TIMESTAMPTZ NOT NULL,
+    block BYTEA NOT NULL,
+    PRIMARY KEY (ingestion_id, day)
+);
+
+CREATE INDEX idx_ts_compressed_blocks_range ON renewable.ts_compressed_blocks(first_datetime, last_datetime);
@@ -0,0 +1,5 @@
+DROP TRIGGER ts_store_daily_summary_delete ON renewable.ts_store;
+DROP TRIGGER ts_store_daily_summary_update ON renewable.ts_store;
+DROP TRIGGER ts_store_daily_summary_insert ON renewable.ts_store;
+DROP FUNCTION renewable.refresh_daily_summary();
+DROP TABLE renewable.ts_daily_summary;
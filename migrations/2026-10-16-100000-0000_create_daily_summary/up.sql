@@ -0,0 +1,71 @@
+-- Per ingestion and day totals, letting coarse aggregations skip ts_store for whole days.
+-- Days are DATE_TRUNC('day', datetime) as the aggregation queries truncate, and the
+-- triggers recompute every day a statement touches from ts_store.
+CREATE TABLE renewable.ts_daily_summary (
+    ingestion_id BIGINT NOT NULL REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    day TIMESTAMPTZ NOT NULL,
+    first_datetime TIMESTAMPTZ NOT NULL,
+    last_datetime TIMESTAMPTZ NOT NULL,
+    total_amount NUMERIC NOT NULL,
+    readings BIGINT NOT NULL,
+    PRIMARY KEY (ingestion_id, day)
+);
+
+CREATE INDEX idx_ts_daily_summary_day ON renewable.ts_daily_summary(day);
+
+INSERT INTO renewable.ts_daily_summary
+SELECT ingestion_id, DATE_TRUNC('day', datetime), MIN(datetime), MAX(datetime), SUM(amount), COUNT(*)
+FROM renewable.ts_store
+GROUP BY 1, 2;
+
+CREATE FUNCTION renewable.refresh_daily_summary() RETURNS TRIGGER AS $$
+DECLARE
+    ids BIGINT[];
+    days TIMESTAMPTZ[];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        SELECT array_agg(ingestion_id), array_agg(day) INTO ids, days
+        FROM (SELECT DISTINCT ingestion_id, DATE_TRUNC('day', datetime) AS day FROM new_rows) t;
+    ELSIF TG_OP = 'DELETE' THEN
+        SELECT array_agg(ingestion_id), array_agg(day) INTO ids, days
+        FROM (SELECT DISTINCT ingestion_id, DATE_TRUNC('day', datetime) AS day FROM old_rows) t;
+    ELSE
+        SELECT array_agg(ingestion_id), array_agg(day) INTO ids, days
+        FROM (
+            SELECT ingestion_id, DATE_TRUNC('day', datetime) AS day FROM new_rows
+            UNION
+            SELECT ingestion_id, DATE_TRUNC('day', datetime) AS day FROM old_rows
+        ) t;
+    END IF;
+
+    DELETE FROM renewable.ts_daily_summary s
+    USING unnest(ids, days) AS t(ingestion_id, day)
+    WHERE s.ingestion_id = t.ingestion_id AND s.day = t.day;
+
+    INSERT INTO renewable.ts_daily_summary
+    SELECT s.ingestion_id, t.day, MIN(s.datetime), MAX(s.datetime), SUM(s.amount), COUNT(*)
+    FROM unnest(ids, days) AS t(ingestion_id, day)
+    JOIN renewable.ts_store s
+      ON s.ingestion_id = t.ingestion_id
+     AND s.datetime >= t.day
+     AND s.datetime < t.day + INTERVAL '1 day'
+    GROUP BY s.ingestion_id, t.day;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ts_store_daily_summary_insert
+AFTER INSERT ON renewable.ts_store
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_update
+AFTER UPDATE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_delete
+AFTER DELETE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
@@ -0,0 +1,3 @@
+ALTER TABLE renewable.ts_metadata DROP COLUMN series_id;
+
+DROP TABLE renewable.series;
@@ -0,0 +1,15 @@
+-- A named stream of readings, such as one meter or asset. Readings reference their series
+-- through the ingestion that stored them, ingestions without one stay unattributed.
+CREATE TABLE renewable.series (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    unit TEXT NOT NULL DEFAULT 'kWh',
+    fuel_type TEXT,
+    site TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE renewable.ts_metadata
+    ADD COLUMN series_id BIGINT REFERENCES renewable.series(id);
+
+CREATE INDEX idx_ts_metadata_series_id ON renewable.ts_metadata(series_id);
@@ -0,0 +1 @@
+ALTER TABLE renewable.ts_store DROP COLUMN extra;
@@ -0,0 +1,3 @@
+-- Columns of a readings CSV beyond the datetime and amount, keyed by header, when the
+-- ingestion captured them. Queried with the JSONB operators, e.g. extra->>'status'.
+ALTER TABLE renewable.ts_store ADD COLUMN extra JSONB;
@@ -0,0 +1 @@
+ALTER TABLE renewable.api_keys DROP COLUMN export_recipient;
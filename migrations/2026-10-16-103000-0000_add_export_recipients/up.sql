@@ -0,0 +1,3 @@
+-- Age or OpenPGP public key the exports of a key are encrypted to before they are
+-- written, for tenants whose policies forbid plain-text meter data outside the database
+ALTER TABLE renewable.api_keys ADD COLUMN export_recipient TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX renewable.idx_ts_metadata_ranked;
+ALTER TABLE renewable.ts_metadata DROP COLUMN priority;
@@ -0,0 +1,8 @@
+-- Priority of an ingestion's source, set from `source_priorities` when it is stored. A
+-- reading is superseded by a reading at the same timestamp from a higher priority
+-- ingestion of the same series, e.g. a provider's final feed over its provisional one.
+ALTER TABLE renewable.ts_metadata ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+-- Queries only look for superseding readings once any ingestion is ranked
+CREATE INDEX idx_ts_metadata_ranked ON renewable.ts_metadata(series_id, priority)
+    WHERE priority <> 0;
@@ -0,0 +1,4 @@
+DROP TRIGGER ts_daily_summary_monthly_delete ON renewable.ts_daily_summary;
+DROP TRIGGER ts_daily_summary_monthly_insert ON renewable.ts_daily_summary;
+DROP FUNCTION renewable.refresh_monthly_summary();
+DROP TABLE renewable.ts_monthly_summary;
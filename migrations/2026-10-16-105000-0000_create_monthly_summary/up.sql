@@ -0,0 +1,59 @@
+-- Per ingestion and month totals rolled up from ts_daily_summary, letting monthly,
+-- quarterly and yearly aggregations read one row per ingestion and month for the months
+-- wholly inside their range. The triggers recompute every month a statement on the daily
+-- summaries touches, so months follow the days as the days follow ts_store.
+CREATE TABLE renewable.ts_monthly_summary (
+    ingestion_id BIGINT NOT NULL REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    month TIMESTAMPTZ NOT NULL,
+    total_amount NUMERIC NOT NULL,
+    readings BIGINT NOT NULL,
+    PRIMARY KEY (ingestion_id, month)
+);
+
+CREATE INDEX idx_ts_monthly_summary_month ON renewable.ts_monthly_summary(month);
+
+INSERT INTO renewable.ts_monthly_summary
+SELECT ingestion_id, DATE_TRUNC('month', day), SUM(total_amount), SUM(readings)
+FROM renewable.ts_daily_summary
+GROUP BY 1, 2;
+
+CREATE FUNCTION renewable.refresh_monthly_summary() RETURNS TRIGGER AS $$
+DECLARE
+    ids BIGINT[];
+    months TIMESTAMPTZ[];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        SELECT array_agg(ingestion_id), array_agg(month) INTO ids, months
+        FROM (SELECT DISTINCT ingestion_id, DATE_TRUNC('month', day) AS month FROM new_days) t;
+    ELSE
+        SELECT array_agg(ingestion_id), array_agg(month) INTO ids, months
+        FROM (SELECT DISTINCT ingestion_id, DATE_TRUNC('month', day) AS month FROM old_days) t;
+    END IF;
+
+    DELETE FROM renewable.ts_monthly_summary s
+    USING unnest(ids, months) AS t(ingestion_id, month)
+    WHERE s.ingestion_id = t.ingestion_id AND s.month = t.month;
+
+    INSERT INTO renewable.ts_monthly_summary
+    SELECT d.ingestion_id, t.month, SUM(d.total_amount), SUM(d.readings)
+    FROM unnest(ids, months) AS t(ingestion_id, month)
+    JOIN renewable.ts_daily_summary d
+      ON d.ingestion_id = t.ingestion_id
+     AND d.day >= t.month
+     AND d.day < t.month + INTERVAL '1 month'
+    GROUP BY d.ingestion_id, t.month;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- The daily triggers only ever delete and insert summaries
+CREATE TRIGGER ts_daily_summary_monthly_insert
+AFTER INSERT ON renewable.ts_daily_summary
+REFERENCING NEW TABLE AS new_days
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_monthly_summary();
+
+CREATE TRIGGER ts_daily_summary_monthly_delete
+AFTER DELETE ON renewable.ts_daily_summary
+REFERENCING OLD TABLE AS old_days
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_monthly_summary();
@@ -0,0 +1,42 @@
+ALTER TABLE renewable.ts_store RENAME TO ts_store_partitioned;
+ALTER TABLE renewable.ts_store_partitioned RENAME CONSTRAINT ts_store_pkey TO ts_store_partitioned_pkey;
+DROP INDEX renewable.idx_ts_store_datetime;
+DROP INDEX renewable.idx_ts_store_datetime_recorded_at;
+DROP TRIGGER ts_store_daily_summary_insert ON renewable.ts_store_partitioned;
+DROP TRIGGER ts_store_daily_summary_update ON renewable.ts_store_partitioned;
+DROP TRIGGER ts_store_daily_summary_delete ON renewable.ts_store_partitioned;
+
+CREATE TABLE renewable.ts_store (
+    -- Named as before, the old table's constraint of that name is dropped with it
+    ingestion_id BIGINT NOT NULL CONSTRAINT ts_store_ingestion_id_fkey
+        REFERENCES renewable.ts_metadata(ingestion_id),
+    datetime TIMESTAMPTZ NOT NULL,
+    amount NUMERIC(28, 6) NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    extra JSONB,
+    PRIMARY KEY (ingestion_id, datetime)
+);
+
+CREATE INDEX idx_ts_store_datetime ON renewable.ts_store(ingestion_id, datetime);
+CREATE INDEX idx_ts_store_datetime_recorded_at ON renewable.ts_store(datetime, recorded_at);
+
+INSERT INTO renewable.ts_store (ingestion_id, datetime, amount, recorded_at, extra)
+SELECT ingestion_id, datetime, amount, recorded_at, extra FROM renewable.ts_store_partitioned;
+
+DROP TABLE renewable.ts_store_partitioned;
+DROP FUNCTION renewable.ensure_ts_store_partition(TIMESTAMPTZ);
+
+CREATE TRIGGER ts_store_daily_summary_insert
+AFTER INSERT ON renewable.ts_store
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_update
+AFTER UPDATE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_delete
+AFTER DELETE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
@@ -0,0 +1,85 @@
+-- Range partitions of ts_store by UTC month, so scans of a range and retention touch only
+-- the months involved. Readings of a month without a partition land in ts_store_default
+-- until renewable.ensure_ts_store_partition creates the month's partition, moving them in.
+ALTER TABLE renewable.ts_store RENAME TO ts_store_unpartitioned;
+ALTER TABLE renewable.ts_store_unpartitioned RENAME CONSTRAINT ts_store_pkey TO ts_store_unpartitioned_pkey;
+DROP INDEX renewable.idx_ts_store_datetime;
+DROP INDEX renewable.idx_ts_store_datetime_recorded_at;
+DROP TRIGGER ts_store_daily_summary_insert ON renewable.ts_store_unpartitioned;
+DROP TRIGGER ts_store_daily_summary_update ON renewable.ts_store_unpartitioned;
+DROP TRIGGER ts_store_daily_summary_delete ON renewable.ts_store_unpartitioned;
+
+CREATE TABLE renewable.ts_store (
+    -- Named as before, the old table's constraint of that name is dropped with it
+    ingestion_id BIGINT NOT NULL CONSTRAINT ts_store_ingestion_id_fkey
+        REFERENCES renewable.ts_metadata(ingestion_id),
+    datetime TIMESTAMPTZ NOT NULL,
+    amount NUMERIC(28, 6) NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    extra JSONB,
+    PRIMARY KEY (ingestion_id, datetime)
+) PARTITION BY RANGE (datetime);
+
+CREATE INDEX idx_ts_store_datetime ON renewable.ts_store(ingestion_id, datetime);
+CREATE INDEX idx_ts_store_datetime_recorded_at ON renewable.ts_store(datetime, recorded_at);
+
+CREATE TABLE renewable.ts_store_default PARTITION OF renewable.ts_store DEFAULT;
+
+-- Creates the partition of the UTC month holding `month`, moving its readings out of the
+-- default partition, and returns whether it had to. Serialised by an advisory lock so
+-- instances maintaining partitions at the same time do not collide.
+CREATE FUNCTION renewable.ensure_ts_store_partition(month TIMESTAMPTZ) RETURNS BOOLEAN AS $$
+DECLARE
+    lower_bound TIMESTAMPTZ := DATE_TRUNC('month', month, 'UTC');
+    upper_bound TIMESTAMPTZ := lower_bound + INTERVAL '1 month';
+    partition_name TEXT := 'ts_store_' || TO_CHAR(lower_bound AT TIME ZONE 'UTC', 'YYYY_MM');
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtext('renewable.ts_store partitions'));
+    IF to_regclass('renewable.' || partition_name) IS NOT NULL THEN
+        RETURN FALSE;
+    END IF;
+
+    EXECUTE format(
+        'CREATE TABLE renewable.%I (LIKE renewable.ts_store INCLUDING DEFAULTS)',
+        partition_name
+    );
+    -- Statements on a partition do not fire the statement triggers of ts_store, so the
+    -- summaries of the moved readings are left as they are
+    EXECUTE format(
+        'WITH moved AS ( '
+        '    DELETE FROM renewable.ts_store_default WHERE datetime >= %L AND datetime < %L '
+        '    RETURNING * '
+        ') INSERT INTO renewable.%I SELECT * FROM moved',
+        lower_bound, upper_bound, partition_name
+    );
+    EXECUTE format(
+        'ALTER TABLE renewable.ts_store ATTACH PARTITION renewable.%I FOR VALUES FROM (%L) TO (%L)',
+        partition_name, lower_bound, upper_bound
+    );
+    RETURN TRUE;
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT renewable.ensure_ts_store_partition(month)
+FROM (SELECT DISTINCT DATE_TRUNC('month', datetime, 'UTC') AS month FROM renewable.ts_store_unpartitioned) months;
+
+INSERT INTO renewable.ts_store (ingestion_id, datetime, amount, recorded_at, extra)
+SELECT ingestion_id, datetime, amount, recorded_at, extra FROM renewable.ts_store_unpartitioned;
+
+DROP TABLE renewable.ts_store_unpartitioned;
+
+-- Statement triggers on a partitioned table see the rows written to every partition
+CREATE TRIGGER ts_store_daily_summary_insert
+AFTER INSERT ON renewable.ts_store
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_update
+AFTER UPDATE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
+
+CREATE TRIGGER ts_store_daily_summary_delete
+AFTER DELETE ON renewable.ts_store
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT EXECUTE FUNCTION renewable.refresh_daily_summary();
@@ -0,0 +1,6 @@
+ALTER TABLE renewable.query_history
+    DROP COLUMN duration_ms,
+    DROP COLUMN row_count,
+    DROP COLUMN status;
+
+DROP TYPE renewable.query_status;
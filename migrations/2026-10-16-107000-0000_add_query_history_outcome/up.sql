@@ -0,0 +1,7 @@
+CREATE TYPE renewable.query_status AS ENUM ('Succeeded', 'Failed');
+
+-- Entries recorded before queries were timed keep no duration or row count
+ALTER TABLE renewable.query_history
+    ADD COLUMN duration_ms BIGINT,
+    ADD COLUMN row_count BIGINT,
+    ADD COLUMN status renewable.query_status NOT NULL DEFAULT 'Succeeded';
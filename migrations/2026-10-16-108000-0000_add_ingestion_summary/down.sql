@@ -0,0 +1,9 @@
+ALTER TABLE renewable.ts_metadata
+    DROP COLUMN status,
+    DROP COLUMN duration_ms,
+    DROP COLUMN error_count,
+    DROP COLUMN max_datetime,
+    DROP COLUMN min_datetime,
+    DROP COLUMN row_count;
+
+DROP TYPE renewable.ingestion_status;
@@ -0,0 +1,29 @@
+-- Summary of each ingestion kept by the ingestion pipeline, so listing ingestions reads
+-- ts_metadata alone rather than scanning the readings for their count and span
+CREATE TYPE renewable.ingestion_status AS ENUM ('Pending', 'Complete', 'Failed');
+
+ALTER TABLE renewable.ts_metadata
+    ADD COLUMN row_count BIGINT,
+    ADD COLUMN min_datetime TIMESTAMPTZ,
+    ADD COLUMN max_datetime TIMESTAMPTZ,
+    ADD COLUMN error_count BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN duration_ms BIGINT,
+    ADD COLUMN status renewable.ingestion_status NOT NULL DEFAULT 'Complete';
+
+UPDATE renewable.ts_metadata m
+SET row_count = COALESCE(s.readings, 0),
+    min_datetime = s.first_datetime,
+    max_datetime = s.last_datetime
+FROM (
+    SELECT m.ingestion_id,
+           SUM(d.readings)::BIGINT AS readings,
+           MIN(d.first_datetime) AS first_datetime,
+           MAX(d.last_datetime) AS last_datetime
+    FROM renewable.ts_metadata m
+    LEFT JOIN renewable.ts_daily_summary d ON d.ingestion_id = m.ingestion_id
+    GROUP BY m.ingestion_id
+) s
+WHERE s.ingestion_id = m.ingestion_id;
+
+-- Ingestions recorded from here on start out pending until their readings are stored
+ALTER TABLE renewable.ts_metadata ALTER COLUMN status SET DEFAULT 'Pending';
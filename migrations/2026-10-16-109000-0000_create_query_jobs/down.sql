@@ -0,0 +1 @@
+DROP TABLE renewable.query_jobs;
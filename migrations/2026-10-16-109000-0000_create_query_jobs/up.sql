@@ -0,0 +1,16 @@
+-- Aggregations answered in the background, holding the JSON response once complete
+CREATE TABLE renewable.query_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    status renewable.job_status NOT NULL DEFAULT 'Pending',
+    aggregation renewable.aggregation_kind NOT NULL,
+    from_date TIMESTAMPTZ,
+    to_date TIMESTAMPTZ,
+    api_key_id BIGINT REFERENCES renewable.api_keys(id) ON DELETE SET NULL,
+    result JSONB,
+    row_count BIGINT,
+    error TEXT,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_query_jobs_created_at ON renewable.query_jobs(created_at);
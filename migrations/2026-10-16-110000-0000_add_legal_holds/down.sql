@@ -0,0 +1,2 @@
+DROP TABLE renewable.audit_log;
+DROP TABLE renewable.legal_holds;
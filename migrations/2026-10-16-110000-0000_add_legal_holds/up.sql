@@ -0,0 +1,19 @@
+-- Ingestions held during a dispute, kept from retention and deletion until released
+CREATE TABLE renewable.legal_holds (
+    ingestion_id BIGINT PRIMARY KEY REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE RESTRICT,
+    placed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    reason TEXT NOT NULL
+);
+
+-- Placed and released holds, and the deletions and purges they refused. Entries outlive
+-- their ingestion, so it is not referenced.
+CREATE TABLE renewable.audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    action TEXT NOT NULL,
+    ingestion_id BIGINT,
+    permitted BOOLEAN NOT NULL,
+    detail TEXT
+);
+
+CREATE INDEX idx_audit_log_recorded_at ON renewable.audit_log(recorded_at);
@@ -0,0 +1 @@
+DROP TABLE renewable.storage_stats;
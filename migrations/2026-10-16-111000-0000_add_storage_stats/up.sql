@@ -0,0 +1,11 @@
+-- Daily samples of the size of every table and partition, for forecasting growth
+CREATE TABLE renewable.storage_stats (
+    sampled_on DATE NOT NULL,
+    relation TEXT NOT NULL,
+    -- Partitioned table a partition belongs to
+    parent TEXT,
+    total_bytes BIGINT NOT NULL,
+    index_bytes BIGINT NOT NULL,
+    row_estimate BIGINT NOT NULL,
+    PRIMARY KEY (sampled_on, relation)
+);
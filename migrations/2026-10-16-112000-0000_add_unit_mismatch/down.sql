@@ -0,0 +1 @@
+ALTER TABLE renewable.ts_metadata DROP COLUMN unit_mismatch;
@@ -0,0 +1,3 @@
+-- Median daily mean reading of an ingestion over that of its series' history, set when
+-- they are about a power of 1000 apart, such as MWh uploaded as kWh
+ALTER TABLE renewable.ts_metadata ADD COLUMN unit_mismatch DOUBLE PRECISION;
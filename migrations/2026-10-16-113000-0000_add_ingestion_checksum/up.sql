@@ -0,0 +1,6 @@
+-- SHA-256 of the content of the file an ingestion was read from, so a copy of a file
+-- already ingested under another source is skipped. Failed ingestions may be retried.
+ALTER TABLE renewable.ts_metadata ADD COLUMN checksum TEXT;
+
+CREATE UNIQUE INDEX idx_ts_metadata_checksum ON renewable.ts_metadata(checksum)
+    WHERE status <> 'Failed';
@@ -0,0 +1,2 @@
+DROP TABLE renewable.ingestion_chain;
+DROP FUNCTION renewable.ingestion_chain_append_only();
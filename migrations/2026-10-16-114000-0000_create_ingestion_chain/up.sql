@@ -0,0 +1,32 @@
+-- Hash chain over every change to the readings of an ingestion: each entry records a
+-- hash of the ingestion's readings after the change and a hash over itself and the entry
+-- before, so history altered outside the application no longer verifies
+CREATE TABLE renewable.ingestion_chain (
+    seq BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    event TEXT NOT NULL,
+    ingestion_id BIGINT NOT NULL,
+    readings BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    -- NULL for the first entry only, unique so the chain cannot fork
+    previous_hash TEXT UNIQUE,
+    hash TEXT NOT NULL UNIQUE
+);
+
+CREATE INDEX idx_ingestion_chain_ingestion_id ON renewable.ingestion_chain(ingestion_id, seq);
+CREATE UNIQUE INDEX idx_ingestion_chain_first ON renewable.ingestion_chain((previous_hash IS NULL))
+    WHERE previous_hash IS NULL;
+
+CREATE FUNCTION renewable.ingestion_chain_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'renewable.ingestion_chain is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ingestion_chain_append_only
+    BEFORE UPDATE OR DELETE ON renewable.ingestion_chain
+    FOR EACH ROW EXECUTE FUNCTION renewable.ingestion_chain_append_only();
+
+CREATE TRIGGER ingestion_chain_no_truncate
+    BEFORE TRUNCATE ON renewable.ingestion_chain
+    FOR EACH STATEMENT EXECUTE FUNCTION renewable.ingestion_chain_append_only();
@@ -0,0 +1 @@
+DROP TABLE renewable.ts_store_revisions;
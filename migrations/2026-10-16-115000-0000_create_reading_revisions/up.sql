@@ -0,0 +1,15 @@
+-- Superseded versions of readings: ts_store holds the latest revision of each reading,
+-- valid from its recorded_at, and an overwriting correction moves the version it
+-- replaces here, numbered from 1 and valid until the correction was recorded
+CREATE TABLE renewable.ts_store_revisions (
+    ingestion_id BIGINT NOT NULL REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    datetime TIMESTAMPTZ NOT NULL,
+    revision INT NOT NULL CHECK (revision > 0),
+    amount NUMERIC NOT NULL,
+    extra JSONB,
+    valid_from TIMESTAMPTZ NOT NULL,
+    valid_to TIMESTAMPTZ NOT NULL CHECK (valid_to >= valid_from),
+    PRIMARY KEY (ingestion_id, datetime, revision)
+);
+
+CREATE INDEX idx_ts_store_revisions_datetime ON renewable.ts_store_revisions(datetime);
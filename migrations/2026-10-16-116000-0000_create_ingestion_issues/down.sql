@@ -0,0 +1 @@
+DROP TABLE renewable.ingestion_issues;
@@ -0,0 +1,12 @@
+-- Problems found in an ingestion's file: rows that could not be parsed, timestamps held
+-- by several readings, negative amounts and gaps in the reading interval
+CREATE TABLE renewable.ingestion_issues (
+    id BIGSERIAL PRIMARY KEY,
+    ingestion_id BIGINT NOT NULL REFERENCES renewable.ts_metadata(ingestion_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    line BIGINT,
+    datetime TIMESTAMPTZ,
+    detail TEXT NOT NULL
+);
+
+CREATE INDEX idx_ingestion_issues_ingestion_id ON renewable.ingestion_issues(ingestion_id, id);
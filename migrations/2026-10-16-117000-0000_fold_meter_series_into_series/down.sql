@@ -0,0 +1,20 @@
+CREATE TABLE renewable.meter_series (
+    id BIGSERIAL PRIMARY KEY,
+    meter_id BIGINT NOT NULL REFERENCES renewable.meters(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    unit TEXT NOT NULL DEFAULT 'kWh',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (meter_id, name)
+);
+
+INSERT INTO renewable.meter_series (meter_id, name, unit, created_at)
+SELECT s.meter_id, SUBSTRING(s.name FROM LENGTH(m.meter_code) + 2), s.unit, s.created_at
+FROM renewable.series s
+JOIN renewable.meters m ON m.id = s.meter_id
+WHERE s.name LIKE m.meter_code || '/%';
+
+DELETE FROM renewable.series s
+WHERE s.meter_id IS NOT NULL
+  AND NOT EXISTS (SELECT 1 FROM renewable.ts_metadata WHERE series_id = s.id);
+
+ALTER TABLE renewable.series DROP COLUMN meter_id;
@@ -0,0 +1,15 @@
+-- A meter's channels are series like any other, so readings ingested under one are
+-- attributed to its meter. Series names are unique across meters, so a channel's is
+-- qualified by its meter's code, e.g. MTR-001/generation.
+ALTER TABLE renewable.series
+    ADD COLUMN meter_id BIGINT REFERENCES renewable.meters(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_series_meter_id ON renewable.series(meter_id);
+
+INSERT INTO renewable.series (name, unit, site, meter_id, created_at)
+SELECT m.meter_code || '/' || ms.name, ms.unit, m.site_name, m.id, ms.created_at
+FROM renewable.meter_series ms
+JOIN renewable.meters m ON m.id = ms.meter_id
+ON CONFLICT (name) DO NOTHING;
+
+DROP TABLE renewable.meter_series;
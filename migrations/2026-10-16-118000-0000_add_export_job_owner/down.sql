@@ -0,0 +1 @@
+ALTER TABLE renewable.export_jobs DROP COLUMN api_key_id;
@@ -0,0 +1,4 @@
+-- Key that created each export job, the only key its status and download URL are shown to
+-- as the file carries that key's watermark. Jobs created before are shown to no key.
+ALTER TABLE renewable.export_jobs
+    ADD COLUMN api_key_id BIGINT REFERENCES renewable.api_keys(id) ON DELETE SET NULL;
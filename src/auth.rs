@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    db::{
+        PgError, TimedPool,
+        api_keys::{ensure_api_key, find_active_key},
+    },
+    error::ApiError,
+};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use deadpool_diesel::postgres::Pool;
+use sha2::{Digest as _, Sha256};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Keys are only ever stored and compared as their SHA-256 digest
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Registers `BOOTSTRAP_API_KEY` when set, so a fresh deployment has a key to call the API with
+pub async fn bootstrap_api_key(pg_pool: &Pool) -> Result<(), PgError> {
+    let Ok(key) = env::var("BOOTSTRAP_API_KEY") else {
+        return Ok(());
+    };
+    let key_hash = hash_key(&key);
+
+    let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+    conn.interact(move |conn| ensure_api_key("bootstrap", &key_hash, conn))
+        .await
+        .map_err(PgError::InteractionError)?
+        .map_err(PgError::DieselError)?;
+    Ok(())
+}
+
+/// Id of the API key that authenticated the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKey(pub i64);
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiKey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .copied()
+            .ok_or(ApiError::Unauthorized("Missing API key"))
+    }
+}
+
+/// Resolves the `X-Api-Key` of REST requests. With `query_fallback` set, the keys found
+/// active are remembered so that queries the hot cache can answer still authenticate
+/// while Postgres is unavailable, as the keys stood when it was last reached.
+#[derive(Clone)]
+pub struct ApiKeyVerifier {
+    pg_pool: TimedPool,
+    known: Option<Arc<RwLock<HashMap<String, i64>>>>,
+}
+
+impl ApiKeyVerifier {
+    pub fn new(pg_pool: TimedPool, remember: bool) -> Self {
+        Self {
+            pg_pool,
+            known: remember.then(Arc::default),
+        }
+    }
+
+    /// [`resolve_api_key`], falling back to the keys remembered when Postgres is unavailable
+    pub async fn resolve(&self, key: Option<&str>) -> Result<i64, ApiError> {
+        let resolved = resolve_api_key(&self.pg_pool, key).await;
+        let (Some(known), Some(key)) = (&self.known, key) else {
+            return resolved;
+        };
+        let key_hash = hash_key(key);
+        match resolved {
+            Ok(key_id) => {
+                known
+                    .write()
+                    .expect("known keys lock poisoned")
+                    .insert(key_hash, key_id);
+                Ok(key_id)
+            }
+            Err(e) if e.is_database_unavailable() => known
+                .read()
+                .expect("known keys lock poisoned")
+                .get(&key_hash)
+                .copied()
+                .ok_or(e),
+            Err(e) => {
+                known
+                    .write()
+                    .expect("known keys lock poisoned")
+                    .remove(&key_hash);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Rejects requests without an active `X-Api-Key` with 401, otherwise records the key
+/// on the request for handlers to attribute their work to
+pub async fn require_api_key(
+    State(verifier): State<ApiKeyVerifier>,
+    request: Request,
+    next: Next,
+) -> Response {
+    authenticate(&verifier, request, next)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+/// Id of the active key matching `key`, shared by the REST middleware and the gRPC service.
+/// The connection is returned before this resolves, so the caller can take its own.
+pub async fn resolve_api_key(pg_pool: &TimedPool, key: Option<&str>) -> Result<i64, ApiError> {
+    let key = key.ok_or(ApiError::Unauthorized("Missing API key"))?;
+    let key_hash = hash_key(key);
+
+    let conn = pg_pool.get().await.map_err(ApiError::Pool)?;
+    conn.interact(move |conn| find_active_key(&key_hash, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::Unauthorized("Invalid API key"))
+}
+
+/// Digest of `ADMIN_TOKEN`, the operator secret guarding the `/admin` routes. API keys
+/// are handed to callers so never grant admin access, and the routes are disabled when unset.
+#[derive(Clone)]
+pub struct AdminToken(Option<String>);
+
+impl AdminToken {
+    pub fn from_env() -> Self {
+        let token = env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        Self(token.as_deref().map(hash_key))
+    }
+
+    pub fn verify(&self, token: Option<&str>) -> Result<(), ApiError> {
+        let expected = self
+            .0
+            .as_deref()
+            .ok_or(ApiError::Forbidden("Admin API disabled"))?;
+        let token = token.ok_or(ApiError::Unauthorized("Missing admin token"))?;
+        if hash_key(token) != expected {
+            return Err(ApiError::Unauthorized("Invalid admin token"));
+        }
+        Ok(())
+    }
+}
+
+/// Rejects requests without the configured `X-Admin-Token`
+pub async fn require_admin_token(
+    State(admin_token): State<AdminToken>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match admin_token.verify(token) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn authenticate(
+    verifier: &ApiKeyVerifier,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let key_id = verifier.resolve(key).await?;
+
+    request.extensions_mut().insert(ApiKey(key_id));
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use deadpool_diesel::{
+        Runtime,
+        postgres::{Manager, Pool},
+    };
+
+    use super::{AdminToken, ApiKeyVerifier, hash_key};
+    use crate::{db::TimedPool, error::ApiError};
+
+    #[tokio::test]
+    async fn test_known_keys_authenticate_while_postgres_is_unavailable() {
+        let unreachable = Pool::builder(Manager::new(
+            "postgres://nobody@127.0.0.1:1/renewable",
+            Runtime::Tokio1,
+        ))
+        .build()
+        .unwrap();
+
+        let verifier = ApiKeyVerifier::new(TimedPool::new(unreachable), true);
+        if let Some(known) = &verifier.known {
+            known.write().unwrap().insert(hash_key("secret"), 7);
+        }
+        assert_eq!(verifier.resolve(Some("secret")).await.unwrap(), 7);
+        let unknown = verifier.resolve(Some("other")).await.unwrap_err();
+        assert!(unknown.is_database_unavailable());
+        assert!(matches!(
+            verifier.resolve(None).await,
+            Err(ApiError::Unauthorized(_))
+        ));
+
+        let forgetful = ApiKeyVerifier {
+            known: None,
+            ..verifier.clone()
+        };
+        assert!(
+            forgetful
+                .resolve(Some("secret"))
+                .await
+                .unwrap_err()
+                .is_database_unavailable()
+        );
+    }
+
+    #[test]
+    fn test_hash_key_is_stable_hex_digest() {
+        let hash = hash_key("secret");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_key("secret"));
+        assert_ne!(hash, hash_key("Secret"));
+    }
+
+    #[test]
+    fn test_admin_token_verify() {
+        let disabled = AdminToken(None);
+        assert!(matches!(
+            disabled.verify(Some("root")),
+            Err(ApiError::Forbidden(_))
+        ));
+
+        let token = AdminToken(Some(hash_key("root")));
+        assert!(token.verify(Some("root")).is_ok());
+        assert!(matches!(
+            token.verify(Some("Root")),
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert!(matches!(token.verify(None), Err(ApiError::Unauthorized(_))));
+    }
+}
@@ -1,55 +1,727 @@
-use std::{error::Error, net::SocketAddr, time::Duration};
+use std::{
+    env,
+    error::Error,
+    fs, io, iter,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+};
 
 use axum::{
-    Router,
-    http::StatusCode,
-    routing::{get, post},
+    Router, middleware,
+    routing::{delete, get, post, put},
 };
+use chrono::{TimeDelta, Utc};
+use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
+#[cfg(feature = "redis-cache")]
+use renewable_ts_axum::cache::SharedCache;
 use renewable_ts_axum::{
-    db::{establish_pg_connection, seed_database::seed_database},
-    logger::init_logging,
-    route,
-    shutdown::shutdown_signal,
+    auth::{AdminToken, ApiKeyVerifier, bootstrap_api_key, require_admin_token, require_api_key},
+    build_info::log_startup_banner,
+    config::AppConfig,
+    cursor::CursorSigner,
+    db::{
+        Pools, api_keys::api_key_ids, establish_pg_connection, establish_read_pool,
+        seed_database::seed_database,
+    },
+    deadline::enforce_deadline,
+    decimal,
+    drift::DriftConfig,
+    error::scope_request_id,
+    export::ExportConfig,
+    grpc::{self, TimeSeriesService},
+    history::HistoryWriter,
+    hot_cache::HotCache,
+    leader::LeaderElection,
+    listener::{ListenerConfig, ServerTuning},
+    live::IngestionEvents,
+    logger::{init_logging, init_logging_to},
+    notify::ChangeFeed,
+    offline,
+    openapi::ApiDoc,
+    partitions,
+    read_only::{ReadOnlyMode, reject_writes},
+    register::RegisterConfig,
+    response_cache::ResponseCache,
+    retention::{self, RetentionJob},
+    rounding, route, schema_check,
+    selftest::{self, SelfTestConfig},
+    shadow::ShadowQueries,
+    shutdown::Shutdown,
+    state::AppState,
+    storage_stats,
+    telemetry::{self, Telemetry, count_usage},
+    upload::UploadBucket,
+    watcher::{self, Outcome},
+    watermark,
+};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
 };
-use tokio::net::TcpListener;
-use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use utoipa::OpenApi as _;
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Renewable time series API, and the one-off jobs run against its database
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Serves the API when omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// What the binary was asked to do by its first argument
+#[derive(Debug, PartialEq, Subcommand)]
+enum Command {
+    /// Migrate the database, then serve the REST and, when configured, gRPC APIs
+    Serve,
+    /// Apply pending migrations and exit
+    Migrate,
+    /// Ingest a readings file as a watched one is, printing the ingestion as JSON
+    Ingest { file: PathBuf },
+    /// Remove readings and query history older than `retention_days` once
+    Purge {
+        /// Only count what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check a running instance end to end, printing the verdict as JSON
+    #[command(name = "selftest")]
+    SelfTest,
+    /// Compare the database schema with the one the migrations define
+    SchemaCheck {
+        /// Print the SQL bringing the database back in line
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Answer an aggregation request from Parquet exports, without Postgres
+    OfflineQuery {
+        request: PathBuf,
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+    },
+    /// Score every API key against the watermark of an export
+    TraceWatermark { export: PathBuf },
+    /// Pack every ingestion not yet compressed into delta-of-delta blocks
+    #[cfg(feature = "compressed-storage")]
+    CompressArchive,
+}
+
+impl Command {
+    /// Parses the arguments following the program name, serving when there are none
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, clap::Error> {
+        let cli = Cli::try_parse_from(iter::once(env!("CARGO_BIN_NAME").to_string()).chain(args))?;
+        Ok(cli.command.unwrap_or(Self::Serve))
+    }
+
+    /// The selftest verdict, ingestion, compression, schema and watermark reports own
+    /// stdout, so their logs go to stderr
+    fn owns_stdout(&self) -> bool {
+        !matches!(self, Self::Serve | Self::Migrate | Self::Purge { .. })
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().ok();
-    init_logging();
+    let command = Command::parse(env::args().skip(1)).unwrap_or_else(|e| e.exit());
+    if command.owns_stdout() {
+        init_logging_to(io::stderr);
+    } else {
+        init_logging();
+    }
+
+    let config = AppConfig::load().inspect_err(|e| error!("Unable to load config: {e:?}"))?;
+    rounding::install(config.rounding_policy());
+
+    match command {
+        Command::Serve => serve(config).await,
+        Command::Migrate => run_migrate(&config).await,
+        Command::Ingest { file } => run_ingest(&config, &file).await,
+        Command::Purge { dry_run } => run_purge(&config, dry_run).await,
+        Command::SelfTest => run_selftest(&config).await,
+        Command::SchemaCheck { fix } => run_schema_check(&config, fix).await,
+        Command::OfflineQuery { request, archives } => {
+            run_offline_query(&config, &request, &archives).await
+        }
+        Command::TraceWatermark { export } => run_trace_watermark(&config, &export).await,
+        #[cfg(feature = "compressed-storage")]
+        Command::CompressArchive => run_compress_archive(&config).await,
+    }
+}
+
+/// Migrates the database, then serves the REST and, when configured, gRPC APIs until
+/// shutdown
+async fn serve(config: AppConfig) -> Result<(), Box<dyn Error>> {
+    log_startup_banner(&config);
 
     // Create Postgres connection pool and run migrations
-    let pg_pool = establish_pg_connection()
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+    // Aggregations are answered by the read replica when one is configured
+    let read_pool = establish_read_pool(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure read replica: {e:?}"))?;
+    if read_pool.is_some() {
+        info!("Routing aggregation reads to the DATABASE_READ_URL replica");
+    }
+    let db = Pools::new(pg_pool.clone(), read_pool);
+
+    // Hand-made schema changes are reported before they surface as query errors
+    match schema_check::check_pool(&pg_pool).await {
+        Ok(drift) => {
+            for difference in &drift {
+                warn!("Schema drift: {difference}, run `schema-check --fix` for the SQL");
+            }
+        }
+        Err(e) => warn!("Unable to check the schema for drift: {e}"),
+    }
+
+    // A warm standby leaves the database untouched until it is switched to read-write
+    if config.read_only {
+        info!("Starting read-only, skipping seeding and the bootstrap API key");
+    } else {
+        // Seed the database with initial data
+        seed_database(&pg_pool, &config).await?;
+        bootstrap_api_key(&pg_pool)
+            .await
+            .inspect_err(|e| error!("Unable to register bootstrap API key: {e:?}"))?;
+    }
+
+    // Optionally warm the in-memory cache of the most recent readings
+    let hot_cache = HotCache::from_env().map(Arc::new);
+    if let Some(cache) = &hot_cache {
+        cache.refresh(&pg_pool).await?;
+    } else if config.query_fallback {
+        warn!("query_fallback needs HOT_CACHE_DAYS, queries fail while Postgres is unavailable");
+    }
+
+    let listener = config
+        .listen_addr
+        .parse::<ListenerConfig>()
+        .inspect_err(|e| error!("Unable to configure listener: {e:?}"))?
+        .bind()
+        .await
+        .inspect_err(|e| error!("Unable to bind listener: {e:?}"))?;
+    let export_config =
+        ExportConfig::from_env().inspect_err(|e| error!("Unable to configure exports: {e:?}"))?;
+    let cursor_signer =
+        CursorSigner::from_env().inspect_err(|e| error!("Unable to configure cursors: {e}"))?;
+    let register_config = RegisterConfig::from_env()
+        .inspect_err(|e| error!("Unable to configure register readings: {e}"))?;
+    let drift_config = DriftConfig::from_env(config.reading_interval())
+        .inspect_err(|e| error!("Unable to configure clock drift: {e}"))?;
+    let uploads = UploadBucket::from_config(&config)
+        .inspect_err(|e| error!("Unable to configure the upload bucket: {e}"))?;
+    let history = HistoryWriter::spawn(
+        pg_pool.clone(),
+        config.history_buffer,
+        config.history_flush_interval(),
+    );
+    let read_only = ReadOnlyMode::new(config.read_only);
+    let response_cache = ResponseCache::from_config(&config).map(Arc::new);
+    #[cfg(feature = "redis-cache")]
+    let shared_cache = match &config.redis_url {
+        Some(url) => Some(
+            SharedCache::connect(url)
+                .await
+                .inspect_err(|e| error!("Unable to connect to Redis: {e}"))?,
+        ),
+        None => None,
+    };
+    let shutdown = Shutdown::new(config.shutdown_grace());
+    // Usage is only counted once an operator opts in
+    let telemetry = config.telemetry_dir.clone().map(|dir| {
+        let telemetry = Telemetry::new(
+            telemetry::configured_features(&config, hot_cache.is_some()),
+            Utc::now(),
+        );
+        telemetry.spawn(dir, config.telemetry_interval());
+        telemetry
+    });
+    let shadow = config.shadow_query_one_in.map(ShadowQueries::new);
+    let state = AppState {
+        db: db.clone(),
+        config,
+        export_config,
+        cursor_signer,
+        register_config,
+        drift_config,
+        uploads,
+        hot_cache,
+        response_cache,
+        #[cfg(feature = "redis-cache")]
+        shared_cache,
+        history,
+        read_only,
+        ingestion_events: IngestionEvents::default(),
+        change_feed: ChangeFeed::default(),
+        retention: RetentionJob::default(),
+        leader: LeaderElection::default(),
+        shutdown,
+        telemetry,
+        shadow,
+    };
+
+    // Writes through other instances sharing the database drop this one's caches
+    state
+        .change_feed
+        .spawn_listener(state.clone())
+        .inspect_err(|e| error!("Unable to listen for changes: {e}"))?;
+
+    // The background jobs below run on whichever replica holds leadership
+    state
+        .leader
+        .spawn(state.read_only.clone())
+        .inspect_err(|e| error!("Unable to stand for leader election: {e}"))?;
+
+    // Readings of the coming months land in partitions created ahead of them
+    partitions::spawn(state.clone());
+
+    // Table and partition sizes are sampled daily to report their growth
+    storage_stats::spawn(state.clone());
+
+    // Readings and query history past `retention_days` are purged in the background
+    state.retention.spawn(state.clone());
+
+    // The gRPC service runs alongside the REST API on its own port
+    if let Some(addr) = state.config.grpc_listen_addr {
+        let service = TimeSeriesService::new(state.clone())
+            .inspect_err(|e| error!("Unable to configure gRPC service: {e}"))?;
+        let server = grpc::serve(addr, service)
+            .inspect_err(|e| error!("Unable to bind gRPC listener: {e:?}"))?;
+        state.shutdown.spawn("gRPC server", async move {
+            if let Err(e) = server.await {
+                error!("gRPC server failed: {e:?}");
+            }
+        });
+    }
+
+    // New CSV files dropped into the watched directory are ingested in the background
+    if let Some(dir) = state.config.watch_dir.clone() {
+        watcher::spawn(state.clone(), dir, state.config.watch_interval())
+            .inspect_err(|e| error!("Unable to watch directory: {e}"))?;
+    }
+
+    let tuning =
+        ServerTuning::from_env().inspect_err(|e| error!("Unable to configure server: {e:?}"))?;
+
+    // A termination signal stops both servers accepting connections, then in-flight
+    // requests and background work are drained before the pool is closed
+    let shutdown = state.shutdown.clone();
+    shutdown.listen();
+    let app = build_router(state, AdminToken::from_env());
+
+    listener.serve(app, &tuning, &shutdown).await?;
+    shutdown.drain(&db).await;
+    Ok(())
+}
+
+/// Applies pending migrations and exits, without serving
+async fn run_migrate(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to migrate DB: {e:?}"))?;
+    pg_pool.close();
+    info!("Migrations applied");
+    Ok(())
+}
+
+/// Ingests one readings file as a watched file is, its path being the ingestion source,
+/// printing the ingestion as JSON and exiting non-zero when it holds no valid readings
+async fn run_ingest(config: &AppConfig, file: &Path) -> Result<(), Box<dyn Error>> {
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+
+    match watcher::ingest_file(config, &pg_pool, file).await? {
+        Outcome::Ingested(notification) => {
+            println!("{}", serde_json::to_string(&notification)?);
+            // Running instances drop the aggregations cached before these readings
+            ChangeFeed::default().announce(&pg_pool).await;
+        }
+        Outcome::Duplicate => info!(source = %file.display(), "File already ingested"),
+        Outcome::Empty => {
+            error!(source = %file.display(), "File has no valid readings");
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Purges readings and query history older than `retention_days` once, counting the rows
+/// due to go instead with `--dry-run` or `retention_dry_run`
+async fn run_purge(config: &AppConfig, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let Some(days) = config.retention_days else {
+        return Err("purge needs retention_days".into());
+    };
+    let dry_run = dry_run || config.retention_dry_run;
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+
+    let cutoff = Utc::now() - TimeDelta::days(i64::from(days));
+    let purge = retention::purge(&pg_pool, cutoff, dry_run).await?;
+    info!(
+        %cutoff,
+        dry_run,
+        readings = purge.readings,
+        query_history = purge.query_history,
+        partitions = purge.partitions,
+        held = purge.held,
+        "Purged expired rows"
+    );
+    if !dry_run && purge.readings > 0 {
+        ChangeFeed::default().announce(&pg_pool).await;
+    }
+    Ok(())
+}
+
+/// Exercises a running instance end to end, printing the verdict as JSON and exiting
+/// non-zero when any check fails
+async fn run_selftest(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let selftest_config =
+        SelfTestConfig::from_env().inspect_err(|e| error!("Unable to configure selftest: {e}"))?;
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+
+    let verdict = selftest::run(&pg_pool, selftest_config).await?;
+    println!("{}", serde_json::to_string(&verdict)?);
+    if !verdict.passed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints each difference between the database and the schema this build expects, or
+/// with `--fix` a SQL script resolving them for review, exiting non-zero on any drift
+async fn run_schema_check(config: &AppConfig, fix: bool) -> Result<(), Box<dyn Error>> {
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+
+    let drift = schema_check::check_pool(&pg_pool).await?;
+    for difference in &drift {
+        if fix {
+            println!("-- {difference}\n{}", difference.fix());
+        } else {
+            println!("{difference}");
+        }
+    }
+    if !drift.is_empty() {
+        process::exit(1);
+    }
+    info!("Schema matches the migrations");
+    Ok(())
+}
+
+/// Answers the aggregation request in the JSON file of the first argument from the raw
+/// Parquet exports named by the rest, printing the response the query endpoint would give
+async fn run_offline_query(
+    config: &AppConfig,
+    request: &Path,
+    archives: &[PathBuf],
+) -> Result<(), Box<dyn Error>> {
+    let request = serde_json::from_slice(&fs::read(request)?)?;
+    let ctx = offline::open_archives(archives)
+        .await
+        .inspect_err(|e| error!("Unable to read the archives: {e}"))?;
+    info!("Querying {} archives", archives.len());
+
+    let now = Utc::now();
+    let response = offline::answer(request, &ctx, config.default_query_start(now), now).await?;
+    println!("{}", serde_json::to_string(&response)?);
+    Ok(())
+}
+
+/// Scores every API key against the watermark of an export, printing a JSON line per key,
+/// best match first
+async fn run_trace_watermark(config: &AppConfig, file: &Path) -> Result<(), Box<dyn Error>> {
+    let export_config =
+        ExportConfig::from_env().inspect_err(|e| error!("Unable to configure exports: {e:?}"))?;
+    let amounts = watermark::read_export(file)?;
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
         .await
         .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+    let api_key_ids = pg_pool
+        .get()
+        .await?
+        .interact(api_key_ids)
+        .await
+        .map_err(|e| e.to_string())??;
+
+    for candidate in watermark::trace(&export_config.signing_key, &api_key_ids, &amounts) {
+        println!("{}", serde_json::to_string(&candidate)?);
+    }
+    Ok(())
+}
 
-    // Seed the database with initial data
-    seed_database(&pg_pool).await?;
+/// Packs every ingestion not yet compressed into delta-of-delta blocks, printing the
+/// storage each takes before and after as JSON lines
+#[cfg(feature = "compressed-storage")]
+async fn run_compress_archive(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    use renewable_ts_axum::db::compressed_storage::compress_archive;
+
+    let pg_pool = establish_pg_connection(&config.pool_tuning())
+        .await
+        .inspect_err(|e| error!("Unable to configure DB: {e:?}"))?;
+    let conn = pg_pool.get().await?;
+    let reports = conn
+        .interact(compress_archive)
+        .await
+        .map_err(|e| e.to_string())??;
+    for report in &reports {
+        println!("{}", serde_json::to_string(report)?);
+    }
+    info!(ingestions = reports.len(), "Compressed archive");
+    Ok(())
+}
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8000));
-    info!("listening on {addr}");
-    let listener = TcpListener::bind(addr).await.unwrap();
+fn build_router(state: AppState, admin_token: AdminToken) -> Router {
+    // Each group of routes is given its own timeout, see `enforce_deadline`
+    let timeout = |timeout| middleware::from_fn_with_state(timeout, enforce_deadline);
 
-    let app = Router::new()
+    // Endpoints writing to the database, rejected while the instance is read-only
+    let writes = Router::new()
+        // Readings Upload Endpoint
+        .route("/timeseries/v1/ingestions", post(route::post_ingestion))
+        .route(
+            "/timeseries/v1/ingestions/{id}",
+            delete(route::delete_ingestion_by_id),
+        )
+        // Pre-signed Upload Endpoints, files are uploaded to the bucket then ingested from it
+        .route("/timeseries/v1/uploads", post(route::post_upload_url))
+        .route(
+            "/timeseries/v1/uploads/ingest",
+            post(route::post_uploaded_file),
+        )
+        // Meter Onboarding Endpoint
+        .route("/timeseries/v1/meters/bulk", post(route::post_meters_bulk))
+        // Expected Generation Profile Endpoint
+        .route(
+            "/timeseries/v1/meters/{meter_code}/profile",
+            put(route::put_meter_profile),
+        )
+        // Series Endpoints
+        .route("/timeseries/v1/series", post(route::post_series))
+        .route(
+            "/timeseries/v1/series/{id}",
+            put(route::put_series).delete(route::delete_series_by_id),
+        )
+        // Asynchronous Export Endpoint, jobs are tracked in the database
+        .route("/timeseries/v1/exports", post(route::post_export))
+        // Asynchronous Query Endpoint, jobs hold their result once answered
+        .route("/timeseries/v1/query/async", post(route::post_query_async))
+        .route_layer(middleware::from_fn_with_state(
+            state.read_only.clone(),
+            reject_writes,
+        ))
+        .route_layer(timeout(state.config.ingest_timeout()));
+
+    // Endpoints reading stored data
+    let reads = Router::new()
         // Query Endpoint
         .route("/timeseries/v1/query", post(route::post_query_ts))
+        // Text Query Language Endpoint
+        .route("/timeseries/v1/query/dsl", post(route::post_query_dsl))
+        // Streamed Query Endpoint
+        .route(
+            "/timeseries/v1/query/stream",
+            post(route::post_query_stream),
+        )
+        // Snapshot Diff Endpoint
+        .route("/timeseries/v1/query/diff", post(route::post_query_diff))
+        .route("/timeseries/v1/query/power", post(route::post_query_power))
+        .route(
+            "/timeseries/v1/query/ranges",
+            post(route::post_query_ranges),
+        )
+        // Calendar Endpoint
+        .route(
+            "/timeseries/v1/analytics/calendar/{year}",
+            get(route::get_calendar),
+        )
         // Query History Endpoint
         .route(
             "/timeseries/v1/query/history",
             get(route::get_query_history),
         )
-        .fallback(route::handler_404)
+        // Ingestions Endpoint
+        .route("/timeseries/v1/ingestions", get(route::get_ingestions))
+        .route(
+            "/timeseries/v1/ingestions/{id}/clock-drift",
+            get(route::get_ingestion_clock_drift),
+        )
+        .route(
+            "/timeseries/v1/ingestions/{id}/issues",
+            get(route::get_ingestion_issues),
+        )
+        .route("/timeseries/v1/series", get(route::get_series_list))
+        .route("/timeseries/v1/series/{id}", get(route::get_series_by_id))
+        // Upload Format Detection Endpoint
+        .route(
+            "/timeseries/v1/ingest/detect-format",
+            post(route::post_detect_format),
+        )
+        // Expected Generation Variance Endpoint
+        .route(
+            "/timeseries/v1/meters/{meter_code}/variance",
+            get(route::get_meter_variance),
+        )
+        // Asynchronous Export Status Endpoint
+        .route("/timeseries/v1/exports/{id}", get(route::get_export))
+        // Asynchronous Query Status Endpoint
+        .route("/timeseries/v1/jobs/{id}", get(route::get_query_job))
+        .route(
+            "/timeseries/v1/export/parquet",
+            get(route::get_parquet_export),
+        )
+        // Live Ingestion Updates Endpoint
+        .route("/timeseries/v1/ws", get(route::get_live_updates))
+        // GraphQL Endpoint
+        .route("/graphql", post(route::post_graphql))
+        .route_layer(timeout(state.config.query_timeout()));
+
+    // Endpoints requiring an `X-Api-Key`
+    let authenticated = reads
+        .merge(writes)
+        .route_layer(middleware::from_fn_with_state(
+            ApiKeyVerifier::new(state.db.primary().clone(), state.config.query_fallback),
+            require_api_key,
+        ));
+
+    // Operator endpoints requiring the `X-Admin-Token`
+    let admin = Router::new()
+        .route(
+            "/admin/v1/read-only",
+            get(route::get_read_only).put(route::put_read_only),
+        )
+        .route(
+            "/admin/v1/api-keys/{id}/export-recipient",
+            put(route::put_export_recipient),
+        )
+        .route(
+            "/admin/v1/ingestions/{id}/legal-hold",
+            put(route::put_legal_hold).delete(route::delete_legal_hold),
+        )
+        .route("/admin/v1/audit-log", get(route::get_audit_log))
+        .route("/admin/v1/storage", get(route::get_storage))
+        .route("/admin/v1/chain/verify", get(route::get_chain_verification))
+        .route("/admin/v1/views/{name}", put(route::put_saved_view))
+        .route_layer(middleware::from_fn_with_state(
+            admin_token,
+            require_admin_token,
+        ));
+
+    let health = Router::new()
+        // Liveness and Readiness Endpoints
+        .route("/healthz", get(route::get_healthz))
+        .route("/readyz", get(route::get_readyz))
+        // Build Info Endpoint
+        .route("/version", get(route::get_version))
+        .route_layer(timeout(state.config.health_timeout()));
+
+    let router = Router::new()
+        // Export downloads are authorised by their signed URL
+        .route(
+            "/timeseries/v1/exports/{id}/download",
+            get(route::download_export),
+        )
+        // The playground page holds no data, its queries are authenticated
+        .route("/graphql", get(route::get_graphql_playground))
+        .merge(admin)
+        .route_layer(timeout(state.config.request_timeout()))
+        .merge(health)
+        .merge(authenticated)
+        // API Documentation
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", ApiDoc::openapi()))
+        .fallback(route::handler_404);
+    // Requests are counted by the template of the route they matched
+    let router = match state.telemetry.clone() {
+        Some(telemetry) => router.layer(middleware::from_fn_with_state(telemetry, count_usage)),
+        None => router,
+    };
+    router
         .layer((
+            SetRequestIdLayer::x_request_id(MakeRequestUuid),
             TraceLayer::new_for_http(),
-            TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, Duration::from_secs(2)),
+            PropagateRequestIdLayer::x_request_id(),
+            middleware::from_fn(scope_request_id),
+            middleware::from_fn(decimal::select_format),
         ))
-        .with_state(pg_pool);
+        .with_state(state)
+}
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
-    Ok(())
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::Command;
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        Command::parse(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert_eq!(parse(&[]).unwrap(), Command::Serve);
+        assert_eq!(parse(&["serve"]).unwrap(), Command::Serve);
+        assert_eq!(parse(&["migrate"]).unwrap(), Command::Migrate);
+        assert_eq!(
+            parse(&["ingest", "incoming/readings.csv.gz"]).unwrap(),
+            Command::Ingest {
+                file: PathBuf::from("incoming/readings.csv.gz")
+            }
+        );
+        assert_eq!(
+            parse(&["purge", "--dry-run"]).unwrap(),
+            Command::Purge { dry_run: true }
+        );
+        assert_eq!(
+            parse(&["purge"]).unwrap(),
+            Command::Purge { dry_run: false }
+        );
+        assert_eq!(
+            parse(&["schema-check"]).unwrap(),
+            Command::SchemaCheck { fix: false }
+        );
+        assert_eq!(
+            parse(&["schema-check", "--fix"]).unwrap(),
+            Command::SchemaCheck { fix: true }
+        );
+        assert_eq!(
+            parse(&["offline-query", "request.json", "a.parquet", "b.parquet"]).unwrap(),
+            Command::OfflineQuery {
+                request: PathBuf::from("request.json"),
+                archives: vec![PathBuf::from("a.parquet"), PathBuf::from("b.parquet")],
+            }
+        );
+        assert!(!Command::Migrate.owns_stdout());
+        assert!(
+            Command::Ingest {
+                file: PathBuf::new()
+            }
+            .owns_stdout()
+        );
+
+        assert!(parse(&["ingest"]).is_err());
+        assert!(parse(&["ingest", "a.csv", "b.csv"]).is_err());
+        assert!(parse(&["offline-query", "request.json"]).is_err());
+        assert!(parse(&["trace-watermark"]).is_err());
+        assert!(parse(&["unknown"]).is_err());
+    }
+
+    #[test]
+    fn test_misspelt_flags_are_refused_rather_than_ignored() {
+        assert!(parse(&["purge", "--dryrun"]).is_err());
+        assert!(parse(&["purge", "--dry_run"]).is_err());
+        assert!(parse(&["purge", "-n"]).is_err());
+        assert!(parse(&["purge", "--dry-run", "extra"]).is_err());
+        assert!(parse(&["schema-check", "--fixx"]).is_err());
+    }
 }
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bigdecimal::BigDecimal;
+use chrono::{
+    DateTime, Datelike as _, Days, DurationRound as _, LocalResult, Months, NaiveDate, NaiveTime,
+    Offset as _, TimeDelta, TimeZone as _, Utc,
+};
+use chrono_tz::Tz;
+
+use crate::model::{
+    api_request::{Aggregation, FillMissing},
+    api_response::{AggregationQueryRecord, BucketCompleteness},
+};
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+fn first_of_month(year: i32, month: u32) -> DateTime<Utc> {
+    start_of_day(NaiveDate::from_ymd_opt(year, month, 1).expect("valid first of month"))
+}
+
+/// Truncates a timestamp to the start of its bucket, matching Postgres `DATE_TRUNC`
+pub fn truncate(kind: Aggregation, datetime: DateTime<Utc>) -> DateTime<Utc> {
+    let date = datetime.date_naive();
+    match kind {
+        Aggregation::Hourly => datetime
+            .duration_trunc(TimeDelta::hours(1))
+            .unwrap_or(datetime),
+        Aggregation::DayInMonth => start_of_day(date),
+        Aggregation::Weekly => {
+            start_of_day(date - Days::new(u64::from(date.weekday().num_days_from_monday())))
+        }
+        Aggregation::Monthly => first_of_month(date.year(), date.month()),
+        Aggregation::Quarterly => first_of_month(date.year(), date.month0() / 3 * 3 + 1),
+        Aggregation::Yearly => first_of_month(date.year(), 1),
+    }
+}
+
+/// Start of the bucket holding `datetime` on the local calendar of `zone`, as Postgres
+/// resolves it: an ambiguous local start is taken in standard time, and one skipped by a
+/// transition at the offset in effect before it
+pub fn truncate_in(kind: Aggregation, datetime: DateTime<Utc>, zone: Tz) -> DateTime<Utc> {
+    let local = datetime.with_timezone(&zone).naive_local().and_utc();
+    let start = truncate(kind, local).naive_utc();
+    match zone.from_local_datetime(&start) {
+        LocalResult::Single(start) | LocalResult::Ambiguous(_, start) => start.to_utc(),
+        LocalResult::None => {
+            let before = zone
+                .offset_from_utc_datetime(&(start - TimeDelta::days(1)))
+                .fix();
+            (start - TimeDelta::seconds(i64::from(before.local_minus_utc()))).and_utc()
+        }
+    }
+}
+
+/// Returns the start of the bucket following `bucket`
+pub fn advance(kind: Aggregation, bucket: DateTime<Utc>) -> DateTime<Utc> {
+    let months = |n: u32| {
+        bucket
+            .checked_add_months(Months::new(n))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    };
+    match kind {
+        Aggregation::Hourly => bucket + TimeDelta::hours(1),
+        Aggregation::DayInMonth => bucket + TimeDelta::days(1),
+        Aggregation::Weekly => bucket + TimeDelta::weeks(1),
+        Aggregation::Monthly => months(1),
+        Aggregation::Quarterly => months(3),
+        Aggregation::Yearly => months(12),
+    }
+}
+
+/// Number of readings the whole bucket starting at `bucket` holds at `interval`
+pub fn expected_points(kind: Aggregation, bucket: DateTime<Utc>, interval: TimeDelta) -> i64 {
+    let span = advance(kind, bucket) - bucket;
+    span.num_seconds() / interval.num_seconds().max(1)
+}
+
+/// Pairs each record with its reading count from `counts`, keyed by bucket start.
+/// Buckets clipped by the requested range are measured against their full span.
+pub fn completeness(
+    kind: Aggregation,
+    records: &[AggregationQueryRecord],
+    counts: Vec<(DateTime<Utc>, i64)>,
+    interval: TimeDelta,
+) -> Vec<BucketCompleteness> {
+    let counts: HashMap<_, _> = counts.into_iter().collect();
+    records
+        .iter()
+        .map(|record| {
+            let expected_points = expected_points(kind, record.datetime, interval);
+            let actual_points = counts.get(&record.datetime).copied().unwrap_or_default();
+            BucketCompleteness {
+                datetime: record.datetime,
+                expected_points,
+                actual_points,
+                complete: actual_points >= expected_points,
+            }
+        })
+        .collect()
+}
+
+/// Emits every bucket between the requested bounds (or the bounds of the data when
+/// omitted), ordered by time, with missing buckets filled according to `fill`.
+/// `to_date` is exclusive, so a bucket starting exactly at it is not emitted.
+pub fn fill_missing(
+    kind: Aggregation,
+    from_date: Option<DateTime<Utc>>,
+    to_date: Option<DateTime<Utc>>,
+    records: Vec<AggregationQueryRecord>,
+    fill: FillMissing,
+) -> Vec<AggregationQueryRecord> {
+    let mut totals: BTreeMap<DateTime<Utc>, Option<BigDecimal>> = records
+        .into_iter()
+        .map(|record| (record.datetime, record.total_amount))
+        .collect();
+
+    let start = from_date
+        .map(|from| truncate(kind, from))
+        .or_else(|| totals.keys().next().copied());
+    let end = to_date
+        .map(|to| truncate(kind, to - TimeDelta::microseconds(1)))
+        .or_else(|| totals.keys().next_back().copied());
+    let (Some(start), Some(end)) = (start, end) else {
+        return Vec::new();
+    };
+
+    let mut filled = Vec::new();
+    let mut bucket = start;
+    while bucket <= end {
+        let total_amount = totals.remove(&bucket).unwrap_or_else(|| match fill {
+            FillMissing::Null => None,
+            FillMissing::Zero => Some(BigDecimal::from(0)),
+        });
+        filled.push(AggregationQueryRecord {
+            datetime: bucket,
+            total_amount,
+        });
+        bucket = advance(kind, bucket);
+    }
+    filled
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+    use test_case::test_case;
+
+    use super::{advance, completeness, expected_points, fill_missing, truncate};
+    use crate::model::{
+        api_request::{Aggregation, FillMissing},
+        api_response::{AggregationQueryRecord, BucketCompleteness},
+    };
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    #[test_case(Aggregation::Hourly, at(2025, 5, 14, 13), at(2025, 5, 14, 14))]
+    #[test_case(Aggregation::DayInMonth, at(2025, 5, 14, 0), at(2025, 5, 15, 0))]
+    #[test_case(Aggregation::Weekly, at(2025, 5, 12, 0), at(2025, 5, 19, 0))]
+    #[test_case(Aggregation::Monthly, at(2025, 5, 1, 0), at(2025, 6, 1, 0))]
+    #[test_case(Aggregation::Quarterly, at(2025, 4, 1, 0), at(2025, 7, 1, 0))]
+    #[test_case(Aggregation::Yearly, at(2025, 1, 1, 0), at(2026, 1, 1, 0))]
+    fn test_truncate_and_advance(
+        kind: Aggregation,
+        expected_bucket: DateTime<Utc>,
+        expected_next: DateTime<Utc>,
+    ) {
+        let datetime = Utc.with_ymd_and_hms(2025, 5, 14, 13, 45, 10).unwrap();
+        let bucket = truncate(kind, datetime);
+        assert_eq!(bucket, expected_bucket);
+        assert_eq!(advance(kind, bucket), expected_next);
+    }
+
+    #[test_case(Aggregation::Hourly, at(2025, 1, 1, 0), 60, 1)]
+    #[test_case(Aggregation::DayInMonth, at(2025, 1, 1, 0), 30, 48)]
+    #[test_case(Aggregation::Monthly, at(2025, 2, 1, 0), 60, 672)]
+    #[test_case(Aggregation::Yearly, at(2024, 1, 1, 0), 60, 8784)]
+    fn test_expected_points(kind: Aggregation, bucket: DateTime<Utc>, minutes: i64, expected: i64) {
+        assert_eq!(
+            expected_points(kind, bucket, TimeDelta::minutes(minutes)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_completeness_flags_partial_buckets() {
+        let records = vec![
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 1, 0),
+                total_amount: Some(BigDecimal::from(24)),
+            },
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 2, 0),
+                total_amount: Some(BigDecimal::from(9)),
+            },
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 3, 0),
+                total_amount: None,
+            },
+        ];
+        let counts = vec![(at(2025, 1, 1, 0), 24), (at(2025, 1, 2, 0), 9)];
+
+        let flags = completeness(
+            Aggregation::DayInMonth,
+            &records,
+            counts,
+            TimeDelta::hours(1),
+        );
+        let summary: Vec<_> = flags
+            .iter()
+            .map(|bucket| (bucket.actual_points, bucket.complete))
+            .collect();
+        assert_eq!(summary, [(24, true), (9, false), (0, false)]);
+        assert_eq!(
+            flags[0],
+            BucketCompleteness {
+                datetime: at(2025, 1, 1, 0),
+                expected_points: 24,
+                actual_points: 24,
+                complete: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fill_missing_buckets() {
+        let records = vec![
+            AggregationQueryRecord {
+                datetime: at(2025, 3, 1, 0),
+                total_amount: Some(BigDecimal::from(5)),
+            },
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 1, 0),
+                total_amount: Some(BigDecimal::from(7)),
+            },
+        ];
+
+        let filled = fill_missing(
+            Aggregation::Monthly,
+            Some(at(2024, 12, 15, 0)),
+            Some(at(2025, 4, 1, 0)),
+            records,
+            FillMissing::Zero,
+        );
+        let totals: Vec<_> = filled.iter().map(|r| r.total_amount.clone()).collect();
+        // April starts exactly at the exclusive end so is not emitted
+        assert_eq!(filled[0].datetime, at(2024, 12, 1, 0));
+        assert_eq!(
+            totals,
+            vec![
+                Some(BigDecimal::from(0)),
+                Some(BigDecimal::from(7)),
+                Some(BigDecimal::from(0)),
+                Some(BigDecimal::from(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_missing_uses_data_bounds() {
+        let records = vec![
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 1, 3),
+                total_amount: Some(BigDecimal::from(1)),
+            },
+            AggregationQueryRecord {
+                datetime: at(2025, 1, 1, 0),
+                total_amount: Some(BigDecimal::from(1)),
+            },
+        ];
+
+        let filled = fill_missing(Aggregation::Hourly, None, None, records, FillMissing::Null);
+        assert_eq!(filled.len(), 4);
+        assert!(filled[1].total_amount.is_none() && filled[2].total_amount.is_none());
+
+        assert!(
+            fill_missing(
+                Aggregation::Hourly,
+                None,
+                None,
+                Vec::new(),
+                FillMissing::Null
+            )
+            .is_empty()
+        );
+    }
+}
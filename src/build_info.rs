@@ -0,0 +1,64 @@
+use chrono::DateTime;
+use tracing::info;
+
+use crate::{config::AppConfig, model::api_response::BuildInfo};
+
+/// Build details compiled in by `build.rs`
+pub fn build_info() -> BuildInfo {
+    let built_at = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .unwrap_or_default();
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BUILD_GIT_SHA"),
+        built_at,
+        features: env!("BUILD_FEATURES")
+            .split(',')
+            .filter(|feature| !feature.is_empty())
+            .collect(),
+        schema_migration: env!("BUILD_SCHEMA_MIGRATION"),
+    }
+}
+
+/// Logs what is starting and how it is configured as a single structured event, so each
+/// environment's logs show exactly which build is running
+pub fn log_startup_banner(config: &AppConfig) {
+    let build = build_info();
+    info!(
+        version = build.version,
+        git_sha = build.git_sha,
+        built_at = %build.built_at,
+        features = ?build.features,
+        schema_migration = build.schema_migration,
+        listen_addr = config.listen_addr,
+        grpc_listen_addr = ?config.grpc_listen_addr,
+        read_only = config.read_only,
+        "Starting {}",
+        env!("CARGO_PKG_NAME"),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use diesel::{migration::MigrationSource, pg::Pg};
+
+    use super::build_info;
+    use crate::db::MIGRATIONS;
+
+    #[test]
+    fn test_schema_migration_is_newest_embedded_migration() {
+        let newest = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+            .unwrap()
+            .iter()
+            .map(|migration| migration.name().to_string())
+            .max()
+            .unwrap();
+
+        let info = build_info();
+        assert_eq!(info.schema_migration, newest);
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use redis::{
+    AsyncCommands as _,
+    aio::{ConnectionManager, ConnectionManagerConfig},
+};
+use sha2::{Digest as _, Sha256};
+
+const KEY_PREFIX: &str = "renewable";
+/// Bounds every connection attempt and command, so an unreachable Redis slows requests by
+/// at most this much before they fall back to computing the response
+const TIMEOUT: Duration = Duration::from_millis(500);
+/// Bumped on every invalidation, cached values are keyed by the generation they were
+/// computed in so bumping it drops them on every replica at once
+const GENERATION_KEY: &str = "renewable:generation";
+
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    #[error("redis error {0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+/// Redis backed store shared by every replica, holding cached responses so a result is
+/// computed once per deployment rather than once per instance. Configured through
+/// `redis_url`.
+#[derive(Clone)]
+pub struct SharedCache {
+    connection: ConnectionManager,
+}
+
+impl SharedCache {
+    /// Connects to `url`, reconnecting whenever the connection drops
+    pub async fn connect(url: &str) -> Result<Self, CacheError> {
+        let config = ConnectionManagerConfig::new()
+            .set_number_of_retries(1)
+            .set_connection_timeout(TIMEOUT)
+            .set_response_timeout(TIMEOUT);
+        let connection = redis::Client::open(url)?
+            .get_connection_manager_with_config(config)
+            .await?;
+        Ok(Self { connection })
+    }
+
+    /// Current generation, to key values computed after this call by
+    pub async fn generation(&self) -> Result<u64, CacheError> {
+        let generation: Option<u64> = self.connection.clone().get(GENERATION_KEY).await?;
+        Ok(generation.unwrap_or_default())
+    }
+
+    /// Drops every cached value on every replica, called whenever stored readings change
+    pub async fn invalidate(&self) -> Result<(), CacheError> {
+        let _: u64 = self.connection.clone().incr(GENERATION_KEY, 1).await?;
+        Ok(())
+    }
+
+    /// The value cached under `key` in `generation`
+    pub async fn get(&self, generation: u64, key: &str) -> Result<Option<Vec<u8>>, CacheError> {
+        Ok(self
+            .connection
+            .clone()
+            .get(cache_key(generation, key))
+            .await?)
+    }
+
+    /// Caches `value` under `key` in `generation` for `ttl`
+    pub async fn set(
+        &self,
+        generation: u64,
+        key: &str,
+        value: &[u8],
+        ttl: Duration,
+    ) -> Result<(), CacheError> {
+        let _: () = self
+            .connection
+            .clone()
+            .set_ex(cache_key(generation, key), value, ttl.as_secs().max(1))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Redis key of a cached value, `key` hashed so arbitrarily long descriptions of a query
+/// make fixed length keys
+fn cache_key(generation: u64, key: &str) -> String {
+    let digest = hex::encode(Sha256::digest(key.as_bytes()));
+    format!("{KEY_PREFIX}:cache:{generation}:{digest}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::cache_key;
+
+    #[test]
+    fn test_cache_keys_are_scoped_by_generation() {
+        let key = cache_key(3, "Monthly 2025");
+        assert!(key.starts_with("renewable:cache:3:"));
+        assert_eq!(key.len(), "renewable:cache:3:".len() + 64);
+        assert_eq!(key, cache_key(3, "Monthly 2025"));
+        assert_ne!(key, cache_key(4, "Monthly 2025"));
+        assert_ne!(key, cache_key(3, "Monthly 2026"));
+    }
+}
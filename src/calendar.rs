@@ -0,0 +1,110 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Datelike as _, NaiveDate, NaiveTime, Utc};
+
+use crate::model::api_response::{AggregationQueryRecord, CalendarMonth, CalendarResponse};
+
+/// First instant of `year` and of the year after it, `None` beyond the supported dates
+pub fn year_range(year: i32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = |year| {
+        NaiveDate::from_ymd_opt(year, 1, 1).map(|date| date.and_time(NaiveTime::MIN).and_utc())
+    };
+    Some((start(year)?, start(year.checked_add(1)?)?))
+}
+
+/// Lays out the daily totals of `year` as one entry per UTC day from 1 January, `None`
+/// for days without readings, alongside the total, days with readings and best day of
+/// each month
+pub fn calendar(year: i32, daily: Vec<AggregationQueryRecord>) -> CalendarResponse {
+    let length = NaiveDate::from_ymd_opt(year, 12, 31).map_or(0, |last| last.ordinal() as usize);
+    let mut days: Vec<Option<BigDecimal>> = vec![None; length];
+    for record in daily {
+        if record.datetime.year() == year
+            && let Some(day) = days.get_mut(record.datetime.ordinal0() as usize)
+        {
+            *day = record.total_amount;
+        }
+    }
+
+    let mut months: Vec<CalendarMonth> = (1..=12)
+        .map(|month| CalendarMonth {
+            month,
+            total_amount: None,
+            days_with_readings: 0,
+            peak_day: None,
+        })
+        .collect();
+    let mut peaks: Vec<Option<&BigDecimal>> = vec![None; 12];
+    for (date, total) in days.iter().enumerate().filter_map(|(index, total)| {
+        let date = NaiveDate::from_yo_opt(year, u32::try_from(index).ok()? + 1)?;
+        Some((date, total.as_ref()?))
+    }) {
+        let index = date.month0() as usize;
+        let month = &mut months[index];
+        month.total_amount = Some(month.total_amount.take().unwrap_or_default() + total);
+        month.days_with_readings += 1;
+        if peaks[index].is_none_or(|peak| total > peak) {
+            peaks[index] = Some(total);
+            month.peak_day = Some(date);
+        }
+    }
+
+    CalendarResponse { year, days, months }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{NaiveDate, TimeZone as _, Utc};
+
+    use super::{calendar, year_range};
+    use crate::model::api_response::AggregationQueryRecord;
+
+    fn day(y: i32, m: u32, d: u32, amount: i64) -> AggregationQueryRecord {
+        AggregationQueryRecord {
+            datetime: Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap(),
+            total_amount: Some(BigDecimal::from(amount)),
+        }
+    }
+
+    #[test]
+    fn test_calendar_lays_out_every_day_and_month() {
+        let leap = calendar(
+            2024,
+            vec![
+                day(2024, 1, 1, 5),
+                day(2024, 2, 29, 7),
+                day(2024, 2, 3, 9),
+                day(2024, 12, 31, 1),
+                // Outside the year
+                day(2025, 1, 1, 100),
+            ],
+        );
+        assert_eq!(leap.days.len(), 366);
+        assert_eq!(leap.days[0], Some(BigDecimal::from(5)));
+        assert_eq!(leap.days[59], Some(BigDecimal::from(7)));
+        assert_eq!(leap.days[365], Some(BigDecimal::from(1)));
+        assert_eq!(leap.days.iter().flatten().count(), 4);
+
+        let february = &leap.months[1];
+        assert_eq!(february.month, 2);
+        assert_eq!(february.total_amount, Some(BigDecimal::from(16)));
+        assert_eq!(february.days_with_readings, 2);
+        assert_eq!(february.peak_day, NaiveDate::from_ymd_opt(2024, 2, 3));
+        assert_eq!(leap.months[2].total_amount, None);
+        assert_eq!(leap.months[2].days_with_readings, 0);
+
+        assert_eq!(calendar(2025, Vec::new()).days.len(), 365);
+    }
+
+    #[test]
+    fn test_year_range() {
+        assert_eq!(
+            year_range(2024),
+            Some((
+                Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(year_range(i32::MAX), None);
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest as _, Sha256};
+
+use crate::model::{
+    api_response::{AlteredIngestion, ChainVerification},
+    database::ChainEntry,
+};
+
+/// Hex SHA-256 of the readings of an ingestion holding none
+pub const EMPTY_CONTENT: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Line of one reading in the text hashed into an entry's `content_hash`, the readings
+/// of an ingestion being joined by newlines in datetime order. Postgres computes the same
+/// text when an entry is appended or verified.
+pub fn content_line(datetime: DateTime<Utc>, amount: &str) -> String {
+    format!("{},{amount}", datetime.timestamp_micros())
+}
+
+/// Hash of an entry, over the hash of the entry before and everything it records
+pub fn link_hash(
+    previous_hash: Option<&str>,
+    event: &str,
+    ingestion_id: i64,
+    readings: i64,
+    content_hash: &str,
+    recorded_at: DateTime<Utc>,
+) -> String {
+    let text = format!(
+        "{}|{event}|{ingestion_id}|{readings}|{content_hash}|{}",
+        previous_hash.unwrap_or_default(),
+        recorded_at.timestamp_micros()
+    );
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// Checks `entries`, ordered by sequence number, link up and that `contents`, the
+/// readings count and content hash each ingestion holds now, match those of its latest
+/// entry. Ingestions absent from `contents` hold no readings.
+pub fn verify(
+    now: DateTime<Utc>,
+    entries: &[ChainEntry],
+    contents: &HashMap<i64, (i64, String)>,
+    unchained: i64,
+) -> ChainVerification {
+    let mut previous: Option<&str> = None;
+    let mut broken_at = None;
+    for entry in entries {
+        let hash = link_hash(
+            previous,
+            &entry.event,
+            entry.ingestion_id,
+            entry.readings,
+            &entry.content_hash,
+            entry.recorded_at,
+        );
+        if entry.previous_hash.as_deref() != previous || entry.hash != hash {
+            broken_at = Some(entry.seq);
+            break;
+        }
+        previous = Some(&entry.hash);
+    }
+
+    let mut latest: HashMap<i64, &ChainEntry> = HashMap::new();
+    for entry in entries {
+        latest.insert(entry.ingestion_id, entry);
+    }
+    let mut altered: Vec<_> = latest
+        .into_values()
+        .filter_map(|entry| {
+            let (readings, hash) = contents
+                .get(&entry.ingestion_id)
+                .map_or((0, EMPTY_CONTENT), |(readings, hash)| (*readings, hash));
+            (readings != entry.readings || hash != entry.content_hash).then(|| AlteredIngestion {
+                ingestion_id: entry.ingestion_id,
+                seq: entry.seq,
+                expected_readings: entry.readings,
+                readings,
+                expected_hash: entry.content_hash.clone(),
+                hash: hash.to_string(),
+            })
+        })
+        .collect();
+    altered.sort_by_key(|ingestion| ingestion.ingestion_id);
+
+    ChainVerification {
+        verified_at: now,
+        intact: broken_at.is_none() && altered.is_empty(),
+        entries: entries.len(),
+        head: entries.last().map(|entry| entry.hash.clone()),
+        broken_at,
+        altered,
+        unchained,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+    use sha2::{Digest as _, Sha256};
+
+    use super::{EMPTY_CONTENT, content_line, link_hash, verify};
+    use crate::model::database::ChainEntry;
+
+    fn chain(events: &[(&str, i64, i64, &str)]) -> Vec<ChainEntry> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let mut previous: Option<String> = None;
+        let mut entries = Vec::new();
+        for (seq, &(event, ingestion_id, readings, content_hash)) in (1..).zip(events) {
+            let recorded_at = start + TimeDelta::minutes(seq);
+            let hash = link_hash(
+                previous.as_deref(),
+                event,
+                ingestion_id,
+                readings,
+                content_hash,
+                recorded_at,
+            );
+            entries.push(ChainEntry {
+                seq,
+                recorded_at,
+                event: event.to_string(),
+                ingestion_id,
+                readings,
+                content_hash: content_hash.to_string(),
+                previous_hash: previous.replace(hash.clone()),
+                hash,
+            });
+        }
+        entries
+    }
+
+    #[test]
+    fn test_empty_content_hashes_nothing() {
+        assert_eq!(EMPTY_CONTENT, hex::encode(Sha256::digest(b"")));
+        let datetime = Utc.with_ymd_and_hms(2025, 1, 1, 0, 30, 0).unwrap();
+        assert_eq!(
+            content_line(datetime, "1.500000"),
+            "1735691400000000,1.500000"
+        );
+    }
+
+    #[test]
+    fn test_verify_finds_rewritten_entries_and_altered_readings() {
+        let entries = chain(&[
+            ("ingested", 1, 2, "aa"),
+            ("ingested", 2, 3, "bb"),
+            ("deleted", 1, 0, EMPTY_CONTENT),
+        ]);
+        let contents = HashMap::from([(2, (3, "bb".to_string()))]);
+        let verification = verify(Utc::now(), &entries, &contents, 0);
+        assert!(verification.intact);
+        assert_eq!(verification.head.as_ref(), Some(&entries[2].hash));
+
+        // Readings changed behind the chain's back
+        let contents = HashMap::from([(2, (3, "cc".to_string()))]);
+        let verification = verify(Utc::now(), &entries, &contents, 0);
+        assert!(!verification.intact);
+        assert_eq!(verification.broken_at, None);
+        assert_eq!(verification.altered[0].ingestion_id, 2);
+
+        // An entry rewritten to match, without recomputing the chain
+        let mut rewritten = entries.clone();
+        rewritten[1].content_hash = "cc".to_string();
+        let verification = verify(Utc::now(), &rewritten, &contents, 0);
+        assert_eq!(verification.broken_at, Some(2));
+        assert!(verification.altered.is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, Decimal128Array, Int32Array, Int64Array, RecordBatch, TimestampMicrosecondArray,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use bigdecimal::{BigDecimal, ToPrimitive as _};
+use chrono::{DateTime, Utc};
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+
+use crate::{
+    model::{
+        AMOUNT_PRECISION, AMOUNT_SCALE, api_response::AggregationQueryRecord, database::TSStore,
+    },
+    rounding,
+    watermark::Watermark,
+};
+
+/// Rows per record batch, each flushed as it is encoded (as its own row group for Parquet)
+/// so output can be streamed before the whole result is converted
+pub const BATCH_ROWS: usize = 65_536;
+
+/// Widest decimal Arrow can hold in 128 bits, used for totals summed across readings
+const TOTAL_PRECISION: u8 = 38;
+const READING_PRECISION: u8 = AMOUNT_PRECISION as u8;
+const SCALE: i8 = AMOUNT_SCALE as i8;
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+/// Scales `amount` to an Arrow decimal at [`AMOUNT_SCALE`], after applying the rounding
+/// policy, or marking it for the exporting key when a `watermark` is given
+fn decimal(
+    amount: &BigDecimal,
+    datetime: DateTime<Utc>,
+    watermark: Option<&Watermark>,
+) -> Result<i128, ArrowError> {
+    let rounded = match watermark {
+        Some(watermark) => watermark.mark(datetime, amount),
+        None => rounding::current().apply(amount).with_scale(AMOUNT_SCALE),
+    };
+    let (digits, _) = rounded.as_bigint_and_exponent();
+    digits
+        .to_i128()
+        .ok_or_else(|| ArrowError::InvalidArgumentError(format!("{amount} overflows i128")))
+}
+
+fn timestamps<'a>(datetimes: impl Iterator<Item = &'a chrono::DateTime<chrono::Utc>>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter_values(datetimes.map(|dt| dt.timestamp_micros()))
+            .with_timezone("UTC"),
+    )
+}
+
+pub fn aggregation_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("datetime", timestamp_type(), false),
+        Field::new(
+            "total_amount",
+            DataType::Decimal128(TOTAL_PRECISION, SCALE),
+            true,
+        ),
+    ]))
+}
+
+/// Columnar form of aggregation buckets, empty buckets have a null total
+pub fn aggregation_batch(records: &[AggregationQueryRecord]) -> Result<RecordBatch, ArrowError> {
+    watermarked_aggregation_batch(records, None)
+}
+
+/// [`aggregation_batch`] with totals marked for the exporting key when a `watermark` is given
+pub fn watermarked_aggregation_batch(
+    records: &[AggregationQueryRecord],
+    watermark: Option<&Watermark>,
+) -> Result<RecordBatch, ArrowError> {
+    let totals = records
+        .iter()
+        .map(|record| {
+            record
+                .total_amount
+                .as_ref()
+                .map(|amount| decimal(amount, record.datetime, watermark))
+                .transpose()
+        })
+        .collect::<Result<Decimal128Array, _>>()?
+        .with_precision_and_scale(TOTAL_PRECISION, SCALE)?;
+    RecordBatch::try_new(
+        aggregation_schema(),
+        vec![
+            timestamps(records.iter().map(|record| &record.datetime)),
+            Arc::new(totals),
+        ],
+    )
+}
+
+pub fn totals_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("datetime", timestamp_type(), false),
+        Field::new(
+            "amount",
+            DataType::Decimal128(TOTAL_PRECISION, SCALE),
+            false,
+        ),
+    ]))
+}
+
+/// Columnar form of readings already summed per timestamp, as held by the hot cache,
+/// scaled to [`AMOUNT_SCALE`] without applying the rounding policy
+pub fn totals_batch(rows: &[(DateTime<Utc>, BigDecimal)]) -> Result<RecordBatch, ArrowError> {
+    let amounts =
+        rows.iter()
+            .map(|(_, amount)| {
+                let (digits, _) = amount.with_scale(AMOUNT_SCALE).as_bigint_and_exponent();
+                digits.to_i128().map(Some).ok_or_else(|| {
+                    ArrowError::InvalidArgumentError(format!("{amount} overflows i128"))
+                })
+            })
+            .collect::<Result<Decimal128Array, _>>()?
+            .with_precision_and_scale(TOTAL_PRECISION, SCALE)?;
+    RecordBatch::try_new(
+        totals_schema(),
+        vec![
+            timestamps(rows.iter().map(|(datetime, _)| datetime)),
+            Arc::new(amounts),
+        ],
+    )
+}
+
+/// Series and priority of each ingestion by id, exported with its readings so those
+/// superseded can be told apart offline
+pub type IngestionRanks = HashMap<i64, (Option<i64>, i32)>;
+
+pub fn readings_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("ingestion_id", DataType::Int64, false),
+        Field::new("datetime", timestamp_type(), false),
+        Field::new(
+            "amount",
+            DataType::Decimal128(READING_PRECISION, SCALE),
+            false,
+        ),
+        Field::new("recorded_at", timestamp_type(), false),
+        Field::new("series_id", DataType::Int64, true),
+        Field::new("priority", DataType::Int32, false),
+    ]))
+}
+
+/// Columnar form of stored readings, one row per ingestion and timestamp, with the series
+/// and priority of its ingestion from `ranks`, unranked when missing
+pub fn readings_batch(
+    readings: &[TSStore],
+    ranks: &IngestionRanks,
+) -> Result<RecordBatch, ArrowError> {
+    watermarked_readings_batch(readings, ranks, None)
+}
+
+/// [`readings_batch`] with amounts marked for the exporting key when a `watermark` is given
+pub fn watermarked_readings_batch(
+    readings: &[TSStore],
+    ranks: &IngestionRanks,
+    watermark: Option<&Watermark>,
+) -> Result<RecordBatch, ArrowError> {
+    let rank = |reading: &TSStore| {
+        ranks
+            .get(&reading.ingestion_id)
+            .copied()
+            .unwrap_or_default()
+    };
+    let amounts = readings
+        .iter()
+        .map(|reading| decimal(&reading.amount, reading.datetime, watermark).map(Some))
+        .collect::<Result<Decimal128Array, _>>()?
+        .with_precision_and_scale(READING_PRECISION, SCALE)?;
+    RecordBatch::try_new(
+        readings_schema(),
+        vec![
+            Arc::new(Int64Array::from_iter_values(
+                readings.iter().map(|reading| reading.ingestion_id),
+            )),
+            timestamps(readings.iter().map(|reading| &reading.datetime)),
+            Arc::new(amounts),
+            timestamps(readings.iter().map(|reading| &reading.recorded_at)),
+            Arc::new(Int64Array::from_iter(
+                readings.iter().map(|reading| rank(reading).0),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                readings.iter().map(|reading| rank(reading).1),
+            )),
+        ],
+    )
+}
+
+/// Encodes `rows` as a Parquet file, converting and flushing [`BATCH_ROWS`] at a time
+pub fn write_parquet<T, W>(
+    writer: W,
+    schema: SchemaRef,
+    rows: &[T],
+    to_batch: impl Fn(&[T]) -> Result<RecordBatch, ArrowError>,
+) -> Result<(), ParquetError>
+where
+    W: io::Write + Send,
+{
+    let mut writer = ArrowWriter::try_new(writer, schema, None)?;
+    for chunk in rows.chunks(BATCH_ROWS) {
+        writer.write(&to_batch(chunk)?)?;
+        writer.flush()?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// Encodes rows handed over one at a time as a Parquet file, converting and flushing
+/// [`BATCH_ROWS`] at a time, so the rows are never all held at once
+pub struct ParquetStream<T, W: io::Write + Send, F> {
+    writer: ArrowWriter<W>,
+    rows: Vec<T>,
+    to_batch: F,
+}
+
+impl<T, W, F> ParquetStream<T, W, F>
+where
+    W: io::Write + Send,
+    F: Fn(&[T]) -> Result<RecordBatch, ArrowError>,
+{
+    pub fn try_new(writer: W, schema: SchemaRef, to_batch: F) -> Result<Self, ParquetError> {
+        Ok(Self {
+            writer: ArrowWriter::try_new(writer, schema, None)?,
+            rows: Vec::with_capacity(BATCH_ROWS),
+            to_batch,
+        })
+    }
+
+    pub fn push(&mut self, row: T) -> Result<(), ParquetError> {
+        self.rows.push(row);
+        if self.rows.len() == BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ParquetError> {
+        self.writer.write(&(self.to_batch)(&self.rows)?)?;
+        self.writer.flush()?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Writes the rows still buffered and the file footer
+    pub fn finish(mut self) -> Result<(), ParquetError> {
+        if !self.rows.is_empty() {
+            self.write_batch()?;
+        }
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Encodes `rows` as an Arrow IPC stream, converting and flushing [`BATCH_ROWS`] at a time
+pub fn write_ipc_stream<T, W>(
+    writer: W,
+    schema: SchemaRef,
+    rows: &[T],
+    to_batch: impl Fn(&[T]) -> Result<RecordBatch, ArrowError>,
+) -> Result<(), ArrowError>
+where
+    W: io::Write,
+{
+    let mut writer = StreamWriter::try_new(writer, &schema)?;
+    for chunk in rows.chunks(BATCH_ROWS) {
+        writer.write(&to_batch(chunk)?)?;
+        writer.flush()?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::Bytes;
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, TimeZone as _, Utc};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use arrow_ipc::reader::StreamReader;
+
+    use super::{
+        BATCH_ROWS, ParquetStream, aggregation_batch, aggregation_schema, write_ipc_stream,
+        write_parquet,
+    };
+    use crate::model::api_response::AggregationQueryRecord;
+
+    fn records() -> Vec<AggregationQueryRecord> {
+        let day = |d| -> DateTime<Utc> { Utc.with_ymd_and_hms(2025, 1, d, 0, 0, 0).unwrap() };
+        vec![
+            AggregationQueryRecord {
+                datetime: day(1),
+                total_amount: Some("9000.125".parse::<BigDecimal>().unwrap()),
+            },
+            AggregationQueryRecord {
+                datetime: day(2),
+                total_amount: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_aggregation_round_trips_through_parquet() {
+        let records = records();
+
+        let mut buffer = Vec::new();
+        write_parquet(
+            &mut buffer,
+            aggregation_schema(),
+            &records,
+            aggregation_batch,
+        )
+        .unwrap();
+
+        let batches = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(buffer))
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0], aggregation_batch(&records).unwrap());
+    }
+
+    #[test]
+    fn test_rows_pushed_one_at_a_time_encode_as_written_whole() {
+        let records: Vec<_> = (0..BATCH_ROWS + 3)
+            .map(|i| AggregationQueryRecord {
+                datetime: DateTime::from_timestamp(i64::try_from(i).unwrap() * 3600, 0).unwrap(),
+                total_amount: Some(BigDecimal::from(i64::try_from(i).unwrap())),
+            })
+            .collect();
+
+        let mut whole = Vec::new();
+        write_parquet(
+            &mut whole,
+            aggregation_schema(),
+            &records,
+            aggregation_batch,
+        )
+        .unwrap();
+        let mut pushed = Vec::new();
+        let mut stream =
+            ParquetStream::try_new(&mut pushed, aggregation_schema(), aggregation_batch).unwrap();
+        for record in records {
+            stream.push(record).unwrap();
+        }
+        stream.finish().unwrap();
+
+        let batches = |file: Vec<u8>| {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(file)).unwrap();
+            // A row group is flushed every BATCH_ROWS rows
+            assert_eq!(reader.metadata().num_row_groups(), 2);
+            reader
+                .build()
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        assert_eq!(batches(pushed), batches(whole));
+    }
+
+    #[test]
+    fn test_aggregation_round_trips_through_ipc_stream() {
+        let records = records();
+
+        let mut buffer = Vec::new();
+        write_ipc_stream(
+            &mut buffer,
+            aggregation_schema(),
+            &records,
+            aggregation_batch,
+        )
+        .unwrap();
+
+        let batches = StreamReader::try_new(buffer.as_slice(), None)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches, [aggregation_batch(&records).unwrap()]);
+    }
+}
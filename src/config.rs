@@ -0,0 +1,625 @@
+use std::{collections::BTreeMap, env, net::SocketAddr, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, DurationRound as _, TimeDelta, Utc};
+use figment::{
+    Figment,
+    providers::{Env, Format as _, Serialized, Toml},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{PoolRecycling, PoolTuning, query::DEFAULT_HISTORY_LIMIT},
+    model::csv::{CsvSchema, EnergyUnit},
+    rounding::{RoundingMode, RoundingPolicy},
+    upload,
+};
+
+const DEFAULT_CONFIG_FILE: &str = "renewable.toml";
+const MINUTES_PER_DAY: i64 = 24 * 60;
+/// A century, keeping the default window start representable
+const MAX_DEFAULT_QUERY_DAYS: i64 = 36_600;
+
+/// Environment variables that override values from the config file
+const ENV_KEYS: [&str; 48] = [
+    "listen_addr",
+    "grpc_listen_addr",
+    "request_timeout_secs",
+    "query_timeout_secs",
+    "ingest_timeout_secs",
+    "health_timeout_ms",
+    "shutdown_grace_secs",
+    "db_pool_size",
+    "db_pool_min_idle",
+    "db_pool_wait_timeout_ms",
+    "db_pool_connect_timeout_ms",
+    "db_pool_recycle_timeout_ms",
+    "db_pool_recycling",
+    "db_statement_timeout_ms",
+    "history_limit",
+    "history_flush_ms",
+    "history_buffer",
+    "rounding_mode",
+    "rounding_scale",
+    "max_query_span_days",
+    "max_query_buckets",
+    "default_query_days",
+    "max_stream_rows",
+    "response_cache_entries",
+    "response_cache_ttl_secs",
+    "redis_url",
+    "query_fallback",
+    "shadow_query_one_in",
+    "reading_interval_minutes",
+    "read_only",
+    "partition_months_ahead",
+    "retention_days",
+    "retention_interval_secs",
+    "retention_dry_run",
+    "source_priorities",
+    "reject_unit_mismatch",
+    "watch_dir",
+    "watch_interval_secs",
+    "upload_bucket",
+    "upload_url_ttl_secs",
+    "telemetry_dir",
+    "telemetry_interval_secs",
+    "csv_datetime_column",
+    "csv_amount_column",
+    "csv_datetime_format",
+    "csv_decimal_separator",
+    "csv_unit",
+    "csv_capture_extra",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("unable to load config {0}")]
+    Figment(Box<figment::Error>),
+
+    #[error("invalid config {0}")]
+    Invalid(&'static str),
+}
+
+/// Service settings layered from defaults, an optional TOML file and the environment.
+///
+/// The file is read from `CONFIG_FILE`, falling back to `renewable.toml` when present,
+/// and each key can be overridden by the upper-cased environment variable.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AppConfig {
+    /// TCP address, `unix:<path>` or `systemd`, see [`crate::listener::ListenerConfig`]
+    pub listen_addr: String,
+    /// TCP address of the gRPC service, not served when unset
+    pub grpc_listen_addr: Option<SocketAddr>,
+    /// Timeout of the endpoints outside the query, ingest and health groups, such as the
+    /// admin endpoints and signed export downloads
+    pub request_timeout_secs: u64,
+    /// Timeout of the endpoints reading stored data, queries, GraphQL and exports
+    pub query_timeout_secs: u64,
+    /// Timeout of the endpoints writing to the database, uploads above all
+    pub ingest_timeout_secs: u64,
+    /// Timeout of `/healthz`, `/readyz` and `/version`
+    pub health_timeout_ms: u64,
+    /// Time given to in-flight requests, background jobs and database work to finish
+    /// after a termination signal before they are cancelled
+    pub shutdown_grace_secs: u64,
+    /// Maximum Postgres connections, defaults to four per CPU when unset
+    pub db_pool_size: Option<usize>,
+    /// Connections opened at startup, kept open once used
+    pub db_pool_min_idle: usize,
+    /// How long a request waits for a free connection before failing with a 503, waiting
+    /// for as long as its timeout allows when unset
+    pub db_pool_wait_timeout_ms: Option<u64>,
+    /// How long opening a connection may take, unbounded when unset
+    pub db_pool_connect_timeout_ms: Option<u64>,
+    /// How long checking a pooled connection may take before it is replaced, unbounded
+    /// when unset
+    pub db_pool_recycle_timeout_ms: Option<u64>,
+    /// `fast` only checks a pooled connection holds no open transaction, `verified` also
+    /// runs a test query
+    pub db_pool_recycling: PoolRecycling,
+    /// `statement_timeout` set on every connection as it is checked out, Postgres' own
+    /// default applying when unset
+    pub db_statement_timeout_ms: Option<u64>,
+    /// Number of entries returned by the query history endpoint
+    pub history_limit: i64,
+    /// Interval between batched query history inserts
+    pub history_flush_ms: u64,
+    /// Query history entries held between flushes before new ones are dropped
+    pub history_buffer: usize,
+    pub rounding_mode: RoundingMode,
+    /// Decimal places amounts are rounded to, unrounded when unset
+    pub rounding_scale: Option<i64>,
+    /// Widest closed date range a request may ask for, unlimited when unset
+    pub max_query_span_days: Option<i64>,
+    /// Most buckets an aggregation is estimated to return before it is refused with a
+    /// 413, unlimited when unset
+    pub max_query_buckets: Option<i64>,
+    /// Trailing days an aggregation request without a `datetime_filter` covers
+    pub default_query_days: i64,
+    /// Buckets a streamed query, or readings a raw Parquet export, may send before it is
+    /// ended with an error
+    pub max_stream_rows: usize,
+    /// Aggregation results cached in process, the cache is disabled when zero
+    pub response_cache_entries: usize,
+    /// Age at which cached aggregation results are recomputed, bounding staleness after
+    /// writes through other instances
+    pub response_cache_ttl_secs: u64,
+    /// Redis shared by every replica for cached responses, for builds with the
+    /// `redis-cache` feature. Unset keeps caching in process.
+    pub redis_url: Option<String>,
+    /// Answer queries from the hot cache while Postgres is unavailable, flagging the
+    /// responses as degraded. Needs `HOT_CACHE_DAYS`.
+    pub query_fallback: bool,
+    /// Runs one in this many aggregations answered from the daily and monthly summaries
+    /// again over `ts_store` in the background, logging buckets whose totals differ.
+    /// Unset runs none.
+    pub shadow_query_one_in: Option<u64>,
+    /// Native interval between readings, used for clock drift and bucket completeness
+    pub reading_interval_minutes: i64,
+    /// Start as a warm standby rejecting writes, switched at runtime through the admin API
+    pub read_only: bool,
+    /// Months after the current one given a `ts_store` partition ahead of their readings
+    pub partition_months_ahead: u32,
+    /// Days readings and query history are kept for, kept forever when unset
+    pub retention_days: Option<u32>,
+    pub retention_interval_secs: u64,
+    /// Only count and log the rows retention would purge
+    pub retention_dry_run: bool,
+    /// Priority of the ingestions whose source starts with each prefix, the longest
+    /// matching prefix applying and unmatched sources ranking 0. Queries take a reading
+    /// from the highest priority ingestion of a series holding its timestamp.
+    pub source_priorities: BTreeMap<String, i32>,
+    /// Refuses uploads to a series whose readings are about a power of 1000 off those it
+    /// held before, which are otherwise stored and flagged
+    pub reject_unit_mismatch: bool,
+    /// Directory polled for new readings files to ingest, not watched when unset
+    pub watch_dir: Option<PathBuf>,
+    pub watch_interval_secs: u64,
+    /// `s3://`, `gs://` or `az://` bucket, and optional prefix, clients are issued
+    /// pre-signed upload URLs under, not offered when unset
+    pub upload_bucket: Option<String>,
+    /// How long a pre-signed upload URL stays valid
+    pub upload_url_ttl_secs: u64,
+    /// Directory anonymous usage reports are written to, telemetry being off when unset
+    pub telemetry_dir: Option<PathBuf>,
+    pub telemetry_interval_secs: u64,
+    /// Header of the readings CSV column holding the UTC timestamp
+    pub csv_datetime_column: String,
+    /// Header of the readings CSV column holding the energy amount
+    pub csv_amount_column: String,
+    /// `chrono` format of the timestamp column
+    pub csv_datetime_format: String,
+    /// `.` or `,`, the other being accepted as a thousands separator
+    pub csv_decimal_separator: char,
+    /// Unit of the amount column, converted to kWh on ingestion
+    pub csv_unit: EnergyUnit,
+    /// Store the other columns of each readings CSV row in `ts_store.extra` as JSON
+    pub csv_capture_extra: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        let schema = CsvSchema::default();
+        Self {
+            listen_addr: "0.0.0.0:8000".to_string(),
+            grpc_listen_addr: None,
+            request_timeout_secs: 2,
+            query_timeout_secs: 30,
+            ingest_timeout_secs: 120,
+            health_timeout_ms: 500,
+            shutdown_grace_secs: 30,
+            db_pool_size: None,
+            db_pool_min_idle: 0,
+            db_pool_wait_timeout_ms: None,
+            db_pool_connect_timeout_ms: None,
+            db_pool_recycle_timeout_ms: None,
+            db_pool_recycling: PoolRecycling::default(),
+            db_statement_timeout_ms: None,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            history_flush_ms: 250,
+            history_buffer: 4096,
+            rounding_mode: RoundingMode::default(),
+            rounding_scale: None,
+            max_query_span_days: None,
+            max_query_buckets: None,
+            default_query_days: 30,
+            max_stream_rows: 1_000_000,
+            response_cache_entries: 0,
+            response_cache_ttl_secs: 60,
+            redis_url: None,
+            query_fallback: false,
+            shadow_query_one_in: None,
+            reading_interval_minutes: 60,
+            read_only: false,
+            partition_months_ahead: 3,
+            retention_days: None,
+            retention_interval_secs: 3600,
+            retention_dry_run: false,
+            source_priorities: BTreeMap::new(),
+            reject_unit_mismatch: false,
+            watch_dir: None,
+            watch_interval_secs: 30,
+            upload_bucket: None,
+            upload_url_ttl_secs: 3600,
+            telemetry_dir: None,
+            telemetry_interval_secs: 86_400,
+            csv_datetime_column: schema.datetime_column,
+            csv_amount_column: schema.amount_column,
+            csv_datetime_format: schema.datetime_format,
+            csv_decimal_separator: schema.decimal_separator,
+            csv_unit: schema.unit,
+            csv_capture_extra: schema.capture_extra,
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = env::var("CONFIG_FILE").unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string());
+        Self::extract(
+            Figment::from(Serialized::defaults(Self::default()))
+                .merge(Toml::file(file))
+                .merge(Env::raw().only(&ENV_KEYS)),
+        )
+    }
+
+    fn extract(figment: Figment) -> Result<Self, ConfigError> {
+        let config: Self = figment
+            .extract()
+            .map_err(|e| ConfigError::Figment(Box::new(e)))?;
+        if config.request_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "request_timeout_secs must be positive",
+            ));
+        }
+        if config.query_timeout_secs == 0 {
+            return Err(ConfigError::Invalid("query_timeout_secs must be positive"));
+        }
+        if config.ingest_timeout_secs == 0 {
+            return Err(ConfigError::Invalid("ingest_timeout_secs must be positive"));
+        }
+        if config.health_timeout_ms == 0 {
+            return Err(ConfigError::Invalid("health_timeout_ms must be positive"));
+        }
+        if config.db_pool_size == Some(0) {
+            return Err(ConfigError::Invalid("db_pool_size must be positive"));
+        }
+        if config
+            .db_pool_size
+            .is_some_and(|size| config.db_pool_min_idle > size)
+        {
+            return Err(ConfigError::Invalid(
+                "db_pool_min_idle must not exceed db_pool_size",
+            ));
+        }
+        if [
+            config.db_pool_wait_timeout_ms,
+            config.db_pool_connect_timeout_ms,
+            config.db_pool_recycle_timeout_ms,
+            config.db_statement_timeout_ms,
+        ]
+        .contains(&Some(0))
+        {
+            return Err(ConfigError::Invalid("db_pool timeouts must be positive"));
+        }
+        if config.max_stream_rows == 0 {
+            return Err(ConfigError::Invalid("max_stream_rows must be positive"));
+        }
+        if config.response_cache_ttl_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "response_cache_ttl_secs must be positive",
+            ));
+        }
+        if config.redis_url.is_some() && !cfg!(feature = "redis-cache") {
+            return Err(ConfigError::Invalid(
+                "redis_url requires a build with the redis-cache feature",
+            ));
+        }
+        if config.history_limit <= 0 {
+            return Err(ConfigError::Invalid("history_limit must be positive"));
+        }
+        if config.history_flush_ms == 0 {
+            return Err(ConfigError::Invalid("history_flush_ms must be positive"));
+        }
+        if config.watch_interval_secs == 0 {
+            return Err(ConfigError::Invalid("watch_interval_secs must be positive"));
+        }
+        if config
+            .upload_bucket
+            .as_deref()
+            .is_some_and(|bucket| !upload::is_bucket_url(bucket))
+        {
+            return Err(ConfigError::Invalid(
+                "upload_bucket must be an s3://, gs:// or az:// URL",
+            ));
+        }
+        if config.upload_url_ttl_secs == 0 {
+            return Err(ConfigError::Invalid("upload_url_ttl_secs must be positive"));
+        }
+        if config.telemetry_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "telemetry_interval_secs must be positive",
+            ));
+        }
+        if config.retention_days == Some(0) {
+            return Err(ConfigError::Invalid("retention_days must be positive"));
+        }
+        if config.retention_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "retention_interval_secs must be positive",
+            ));
+        }
+        if config.source_priorities.keys().any(String::is_empty) {
+            return Err(ConfigError::Invalid(
+                "source_priorities prefixes must not be empty",
+            ));
+        }
+        if !matches!(config.csv_decimal_separator, '.' | ',') {
+            return Err(ConfigError::Invalid(
+                "csv_decimal_separator must be '.' or ','",
+            ));
+        }
+        if !CsvSchema::is_valid_format(&config.csv_datetime_format) {
+            return Err(ConfigError::Invalid(
+                "csv_datetime_format is not a valid datetime format",
+            ));
+        }
+        if config.history_buffer == 0 {
+            return Err(ConfigError::Invalid("history_buffer must be positive"));
+        }
+        if config.rounding_scale.is_some_and(|scale| scale < 0) {
+            return Err(ConfigError::Invalid("rounding_scale must not be negative"));
+        }
+        if config.max_query_span_days.is_some_and(|days| days <= 0) {
+            return Err(ConfigError::Invalid("max_query_span_days must be positive"));
+        }
+        if config.max_query_buckets.is_some_and(|buckets| buckets <= 0) {
+            return Err(ConfigError::Invalid("max_query_buckets must be positive"));
+        }
+        if config.shadow_query_one_in == Some(0) {
+            return Err(ConfigError::Invalid("shadow_query_one_in must be positive"));
+        }
+        if !(1..=MAX_DEFAULT_QUERY_DAYS).contains(&config.default_query_days) {
+            return Err(ConfigError::Invalid(
+                "default_query_days must be between 1 and 36600",
+            ));
+        }
+        // The reading grid is anchored at midnight UTC, so the interval must tile a day
+        if config.reading_interval_minutes <= 0
+            || MINUTES_PER_DAY % config.reading_interval_minutes != 0
+        {
+            return Err(ConfigError::Invalid(
+                "reading_interval_minutes must divide a day",
+            ));
+        }
+        Ok(config)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn query_timeout(&self) -> Duration {
+        Duration::from_secs(self.query_timeout_secs)
+    }
+
+    pub fn ingest_timeout(&self) -> Duration {
+        Duration::from_secs(self.ingest_timeout_secs)
+    }
+
+    pub fn health_timeout(&self) -> Duration {
+        Duration::from_millis(self.health_timeout_ms)
+    }
+
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_secs)
+    }
+
+    /// Sizing and timeouts of the primary's and the read replica's pools
+    pub fn pool_tuning(&self) -> PoolTuning {
+        PoolTuning {
+            max_size: self.db_pool_size,
+            min_idle: self.db_pool_min_idle,
+            wait_timeout: self.db_pool_wait_timeout_ms.map(Duration::from_millis),
+            create_timeout: self.db_pool_connect_timeout_ms.map(Duration::from_millis),
+            recycle_timeout: self.db_pool_recycle_timeout_ms.map(Duration::from_millis),
+            recycling: self.db_pool_recycling,
+            statement_timeout: self.db_statement_timeout_ms.map(Duration::from_millis),
+        }
+    }
+
+    pub fn reading_interval(&self) -> TimeDelta {
+        TimeDelta::minutes(self.reading_interval_minutes)
+    }
+
+    /// Start of the window covered by a request without a `datetime_filter`, the last
+    /// `default_query_days` days from the start of the hour so repeated requests share a
+    /// cache key
+    pub fn default_query_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let start = now - TimeDelta::days(self.default_query_days);
+        start.duration_trunc(TimeDelta::hours(1)).unwrap_or(start)
+    }
+
+    pub fn watch_interval(&self) -> Duration {
+        Duration::from_secs(self.watch_interval_secs)
+    }
+
+    pub fn upload_url_ttl(&self) -> Duration {
+        Duration::from_secs(self.upload_url_ttl_secs)
+    }
+
+    pub fn telemetry_interval(&self) -> Duration {
+        Duration::from_secs(self.telemetry_interval_secs)
+    }
+
+    pub fn retention_interval(&self) -> Duration {
+        Duration::from_secs(self.retention_interval_secs)
+    }
+
+    pub fn response_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.response_cache_ttl_secs)
+    }
+
+    /// Priority given to ingestions of `source`, see [`Self::source_priorities`]
+    pub fn source_priority(&self, source: &str) -> i32 {
+        self.source_priorities
+            .iter()
+            .filter(|(prefix, _)| source.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(0, |(_, priority)| *priority)
+    }
+
+    pub fn reject_unit_mismatch(&self) -> bool {
+        self.reject_unit_mismatch
+    }
+
+    pub fn history_flush_interval(&self) -> Duration {
+        Duration::from_millis(self.history_flush_ms)
+    }
+
+    pub fn rounding_policy(&self) -> RoundingPolicy {
+        RoundingPolicy {
+            mode: self.rounding_mode,
+            scale: self.rounding_scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::{
+        Figment,
+        providers::{Format as _, Serialized, Toml},
+    };
+
+    use super::AppConfig;
+    use crate::{db::PoolRecycling, rounding::RoundingMode};
+
+    fn from_toml(toml: &str) -> Result<AppConfig, super::ConfigError> {
+        AppConfig::extract(
+            Figment::from(Serialized::defaults(AppConfig::default())).merge(Toml::string(toml)),
+        )
+    }
+
+    #[test]
+    fn test_config_layers_file_over_defaults() {
+        let config = from_toml(
+            r#"
+            listen_addr = "unix:/run/renewable/api.sock"
+            db_pool_size = 32
+            db_pool_min_idle = 4
+            db_pool_wait_timeout_ms = 1500
+            db_pool_recycling = "verified"
+            db_statement_timeout_ms = 30000
+            rounding_mode = "half_up"
+            rounding_scale = 2
+            grpc_listen_addr = "127.0.0.1:50051"
+            read_only = true
+            query_timeout_secs = 90
+            query_fallback = true
+            reject_unit_mismatch = true
+            telemetry_dir = "usage"
+
+            [source_priorities]
+            "provider-final" = 10
+            "provider" = 5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.listen_addr, "unix:/run/renewable/api.sock");
+        assert_eq!(config.db_pool_size, Some(32));
+        let tuning = config.pool_tuning();
+        assert_eq!(
+            (tuning.max_size, tuning.min_idle, tuning.recycling),
+            (Some(32), 4, PoolRecycling::Verified)
+        );
+        assert_eq!(
+            tuning.wait_timeout,
+            Some(std::time::Duration::from_millis(1500))
+        );
+        assert_eq!(tuning.create_timeout, None);
+        assert_eq!(
+            tuning.statement_timeout,
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            config.grpc_listen_addr,
+            Some("127.0.0.1:50051".parse().unwrap())
+        );
+        assert!(config.read_only);
+        assert!(config.query_fallback);
+        assert!(config.reject_unit_mismatch());
+        assert_eq!(
+            config.telemetry_dir.as_deref(),
+            Some(std::path::Path::new("usage"))
+        );
+        assert_eq!(
+            config.telemetry_interval(),
+            std::time::Duration::from_secs(86_400)
+        );
+        assert_eq!(config.source_priority("provider-final-2025-01.csv"), 10);
+        assert_eq!(config.source_priority("provider-provisional.csv"), 5);
+        assert_eq!(config.source_priority("upload.csv"), 0);
+        assert_eq!(config.request_timeout_secs, 2);
+        assert_eq!(config.query_timeout(), std::time::Duration::from_secs(90));
+        assert_eq!(config.ingest_timeout_secs, 120);
+        assert_eq!(
+            config.health_timeout(),
+            std::time::Duration::from_millis(500)
+        );
+        assert_eq!(config.shutdown_grace_secs, 30);
+        assert_eq!(config.history_limit, AppConfig::default().history_limit);
+        assert_eq!(config.rounding_policy().mode, RoundingMode::HalfUp);
+        assert_eq!(config.rounding_policy().scale, Some(2));
+        assert_eq!(
+            config.default_query_start("2025-03-31T10:45:12Z".parse().unwrap()),
+            "2025-03-01T10:00:00Z"
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_rejects_invalid_values() {
+        assert!(from_toml("request_timeout_secs = 0").is_err());
+        assert!(from_toml("query_timeout_secs = 0").is_err());
+        assert!(from_toml("health_timeout_ms = 0").is_err());
+        assert!(from_toml("history_limit = -1").is_err());
+        assert!(from_toml("db_pool_size = \"many\"").is_err());
+        assert!(from_toml("db_pool_size = 2\ndb_pool_min_idle = 3").is_err());
+        assert!(from_toml("db_pool_wait_timeout_ms = 0").is_err());
+        assert!(from_toml("db_pool_recycling = \"sometimes\"").is_err());
+        assert!(from_toml("grpc_listen_addr = \"localhost\"").is_err());
+        assert!(from_toml("rounding_mode = \"sideways\"").is_err());
+        assert!(from_toml("rounding_scale = -1").is_err());
+        assert!(from_toml("max_query_span_days = 0").is_err());
+        assert!(from_toml("max_query_buckets = 0").is_err());
+        assert!(from_toml("shadow_query_one_in = 0").is_err());
+        assert!(from_toml("db_statement_timeout_ms = 0").is_err());
+        assert!(from_toml("default_query_days = 0").is_err());
+        assert!(from_toml("max_stream_rows = 0").is_err());
+        assert!(from_toml("response_cache_ttl_secs = 0").is_err());
+        assert_eq!(
+            from_toml("redis_url = \"redis://localhost:6379\"").is_ok(),
+            cfg!(feature = "redis-cache")
+        );
+        assert!(from_toml("history_flush_ms = 0").is_err());
+        assert!(from_toml("history_buffer = 0").is_err());
+        assert!(from_toml("watch_interval_secs = 0").is_err());
+        assert!(from_toml("telemetry_interval_secs = 0").is_err());
+        assert!(from_toml("upload_bucket = \"uploads\"").is_err());
+        assert!(from_toml("upload_bucket = \"s3://meters/uploads\"").is_ok());
+        assert!(from_toml("upload_url_ttl_secs = 0").is_err());
+        assert!(from_toml("partition_months_ahead = -1").is_err());
+        assert!(from_toml("retention_days = 0").is_err());
+        assert!(from_toml("retention_interval_secs = 0").is_err());
+        assert!(from_toml("reading_interval_minutes = 7").is_err());
+        assert!(from_toml("reading_interval_minutes = 30").is_ok());
+        assert!(from_toml("csv_decimal_separator = \";\"").is_err());
+        assert!(from_toml("csv_datetime_format = \"%Q\"").is_err());
+        assert!(from_toml("csv_unit = \"gwh\"").is_err());
+        assert!(from_toml("source_priorities = { \"\" = 1 }").is_err());
+        assert!(from_toml("source_priorities = { final = \"high\" }").is_err());
+        assert!(from_toml("csv_unit = \"mwh\"").is_ok());
+    }
+}
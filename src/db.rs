@@ -1,8 +1,20 @@
-use std::env;
+use std::{
+    env,
+    ops::Deref,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
-use deadpool_diesel::{InteractError, Manager, Pool, PoolError, Runtime, postgres::BuildError};
-use diesel::PgConnection;
+use deadpool_diesel::{
+    InteractError, Manager, ManagerConfig, Pool, PoolError, RecyclingMethod, Runtime,
+    postgres::{BuildError, Hook, HookError, Object},
+};
+use diesel::{Connection as _, PgConnection, QueryResult, RunQueryDsl as _};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness as _, embed_migrations};
+use serde::{Deserialize, Serialize};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
@@ -33,96 +45,725 @@ pub enum PgError {
     DieselError(diesel::result::Error),
 }
 
-pub async fn establish_pg_connection() -> Result<Pool<Manager<PgConnection>>, PgError> {
-    let database_url = env::var("DATABASE_URL").map_err(|_| PgError::DatabaseURL)?;
-    let pg_manager = Manager::new(database_url, Runtime::Tokio1);
+/// How connections are checked before a pooled one is handed out again
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolRecycling {
+    /// Only check the connection holds no open transaction
+    #[default]
+    Fast,
+    /// Also run a test query, catching connections the server closed
+    Verified,
+}
+
+/// Sizing and timeouts of a connection pool, from the `db_pool_*` settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolTuning {
+    /// Defaults to four per CPU when unset
+    pub max_size: Option<usize>,
+    /// Connections opened when the pool is built. Idle connections are never closed, so
+    /// they stay open.
+    pub min_idle: usize,
+    /// How long a checkout waits for a free connection before failing, unbounded when unset
+    pub wait_timeout: Option<Duration>,
+    /// How long opening a connection may take
+    pub create_timeout: Option<Duration>,
+    /// How long checking a pooled connection may take before it is replaced
+    pub recycle_timeout: Option<Duration>,
+    pub recycling: PoolRecycling,
+    /// `statement_timeout` set on each connection as it is created or checked out again
+    pub statement_timeout: Option<Duration>,
+}
+
+async fn build_pool(database_url: String, tuning: &PoolTuning) -> Result<TimedPool, PgError> {
+    let recycling_method = match tuning.recycling {
+        PoolRecycling::Fast => RecyclingMethod::Fast,
+        PoolRecycling::Verified => RecyclingMethod::Verified,
+    };
+    let pg_manager = Manager::from_config(
+        database_url,
+        Runtime::Tokio1,
+        ManagerConfig { recycling_method },
+    );
+
+    let mut builder = Pool::builder(pg_manager)
+        .runtime(Runtime::Tokio1)
+        .wait_timeout(tuning.wait_timeout)
+        .create_timeout(tuning.create_timeout)
+        .recycle_timeout(tuning.recycle_timeout);
+    if let Some(max_size) = tuning.max_size {
+        builder = builder.max_size(max_size);
+    }
+    if let Some(timeout) = tuning.statement_timeout {
+        builder = builder
+            .post_create(statement_timeout_hook(timeout))
+            .post_recycle(statement_timeout_hook(timeout));
+    }
+    let pg_pool = builder.build().map_err(PgError::PoolBuildError)?;
+
+    // Connections returned to the pool stay open, so holding `min_idle` at once keeps
+    // them ready for the first requests
+    let min_idle = tuning.min_idle.min(pg_pool.status().max_size);
+    let mut warm = Vec::with_capacity(min_idle);
+    for _ in 0..min_idle {
+        warm.push(pg_pool.get().await.map_err(PgError::ConnectionError)?);
+    }
+    Ok(TimedPool::new(pg_pool))
+}
+
+/// Sets `statement_timeout` for the session, so it also holds outside the transactions
+/// of [`with_statement_timeout`] and is restored should anything have changed it
+fn statement_timeout_hook(timeout: Duration) -> Hook {
+    let millis = timeout.as_millis().max(1);
+    Hook::async_fn(move |conn, _| {
+        Box::pin(async move {
+            conn.interact(move |conn| {
+                diesel::sql_query(format!("SET statement_timeout = {millis}")).execute(conn)
+            })
+            .await
+            .map_err(|e| HookError::message(e.to_string()))?
+            .map_err(|e| HookError::Backend(deadpool_diesel::Error::Ping(e)))?;
+            Ok(())
+        })
+    })
+}
 
-    let pg_pool: Pool<Manager<PgConnection>> = Pool::builder(pg_manager)
-        .build()
-        .map_err(PgError::PoolBuildError)?;
+pub async fn establish_pg_connection(tuning: &PoolTuning) -> Result<TimedPool, PgError> {
+    let database_url = env::var("DATABASE_URL").map_err(|_| PgError::DatabaseURL)?;
+    let pg_pool = build_pool(database_url, tuning).await?;
 
     {
         let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
 
-        conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
-            .await
-            .map_err(PgError::InteractionError)?
-            .unwrap();
+        // Migrations are not bound by `statement_timeout`, the hook restoring it on the
+        // connection's next checkout
+        conn.interact(|conn| {
+            diesel::sql_query("SET statement_timeout = 0").execute(conn)?;
+            conn.run_pending_migrations(MIGRATIONS).map(|_| ())
+        })
+        .await
+        .map_err(PgError::InteractionError)?
+        .unwrap();
     }
 
     Ok(pg_pool)
 }
 
+/// Checkouts of a pool since startup and how long they waited for a connection
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PoolWaits {
+    pub checkouts: u64,
+    pub total: Duration,
+    pub longest: Duration,
+}
+
+#[derive(Debug, Default)]
+struct WaitCounters {
+    checkouts: AtomicU64,
+    total_micros: AtomicU64,
+    longest_micros: AtomicU64,
+}
+
+/// Pool timing how long each checkout waits for a connection. It dereferences to the
+/// pool, so it can be passed wherever one is taken, only checkouts through
+/// [`TimedPool::get`] being timed.
+#[derive(Clone)]
+pub struct TimedPool {
+    pool: Pool<Manager<PgConnection>>,
+    waits: Arc<WaitCounters>,
+}
+
+impl TimedPool {
+    pub fn new(pool: Pool<Manager<PgConnection>>) -> Self {
+        Self {
+            pool,
+            waits: Arc::default(),
+        }
+    }
+
+    /// Checks a connection out, timing the wait whether or not one is handed out
+    pub async fn get(&self) -> Result<Object, PoolError> {
+        let started = Instant::now();
+        let conn = self.pool.get().await;
+        let waited = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.waits.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.waits.total_micros.fetch_add(waited, Ordering::Relaxed);
+        self.waits
+            .longest_micros
+            .fetch_max(waited, Ordering::Relaxed);
+        conn
+    }
+
+    pub fn waits(&self) -> PoolWaits {
+        PoolWaits {
+            checkouts: self.waits.checkouts.load(Ordering::Relaxed),
+            total: Duration::from_micros(self.waits.total_micros.load(Ordering::Relaxed)),
+            longest: Duration::from_micros(self.waits.longest_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Deref for TimedPool {
+    type Target = Pool<Manager<PgConnection>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// Connection pools handed to the handlers: the primary, which takes every write and the
+/// reads that must see them, and the read replica at `DATABASE_READ_URL` when one is set,
+/// which answers aggregations. Without a replica both are the primary's pool.
+#[derive(Clone)]
+pub struct Pools {
+    primary: TimedPool,
+    replica: Option<TimedPool>,
+}
+
+impl Pools {
+    pub fn new(primary: TimedPool, replica: Option<TimedPool>) -> Self {
+        Self { primary, replica }
+    }
+
+    /// Pool for writes, and for reads following them
+    pub fn primary(&self) -> &TimedPool {
+        &self.primary
+    }
+
+    /// Pool for aggregation reads, the replica's when one is configured
+    pub fn read(&self) -> &TimedPool {
+        self.replica.as_ref().unwrap_or(&self.primary)
+    }
+
+    pub fn replica(&self) -> Option<&TimedPool> {
+        self.replica.as_ref()
+    }
+
+    pub fn close(&self) {
+        self.primary.close();
+        if let Some(replica) = &self.replica {
+            replica.close();
+        }
+    }
+}
+
+/// Pool of the read replica at `DATABASE_READ_URL`, `None` when it is unset. Migrations
+/// are left to the primary, whose schema the replica follows.
+pub async fn establish_read_pool(tuning: &PoolTuning) -> Result<Option<TimedPool>, PgError> {
+    let Ok(database_url) = env::var("DATABASE_READ_URL") else {
+        return Ok(None);
+    };
+    let pg_pool = build_pool(database_url, tuning).await?;
+    // Fail at startup rather than on the first query when the replica is unreachable
+    drop(pg_pool.get().await.map_err(PgError::ConnectionError)?);
+    Ok(Some(pg_pool))
+}
+
+/// Runs `query` in a transaction bounded by `SET LOCAL statement_timeout`, so Postgres
+/// cancels the work once the caller has stopped waiting. Unbounded when `timeout` is `None`.
+pub fn with_statement_timeout<T>(
+    conn: &mut PgConnection,
+    timeout: Option<Duration>,
+    query: impl FnOnce(&mut PgConnection) -> QueryResult<T>,
+) -> QueryResult<T> {
+    let Some(timeout) = timeout else {
+        return query(conn);
+    };
+    // A zero statement_timeout disables the limit, so an expired deadline still gets 1ms
+    let millis = timeout.as_millis().clamp(1, u128::from(u32::MAX));
+    conn.transaction(|conn| {
+        diesel::sql_query(format!("SET LOCAL statement_timeout = {millis}")).execute(conn)?;
+        query(conn)
+    })
+}
+
+/// Whether Postgres cancelled the statement because `statement_timeout` elapsed
+pub fn is_statement_timeout(error: &diesel::result::Error) -> bool {
+    matches!(
+        error,
+        diesel::result::Error::DatabaseError(_, info)
+            if info.message().contains("canceling statement due to statement timeout")
+    )
+}
+
 pub mod seed_database {
-    use std::{env, fs::File, io::BufReader, path::Path};
+    use std::{
+        collections::BTreeMap,
+        env,
+        fs::File,
+        io::{BufReader, Read},
+        time::Instant,
+    };
 
-    use diesel::{OptionalEmptyChangesetExtension, RunQueryDsl, connection::Connection};
-    use tracing::{error, info};
+    use chrono::{DateTime, Utc};
+    use diesel::{
+        ExpressionMethods as _, OptionalEmptyChangesetExtension, OptionalExtension as _,
+        PgConnection, QueryDsl as _, QueryResult, RunQueryDsl,
+        connection::Connection,
+        dsl::{count_star, max, min},
+        upsert::excluded,
+    };
+    use tracing::{error, info, warn};
 
     use crate::{
+        config::AppConfig,
         db::PgError,
-        file_reader::csv_stream,
-        model::database::{TSMetadata, TSStore},
+        drift::{self, DriftConfig, DriftReport},
+        file_reader::{self, ChecksumReader, FileLocation, ReadingsFormat},
+        issues::{self, Issue},
+        model::{
+            check_amount_bounds,
+            csv::{CSVRecord, CsvSchema},
+            database::{
+                AuditAction, AuditEntry, ChainEvent, IngestionClockDrift, IngestionCorrection,
+                IngestionIssue, IngestionStatus, LegalHold, TSMetadata, TSStore,
+            },
+        },
+        register::{self, ReadingKind, RegisterConfig},
         renewable_schema,
+        unit_check::{self, UnitMismatch},
     };
 
-    fn get_seed_file(path_str: &str) -> Result<File, PgError> {
-        let seed_filepath = Path::new(path_str);
-        if !seed_filepath.is_file() {
-            error!("SEED_FILE path does not exist");
-            return Err(PgError::SeedFileValidationError);
-        }
-        let Some(extension) = seed_filepath.extension() else {
-            error!("SEED_FILE extension not known");
+    /// Readings written per `INSERT`, their five columns each keeping the statement under
+    /// Postgres' limit of 65,535 bind parameters
+    const READINGS_PER_INSERT: usize = 10_000;
+
+    /// Opens `SEED_FILE`, a local path or an object store URL, decompressing `.gz` and
+    /// `.zst` files as they are read
+    async fn open_seed_file(
+        location: &FileLocation,
+    ) -> Result<(ReadingsFormat, Box<dyn Read + Send>), PgError> {
+        let Some((format, compression)) = location.kind() else {
+            error!("SEED_FILE should be a .csv, .json, .ndjson or .jsonl file");
             return Err(PgError::SeedFileValidationError);
         };
-        if extension != "csv" {
-            error!("SEED_FILE should be a .csv file");
-            return Err(PgError::SeedFileValidationError);
-        }
-
-        File::open(seed_filepath).map_err(|_| PgError::SeedFileValidationError)
+        let reader: Box<dyn Read + Send> = match location {
+            FileLocation::Local(path) => {
+                if !path.is_file() {
+                    error!("SEED_FILE path does not exist");
+                    return Err(PgError::SeedFileValidationError);
+                }
+                let file = File::open(path).map_err(|_| PgError::SeedFileValidationError)?;
+                Box::new(file)
+            }
+            FileLocation::Remote(url) => {
+                info!(
+                    source = location.source(),
+                    "Streaming SEED_FILE from object store"
+                );
+                let reader = file_reader::open_remote(url).await.map_err(|e| {
+                    error!("Unable to open SEED_FILE: {e}");
+                    PgError::SeedFileValidationError
+                })?;
+                Box::new(reader)
+            }
+        };
+        let reader = compression.decoder(reader).map_err(|e| {
+            error!("Unable to decompress SEED_FILE: {e}");
+            PgError::SeedFileValidationError
+        })?;
+        Ok((format, reader))
     }
 
-    pub async fn seed_database(pg_pool: &deadpool_diesel::postgres::Pool) -> Result<(), PgError> {
-        info!("Seeding database");
-        let env_var: String = env::var("SEED_FILE").map_err(|_| PgError::SeedFilePath)?;
-        let seed_file = get_seed_file(&env_var)?;
+    /// Stores `readings` as a new ingestion of `source` in `series_id` ranked `priority`,
+    /// returning its id and the number of rows written, or `None` when the metadata insert
+    /// conflicts. `rejected` rows of the source were skipped as invalid.
+    pub fn insert_ingestion(
+        source: String,
+        series_id: Option<i64>,
+        priority: i32,
+        readings: Vec<CSVRecord>,
+        rejected: usize,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<(i64, usize)>> {
+        store_ingestion(
+            TSMetadata {
+                series_id,
+                priority,
+                error_count: i64::try_from(rejected).unwrap_or(i64::MAX),
+                ..TSMetadata::new(source)
+            },
+            readings,
+            |_, _| Ok(()),
+            conn,
+        )
+    }
 
-        let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+    /// Records `metadata` as a pending ingestion, then stores `readings` and whatever
+    /// `finish` writes in one transaction, completing the ingestion's summary. When that
+    /// transaction fails the ingestion is kept, marked failed.
+    fn store_ingestion(
+        metadata: TSMetadata,
+        readings: Vec<CSVRecord>,
+        finish: impl FnOnce(i64, &mut PgConnection) -> QueryResult<()>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<(i64, usize)>> {
+        use renewable_schema::ts_metadata::dsl;
 
-        conn.interact(|conn| {
-            conn.transaction::<_, diesel::result::Error, _>(|conn| {
-                // Insert Metadata about the seed file
-                let Ok(Some(ingestion_id)) =
-                    diesel::insert_into(renewable_schema::ts_metadata::table)
-                        .values(TSMetadata::new(env_var))
-                        .returning(renewable_schema::ts_metadata::ingestion_id)
-                        .on_conflict_do_nothing()
-                        .get_result::<i64>(conn)
-                        .optional_empty_changeset()
-                else {
-                    info!("Data has already been ingested");
-                    return Ok(());
-                };
+        let started = Instant::now();
+        // Insert Metadata about the source, nothing being returned when the file's checksum
+        // is already held
+        let Some(ingestion_id) = diesel::insert_into(dsl::ts_metadata)
+            .values(metadata)
+            .returning(dsl::ingestion_id)
+            .on_conflict_do_nothing()
+            .get_result::<i64>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let elapsed_ms = || i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX);
 
-                // Read in the data from the .csv file
-                let buffer = BufReader::new(seed_file);
-                let records: Vec<TSStore> = csv_stream(buffer)
-                    .flatten()
-                    .map(|r| (ingestion_id, r).into())
-                    .collect();
+        let min_datetime = readings.iter().map(|r| r.datetime).min();
+        let max_datetime = readings.iter().map(|r| r.datetime).max();
+        let stored = conn.transaction(|conn| {
+            let records: Vec<TSStore> = readings
+                .into_iter()
+                .map(|r| (ingestion_id, r).into())
+                .collect();
 
-                // Insert Time Series data
-                let inserted_rows = diesel::insert_into(renewable_schema::ts_store::table)
-                    .values(records)
+            // Insert Time Series data
+            let mut inserted_rows = 0;
+            for chunk in records.chunks(READINGS_PER_INSERT) {
+                inserted_rows += diesel::insert_into(renewable_schema::ts_store::table)
+                    .values(chunk)
                     .on_conflict_do_nothing()
                     .execute(conn)
-                    .optional_empty_changeset()?;
+                    .optional_empty_changeset()?
+                    .unwrap_or_default();
+            }
+            finish(ingestion_id, conn)?;
+            diesel::update(dsl::ts_metadata.find(ingestion_id))
+                .set((
+                    dsl::row_count.eq(i64::try_from(inserted_rows).unwrap_or(i64::MAX)),
+                    dsl::min_datetime.eq(min_datetime),
+                    dsl::max_datetime.eq(max_datetime),
+                    dsl::duration_ms.eq(elapsed_ms()),
+                    dsl::status.eq(IngestionStatus::Complete),
+                ))
+                .execute(conn)?;
+            super::chain::append(ChainEvent::Ingested, ingestion_id, conn)?;
+            Ok(inserted_rows)
+        });
+
+        match stored {
+            Ok(inserted_rows) => Ok(Some((ingestion_id, inserted_rows))),
+            Err(e) => {
+                let failed = diesel::update(dsl::ts_metadata.find(ingestion_id))
+                    .set((
+                        dsl::duration_ms.eq(elapsed_ms()),
+                        dsl::status.eq(IngestionStatus::Failed),
+                    ))
+                    .execute(conn);
+                if let Err(e) = failed {
+                    error!(ingestion_id, "Unable to mark ingestion failed: {e}");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Merges `readings` into the complete ingestion `ingestion_id`, adding those at
+    /// timestamps it does not hold and, with `overwrite`, replacing the amounts of those
+    /// it does, the last of an upload's readings at one timestamp winning. Replaced
+    /// versions are kept in `ts_store_revisions`, valid until the correction. The
+    /// ingestion's summary is brought up to date, any compressed blocks of it dropped to
+    /// be packed again, and the change appended to the hash chain.
+    ///
+    /// An ingestion under legal hold is left untouched, the refusal audited and the hold
+    /// returned as the error.
+    pub fn correct_ingestion(
+        ingestion_id: i64,
+        overwrite: bool,
+        readings: Vec<CSVRecord>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Result<IngestionCorrection, LegalHold>> {
+        use renewable_schema::{ts_compressed_blocks, ts_metadata::dsl, ts_store};
+
+        let received = readings.len();
+        conn.transaction(|conn| {
+            // Corrections of one ingestion are applied one at a time, and a hold placed
+            // meanwhile waits on the lock as its foreign key takes a share of the row
+            dsl::ts_metadata
+                .find(ingestion_id)
+                .select(dsl::ingestion_id)
+                .for_update()
+                .get_result::<i64>(conn)?;
+            if let Some(hold) = super::legal_holds::find_hold(ingestion_id, conn)? {
+                let refusal = AuditEntry::new(
+                    AuditAction::CorrectIngestion,
+                    Some(ingestion_id),
+                    false,
+                    format!("refused under legal hold: {}", hold.reason),
+                );
+                super::legal_holds::record_audit(refusal, conn)?;
+                return Ok(Err(hold));
+            }
+
+            let readings: BTreeMap<_, _> = if overwrite {
+                readings.into_iter().map(|r| (r.datetime, r)).collect()
+            } else {
+                readings
+                    .into_iter()
+                    .rev()
+                    .map(|r| (r.datetime, r))
+                    .collect()
+            };
+            let datetimes: Vec<_> = readings.keys().copied().collect();
+            let held = ts_store::table
+                .filter(ts_store::ingestion_id.eq(ingestion_id))
+                .filter(ts_store::datetime.eq_any(&datetimes))
+                .count()
+                .get_result::<i64>(conn)?;
+            let held = usize::try_from(held).unwrap_or_default();
+            let recorded_at = Utc::now();
+            if overwrite {
+                archive_revisions(ingestion_id, &datetimes, recorded_at, conn)?;
+            }
+            let records: Vec<TSStore> = readings
+                .into_values()
+                .map(|r| TSStore {
+                    recorded_at,
+                    ..(ingestion_id, r).into()
+                })
+                .collect();
+            let mut written = 0;
+            for chunk in records.chunks(READINGS_PER_INSERT) {
+                let insert = diesel::insert_into(ts_store::table)
+                    .values(chunk)
+                    .on_conflict((ts_store::ingestion_id, ts_store::datetime));
+                written += if overwrite {
+                    insert
+                        .do_update()
+                        .set((
+                            ts_store::amount.eq(excluded(ts_store::amount)),
+                            ts_store::recorded_at.eq(excluded(ts_store::recorded_at)),
+                            ts_store::extra.eq(excluded(ts_store::extra)),
+                        ))
+                        .execute(conn)?
+                } else {
+                    insert.do_nothing().execute(conn)?
+                };
+            }
+            let updated = if overwrite { held } else { 0 };
+            let inserted = written - updated;
+
+            let (row_count, min_datetime, max_datetime) = ts_store::table
+                .filter(ts_store::ingestion_id.eq(ingestion_id))
+                .select((
+                    count_star(),
+                    min(ts_store::datetime),
+                    max(ts_store::datetime),
+                ))
+                .get_result::<(i64, Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(conn)?;
+            diesel::update(dsl::ts_metadata.find(ingestion_id))
+                .set((
+                    dsl::row_count.eq(row_count),
+                    dsl::min_datetime.eq(min_datetime),
+                    dsl::max_datetime.eq(max_datetime),
+                ))
+                .execute(conn)?;
+            diesel::delete(
+                ts_compressed_blocks::table
+                    .filter(ts_compressed_blocks::ingestion_id.eq(ingestion_id)),
+            )
+            .execute(conn)?;
+            super::chain::append(ChainEvent::Corrected, ingestion_id, conn)?;
+
+            Ok(Ok(IngestionCorrection {
+                ingestion_id,
+                inserted,
+                updated,
+                skipped: received - inserted - updated,
+            }))
+        })
+    }
+
+    /// Copies the readings of `ingestion_id` at `datetimes` into `ts_store_revisions` as
+    /// the revision after the last one kept of each, valid from when they were recorded
+    /// until `valid_to`
+    fn archive_revisions(
+        ingestion_id: i64,
+        datetimes: &[DateTime<Utc>],
+        valid_to: DateTime<Utc>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use diesel::sql_types::{Array, BigInt, Timestamptz};
+
+        diesel::sql_query(
+            "INSERT INTO renewable.ts_store_revisions \
+                 (ingestion_id, datetime, revision, amount, extra, valid_from, valid_to) \
+             SELECT s.ingestion_id, s.datetime, \
+                    COALESCE(( \
+                        SELECT MAX(r.revision) FROM renewable.ts_store_revisions r \
+                        WHERE r.ingestion_id = s.ingestion_id AND r.datetime = s.datetime \
+                    ), 0) + 1, \
+                    s.amount, s.extra, LEAST(s.recorded_at, $3), $3 \
+             FROM renewable.ts_store s \
+             WHERE s.ingestion_id = $1 AND s.datetime = ANY($2)",
+        )
+        .bind::<BigInt, _>(ingestion_id)
+        .bind::<Array<Timestamptz>, _>(datetimes)
+        .bind::<Timestamptz, _>(valid_to)
+        .execute(conn)
+    }
+
+    /// Median over the days of the absolute mean reading of each day the ingestions of
+    /// `series_id` hold, from `ts_daily_summary`, `None` before the series holds readings.
+    /// Ingestions flagged with a unit mismatch are left out.
+    pub fn series_median_daily_reading(
+        series_id: i64,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<f64>> {
+        #[derive(diesel::QueryableByName)]
+        struct Median {
+            #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+            median: Option<f64>,
+        }
+
+        diesel::sql_query(
+            "SELECT (percentile_cont(0.5) WITHIN GROUP \
+                        (ORDER BY ABS(s.total_amount / s.readings)))::FLOAT8 AS median \
+             FROM renewable.ts_daily_summary s \
+             JOIN renewable.ts_metadata m ON m.ingestion_id = s.ingestion_id \
+             WHERE m.series_id = $1 AND m.unit_mismatch IS NULL \
+               AND s.readings > 0 AND s.total_amount <> 0",
+        )
+        .bind::<diesel::sql_types::BigInt, _>(series_id)
+        .get_result::<Median>(conn)
+        .map(|row| row.median)
+    }
+
+    /// Checks `readings` against what `series_id` held before, see [`UnitMismatch`]
+    pub fn check_units(
+        series_id: i64,
+        readings: &[CSVRecord],
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<UnitMismatch>> {
+        let Some(ingestion) = unit_check::median_daily_reading(readings) else {
+            return Ok(None);
+        };
+        Ok(series_median_daily_reading(series_id, conn)?
+            .and_then(|history| UnitMismatch::between(ingestion, history)))
+    }
+
+    /// Flags a stored ingestion whose readings look to be in another unit than its series'
+    pub fn flag_unit_mismatch(
+        ingestion_id: i64,
+        mismatch: UnitMismatch,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        use renewable_schema::ts_metadata::dsl;
+
+        diesel::update(dsl::ts_metadata.find(ingestion_id))
+            .set(dsl::unit_mismatch.eq(mismatch.ratio))
+            .execute(conn)
+    }
+
+    /// Stores the clock drift found while ingesting
+    pub fn record_clock_drift(
+        drift: &IngestionClockDrift,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(renewable_schema::ingestion_clock_drift::table)
+            .values(drift)
+            .execute(conn)
+    }
+
+    /// Stores the problems found in an ingestion's file, in batches small enough for the
+    /// bind parameter limit
+    pub fn record_issues(
+        ingestion_id: i64,
+        issues: Vec<Issue>,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        let records: Vec<IngestionIssue> = issues
+            .into_iter()
+            .map(|issue| issue.into_record(ingestion_id))
+            .collect();
+        let mut recorded = 0;
+        for batch in records.chunks(10_000) {
+            recorded += diesel::insert_into(renewable_schema::ingestion_issues::table)
+                .values(batch)
+                .execute(conn)?;
+        }
+        Ok(recorded)
+    }
 
-                info!("Seeded database with {inserted_rows:?} records");
+    /// As [`insert_ingestion`], recording the clock drift and issues found in the readings
+    /// alongside them in the same transaction
+    pub fn insert_ingestion_with_drift(
+        source: String,
+        series_id: Option<i64>,
+        priority: i32,
+        prepared: PreparedReadings,
+        drift_config: &DriftConfig,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Option<(i64, usize)>> {
+        let PreparedReadings {
+            readings,
+            report,
+            rejected,
+            checksum,
+            issues,
+        } = prepared;
+        store_ingestion(
+            TSMetadata {
+                series_id,
+                priority,
+                error_count: i64::try_from(rejected).unwrap_or(i64::MAX),
+                checksum,
+                ..TSMetadata::new(source)
+            },
+            readings,
+            |ingestion_id, conn| {
+                record_clock_drift(&report.into_record(ingestion_id, drift_config), conn)?;
+                record_issues(ingestion_id, issues, conn)?;
                 Ok(())
-            })
+            },
+            conn,
+        )
+    }
+
+    pub async fn seed_database(
+        pg_pool: &deadpool_diesel::postgres::Pool,
+        config: &AppConfig,
+    ) -> Result<(), PgError> {
+        info!("Seeding database");
+        let env_var: String = env::var("SEED_FILE").map_err(|_| PgError::SeedFilePath)?;
+        let location = FileLocation::parse(&env_var);
+        let (format, seed_file) = open_seed_file(&location).await?;
+        let source = location.source();
+        let priority = config.source_priority(&source);
+        let csv_schema = CsvSchema::from(config);
+        let register_config = RegisterConfig::from_env().map_err(|e| {
+            error!("{e}");
+            PgError::SeedFileValidationError
+        })?;
+        let drift_config = DriftConfig::from_env(config.reading_interval()).map_err(|e| {
+            error!("{e}");
+            PgError::SeedFileValidationError
+        })?;
+
+        let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+
+        conn.interact(move |conn| {
+            // Read in the data from the seed file
+            let buffer = BufReader::new(seed_file);
+            let prepared =
+                prepare_readings(buffer, format, &csv_schema, &register_config, &drift_config);
+            match insert_ingestion_with_drift(
+                source,
+                None,
+                priority,
+                prepared,
+                &drift_config,
+                conn,
+            )? {
+                Some((_, inserted_rows)) => info!("Seeded database with {inserted_rows} records"),
+                None => info!("Data has already been ingested"),
+            }
+            Ok::<_, diesel::result::Error>(())
         })
         .await
         .map_err(PgError::InteractionError)?
@@ -130,168 +771,2925 @@ pub mod seed_database {
 
         Ok(())
     }
+
+    /// Readings decoded by [`prepare_readings`], ready to be stored
+    pub struct PreparedReadings {
+        pub readings: Vec<CSVRecord>,
+        pub report: DriftReport,
+        /// Rows skipped as invalid
+        pub rejected: usize,
+        /// Hex SHA-256 of the file's content, once decompressed, `None` when it could not
+        /// be read to the end
+        pub checksum: Option<String>,
+        /// Rows skipped and readings worth a second look, see [`issues::inspect`]
+        pub issues: Vec<Issue>,
+    }
+
+    /// Decodes a file of readings the way the seed file is: invalid rows are skipped,
+    /// register readings converted to intervals and clock drift measured, collecting the
+    /// issues found along the way
+    pub fn prepare_readings<R: Read>(
+        buffer: R,
+        format: ReadingsFormat,
+        csv_schema: &CsvSchema,
+        register_config: &RegisterConfig,
+        drift_config: &DriftConfig,
+    ) -> PreparedReadings {
+        let mut buffer = ChecksumReader::new(buffer);
+        let (readings, rejected) = file_reader::readings(&mut buffer, format, csv_schema);
+        let checksum = buffer
+            .finish()
+            .inspect_err(|e| warn!("Unable to checksum the file: {e}"))
+            .ok();
+        for reason in &rejected {
+            warn!("Skipping row: {reason}");
+        }
+        if !rejected.is_empty() {
+            warn!("Skipped {} invalid rows", rejected.len());
+        }
+        let mut found: Vec<Issue> = rejected.iter().map(Issue::parse_failure).collect();
+        let readings = match register_config.reading_kind {
+            ReadingKind::Interval => readings,
+            ReadingKind::Cumulative => {
+                let (deltas, events) =
+                    register::to_interval(readings, register_config.rollover_at.as_ref());
+                info!(
+                    "Converted register readings with {} discontinuities",
+                    events.len()
+                );
+                deltas
+                    .into_iter()
+                    .filter(|delta| match check_amount_bounds(&delta.amount) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!(datetime = %delta.datetime, "Skipping delta: {e}");
+                            found.push(Issue::rejected_delta(delta, e));
+                            false
+                        }
+                    })
+                    .collect()
+            }
+        };
+
+        found.extend(issues::inspect(&readings, drift_config.interval));
+        let (readings, report) = drift::analyse(readings, drift_config);
+        if report.drifted > 0 {
+            warn!(
+                drifted = report.drifted,
+                max_offset_secs = report.max_offset_secs,
+                collisions = report.collisions,
+                "Readings drift off the {} minute grid",
+                drift_config.interval.num_minutes()
+            );
+        }
+        PreparedReadings {
+            readings,
+            report,
+            rejected: rejected.len(),
+            checksum,
+            issues: found,
+        }
+    }
 }
 
+/// Reading ranges are half open, `from_date` inclusive and `to_date` exclusive, see
+/// [`RangeEnd`](crate::model::api_request::RangeEnd)
 pub mod query {
 
     use crate::{
+        columnar::IngestionRanks,
         model::{
-            api_request::Aggregation, api_response::AggregationQueryRecord, database::QueryHistory,
+            api_request::{Aggregation, HistoryFilter, TotalFilter},
+            api_response::{
+                AggregationQueryRecord, BucketSource, DeletedIngestion, FuelTypeRecord,
+                IngestionLineage, IngestionSummary,
+            },
+            database::{
+                BucketEnergy, ChainEvent, IngestionClockDrift, IngestionIssue, IngestionStatus,
+                QueryHistory, RangeBucket, TSStore,
+            },
         },
         renewable_schema::{
-            query_history::dsl::{executed_at, query_history},
-            ts_store,
+            ingestion_clock_drift, ingestion_issues, meters,
+            query_history::dsl::{executed_at, id as history_id, query_history},
+            series, ts_daily_summary, ts_metadata, ts_store, ts_store_revisions,
         },
     };
+    use bigdecimal::BigDecimal;
     use chrono::Utc;
+    use chrono_tz::Tz;
     use diesel::Connection as _;
-    use diesel::dsl::sql;
-    use diesel::sql_types::{Nullable, Numeric};
+    use diesel::dsl::{GroupBy, IntoBoxed, Select, count, exists, max, sql, sum};
+    use diesel::expression::{
+        AppearsOnTable, Expression, IsContainedInGroupBy, SelectableExpression, ValidGrouping,
+        is_aggregate, is_contained_in_group_by,
+    };
+    use diesel::helper_types;
+    use diesel::pg::{Pg, PgRowByRowLoadingMode};
+    use diesel::query_builder::{AstPass, BoxedSqlQuery, QueryFragment, QueryId, SqlQuery};
+    use diesel::sql_types::{Array, BigInt, Bool, Nullable, Numeric, SingleValue, SqlType};
     use diesel::{
-        ExpressionMethods as _, QueryDsl as _, RunQueryDsl as _, SelectableHelper as _,
-        define_sql_function,
-        sql_types::{Text, Timestamptz},
+        AggregateExpressionMethods as _, BoolExpressionMethods as _, ExpressionMethods as _,
+        OptionalExtension as _, QueryDsl as _, QueryResult, RunQueryDsl as _,
+        SelectableHelper as _, define_sql_function,
+        sql_types::{Text, Timestamp, Timestamptz},
     };
 
-    pub(crate) const DEFAULT_HISTORY_LIMIT: i64 = 10;
+    pub const DEFAULT_HISTORY_LIMIT: i64 = 10;
 
     define_sql_function! {
         #[sql_name = "DATE_TRUNC"]
-        fn date_trunc(period: Text, ts: Timestamptz) -> Timestamptz;
+        fn date_trunc<T: SqlType + SingleValue>(period: Text, ts: T) -> T;
     }
 
-    pub fn query_request_history(
-        conn: &mut diesel::PgConnection,
-    ) -> Result<Vec<QueryHistory>, diesel::result::Error> {
-        query_history
-            .select(QueryHistory::as_select())
-            .order_by(executed_at.desc())
-            .limit(DEFAULT_HISTORY_LIMIT)
-            .get_results::<QueryHistory>(conn)
+    define_sql_function! {
+        /// `ts AT TIME ZONE zone`, the wall clock time at instant `ts` in `zone`
+        #[sql_name = "TIMEZONE"]
+        fn local_time(zone: Text, ts: Timestamptz) -> Timestamp;
     }
 
-    pub fn aggregate_ts_query(
-        aggregation_kind: Aggregation,
-        from_date: Option<chrono::DateTime<Utc>>,
-        to_date: Option<chrono::DateTime<Utc>>,
-        conn: &mut diesel::PgConnection,
-    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
-        conn.transaction(|conn| {
-            // Persist the query in history
-            let history_entry = QueryHistory::new(from_date, to_date, aggregation_kind);
-            diesel::insert_into(query_history)
-                .values(&history_entry)
-                .execute(conn)?;
+    define_sql_function! {
+        /// `ts AT TIME ZONE zone`, the instant the wall clock in `zone` shows `ts`
+        #[sql_name = "TIMEZONE"]
+        fn zoned_time(zone: Text, ts: Timestamp) -> Timestamptz;
+    }
 
-            // Construct and execute the aggregation query
-            let period = <&str>::from(aggregation_kind);
-            let datetime_expr = sql::<Timestamptz>(&format!("DATE_TRUNC('{period}', datetime)"));
-            let sum_expr = sql::<Nullable<Numeric>>("SUM(amount)");
-            let group_expr = sql::<Timestamptz>(&format!("DATE_TRUNC('{period}', datetime)"));
+    /// Start of a reading's bucket, see [`bucket_start`]
+    pub(super) type BucketStart = zoned_time<
+        &'static str,
+        date_trunc<Timestamp, &'static str, local_time<&'static str, ts_store::datetime>>,
+    >;
 
-            let mut query = ts_store::table
-                .select((datetime_expr, sum_expr))
-                .group_by(group_expr)
-                .into_boxed();
+    /// Start of the bucket holding each reading, truncated on the local calendar of
+    /// `timezone` with the period and zone bound as parameters, so every query shares one
+    /// prepared statement whatever the aggregation
+    fn bucket_start(aggregation_kind: Aggregation, timezone: Tz) -> BucketStart {
+        zoned_time(
+            timezone.name(),
+            date_trunc::<Timestamp, _, _>(
+                <&str>::from(aggregation_kind),
+                local_time(timezone.name(), ts_store::datetime),
+            ),
+        )
+    }
 
-            if let Some(from) = from_date {
-                query = query.filter(ts_store::datetime.ge(from));
-            }
-            if let Some(to) = to_date {
-                query = query.filter(ts_store::datetime.le(to));
-            }
+    /// `GROUP BY 1` and `ORDER BY 1`. Postgres cannot match a [`bucket_start`] repeated in
+    /// the grouping with the one selected as their parameters differ, so buckets are grouped
+    /// by position. The bucket depends on `datetime` alone, which is what diesel is told.
+    #[derive(Debug, Clone, Copy, QueryId)]
+    pub(super) struct FirstColumn;
 
-            query.load(conn)
-        })
+    impl Expression for FirstColumn {
+        type SqlType = Timestamptz;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::env;
+    impl QueryFragment<Pg> for FirstColumn {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            out.push_sql("1");
+            Ok(())
+        }
+    }
 
-    use bigdecimal::BigDecimal;
-    use chrono::{DateTime, Duration, TimeZone, Utc};
-    use diesel::{Connection, PgConnection, RunQueryDsl};
-    use serial_test::serial;
-    use test_case::test_case;
+    impl<GB> ValidGrouping<GB> for FirstColumn {
+        type IsAggregate = is_aggregate::Never;
+    }
 
-    use crate::{
-        db::query::{DEFAULT_HISTORY_LIMIT, aggregate_ts_query, query_request_history},
-        model::{api_request::Aggregation, database::TSStore},
-        renewable_schema::{query_history, ts_metadata, ts_store},
-    };
+    impl<QS> SelectableExpression<QS> for FirstColumn {}
 
-    fn get_test_connection() -> PgConnection {
-        dotenvy::dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        PgConnection::establish(&database_url).expect("Failed to connect to database")
-    }
+    impl<QS> AppearsOnTable<QS> for FirstColumn {}
 
-    fn cleanup_tables(conn: &mut PgConnection) {
-        diesel::delete(query_history::table).execute(conn).unwrap();
-        diesel::delete(ts_store::table).execute(conn).unwrap();
-        diesel::delete(ts_metadata::table).execute(conn).unwrap();
+    impl IsContainedInGroupBy<ts_store::datetime> for FirstColumn {
+        type Output = is_contained_in_group_by::Yes;
     }
 
-    fn seed_ts_metadata(conn: &mut PgConnection) -> i64 {
-        use crate::model::database::TSMetadata;
+    /// Up to `limit` history entries matching `filter`, newest first, continuing after the
+    /// `(executed_at, id)` of the last entry of the previous page when given
+    pub fn query_request_history(
+        limit: i64,
+        after: Option<(chrono::DateTime<Utc>, i64)>,
+        filter: &HistoryFilter,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<QueryHistory>, diesel::result::Error> {
+        use crate::renewable_schema::query_history::dsl::{aggregation, status};
 
-        diesel::insert_into(ts_metadata::table)
-            .values(TSMetadata::new("test_source".to_string()))
-            .returning(ts_metadata::ingestion_id)
-            .get_result::<i64>(conn)
-            .unwrap()
+        let mut query = query_history
+            .select(QueryHistory::as_select())
+            .order_by((executed_at.desc(), history_id.desc()))
+            .limit(limit)
+            .into_boxed();
+        if let Some(kind) = filter.aggregation {
+            query = query.filter(aggregation.eq(kind));
+        }
+        if let Some(from) = filter.executed_from {
+            query = query.filter(executed_at.ge(from));
+        }
+        if let Some(to) = filter.executed_to {
+            query = query.filter(executed_at.lt(to));
+        }
+        if let Some(outcome) = filter.status {
+            query = query.filter(status.eq(outcome));
+        }
+        if let Some((after_executed_at, after_id)) = after {
+            query = query.filter(
+                executed_at.lt(after_executed_at).or(executed_at
+                    .eq(after_executed_at)
+                    .and(history_id.lt(after_id))),
+            );
+        }
+        query.get_results::<QueryHistory>(conn)
     }
 
-    fn seed_ts_data(conn: &mut PgConnection, ingestion_id: i64) {
-        let base_date = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
-        let records: Vec<TSStore> = (0..48)
-            .map(|i| TSStore {
-                ingestion_id,
-                datetime: base_date + Duration::hours(i),
-                amount: BigDecimal::from(100 * (i + 1)),
-            })
+    /// Writes a batch of history entries in a single statement
+    pub fn insert_query_history(
+        entries: &[QueryHistory],
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::insert_into(query_history)
+            .values(entries)
+            .execute(conn)
+    }
+
+    /// Loads readings from the last `days` days before the latest stored reading,
+    /// summed per timestamp and ordered, returning the window start alongside them
+    #[allow(clippy::type_complexity)]
+    pub fn load_recent_window(
+        days: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<
+        Option<(
+            chrono::DateTime<Utc>,
+            Vec<(chrono::DateTime<Utc>, BigDecimal)>,
+        )>,
+        diesel::result::Error,
+    > {
+        let Some(latest) = ts_store::table
+            .select(max(ts_store::datetime))
+            .first::<Option<chrono::DateTime<Utc>>>(conn)?
+        else {
+            return Ok(None);
+        };
+
+        let start = latest - chrono::TimeDelta::days(days);
+        let rows = ts_store::table
+            .filter(ts_store::datetime.ge(start))
+            .filter(sql::<Bool>(&format!(
+                "NOT ({RANKED} AND EXISTS ({}))",
+                outranking_readings("renewable.ts_store", "renewable.ts_store")
+            )))
+            .group_by(ts_store::datetime)
+            .select((ts_store::datetime, sum(ts_store::amount)))
+            .order_by(ts_store::datetime)
+            .load::<(chrono::DateTime<Utc>, Option<BigDecimal>)>(conn)?
+            .into_iter()
+            .filter_map(|(datetime, amount)| amount.map(|amount| (datetime, amount)))
             .collect();
 
-        diesel::insert_into(ts_store::table)
-            .values(&records)
-            .execute(conn)
-            .expect("Failed to seed ts_store");
+        Ok(Some((start, rows)))
     }
 
-    fn test_from_date() -> DateTime<Utc> {
-        Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap()
+    /// Every ingestion with the summary recorded when it was stored, so listing them reads
+    /// no readings
+    pub fn query_ingestions(
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<IngestionSummary>, diesel::result::Error> {
+        ts_metadata::table
+            .select((
+                ts_metadata::ingestion_id,
+                ts_metadata::source,
+                ts_metadata::ingestion_datetime,
+                ts_metadata::row_count,
+                ts_metadata::min_datetime,
+                ts_metadata::max_datetime,
+                ts_metadata::error_count,
+                ts_metadata::duration_ms,
+                ts_metadata::status,
+                ts_metadata::unit_mismatch,
+                ts_metadata::checksum,
+            ))
+            .order_by(ts_metadata::ingestion_id)
+            .load(conn)
     }
 
-    fn test_to_date() -> DateTime<Utc> {
-        Utc.with_ymd_and_hms(2024, 1, 16, 12, 0, 0).unwrap()
+    /// Whether an ingestion of `source` was recorded that did not fail, failed ones may be
+    /// retried
+    pub fn source_ingested(
+        source: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<bool, diesel::result::Error> {
+        diesel::select(diesel::dsl::exists(
+            ts_metadata::table
+                .filter(ts_metadata::source.eq(source))
+                .filter(ts_metadata::status.ne(IngestionStatus::Failed)),
+        ))
+        .get_result(conn)
     }
 
-    #[test]
-    #[serial]
-    fn test_query_request_history_respects_limit_and_ordering() {
-        let mut conn = get_test_connection();
-        cleanup_tables(&mut conn);
+    /// Id and series of the ingestion of `source` that did not fail, `None` when there is
+    /// none
+    pub fn find_source_ingestion(
+        source: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<(i64, Option<i64>)>> {
+        ts_metadata::table
+            .filter(ts_metadata::source.eq(source))
+            .filter(ts_metadata::status.ne(IngestionStatus::Failed))
+            .select((ts_metadata::ingestion_id, ts_metadata::series_id))
+            .first(conn)
+            .optional()
+    }
 
-        let ingestion_id = seed_ts_metadata(&mut conn);
-        seed_ts_data(&mut conn, ingestion_id);
+    /// Earliest and latest reading held, read from the daily summaries so a request can
+    /// be sized up cheaply before it runs, `None` when nothing is held
+    pub fn reading_extent(
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<(chrono::DateTime<Utc>, chrono::DateTime<Utc>)>> {
+        ts_daily_summary::table
+            .select((
+                diesel::dsl::min(ts_daily_summary::first_datetime),
+                max(ts_daily_summary::last_datetime),
+            ))
+            .first::<(Option<chrono::DateTime<Utc>>, Option<chrono::DateTime<Utc>>)>(conn)
+            .map(|(first, last)| first.zip(last))
+    }
 
-        for _ in 0..15 {
-            aggregate_ts_query(Aggregation::Hourly, None, None, &mut conn).unwrap();
-        }
+    pub fn query_clock_drift(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<IngestionClockDrift>, diesel::result::Error> {
+        ingestion_clock_drift::table
+            .find(ingestion_id)
+            .select(IngestionClockDrift::as_select())
+            .first(conn)
+            .optional()
+    }
 
-        let result = query_request_history(&mut conn);
-        assert!(result.is_ok());
-        let history = result.unwrap();
-        assert_eq!(history.len(), DEFAULT_HISTORY_LIMIT as usize);
+    /// Clock drift reports of several ingestions in one round trip, ingestions without a
+    /// report are absent
+    pub fn query_clock_drifts(
+        ingestion_ids: &[i64],
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<IngestionClockDrift>, diesel::result::Error> {
+        ingestion_clock_drift::table
+            .filter(ingestion_clock_drift::ingestion_id.eq_any(ingestion_ids))
+            .select(IngestionClockDrift::as_select())
+            .load(conn)
+    }
 
-        for i in 0..history.len() - 1 {
-            assert!(history[i].executed_at >= history[i + 1].executed_at);
+    /// Issues found in an ingestion's file in the order they were recorded, `None` when
+    /// the ingestion does not exist
+    pub fn query_ingestion_issues(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<Vec<IngestionIssue>>, diesel::result::Error> {
+        let exists = diesel::select(diesel::dsl::exists(ts_metadata::table.find(ingestion_id)))
+            .get_result::<bool>(conn)?;
+        if !exists {
+            return Ok(None);
         }
+        ingestion_issues::table
+            .filter(ingestion_issues::ingestion_id.eq(ingestion_id))
+            .order(ingestion_issues::id)
+            .select(IngestionIssue::as_select())
+            .load(conn)
+            .map(Some)
     }
 
-    #[test_case(Aggregation::Hourly, None, None)]
+    /// Removes an ingestion and all of its readings, returning `None` when it does not exist
+    pub fn delete_ingestion(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<DeletedIngestion>, diesel::result::Error> {
+        conn.transaction(|conn| {
+            let deleted_readings =
+                diesel::delete(ts_store::table.filter(ts_store::ingestion_id.eq(ingestion_id)))
+                    .execute(conn)?;
+            let deleted_metadata =
+                diesel::delete(ts_metadata::table.find(ingestion_id)).execute(conn)?;
+            if deleted_metadata > 0 {
+                super::chain::append(ChainEvent::Deleted, ingestion_id, conn)?;
+            }
+
+            Ok((deleted_metadata > 0).then_some(DeletedIngestion {
+                ingestion_id,
+                deleted_metadata,
+                deleted_readings,
+            }))
+        })
+    }
+
+    /// Resolves the ingestions with at least one reading inside the range, probing
+    /// the (ingestion_id, datetime) index once per ingestion
+    pub fn query_lineage(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<IngestionLineage>, diesel::result::Error> {
+        let readings = recorded_readings(3);
+        diesel::sql_query(format!(
+            "SELECT m.ingestion_id, m.source, m.ingestion_datetime \
+             FROM renewable.ts_metadata m \
+             WHERE ($4::BIGINT IS NULL OR m.series_id = $4) \
+             AND EXISTS ( \
+                 SELECT 1 FROM {readings} s \
+                 WHERE s.ingestion_id = m.ingestion_id \
+                 AND ($1 IS NULL OR s.datetime >= $1) \
+                 AND ($2 IS NULL OR s.datetime < $2) \
+             ) \
+             ORDER BY m.ingestion_id"
+        ))
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Nullable<Timestamptz>, _>(as_recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .load(conn)
+    }
+
+    /// Ingestions stored under `series_id`
+    fn series_ingestions(
+        series_id: i64,
+    ) -> diesel::dsl::Select<
+        diesel::dsl::Filter<ts_metadata::table, diesel::dsl::Eq<ts_metadata::series_id, i64>>,
+        ts_metadata::ingestion_id,
+    > {
+        ts_metadata::table
+            .filter(ts_metadata::series_id.eq(series_id))
+            .select(ts_metadata::ingestion_id)
+    }
+
+    /// Monthly totals of the current readings of the series of meter `meter_code`, for
+    /// internal use
+    pub fn monthly_actuals(
+        meter_code: &str,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
+        let meter_ingestions = ts_metadata::table
+            .inner_join(series::table.inner_join(meters::table))
+            .filter(meters::meter_code.eq(meter_code))
+            .select(ts_metadata::ingestion_id);
+        aggregation_query(
+            Aggregation::Monthly,
+            from_date,
+            to_date,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+        )
+        .filter(ts_store::ingestion_id.eq_any(meter_ingestions))
+        .load(conn)
+    }
+
+    pub fn aggregate_ts_query(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
+        aggregate_ts_query_having(
+            aggregation_kind,
+            from_date,
+            to_date,
+            as_recorded_by,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            conn,
+        )
+    }
+
+    /// As [`aggregate_ts_query`], limited to the readings of `series_id` when given,
+    /// dropping buckets whose total fails `having` with a SQL `HAVING` clause and with
+    /// buckets starting on the boundaries of `timezone`
+    #[allow(clippy::too_many_arguments)]
+    pub fn aggregate_ts_query_having(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
+        // Readings overwritten since were recorded by then at an earlier revision
+        if let Some(recorded_by) = as_recorded_by {
+            return recorded_by_query(
+                aggregation_kind,
+                from_date,
+                to_date,
+                recorded_by,
+                series_id,
+                having,
+                timezone,
+            )
+            .load(conn);
+        }
+        // Daily summaries hold UTC days, so only UTC buckets of a day or more can use them,
+        // and they total every ingestion so cannot once superseded readings are left out
+        if aggregation_kind != Aggregation::Hourly
+            && timezone == Tz::UTC
+            && !has_ranked_ingestions(conn)?
+        {
+            return summary_aggregation(
+                aggregation_kind,
+                from_date,
+                to_date,
+                series_id,
+                having,
+                conn,
+            );
+        }
+        aggregation_query(
+            aggregation_kind,
+            from_date,
+            to_date,
+            series_id,
+            having,
+            timezone,
+        )
+        .load(conn)
+    }
+
+    /// As [`aggregate_ts_query_having`] in UTC, always scanning `ts_store` rather than
+    /// reading the summaries, the answer shadow queries check the summaries against
+    pub fn scan_ts_query(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
+        aggregation_query(
+            aggregation_kind,
+            from_date,
+            to_date,
+            series_id,
+            having,
+            Tz::UTC,
+        )
+        .load(conn)
+    }
+
+    /// Buckets in bucket order over the readings as they stood at `recorded_by`, each
+    /// taken at the revision valid then, see [`recorded_readings`]. Readings first
+    /// recorded later are left out.
+    fn recorded_by_query<'a>(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        recorded_by: chrono::DateTime<Utc>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+    ) -> BoxedSqlQuery<'a, Pg, SqlQuery> {
+        let readings = recorded_readings(3);
+        let bucket = bucket_clause("v.datetime", 5);
+        let having_clause = having_clause("SUM(v.amount)", 7);
+        let [gt, ge, lt, le] = having.bounds();
+        diesel::sql_query(format!(
+            "WITH v AS ( \
+                 SELECT ingestion_id, datetime, amount \
+                 FROM {readings} r \
+                 WHERE ($1 IS NULL OR datetime >= $1) \
+                 AND ($2 IS NULL OR datetime < $2) \
+             ) \
+             SELECT {bucket} AS datetime, SUM(v.amount) AS total_amount \
+             FROM v \
+             JOIN renewable.ts_metadata m ON m.ingestion_id = v.ingestion_id \
+             WHERE ($4::BIGINT IS NULL OR m.series_id = $4) \
+             AND NOT ({RANKED} AND EXISTS ( \
+                 SELECT 1 FROM renewable.ts_metadata pw \
+                 JOIN v po ON po.ingestion_id = pw.ingestion_id AND po.datetime = v.datetime \
+                 WHERE pw.series_id IS NOT DISTINCT FROM m.series_id \
+                 AND pw.priority > m.priority \
+             )) \
+             GROUP BY 1 \
+             {having_clause} \
+             ORDER BY 1"
+        ))
+        .into_boxed()
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Timestamptz, _>(recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Text, _>(timezone.name())
+        .bind::<Nullable<Numeric>, _>(gt)
+        .bind::<Nullable<Numeric>, _>(ge)
+        .bind::<Nullable<Numeric>, _>(lt)
+        .bind::<Nullable<Numeric>, _>(le)
+    }
+
+    /// Series and priority of every ingestion, exported along with raw readings
+    pub fn ingestion_ranks(
+        conn: &mut diesel::PgConnection,
+    ) -> Result<IngestionRanks, diesel::result::Error> {
+        ts_metadata::table
+            .select((
+                ts_metadata::ingestion_id,
+                ts_metadata::series_id,
+                ts_metadata::priority,
+            ))
+            .load::<(i64, Option<i64>, i32)>(conn)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(ingestion_id, series_id, priority)| {
+                        (ingestion_id, (series_id, priority))
+                    })
+                    .collect()
+            })
+    }
+
+    /// Stored readings of every ingestion within the range, ordered by timestamp
+    pub fn query_readings(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<TSStore>, diesel::result::Error> {
+        readings_query(from_date, to_date).load(conn)
+    }
+
+    /// As [`query_readings`], handing each reading to `each` in timestamp order as Postgres
+    /// returns it rather than collecting them, stopping early once `each` returns `false`.
+    /// Returns the number of readings handed over.
+    pub fn stream_readings(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+        each: impl FnMut(TSStore) -> bool,
+    ) -> Result<usize, diesel::result::Error> {
+        hand_over(
+            readings_query(from_date, to_date).load_iter::<TSStore, PgRowByRowLoadingMode>(conn)?,
+            each,
+        )
+    }
+
+    /// Readings of `ts_store` within the range, ordered by timestamp then ingestion
+    fn readings_query<'a>(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+    ) -> IntoBoxed<'a, ts_store::table, Pg> {
+        let mut query = ts_store::table.into_boxed();
+        if let Some(from) = from_date {
+            query = query.filter(ts_store::datetime.ge(from));
+        }
+        if let Some(to) = to_date {
+            query = query.filter(ts_store::datetime.lt(to));
+        }
+        query.order_by((ts_store::datetime, ts_store::ingestion_id))
+    }
+
+    /// Evaluates the aggregation as known at two record timestamps within one transaction
+    /// and returns the (baseline, compare) results
+    pub fn diff_ts_query(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        baseline_recorded_by: chrono::DateTime<Utc>,
+        compare_recorded_by: chrono::DateTime<Utc>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<(Vec<AggregationQueryRecord>, Vec<AggregationQueryRecord>), diesel::result::Error>
+    {
+        conn.transaction(|conn| {
+            let baseline = aggregate_ts_query(
+                aggregation_kind,
+                from_date,
+                to_date,
+                Some(baseline_recorded_by),
+                conn,
+            )?;
+            let compare = aggregate_ts_query(
+                aggregation_kind,
+                from_date,
+                to_date,
+                Some(compare_recorded_by),
+                conn,
+            )?;
+            Ok((baseline, compare))
+        })
+    }
+
+    /// Distinct reading timestamps per bucket, counting a timestamp held by several
+    /// ingestions once
+    pub fn bucket_point_counts(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<(chrono::DateTime<Utc>, i64)>, diesel::result::Error> {
+        let mut query = ts_store::table
+            .group_by(FirstColumn)
+            .select((
+                bucket_start(aggregation_kind, Tz::UTC),
+                count(ts_store::datetime).aggregate_distinct(),
+            ))
+            .into_boxed();
+
+        if let Some(from) = from_date {
+            query = query.filter(ts_store::datetime.ge(from));
+        }
+        if let Some(to) = to_date {
+            query = query.filter(ts_store::datetime.lt(to));
+        }
+        if let Some(recorded_by) = as_recorded_by {
+            // A reading overwritten since was first recorded when its first revision was
+            let revised = ts_store_revisions::table
+                .filter(ts_store_revisions::ingestion_id.eq(ts_store::ingestion_id))
+                .filter(ts_store_revisions::datetime.eq(ts_store::datetime))
+                .filter(ts_store_revisions::valid_from.le(recorded_by));
+            query = query.filter(ts_store::recorded_at.le(recorded_by).or(exists(revised)));
+        }
+        if let Some(series_id) = series_id {
+            query = query.filter(ts_store::ingestion_id.eq_any(series_ingestions(series_id)));
+        }
+
+        query.load(conn)
+    }
+
+    /// Bucket totals split by the fuel type of the series each reading's ingestion belongs
+    /// to, ordered by bucket then fuel type with unattributed readings last
+    pub fn fuel_type_breakdown(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<FuelTypeRecord>, diesel::result::Error> {
+        let readings = recorded_readings(3);
+        let bucket = bucket_clause("s.datetime", 5);
+        let superseded = superseded_clause("s", 3);
+        diesel::sql_query(format!(
+            "SELECT {bucket} AS datetime, \
+                    se.fuel_type, \
+                    SUM(s.amount) AS total_amount \
+             FROM {readings} s \
+             JOIN renewable.ts_metadata m ON m.ingestion_id = s.ingestion_id \
+             LEFT JOIN renewable.series se ON se.id = m.series_id \
+             WHERE ($1 IS NULL OR s.datetime >= $1) \
+             AND ($2 IS NULL OR s.datetime < $2) \
+             AND ($4::BIGINT IS NULL OR m.series_id = $4) \
+             AND NOT {superseded} \
+             GROUP BY 1, 2 \
+             ORDER BY 1, 2 NULLS LAST"
+        ))
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Nullable<Timestamptz>, _>(as_recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Text, _>(timezone.name())
+        .load(conn)
+    }
+
+    /// Sources each bucket's total was taken from once superseded readings are left out,
+    /// with the readings each contributed, ordered by bucket then source
+    pub fn bucket_sources(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<BucketSource>, diesel::result::Error> {
+        let readings = recorded_readings(3);
+        let bucket = bucket_clause("s.datetime", 5);
+        let superseded = superseded_clause("s", 3);
+        diesel::sql_query(format!(
+            "SELECT {bucket} AS datetime, \
+                    m.source, \
+                    m.priority, \
+                    COUNT(*) AS readings \
+             FROM {readings} s \
+             JOIN renewable.ts_metadata m ON m.ingestion_id = s.ingestion_id \
+             WHERE ($1 IS NULL OR s.datetime >= $1) \
+             AND ($2 IS NULL OR s.datetime < $2) \
+             AND ($4::BIGINT IS NULL OR m.series_id = $4) \
+             AND NOT {superseded} \
+             GROUP BY 1, 2, 3 \
+             ORDER BY 1, 2"
+        ))
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Nullable<Timestamptz>, _>(as_recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Text, _>(timezone.name())
+        .load(conn)
+    }
+
+    /// Energy per bucket along with its largest interval reading, from which average and
+    /// peak power are derived
+    #[allow(clippy::too_many_arguments)]
+    pub fn bucket_energy(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<BucketEnergy>, diesel::result::Error> {
+        let readings = recorded_readings(3);
+        let having_clause = having_clause("SUM(r.amount)", 5);
+        let bucket = bucket_clause("r.datetime", 9);
+        let superseded = superseded_clause("s", 3);
+        let [gt, ge, lt, le] = having.bounds();
+        diesel::sql_query(format!(
+            "SELECT {bucket} AS datetime, \
+                    SUM(r.amount) AS total_amount, \
+                    MAX(r.amount) AS peak_amount, \
+                    COUNT(*) AS readings \
+             FROM ( \
+                 SELECT s.datetime, SUM(s.amount) AS amount \
+                 FROM {readings} s \
+                 WHERE ($1 IS NULL OR s.datetime >= $1) \
+                 AND ($2 IS NULL OR s.datetime < $2) \
+                 AND ($4::BIGINT IS NULL OR s.ingestion_id IN ( \
+                     SELECT ingestion_id FROM renewable.ts_metadata WHERE series_id = $4)) \
+                 AND NOT {superseded} \
+                 GROUP BY s.datetime \
+             ) r \
+             GROUP BY 1 \
+             {having_clause} \
+             ORDER BY 1"
+        ))
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Nullable<Timestamptz>, _>(as_recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Nullable<Numeric>, _>(gt)
+        .bind::<Nullable<Numeric>, _>(ge)
+        .bind::<Nullable<Numeric>, _>(lt)
+        .bind::<Nullable<Numeric>, _>(le)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Text, _>(timezone.name())
+        .load(conn)
+    }
+
+    /// `(from_date, to_date)` with `to_date` exclusive, see
+    /// [`crate::model::api_request::TimeSeriesRange::half_open`]
+    pub type HalfOpenRange = (Option<chrono::DateTime<Utc>>, Option<chrono::DateTime<Utc>>);
+
+    /// Buckets of every `(from_date, to_date)` range in one statement, joining the readings
+    /// to the ranges holding them so a reading in overlapping ranges counts towards each.
+    /// Buckets follow the local calendar of `timezone`, as those of [`aggregate_ts_query`],
+    /// and are ordered by range, then time.
+    #[allow(clippy::too_many_arguments)]
+    pub fn multi_range_ts_query(
+        aggregation_kind: Aggregation,
+        ranges: &[HalfOpenRange],
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<RangeBucket>, diesel::result::Error> {
+        let (from_dates, to_dates): (Vec<_>, Vec<_>) = ranges.iter().copied().unzip();
+        let readings = recorded_readings(3);
+        let having_clause = having_clause("SUM(s.amount)", 5);
+        let bucket = bucket_clause("s.datetime", 9);
+        let superseded = superseded_clause("s", 3);
+        let [gt, ge, lt, le] = having.bounds();
+        diesel::sql_query(format!(
+            "SELECT r.range_index - 1 AS range_index, \
+                    {bucket} AS datetime, \
+                    SUM(s.amount) AS total_amount \
+             FROM {readings} s \
+             JOIN UNNEST($1::TIMESTAMPTZ[], $2::TIMESTAMPTZ[]) \
+                  WITH ORDINALITY AS r(from_date, to_date, range_index) \
+               ON (r.from_date IS NULL OR s.datetime >= r.from_date) \
+              AND (r.to_date IS NULL OR s.datetime < r.to_date) \
+             WHERE ($4::BIGINT IS NULL OR s.ingestion_id IN ( \
+                 SELECT ingestion_id FROM renewable.ts_metadata WHERE series_id = $4)) \
+             AND NOT {superseded} \
+             GROUP BY 1, 2 \
+             {having_clause} \
+             ORDER BY 1, 2"
+        ))
+        .bind::<Array<Nullable<Timestamptz>>, _>(from_dates)
+        .bind::<Array<Nullable<Timestamptz>>, _>(to_dates)
+        .bind::<Nullable<Timestamptz>, _>(as_recorded_by)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Nullable<Numeric>, _>(gt)
+        .bind::<Nullable<Numeric>, _>(ge)
+        .bind::<Nullable<Numeric>, _>(lt)
+        .bind::<Nullable<Numeric>, _>(le)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Text, _>(timezone.name())
+        .load(conn)
+    }
+
+    /// As [`aggregate_ts_query`], handing each bucket to `each` in bucket order as Postgres
+    /// returns it rather than collecting the result, stopping early once `each` returns
+    /// `false`. Returns the number of buckets handed over.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_ts_query(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        as_recorded_by: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+        conn: &mut diesel::PgConnection,
+        each: impl FnMut(AggregationQueryRecord) -> bool,
+    ) -> Result<usize, diesel::result::Error> {
+        match as_recorded_by {
+            Some(recorded_by) => hand_over(
+                recorded_by_query(
+                    aggregation_kind,
+                    from_date,
+                    to_date,
+                    recorded_by,
+                    series_id,
+                    having,
+                    timezone,
+                )
+                .load_iter::<AggregationQueryRecord, PgRowByRowLoadingMode>(conn)?,
+                each,
+            ),
+            None => hand_over(
+                aggregation_query(
+                    aggregation_kind,
+                    from_date,
+                    to_date,
+                    series_id,
+                    having,
+                    timezone,
+                )
+                .order_by(FirstColumn)
+                .load_iter::<AggregationQueryRecord, PgRowByRowLoadingMode>(conn)?,
+                each,
+            ),
+        }
+    }
+
+    /// Hands `rows` to `each` until it returns `false`, see [`stream_ts_query`]
+    pub(super) fn hand_over<T>(
+        rows: impl Iterator<Item = QueryResult<T>>,
+        mut each: impl FnMut(T) -> bool,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut sent = 0;
+        for record in rows {
+            if !each(record?) {
+                break;
+            }
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Raw SQL [`bucket_start`] of `column`, the period bound as parameter `$first` and
+    /// the timezone as the one after it
+    fn bucket_clause(column: &str, first: usize) -> String {
+        let zone = first + 1;
+        format!("DATE_TRUNC(${first}, {column} AT TIME ZONE ${zone}) AT TIME ZONE ${zone}")
+    }
+
+    /// `HAVING` clause of the raw SQL aggregations, applying [`TotalFilter::bounds`] bound
+    /// as four parameters from `$first` to the bucket total `total`
+    fn having_clause(total: &str, first: usize) -> String {
+        let [gt, ge, lt, le] = [first, first + 1, first + 2, first + 3];
+        format!(
+            "HAVING (${gt}::NUMERIC IS NULL OR {total} > ${gt}) \
+             AND (${ge}::NUMERIC IS NULL OR {total} >= ${ge}) \
+             AND (${lt}::NUMERIC IS NULL OR {total} < ${lt}) \
+             AND (${le}::NUMERIC IS NULL OR {total} <= ${le})"
+        )
+    }
+
+    /// Whether any ingestion is ranked, only then may a reading be superseded
+    const RANKED: &str = "EXISTS (SELECT 1 FROM renewable.ts_metadata WHERE priority <> 0)";
+
+    /// Subquery finding the readings of `readings` that supersede the reading `store`,
+    /// those at its timestamp from a higher priority ingestion of the same series, aliased
+    /// `po`
+    fn outranking_readings(store: &str, readings: &str) -> String {
+        format!(
+            "SELECT 1 FROM renewable.ts_metadata pm \
+             JOIN renewable.ts_metadata pw \
+               ON pw.series_id IS NOT DISTINCT FROM pm.series_id AND pw.priority > pm.priority \
+             JOIN {readings} po \
+               ON po.ingestion_id = pw.ingestion_id AND po.datetime = {store}.datetime \
+             WHERE pm.ingestion_id = {store}.ingestion_id"
+        )
+    }
+
+    /// Raw SQL condition holding for readings of `store` superseded by a reading recorded
+    /// by the nullable timestamp parameter `$recorded_by`, see [`outranking_readings`]
+    fn superseded_clause(store: &str, recorded_by: usize) -> String {
+        format!(
+            "({RANKED} AND EXISTS ({}))",
+            outranking_readings(store, &recorded_readings(recorded_by))
+        )
+    }
+
+    /// Raw SQL subquery of the readings recorded by the nullable timestamp parameter
+    /// `$recorded_by`, each at the revision current then: the latest when recorded by then,
+    /// otherwise the one kept in `ts_store_revisions` whose validity covers it. Every
+    /// reading of `ts_store` when the parameter is null.
+    fn recorded_readings(recorded_by: usize) -> String {
+        format!(
+            "(SELECT ingestion_id, datetime, amount \
+              FROM renewable.ts_store \
+              WHERE ${recorded_by}::TIMESTAMPTZ IS NULL OR recorded_at <= ${recorded_by} \
+              UNION ALL \
+              SELECT ingestion_id, datetime, amount \
+              FROM renewable.ts_store_revisions \
+              WHERE valid_from <= ${recorded_by} AND valid_to > ${recorded_by})"
+        )
+    }
+
+    /// Whether any ingestion is ranked above or below the default priority
+    pub fn has_ranked_ingestions(conn: &mut diesel::PgConnection) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            ts_metadata::table.filter(ts_metadata::priority.ne(0)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Buckets of a day or coarser summed from `ts_daily_summary` for the days wholly
+    /// inside the range, reading `ts_store` only for the partial days at either end.
+    /// Monthly and coarser buckets take the months wholly inside the range from
+    /// `ts_monthly_summary` instead, leaving the daily summaries to the partial months.
+    /// Summaries hold every reading ever stored, so as-of queries cannot use them.
+    /// The summary triggers run in the statement that writes `ts_store`, so summaries are
+    /// never behind the latest ingestion and need no read-through of a fresher tail.
+    fn summary_aggregation(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Vec<AggregationQueryRecord>, diesel::result::Error> {
+        let series_clause = "($3::BIGINT IS NULL OR ingestion_id IN ( \
+             SELECT ingestion_id FROM renewable.ts_metadata WHERE series_id = $3))";
+        let having_clause = having_clause("SUM(d.amount)", 4);
+        let [gt, ge, lt, le] = having.bounds();
+        let by_month = matches!(
+            aggregation_kind,
+            Aggregation::Monthly | Aggregation::Quarterly | Aggregation::Yearly
+        );
+        diesel::sql_query(format!(
+            "SELECT DATE_TRUNC($8, d.day) AS datetime, SUM(d.amount) AS total_amount \
+             FROM ( \
+                 SELECT month AS day, total_amount AS amount \
+                 FROM renewable.ts_monthly_summary \
+                 WHERE $9 \
+                 AND ($1 IS NULL OR month >= $1) \
+                 AND ($2 IS NULL OR month + INTERVAL '1 month' <= $2) \
+                 AND {series_clause} \
+                 UNION ALL \
+                 SELECT day, total_amount AS amount \
+                 FROM renewable.ts_daily_summary \
+                 WHERE ($1 IS NULL OR day >= $1) \
+                 AND ($2 IS NULL OR day + INTERVAL '1 day' <= $2) \
+                 AND {series_clause} \
+                 AND NOT ($9 \
+                     AND ($1 IS NULL OR DATE_TRUNC('month', day) >= $1) \
+                     AND ($2 IS NULL OR DATE_TRUNC('month', day) + INTERVAL '1 month' <= $2)) \
+                 UNION ALL \
+                 SELECT DATE_TRUNC('day', datetime), amount \
+                 FROM renewable.ts_store \
+                 WHERE ($1 IS NULL OR datetime >= $1) \
+                 AND ($2 IS NULL OR datetime < $2) \
+                 AND {series_clause} \
+                 AND ( \
+                     ($1 IS NOT NULL AND DATE_TRUNC('day', $1) <> $1 \
+                      AND datetime < DATE_TRUNC('day', $1) + INTERVAL '1 day') \
+                     OR ($2 IS NOT NULL AND DATE_TRUNC('day', $2) <> $2 \
+                      AND datetime >= DATE_TRUNC('day', $2)) \
+                 ) \
+             ) d \
+             GROUP BY 1 \
+             {having_clause} \
+             ORDER BY 1"
+        ))
+        .bind::<Nullable<Timestamptz>, _>(from_date)
+        .bind::<Nullable<Timestamptz>, _>(to_date)
+        .bind::<Nullable<BigInt>, _>(series_id)
+        .bind::<Nullable<Numeric>, _>(gt)
+        .bind::<Nullable<Numeric>, _>(ge)
+        .bind::<Nullable<Numeric>, _>(lt)
+        .bind::<Nullable<Numeric>, _>(le)
+        .bind::<Text, _>(<&str>::from(aggregation_kind))
+        .bind::<Bool, _>(by_month)
+        .load(conn)
+    }
+
+    /// Buckets summed per `DATE_TRUNC` period, left unordered
+    pub(super) type AggregationQuery<'a> = IntoBoxed<
+        'a,
+        Select<
+            GroupBy<ts_store::table, FirstColumn>,
+            (BucketStart, helper_types::sum<ts_store::amount>),
+        >,
+        Pg,
+    >;
+
+    pub(super) fn aggregation_query<'a>(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        series_id: Option<i64>,
+        having: &TotalFilter,
+        timezone: Tz,
+    ) -> AggregationQuery<'a> {
+        // Construct the aggregation query
+        let mut query = ts_store::table
+            .group_by(FirstColumn)
+            .select((
+                bucket_start(aggregation_kind, timezone),
+                sum(ts_store::amount),
+            ))
+            .into_boxed();
+
+        if let Some(from) = from_date {
+            query = query.filter(ts_store::datetime.ge(from));
+        }
+        if let Some(to) = to_date {
+            query = query.filter(ts_store::datetime.lt(to));
+        }
+        if let Some(series_id) = series_id {
+            query = query.filter(ts_store::ingestion_id.eq_any(series_ingestions(series_id)));
+        }
+        let outranking = outranking_readings("renewable.ts_store", "renewable.ts_store");
+        query = query.filter(sql::<Bool>(&format!(
+            "NOT ({RANKED} AND EXISTS ({outranking}))"
+        )));
+        if let [None, None, None, None] = having.bounds() {
+            return query;
+        }
+        let [gt, ge, lt, le] = having.bounds();
+        query.having(
+            sql::<Bool>("(")
+                .bind::<Nullable<Numeric>, _>(gt.clone())
+                .sql("::NUMERIC IS NULL OR SUM(amount) > ")
+                .bind::<Nullable<Numeric>, _>(gt)
+                .sql(") AND (")
+                .bind::<Nullable<Numeric>, _>(ge.clone())
+                .sql("::NUMERIC IS NULL OR SUM(amount) >= ")
+                .bind::<Nullable<Numeric>, _>(ge)
+                .sql(") AND (")
+                .bind::<Nullable<Numeric>, _>(lt.clone())
+                .sql("::NUMERIC IS NULL OR SUM(amount) < ")
+                .bind::<Nullable<Numeric>, _>(lt)
+                .sql(") AND (")
+                .bind::<Nullable<Numeric>, _>(le.clone())
+                .sql("::NUMERIC IS NULL OR SUM(amount) <= ")
+                .bind::<Nullable<Numeric>, _>(le)
+                .sql(")"),
+        )
+    }
+}
+
+pub mod health {
+    use diesel::{RunQueryDsl as _, dsl::sql, sql_types::Double, sql_types::Nullable};
+
+    /// Seconds since the last replayed transaction when connected to a standby,
+    /// `None` when connected to a primary
+    pub fn replication_lag_seconds(
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<f64>, diesel::result::Error> {
+        diesel::select(sql::<Nullable<Double>>(
+            "CASE WHEN pg_is_in_recovery() \
+             THEN COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::float8, 0) \
+             END",
+        ))
+        .get_result(conn)
+    }
+}
+
+pub mod partitions {
+    use chrono::{DateTime, Months, Utc};
+    use diesel::{
+        QueryResult, RunQueryDsl as _,
+        dsl::sql,
+        sql_types::{Bool, Timestamptz},
+    };
+
+    /// Creates the `ts_store` partitions of the UTC month holding `now` and the
+    /// `months_ahead` after it, moving any of their readings out of the default partition,
+    /// and returns how many were missing
+    pub fn ensure_ts_store_partitions(
+        now: DateTime<Utc>,
+        months_ahead: u32,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<usize> {
+        let mut created = 0;
+        for month in
+            (0..=months_ahead).filter_map(|ahead| now.checked_add_months(Months::new(ahead)))
+        {
+            let missing = diesel::select(
+                sql::<Bool>("renewable.ensure_ts_store_partition(")
+                    .bind::<Timestamptz, _>(month)
+                    .sql(")"),
+            )
+            .get_result::<bool>(conn)?;
+            created += usize::from(missing);
+        }
+        Ok(created)
+    }
+}
+
+pub mod retention {
+    use chrono::{DateTime, Months, Utc};
+    use diesel::{
+        Connection as _, ExpressionMethods as _, QueryDsl as _, QueryResult, RunQueryDsl as _,
+        dsl::count_star,
+        sql_types::{Array, BigInt, Timestamptz},
+    };
+
+    use super::legal_holds::{held_ingestions, record_audit};
+    use crate::{
+        model::database::{AuditAction, AuditEntry, ChainEvent, ExpiredPartition, RetentionPurge},
+        renewable_schema::{query_history, ts_daily_summary, ts_store, ts_store_revisions},
+    };
+
+    #[derive(diesel::QueryableByName)]
+    struct Count {
+        #[diesel(sql_type = BigInt)]
+        count: i64,
+    }
+
+    /// Month partitions of `ts_store` holding nothing from `cutoff` on
+    fn expired_partitions(
+        cutoff: DateTime<Utc>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<ExpiredPartition>> {
+        diesel::sql_query(
+            "SELECT name, lower_bound FROM ( \
+                 SELECT c.relname::TEXT AS name, \
+                        to_date(substring(c.relname FROM 10), 'YYYY_MM')::TIMESTAMP \
+                            AT TIME ZONE 'UTC' AS lower_bound \
+                 FROM pg_inherits i \
+                 JOIN pg_class c ON c.oid = i.inhrelid \
+                 WHERE i.inhparent = 'renewable.ts_store'::regclass \
+                 AND c.relname ~ '^ts_store_[0-9]{4}_[0-9]{2}$') partitions \
+             WHERE lower_bound + INTERVAL '1 month' <= $1 \
+             ORDER BY lower_bound",
+        )
+        .bind::<Timestamptz, _>(cutoff)
+        .load(conn)
+    }
+
+    /// Drops a month partition whole, with the daily summaries of its readings, which
+    /// the triggers of `ts_store` do not see go
+    fn drop_partition(
+        partition: &ExpiredPartition,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<u64> {
+        let upper_bound = partition.lower_bound + Months::new(1);
+        let readings = diesel::sql_query(format!(
+            "SELECT COUNT(*) AS count FROM renewable.\"{}\"",
+            partition.name
+        ))
+        .get_result::<Count>(conn)?
+        .count;
+        diesel::delete(
+            ts_daily_summary::table
+                .filter(ts_daily_summary::first_datetime.ge(partition.lower_bound))
+                .filter(ts_daily_summary::last_datetime.lt(upper_bound)),
+        )
+        .execute(conn)?;
+        diesel::sql_query(format!("DROP TABLE renewable.\"{}\"", partition.name)).execute(conn)?;
+        Ok(u64::try_from(readings).unwrap_or_default())
+    }
+
+    /// Deletes the readings, their superseded revisions and the query history entries
+    /// from before `cutoff`, dropping the month partitions of `ts_store` that end by then
+    /// rather than deleting their rows.
+    /// A dry run only counts what would go.
+    ///
+    /// Readings of ingestions under legal hold are kept, as are the partitions holding
+    /// them, and each held ingestion with expired readings is recorded in the audit log.
+    pub fn purge_before(
+        cutoff: DateTime<Utc>,
+        dry_run: bool,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<RetentionPurge> {
+        let held = held_ingestions(conn)?;
+        let expired_readings = ts_store::table
+            .filter(ts_store::datetime.lt(cutoff))
+            .filter(ts_store::ingestion_id.ne_all(held.clone()));
+        let expired_history = query_history::table.filter(query_history::executed_at.lt(cutoff));
+        let held_readings: Vec<(i64, i64)> = ts_store::table
+            .filter(ts_store::datetime.lt(cutoff))
+            .filter(ts_store::ingestion_id.eq_any(held.clone()))
+            .group_by(ts_store::ingestion_id)
+            .select((ts_store::ingestion_id, count_star()))
+            .order(ts_store::ingestion_id)
+            .load(conn)?;
+        let held_total = held_readings
+            .iter()
+            .map(|(_, readings)| readings)
+            .sum::<i64>();
+        if dry_run {
+            let readings = expired_readings.count().get_result::<i64>(conn)?;
+            let history = expired_history.count().get_result::<i64>(conn)?;
+            return Ok(RetentionPurge {
+                readings: u64::try_from(readings).unwrap_or_default(),
+                query_history: u64::try_from(history).unwrap_or_default(),
+                partitions: 0,
+                held: u64::try_from(held_total).unwrap_or_default(),
+            });
+        }
+
+        conn.transaction(|conn| {
+            // Ingestions losing readings, each recorded in the hash chain once they have
+            let purged: Vec<i64> = expired_readings
+                .clone()
+                .select(ts_store::ingestion_id)
+                .distinct()
+                .order(ts_store::ingestion_id)
+                .load(conn)?;
+            let mut purge = RetentionPurge {
+                held: u64::try_from(held_total).unwrap_or_default(),
+                ..RetentionPurge::default()
+            };
+            for partition in expired_partitions(cutoff, conn)? {
+                if holds_any(&partition, &held, conn)? {
+                    continue;
+                }
+                purge.readings += drop_partition(&partition, conn)?;
+                purge.partitions += 1;
+            }
+            // Readings in the default partition, the month holding the cutoff or a month
+            // kept for held readings
+            purge.readings += diesel::delete(expired_readings).execute(conn)? as u64;
+            diesel::delete(
+                ts_store_revisions::table
+                    .filter(ts_store_revisions::datetime.lt(cutoff))
+                    .filter(ts_store_revisions::ingestion_id.ne_all(held)),
+            )
+            .execute(conn)?;
+            purge.query_history = diesel::delete(expired_history).execute(conn)? as u64;
+            for ingestion_id in purged {
+                super::chain::append(ChainEvent::Purged, ingestion_id, conn)?;
+            }
+            for (ingestion_id, readings) in held_readings {
+                record_audit(
+                    AuditEntry::new(
+                        AuditAction::RetentionPurge,
+                        Some(ingestion_id),
+                        false,
+                        format!("{readings} readings before {cutoff} kept under legal hold"),
+                    ),
+                    conn,
+                )?;
+            }
+            Ok(purge)
+        })
+    }
+
+    /// Whether a month partition holds readings of any of the `held` ingestions
+    fn holds_any(
+        partition: &ExpiredPartition,
+        held: &[i64],
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<bool> {
+        if held.is_empty() {
+            return Ok(false);
+        }
+        let readings = diesel::sql_query(format!(
+            "SELECT COUNT(*) AS count FROM renewable.\"{}\" WHERE ingestion_id = ANY($1)",
+            partition.name
+        ))
+        .bind::<Array<BigInt>, _>(held)
+        .get_result::<Count>(conn)?
+        .count;
+        Ok(readings > 0)
+    }
+}
+
+pub mod storage_stats {
+    use chrono::NaiveDate;
+    use diesel::{
+        ExpressionMethods as _, QueryDsl as _, QueryResult, RunQueryDsl as _,
+        SelectableHelper as _,
+        dsl::sql,
+        sql_types::{BigInt, Date},
+        upsert::excluded,
+    };
+
+    use crate::{model::database::StorageSample, renewable_schema::storage_stats};
+
+    /// Sizes of the tables, partitions and materialized views of the `renewable` schema,
+    /// partitioned tables adding up their partitions
+    pub fn relation_sizes(
+        today: NaiveDate,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<StorageSample>> {
+        let mut sizes: Vec<StorageSample> = diesel::sql_query(
+            "SELECT $1 AS sampled_on, c.relname::TEXT AS relation, \
+                    p.relname::TEXT AS parent, \
+                    pg_total_relation_size(c.oid) AS total_bytes, \
+                    pg_indexes_size(c.oid) AS index_bytes, \
+                    GREATEST(c.reltuples, 0)::BIGINT AS row_estimate \
+             FROM pg_class c \
+             JOIN pg_namespace n ON n.oid = c.relnamespace \
+             LEFT JOIN pg_inherits i ON i.inhrelid = c.oid \
+             LEFT JOIN pg_class p ON p.oid = i.inhparent \
+             WHERE n.nspname = 'renewable' AND c.relkind IN ('r', 'p', 'm') \
+             ORDER BY c.relname",
+        )
+        .bind::<Date, _>(today)
+        .load(conn)?;
+
+        let partitions: Vec<_> = sizes
+            .iter()
+            .filter_map(|size| {
+                let parent = size.parent.clone()?;
+                Some((
+                    parent,
+                    size.total_bytes,
+                    size.index_bytes,
+                    size.row_estimate,
+                ))
+            })
+            .collect();
+        for (parent, total_bytes, index_bytes, row_estimate) in partitions {
+            if let Some(table) = sizes.iter_mut().find(|size| size.relation == parent) {
+                table.total_bytes += total_bytes;
+                table.index_bytes += index_bytes;
+                table.row_estimate += row_estimate;
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Size of the whole database, including what lies outside the `renewable` schema
+    pub fn database_size(conn: &mut diesel::PgConnection) -> QueryResult<i64> {
+        diesel::select(sql::<BigInt>("pg_database_size(current_database())")).get_result(conn)
+    }
+
+    /// Samples the size of every relation as of `today`, replacing an earlier sample of
+    /// that day, and returns how many were sampled
+    pub fn record_storage_sample(
+        today: NaiveDate,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<usize> {
+        let sizes = relation_sizes(today, conn)?;
+        diesel::insert_into(storage_stats::table)
+            .values(&sizes)
+            .on_conflict((storage_stats::sampled_on, storage_stats::relation))
+            .do_update()
+            .set((
+                storage_stats::parent.eq(excluded(storage_stats::parent)),
+                storage_stats::total_bytes.eq(excluded(storage_stats::total_bytes)),
+                storage_stats::index_bytes.eq(excluded(storage_stats::index_bytes)),
+                storage_stats::row_estimate.eq(excluded(storage_stats::row_estimate)),
+            ))
+            .execute(conn)
+    }
+
+    /// Samples taken on or after `since`, oldest first
+    pub fn storage_samples_since(
+        since: NaiveDate,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<StorageSample>> {
+        storage_stats::table
+            .filter(storage_stats::sampled_on.ge(since))
+            .order((storage_stats::sampled_on, storage_stats::relation))
+            .select(StorageSample::as_select())
+            .load(conn)
+    }
+}
+
+pub mod catalog {
+    use diesel::{QueryResult, RunQueryDsl as _};
+
+    use crate::model::database::{LiveColumn, LiveIndex};
+
+    /// Every column of the `renewable` tables, ordered by table and position
+    pub fn live_columns(conn: &mut diesel::PgConnection) -> QueryResult<Vec<LiveColumn>> {
+        diesel::sql_query(
+            "SELECT c.table_name::TEXT AS table_name, \
+                    c.column_name::TEXT AS column_name, \
+                    c.udt_name::TEXT AS udt_name, \
+                    c.is_nullable = 'YES' AS nullable, \
+                    c.column_default IS NOT NULL AS has_default \
+             FROM information_schema.columns c \
+             JOIN information_schema.tables t \
+               ON t.table_schema = c.table_schema AND t.table_name = c.table_name \
+             WHERE c.table_schema = 'renewable' AND t.table_type = 'BASE TABLE' \
+             ORDER BY c.table_name, c.ordinal_position",
+        )
+        .load(conn)
+    }
+
+    /// Indexes of the `renewable` tables, leaving out those Postgres creates for primary
+    /// key, unique and exclusion constraints
+    pub fn live_indexes(conn: &mut diesel::PgConnection) -> QueryResult<Vec<LiveIndex>> {
+        diesel::sql_query(
+            "SELECT i.relname::TEXT AS index_name, \
+                    t.relname::TEXT AS table_name, \
+                    pg_get_indexdef(i.oid) AS definition \
+             FROM pg_index x \
+             JOIN pg_class i ON i.oid = x.indexrelid \
+             JOIN pg_class t ON t.oid = x.indrelid \
+             JOIN pg_namespace n ON n.oid = t.relnamespace \
+             WHERE n.nspname = 'renewable' \
+             AND NOT EXISTS ( \
+                 SELECT 1 FROM pg_constraint c \
+                 WHERE c.conindid = x.indexrelid AND c.contype IN ('p', 'u', 'x')) \
+             ORDER BY 2, 1",
+        )
+        .load(conn)
+    }
+}
+
+pub mod api_keys {
+    use chrono::Utc;
+    use diesel::{
+        ExpressionMethods as _, OptionalExtension as _, QueryDsl as _, QueryResult,
+        RunQueryDsl as _,
+    };
+
+    use crate::{model::database::ApiKeyRecord, renewable_schema::api_keys};
+
+    /// Id of the unrevoked key with the given digest
+    pub fn find_active_key(
+        key_hash: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<i64>> {
+        api_keys::table
+            .filter(api_keys::key_hash.eq(key_hash))
+            .filter(api_keys::revoked_at.is_null())
+            .select(api_keys::id)
+            .first(conn)
+            .optional()
+    }
+
+    /// Registers a key digest under `name`, leaving an existing key with the digest untouched
+    pub fn ensure_api_key(
+        name: &str,
+        key_hash: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(api_keys::table)
+            .values(ApiKeyRecord::new(name.to_string(), key_hash.to_string()))
+            .on_conflict(api_keys::key_hash)
+            .do_nothing()
+            .execute(conn)
+    }
+
+    /// Ids of every key, revoked ones included, in creation order
+    pub fn api_key_ids(conn: &mut diesel::PgConnection) -> QueryResult<Vec<i64>> {
+        api_keys::table
+            .select(api_keys::id)
+            .order(api_keys::id)
+            .load(conn)
+    }
+
+    pub fn revoke_api_key(key_id: i64, conn: &mut diesel::PgConnection) -> QueryResult<usize> {
+        diesel::update(api_keys::table.find(key_id))
+            .set(api_keys::revoked_at.eq(Utc::now()))
+            .execute(conn)
+    }
+
+    /// Public key the exports of `key_id` are encrypted to, if any
+    pub fn export_recipient(
+        key_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<String>> {
+        api_keys::table
+            .find(key_id)
+            .select(api_keys::export_recipient)
+            .first(conn)
+            .optional()
+            .map(Option::flatten)
+    }
+
+    /// Sets or clears the export recipient of `key_id`, returning whether the key exists
+    pub fn set_export_recipient(
+        key_id: i64,
+        recipient: Option<&str>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<bool> {
+        diesel::update(api_keys::table.find(key_id))
+            .set(api_keys::export_recipient.eq(recipient))
+            .execute(conn)
+            .map(|updated| updated > 0)
+    }
+}
+
+pub mod legal_holds {
+    use chrono::Utc;
+    use diesel::{
+        Connection as _, ExpressionMethods as _, OptionalExtension as _, QueryDsl as _,
+        QueryResult, RunQueryDsl as _, SelectableHelper as _, dsl::exists,
+    };
+
+    use crate::{
+        model::database::{AuditAction, AuditEntry, LegalHold},
+        renewable_schema::{audit_log, legal_holds, ts_metadata},
+    };
+
+    pub fn find_hold(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<LegalHold>> {
+        legal_holds::table
+            .find(ingestion_id)
+            .select(LegalHold::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    /// Ids of the ingestions under legal hold
+    pub fn held_ingestions(conn: &mut diesel::PgConnection) -> QueryResult<Vec<i64>> {
+        legal_holds::table
+            .select(legal_holds::ingestion_id)
+            .order(legal_holds::ingestion_id)
+            .load(conn)
+    }
+
+    /// Holds `ingestion_id`, replacing the reason of a hold already placed, returning
+    /// `None` when the ingestion does not exist
+    pub fn place_hold(
+        ingestion_id: i64,
+        reason: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<LegalHold>> {
+        conn.transaction(|conn| {
+            let ingested = diesel::select(exists(ts_metadata::table.find(ingestion_id)))
+                .get_result::<bool>(conn)?;
+            if !ingested {
+                return Ok(None);
+            }
+            let hold = diesel::insert_into(legal_holds::table)
+                .values(LegalHold {
+                    ingestion_id,
+                    placed_at: Utc::now(),
+                    reason: reason.to_string(),
+                })
+                .on_conflict(legal_holds::ingestion_id)
+                .do_update()
+                .set(legal_holds::reason.eq(reason))
+                .returning(LegalHold::as_returning())
+                .get_result(conn)?;
+            record_audit(
+                AuditEntry::new(
+                    AuditAction::LegalHoldPlaced,
+                    Some(ingestion_id),
+                    true,
+                    reason,
+                ),
+                conn,
+            )?;
+            Ok(Some(hold))
+        })
+    }
+
+    /// Lifts the hold on `ingestion_id`, returning `None` when it was not held
+    pub fn release_hold(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<LegalHold>> {
+        conn.transaction(|conn| {
+            let hold = diesel::delete(legal_holds::table.find(ingestion_id))
+                .returning(LegalHold::as_returning())
+                .get_result(conn)
+                .optional()?;
+            if let Some(hold) = &hold {
+                record_audit(
+                    AuditEntry::new(
+                        AuditAction::LegalHoldReleased,
+                        Some(ingestion_id),
+                        true,
+                        hold.reason.as_str(),
+                    ),
+                    conn,
+                )?;
+            }
+            Ok(hold)
+        })
+    }
+
+    pub fn record_audit(entry: AuditEntry, conn: &mut diesel::PgConnection) -> QueryResult<()> {
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .execute(conn)
+            .map(|_| ())
+    }
+
+    /// Latest audit log entries, of one ingestion when `ingestion_id` is given, newest first
+    pub fn audit_entries(
+        ingestion_id: Option<i64>,
+        limit: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<AuditEntry>> {
+        let mut query = audit_log::table
+            .select(AuditEntry::as_select())
+            .order(audit_log::id.desc())
+            .limit(limit)
+            .into_boxed();
+        if let Some(ingestion_id) = ingestion_id {
+            query = query.filter(audit_log::ingestion_id.eq(ingestion_id));
+        }
+        query.load(conn)
+    }
+}
+
+pub mod chain {
+    use std::collections::HashMap;
+
+    use chrono::{DateTime, Utc};
+    use diesel::{
+        ExpressionMethods as _, OptionalExtension as _, QueryDsl as _, QueryResult,
+        RunQueryDsl as _, SelectableHelper as _,
+        dsl::{count_star, not},
+        sql_types::{Array, BigInt, Text},
+    };
+
+    use crate::{
+        chain::{self, EMPTY_CONTENT},
+        model::{
+            api_response::ChainVerification,
+            database::{ChainEntry, ChainEvent, IngestionStatus},
+        },
+        renewable_schema::{ingestion_chain, ts_metadata},
+    };
+
+    #[derive(diesel::QueryableByName)]
+    struct Content {
+        #[diesel(sql_type = BigInt)]
+        ingestion_id: i64,
+        #[diesel(sql_type = BigInt)]
+        readings: i64,
+        #[diesel(sql_type = Text)]
+        content_hash: String,
+    }
+
+    /// Readings count and content hash each of `ingestion_ids` holds in `ts_store`, the
+    /// text hashed being made of [`chain::content_line`]s. Ingestions holding no readings
+    /// are absent.
+    fn contents(
+        ingestion_ids: &[i64],
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<HashMap<i64, (i64, String)>> {
+        let rows: Vec<Content> = diesel::sql_query(
+            "SELECT ingestion_id, COUNT(*)::BIGINT AS readings, \
+                    encode(sha256(convert_to(string_agg( \
+                        (EXTRACT(EPOCH FROM datetime) * 1000000)::BIGINT || ',' || amount, \
+                        E'\\n' ORDER BY datetime), 'UTF8')), 'hex') AS content_hash \
+             FROM renewable.ts_store \
+             WHERE ingestion_id = ANY($1) \
+             GROUP BY ingestion_id",
+        )
+        .bind::<Array<BigInt>, _>(ingestion_ids)
+        .load(conn)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.ingestion_id, (row.readings, row.content_hash)))
+            .collect())
+    }
+
+    /// Appends `event` on `ingestion_id` to the chain, recording the readings it holds
+    /// now. Call it inside the transaction making the change, appends being serialized
+    /// by a transaction-level advisory lock so each links to the one before.
+    pub fn append(
+        event: ChainEvent,
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<ChainEntry> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext('renewable.ingestion_chain'))")
+            .execute(conn)?;
+        let previous_hash = ingestion_chain::table
+            .select(ingestion_chain::hash)
+            .order(ingestion_chain::seq.desc())
+            .first::<String>(conn)
+            .optional()?;
+        let (readings, content_hash) = contents(&[ingestion_id], conn)?
+            .remove(&ingestion_id)
+            .unwrap_or_else(|| (0, EMPTY_CONTENT.to_string()));
+        // Kept to the microsecond Postgres stores, so the hash can be recomputed
+        let now = Utc::now();
+        let recorded_at = DateTime::from_timestamp_micros(now.timestamp_micros()).unwrap_or(now);
+        let entry = ChainEntry {
+            seq: 0,
+            recorded_at,
+            event: event.as_str().to_string(),
+            ingestion_id,
+            readings,
+            hash: chain::link_hash(
+                previous_hash.as_deref(),
+                event.as_str(),
+                ingestion_id,
+                readings,
+                &content_hash,
+                recorded_at,
+            ),
+            content_hash,
+            previous_hash,
+        };
+        diesel::insert_into(ingestion_chain::table)
+            .values(&entry)
+            .returning(ChainEntry::as_returning())
+            .get_result(conn)
+    }
+
+    /// Checks the whole chain, and the readings each ingestion in it holds now against
+    /// its latest entry
+    pub fn verify_chain(conn: &mut diesel::PgConnection) -> QueryResult<ChainVerification> {
+        let entries: Vec<ChainEntry> = ingestion_chain::table
+            .select(ChainEntry::as_select())
+            .order(ingestion_chain::seq)
+            .load(conn)?;
+        let mut ingestion_ids: Vec<i64> = entries.iter().map(|entry| entry.ingestion_id).collect();
+        ingestion_ids.sort_unstable();
+        ingestion_ids.dedup();
+        let contents = contents(&ingestion_ids, conn)?;
+        let unchained = ts_metadata::table
+            .filter(ts_metadata::status.eq(IngestionStatus::Complete))
+            .filter(not(ts_metadata::ingestion_id.eq_any(
+                ingestion_chain::table.select(ingestion_chain::ingestion_id),
+            )))
+            .select(count_star())
+            .get_result(conn)?;
+        Ok(chain::verify(Utc::now(), &entries, &contents, unchained))
+    }
+}
+
+pub mod meters {
+    use bigdecimal::BigDecimal;
+    use chrono::Utc;
+    use diesel::{
+        Connection as _, ExpressionMethods as _, OptionalExtension as _, QueryDsl as _,
+        QueryResult, RunQueryDsl as _, SelectableHelper as _,
+        result::{DatabaseErrorKind, Error},
+    };
+
+    use crate::{
+        model::{
+            api_request::{MeterOnboarding, ProfileMonth},
+            api_response::MeterOnboardingResult,
+            database::{Meter, MeterProfile, Series},
+        },
+        renewable_schema::{meter_profiles, meters, series},
+    };
+
+    fn onboard_meter(
+        meter: MeterOnboarding,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<(i64, usize)> {
+        let capacity_kw = meter
+            .capacity_kw
+            .map(|capacity| BigDecimal::try_from(capacity).unwrap_or_default());
+        let record = Meter::new(meter.meter_code, meter.site_name, capacity_kw);
+        let meter_id = diesel::insert_into(meters::table)
+            .values(&record)
+            .returning(meters::id)
+            .get_result(conn)?;
+
+        let channels: Vec<Series> = meter
+            .series
+            .iter()
+            .map(|channel| Series::meter_channel(meter_id, &record, channel))
+            .collect();
+        let series_created = diesel::insert_into(series::table)
+            .values(channels)
+            .execute(conn)?;
+        Ok((meter_id, series_created))
+    }
+
+    fn find_meter_id(
+        meter_code: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<i64>> {
+        meters::table
+            .filter(meters::meter_code.eq(meter_code))
+            .select(meters::id)
+            .first(conn)
+            .optional()
+    }
+
+    /// Replaces the meter's expected generation profile, `None` when the meter is unknown
+    pub fn replace_meter_profile(
+        meter_code: &str,
+        months: Vec<ProfileMonth>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<usize>> {
+        conn.transaction(|conn| {
+            let Some(meter_id) = find_meter_id(meter_code, conn)? else {
+                return Ok(None);
+            };
+            diesel::delete(meter_profiles::table.filter(meter_profiles::meter_id.eq(meter_id)))
+                .execute(conn)?;
+
+            let uploaded_at = Utc::now();
+            let rows: Vec<MeterProfile> = months
+                .into_iter()
+                .map(|entry| MeterProfile {
+                    meter_id,
+                    month: i16::try_from(entry.month).unwrap_or_default(),
+                    p50_kwh: BigDecimal::try_from(entry.p50_kwh).unwrap_or_default(),
+                    p90_kwh: BigDecimal::try_from(entry.p90_kwh).unwrap_or_default(),
+                    uploaded_at,
+                })
+                .collect();
+            diesel::insert_into(meter_profiles::table)
+                .values(rows)
+                .execute(conn)
+                .map(Some)
+        })
+    }
+
+    /// Profile entries ordered by month, `None` when the meter is unknown
+    pub fn load_meter_profile(
+        meter_code: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<Vec<MeterProfile>>> {
+        let Some(meter_id) = find_meter_id(meter_code, conn)? else {
+            return Ok(None);
+        };
+        meter_profiles::table
+            .filter(meter_profiles::meter_id.eq(meter_id))
+            .order_by(meter_profiles::month)
+            .select(MeterProfile::as_select())
+            .load(conn)
+            .map(Some)
+    }
+
+    fn describe(error: &Error) -> String {
+        match error {
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)
+                if info.constraint_name() == Some("meters_meter_code_key") =>
+            {
+                "meter_code already exists".to_string()
+            }
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                "duplicate series name for meter".to_string()
+            }
+            other => other.to_string(),
+        }
+    }
+
+    /// Onboards every row in a single transaction, each row inside its own savepoint so a
+    /// failing row is reported without discarding the rest of the batch
+    pub fn onboard_meters(
+        rows: Vec<Result<MeterOnboarding, String>>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<MeterOnboardingResult>> {
+        conn.transaction(|conn| {
+            let mut results = Vec::with_capacity(rows.len());
+            for (row, meter) in rows.into_iter().enumerate() {
+                let meter_code = meter.as_ref().ok().map(|m| m.meter_code.clone());
+                let outcome = meter
+                    .and_then(|meter| meter.validate().map(|()| meter))
+                    .and_then(|meter| {
+                        conn.transaction(|conn| onboard_meter(meter, conn))
+                            .map_err(|e| describe(&e))
+                    });
+                results.push(match outcome {
+                    Ok((meter_id, series_created)) => MeterOnboardingResult {
+                        row,
+                        meter_code,
+                        meter_id: Some(meter_id),
+                        series_created,
+                        error: None,
+                    },
+                    Err(error) => MeterOnboardingResult {
+                        row,
+                        meter_code,
+                        meter_id: None,
+                        series_created: 0,
+                        error: Some(error),
+                    },
+                });
+            }
+            Ok(results)
+        })
+    }
+}
+
+pub mod series {
+    use diesel::{
+        ExpressionMethods as _, OptionalExtension as _, QueryDsl as _, QueryResult,
+        RunQueryDsl as _, SelectableHelper as _,
+    };
+
+    use crate::{
+        model::{api_request::SeriesDefinition, database::Series},
+        renewable_schema::series,
+    };
+
+    pub fn create_series(
+        definition: SeriesDefinition,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Series> {
+        diesel::insert_into(series::table)
+            .values(Series::from(definition))
+            .returning(Series::as_returning())
+            .get_result(conn)
+    }
+
+    /// Every series ordered by name
+    pub fn list_series(conn: &mut diesel::PgConnection) -> QueryResult<Vec<Series>> {
+        series::table
+            .order_by(series::name)
+            .select(Series::as_select())
+            .load(conn)
+    }
+
+    pub fn get_series(id: i64, conn: &mut diesel::PgConnection) -> QueryResult<Option<Series>> {
+        series::table
+            .find(id)
+            .select(Series::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    pub fn find_series_id(name: &str, conn: &mut diesel::PgConnection) -> QueryResult<Option<i64>> {
+        series::table
+            .filter(series::name.eq(name))
+            .select(series::id)
+            .first(conn)
+            .optional()
+    }
+
+    /// Replaces every field of the series, `None` when it is unknown
+    pub fn update_series(
+        id: i64,
+        definition: SeriesDefinition,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Option<Series>> {
+        diesel::update(series::table.find(id))
+            .set((
+                series::name.eq(definition.name),
+                series::unit.eq(definition.unit),
+                series::fuel_type.eq(definition.fuel_type),
+                series::site.eq(definition.site),
+            ))
+            .returning(Series::as_returning())
+            .get_result(conn)
+            .optional()
+    }
+
+    /// Deletes the series, `None` when it is unknown. Fails with a foreign key violation
+    /// while ingestions still reference it.
+    pub fn delete_series(id: i64, conn: &mut diesel::PgConnection) -> QueryResult<Option<Series>> {
+        diesel::delete(series::table.find(id))
+            .returning(Series::as_returning())
+            .get_result(conn)
+            .optional()
+    }
+}
+
+pub mod export_jobs {
+    use chrono::Utc;
+    use diesel::{
+        ExpressionMethods as _, OptionalExtension as _, QueryDsl as _, RunQueryDsl as _,
+        SelectableHelper as _,
+    };
+
+    use crate::{
+        model::{
+            api_request::Aggregation,
+            database::{ExportJob, JobStatus},
+        },
+        renewable_schema::export_jobs,
+    };
+
+    pub fn create_export_job(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        api_key_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<ExportJob, diesel::result::Error> {
+        diesel::insert_into(export_jobs::table)
+            .values(ExportJob::new(
+                from_date,
+                to_date,
+                aggregation_kind,
+                Some(api_key_id),
+            ))
+            .returning(ExportJob::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn get_export_job(
+        job_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<ExportJob>, diesel::result::Error> {
+        export_jobs::table
+            .find(job_id)
+            .select(ExportJob::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    pub fn mark_export_running(
+        job_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(export_jobs::table.find(job_id))
+            .set(export_jobs::status.eq(JobStatus::Running))
+            .execute(conn)
+    }
+
+    pub fn mark_export_complete(
+        job_id: i64,
+        file_path: &str,
+        row_count: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(export_jobs::table.find(job_id))
+            .set((
+                export_jobs::status.eq(JobStatus::Complete),
+                export_jobs::file_path.eq(file_path),
+                export_jobs::row_count.eq(row_count),
+                export_jobs::completed_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+    }
+
+    pub fn mark_export_failed(
+        job_id: i64,
+        error: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(export_jobs::table.find(job_id))
+            .set((
+                export_jobs::status.eq(JobStatus::Failed),
+                export_jobs::error.eq(error),
+                export_jobs::completed_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+    }
+}
+
+pub mod query_jobs {
+    use chrono::Utc;
+    use diesel::{
+        ExpressionMethods as _, OptionalExtension as _, QueryDsl as _, RunQueryDsl as _,
+        SelectableHelper as _,
+    };
+
+    use crate::{
+        model::{
+            api_request::Aggregation,
+            database::{JobStatus, QueryJob},
+        },
+        renewable_schema::query_jobs,
+    };
+
+    pub fn create_query_job(
+        aggregation_kind: Aggregation,
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        api_key_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<QueryJob, diesel::result::Error> {
+        diesel::insert_into(query_jobs::table)
+            .values(QueryJob::new(
+                from_date,
+                to_date,
+                aggregation_kind,
+                Some(api_key_id),
+            ))
+            .returning(QueryJob::as_returning())
+            .get_result(conn)
+    }
+
+    /// The job `job_id` when it was submitted by `api_key_id`, so no key can read the
+    /// results of another's jobs
+    pub fn get_query_job(
+        job_id: i64,
+        api_key_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<Option<QueryJob>, diesel::result::Error> {
+        query_jobs::table
+            .find(job_id)
+            .filter(query_jobs::api_key_id.eq(api_key_id))
+            .select(QueryJob::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    pub fn mark_query_running(
+        job_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(query_jobs::table.find(job_id))
+            .set(query_jobs::status.eq(JobStatus::Running))
+            .execute(conn)
+    }
+
+    pub fn mark_query_complete(
+        job_id: i64,
+        result: &serde_json::Value,
+        row_count: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(query_jobs::table.find(job_id))
+            .set((
+                query_jobs::status.eq(JobStatus::Complete),
+                query_jobs::result.eq(result),
+                query_jobs::row_count.eq(row_count),
+                query_jobs::completed_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+    }
+
+    pub fn mark_query_failed(
+        job_id: i64,
+        error: &str,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::update(query_jobs::table.find(job_id))
+            .set((
+                query_jobs::status.eq(JobStatus::Failed),
+                query_jobs::error.eq(error),
+                query_jobs::completed_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+    }
+}
+
+/// Views over the readings holding the aggregations the API serves, so BI tools reading
+/// the database directly share its definitions
+pub mod views {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use chrono_tz::Tz;
+    use diesel::{
+        Connection as _, QueryResult, RunQueryDsl as _,
+        dsl::sql,
+        pg::Pg,
+        result::Error,
+        sql_types::{Nullable, Text},
+    };
+
+    use crate::model::{api_request::SavedViewDefinition, api_response::SavedView};
+
+    /// Prefix of every saved view's relation, keeping them apart from the service's tables
+    const RELATION_PREFIX: &str = "saved_";
+
+    /// The `SELECT` of the aggregation query for `definition`, its parameters written out
+    /// as literals since views take none
+    pub fn view_definition(definition: &SavedViewDefinition) -> QueryResult<String> {
+        let (from_date, to_date) = definition
+            .datetime_filter
+            .as_ref()
+            .map(|range| range.half_open())
+            .unwrap_or_default();
+        let timezone = definition.timezone.unwrap_or(Tz::UTC);
+        let having = definition.having.clone().unwrap_or_default();
+        let query = super::query::aggregation_query(
+            definition.aggregation_kind,
+            from_date,
+            to_date,
+            definition.series_id,
+            &having,
+            timezone,
+        );
+
+        // Parameters in the order the query binds them: the bucket's zone, period and zone,
+        // the range, the series and each bound of the total twice
+        let zone = text_literal(timezone.name());
+        let mut literals = vec![
+            zone.clone(),
+            text_literal(<&str>::from(definition.aggregation_kind)),
+            zone,
+        ];
+        literals.extend(from_date.map(timestamptz_literal));
+        literals.extend(to_date.map(timestamptz_literal));
+        literals.extend(definition.series_id.map(|id| format!("{id}::BIGINT")));
+        let bounds = having.bounds();
+        if bounds.iter().any(Option::is_some) {
+            for bound in bounds {
+                let literal = match bound {
+                    Some(bound) => format!("'{bound}'::NUMERIC"),
+                    None => "NULL::NUMERIC".to_string(),
+                };
+                literals.extend([literal.clone(), literal]);
+            }
+        }
+
+        let debug = diesel::debug_query::<Pg, _>(&query).to_string();
+        let statement = debug.split(" -- binds: ").next().unwrap_or_default();
+        inline_parameters(statement, &literals)
+    }
+
+    /// Replaces each `$n` placeholder of `statement` with the `n`th of `literals`, failing
+    /// unless every literal is used
+    fn inline_parameters(statement: &str, literals: &[String]) -> QueryResult<String> {
+        let mismatch = || {
+            Error::QueryBuilderError(
+                format!(
+                    "view parameters do not match the query's, {} given",
+                    literals.len()
+                )
+                .into(),
+            )
+        };
+        let mut inlined = String::with_capacity(statement.len());
+        let mut used = vec![false; literals.len()];
+        let mut rest = statement;
+        while let Some(start) = rest.find('$') {
+            inlined.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let index = rest[..digits]
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .filter(|&index| index < literals.len())
+                .ok_or_else(mismatch)?;
+            inlined.push_str(&literals[index]);
+            used[index] = true;
+            rest = &rest[digits..];
+        }
+        inlined.push_str(rest);
+        if !used.iter().all(|&used| used) {
+            return Err(mismatch());
+        }
+        Ok(inlined)
+    }
+
+    fn text_literal(value: &str) -> String {
+        format!("'{}'::TEXT", value.replace('\'', "''"))
+    }
+
+    fn timestamptz_literal(value: DateTime<Utc>) -> String {
+        format!(
+            "'{}'::TIMESTAMPTZ",
+            value.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+        )
+    }
+
+    /// Creates the view `name` holding `definition`, replacing any view saved under that
+    /// name before so saving again refreshes a materialized view
+    pub fn save_view(
+        name: &str,
+        definition: &SavedViewDefinition,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<SavedView> {
+        let select = view_definition(definition)?;
+        let relation = format!("renewable.{RELATION_PREFIX}{name}");
+        conn.transaction(|conn| {
+            let existing: Option<String> = diesel::select(
+                sql::<Nullable<Text>>(
+                    "(SELECT relkind::TEXT FROM pg_class WHERE oid = to_regclass(",
+                )
+                .bind::<Text, _>(&relation)
+                .sql("))"),
+            )
+            .get_result(conn)?;
+            let kind = if definition.materialized {
+                "MATERIALIZED VIEW"
+            } else {
+                "VIEW"
+            };
+            let create = match existing.as_deref() {
+                // A plain view is replaced in place, keeping the objects built on it
+                Some("v") if !definition.materialized => "CREATE OR REPLACE",
+                Some("v") => {
+                    diesel::sql_query(format!("DROP VIEW {relation}")).execute(conn)?;
+                    "CREATE"
+                }
+                Some("m") => {
+                    diesel::sql_query(format!("DROP MATERIALIZED VIEW {relation}"))
+                        .execute(conn)?;
+                    "CREATE"
+                }
+                _ => "CREATE",
+            };
+            diesel::sql_query(format!(
+                "{create} {kind} {relation} (datetime, total_amount) AS {select}"
+            ))
+            .execute(conn)?;
+            Ok(SavedView {
+                name: name.to_string(),
+                relation,
+                materialized: definition.materialized,
+                definition: select,
+            })
+        })
+    }
+}
+
+/// Experimental delta-of-delta storage, see [`crate::delta_block`]. Blocks are written
+/// alongside `ts_store`, and the raw readings read path decodes them in place of the rows
+/// of every compressed ingestion.
+#[cfg(feature = "compressed-storage")]
+pub mod compressed_storage {
+    use chrono::Utc;
+    use diesel::{
+        Connection as _, ExpressionMethods as _, QueryDsl as _, QueryResult, RunQueryDsl as _,
+        SelectableHelper as _,
+        dsl::{IntoBoxed, sql},
+        pg::{Pg, PgRowByRowLoadingMode},
+        result::Error,
+        sql_types::BigInt,
+    };
+
+    use super::query::hand_over;
+    use crate::{
+        delta_block,
+        model::{
+            csv::CSVRecord,
+            database::{CompressedBlock, CompressionReport, IngestionStatus, TSStore},
+        },
+        renewable_schema::{ts_compressed_blocks, ts_metadata, ts_store},
+    };
+
+    /// Blocks inserted per statement, well inside the bind parameter limit
+    const INSERT_CHUNK: usize = 1000;
+
+    fn block_error(e: delta_block::BlockError) -> Error {
+        Error::DeserializationError(Box::new(e))
+    }
+
+    /// Packs the readings of one ingestion into a block per UTC day, replacing any blocks
+    /// written before
+    pub fn compress_ingestion(
+        ingestion_id: i64,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<CompressionReport> {
+        conn.transaction(|conn| {
+            let rows: Vec<TSStore> = ts_store::table
+                .filter(ts_store::ingestion_id.eq(ingestion_id))
+                .order_by(ts_store::datetime)
+                .load(conn)?;
+
+            let mut blocks = Vec::new();
+            for day in rows.chunk_by(|a, b| a.datetime.date_naive() == b.datetime.date_naive()) {
+                let readings: Vec<_> = day
+                    .iter()
+                    .map(|row| CSVRecord {
+                        datetime: row.datetime,
+                        amount: row.amount.clone(),
+                        extra: None,
+                    })
+                    .collect();
+                let (Some(first), Some(last)) = (day.first(), day.last()) else {
+                    continue;
+                };
+                blocks.push(CompressedBlock {
+                    ingestion_id,
+                    day: first.datetime.date_naive(),
+                    first_datetime: first.datetime,
+                    last_datetime: last.datetime,
+                    readings: i32::try_from(day.len()).unwrap_or(i32::MAX),
+                    recorded_at: day
+                        .iter()
+                        .map(|row| row.recorded_at)
+                        .min()
+                        .unwrap_or_else(Utc::now),
+                    block: delta_block::encode(&readings)
+                        .map_err(|e| Error::SerializationError(Box::new(e)))?,
+                });
+            }
+
+            diesel::delete(
+                ts_compressed_blocks::table
+                    .filter(ts_compressed_blocks::ingestion_id.eq(ingestion_id)),
+            )
+            .execute(conn)?;
+            for chunk in blocks.chunks(INSERT_CHUNK) {
+                diesel::insert_into(ts_compressed_blocks::table)
+                    .values(chunk)
+                    .execute(conn)?;
+            }
+
+            let row_bytes = ts_store::table
+                .filter(ts_store::ingestion_id.eq(ingestion_id))
+                .select(sql::<BigInt>(
+                    "COALESCE(SUM(pg_column_size(ts_store.*)), 0)::BIGINT",
+                ))
+                .get_result(conn)?;
+            let block_bytes = ts_compressed_blocks::table
+                .filter(ts_compressed_blocks::ingestion_id.eq(ingestion_id))
+                .select(sql::<BigInt>(
+                    "COALESCE(SUM(pg_column_size(ts_compressed_blocks.*)), 0)::BIGINT",
+                ))
+                .get_result(conn)?;
+            Ok(CompressionReport {
+                ingestion_id,
+                blocks: blocks.len(),
+                readings: rows.len(),
+                row_bytes,
+                block_bytes,
+            })
+        })
+    }
+
+    /// Compresses every complete ingestion without blocks, oldest first
+    pub fn compress_archive(
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<CompressionReport>> {
+        let compressed = ts_compressed_blocks::table
+            .select(ts_compressed_blocks::ingestion_id)
+            .distinct();
+        let pending: Vec<i64> = ts_metadata::table
+            .filter(ts_metadata::status.eq(IngestionStatus::Complete))
+            .filter(ts_metadata::ingestion_id.ne_all(compressed))
+            .select(ts_metadata::ingestion_id)
+            .order_by(ts_metadata::ingestion_id)
+            .load(conn)?;
+        pending
+            .into_iter()
+            .map(|ingestion_id| compress_ingestion(ingestion_id, conn))
+            .collect()
+    }
+
+    /// [`query_readings`](super::query::query_readings) with compressed ingestions decoded
+    /// from their blocks
+    pub fn query_readings(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<TSStore>> {
+        let mut readings: Vec<TSStore> = uncompressed_readings(from_date, to_date).load(conn)?;
+        readings.extend(decoded_readings(from_date, to_date, conn)?);
+        readings.sort_by_key(|reading| (reading.datetime, reading.ingestion_id));
+        Ok(readings)
+    }
+
+    /// [`stream_readings`](super::query::stream_readings) with compressed ingestions
+    /// decoded from their blocks. The blocks in range are decoded up front, their readings
+    /// merged in order into the rows streamed from `ts_store`.
+    pub fn stream_readings(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+        each: impl FnMut(TSStore) -> bool,
+    ) -> QueryResult<usize> {
+        let mut decoded = decoded_readings(from_date, to_date, conn)?
+            .into_iter()
+            .peekable();
+        let key = |reading: &TSStore| (reading.datetime, reading.ingestion_id);
+        let mut rows = uncompressed_readings(from_date, to_date)
+            .load_iter::<TSStore, PgRowByRowLoadingMode>(conn)?
+            .peekable();
+        let merged = std::iter::from_fn(|| match (rows.peek(), decoded.peek()) {
+            (Some(Ok(row)), Some(reading)) if key(reading) < key(row) => decoded.next().map(Ok),
+            (Some(_), _) => rows.next(),
+            (None, _) => decoded.next().map(Ok),
+        });
+        hand_over(merged, each)
+    }
+
+    /// Rows of `ts_store` within the range left uncompressed, ordered by timestamp then
+    /// ingestion
+    fn uncompressed_readings<'a>(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+    ) -> IntoBoxed<'a, ts_store::table, Pg> {
+        let compressed = ts_compressed_blocks::table
+            .select(ts_compressed_blocks::ingestion_id)
+            .distinct();
+        let mut rows = ts_store::table
+            .filter(ts_store::ingestion_id.ne_all(compressed))
+            .into_boxed();
+        if let Some(from) = from_date {
+            rows = rows.filter(ts_store::datetime.ge(from));
+        }
+        if let Some(to) = to_date {
+            rows = rows.filter(ts_store::datetime.lt(to));
+        }
+        rows.order_by((ts_store::datetime, ts_store::ingestion_id))
+    }
+
+    /// Readings within the range decoded from the blocks of compressed ingestions, ordered
+    /// by timestamp then ingestion
+    fn decoded_readings(
+        from_date: Option<chrono::DateTime<Utc>>,
+        to_date: Option<chrono::DateTime<Utc>>,
+        conn: &mut diesel::PgConnection,
+    ) -> QueryResult<Vec<TSStore>> {
+        let mut blocks = ts_compressed_blocks::table.into_boxed();
+        if let Some(from) = from_date {
+            blocks = blocks.filter(ts_compressed_blocks::last_datetime.ge(from));
+        }
+        if let Some(to) = to_date {
+            blocks = blocks.filter(ts_compressed_blocks::first_datetime.lt(to));
+        }
+
+        let in_range = |datetime: &chrono::DateTime<Utc>| {
+            from_date.is_none_or(|from| *datetime >= from)
+                && to_date.is_none_or(|to| *datetime < to)
+        };
+        let mut readings = Vec::new();
+        for block in blocks.select(CompressedBlock::as_select()).load(conn)? {
+            let decoded = delta_block::decode(&block.block).map_err(block_error)?;
+            readings.extend(
+                decoded
+                    .into_iter()
+                    .filter(|reading| in_range(&reading.datetime))
+                    .map(|reading| TSStore {
+                        ingestion_id: block.ingestion_id,
+                        datetime: reading.datetime,
+                        amount: reading.amount,
+                        recorded_at: block.recorded_at,
+                        extra: None,
+                    }),
+            );
+        }
+        readings.sort_by_key(|reading| (reading.datetime, reading.ingestion_id));
+        Ok(readings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, time::Duration as StdDuration};
+
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Tz};
+    use diesel::{
+        Connection, ExpressionMethods as _, PgConnection, QueryDsl as _, RunQueryDsl, pg::Pg,
+    };
+    use serial_test::serial;
+    use test_case::test_case;
+
+    use crate::{
+        columnar,
+        db::{
+            PoolTuning,
+            api_keys::{ensure_api_key, find_active_key, revoke_api_key},
+            build_pool,
+            export_jobs::{
+                create_export_job, get_export_job, mark_export_complete, mark_export_failed,
+                mark_export_running,
+            },
+            health::replication_lag_seconds,
+            is_statement_timeout,
+            legal_holds::{audit_entries, find_hold, place_hold, release_hold},
+            meters::{load_meter_profile, onboard_meters, replace_meter_profile},
+            partitions::ensure_ts_store_partitions,
+            query::{
+                DEFAULT_HISTORY_LIMIT, aggregate_ts_query, aggregate_ts_query_having,
+                aggregation_query, bucket_energy, bucket_point_counts, bucket_sources,
+                delete_ingestion, diff_ts_query, fuel_type_breakdown, ingestion_ranks,
+                insert_query_history, load_recent_window, monthly_actuals, multi_range_ts_query,
+                query_clock_drift, query_clock_drifts, query_ingestions, query_lineage,
+                query_readings, query_request_history, source_ingested, stream_readings,
+                stream_ts_query,
+            },
+            query_jobs::{
+                create_query_job, get_query_job, mark_query_complete, mark_query_failed,
+                mark_query_running,
+            },
+            retention::purge_before,
+            seed_database::{
+                check_units, flag_unit_mismatch, insert_ingestion, insert_ingestion_with_drift,
+                prepare_readings, record_clock_drift,
+            },
+            series::{
+                create_series, delete_series, find_series_id, get_series, list_series,
+                update_series,
+            },
+            storage_stats::{record_storage_sample, storage_samples_since},
+            views::save_view,
+            with_statement_timeout,
+        },
+        engine,
+        model::{
+            api_request::{
+                Aggregation, HistoryFilter, MeterOnboarding, ProfileMonth, RangeEnd,
+                SavedViewDefinition, SeriesDefinition, TotalFilter,
+            },
+            api_response::AggregationQueryRecord,
+            csv::CSVRecord,
+            database::{
+                IngestionClockDrift, IngestionStatus, JobStatus, QueryHistory, QueryStatus,
+                RetentionPurge, TSStore,
+            },
+        },
+        offline,
+        renewable_schema::{
+            api_keys, audit_log, export_jobs, legal_holds, meters, query_history, query_jobs,
+            series, storage_stats, ts_daily_summary, ts_metadata, ts_monthly_summary, ts_store,
+        },
+        schema_check,
+    };
+
+    fn get_test_connection() -> PgConnection {
+        dotenvy::dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        PgConnection::establish(&database_url).expect("Failed to connect to database")
+    }
+
+    fn cleanup_tables(conn: &mut PgConnection) {
+        diesel::delete(legal_holds::table).execute(conn).unwrap();
+        diesel::delete(audit_log::table).execute(conn).unwrap();
+        diesel::delete(export_jobs::table).execute(conn).unwrap();
+        diesel::delete(query_jobs::table).execute(conn).unwrap();
+        diesel::delete(api_keys::table).execute(conn).unwrap();
+        diesel::delete(meters::table).execute(conn).unwrap();
+        diesel::delete(query_history::table).execute(conn).unwrap();
+        diesel::delete(ts_store::table).execute(conn).unwrap();
+        diesel::delete(ts_metadata::table).execute(conn).unwrap();
+        diesel::delete(series::table).execute(conn).unwrap();
+    }
+
+    fn seed_ts_metadata(conn: &mut PgConnection) -> i64 {
+        use crate::model::database::TSMetadata;
+
+        diesel::insert_into(ts_metadata::table)
+            .values(TSMetadata::new("test_source".to_string()))
+            .returning(ts_metadata::ingestion_id)
+            .get_result::<i64>(conn)
+            .unwrap()
+    }
+
+    fn seed_ts_data(conn: &mut PgConnection, ingestion_id: i64) {
+        let base_date = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
+        let records: Vec<TSStore> = (0..48)
+            .map(|i| TSStore {
+                ingestion_id,
+                datetime: base_date + Duration::hours(i),
+                amount: BigDecimal::from(100 * (i + 1)),
+                recorded_at: Utc::now(),
+                extra: None,
+            })
+            .collect();
+
+        diesel::insert_into(ts_store::table)
+            .values(&records)
+            .execute(conn)
+            .expect("Failed to seed ts_store");
+    }
+
+    /// Raw Parquet export of every stored reading, ranked, written to a scratch file named
+    /// after `name`
+    fn archive_readings(name: &str, conn: &mut PgConnection) -> std::path::PathBuf {
+        let ranks = ingestion_ranks(conn).unwrap();
+        let path = env::temp_dir().join(format!("{name}-{}.parquet", std::process::id()));
+        columnar::write_parquet(
+            std::fs::File::create(&path).unwrap(),
+            columnar::readings_schema(),
+            &query_readings(None, None, conn).unwrap(),
+            |readings| columnar::readings_batch(readings, &ranks),
+        )
+        .unwrap();
+        path
+    }
+
+    /// Ingestions overlapping those of [`seed_ts_data`]: one of the same series outranking
+    /// it for part of its readings, one it outranks running past its last reading, and one
+    /// of another series summed alongside it
+    fn seed_overlapping_ingestions(conn: &mut PgConnection) {
+        let base_date = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
+        let readings = |hours: std::ops::Range<i64>, amount: i64| {
+            hours
+                .map(|i| CSVRecord {
+                    datetime: base_date + Duration::hours(i),
+                    amount: BigDecimal::from(amount),
+                    extra: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let other = create_series(series_definition("other"), conn).unwrap().id;
+        for (source, series_id, priority, hours, amount) in [
+            ("final", None, 10, 5..20, 7),
+            ("provisional", None, -1, 40..60, 3),
+            ("other", Some(other), 0, 10..30, 11),
+        ] {
+            insert_ingestion(
+                source.to_string(),
+                series_id,
+                priority,
+                readings(hours, amount),
+                0,
+                conn,
+            )
+            .unwrap();
+        }
+    }
+
+    fn test_from_date() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap()
+    }
+
+    fn test_to_date() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 16, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_request_history_respects_limit_and_ordering() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let entries: Vec<_> = (0..15)
+            .map(|_| QueryHistory::new(None, None, Aggregation::Hourly, None))
+            .collect();
+        assert_eq!(insert_query_history(&entries, &mut conn).unwrap(), 15);
+
+        let result = query_request_history(
+            DEFAULT_HISTORY_LIMIT,
+            None,
+            &HistoryFilter::default(),
+            &mut conn,
+        );
+        assert!(result.is_ok());
+        let history = result.unwrap();
+        assert_eq!(history.len(), DEFAULT_HISTORY_LIMIT as usize);
+
+        for i in 0..history.len() - 1 {
+            assert!(history[i].executed_at >= history[i + 1].executed_at);
+        }
+
+        // Entries batched in one statement can share a timestamp, the id breaks the tie
+        let last = history.last().unwrap();
+        let rest = query_request_history(
+            100,
+            Some((last.executed_at, last.id)),
+            &HistoryFilter::default(),
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(rest.len(), 15 - history.len());
+        assert!(
+            rest.iter()
+                .all(|entry| history.iter().all(|seen| seen.id != entry.id))
+        );
+
+        let mut failed = QueryHistory::new(None, None, Aggregation::Yearly, None);
+        failed.duration_ms = Some(2_000);
+        failed.status = QueryStatus::Failed;
+        insert_query_history(&[failed], &mut conn).unwrap();
+        let latest =
+            &query_request_history(1, None, &HistoryFilter::default(), &mut conn).unwrap()[0];
+        assert_eq!(
+            (latest.duration_ms, latest.row_count, latest.status),
+            (Some(2_000), None, QueryStatus::Failed)
+        );
+
+        let matching = |filter: HistoryFilter, conn: &mut PgConnection| {
+            query_request_history(100, None, &filter, conn)
+                .unwrap()
+                .len()
+        };
+        let failed = HistoryFilter {
+            status: Some(QueryStatus::Failed),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(matching(failed.clone(), &mut conn), 1);
+        let hourly = HistoryFilter {
+            aggregation: Some(Aggregation::Hourly),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(matching(hourly.clone(), &mut conn), 15);
+        assert_eq!(
+            matching(
+                HistoryFilter {
+                    aggregation: Some(Aggregation::Hourly),
+                    ..failed
+                },
+                &mut conn
+            ),
+            0
+        );
+        // Executed within the window, from inclusive and to exclusive
+        let executed_at = latest.executed_at;
+        let window = |executed_from, executed_to| HistoryFilter {
+            executed_from,
+            executed_to,
+            ..HistoryFilter::default()
+        };
+        assert_eq!(matching(window(Some(executed_at), None), &mut conn), 1);
+        assert_eq!(matching(window(None, Some(executed_at)), &mut conn), 15);
+        assert_eq!(
+            matching(
+                window(Some(Utc::now() + Duration::hours(1)), None),
+                &mut conn
+            ),
+            0
+        );
+        assert_eq!(
+            matching(
+                HistoryFilter {
+                    executed_to: Some(executed_at),
+                    ..hourly
+                },
+                &mut conn
+            ),
+            15
+        );
+    }
+
+    #[test_case(Aggregation::Hourly, None, None)]
     #[test_case(Aggregation::Hourly, Some(test_from_date()), None)]
     #[test_case(Aggregation::Hourly, None, Some(test_to_date()))]
     #[test_case(Aggregation::Hourly, Some(test_from_date()), Some(test_to_date()))]
@@ -299,41 +3697,2565 @@ mod tests {
     #[test_case(Aggregation::DayInMonth, Some(test_from_date()), None)]
     #[test_case(Aggregation::DayInMonth, None, Some(test_to_date()))]
     #[test_case(Aggregation::DayInMonth, Some(test_from_date()), Some(test_to_date()))]
+    #[test_case(Aggregation::Weekly, None, None)]
+    #[test_case(Aggregation::Weekly, Some(test_from_date()), None)]
+    #[test_case(Aggregation::Weekly, None, Some(test_to_date()))]
+    #[test_case(Aggregation::Weekly, Some(test_from_date()), Some(test_to_date()))]
     #[test_case(Aggregation::Monthly, None, None)]
     #[test_case(Aggregation::Monthly, Some(test_from_date()), None)]
     #[test_case(Aggregation::Monthly, None, Some(test_to_date()))]
     #[test_case(Aggregation::Monthly, Some(test_from_date()), Some(test_to_date()))]
+    #[test_case(Aggregation::Quarterly, None, None)]
+    #[test_case(Aggregation::Quarterly, Some(test_from_date()), None)]
+    #[test_case(Aggregation::Quarterly, None, Some(test_to_date()))]
+    #[test_case(Aggregation::Quarterly, Some(test_from_date()), Some(test_to_date()))]
     #[test_case(Aggregation::Yearly, None, None)]
     #[test_case(Aggregation::Yearly, Some(test_from_date()), None)]
     #[test_case(Aggregation::Yearly, None, Some(test_to_date()))]
     #[test_case(Aggregation::Yearly, Some(test_from_date()), Some(test_to_date()))]
     #[serial]
-    fn test_aggregate_ts_query(
-        aggregation_kind: Aggregation,
-        from_date: Option<DateTime<Utc>>,
-        to_date: Option<DateTime<Utc>>,
-    ) {
+    fn test_aggregate_ts_query(
+        aggregation_kind: Aggregation,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+    ) {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let result = aggregate_ts_query(aggregation_kind, from_date, to_date, None, &mut conn);
+        assert!(result.is_ok());
+        let records = result.unwrap();
+
+        if from_date.is_some() || to_date.is_some() {
+            let unfiltered =
+                aggregate_ts_query(aggregation_kind, None, None, None, &mut conn).unwrap();
+            assert!(records.len() <= unfiltered.len());
+        }
+    }
+
+    #[test_case(None, None; "unbounded")]
+    #[test_case(Some("2024-01-15T13:30:00Z"), Some("2024-01-17T05:00:00Z"); "partial days at both ends")]
+    #[test_case(Some("2024-01-16T00:00:00Z"), Some("2024-01-17T00:00:00Z"); "whole day")]
+    #[test_case(Some("2024-01-16T02:00:00Z"), Some("2024-01-16T07:00:00Z"); "within one day")]
+    #[serial]
+    fn test_daily_summaries_match_readings(from_date: Option<&str>, to_date: Option<&str>) {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let first = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, first);
+        let second = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, second);
+        // Summaries follow deletes of part of a day
+        diesel::delete(
+            ts_store::table
+                .filter(ts_store::ingestion_id.eq(second))
+                .filter(ts_store::datetime.lt(Utc.with_ymd_and_hms(2024, 1, 16, 3, 0, 0).unwrap())),
+        )
+        .execute(&mut conn)
+        .unwrap();
+
+        let parse = |date: Option<&str>| date.map(|d| d.parse::<DateTime<Utc>>().unwrap());
+        let (from_date, to_date) = (parse(from_date), parse(to_date));
+        let buckets = |records: Vec<crate::model::api_response::AggregationQueryRecord>| {
+            records
+                .into_iter()
+                .map(|record| (record.datetime, record.total_amount))
+                .collect::<Vec<_>>()
+        };
+        // Any as-of timestamp reads ts_store directly
+        let from_readings = buckets(
+            aggregate_ts_query(
+                Aggregation::DayInMonth,
+                from_date,
+                to_date,
+                Some(Utc::now()),
+                &mut conn,
+            )
+            .unwrap(),
+        );
+        let from_summaries = buckets(
+            aggregate_ts_query(Aggregation::DayInMonth, from_date, to_date, None, &mut conn)
+                .unwrap(),
+        );
+        assert!(!from_readings.is_empty());
+        assert_eq!(from_summaries, from_readings);
+
+        let (readings, first_datetime) = ts_daily_summary::table
+            .filter(ts_daily_summary::ingestion_id.eq(second))
+            .select((
+                diesel::dsl::sum(ts_daily_summary::readings),
+                diesel::dsl::min(ts_daily_summary::first_datetime),
+            ))
+            .first::<(Option<BigDecimal>, Option<DateTime<Utc>>)>(&mut conn)
+            .unwrap();
+        assert_eq!(
+            (readings, first_datetime),
+            (
+                Some(BigDecimal::from(31)),
+                Some(Utc.with_ymd_and_hms(2024, 1, 16, 3, 0, 0).unwrap())
+            )
+        );
+    }
+
+    #[test_case(Aggregation::Monthly, None, None; "monthly unbounded")]
+    #[test_case(Aggregation::Monthly, Some("2024-01-31T05:00:00Z"), Some("2024-03-01T07:00:00Z"); "monthly partial months")]
+    #[test_case(Aggregation::Quarterly, Some("2024-02-01T00:00:00Z"), Some("2024-04-01T00:00:00Z"); "quarterly whole months")]
+    #[test_case(Aggregation::Yearly, Some("2024-02-10T00:00:00Z"), None; "yearly from a day")]
+    #[serial]
+    fn test_monthly_summaries_match_readings(
+        aggregation_kind: Aggregation,
+        from_date: Option<&str>,
+        to_date: Option<&str>,
+    ) {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 20, 1, 0, 0).unwrap();
+        for _ in 0..2 {
+            let ingestion_id = seed_ts_metadata(&mut conn);
+            let records: Vec<TSStore> = (0..320)
+                .map(|i| TSStore {
+                    ingestion_id,
+                    datetime: start + Duration::hours(6 * i),
+                    amount: BigDecimal::from(i + 1),
+                    recorded_at: Utc::now(),
+                    extra: None,
+                })
+                .collect();
+            diesel::insert_into(ts_store::table)
+                .values(&records)
+                .execute(&mut conn)
+                .unwrap();
+        }
+        // Months follow deletes of part of a day
+        diesel::delete(ts_store::table.filter(ts_store::datetime.between(
+            Utc.with_ymd_and_hms(2024, 2, 14, 3, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 2, 20, 0, 0, 0).unwrap(),
+        )))
+        .execute(&mut conn)
+        .unwrap();
+
+        let parse = |date: Option<&str>| date.map(|d| d.parse::<DateTime<Utc>>().unwrap());
+        let (from_date, to_date) = (parse(from_date), parse(to_date));
+        let buckets = |records: Vec<crate::model::api_response::AggregationQueryRecord>| {
+            records
+                .into_iter()
+                .map(|record| (record.datetime, record.total_amount))
+                .collect::<Vec<_>>()
+        };
+        let from_readings = buckets(
+            aggregate_ts_query(
+                aggregation_kind,
+                from_date,
+                to_date,
+                Some(Utc::now()),
+                &mut conn,
+            )
+            .unwrap(),
+        );
+        let from_summaries = buckets(
+            aggregate_ts_query(aggregation_kind, from_date, to_date, None, &mut conn).unwrap(),
+        );
+        assert!(!from_readings.is_empty());
+        assert_eq!(from_summaries, from_readings);
+
+        let months: i64 = ts_monthly_summary::table
+            .select(ts_monthly_summary::readings)
+            .load::<i64>(&mut conn)
+            .unwrap()
+            .into_iter()
+            .sum();
+        let readings: i64 = ts_store::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(months, readings);
+    }
+
+    #[test]
+    #[serial]
+    fn test_adjacent_ranges_count_boundary_reading_once() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let day = |d| Some(Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap());
+        let hours = |from, to, conn: &mut PgConnection| {
+            aggregate_ts_query(Aggregation::Hourly, from, to, None, conn)
+                .unwrap()
+                .len()
+        };
+        // Readings run hourly from 10:00 on the 15th, so the 16th starts on a reading
+        assert_eq!(hours(day(15), day(16), &mut conn), 14);
+        assert_eq!(hours(day(16), day(17), &mut conn), 24);
+        assert_eq!(hours(day(15), day(17), &mut conn), 38);
+
+        let inclusive = RangeEnd::Inclusive.exclusive_end(day(16));
+        assert_eq!(hours(day(15), inclusive, &mut conn), 15);
+        let readings = query_readings(day(16), inclusive, &mut conn).unwrap();
+        assert_eq!(readings.len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_multi_range_query_matches_each_range_alone() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let day = |d| Some(Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap());
+        // Overlapping ranges each count the shared readings, an empty range yields nothing
+        let ranges = [
+            (day(15), day(16)),
+            (Some(test_from_date()), Some(test_to_date())),
+            (day(20), day(21)),
+            (None, None),
+        ];
+        let buckets = multi_range_ts_query(
+            Aggregation::DayInMonth,
+            &ranges,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+
+        for (index, &(from_date, to_date)) in ranges.iter().enumerate() {
+            let tagged: Vec<_> = buckets
+                .iter()
+                .filter(|bucket| bucket.range_index == index as i64)
+                .map(|bucket| (bucket.datetime, bucket.total_amount.clone()))
+                .collect();
+            let mut alone: Vec<_> = aggregate_ts_query(
+                Aggregation::DayInMonth,
+                from_date,
+                to_date,
+                Some(Utc::now()),
+                &mut conn,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.datetime, record.total_amount))
+            .collect();
+            alone.sort_by_key(|(datetime, _)| *datetime);
+            assert_eq!(tagged, alone, "range {index}");
+        }
+        assert_eq!(buckets.len(), 1 + 2 + 3);
+    }
+
+    #[test]
+    #[serial]
+    fn test_multi_range_query_honours_timezone_series_and_having() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let series_id = create_series(series_definition("north"), &mut conn)
+            .unwrap()
+            .id;
+        let attributed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, attributed);
+        diesel::update(ts_metadata::table.find(attributed))
+            .set(ts_metadata::series_id.eq(series_id))
+            .execute(&mut conn)
+            .unwrap();
+        let unattributed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, unattributed);
+
+        let day = |d| Some(Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap());
+        let ranges = [(day(15), day(17)), (None, None)];
+        // Only the first New York day, its readings running to 04:00 UTC, totals below this
+        let having = TotalFilter {
+            le: Some(20000.0),
+            ..TotalFilter::default()
+        };
+        for (series_id, having) in [
+            (None, TotalFilter::default()),
+            (Some(series_id), TotalFilter::default()),
+            (Some(series_id), having),
+        ] {
+            let buckets = multi_range_ts_query(
+                Aggregation::DayInMonth,
+                &ranges,
+                None,
+                series_id,
+                &having,
+                New_York,
+                &mut conn,
+            )
+            .unwrap();
+            for (index, &(from_date, to_date)) in ranges.iter().enumerate() {
+                let tagged: Vec<_> = buckets
+                    .iter()
+                    .filter(|bucket| bucket.range_index == index as i64)
+                    .map(|bucket| (bucket.datetime, bucket.total_amount.clone()))
+                    .collect();
+                let alone: Vec<_> = aggregate_ts_query_having(
+                    Aggregation::DayInMonth,
+                    from_date,
+                    to_date,
+                    Some(Utc::now()),
+                    series_id,
+                    &having,
+                    New_York,
+                    &mut conn,
+                )
+                .unwrap()
+                .into_iter()
+                .map(|record| (record.datetime, record.total_amount))
+                .collect();
+                assert_eq!(tagged, alone, "range {index} of series {series_id:?}");
+            }
+            let screened = having.le.is_some();
+            assert_eq!(buckets.len(), if screened { 2 } else { 2 + 3 });
+        }
+
+        // Days start at midnight in New York, 05:00 UTC in January
+        let starts: Vec<_> = multi_range_ts_query(
+            Aggregation::DayInMonth,
+            &ranges[..1],
+            None,
+            None,
+            &TotalFilter::default(),
+            New_York,
+            &mut conn,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|bucket| bucket.datetime)
+        .collect();
+        assert_eq!(
+            starts,
+            [
+                Utc.with_ymd_and_hms(2024, 1, 15, 5, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 1, 16, 5, 0, 0).unwrap(),
+            ]
+        );
+    }
+
+    fn series_definition(name: &str) -> SeriesDefinition {
+        SeriesDefinition {
+            name: name.to_string(),
+            unit: "kWh".to_string(),
+            fuel_type: Some("solar".to_string()),
+            site: None,
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_migrated_schema_matches_snapshot() {
+        let mut conn = get_test_connection();
+        assert_eq!(schema_check::check(&mut conn).unwrap(), []);
+
+        diesel::sql_query("CREATE INDEX hand_made ON renewable.ts_store(amount)")
+            .execute(&mut conn)
+            .unwrap();
+        let drift = schema_check::check(&mut conn);
+        diesel::sql_query("DROP INDEX renewable.hand_made")
+            .execute(&mut conn)
+            .unwrap();
+        assert!(matches!(
+            drift.unwrap().as_slice(),
+            [schema_check::Drift::ExtraIndex { name, table, .. }]
+                if name == "hand_made" && table == "ts_store"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_partitions_take_their_months_readings_from_the_default() {
+        #[derive(diesel::QueryableByName)]
+        struct Placement {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            partition: String,
+        }
+        let placements = |conn: &mut PgConnection| {
+            diesel::sql_query(
+                "SELECT tableoid::regclass::TEXT AS partition FROM renewable.ts_store \
+                 WHERE datetime >= '2099-05-01' ORDER BY datetime",
+            )
+            .load::<Placement>(conn)
+            .unwrap()
+            .into_iter()
+            .map(|placement| placement.partition)
+            .collect::<Vec<_>>()
+        };
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        let readings: Vec<TSStore> = ["2099-05-31T23:00:00Z", "2099-06-01T00:00:00Z"]
+            .into_iter()
+            .map(|datetime| TSStore {
+                ingestion_id,
+                datetime: datetime.parse().unwrap(),
+                amount: BigDecimal::from(1),
+                recorded_at: Utc::now(),
+                extra: None,
+            })
+            .collect();
+        diesel::insert_into(ts_store::table)
+            .values(&readings)
+            .execute(&mut conn)
+            .unwrap();
+        assert_eq!(
+            placements(&mut conn),
+            ["renewable.ts_store_default", "renewable.ts_store_default"]
+        );
+
+        let now = "2099-05-31T12:00:00Z".parse().unwrap();
+        assert_eq!(ensure_ts_store_partitions(now, 1, &mut conn).unwrap(), 2);
+        assert_eq!(ensure_ts_store_partitions(now, 1, &mut conn).unwrap(), 0);
+        assert_eq!(
+            placements(&mut conn),
+            ["renewable.ts_store_2099_05", "renewable.ts_store_2099_06"]
+        );
+        // The daily summaries still account for the moved readings
+        let (from, to) = (
+            "2099-05-01T00:00:00Z".parse().unwrap(),
+            "2099-07-01T00:00:00Z".parse().unwrap(),
+        );
+        let monthly =
+            aggregate_ts_query(Aggregation::Monthly, Some(from), Some(to), None, &mut conn)
+                .unwrap();
+        assert_eq!(monthly.len(), 2);
+
+        cleanup_tables(&mut conn);
+        diesel::sql_query("DROP TABLE renewable.ts_store_2099_05, renewable.ts_store_2099_06")
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_retention_drops_expired_months_and_deletes_the_rest() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        ensure_ts_store_partitions(test_from_date(), 0, &mut conn).unwrap();
+        seed_ts_data(&mut conn, ingestion_id);
+        diesel::insert_into(ts_store::table)
+            .values(TSStore {
+                ingestion_id,
+                datetime: Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0).unwrap(),
+                amount: BigDecimal::from(1),
+                recorded_at: Utc::now(),
+                extra: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        insert_query_history(
+            &[QueryHistory::new(None, None, Aggregation::Monthly, None)],
+            &mut conn,
+        )
+        .unwrap();
+        let cutoff = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+
+        let due = purge_before(cutoff, true, &mut conn).unwrap();
+        assert_eq!(
+            (due.readings, due.query_history, due.partitions),
+            (49, 0, 0)
+        );
+        assert_eq!(
+            ts_store::table
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            49
+        );
+
+        let purged = purge_before(cutoff, false, &mut conn).unwrap();
+        assert_eq!(
+            purged,
+            RetentionPurge {
+                readings: 49,
+                query_history: 0,
+                partitions: 1,
+                held: 0,
+            }
+        );
+        assert_eq!(
+            ts_store::table
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            0
+        );
+        // Including the summaries of the readings dropped with their partition
+        let summaries = ts_daily_summary::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .unwrap();
+        assert_eq!(summaries, 0);
+        let summaries = ts_monthly_summary::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .unwrap();
+        assert_eq!(summaries, 0);
+        assert_eq!(
+            query_history::table
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            1
+        );
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_legal_hold_keeps_readings_from_retention_and_deletion() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let held = seed_ts_metadata(&mut conn);
+        let expiring = seed_ts_metadata(&mut conn);
+        ensure_ts_store_partitions(test_from_date(), 0, &mut conn).unwrap();
+        seed_ts_data(&mut conn, held);
+        seed_ts_data(&mut conn, expiring);
+
+        assert!(
+            place_hold(i64::MAX, "unknown", &mut conn)
+                .unwrap()
+                .is_none()
+        );
+        let hold = place_hold(held, "Dispute 2025-014", &mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(find_hold(held, &mut conn).unwrap(), Some(hold));
+        // The database refuses to delete a held ingestion whatever the caller checked
+        assert!(delete_ingestion(held, &mut conn).is_err());
+
+        let cutoff = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let due = purge_before(cutoff, true, &mut conn).unwrap();
+        assert_eq!((due.readings, due.held), (48, 48));
+        let purged = purge_before(cutoff, false, &mut conn).unwrap();
+        assert_eq!(
+            (purged.readings, purged.partitions, purged.held),
+            (48, 0, 48)
+        );
+        let remaining: Vec<i64> = ts_store::table
+            .select(ts_store::ingestion_id)
+            .distinct()
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(remaining, [held]);
+
+        assert!(release_hold(held, &mut conn).unwrap().is_some());
+        assert!(release_hold(held, &mut conn).unwrap().is_none());
+        let actions: Vec<_> = audit_entries(Some(held), 10, &mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.action, entry.permitted))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("legal_hold_released".to_string(), true),
+                ("retention_purge".to_string(), false),
+                ("legal_hold_placed".to_string(), true),
+            ]
+        );
+        assert!(delete_ingestion(held, &mut conn).unwrap().is_some());
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_storage_sample_adds_partitions_to_their_table() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        ensure_ts_store_partitions(test_from_date(), 0, &mut conn).unwrap();
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let today = Utc::now().date_naive();
+        let sampled = record_storage_sample(today, &mut conn).unwrap();
+        // Sampling again the same day replaces the day's sample
+        assert_eq!(record_storage_sample(today, &mut conn).unwrap(), sampled);
+        let samples = storage_samples_since(today, &mut conn).unwrap();
+        assert_eq!(samples.len(), sampled);
+
+        let ts_store = samples.iter().find(|s| s.relation == "ts_store").unwrap();
+        let partitions: Vec<_> = samples
+            .iter()
+            .filter(|s| s.parent.as_deref() == Some("ts_store"))
+            .collect();
+        assert!(partitions.iter().any(|s| s.relation == "ts_store_default"));
+        assert_eq!(
+            ts_store.total_bytes,
+            partitions.iter().map(|s| s.total_bytes).sum::<i64>()
+        );
+        assert!(ts_store.total_bytes > 0 && ts_store.index_bytes > 0);
+
+        diesel::delete(storage_stats::table)
+            .execute(&mut conn)
+            .unwrap();
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_series_crud() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let created = create_series(series_definition("north"), &mut conn).unwrap();
+        assert!(matches!(
+            create_series(series_definition("north"), &mut conn),
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _
+            ))
+        ));
+        create_series(series_definition("east"), &mut conn).unwrap();
+        let names: Vec<_> = list_series(&mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|series| series.name)
+            .collect();
+        assert_eq!(names, ["east", "north"]);
+
+        let renamed = update_series(created.id, series_definition("south"), &mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(get_series(created.id, &mut conn).unwrap(), Some(renamed));
+        assert_eq!(
+            find_series_id("south", &mut conn).unwrap(),
+            Some(created.id)
+        );
+        assert_eq!(find_series_id("north", &mut conn).unwrap(), None);
+
+        // Ingestions keep their series until they are deleted
+        let readings = vec![CSVRecord {
+            datetime: test_from_date(),
+            amount: BigDecimal::from(1),
+            extra: None,
+        }];
+        let (ingestion_id, _) = insert_ingestion(
+            "south".to_string(),
+            Some(created.id),
+            0,
+            readings,
+            0,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(delete_series(created.id, &mut conn).is_err());
+        delete_ingestion(ingestion_id, &mut conn).unwrap();
+        assert!(delete_series(created.id, &mut conn).unwrap().is_some());
+        assert_eq!(delete_series(created.id, &mut conn).unwrap(), None);
+        assert_eq!(
+            update_series(created.id, series_definition("west"), &mut conn).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_series_filter_limits_every_query_path() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let series_id = create_series(series_definition("north"), &mut conn)
+            .unwrap()
+            .id;
+        let attributed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, attributed);
+        diesel::update(ts_metadata::table.find(attributed))
+            .set(ts_metadata::series_id.eq(series_id))
+            .execute(&mut conn)
+            .unwrap();
+        // Unattributed readings at the same timestamps double every unfiltered total
+        let unattributed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, unattributed);
+
+        let totals = |kind, as_recorded_by, series_id, conn: &mut PgConnection| {
+            aggregate_ts_query_having(
+                kind,
+                None,
+                None,
+                as_recorded_by,
+                series_id,
+                &TotalFilter::default(),
+                Tz::UTC,
+                conn,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|record| record.total_amount.unwrap())
+            .sum::<BigDecimal>()
+        };
+        // Daily summaries, ts_store and the hourly path all honour the filter
+        for (kind, as_recorded_by) in [
+            (Aggregation::DayInMonth, None),
+            (Aggregation::DayInMonth, Some(Utc::now())),
+            (Aggregation::Hourly, None),
+        ] {
+            let all = totals(kind, as_recorded_by, None, &mut conn);
+            let north = totals(kind, as_recorded_by, Some(series_id), &mut conn);
+            assert_eq!(all, north.clone() * BigDecimal::from(2), "{kind:?}");
+        }
+
+        let energy = bucket_energy(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            Some(series_id),
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(energy.iter().map(|bucket| bucket.readings).sum::<i64>(), 48);
+        let lineage = query_lineage(None, None, None, Some(series_id), &mut conn).unwrap();
+        assert_eq!(
+            lineage
+                .iter()
+                .map(|ingestion| ingestion.ingestion_id)
+                .collect::<Vec<_>>(),
+            [attributed]
+        );
+        let sent = stream_ts_query(
+            Aggregation::Hourly,
+            None,
+            None,
+            None,
+            Some(series_id + 1),
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(sent, 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_fuel_type_breakdown_splits_each_bucket() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        for (name, fuel_type) in [("north", "wind"), ("south", "solar")] {
+            let series_id = create_series(
+                SeriesDefinition {
+                    fuel_type: Some(fuel_type.to_string()),
+                    ..series_definition(name)
+                },
+                &mut conn,
+            )
+            .unwrap()
+            .id;
+            let ingestion_id = seed_ts_metadata(&mut conn);
+            seed_ts_data(&mut conn, ingestion_id);
+            diesel::update(ts_metadata::table.find(ingestion_id))
+                .set(ts_metadata::series_id.eq(series_id))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        let unattributed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, unattributed);
+
+        let breakdown = fuel_type_breakdown(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        let totals =
+            aggregate_ts_query(Aggregation::DayInMonth, None, None, None, &mut conn).unwrap();
+        assert_eq!(breakdown.len(), totals.len() * 3);
+        assert!(breakdown.is_sorted_by_key(|part| part.datetime));
+        for parts in breakdown.chunks(3) {
+            // Fuel types sort by name with unattributed readings last
+            assert_eq!(
+                parts
+                    .iter()
+                    .map(|part| part.fuel_type.as_deref())
+                    .collect::<Vec<_>>(),
+                [Some("solar"), Some("wind"), None]
+            );
+            let bucket = totals
+                .iter()
+                .find(|bucket| bucket.datetime == parts[0].datetime)
+                .unwrap();
+            assert!(parts.iter().all(|part| part.datetime == bucket.datetime));
+            assert_eq!(
+                parts
+                    .iter()
+                    .map(|part| part.total_amount.clone().unwrap())
+                    .sum::<BigDecimal>(),
+                bucket.total_amount.clone().unwrap()
+            );
+        }
+
+        let south = find_series_id("south", &mut conn).unwrap();
+        let filtered = fuel_type_breakdown(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            south,
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(filtered.len(), totals.len());
+        assert!(
+            filtered
+                .iter()
+                .all(|part| part.fuel_type.as_deref() == Some("solar"))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_stream_ts_query_yields_ordered_buckets_and_stops_early() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let expected = aggregate_ts_query(Aggregation::Hourly, None, None, None, &mut conn)
+            .unwrap()
+            .len();
+        let mut streamed = Vec::new();
+        let sent = stream_ts_query(
+            Aggregation::Hourly,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+            |record| {
+                streamed.push(record.datetime);
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(sent, expected);
+        assert!(streamed.is_sorted());
+
+        let sent = stream_ts_query(
+            Aggregation::Hourly,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+            |_| false,
+        )
+        .unwrap();
+        assert_eq!(sent, 0);
+
+        let screened = TotalFilter {
+            lt: Some(250.0),
+            ..TotalFilter::default()
+        };
+        let sent = stream_ts_query(
+            Aggregation::Hourly,
+            None,
+            None,
+            None,
+            None,
+            &screened,
+            Tz::UTC,
+            &mut conn,
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(sent, 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_having_screens_buckets_in_every_query_path() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        // Days of 14, 24 and 10 hourly readings totalling 10500, 63600 and 43500
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let days = |having: &TotalFilter, as_recorded_by, conn: &mut PgConnection| {
+            let mut totals: Vec<_> = aggregate_ts_query_having(
+                Aggregation::DayInMonth,
+                None,
+                None,
+                as_recorded_by,
+                None,
+                having,
+                Tz::UTC,
+                conn,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|record| record.total_amount.unwrap())
+            .collect();
+            totals.sort();
+            totals
+        };
+        let above = TotalFilter {
+            gt: Some(20000.0),
+            ..TotalFilter::default()
+        };
+        let between = TotalFilter {
+            ge: Some(10500.0),
+            le: Some(43500.0),
+            ..TotalFilter::default()
+        };
+        for as_recorded_by in [None, Some(Utc::now())] {
+            assert_eq!(
+                days(&above, as_recorded_by, &mut conn),
+                [BigDecimal::from(43500), BigDecimal::from(63600)]
+            );
+            assert_eq!(
+                days(&between, as_recorded_by, &mut conn),
+                [BigDecimal::from(10500), BigDecimal::from(43500)]
+            );
+        }
+
+        let buckets = bucket_energy(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter {
+                gt: Some(50000.0),
+                ..TotalFilter::default()
+            },
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].total_amount, Some(BigDecimal::from(63600)));
+    }
+
+    #[test]
+    fn test_aggregation_sql_is_shared_by_every_period_and_zone() {
+        let statement = |kind, timezone| {
+            let query =
+                aggregation_query(kind, None, None, None, &TotalFilter::default(), timezone);
+            diesel::debug_query::<Pg, _>(&query).to_string()
+        };
+        let hourly = statement(Aggregation::Hourly, Tz::UTC);
+        let yearly = statement(Aggregation::Yearly, New_York);
+        let (hourly_sql, hourly_binds) = hourly.split_once("-- binds: ").unwrap();
+        let (yearly_sql, yearly_binds) = yearly.split_once("-- binds: ").unwrap();
+
+        assert_eq!(hourly_sql, yearly_sql);
+        assert!(hourly_sql.contains("GROUP BY 1"), "{hourly_sql}");
+        assert_eq!(hourly_binds, r#"["UTC", "hour", "UTC"]"#);
+        assert_eq!(
+            yearly_binds,
+            r#"["America/New_York", "year", "America/New_York"]"#
+        );
+    }
+
+    #[test_case(Aggregation::Hourly, None, None, TotalFilter::default(), Tz::UTC, false; "hourly unbounded")]
+    #[test_case(Aggregation::DayInMonth, Some(test_from_date()), Some(test_to_date()), TotalFilter { gt: Some(1000.0), ..TotalFilter::default() }, Tz::Asia__Kolkata, false; "zoned range with bounds")]
+    #[test_case(Aggregation::Weekly, None, Some(test_to_date()), TotalFilter { le: Some(100000.5), ..TotalFilter::default() }, Tz::America__New_York, false; "weekly bounded")]
+    #[test_case(Aggregation::Hourly, None, None, TotalFilter::default(), Tz::Europe__London, true; "ranked hourly zoned")]
+    #[test_case(Aggregation::DayInMonth, Some(test_from_date()), Some(test_to_date()), TotalFilter { gt: Some(1000.0), ..TotalFilter::default() }, Tz::Asia__Kolkata, true; "ranked zoned range with bounds")]
+    #[test_case(Aggregation::Weekly, None, None, TotalFilter::default(), Tz::America__New_York, true; "ranked weekly zoned")]
+    #[tokio::test]
+    #[serial]
+    async fn test_offline_aggregation_matches_the_database(
+        aggregation_kind: Aggregation,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        having: TotalFilter,
+        zone: Tz,
+        ranked: bool,
+    ) {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+        if ranked {
+            seed_overlapping_ingestions(&mut conn);
+        }
+
+        let archive = offline::open_archives(&[archive_readings("offline-matches", &mut conn)])
+            .await
+            .unwrap();
+
+        let totals = |records: Vec<AggregationQueryRecord>| {
+            records
+                .into_iter()
+                .map(|record| (record.datetime, record.total_amount))
+                .collect::<Vec<_>>()
+        };
+        let served = aggregate_ts_query_having(
+            aggregation_kind,
+            from_date,
+            to_date,
+            None,
+            None,
+            &having,
+            zone,
+            &mut conn,
+        )
+        .unwrap();
+        assert!(!served.is_empty());
+        let offline = engine::aggregate(
+            &archive,
+            aggregation_kind,
+            from_date,
+            to_date,
+            &having,
+            zone,
+        )
+        .await
+        .unwrap();
+        assert_eq!(totals(offline), totals(served));
+    }
+
+    #[test_case(r#"{"aggregation_kind": "Hourly"}"#; "hourly unbounded")]
+    #[test_case(r#"{"aggregation_kind": "DayInMonth", "timezone": "Europe/Helsinki", "datetime_filter": {"from_date": "2024-01-15T13:30:00Z", "to_date": "2024-01-17T05:00:00Z"}, "having": {"gt": 1000, "le": 100000.5}}"#; "zoned range with bounds")]
+    #[test_case(r#"{"aggregation_kind": "Monthly", "series_id": 0, "datetime_filter": {"to_date": "2024-02-01T00:00:00Z", "to_bound": "inclusive"}, "materialized": true}"#; "materialized series")]
+    #[serial]
+    fn test_saved_views_hold_the_aggregation_served(definition: &str) {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let definition: SavedViewDefinition = serde_json::from_str(definition).unwrap();
+        let (from_date, to_date) = definition
+            .datetime_filter
+            .as_ref()
+            .map(|range| range.half_open())
+            .unwrap_or_default();
+        let served: Vec<AggregationQueryRecord> = aggregation_query(
+            definition.aggregation_kind,
+            from_date,
+            to_date,
+            definition.series_id,
+            &definition.having.clone().unwrap_or_default(),
+            definition.timezone.unwrap_or(Tz::UTC),
+        )
+        .load(&mut conn)
+        .unwrap();
+        let held = |view: &str, conn: &mut PgConnection| {
+            diesel::sql_query(format!("SELECT * FROM {view} ORDER BY datetime"))
+                .load::<AggregationQueryRecord>(conn)
+                .unwrap()
+                .into_iter()
+                .map(|record| (record.datetime, record.total_amount))
+                .collect::<Vec<_>>()
+        };
+        let served: Vec<_> = served
+            .into_iter()
+            .map(|record| (record.datetime, record.total_amount))
+            .collect();
+
+        let view = save_view("test_view", &definition, &mut conn).unwrap();
+        assert_eq!(view.relation, "renewable.saved_test_view");
+        assert!(!view.definition.contains('$'), "{}", view.definition);
+        assert_eq!(held(&view.relation, &mut conn), served);
+
+        // Saving again switches between plain and materialized views in place
+        let toggled = SavedViewDefinition {
+            materialized: !definition.materialized,
+            ..definition
+        };
+        let view = save_view("test_view", &toggled, &mut conn).unwrap();
+        let relkind: String = diesel::select(diesel::dsl::sql::<diesel::sql_types::Text>(
+            "(SELECT relkind::TEXT FROM pg_class WHERE oid = 'renewable.saved_test_view'::regclass)",
+        ))
+        .get_result(&mut conn)
+        .unwrap();
+        assert_eq!(relkind, if toggled.materialized { "m" } else { "v" });
+        assert_eq!(held(&view.relation, &mut conn), served);
+
+        let kind = if toggled.materialized {
+            "MATERIALIZED VIEW"
+        } else {
+            "VIEW"
+        };
+        diesel::sql_query(format!("DROP {kind} {}", view.relation))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_timezone_buckets_follow_the_local_calendar() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        // New York days start at 05:00 UTC in January, moving 5 hourly readings of each
+        // UTC day into the local day before
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+        let expected = [
+            (Utc.with_ymd_and_hms(2024, 1, 15, 5, 0, 0).unwrap(), 19000),
+            (Utc.with_ymd_and_hms(2024, 1, 16, 5, 0, 0).unwrap(), 75600),
+            (Utc.with_ymd_and_hms(2024, 1, 17, 5, 0, 0).unwrap(), 23000),
+        ]
+        .map(|(datetime, total)| (datetime, BigDecimal::from(total)));
+
+        for as_recorded_by in [None, Some(Utc::now())] {
+            let mut days: Vec<_> = aggregate_ts_query_having(
+                Aggregation::DayInMonth,
+                None,
+                None,
+                as_recorded_by,
+                None,
+                &TotalFilter::default(),
+                New_York,
+                &mut conn,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.datetime, record.total_amount.unwrap()))
+            .collect();
+            days.sort();
+            assert_eq!(days, expected, "{as_recorded_by:?}");
+        }
+
+        let energy = bucket_energy(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            New_York,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(
+            energy
+                .iter()
+                .map(|bucket| (bucket.datetime, bucket.readings))
+                .collect::<Vec<_>>(),
+            [(expected[0].0, 19), (expected[1].0, 24), (expected[2].0, 5)]
+        );
+
+        let mut streamed = Vec::new();
+        stream_ts_query(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            New_York,
+            &mut conn,
+            |record| {
+                streamed.push(record.datetime);
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(streamed, expected.map(|(datetime, _)| datetime));
+    }
+
+    #[test]
+    #[serial]
+    fn test_bucket_energy_sums_overlapping_ingestions_before_the_peak() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let first = seed_ts_metadata(&mut conn);
+        let second = seed_ts_metadata(&mut conn);
+        let at = test_from_date();
+        let reading = |ingestion_id, hours, amount: i64| TSStore {
+            ingestion_id,
+            datetime: at + Duration::hours(hours),
+            amount: BigDecimal::from(amount),
+            recorded_at: Utc::now(),
+            extra: None,
+        };
+        diesel::insert_into(ts_store::table)
+            .values(vec![
+                reading(first, 0, 3),
+                reading(second, 0, 4),
+                reading(first, 1, 5),
+            ])
+            .execute(&mut conn)
+            .unwrap();
+
+        let buckets = bucket_energy(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].total_amount, Some(BigDecimal::from(12)));
+        assert_eq!(buckets[0].peak_amount, Some(BigDecimal::from(7)));
+        assert_eq!(buckets[0].readings, 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_higher_priority_ingestions_supersede_overlapping_readings() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let start = Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap();
+        let readings = |hours, amount: i64| {
+            (0..hours)
+                .map(|i| CSVRecord {
+                    datetime: start + Duration::hours(i),
+                    amount: BigDecimal::from(amount),
+                    extra: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        // Another series at the same timestamps is summed, never superseded
+        let other = create_series(series_definition("other"), &mut conn)
+            .unwrap()
+            .id;
+        insert_ingestion(
+            "other".to_string(),
+            Some(other),
+            0,
+            readings(4, 100),
+            0,
+            &mut conn,
+        )
+        .unwrap();
+        insert_ingestion(
+            "provisional".to_string(),
+            None,
+            0,
+            readings(4, 1),
+            0,
+            &mut conn,
+        )
+        .unwrap();
+        let before_final = Utc::now();
+        insert_ingestion("final".to_string(), None, 10, readings(2, 5), 0, &mut conn).unwrap();
+
+        let hourly = |as_recorded_by, conn: &mut PgConnection| {
+            aggregate_ts_query(Aggregation::Hourly, None, None, as_recorded_by, conn)
+                .unwrap()
+                .into_iter()
+                .map(|record| record.total_amount.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hourly(None, &mut conn),
+            [105, 105, 101, 101].map(BigDecimal::from)
+        );
+        assert_eq!(
+            hourly(Some(before_final), &mut conn),
+            [101; 4].map(BigDecimal::from)
+        );
+        // Raw archives carry each ingestion's rank, so offline totals supersede alike
+        let archive = offline::open_archives(&[archive_readings("offline-ranked", &mut conn)])
+            .await
+            .unwrap();
+        let offline_hourly: Vec<_> = engine::aggregate(
+            &archive,
+            Aggregation::Hourly,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.total_amount.unwrap())
+        .collect();
+        assert_eq!(offline_hourly, hourly(None, &mut conn));
+        // Daily summaries total every ingestion, so are bypassed once any is ranked
+        let daily =
+            aggregate_ts_query(Aggregation::DayInMonth, None, None, None, &mut conn).unwrap();
+        assert_eq!(daily[0].total_amount, Some(BigDecimal::from(412)));
+        let energy = bucket_energy(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            &TotalFilter::default(),
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(energy[0].total_amount, Some(BigDecimal::from(412)));
+        let (_, window) = load_recent_window(1, &mut conn).unwrap().unwrap();
+        assert_eq!(window[0].1, BigDecimal::from(105));
+
+        let sources = bucket_sources(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            None,
+            None,
+            Tz::UTC,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(
+            sources
+                .iter()
+                .map(|source| (source.source.as_str(), source.priority, source.readings))
+                .collect::<Vec<_>>(),
+            [("final", 10, 2), ("other", 0, 4), ("provisional", 0, 2)]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_export_job_lifecycle() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        ensure_api_key("ops", "digest", &mut conn).unwrap();
+        let key_id = find_active_key("digest", &mut conn).unwrap().unwrap();
+
+        let job = create_export_job(
+            Aggregation::Monthly,
+            Some(test_from_date()),
+            None,
+            key_id,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.from_date, Some(test_from_date()));
+        assert_eq!(job.api_key_id, Some(key_id));
+
+        mark_export_running(job.id, &mut conn).unwrap();
+        let running = get_export_job(job.id, &mut conn).unwrap().unwrap();
+        assert_eq!(running.status, JobStatus::Running);
+
+        mark_export_complete(job.id, "exports/export-1.csv", 12, &mut conn).unwrap();
+        let complete = get_export_job(job.id, &mut conn).unwrap().unwrap();
+        assert_eq!(complete.status, JobStatus::Complete);
+        assert_eq!(complete.row_count, Some(12));
+        assert_eq!(complete.file_path.as_deref(), Some("exports/export-1.csv"));
+        assert!(complete.completed_at.is_some());
+
+        let failed = create_export_job(Aggregation::Hourly, None, None, key_id, &mut conn).unwrap();
+        mark_export_failed(failed.id, "disk full", &mut conn).unwrap();
+        let failed = get_export_job(failed.id, &mut conn).unwrap().unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("disk full"));
+
+        assert!(get_export_job(-1, &mut conn).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_job_lifecycle() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        ensure_api_key("ops", "digest", &mut conn).unwrap();
+        let key_id = find_active_key("digest", &mut conn).unwrap().unwrap();
+
+        let job = create_query_job(
+            Aggregation::Monthly,
+            Some(test_from_date()),
+            None,
+            key_id,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.api_key_id, Some(key_id));
+        assert!(job.result.is_none());
+
+        mark_query_running(job.id, &mut conn).unwrap();
+        let running = get_query_job(job.id, key_id, &mut conn).unwrap().unwrap();
+        assert_eq!(running.status, JobStatus::Running);
+
+        let body = serde_json::json!({ "data": [{ "datetime": "2024-01", "sum": "4.5" }] });
+        mark_query_complete(job.id, &body, 1, &mut conn).unwrap();
+        let complete = get_query_job(job.id, key_id, &mut conn).unwrap().unwrap();
+        assert_eq!(complete.status, JobStatus::Complete);
+        assert_eq!(complete.row_count, Some(1));
+        assert_eq!(complete.result, Some(body));
+        assert!(complete.completed_at.is_some());
+
+        let failed = create_query_job(Aggregation::Hourly, None, None, key_id, &mut conn).unwrap();
+        mark_query_failed(failed.id, "canceling statement", &mut conn).unwrap();
+        let failed = get_query_job(failed.id, key_id, &mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("canceling statement"));
+        assert!(failed.result.is_none());
+
+        // Other keys cannot read a job, not even by guessing its id
+        ensure_api_key("other", "other-digest", &mut conn).unwrap();
+        let other_id = find_active_key("other-digest", &mut conn).unwrap().unwrap();
+        assert!(
+            get_query_job(job.id, other_id, &mut conn)
+                .unwrap()
+                .is_none()
+        );
+        assert!(get_query_job(-1, key_id, &mut conn).unwrap().is_none());
+
+        // Jobs outlive the key that created them, readable by no key once it is gone
+        diesel::delete(api_keys::table).execute(&mut conn).unwrap();
+        let orphaned: Option<i64> = query_jobs::table
+            .find(job.id)
+            .select(query_jobs::api_key_id)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(orphaned, None);
+        assert!(get_query_job(job.id, key_id, &mut conn).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_recent_window() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        assert!(load_recent_window(1, &mut conn).unwrap().is_none());
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let (start, rows) = load_recent_window(1, &mut conn).unwrap().unwrap();
+        let latest = Utc.with_ymd_and_hms(2024, 1, 17, 9, 0, 0).unwrap();
+        assert_eq!(start, latest - Duration::days(1));
+        assert_eq!(rows.len(), 25);
+        assert_eq!(rows.last().unwrap().0, latest);
+        assert!(rows.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_lineage() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let first_ingestion = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, first_ingestion);
+        let second_ingestion = seed_ts_metadata(&mut conn);
+        diesel::insert_into(ts_store::table)
+            .values(TSStore {
+                ingestion_id: second_ingestion,
+                datetime: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+                amount: BigDecimal::from(1),
+                recorded_at: Utc::now(),
+                extra: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+
+        let ids = |lineage: Vec<crate::model::api_response::IngestionLineage>| {
+            lineage
+                .into_iter()
+                .map(|l| l.ingestion_id)
+                .collect::<Vec<_>>()
+        };
+        let all = query_lineage(None, None, None, None, &mut conn).unwrap();
+        assert_eq!(all[0].source, "test_source");
+        assert_eq!(ids(all), vec![first_ingestion, second_ingestion]);
+
+        let february = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        let later = query_lineage(Some(february), None, None, None, &mut conn).unwrap();
+        assert_eq!(ids(later), vec![second_ingestion]);
+
+        let earlier = query_lineage(None, Some(test_to_date()), None, None, &mut conn).unwrap();
+        assert_eq!(ids(earlier), vec![first_ingestion]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_ingestions() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let reading = |hour, amount| CSVRecord {
+            datetime: test_from_date() + Duration::hours(hour),
+            amount,
+            extra: None,
+        };
+        let readings = (0..48)
+            .map(|hour| reading(hour, BigDecimal::from(1)))
+            .collect();
+        let (loaded, _) = insert_ingestion("loaded".to_string(), None, 0, readings, 2, &mut conn)
+            .unwrap()
+            .unwrap();
+        let pending = seed_ts_metadata(&mut conn);
+        // Too large for the amount column, so storing the readings fails
+        let overflowing = vec![reading(0, "1e24".parse().unwrap())];
+        assert!(
+            insert_ingestion("overflow".to_string(), None, 0, overflowing, 0, &mut conn).is_err()
+        );
+
+        let ingestions = query_ingestions(&mut conn).unwrap();
+        assert_eq!(ingestions.len(), 3);
+
+        assert_eq!(ingestions[0].ingestion_id, loaded);
+        assert_eq!(ingestions[0].status, IngestionStatus::Complete);
+        assert_eq!(ingestions[0].row_count, Some(48));
+        assert_eq!(ingestions[0].error_count, 2);
+        assert!(ingestions[0].duration_ms.is_some());
+        assert_eq!(ingestions[0].min_datetime, Some(test_from_date()));
+        assert_eq!(
+            ingestions[0].max_datetime,
+            Some(test_from_date() + Duration::hours(47))
+        );
+
+        assert_eq!(ingestions[1].ingestion_id, pending);
+        assert_eq!(ingestions[1].status, IngestionStatus::Pending);
+        assert!(ingestions[1].row_count.is_none());
+        assert!(ingestions[1].min_datetime.is_none());
+
+        // A failed ingestion keeps no readings and may be retried
+        assert_eq!(ingestions[2].status, IngestionStatus::Failed);
+        assert!(ingestions[2].row_count.is_none());
+        assert!(ingestions[2].duration_ms.is_some());
+        assert_eq!(
+            ts_store::table
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            48
+        );
+        assert!(!source_ingested("overflow", &mut conn).unwrap());
+        assert!(source_ingested("loaded", &mut conn).unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_aggregate_ts_query_as_recorded_by() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let before_ingestion = Utc::now();
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+
+        let known_then = aggregate_ts_query(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            Some(before_ingestion),
+            &mut conn,
+        )
+        .unwrap();
+        assert!(known_then.is_empty());
+        assert!(
+            query_lineage(None, None, Some(before_ingestion), None, &mut conn)
+                .unwrap()
+                .is_empty()
+        );
+
+        let known_now = aggregate_ts_query(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            Some(Utc::now()),
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(known_now.len(), 3);
+    }
+
+    #[test]
+    #[serial]
+    fn test_delete_ingestion() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let deleted = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, deleted);
+        let kept = seed_ts_metadata(&mut conn);
+
+        let result = delete_ingestion(deleted, &mut conn).unwrap().unwrap();
+        assert_eq!(result.deleted_metadata, 1);
+        assert_eq!(result.deleted_readings, 48);
+
+        let remaining = query_ingestions(&mut conn).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].ingestion_id, kept);
+
+        assert!(delete_ingestion(deleted, &mut conn).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_stream_readings_hands_over_in_order_until_stopped() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+        let first = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, first);
+        let second = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, second);
+
+        let key = |reading: &TSStore| (reading.datetime, reading.ingestion_id);
+        let (from, to) = (Some(test_from_date()), Some(test_to_date()));
+        let mut streamed = Vec::new();
+        let sent = stream_readings(from, to, &mut conn, |reading| {
+            streamed.push(key(&reading));
+            true
+        })
+        .unwrap();
+        assert_eq!(sent, 48);
+        assert_eq!(
+            streamed,
+            query_readings(from, to, &mut conn)
+                .unwrap()
+                .iter()
+                .map(key)
+                .collect::<Vec<_>>()
+        );
+
+        // Nothing is handed over past the reading refused
+        let mut seen = 0;
+        let sent = stream_readings(None, None, &mut conn, |_| {
+            seen += 1;
+            seen <= 5
+        })
+        .unwrap();
+        assert_eq!((sent, seen), (5, 6));
+    }
+
+    #[cfg(feature = "compressed-storage")]
+    #[test]
+    #[serial]
+    fn test_compressed_ingestions_read_back_like_rows() {
+        use crate::db::compressed_storage::{self, compress_ingestion};
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let compressed = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, compressed);
+        let raw = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, raw);
+
+        let report = compress_ingestion(compressed, &mut conn).unwrap();
+        assert_eq!((report.blocks, report.readings), (3, 48));
+        assert!(report.block_bytes < report.row_bytes, "{report:?}");
+
+        let from = Some(Utc.with_ymd_and_hms(2024, 1, 15, 20, 0, 0).unwrap());
+        let to = Some(Utc.with_ymd_and_hms(2024, 1, 16, 12, 0, 0).unwrap());
+        let key = |readings: Vec<TSStore>| -> Vec<_> {
+            readings
+                .into_iter()
+                .map(|r| (r.ingestion_id, r.datetime, r.amount))
+                .collect()
+        };
+        let expected = key(query_readings(from, to, &mut conn).unwrap());
+        assert_eq!(expected.len(), 32);
+        assert_eq!(
+            key(compressed_storage::query_readings(from, to, &mut conn).unwrap()),
+            expected
+        );
+        let mut streamed = Vec::new();
+        compressed_storage::stream_readings(from, to, &mut conn, |reading| {
+            streamed.push(reading);
+            true
+        })
+        .unwrap();
+        assert_eq!(key(streamed), expected);
+
+        // Blocks go with their ingestion
+        delete_ingestion(compressed, &mut conn).unwrap();
+        assert_eq!(
+            compressed_storage::query_readings(None, None, &mut conn)
+                .unwrap()
+                .len(),
+            48
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_insert_ingestion() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let start = Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap();
+        let readings = (0..3)
+            .map(|i| CSVRecord {
+                datetime: start + Duration::hours(i),
+                amount: BigDecimal::from(i),
+                extra: (i == 2).then(|| serde_json::json!({"status": "EST"})),
+            })
+            .collect();
+
+        let (ingestion_id, inserted) =
+            insert_ingestion("generated".to_string(), None, 0, readings, 0, &mut conn)
+                .unwrap()
+                .unwrap();
+        assert_eq!(inserted, 3);
+        assert_eq!(
+            query_ingestions(&mut conn).unwrap()[0].ingestion_id,
+            ingestion_id
+        );
+
+        let readings = query_readings(Some(start + Duration::hours(1)), None, &mut conn).unwrap();
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].datetime, start + Duration::hours(1));
+        assert_eq!(
+            readings[1].extra,
+            Some(serde_json::json!({"status": "EST"}))
+        );
+
+        // Captured columns are queryable with the JSONB operators
+        let estimated: i64 = ts_store::table
+            .filter(diesel::dsl::sql::<diesel::sql_types::Bool>(
+                "extra->>'status' = 'EST'",
+            ))
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(estimated, 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_files_over_the_bind_parameter_limit_are_stored_whole() {
+        use crate::db::seed_database::correct_ingestion;
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        // Five bind parameters a reading put 30,000 of them well over Postgres' 65,535
+        let start = Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap();
+        let readings = |from: i64, amount: i64| -> Vec<CSVRecord> {
+            (from..from + 30_000)
+                .map(|i| CSVRecord {
+                    datetime: start + Duration::minutes(15 * i),
+                    amount: BigDecimal::from(amount),
+                    extra: None,
+                })
+                .collect()
+        };
+        let (ingestion_id, inserted) = insert_ingestion(
+            "large.csv".to_string(),
+            None,
+            0,
+            readings(0, 1),
+            0,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(inserted, 30_000);
+
+        let corrected = correct_ingestion(ingestion_id, true, readings(15_000, 2), &mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (corrected.inserted, corrected.updated, corrected.skipped),
+            (15_000, 15_000, 0)
+        );
+        let (row_count, total) = ts_store::table
+            .filter(ts_store::ingestion_id.eq(ingestion_id))
+            .select((
+                diesel::dsl::count_star(),
+                diesel::dsl::sum(ts_store::amount),
+            ))
+            .get_result::<(i64, Option<BigDecimal>)>(&mut conn)
+            .unwrap();
+        assert_eq!((row_count, total), (45_000, Some(BigDecimal::from(75_000))));
+    }
+
+    #[test]
+    #[serial]
+    fn test_bucket_point_counts_distinct_timestamps() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        // Two ingestions covering the same timestamps count each reading once
+        for _ in 0..2 {
+            let ingestion_id = seed_ts_metadata(&mut conn);
+            seed_ts_data(&mut conn, ingestion_id);
+        }
+
+        let mut counts =
+            bucket_point_counts(Aggregation::DayInMonth, None, None, None, None, &mut conn)
+                .unwrap();
+        counts.sort();
+        let counts: Vec<_> = counts.into_iter().map(|(_, count)| count).collect();
+        assert_eq!(counts, [14, 24, 10]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_clock_drift_report_follows_its_ingestion() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        assert!(
+            query_clock_drift(ingestion_id, &mut conn)
+                .unwrap()
+                .is_none()
+        );
+
+        let drift = IngestionClockDrift {
+            ingestion_id,
+            interval_secs: 3600,
+            readings: 48,
+            drifted_readings: 2,
+            max_offset_secs: -120,
+            snapped: true,
+            collisions: 0,
+        };
+        record_clock_drift(&drift, &mut conn).unwrap();
+        assert_eq!(
+            query_clock_drift(ingestion_id, &mut conn).unwrap().as_ref(),
+            Some(&drift)
+        );
+        assert_eq!(
+            query_clock_drifts(&[ingestion_id, ingestion_id + 1], &mut conn).unwrap(),
+            [drift]
+        );
+
+        delete_ingestion(ingestion_id, &mut conn).unwrap();
+        assert!(
+            query_clock_drift(ingestion_id, &mut conn)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_diff_ts_query() {
         let mut conn = get_test_connection();
         cleanup_tables(&mut conn);
 
         let ingestion_id = seed_ts_metadata(&mut conn);
         seed_ts_data(&mut conn, ingestion_id);
+        let baseline_recorded_by = Utc::now();
 
-        let result = aggregate_ts_query(aggregation_kind, from_date, to_date, &mut conn);
-        assert!(result.is_ok());
-        let records = result.unwrap();
+        let correction_id = seed_ts_metadata(&mut conn);
+        diesel::insert_into(ts_store::table)
+            .values(TSStore {
+                ingestion_id: correction_id,
+                datetime: Utc.with_ymd_and_hms(2024, 1, 16, 0, 0, 0).unwrap(),
+                amount: BigDecimal::from(5),
+                recorded_at: Utc::now(),
+                extra: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
 
-        let history = query_request_history(&mut conn).unwrap();
-        assert!(!history.is_empty());
+        let (baseline, compare) = diff_ts_query(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            baseline_recorded_by,
+            Utc::now(),
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(baseline.len(), compare.len());
 
-        let latest_entry = &history[0];
-        assert_eq!(latest_entry.aggregation, aggregation_kind);
-        assert_eq!(latest_entry.from_date, from_date);
-        assert_eq!(latest_entry.to_date, to_date);
+        let day = Utc.with_ymd_and_hms(2024, 1, 16, 0, 0, 0).unwrap();
+        let total = |records: &[crate::model::api_response::AggregationQueryRecord]| {
+            records
+                .iter()
+                .find(|r| r.datetime == day)
+                .and_then(|r| r.total_amount.clone())
+                .unwrap()
+        };
+        assert_eq!(total(&compare) - total(&baseline), BigDecimal::from(5));
+    }
 
-        if from_date.is_some() || to_date.is_some() {
-            let unfiltered = aggregate_ts_query(aggregation_kind, None, None, &mut conn).unwrap();
-            assert!(records.len() <= unfiltered.len());
+    #[test]
+    #[serial]
+    fn test_replication_lag_on_primary() {
+        let mut conn = get_test_connection();
+        assert!(replication_lag_seconds(&mut conn).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_statement_timeout_cancels_slow_query() {
+        let mut conn = get_test_connection();
+        let sleep =
+            |conn: &mut PgConnection| diesel::sql_query("SELECT pg_sleep(0.2)").execute(conn);
+
+        let Err(e) = with_statement_timeout(&mut conn, Some(StdDuration::from_millis(20)), sleep)
+        else {
+            panic!("query should have been cancelled");
+        };
+        assert!(is_statement_timeout(&e));
+
+        assert!(with_statement_timeout(&mut conn, Some(StdDuration::from_secs(5)), sleep).is_ok());
+        assert!(with_statement_timeout(&mut conn, None, sleep).is_ok());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_statement_timeout_is_set_on_checkout() {
+        dotenvy::dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let tuning = PoolTuning {
+            max_size: Some(1),
+            statement_timeout: Some(StdDuration::from_millis(1500)),
+            ..PoolTuning::default()
+        };
+        let pool = &build_pool(database_url, &tuning).await.unwrap();
+
+        #[derive(diesel::QueryableByName)]
+        struct Setting {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            statement_timeout: String,
+        }
+        let show = |reset: bool| async move {
+            let conn = pool.get().await.unwrap();
+            conn.interact(move |conn| {
+                let setting = diesel::sql_query("SHOW statement_timeout")
+                    .get_result::<Setting>(conn)?
+                    .statement_timeout;
+                if reset {
+                    diesel::sql_query("SET statement_timeout = 0").execute(conn)?;
+                }
+                Ok::<_, diesel::result::Error>(setting)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+        };
+        assert_eq!(show(true).await, "1500ms");
+        // Restored when the same connection is checked out again
+        assert_eq!(show(false).await, "1500ms");
+    }
+
+    #[test]
+    #[serial]
+    fn test_onboard_meters_reports_each_row() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let meter = |code: &str, series: &[&str]| MeterOnboarding {
+            meter_code: code.to_string(),
+            site_name: "Test Site".to_string(),
+            capacity_kw: Some(250.0),
+            series: series.iter().map(ToString::to_string).collect(),
+        };
+        let rows = vec![
+            Ok(meter("MTR-001", &["generation", "export"])),
+            Ok(meter("MTR-001", &["generation"])),
+            Ok(meter("", &[])),
+            Err("unable to decode row".to_string()),
+            Ok(meter("MTR-002", &["generation", "generation"])),
+            Ok(meter("MTR-003", &[])),
+        ];
+
+        let results = onboard_meters(rows, &mut conn).unwrap();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[0].series_created, 2);
+        assert_eq!(
+            results[1].error.as_deref(),
+            Some("meter_code already exists")
+        );
+        assert!(results[2].error.is_some() && results[3].error.is_some());
+        assert_eq!(
+            results[4].error.as_deref(),
+            Some("duplicate series name for meter")
+        );
+        assert!(results[5].meter_id.is_some());
+
+        let meter_id = results[0].meter_id.unwrap();
+        let channels: Vec<String> = series::table
+            .filter(series::meter_id.eq(meter_id))
+            .order(series::name)
+            .select(series::name)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(channels, ["MTR-001/export", "MTR-001/generation"]);
+
+        // The failed series insert rolled back its meter along with it
+        let onboarded: i64 = meters::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(onboarded, 2);
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_api_key_lookup_and_history_attribution() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        assert_eq!(ensure_api_key("ops", "digest", &mut conn).unwrap(), 1);
+        assert_eq!(ensure_api_key("ops", "digest", &mut conn).unwrap(), 0);
+        let key_id = find_active_key("digest", &mut conn).unwrap().unwrap();
+        assert!(find_active_key("unknown", &mut conn).unwrap().is_none());
+
+        let entry = QueryHistory::new(None, None, Aggregation::Monthly, Some(key_id));
+        insert_query_history(&[entry], &mut conn).unwrap();
+        let history = query_request_history(
+            DEFAULT_HISTORY_LIMIT,
+            None,
+            &HistoryFilter::default(),
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(history[0].api_key_id, Some(key_id));
+
+        revoke_api_key(key_id, &mut conn).unwrap();
+        assert!(find_active_key("digest", &mut conn).unwrap().is_none());
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_meter_profile_round_trip() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let onboarded = onboard_meters(
+            vec![Ok(MeterOnboarding {
+                meter_code: "MTR-P50".to_string(),
+                site_name: "Profile Site".to_string(),
+                capacity_kw: None,
+                series: Vec::new(),
+            })],
+            &mut conn,
+        )
+        .unwrap();
+        assert!(onboarded[0].error.is_none());
+
+        let month = |month: u32, p50: f64, p90: f64| ProfileMonth {
+            month,
+            p50_kwh: p50,
+            p90_kwh: p90,
+        };
+        let stored = replace_meter_profile(
+            "MTR-P50",
+            vec![month(2, 200.0, 150.0), month(1, 100.0, 80.0)],
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(stored, Some(2));
+
+        // Uploading again replaces the previous profile
+        replace_meter_profile("MTR-P50", vec![month(1, 120.0, 90.0)], &mut conn).unwrap();
+        let profile = load_meter_profile("MTR-P50", &mut conn).unwrap().unwrap();
+        assert_eq!(profile.len(), 1);
+        assert_eq!(profile[0].p50_kwh, BigDecimal::from(120));
+
+        assert!(
+            replace_meter_profile("MTR-NONE", Vec::new(), &mut conn)
+                .unwrap()
+                .is_none()
+        );
+        assert!(load_meter_profile("MTR-NONE", &mut conn).unwrap().is_none());
+
+        // Readings of no series are no meter's actuals
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+        let actuals = monthly_actuals("MTR-P50", None, None, &mut conn).unwrap();
+        assert!(actuals.is_empty());
+        // Query history is reserved for caller issued queries
+        assert!(
+            query_request_history(
+                DEFAULT_HISTORY_LIMIT,
+                None,
+                &HistoryFilter::default(),
+                &mut conn
+            )
+            .unwrap()
+            .is_empty()
+        );
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_monthly_actuals_total_the_meters_own_series() {
+        use crate::db::series::find_series_id;
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let meter = |code: &str| MeterOnboarding {
+            meter_code: code.to_string(),
+            site_name: "Fleet Site".to_string(),
+            capacity_kw: None,
+            series: vec!["generation".to_string()],
+        };
+        onboard_meters(vec![Ok(meter("MTR-A")), Ok(meter("MTR-B"))], &mut conn).unwrap();
+        for (code, amount) in [("MTR-A", 10), ("MTR-B", 25)] {
+            let series_id = find_series_id(&format!("{code}/generation"), &mut conn).unwrap();
+            insert_ingestion(
+                format!("{code}.csv"),
+                series_id,
+                0,
+                vec![CSVRecord {
+                    datetime: Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap(),
+                    amount: BigDecimal::from(amount),
+                    extra: None,
+                }],
+                0,
+                &mut conn,
+            )
+            .unwrap()
+            .unwrap();
+        }
+
+        for (code, amount) in [("MTR-A", 10), ("MTR-B", 25)] {
+            let actuals = monthly_actuals(code, None, None, &mut conn).unwrap();
+            assert_eq!(actuals.len(), 1, "{code}");
+            assert_eq!(actuals[0].total_amount, Some(BigDecimal::from(amount)));
         }
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_check_units_compares_with_the_series_history() {
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let series_id = create_series(series_definition("north"), &mut conn)
+            .unwrap()
+            .id;
+        let readings = |scale: i64| -> Vec<CSVRecord> {
+            let base_date = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+            (0..48)
+                .map(|i| CSVRecord {
+                    datetime: base_date + Duration::hours(i),
+                    amount: BigDecimal::from(100 * (i + 1) * scale),
+                    extra: None,
+                })
+                .collect()
+        };
+        // Nothing to compare with before the series holds readings
+        assert_eq!(check_units(series_id, &readings(1000), &mut conn), Ok(None));
+
+        let ingestion_id = seed_ts_metadata(&mut conn);
+        seed_ts_data(&mut conn, ingestion_id);
+        diesel::update(ts_metadata::table.find(ingestion_id))
+            .set(ts_metadata::series_id.eq(series_id))
+            .execute(&mut conn)
+            .unwrap();
+
+        assert_eq!(check_units(series_id, &readings(1), &mut conn), Ok(None));
+        let mismatch = check_units(series_id, &readings(1000), &mut conn)
+            .unwrap()
+            .unwrap();
+        assert!((mismatch.factor - 1000.0).abs() < f64::EPSILON);
+
+        flag_unit_mismatch(ingestion_id, mismatch, &mut conn).unwrap();
+        let flagged = query_ingestions(&mut conn).unwrap();
+        assert_eq!(flagged[0].unit_mismatch, Some(mismatch.ratio));
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_copies_of_an_ingested_file_are_skipped() {
+        use crate::{
+            drift::{DriftConfig, DriftMode},
+            file_reader::ReadingsFormat,
+            model::csv::CsvSchema,
+            register::RegisterConfig,
+        };
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let drift_config = DriftConfig {
+            interval: Duration::hours(1),
+            mode: DriftMode::default(),
+        };
+        let file = b"{\"datetime\": \"2024-01-15T10:00:00Z\", \"amount\": \"1.5\"}\n";
+        let ingest = |source: &str, conn: &mut PgConnection| {
+            let prepared = prepare_readings(
+                &file[..],
+                ReadingsFormat::Ndjson,
+                &CsvSchema::default(),
+                &RegisterConfig::default(),
+                &drift_config,
+            );
+            insert_ingestion_with_drift(source.to_string(), None, 0, prepared, &drift_config, conn)
+                .unwrap()
+        };
+
+        let (ingestion_id, _) = ingest("site-a.ndjson", &mut conn).unwrap();
+        assert_eq!(ingest("copy-of-site-a.ndjson", &mut conn), None);
+        let ingestions = query_ingestions(&mut conn).unwrap();
+        assert_eq!(ingestions.len(), 1);
+        assert_eq!(ingestions[0].checksum.as_ref().map(String::len), Some(64));
+
+        // A failed ingestion of the file leaves it free to be ingested again
+        diesel::update(ts_metadata::table.find(ingestion_id))
+            .set(ts_metadata::status.eq(IngestionStatus::Failed))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(ingest("copy-of-site-a.ndjson", &mut conn).is_some());
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_issues_found_while_ingesting_are_listed() {
+        use crate::{
+            db::query::query_ingestion_issues,
+            drift::{DriftConfig, DriftMode},
+            file_reader::ReadingsFormat,
+            model::csv::CsvSchema,
+            register::RegisterConfig,
+        };
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let drift_config = DriftConfig {
+            interval: Duration::hours(1),
+            mode: DriftMode::default(),
+        };
+        let file = b"{\"datetime\": \"2024-01-15T10:00:00Z\", \"amount\": \"1.5\"}
+not json
+{\"datetime\": \"2024-01-15T13:00:00Z\", \"amount\": \"-0.5\"}
+";
+        let prepared = prepare_readings(
+            &file[..],
+            ReadingsFormat::Ndjson,
+            &CsvSchema::default(),
+            &RegisterConfig::default(),
+            &drift_config,
+        );
+        let (ingestion_id, _) = insert_ingestion_with_drift(
+            "site-a.ndjson".to_string(),
+            None,
+            0,
+            prepared,
+            &drift_config,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+
+        let issues = query_ingestion_issues(ingestion_id, &mut conn)
+            .unwrap()
+            .unwrap();
+        let kinds: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.kind.as_str(), issue.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("parse_failure", Some(2)),
+                ("gap", None),
+                ("negative_value", None)
+            ]
+        );
+        assert_eq!(
+            query_ingestion_issues(ingestion_id + 1, &mut conn).unwrap(),
+            None
+        );
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_corrections_merge_into_the_earlier_ingestion() {
+        use crate::{db::seed_database::correct_ingestion, model::database::IngestionCorrection};
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let at = |hour: u32| Utc.with_ymd_and_hms(2024, 1, 15, hour, 0, 0).unwrap();
+        let readings = |amounts: &[(u32, &str)]| -> Vec<CSVRecord> {
+            amounts
+                .iter()
+                .map(|&(hour, amount)| CSVRecord {
+                    datetime: at(hour),
+                    amount: amount.parse().unwrap(),
+                    extra: None,
+                })
+                .collect()
+        };
+        let amount_at = |hour: u32, conn: &mut PgConnection| {
+            ts_store::table
+                .filter(ts_store::datetime.eq(at(hour)))
+                .select(ts_store::amount)
+                .get_result::<BigDecimal>(conn)
+                .unwrap()
+        };
+        let (ingestion_id, _) = insert_ingestion(
+            "site-a.csv".to_string(),
+            None,
+            0,
+            readings(&[(10, "1.5"), (11, "2.5")]),
+            0,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+
+        // The last reading of the upload at a timestamp wins
+        let corrected = correct_ingestion(
+            ingestion_id,
+            true,
+            readings(&[(11, "3"), (12, "4"), (12, "5")]),
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            corrected,
+            IngestionCorrection {
+                ingestion_id,
+                inserted: 1,
+                updated: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(amount_at(11, &mut conn), BigDecimal::from(3));
+        assert_eq!(amount_at(12, &mut conn), BigDecimal::from(5));
+        let (row_count, max_datetime) = ts_metadata::table
+            .find(ingestion_id)
+            .select((ts_metadata::row_count, ts_metadata::max_datetime))
+            .get_result::<(Option<i64>, Option<DateTime<Utc>>)>(&mut conn)
+            .unwrap();
+        assert_eq!((row_count, max_datetime), (Some(3), Some(at(12))));
+        let total = ts_daily_summary::table
+            .filter(ts_daily_summary::ingestion_id.eq(ingestion_id))
+            .select(ts_daily_summary::total_amount)
+            .get_result::<BigDecimal>(&mut conn)
+            .unwrap();
+        assert_eq!(total, "9.5".parse::<BigDecimal>().unwrap());
+
+        // Skipping keeps what is held, the first reading of the upload at a timestamp winning
+        let corrected = correct_ingestion(
+            ingestion_id,
+            false,
+            readings(&[(10, "9"), (13, "1"), (13, "2")]),
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (corrected.inserted, corrected.updated, corrected.skipped),
+            (1, 0, 2)
+        );
+        assert_eq!(
+            amount_at(10, &mut conn),
+            "1.5".parse::<BigDecimal>().unwrap()
+        );
+        assert_eq!(amount_at(13, &mut conn), BigDecimal::from(1));
+
+        // Each correction is chained, leaving the ingestion's readings verified
+        let verification = crate::db::chain::verify_chain(&mut conn).unwrap();
+        assert!(
+            !verification
+                .altered
+                .iter()
+                .any(|altered| altered.ingestion_id == ingestion_id)
+        );
+
+        // A hold refuses every merge, skipping ones included, and audits the refusal
+        place_hold(ingestion_id, "Dispute 2025-014", &mut conn).unwrap();
+        let refused =
+            correct_ingestion(ingestion_id, false, readings(&[(14, "1")]), &mut conn).unwrap();
+        assert_eq!(refused.map_err(|hold| hold.ingestion_id), Err(ingestion_id));
+        assert_eq!(
+            ts_store::table
+                .filter(ts_store::datetime.eq(at(14)))
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            0
+        );
+        let audited = audit_entries(Some(ingestion_id), 10, &mut conn).unwrap();
+        assert_eq!(audited[0].action, "correct_ingestion");
+        assert!(!audited[0].permitted);
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_as_recorded_by_aggregates_the_revision_valid_then() {
+        use crate::{db::seed_database::correct_ingestion, renewable_schema::ts_store_revisions};
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let at = |hour: u32| Utc.with_ymd_and_hms(2024, 1, 15, hour, 0, 0).unwrap();
+        let readings = |amounts: &[(u32, i64)]| -> Vec<CSVRecord> {
+            amounts
+                .iter()
+                .map(|&(hour, amount)| CSVRecord {
+                    datetime: at(hour),
+                    amount: BigDecimal::from(amount),
+                    extra: None,
+                })
+                .collect()
+        };
+        let (ingestion_id, _) = insert_ingestion(
+            "site-a.csv".to_string(),
+            None,
+            0,
+            readings(&[(10, 1), (11, 2)]),
+            0,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        let ingested = Utc::now();
+        correct_ingestion(ingestion_id, true, readings(&[(11, 3), (12, 4)]), &mut conn)
+            .unwrap()
+            .unwrap();
+        let corrected = Utc::now();
+        correct_ingestion(ingestion_id, true, readings(&[(11, 7)]), &mut conn)
+            .unwrap()
+            .unwrap();
+
+        let total_recorded_by = |recorded_by, conn: &mut PgConnection| {
+            aggregate_ts_query(Aggregation::DayInMonth, None, None, Some(recorded_by), conn)
+                .unwrap()
+                .into_iter()
+                .map(|record| record.total_amount.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            total_recorded_by(ingested, &mut conn),
+            [BigDecimal::from(3)]
+        );
+        assert_eq!(
+            total_recorded_by(corrected, &mut conn),
+            [BigDecimal::from(8)]
+        );
+        assert_eq!(
+            total_recorded_by(Utc::now(), &mut conn),
+            [BigDecimal::from(12)]
+        );
+
+        // An overwritten reading still counts as recorded by then
+        let points = bucket_point_counts(
+            Aggregation::DayInMonth,
+            None,
+            None,
+            Some(ingested),
+            None,
+            &mut conn,
+        )
+        .unwrap();
+        assert_eq!(points[0].1, 2);
+
+        // Every version replaced is kept, numbered in the order it was current
+        let revisions = ts_store_revisions::table
+            .filter(ts_store_revisions::ingestion_id.eq(ingestion_id))
+            .order(ts_store_revisions::revision)
+            .select((
+                ts_store_revisions::datetime,
+                ts_store_revisions::revision,
+                ts_store_revisions::amount,
+            ))
+            .load::<(DateTime<Utc>, i32, BigDecimal)>(&mut conn)
+            .unwrap();
+        assert_eq!(
+            revisions,
+            [
+                (at(11), 1, BigDecimal::from(2)),
+                (at(11), 2, BigDecimal::from(3)),
+            ]
+        );
+
+        cleanup_tables(&mut conn);
+    }
+
+    #[test]
+    #[serial]
+    fn test_chain_reports_readings_altered_behind_its_back() {
+        use crate::{
+            db::chain::verify_chain,
+            drift::{DriftConfig, DriftMode},
+            file_reader::ReadingsFormat,
+            model::csv::CsvSchema,
+            register::RegisterConfig,
+        };
+
+        let mut conn = get_test_connection();
+        cleanup_tables(&mut conn);
+
+        let drift_config = DriftConfig {
+            interval: Duration::hours(1),
+            mode: DriftMode::default(),
+        };
+        let file = b"{\"datetime\": \"2024-01-15T10:00:00Z\", \"amount\": \"1.5\"}\n\
+            {\"datetime\": \"2024-01-15T11:00:00Z\", \"amount\": \"2.5\"}\n";
+        let prepared = prepare_readings(
+            &file[..],
+            ReadingsFormat::Ndjson,
+            &CsvSchema::default(),
+            &RegisterConfig::default(),
+            &drift_config,
+        );
+        let (ingestion_id, _) = insert_ingestion_with_drift(
+            "chained.ndjson".to_string(),
+            None,
+            0,
+            prepared,
+            &drift_config,
+            &mut conn,
+        )
+        .unwrap()
+        .unwrap();
+        let altered = |conn: &mut PgConnection| {
+            let verification = verify_chain(conn).unwrap();
+            assert_eq!(verification.broken_at, None);
+            verification
+                .altered
+                .iter()
+                .any(|altered| altered.ingestion_id == ingestion_id)
+        };
+        assert!(!altered(&mut conn));
+
+        diesel::update(ts_store::table.filter(ts_store::ingestion_id.eq(ingestion_id)))
+            .filter(ts_store::datetime.eq(Utc.with_ymd_and_hms(2024, 1, 15, 11, 0, 0).unwrap()))
+            .set(ts_store::amount.eq(BigDecimal::from(25)))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(altered(&mut conn));
+
+        // Deleting through the service is recorded, leaving nothing to compare
+        delete_ingestion(ingestion_id, &mut conn).unwrap();
+        assert!(!altered(&mut conn));
+        // Nor can the entries be rewritten
+        assert!(
+            diesel::sql_query("UPDATE renewable.ingestion_chain SET readings = 0")
+                .execute(&mut conn)
+                .is_err()
+        );
+
+        cleanup_tables(&mut conn);
     }
 }
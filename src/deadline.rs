@@ -0,0 +1,138 @@
+use std::{
+    convert::Infallible,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, request::Parts},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+use chrono::{DateTime, Utc};
+
+use crate::error::ApiError;
+
+/// Client supplied deadline as an RFC 3339 timestamp, only ever shortens the server timeout
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+
+/// Point in time after which the client is no longer waiting for a response
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Time left before the deadline, `None` when the request is unbounded
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Deadline {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().copied().unwrap_or_default())
+    }
+}
+
+fn client_budget(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let deadline = headers.get(DEADLINE_HEADER)?.to_str().ok()?;
+    let deadline = DateTime::parse_from_rfc3339(deadline).ok()?;
+    Some(
+        (deadline.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Records the earlier of the server timeout of the route group and the client deadline
+/// on the request, answering with a JSON 504 once the server timeout passes
+pub async fn enforce_deadline(
+    State(timeout): State<Duration>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let budget =
+        client_budget(request.headers(), Utc::now()).map_or(timeout, |budget| budget.min(timeout));
+    request
+        .extensions_mut()
+        .insert(Deadline(Some(Instant::now() + budget)));
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => ApiError::Timeout(timeout).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use axum::{
+        Router,
+        body::Body,
+        extract::Request,
+        http::{HeaderMap, HeaderValue, StatusCode},
+        middleware,
+        routing::get,
+    };
+    use chrono::{TimeZone, Utc};
+    use tower::ServiceExt as _;
+
+    use super::{DEADLINE_HEADER, client_budget, enforce_deadline};
+
+    #[test]
+    fn test_client_budget_from_header() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        let mut headers = HeaderMap::new();
+        assert!(client_budget(&headers, now).is_none());
+
+        headers.insert(
+            DEADLINE_HEADER,
+            HeaderValue::from_static("2025-01-01T12:00:01.500Z"),
+        );
+        assert_eq!(
+            client_budget(&headers, now),
+            Some(Duration::from_millis(1500))
+        );
+
+        headers.insert(
+            DEADLINE_HEADER,
+            HeaderValue::from_static("2025-01-01T11:59:00Z"),
+        );
+        assert_eq!(client_budget(&headers, now), Some(Duration::ZERO));
+
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("soon"));
+        assert!(client_budget(&headers, now).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slow_routes_time_out_with_json_body() {
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .route("/fast", get(|| async { "done" }))
+            .route_layer(middleware::from_fn_with_state(
+                Duration::from_millis(20),
+                enforce_deadline,
+            ));
+        let request = |path| Request::get(path).body(Body::empty()).unwrap();
+
+        let fast = app.clone().oneshot(request("/fast")).await.unwrap();
+        assert_eq!(fast.status(), StatusCode::OK);
+
+        let slow = app.oneshot(request("/slow")).await.unwrap();
+        assert_eq!(slow.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(slow.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "deadline_exceeded");
+        assert_eq!(body["message"], "Deadline Exceeded");
+    }
+}
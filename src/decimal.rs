@@ -0,0 +1,252 @@
+use std::{future::Future, str::FromStr};
+
+use axum::{
+    extract::{Query, Request},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive as _};
+use serde::{Deserialize, Serializer};
+
+use crate::{error::ApiError, model::AMOUNT_SCALE, rounding};
+
+tokio::task_local! {
+    /// Format asked for by the client of the request being handled, see [`select_format`]
+    static REQUESTED: DecimalFormat;
+}
+
+/// How decimal amounts are written into a response, after the rounding policy
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecimalFormat {
+    /// The exact decimal as a string, e.g. `"1500.25"`, the default of CSV
+    Exact,
+    /// The nearest `f64`, the default of JSON
+    Float,
+    /// Whole number of millionths, `AMOUNT_SCALE` places, exact where a float is not
+    Scaled,
+}
+
+impl FromStr for DecimalFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "exact" => Ok(Self::Exact),
+            "float" => Ok(Self::Float),
+            "scaled" => Ok(Self::Scaled),
+            other => Err(format!(
+                "unknown decimal format {other}, use exact, float or scaled"
+            )),
+        }
+    }
+}
+
+impl DecimalFormat {
+    /// The format asked for by the client, `default` being that of the content type
+    pub fn current(default: Self) -> Self {
+        requested().unwrap_or(default)
+    }
+
+    /// Millionths in `value`, rounded half to even
+    fn scaled(value: &BigDecimal) -> bigdecimal::num_bigint::BigInt {
+        value
+            .with_scale_round(AMOUNT_SCALE, RoundingMode::HalfEven)
+            .as_bigint_and_exponent()
+            .0
+    }
+
+    /// `value` as the text of a CSV field, empty for a float out of range
+    pub fn render(self, value: &BigDecimal) -> String {
+        match self {
+            Self::Exact => value.to_string(),
+            Self::Float => value.to_f64().map(|v| v.to_string()).unwrap_or_default(),
+            Self::Scaled => Self::scaled(value).to_string(),
+        }
+    }
+
+    /// Writes `value`, `null` for a float out of range and a string for a scaled amount
+    /// beyond an `i128`
+    pub fn serialize<S: Serializer>(
+        self,
+        value: &BigDecimal,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Exact => serializer.collect_str(value),
+            Self::Float => serializer.serialize_some(&value.to_f64()),
+            Self::Scaled => {
+                let units = Self::scaled(value);
+                match units.to_i128() {
+                    Some(units) => serializer.serialize_i128(units),
+                    None => serializer.collect_str(&units),
+                }
+            }
+        }
+    }
+}
+
+/// Format asked for by the client, `None` when left to the content type
+pub fn requested() -> Option<DecimalFormat> {
+    REQUESTED.try_with(|format| *format).ok()
+}
+
+/// Refuses a format other than [`DecimalFormat::Exact`] for a `surface` whose amounts
+/// are typed decimals, e.g. Arrow's `Decimal128` columns, which no other format fits
+pub fn require_exact(surface: &str) -> Result<(), ApiError> {
+    match requested() {
+        None | Some(DecimalFormat::Exact) => Ok(()),
+        Some(_) => Err(ApiError::BadRequest(format!(
+            "{surface} amounts are typed decimals, only decimals=exact is supported"
+        ))),
+    }
+}
+
+/// Runs `f` with the client's `format`, for responses written on a blocking thread
+pub fn within<R>(format: Option<DecimalFormat>, f: impl FnOnce() -> R) -> R {
+    match format {
+        Some(format) => REQUESTED.sync_scope(format, f),
+        None => f(),
+    }
+}
+
+/// Keeps the format asked for by the client for a future spawned to finish the request
+pub fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    // Read when called, not when first polled on another task
+    let format = requested();
+    async move {
+        match format {
+            Some(format) => REQUESTED.scope(format, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Serializes an optional amount in the format of the response, see [`DecimalFormat`]
+pub fn serialize_opt<S>(value: &Option<BigDecimal>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(v) => DecimalFormat::current(DecimalFormat::Float)
+            .serialize(&rounding::current().apply(v), serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// As [`serialize_opt`] for every value of a sequence
+pub fn serialize_opt_seq<S>(values: &[Option<BigDecimal>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    struct Amount<'a>(&'a Option<BigDecimal>);
+
+    impl serde::Serialize for Amount<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_opt(self.0, serializer)
+        }
+    }
+
+    serializer.collect_seq(values.iter().map(Amount))
+}
+
+#[derive(Debug, Deserialize)]
+struct DecimalParams {
+    decimals: Option<String>,
+}
+
+/// Picks `?decimals=` when given, otherwise a `decimals` parameter of a media type in
+/// `Accept`, e.g. `application/json; decimals=exact`
+fn negotiate(decimals: Option<&str>, headers: &HeaderMap) -> Result<Option<DecimalFormat>, String> {
+    let accepted = || {
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .flat_map(|media| media.split(';').skip(1))
+            .find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                (name.trim() == "decimals").then(|| value.trim().trim_matches('"'))
+            })
+    };
+    decimals.or_else(accepted).map(str::parse).transpose()
+}
+
+/// Makes the decimal format asked for by the client available to the response's
+/// serializers, rejecting an unknown one
+pub async fn select_format(request: Request, next: Next) -> Response {
+    let decimals = match Query::<DecimalParams>::try_from_uri(request.uri()) {
+        Ok(Query(params)) => params.decimals,
+        // Malformed queries are left for the handler to reject
+        Err(_) => None,
+    };
+    match negotiate(decimals.as_deref(), request.headers()) {
+        Ok(Some(format)) => REQUESTED.scope(format, next.run(request)).await,
+        Ok(None) => next.run(request).await,
+        Err(e) => ApiError::BadRequest(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr as _;
+
+    use axum::http::{HeaderMap, HeaderValue, header};
+    use bigdecimal::BigDecimal;
+    use serde::Serialize;
+    use test_case::test_case;
+
+    use super::{DecimalFormat, negotiate, require_exact, within};
+
+    #[derive(Serialize)]
+    struct Total {
+        #[serde(serialize_with = "super::serialize_opt")]
+        amount: Option<BigDecimal>,
+    }
+
+    #[test_case(None, r#"{"amount":1500.25}"#)]
+    #[test_case(Some(DecimalFormat::Float), r#"{"amount":1500.25}"#)]
+    #[test_case(Some(DecimalFormat::Exact), r#"{"amount":"1500.25"}"#)]
+    #[test_case(Some(DecimalFormat::Scaled), r#"{"amount":1500250000}"#)]
+    fn test_serialize_in_requested_format(format: Option<DecimalFormat>, expected: &str) {
+        let total = Total {
+            amount: Some(BigDecimal::from_str("1500.25").unwrap()),
+        };
+        let json = within(format, || serde_json::to_string(&total).unwrap());
+        assert_eq!(json, expected);
+    }
+
+    #[test_case(DecimalFormat::Exact, "0.1000001")]
+    #[test_case(DecimalFormat::Float, "0.1000001")]
+    #[test_case(DecimalFormat::Scaled, "100000")]
+    fn test_render(format: DecimalFormat, expected: &str) {
+        let value = BigDecimal::from_str("0.1000001").unwrap();
+        assert_eq!(format.render(&value), expected);
+    }
+
+    #[test_case(None, None, Ok(None); "left to the content type")]
+    #[test_case(Some("scaled"), None, Ok(Some(DecimalFormat::Scaled)); "param")]
+    #[test_case(None, Some("text/csv, application/json; decimals=\"exact\""), Ok(Some(DecimalFormat::Exact)); "accept")]
+    #[test_case(Some("float"), Some("application/json; decimals=exact"), Ok(Some(DecimalFormat::Float)); "param wins")]
+    #[test_case(Some("double"), None, Err("unknown decimal format double, use exact, float or scaled".to_string()); "unknown")]
+    fn test_negotiate(
+        decimals: Option<&str>,
+        accept: Option<&'static str>,
+        expected: Result<Option<DecimalFormat>, String>,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+        }
+        assert_eq!(negotiate(decimals, &headers), expected);
+    }
+
+    #[test_case(None, true)]
+    #[test_case(Some(DecimalFormat::Exact), true)]
+    #[test_case(Some(DecimalFormat::Float), false)]
+    #[test_case(Some(DecimalFormat::Scaled), false)]
+    fn test_require_exact(format: Option<DecimalFormat>, allowed: bool) {
+        assert_eq!(within(format, || require_exact("Arrow")).is_ok(), allowed);
+    }
+}
@@ -0,0 +1,201 @@
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive as _, num_bigint::BigInt};
+use chrono::DateTime;
+
+use crate::model::{AMOUNT_SCALE, csv::CSVRecord};
+
+/// Layout version written as the first byte of every block
+pub const BLOCK_VERSION: u8 = 1;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum BlockError {
+    #[error("block is truncated")]
+    Truncated,
+
+    #[error("unsupported block version {0}")]
+    Version(u8),
+
+    #[error("amount {0} does not fit a block")]
+    Amount(BigDecimal),
+
+    #[error("block holds a timestamp out of range")]
+    Timestamp,
+}
+
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)).cast_unsigned()
+}
+
+fn unzigzag(value: u128) -> i128 {
+    (value >> 1).cast_signed() ^ -(value & 1).cast_signed()
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(block: &mut &[u8]) -> Result<u128, BlockError> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = block.split_first().ok_or(BlockError::Truncated)?;
+        *block = rest;
+        value |= u128::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(BlockError::Truncated)
+}
+
+/// Amount as an integer count of the column's smallest unit, which every stored amount is
+fn mantissa(amount: &BigDecimal) -> Result<i128, BlockError> {
+    let (digits, _) = amount
+        .with_scale_round(AMOUNT_SCALE, RoundingMode::HalfUp)
+        .into_bigint_and_exponent();
+    digits
+        .to_i128()
+        .ok_or_else(|| BlockError::Amount(amount.clone()))
+}
+
+/// Encodes readings ordered by time into one block. Timestamps are stored as the delta
+/// of their deltas, zero for every reading on a regular interval, and amounts XORed with
+/// the previous amount, both as varints so unchanged values take a single byte.
+pub fn encode(readings: &[CSVRecord]) -> Result<Vec<u8>, BlockError> {
+    let mut block = vec![BLOCK_VERSION];
+    write_varint(&mut block, readings.len() as u128);
+
+    let (mut previous, mut previous_delta) = (0i64, 0i64);
+    for (index, reading) in readings.iter().enumerate() {
+        let micros = reading.datetime.timestamp_micros();
+        let value = match index {
+            0 => micros,
+            1 => micros.wrapping_sub(previous),
+            _ => micros.wrapping_sub(previous).wrapping_sub(previous_delta),
+        };
+        write_varint(&mut block, zigzag(i128::from(value)));
+        previous_delta = micros.wrapping_sub(previous);
+        previous = micros;
+    }
+
+    let mut previous = 0u128;
+    for reading in readings {
+        let value = zigzag(mantissa(&reading.amount)?);
+        write_varint(&mut block, value ^ previous);
+        previous = value;
+    }
+    Ok(block)
+}
+
+/// Reverses [`encode`]
+pub fn decode(mut block: &[u8]) -> Result<Vec<CSVRecord>, BlockError> {
+    let (&version, rest) = block.split_first().ok_or(BlockError::Truncated)?;
+    if version != BLOCK_VERSION {
+        return Err(BlockError::Version(version));
+    }
+    block = rest;
+    let count = usize::try_from(read_varint(&mut block)?).map_err(|_| BlockError::Truncated)?;
+    // Every value takes at least a byte, so a count beyond the block is corrupt
+    if count > block.len() {
+        return Err(BlockError::Truncated);
+    }
+
+    let mut timestamps = Vec::with_capacity(count);
+    let (mut previous, mut previous_delta) = (0i64, 0i64);
+    for index in 0..count {
+        let value =
+            i64::try_from(unzigzag(read_varint(&mut block)?)).map_err(|_| BlockError::Timestamp)?;
+        let micros = match index {
+            0 => value,
+            1 => previous.wrapping_add(value),
+            _ => previous.wrapping_add(previous_delta).wrapping_add(value),
+        };
+        previous_delta = micros.wrapping_sub(previous);
+        previous = micros;
+        timestamps.push(DateTime::from_timestamp_micros(micros).ok_or(BlockError::Timestamp)?);
+    }
+
+    let mut readings = Vec::with_capacity(count);
+    let mut previous = 0u128;
+    for datetime in timestamps {
+        let value = read_varint(&mut block)? ^ previous;
+        previous = value;
+        readings.push(CSVRecord {
+            datetime,
+            amount: BigDecimal::new(BigInt::from(unzigzag(value)), AMOUNT_SCALE),
+            extra: None,
+        });
+    }
+    Ok(readings)
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::{BLOCK_VERSION, BlockError, decode, encode};
+    use crate::model::csv::CSVRecord;
+
+    fn reading(minutes: i64, amount: &str) -> CSVRecord {
+        CSVRecord {
+            datetime: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+                + TimeDelta::minutes(minutes),
+            amount: amount.parse().unwrap(),
+            extra: None,
+        }
+    }
+
+    #[test]
+    fn test_blocks_round_trip() {
+        let readings = vec![
+            reading(0, "0"),
+            reading(30, "12.5"),
+            reading(60, "12.5"),
+            reading(95, "-3.000001"),
+            reading(120, "9999999999999999999999.999999"),
+        ];
+        assert_eq!(decode(&encode(&readings).unwrap()).unwrap(), readings);
+        assert_eq!(decode(&encode(&[]).unwrap()).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_regular_day_packs_into_few_bytes() {
+        let readings: Vec<_> = (0..48).map(|i| reading(i * 30, "250.125")).collect();
+        let block = encode(&readings).unwrap();
+        // Header, the first timestamp, delta and amount in full, then a byte per further
+        // timestamp and amount, against 48 rows of at least 30 bytes each in ts_store
+        assert!(
+            block.len() <= 2 + 8 + 5 + 5 + 2 * 47,
+            "{} bytes",
+            block.len()
+        );
+        assert_eq!(decode(&block).unwrap(), readings);
+    }
+
+    #[test]
+    fn test_corrupt_blocks_are_rejected() {
+        let block = encode(&[reading(0, "1"), reading(30, "2")]).unwrap();
+        assert_eq!(
+            decode(&block[..block.len() - 1]),
+            Err(BlockError::Truncated)
+        );
+        assert_eq!(decode(&[]), Err(BlockError::Truncated));
+
+        let mut versioned = block;
+        versioned[0] = BLOCK_VERSION + 1;
+        assert_eq!(
+            decode(&versioned),
+            Err(BlockError::Version(BLOCK_VERSION + 1))
+        );
+        assert!(matches!(
+            encode(&[CSVRecord {
+                amount: "1e40".parse::<BigDecimal>().unwrap(),
+                ..reading(0, "0")
+            }]),
+            Err(BlockError::Amount(_))
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime};
+
+use crate::{
+    file_reader,
+    model::{
+        DATETIME_FORMAT,
+        api_response::{
+            ColumnMapping, ColumnRole, DetectedCandidate, FormatDetection, RoleCandidate,
+        },
+        csv::CsvSchema,
+    },
+};
+
+pub const DEFAULT_SAMPLE_ROWS: usize = 100;
+pub const MAX_SAMPLE_ROWS: usize = 1000;
+
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Datetime layouts recognised, the seed file's first so it wins ties
+const DATETIME_FORMATS: [&str; 9] = [
+    DATETIME_FORMAT,
+    "%Y-%m-%dT%H:%M:%SZ",
+    "%Y-%m-%dT%H:%M:%S%:z",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d/%m/%Y %H:%M",
+    "%m/%d/%Y %H:%M",
+    "%d.%m.%Y %H:%M",
+    "%d-%m-%Y %H:%M",
+];
+
+/// Header words suggesting a column's role, matched case-insensitively
+const DATETIME_HINTS: [&str; 4] = ["time", "date", "period", "interval"];
+const AMOUNT_HINTS: [&str; 7] = [
+    "kwh",
+    "mwh",
+    "quantity",
+    "amount",
+    "value",
+    "energy",
+    "generation",
+];
+
+/// Weight of a matching header name against how many values parse
+const HINT_WEIGHT: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecimalConvention {
+    /// `1,234.5`
+    Point,
+    /// `1.234,5` or `1 234,5`
+    Comma,
+}
+
+impl DecimalConvention {
+    const ALL: [Self; 2] = [Self::Point, Self::Comma];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Point => "point",
+            Self::Comma => "comma",
+        }
+    }
+
+    /// Whether `value` is a number in this convention, with any thousands separators in
+    /// groups of three
+    fn parses(self, value: &str) -> bool {
+        let (decimal, grouping): (char, &[char]) = match self {
+            Self::Point => ('.', &[',']),
+            Self::Comma => (',', &['.', ' ', '\u{a0}']),
+        };
+        let value = value.trim_matches('"').trim();
+        let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+        let (integer, fraction) = match value.split_once(decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (value, None),
+        };
+        if fraction.is_some_and(|fraction| !is_digits(fraction)) {
+            return false;
+        }
+        match grouping
+            .iter()
+            .find(|separator| integer.contains(**separator))
+        {
+            Some(separator) => {
+                let mut groups = integer.split(*separator);
+                let leading = groups.next().unwrap_or_default();
+                (1..=3).contains(&leading.len())
+                    && is_digits(leading)
+                    && groups.all(|group| group.len() == 3 && is_digits(group))
+            }
+            None => is_digits(integer),
+        }
+    }
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn parses_datetime(value: &str, format: &str) -> bool {
+    NaiveDateTime::parse_from_str(value, format).is_ok()
+        || DateTime::parse_from_str(value, format).is_ok()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn share(matching: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        matching as f64 / total as f64
+    }
+}
+
+/// Candidates with any support, most likely first, ties kept in listing order
+fn ranked(candidates: impl IntoIterator<Item = (String, f64)>) -> Vec<DetectedCandidate> {
+    let mut ranked: Vec<DetectedCandidate> = candidates
+        .into_iter()
+        .filter(|(_, confidence)| *confidence > 0.0)
+        .map(|(value, confidence)| DetectedCandidate { value, confidence })
+        .collect();
+    ranked.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    ranked
+}
+
+/// How the values of one column parse
+struct ColumnProfile {
+    datetime_formats: Vec<f64>,
+    decimal_conventions: Vec<f64>,
+}
+
+impl ColumnProfile {
+    fn new(values: &[&str]) -> Self {
+        let values: Vec<&str> = values
+            .iter()
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .collect();
+        let parsing = |parses: &dyn Fn(&str) -> bool| {
+            share(
+                values.iter().filter(|value| parses(value)).count(),
+                values.len(),
+            )
+        };
+        Self {
+            datetime_formats: DATETIME_FORMATS
+                .iter()
+                .map(|format| parsing(&|value| parses_datetime(value, format)))
+                .collect(),
+            decimal_conventions: DecimalConvention::ALL
+                .iter()
+                .map(|convention| parsing(&|value| convention.parses(value)))
+                .collect(),
+        }
+    }
+
+    fn datetime(&self) -> f64 {
+        self.datetime_formats.iter().copied().fold(0.0, f64::max)
+    }
+
+    fn amount(&self) -> f64 {
+        self.decimal_conventions.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+/// The sample split by one delimiter
+struct Layout {
+    delimiter: u8,
+    /// Share of rows with the most common field count, zero for a single column
+    consistency: f64,
+    has_header: bool,
+    header: Vec<String>,
+    columns: Vec<ColumnProfile>,
+    data_rows: usize,
+}
+
+impl Layout {
+    fn new(sample: &[u8], delimiter: u8, sample_rows: usize) -> Self {
+        let rows: Vec<Vec<String>> = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(sample)
+            .into_records()
+            .take(sample_rows + 1)
+            .filter_map(Result::ok)
+            .map(|record| record.iter().map(ToString::to_string).collect())
+            .collect();
+
+        let mut widths: HashMap<usize, usize> = HashMap::new();
+        for row in &rows {
+            *widths.entry(row.len()).or_default() += 1;
+        }
+        let (width, consistent) = widths
+            .into_iter()
+            .max_by_key(|(width, rows)| (*rows, *width))
+            .unwrap_or_default();
+        let consistency = if width < 2 {
+            0.0
+        } else {
+            share(consistent, rows.len())
+        };
+
+        // A header row holds only labels, nothing that parses as a reading
+        let has_header = rows.len() > 1
+            && rows[0].iter().all(|cell| {
+                let profile = ColumnProfile::new(&[cell.as_str()]);
+                profile.datetime() == 0.0 && profile.amount() == 0.0
+            });
+        let (header, data) = match rows.split_first() {
+            Some((header, data)) if has_header => (header.clone(), data),
+            _ => (Vec::new(), rows.as_slice()),
+        };
+        let data = &data[..data.len().min(sample_rows)];
+        let columns = (0..width)
+            .map(|index| {
+                let values: Vec<&str> = data
+                    .iter()
+                    .filter_map(|row| row.get(index).map(String::as_str))
+                    .collect();
+                ColumnProfile::new(&values)
+            })
+            .collect();
+
+        Self {
+            delimiter,
+            consistency,
+            has_header,
+            header,
+            columns,
+            data_rows: data.len(),
+        }
+    }
+
+    /// Consistent splitting that also yields a datetime and an amount column beats
+    /// consistent splitting alone, e.g. `;` over `,` for `1.1.2025 00:00;9,5`
+    fn score(&self) -> f64 {
+        let best =
+            |role: fn(&ColumnProfile) -> f64| self.columns.iter().map(role).fold(0.0, f64::max);
+        self.consistency * (1.0 + best(ColumnProfile::datetime) + best(ColumnProfile::amount)) / 3.0
+    }
+
+    fn column_with(&self, role: fn(&ColumnProfile) -> f64) -> Option<&ColumnProfile> {
+        self.columns
+            .iter()
+            .filter(|column| role(column) > 0.0)
+            .max_by(|a, b| role(a).total_cmp(&role(b)))
+    }
+
+    fn mapping(&self, index: usize, column: &ColumnProfile) -> ColumnMapping {
+        let header = self.header.get(index).cloned();
+        let hinted = |hints: &[&str]| {
+            header.as_ref().map(|header| {
+                let header = header.to_lowercase();
+                f64::from(u8::from(hints.iter().any(|hint| header.contains(hint))))
+            })
+        };
+        let confidence = |parsed: f64, hint: Option<f64>| match hint {
+            Some(hint) => (1.0 - HINT_WEIGHT) * parsed + HINT_WEIGHT * hint,
+            None => parsed,
+        };
+        let mut candidates: Vec<RoleCandidate> = [
+            (
+                ColumnRole::Datetime,
+                confidence(column.datetime(), hinted(&DATETIME_HINTS)),
+            ),
+            (
+                ColumnRole::Amount,
+                confidence(column.amount(), hinted(&AMOUNT_HINTS)),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, confidence)| *confidence > 0.0)
+        .map(|(role, confidence)| RoleCandidate { role, confidence })
+        .collect();
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        ColumnMapping {
+            index,
+            header,
+            candidates,
+        }
+    }
+}
+
+fn delimiter_label(delimiter: u8) -> String {
+    match delimiter {
+        b'\t' => "\\t".to_string(),
+        other => char::from(other).to_string(),
+    }
+}
+
+/// Detects the delimiter, datetime format, decimal convention and column roles of the
+/// first `sample_rows` data rows of `sample`, tolerating layouts the ingestion path
+/// cannot read yet so an upload wizard can propose a mapping. `ingestible` reports whether
+/// the sample reads as `schema` describes.
+pub fn detect_format(sample: &[u8], sample_rows: usize, schema: &CsvSchema) -> FormatDetection {
+    let layouts: Vec<Layout> = DELIMITERS
+        .iter()
+        .map(|delimiter| Layout::new(sample, *delimiter, sample_rows))
+        .collect();
+    let delimiters = ranked(
+        layouts
+            .iter()
+            .map(|layout| (delimiter_label(layout.delimiter), layout.score())),
+    );
+    let layout = layouts
+        .iter()
+        .max_by(|a, b| {
+            a.score()
+                .total_cmp(&b.score())
+                .then(b.delimiter.cmp(&a.delimiter))
+        })
+        .expect("delimiters are listed");
+
+    let datetime_formats = layout
+        .column_with(ColumnProfile::datetime)
+        .map(|column| {
+            ranked(
+                DATETIME_FORMATS
+                    .iter()
+                    .zip(&column.datetime_formats)
+                    .map(|(format, share)| ((*format).to_string(), *share)),
+            )
+        })
+        .unwrap_or_default();
+    let decimal_conventions = layout
+        .column_with(ColumnProfile::amount)
+        .map(|column| {
+            ranked(
+                DecimalConvention::ALL
+                    .iter()
+                    .zip(&column.decimal_conventions)
+                    .map(|(convention, share)| (convention.name().to_string(), *share)),
+            )
+        })
+        .unwrap_or_default();
+    let columns = layout
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| layout.mapping(index, column))
+        .collect();
+
+    let mut readings = file_reader::csv_stream(sample, schema)
+        .take(sample_rows)
+        .peekable();
+    let ingestible = readings.peek().is_some() && readings.all(|reading| reading.is_ok());
+
+    FormatDetection {
+        sampled_rows: layout.data_rows,
+        has_header: layout.has_header,
+        delimiters,
+        datetime_formats,
+        decimal_conventions,
+        columns,
+        ingestible,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{DecimalConvention, detect_format};
+    use crate::model::{api_response::ColumnRole, csv::CsvSchema};
+
+    #[test_case("9,000.000", true, false)]
+    #[test_case("9.000,5", false, true)]
+    #[test_case("1 234,5", false, true)]
+    #[test_case("1,500", true, true)]
+    #[test_case("-12.25", true, false)]
+    #[test_case("12,34,5", false, false)]
+    #[test_case("kWh", false, false)]
+    fn test_decimal_conventions(value: &str, point: bool, comma: bool) {
+        assert_eq!(DecimalConvention::Point.parses(value), point);
+        assert_eq!(DecimalConvention::Comma.parses(value), comma);
+    }
+
+    #[test]
+    fn test_detects_seed_file_layout() {
+        let sample =
+            b"Time (UTC),Quantity kWh\n1 Jan 2025 00:00,\"9,000.000\"\n1 Jan 2025 01:00,\"8,500.5\"\n";
+        let detection = detect_format(sample, 100, &CsvSchema::default());
+
+        assert!(detection.ingestible);
+        assert!(detection.has_header);
+        assert_eq!(detection.sampled_rows, 2);
+        assert_eq!(detection.delimiters[0].value, ",");
+        assert_eq!(detection.datetime_formats[0].value, "%-d %b %Y %H:%M");
+        assert_eq!(detection.decimal_conventions[0].value, "point");
+        assert_eq!(
+            detection.columns[0].candidates[0].role,
+            ColumnRole::Datetime
+        );
+        assert_eq!(detection.columns[1].candidates[0].role, ColumnRole::Amount);
+    }
+
+    #[test]
+    fn test_detects_continental_layout() {
+        let sample = b"Zeitstempel;Energie\n13.01.2025 00:00;1.234,5\n13.01.2025 01:00;980,25\n";
+        let detection = detect_format(sample, 100, &CsvSchema::default());
+
+        assert!(!detection.ingestible);
+        assert_eq!(detection.delimiters[0].value, ";");
+        assert_eq!(detection.datetime_formats[0].value, "%d.%m.%Y %H:%M");
+        assert_eq!(detection.decimal_conventions[0].value, "comma");
+        assert_eq!(detection.decimal_conventions[0].confidence, 1.0);
+        assert_eq!(detection.columns[1].header.as_deref(), Some("Energie"));
+        assert_eq!(detection.columns[1].candidates[0].role, ColumnRole::Amount);
+    }
+
+    #[test]
+    fn test_headerless_sample_is_limited_to_sample_rows() {
+        let sample = b"2025-01-01 00:00|1.5\n2025-01-01 01:00|2\n2025-01-01 02:00|3\n";
+        let detection = detect_format(sample, 2, &CsvSchema::default());
+
+        assert!(!detection.has_header);
+        assert_eq!(detection.sampled_rows, 2);
+        assert_eq!(detection.delimiters[0].value, "|");
+        assert_eq!(detection.datetime_formats[0].value, "%Y-%m-%d %H:%M");
+    }
+}
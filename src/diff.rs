@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, Zero as _};
+use chrono::{DateTime, Utc};
+
+use crate::model::api_response::{AggregationQueryRecord, BucketChange};
+
+/// Compares two evaluations of the same aggregation and reports the buckets whose
+/// totals differ, ordered by time. Buckets only present on one side are included
+/// with the missing total treated as zero for the difference.
+pub fn diff_buckets(
+    baseline: Vec<AggregationQueryRecord>,
+    compare: Vec<AggregationQueryRecord>,
+) -> Vec<BucketChange> {
+    let mut buckets: BTreeMap<DateTime<Utc>, (Option<BigDecimal>, Option<BigDecimal>)> =
+        BTreeMap::new();
+    for record in baseline {
+        buckets.entry(record.datetime).or_default().0 = record.total_amount;
+    }
+    for record in compare {
+        buckets.entry(record.datetime).or_default().1 = record.total_amount;
+    }
+
+    buckets
+        .into_iter()
+        .filter(|(_, (baseline, compare))| baseline != compare)
+        .map(|(datetime, (baseline_amount, compare_amount))| {
+            let difference = compare_amount.clone().unwrap_or_else(BigDecimal::zero)
+                - baseline_amount.clone().unwrap_or_else(BigDecimal::zero);
+            BucketChange {
+                datetime,
+                baseline_amount,
+                compare_amount,
+                difference: Some(difference),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::diff_buckets;
+    use crate::model::api_response::AggregationQueryRecord;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, d, 0, 0, 0).unwrap()
+    }
+
+    fn record(d: u32, amount: i64) -> AggregationQueryRecord {
+        AggregationQueryRecord {
+            datetime: day(d),
+            total_amount: Some(BigDecimal::from(amount)),
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_changed_buckets_only() {
+        let baseline = vec![record(1, 10), record(2, 20), record(3, 30)];
+        let compare = vec![record(3, 35), record(1, 10), record(4, 5)];
+
+        let changes = diff_buckets(baseline, compare);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c.datetime, c.difference.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (day(2), BigDecimal::from(-20)),
+                (day(3), BigDecimal::from(5)),
+                (day(4), BigDecimal::from(5)),
+            ]
+        );
+        assert!(changes[0].compare_amount.is_none());
+        assert!(changes[2].baseline_amount.is_none());
+    }
+}
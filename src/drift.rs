@@ -0,0 +1,183 @@
+use std::{env, str::FromStr};
+
+use chrono::TimeDelta;
+
+use crate::model::{csv::CSVRecord, database::IngestionClockDrift};
+
+/// What to store for readings whose timestamp is off the interval grid
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DriftMode {
+    /// Store timestamps as received, only reporting the drift
+    #[default]
+    Preserve,
+    /// Move each reading to its nearest grid point
+    Snap,
+}
+
+impl FromStr for DriftMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preserve" => Ok(Self::Preserve),
+            "snap" => Ok(Self::Snap),
+            other => Err(format!("unknown clock drift mode {other}")),
+        }
+    }
+}
+
+/// Expected reading grid for a seed file, the configured reading interval with the mode
+/// read from `SEED_CLOCK_DRIFT`
+#[derive(Debug, Clone, Copy)]
+pub struct DriftConfig {
+    pub interval: TimeDelta,
+    pub mode: DriftMode,
+}
+
+impl DriftConfig {
+    pub fn from_env(interval: TimeDelta) -> Result<Self, String> {
+        let mode = match env::var("SEED_CLOCK_DRIFT") {
+            Ok(mode) => mode.parse()?,
+            Err(_) => DriftMode::default(),
+        };
+        Ok(Self { interval, mode })
+    }
+}
+
+/// Clock drift found in a set of readings, before it is attached to an ingestion
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct DriftReport {
+    pub readings: usize,
+    pub drifted: usize,
+    pub max_offset_secs: i64,
+    pub collisions: usize,
+}
+
+impl DriftReport {
+    pub fn into_record(self, ingestion_id: i64, config: &DriftConfig) -> IngestionClockDrift {
+        IngestionClockDrift {
+            ingestion_id,
+            interval_secs: i32::try_from(config.interval.num_seconds()).unwrap_or(i32::MAX),
+            readings: i64::try_from(self.readings).unwrap_or(i64::MAX),
+            drifted_readings: i64::try_from(self.drifted).unwrap_or(i64::MAX),
+            max_offset_secs: self.max_offset_secs,
+            snapped: config.mode == DriftMode::Snap,
+            collisions: i64::try_from(self.collisions).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+/// Signed whole-second distance from `reading` to its nearest grid point, a reading
+/// exactly half an interval out is treated as late
+fn grid_offset(reading: &CSVRecord, interval_secs: i64) -> i64 {
+    let offset = reading.datetime.timestamp().rem_euclid(interval_secs);
+    if offset > interval_secs / 2 {
+        offset - interval_secs
+    } else {
+        offset
+    }
+}
+
+/// Measures how far readings sit from the interval grid, e.g. `:02` or `:17` past the
+/// hour for an hourly meter, which usually points at a faulty gateway clock.
+///
+/// In [`DriftMode::Snap`] drifted readings are moved to their nearest grid point and,
+/// where several land on the same point, only the one closest to it is kept.
+pub fn analyse(readings: Vec<CSVRecord>, config: &DriftConfig) -> (Vec<CSVRecord>, DriftReport) {
+    let interval_secs = config.interval.num_seconds();
+    let mut report = DriftReport {
+        readings: readings.len(),
+        ..DriftReport::default()
+    };
+
+    let mut offsets: Vec<(i64, CSVRecord)> = readings
+        .into_iter()
+        .map(|reading| {
+            let offset = grid_offset(&reading, interval_secs);
+            if offset != 0 {
+                report.drifted += 1;
+                if offset.abs() > report.max_offset_secs.abs() {
+                    report.max_offset_secs = offset;
+                }
+            }
+            (offset, reading)
+        })
+        .collect();
+
+    if config.mode == DriftMode::Preserve {
+        let readings = offsets.into_iter().map(|(_, reading)| reading).collect();
+        return (readings, report);
+    }
+
+    for (offset, reading) in &mut offsets {
+        reading.datetime -= TimeDelta::seconds(*offset);
+    }
+    offsets.sort_by_key(|(offset, reading)| (reading.datetime, offset.abs()));
+    let before = offsets.len();
+    offsets.dedup_by_key(|(_, reading)| reading.datetime);
+    report.collisions = before - offsets.len();
+
+    let readings = offsets.into_iter().map(|(_, reading)| reading).collect();
+    (readings, report)
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+    use test_case::test_case;
+
+    use super::{DriftConfig, DriftMode, DriftReport, analyse};
+    use crate::model::csv::CSVRecord;
+
+    fn reading(hour: u32, minute: u32, amount: i32) -> CSVRecord {
+        CSVRecord {
+            datetime: Utc.with_ymd_and_hms(2025, 1, 1, hour, minute, 0).unwrap(),
+            amount: BigDecimal::from(amount),
+            extra: None,
+        }
+    }
+
+    fn hourly(mode: DriftMode) -> DriftConfig {
+        DriftConfig {
+            interval: TimeDelta::hours(1),
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_preserve_reports_drift_without_moving_readings() {
+        let readings = vec![reading(0, 0, 1), reading(1, 2, 2), reading(1, 43, 3)];
+        let (stored, report) = analyse(readings.clone(), &hourly(DriftMode::Preserve));
+
+        assert_eq!(stored, readings);
+        assert_eq!(
+            report,
+            DriftReport {
+                readings: 3,
+                drifted: 2,
+                max_offset_secs: -17 * 60,
+                collisions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_snap_moves_readings_and_keeps_closest_on_collision() {
+        let readings = vec![reading(0, 2, 1), reading(0, 58, 2), reading(1, 17, 3)];
+        let (stored, report) = analyse(readings, &hourly(DriftMode::Snap));
+
+        // 00:58 snaps to 01:00 and beats 01:17 which lands on the same point
+        assert_eq!(stored, vec![reading(0, 0, 1), reading(1, 0, 2)]);
+        assert_eq!(report.drifted, 3);
+        assert_eq!(report.max_offset_secs, 17 * 60);
+        assert_eq!(report.collisions, 1);
+    }
+
+    #[test_case("snap", Ok(DriftMode::Snap))]
+    #[test_case("Preserve", Ok(DriftMode::Preserve))]
+    #[test_case("shift", Err("unknown clock drift mode shift".to_string()))]
+    fn test_parse_drift_mode(value: &str, expected: Result<DriftMode, String>) {
+        assert_eq!(value.parse::<DriftMode>(), expected);
+    }
+}
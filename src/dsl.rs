@@ -0,0 +1,420 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{
+    bucket,
+    model::api_request::{
+        Aggregation, AmountUnit, RangeEnd, TimeSeriesAggregationRequest, TimeSeriesRange,
+    },
+};
+
+/// Query text rejected by [`parse`], `column` counting characters from 1
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("{message} at column {column}")]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Keyword, identifier, number or date, anything unquoted
+    Word(String),
+    /// Quoted with `'` or `"`, a doubled quote standing for itself
+    Text(String),
+    Symbol(char),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(word) => format!("`{word}`"),
+            Self::Text(text) => format!("'{text}'"),
+            Self::Symbol(symbol) => format!("`{symbol}`"),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '+')
+}
+
+/// Splits `query` into tokens tagged with the column they start at
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+    while let Some((index, c)) = chars.next() {
+        let column = index + 1;
+        match c {
+            c if c.is_whitespace() => {}
+            '(' | ')' | '=' => tokens.push((column, Token::Symbol(c))),
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, next)) if next == c => {
+                            if chars.next_if(|&(_, after)| after == c).is_none() {
+                                break;
+                            }
+                            text.push(c);
+                        }
+                        Some((_, next)) => text.push(next),
+                        None => {
+                            return Err(ParseError {
+                                column,
+                                message: "unterminated string".to_string(),
+                            });
+                        }
+                    }
+                }
+                tokens.push((column, Token::Text(text)));
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, next)) = chars.next_if(|&(_, next)| is_word_char(next)) {
+                    word.push(next);
+                }
+                tokens.push((column, Token::Word(word)));
+            }
+            c => {
+                return Err(ParseError {
+                    column,
+                    message: format!("unexpected `{c}`"),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parsed query, see [`parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DslQuery {
+    pub aggregation_kind: Aggregation,
+    pub from_date: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub to_date: Option<DateTime<Utc>>,
+    pub series_id: Option<i64>,
+    pub series_name: Option<String>,
+    pub unit: AmountUnit,
+}
+
+impl DslQuery {
+    /// The aggregation request the query stands for, validated and answered as any other
+    pub fn into_request(self) -> TimeSeriesAggregationRequest {
+        TimeSeriesAggregationRequest {
+            aggregation_kind: Some(self.aggregation_kind),
+            datetime_filter: Some(TimeSeriesRange {
+                from_date: self.from_date,
+                to_date: self.to_date,
+                to_bound: RangeEnd::Exclusive,
+                from_settlement: None,
+                to_settlement: None,
+            }),
+            fill_missing: None,
+            include_lineage: false,
+            include_completeness: false,
+            include_power: false,
+            include_settlement: false,
+            include_sources: false,
+            having: None,
+            group_by: Vec::new(),
+            unit: self.unit,
+            timezone: None,
+            series_id: self.series_id,
+            series_name: self.series_name,
+            as_recorded_by: None,
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Column just past the query, reported when it ends early
+    end: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let column = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |(column, _)| *column);
+        ParseError {
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseError> {
+        let token = self.peek().cloned().ok_or_else(|| {
+            self.error(format!("expected {expected}, found the end of the query"))
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn unexpected(&mut self, expected: &str, token: &Token) -> ParseError {
+        self.position -= 1;
+        self.error(format!("expected {expected}, found {}", token.describe()))
+    }
+
+    /// Consumes the keyword when it is next, keywords matching in any case
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.keyword(keyword) {
+            return Ok(());
+        }
+        let expected = format!("`{keyword}`");
+        let token = self.next(&expected)?;
+        Err(self.unexpected(&expected, &token))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        let expected = format!("`{symbol}`");
+        match self.next(&expected)? {
+            Token::Symbol(found) if found == symbol => Ok(()),
+            token => Err(self.unexpected(&expected, &token)),
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> Result<String, ParseError> {
+        match self.next(expected)? {
+            Token::Word(word) => Ok(word),
+            token => Err(self.unexpected(expected, &token)),
+        }
+    }
+
+    /// A quoted string or a bare word
+    fn value(&mut self, expected: &str) -> Result<String, ParseError> {
+        match self.next(expected)? {
+            Token::Word(value) | Token::Text(value) => Ok(value),
+            token => Err(self.unexpected(expected, &token)),
+        }
+    }
+
+    fn period(&mut self) -> Result<Aggregation, ParseError> {
+        const EXPECTED: &str = "hour, day, week, month, quarter or year";
+        let period = self.word(EXPECTED)?;
+        Ok(match period.to_ascii_lowercase().as_str() {
+            "hour" => Aggregation::Hourly,
+            "day" => Aggregation::DayInMonth,
+            "week" => Aggregation::Weekly,
+            "month" => Aggregation::Monthly,
+            "quarter" => Aggregation::Quarterly,
+            "year" => Aggregation::Yearly,
+            _ => return Err(self.unexpected(EXPECTED, &Token::Word(period))),
+        })
+    }
+
+    /// A `YYYY`, `YYYY-MM` or `YYYY-MM-DD` UTC calendar period or an RFC 3339 instant,
+    /// returned with the period's start and the start of the next one
+    fn instant(&mut self) -> Result<(DateTime<Utc>, DateTime<Utc>), ParseError> {
+        const EXPECTED: &str = "a date such as 2024, 2024-01, 2024-01-31 or 2024-01-31T00:00:00Z";
+        let text = self.value(EXPECTED)?;
+        if let Ok(instant) = DateTime::parse_from_rfc3339(&text) {
+            let instant = instant.to_utc();
+            return Ok((instant, instant));
+        }
+        let (date, kind) = match text.split('-').count() {
+            1 => (format!("{text}-01-01"), Aggregation::Yearly),
+            2 => (format!("{text}-01"), Aggregation::Monthly),
+            _ => (text.clone(), Aggregation::DayInMonth),
+        };
+        let start = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|start| start.and_utc())
+            .ok_or_else(|| self.unexpected(EXPECTED, &Token::Word(text)))?;
+        Ok((start, bucket::advance(kind, start)))
+    }
+
+    fn condition(&mut self, query: &mut DslQuery) -> Result<(), ParseError> {
+        let field = self.word("`series` or `series_id`")?;
+        self.expect_symbol('=')?;
+        match field.to_ascii_lowercase().as_str() {
+            "series" => query.series_name = Some(self.value("a series name")?),
+            "series_id" => {
+                let id = self.value("a series id")?;
+                query.series_id = Some(
+                    id.parse()
+                        .map_err(|_| self.unexpected("a series id", &Token::Word(id.clone())))?,
+                );
+            }
+            // A meter's readings are those of its channels, each a series of its own
+            "meter" => {
+                self.position -= 2;
+                return Err(self.error(
+                    "meter filters are not supported, filter on one of its series, e.g. series='MTR-001/generation'",
+                ));
+            }
+            _ => {
+                self.position -= 2;
+                return Err(
+                    self.error(format!("expected `series` or `series_id`, found `{field}`"))
+                );
+            }
+        }
+        if query.series_id.is_some() && query.series_name.is_some() {
+            return Err(self.error("filter on either series or series_id"));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a query of the form
+///
+/// ```text
+/// sum(amount) by <hour|day|week|month|quarter|year>
+///     [where series='<name>' | series_id=<id> [and ...]]
+///     [from <date>] [to <date>] [in <kWh|MWh|GWh|J>]
+/// ```
+///
+/// Keywords are case insensitive and clauses may come in any order. Dates are UTC
+/// calendar periods, `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, or RFC 3339 instants. `from`
+/// starts at the beginning of its period and `to` runs to the end of its period, so
+/// `from 2024-01 to 2024-12` covers the whole of 2024, while an instant is an exclusive end.
+pub fn parse(query: &str) -> Result<DslQuery, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        end: query.chars().count() + 1,
+    };
+    parser.expect_keyword("sum")?;
+    parser.expect_symbol('(')?;
+    parser.expect_keyword("amount")?;
+    parser.expect_symbol(')')?;
+    parser.expect_keyword("by")?;
+    let mut query = DslQuery {
+        aggregation_kind: parser.period()?,
+        from_date: None,
+        to_date: None,
+        series_id: None,
+        series_name: None,
+        unit: AmountUnit::KWh,
+    };
+
+    let mut seen: Vec<String> = Vec::new();
+    while parser.peek().is_some() {
+        let clause = parser
+            .word("`where`, `from`, `to` or `in`")?
+            .to_ascii_lowercase();
+        if seen.contains(&clause) {
+            parser.position -= 1;
+            return Err(parser.error(format!("`{clause}` is given more than once")));
+        }
+        match clause.as_str() {
+            "where" => {
+                parser.condition(&mut query)?;
+                while parser.keyword("and") {
+                    parser.condition(&mut query)?;
+                }
+            }
+            "from" => query.from_date = Some(parser.instant()?.0),
+            "to" => query.to_date = Some(parser.instant()?.1),
+            "in" => {
+                let unit = parser.word("kWh, MWh, GWh or J")?;
+                query.unit = match unit.as_str() {
+                    "kWh" => AmountUnit::KWh,
+                    "MWh" => AmountUnit::MWh,
+                    "GWh" => AmountUnit::GWh,
+                    "J" => AmountUnit::J,
+                    _ => return Err(parser.unexpected("kWh, MWh, GWh or J", &Token::Word(unit))),
+                };
+            }
+            _ => {
+                return Err(parser.unexpected(
+                    "`where`, `from`, `to` or `in`",
+                    &Token::Word(clause.clone()),
+                ));
+            }
+        }
+        seen.push(clause);
+    }
+    Ok(query)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone as _, Utc};
+
+    use super::{DslQuery, ParseError, parse};
+    use crate::model::api_request::{Aggregation, AmountUnit};
+
+    #[test]
+    fn test_parse_full_query() {
+        let query =
+            parse("SUM(amount) by month where series='North Farm' from 2024-01 to 2024-12 in MWh")
+                .unwrap();
+        assert_eq!(
+            query,
+            DslQuery {
+                aggregation_kind: Aggregation::Monthly,
+                from_date: Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+                to_date: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+                series_id: None,
+                series_name: Some("North Farm".to_string()),
+                unit: AmountUnit::MWh,
+            }
+        );
+
+        let query =
+            parse("sum(amount) by hour to 2024-03-31T12:00:00+01:00 where series_id = 7 from 2024")
+                .unwrap();
+        assert_eq!(query.aggregation_kind, Aggregation::Hourly);
+        assert_eq!(query.series_id, Some(7));
+        assert_eq!(
+            query.from_date,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            query.to_date,
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 11, 0, 0).unwrap())
+        );
+
+        let query = parse("sum(amount) by day to 2024-02-29 where series='It''s'").unwrap();
+        assert_eq!(
+            query.to_date,
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(query.series_name.as_deref(), Some("It's"));
+        assert_eq!(query.from_date, None);
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_offending_token() {
+        let error = |query: &str| parse(query).unwrap_err();
+        assert_eq!(
+            error("sum(amount) month"),
+            ParseError {
+                column: 13,
+                message: "expected `by`, found `month`".to_string()
+            }
+        );
+        assert_eq!(error("sum(amount) by fortnight").column, 16);
+        assert_eq!(
+            error("sum(amount) by day from").message,
+            "expected a date such as 2024, 2024-01, 2024-01-31 or 2024-01-31T00:00:00Z, found the end of the query"
+        );
+        assert_eq!(error("sum(amount) by day from 2024-13").column, 25);
+        assert_eq!(error("sum(amount) by day where meter='X'").column, 26);
+        assert_eq!(error("sum(amount) by day from 2024 from 2025").column, 30);
+        assert_eq!(error("sum(amount) by day where series='X").column, 33);
+        assert_eq!(error("sum(amount); drop table").column, 12);
+        assert!(parse("sum(amount) by day where series='X' and series_id=1").is_err());
+        assert!(parse("avg(amount) by day").is_err());
+        assert!(parse("sum(amount) by day in kwh").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use arrow_array::{Array as _, ArrayRef, Decimal128Array, TimestampMicrosecondArray};
+use arrow_schema::{DataType, TimeUnit};
+use bigdecimal::{BigDecimal, num_bigint::BigInt};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use datafusion::{
+    common::ScalarValue,
+    error::DataFusionError,
+    logical_expr::{ColumnarValue, Volatility, create_udf},
+    prelude::{SessionConfig, SessionContext},
+};
+
+use crate::{
+    bucket,
+    model::{
+        api_request::{Aggregation, TotalFilter},
+        api_response::AggregationQueryRecord,
+    },
+};
+
+/// Table [`aggregate`] reads, with a UTC microsecond `datetime` and a decimal `amount`
+/// column, and the `series_id` and `priority` of each reading's ingestion when ranked
+pub const READINGS: &str = "readings";
+
+/// Session the readings are registered with, parsing literals such as `having` bounds as
+/// decimals so they compare against totals exactly, as Postgres compares `NUMERIC`
+pub fn session() -> SessionContext {
+    let mut config = SessionConfig::new();
+    config.options_mut().sql_parser.parse_float_as_decimal = true;
+    SessionContext::new_with_config(config)
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+/// `bucket_start(datetime)`, the start of the bucket of `kind` holding each reading on the
+/// calendar of `zone`, resolved as Postgres resolves transitions, see
+/// [`bucket::truncate_in`]. DataFusion's own `date_trunc` takes an ambiguous local start
+/// at its first occurrence.
+fn register_bucket_start(ctx: &SessionContext, kind: Aggregation, zone: Tz) {
+    let bucket_start = move |args: &[ColumnarValue]| {
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let datetimes = arrays[0]
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .ok_or_else(|| DataFusionError::Internal("datetime is not a timestamp".into()))?;
+        let starts: TimestampMicrosecondArray = datetimes
+            .iter()
+            .map(|micros| {
+                micros
+                    .and_then(DateTime::from_timestamp_micros)
+                    .map(|datetime| bucket::truncate_in(kind, datetime, zone).timestamp_micros())
+            })
+            .collect();
+        Ok(ColumnarValue::Array(
+            Arc::new(starts.with_timezone("UTC")) as ArrayRef
+        ))
+    };
+    ctx.register_udf(create_udf(
+        "bucket_start",
+        vec![timestamp_type()],
+        timestamp_type(),
+        Volatility::Immutable,
+        Arc::new(bucket_start),
+    ));
+}
+
+/// Totals of the [`READINGS`] of `ctx` per bucket within the half-open range, passing
+/// `having`, ordered by bucket like
+/// [`aggregate_ts_query_having`](crate::db::query::aggregate_ts_query_having). When the
+/// table is ranked a timestamp's readings are taken from the highest priority ingestions
+/// of each series holding it, as the database supersedes the rest.
+pub async fn aggregate(
+    ctx: &SessionContext,
+    aggregation_kind: Aggregation,
+    from_date: Option<DateTime<Utc>>,
+    to_date: Option<DateTime<Utc>>,
+    having: &TotalFilter,
+    zone: Tz,
+) -> Result<Vec<AggregationQueryRecord>, DataFusionError> {
+    register_bucket_start(ctx, aggregation_kind, zone);
+    let schema = ctx.table_provider(READINGS).await?.schema();
+    let ranked = schema.column_with_name("series_id").is_some()
+        && schema.column_with_name("priority").is_some();
+
+    let mut params = Vec::new();
+    let mut range = vec!["TRUE".to_string()];
+    for (bound, op) in [(from_date, ">="), (to_date, "<")] {
+        if let Some(bound) = bound {
+            params.push(ScalarValue::TimestampMicrosecond(
+                Some(bound.timestamp_micros()),
+                Some("UTC".into()),
+            ));
+            range.push(format!("datetime {op} ${}", params.len()));
+        }
+    }
+    let range = range.join(" AND ");
+    let readings = if ranked {
+        format!(
+            "SELECT datetime, amount FROM ( \
+                 SELECT datetime, amount, COALESCE(priority, 0) AS priority, \
+                        MAX(COALESCE(priority, 0)) OVER (PARTITION BY series_id, datetime) AS top \
+                 FROM {READINGS} \
+                 WHERE {range} \
+             ) AS ranked WHERE priority = top"
+        )
+    } else {
+        format!("SELECT datetime, amount FROM {READINGS} WHERE {range}")
+    };
+    let bounds: Vec<_> = [
+        (having.gt, ">"),
+        (having.ge, ">="),
+        (having.lt, "<"),
+        (having.le, "<="),
+    ]
+    .into_iter()
+    .filter_map(|(bound, op)| bound.map(|bound| format!("SUM(amount) {op} {bound}")))
+    .collect();
+    let having_clause = if bounds.is_empty() {
+        String::new()
+    } else {
+        format!("HAVING {}", bounds.join(" AND "))
+    };
+
+    let batches = ctx
+        .sql(&format!(
+            "SELECT bucket_start(datetime) AS datetime, SUM(amount) AS total_amount \
+             FROM ({readings}) AS r \
+             GROUP BY 1 \
+             {having_clause} \
+             ORDER BY 1"
+        ))
+        .await?
+        .with_param_values(params)?
+        .collect()
+        .await?;
+
+    let mut records = Vec::new();
+    for batch in batches {
+        let starts = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .ok_or_else(|| DataFusionError::Internal("bucket is not a timestamp".into()))?;
+        let totals = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .ok_or_else(|| DataFusionError::Internal("total is not a decimal".into()))?;
+        let scale = i64::from(totals.scale());
+        for row in 0..batch.num_rows() {
+            let datetime = DateTime::from_timestamp_micros(starts.value(row))
+                .ok_or_else(|| DataFusionError::Internal(format!("bucket {row} out of range")))?;
+            records.push(AggregationQueryRecord {
+                datetime,
+                total_amount: totals
+                    .is_valid(row)
+                    .then(|| BigDecimal::new(BigInt::from(totals.value(row)), scale)),
+            });
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use deadpool_diesel::{InteractError, PoolError};
+use diesel::result::DatabaseErrorKind;
+use serde::Serialize;
+use tower_http::request_id::RequestId;
+use tracing::{error, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    db::{PgError, is_statement_timeout},
+    unit_check::UnitMismatch,
+};
+
+tokio::task_local! {
+    /// Id of the request being handled, read when rendering an [`ApiError`]
+    static REQUEST_ID: String;
+}
+
+/// JSON body returned for every failed request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine-readable error code, e.g. `not_found`
+    pub code: &'static str,
+    pub message: String,
+    /// Matches the `x-request-id` response header
+    pub request_id: Option<String>,
+}
+
+/// Failure of a route handler, rendered as an [`ErrorBody`] with a matching status code
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0}")]
+    Unauthorized(&'static str),
+
+    #[error("{0}")]
+    Forbidden(&'static str),
+
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    #[error("{0}")]
+    Gone(&'static str),
+
+    #[error("{0}")]
+    Conflict(&'static str),
+
+    #[error("unable to get a database connection {0}")]
+    Pool(PoolError),
+
+    #[error("database interaction failed {0}")]
+    Interaction(InteractError),
+
+    #[error("database error {0}")]
+    Database(diesel::result::Error),
+
+    #[error("{0}")]
+    Pg(PgError),
+
+    #[error("unable to encode CSV {0}")]
+    Csv(csv::Error),
+
+    #[error("unable to encode Parquet {0}")]
+    Parquet(parquet::errors::ParquetError),
+
+    #[error("object store request failed {0}")]
+    ObjectStore(object_store::Error),
+
+    #[error("unable to buffer response body {0}")]
+    Body(axum::Error),
+
+    #[error("Instance is read-only, writes are rejected until it is switched back")]
+    ReadOnly,
+
+    #[error("ingestion {0} is under legal hold")]
+    LegalHold(i64),
+
+    #[error(
+        "readings are about {}x those the series held before, check the unit of the amounts",
+        .0.factor
+    )]
+    UnitMismatch(UnitMismatch),
+
+    #[error(
+        "query would return about {estimated} buckets, over the {max} allowed, narrow the range or pick a coarser aggregation"
+    )]
+    TooManyBuckets { estimated: i64, max: i64 },
+
+    #[error("request exceeded its {0:?} timeout")]
+    Timeout(Duration),
+}
+
+impl ApiError {
+    pub(crate) fn status_and_code(&self) -> (StatusCode, &'static str) {
+        match self {
+            Self::BadRequest(_) => (StatusCode::BAD_REQUEST, "bad_request"),
+            Self::Unauthorized(_) => (StatusCode::UNAUTHORIZED, "unauthorized"),
+            Self::Forbidden(_) => (StatusCode::FORBIDDEN, "forbidden"),
+            Self::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            Self::Gone(_) => (StatusCode::GONE, "gone"),
+            Self::Conflict(_) => (StatusCode::CONFLICT, "conflict"),
+            Self::ReadOnly => (StatusCode::SERVICE_UNAVAILABLE, "read_only"),
+            Self::LegalHold(_) => (StatusCode::LOCKED, "legal_hold"),
+            Self::UnitMismatch(_) => (StatusCode::UNPROCESSABLE_ENTITY, "unit_mismatch"),
+            Self::TooManyBuckets { .. } => (StatusCode::PAYLOAD_TOO_LARGE, "too_many_buckets"),
+            Self::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "deadline_exceeded"),
+            Self::Pool(_) | Self::Pg(PgError::ConnectionError(_)) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "database_unavailable")
+            }
+            Self::Database(e) | Self::Pg(PgError::DieselError(e)) => database_status(e),
+            Self::Interaction(_)
+            | Self::Pg(_)
+            | Self::Csv(_)
+            | Self::Parquet(_)
+            | Self::ObjectStore(_)
+            | Self::Body(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        }
+    }
+}
+
+impl ApiError {
+    /// Whether the request failed for want of a database connection, rather than on
+    /// anything the database reported
+    pub(crate) fn is_database_unavailable(&self) -> bool {
+        self.status_and_code().1 == "database_unavailable"
+    }
+}
+
+fn database_status(error: &diesel::result::Error) -> (StatusCode, &'static str) {
+    match error {
+        e if is_statement_timeout(e) => (StatusCode::GATEWAY_TIMEOUT, "deadline_exceeded"),
+        diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ClosedConnection | DatabaseErrorKind::UnableToSendCommand,
+            _,
+        ) => (StatusCode::SERVICE_UNAVAILABLE, "database_unavailable"),
+        diesel::result::Error::NotFound => (StatusCode::NOT_FOUND, "not_found"),
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            (StatusCode::CONFLICT, "conflict")
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "database_error"),
+    }
+}
+
+impl ApiError {
+    /// Logs the failure and renders the body sent to the caller
+    pub(crate) fn into_body(self) -> (StatusCode, ErrorBody) {
+        let (status, code) = self.status_and_code();
+        let request_id = REQUEST_ID.try_with(Clone::clone).ok();
+
+        // Server side failures are logged in full but never leak their details, read-only
+        // mode is an operator decision callers need to see
+        let message = if status.is_server_error() && !matches!(self, Self::ReadOnly) {
+            error!(request_id, code, "{self}");
+            match status {
+                StatusCode::GATEWAY_TIMEOUT => "Deadline Exceeded",
+                StatusCode::SERVICE_UNAVAILABLE => "Service Unavailable",
+                _ => "Internal Error",
+            }
+            .to_string()
+        } else {
+            warn!(request_id, code, "{self}");
+            self.to_string()
+        };
+
+        let body = ErrorBody {
+            code,
+            message,
+            request_id,
+        };
+        (status, body)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, body) = self.into_body();
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Makes the id assigned by `SetRequestIdLayer` available to [`ApiError`] responses
+pub async fn scope_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(ToString::to_string);
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use axum::{http::StatusCode, response::IntoResponse as _};
+    use diesel::result::DatabaseErrorKind;
+    use test_case::test_case;
+
+    use super::{ApiError, REQUEST_ID};
+    use crate::unit_check::UnitMismatch;
+
+    #[test_case(ApiError::NotFound("meter"), StatusCode::NOT_FOUND)]
+    #[test_case(ApiError::BadRequest("bad".to_string()), StatusCode::BAD_REQUEST)]
+    #[test_case(
+        ApiError::Database(diesel::result::Error::NotFound),
+        StatusCode::NOT_FOUND
+    )]
+    #[test_case(
+        ApiError::Database(diesel::result::Error::RollbackTransaction),
+        StatusCode::INTERNAL_SERVER_ERROR
+    )]
+    #[test_case(ApiError::ReadOnly, StatusCode::SERVICE_UNAVAILABLE)]
+    #[test_case(
+        ApiError::Database(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::UnableToSendCommand,
+            Box::new("no connection to the server".to_string())
+        )),
+        StatusCode::SERVICE_UNAVAILABLE
+    )]
+    #[test_case(ApiError::Conflict("duplicate"), StatusCode::CONFLICT)]
+    #[test_case(ApiError::LegalHold(7), StatusCode::LOCKED)]
+    #[test_case(
+        ApiError::UnitMismatch(UnitMismatch { ratio: 998.0, factor: 1000.0 }),
+        StatusCode::UNPROCESSABLE_ENTITY
+    )]
+    #[test_case(
+        ApiError::TooManyBuckets { estimated: 8761, max: 5000 },
+        StatusCode::PAYLOAD_TOO_LARGE
+    )]
+    #[test_case(ApiError::Timeout(Duration::from_secs(2)), StatusCode::GATEWAY_TIMEOUT)]
+    fn test_api_error_status(error: ApiError, expected: StatusCode) {
+        assert_eq!(error.into_response().status(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_error_body_carries_request_id() {
+        let response = REQUEST_ID
+            .scope("abc-123".to_string(), async {
+                ApiError::Database(diesel::result::Error::RollbackTransaction).into_response()
+            })
+            .await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["code"], "database_error");
+        assert_eq!(body["message"], "Internal Error");
+        assert_eq!(body["request_id"], "abc-123");
+    }
+}
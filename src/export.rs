@@ -0,0 +1,352 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use deadpool_diesel::{InteractError, PoolError, postgres::Pool};
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+use tracing::{error, info};
+
+use crate::{
+    db::{
+        TimedPool,
+        export_jobs::{mark_export_complete, mark_export_failed, mark_export_running},
+        query::aggregate_ts_query_having,
+    },
+    decimal::{self, DecimalFormat},
+    encryption::ExportRecipient,
+    history::PendingQuery,
+    model::{
+        api_request::{Aggregation, TotalFilter},
+        api_response::AggregationQueryRecord,
+    },
+    rounding,
+    watermark::Watermark,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_EXPORT_DIR: &str = "exports";
+const DEFAULT_URL_TTL_SECS: u64 = 900;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("missing EXPORT_SIGNING_KEY")]
+    SigningKey,
+
+    #[error("invalid EXPORT_URL_TTL_SECS")]
+    UrlTtl,
+
+    #[error("invalid EXPORT_WATERMARK")]
+    Watermark,
+
+    #[error("unable to get connection from pool")]
+    ConnectionError(PoolError),
+
+    #[error("unable to interact with connection {0}")]
+    InteractionError(InteractError),
+
+    #[error("diesel error {0}")]
+    DieselError(diesel::result::Error),
+
+    #[error("unable to write export file {0}")]
+    IoError(std::io::Error),
+
+    #[error("unable to encode export file {0}")]
+    CsvError(csv::Error),
+
+    #[error("unable to encrypt export file {0}")]
+    EncryptionError(std::io::Error),
+}
+
+/// Aggregation written by an export job
+#[derive(Clone, Debug)]
+pub struct ExportQuery {
+    pub aggregation_kind: Aggregation,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    /// Only readings recorded by then, each at the revision current then
+    pub as_recorded_by: Option<DateTime<Utc>>,
+    /// Only buckets whose total passes these bounds are written
+    pub having: TotalFilter,
+    /// Zone whose local calendar buckets follow, bucket starts are still written in UTC
+    pub timezone: Tz,
+}
+
+/// Settings for the asynchronous export subsystem
+#[derive(Clone, Debug)]
+pub struct ExportConfig {
+    pub directory: PathBuf,
+    pub signing_key: Vec<u8>,
+    pub url_ttl: Duration,
+    /// Mark exported amounts per API key, see [`Watermark`]
+    pub watermark: bool,
+}
+
+impl ExportConfig {
+    pub fn from_env() -> Result<Self, ExportError> {
+        let directory = env::var("EXPORT_DIR").unwrap_or_else(|_| DEFAULT_EXPORT_DIR.to_string());
+        let signing_key = env::var("EXPORT_SIGNING_KEY").map_err(|_| ExportError::SigningKey)?;
+        let url_ttl = match env::var("EXPORT_URL_TTL_SECS") {
+            Ok(ttl) => ttl.parse::<u64>().map_err(|_| ExportError::UrlTtl)?,
+            Err(_) => DEFAULT_URL_TTL_SECS,
+        };
+        let watermark = match env::var("EXPORT_WATERMARK") {
+            Ok(watermark) => watermark
+                .parse::<bool>()
+                .map_err(|_| ExportError::Watermark)?,
+            Err(_) => false,
+        };
+
+        Ok(Self {
+            directory: PathBuf::from(directory),
+            signing_key: signing_key.into_bytes(),
+            url_ttl: Duration::from_secs(url_ttl),
+            watermark,
+        })
+    }
+
+    /// Watermark of the exports of `api_key_id`, keyed by the signing key, when enabled
+    pub fn watermark_for(&self, api_key_id: i64) -> Option<Watermark> {
+        self.watermark
+            .then(|| Watermark::new(&self.signing_key, api_key_id))
+    }
+
+    /// Builds a download URL for a completed job that is valid until `expires_at`
+    pub fn signed_download_url(&self, job_id: i64, expires_at: DateTime<Utc>) -> String {
+        let expires = expires_at.timestamp();
+        let signature = sign(&self.signing_key, job_id, expires);
+        format!("/timeseries/v1/exports/{job_id}/download?expires={expires}&signature={signature}")
+    }
+}
+
+fn mac(key: &[u8], job_id: i64, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(format!("{job_id}:{expires}").as_bytes());
+    mac
+}
+
+pub fn sign(key: &[u8], job_id: i64, expires: i64) -> String {
+    hex::encode(mac(key, job_id, expires).finalize().into_bytes())
+}
+
+/// Verifies the signature is authentic, comparing in constant time
+pub fn verify(key: &[u8], job_id: i64, expires: i64, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    mac(key, job_id, expires).verify_slice(&signature).is_ok()
+}
+
+/// Writes `records` as `datetime,total_amount` rows with amounts rounded by the policy,
+/// empty buckets are written with an empty amount
+pub fn write_records<W: io::Write>(
+    writer: &mut csv::Writer<W>,
+    records: &[AggregationQueryRecord],
+) -> csv::Result<()> {
+    writer.write_record(CSV_HEADER)?;
+    for record in records {
+        write_record(writer, record)?;
+    }
+    Ok(())
+}
+
+/// Header row written by [`write_records`]
+pub const CSV_HEADER: [&str; 2] = ["datetime", "total_amount"];
+
+/// One row of [`write_records`], for writers receiving records one at a time
+pub fn write_record<W: io::Write>(
+    writer: &mut csv::Writer<W>,
+    record: &AggregationQueryRecord,
+) -> csv::Result<()> {
+    let total_amount = record
+        .total_amount
+        .as_ref()
+        .map(|amount| {
+            DecimalFormat::current(DecimalFormat::Exact).render(&rounding::current().apply(amount))
+        })
+        .unwrap_or_default();
+    writer.write_record([record.datetime.to_rfc3339(), total_amount])
+}
+
+/// [`write_records`], with amounts marked for the exporting key when a `watermark` is given
+fn write_export_records<W: io::Write>(
+    writer: &mut csv::Writer<W>,
+    records: &[AggregationQueryRecord],
+    watermark: Option<&Watermark>,
+) -> csv::Result<()> {
+    let Some(watermark) = watermark else {
+        return write_records(writer, records);
+    };
+    let format = DecimalFormat::current(DecimalFormat::Exact);
+    writer.write_record(CSV_HEADER)?;
+    for record in records {
+        let total_amount = record
+            .total_amount
+            .as_ref()
+            .map(|amount| format.render(&watermark.mark(record.datetime, amount)))
+            .unwrap_or_default();
+        writer.write_record([record.datetime.to_rfc3339(), total_amount])?;
+    }
+    Ok(())
+}
+
+fn write_csv(
+    path: &Path,
+    records: &[AggregationQueryRecord],
+    recipient: Option<&ExportRecipient>,
+    watermark: Option<&Watermark>,
+) -> Result<(), ExportError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(ExportError::IoError)?;
+    }
+
+    let Some(recipient) = recipient else {
+        let mut writer = csv::Writer::from_path(path).map_err(ExportError::CsvError)?;
+        write_export_records(&mut writer, records, watermark).map_err(ExportError::CsvError)?;
+        return writer.flush().map_err(ExportError::IoError);
+    };
+    // Encrypted exports are assembled in memory so the plain text never reaches the disk
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    write_export_records(&mut writer, records, watermark).map_err(ExportError::CsvError)?;
+    let plaintext = writer
+        .into_inner()
+        .map_err(|e| ExportError::IoError(e.into_error()))?;
+    recipient
+        .encrypt(&plaintext, path)
+        .map_err(ExportError::EncryptionError)
+}
+
+async fn execute_export(
+    pg_pool: &Pool,
+    config: &ExportConfig,
+    job_id: i64,
+    query: ExportQuery,
+    recipient: Option<ExportRecipient>,
+    watermark: Option<Watermark>,
+) -> Result<usize, ExportError> {
+    let conn = pg_pool.get().await.map_err(ExportError::ConnectionError)?;
+    let records = conn
+        .interact(move |conn| {
+            mark_export_running(job_id, conn)?;
+            aggregate_ts_query_having(
+                query.aggregation_kind,
+                query.from_date,
+                query.to_date,
+                query.as_recorded_by,
+                None,
+                &query.having,
+                query.timezone,
+                conn,
+            )
+        })
+        .await
+        .map_err(ExportError::InteractionError)?
+        .map_err(ExportError::DieselError)?;
+
+    let mut file_name = format!("export-{job_id}.csv");
+    if let Some(recipient) = &recipient {
+        file_name = format!("{file_name}.{}", recipient.extension());
+    }
+    let path = config.directory.join(file_name);
+    let rows = records.len();
+    let row_count = i64::try_from(rows).unwrap_or(i64::MAX);
+    let file_path = path.to_string_lossy().into_owned();
+    let decimals = decimal::requested();
+    tokio::task::spawn_blocking(move || {
+        decimal::within(decimals, || {
+            write_csv(&path, &records, recipient.as_ref(), watermark.as_ref())
+        })
+    })
+    .await
+    .map_err(|e| ExportError::IoError(std::io::Error::other(e)))??;
+
+    conn.interact(move |conn| mark_export_complete(job_id, &file_path, row_count, conn))
+        .await
+        .map_err(ExportError::InteractionError)?
+        .map_err(ExportError::DieselError)?;
+
+    info!(job_id, row_count, "Export job complete");
+    Ok(rows)
+}
+
+/// Worker entrypoint spawned for each export job, recording failures against the job and
+/// the outcome in `history`. With a `recipient` the file is encrypted to it and named with
+/// its extension, and with a `watermark` its amounts are marked for the exporting key.
+pub async fn run_export_job(
+    pg_pool: TimedPool,
+    config: ExportConfig,
+    job_id: i64,
+    query: ExportQuery,
+    recipient: Option<ExportRecipient>,
+    watermark: Option<Watermark>,
+    history: PendingQuery,
+) {
+    let Err(e) = execute_export(&pg_pool, &config, job_id, query, recipient, watermark)
+        .await
+        .map(|rows| history.succeeded(Some(rows)))
+    else {
+        return;
+    };
+
+    error!(job_id, "Export job failed: {e}");
+    let message = e.to_string();
+    let Ok(conn) = pg_pool.get().await else {
+        error!(job_id, "Unable to record export job failure");
+        return;
+    };
+    if let Err(e) = conn
+        .interact(move |conn| mark_export_failed(job_id, &message, conn))
+        .await
+    {
+        error!(job_id, "Unable to record export job failure: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone as _, Utc};
+
+    use super::{sign, verify, write_records};
+    use crate::model::api_response::AggregationQueryRecord;
+
+    #[test]
+    fn test_write_records() {
+        let datetime = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let records = [
+            AggregationQueryRecord {
+                datetime,
+                total_amount: Some(BigDecimal::from(9000)),
+            },
+            AggregationQueryRecord {
+                datetime,
+                total_amount: None,
+            },
+        ];
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        write_records(&mut writer, &records).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "datetime,total_amount\n2025-01-01T00:00:00+00:00,9000\n2025-01-01T00:00:00+00:00,\n"
+        );
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let key = b"secret";
+        let signature = sign(key, 42, 1_700_000_000);
+
+        assert!(verify(key, 42, 1_700_000_000, &signature));
+        assert!(!verify(key, 43, 1_700_000_000, &signature));
+        assert!(!verify(key, 42, 1_700_000_001, &signature));
+        assert!(!verify(b"other", 42, 1_700_000_000, &signature));
+        assert!(!verify(key, 42, 1_700_000_000, "not-hex"));
+    }
+}
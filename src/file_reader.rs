@@ -1,20 +1,514 @@
-use std::io;
+use std::{
+    io::{self, BufRead as _},
+    path::PathBuf,
+    str::FromStr as _,
+};
 
-use crate::model::csv::CSVRecord;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use object_store::{
+    ObjectStore, aws::AmazonS3Builder, azure::MicrosoftAzureBuilder,
+    gcp::GoogleCloudStorageBuilder, http::HttpBuilder, path::Path as ObjectPath,
+};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+use tokio_stream::StreamExt as _;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+use url::{Position, Url};
 
-pub fn csv_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = Result<CSVRecord, csv::Error>> {
-    let reader = csv::ReaderBuilder::new()
+use crate::model::{
+    api_request::MeterOnboarding,
+    check_amount_bounds,
+    csv::{CSVRecord, CsvSchema, MeterCSVRecord},
+};
+
+/// Failure to decode one reading of a readings file
+#[derive(thiserror::Error, Debug)]
+pub enum RowError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    /// The file could not be read any further
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The JSON document is not an array, so no reading can be read
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The header lacks a column of the schema, so no row can be read
+    #[error("{0}")]
+    Header(String),
+
+    #[error("line: {line}, {message}")]
+    Field { line: u64, message: String },
+
+    #[error("readings[{index}]: {message}")]
+    Element { index: usize, message: String },
+}
+
+impl RowError {
+    /// Line of the file the error was found on, when known
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            Self::Csv(e) => e.position().map(csv::Position::line),
+            Self::Field { line, .. } => Some(*line),
+            Self::Io(_) | Self::Json(_) | Self::Header(_) | Self::Element { .. } => None,
+        }
+    }
+}
+
+/// Reading of a JSON or NDJSON file, the amount in kWh as a number or a string
+#[derive(Deserialize)]
+struct JsonReading {
+    datetime: DateTime<Utc>,
+    amount: JsonAmount,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonAmount {
+    Number(serde_json::Number),
+    Text(String),
+}
+
+impl TryFrom<JsonReading> for CSVRecord {
+    type Error = String;
+
+    fn try_from(reading: JsonReading) -> Result<Self, Self::Error> {
+        let amount = match reading.amount {
+            JsonAmount::Number(number) => number.to_string(),
+            JsonAmount::Text(text) => text,
+        };
+        let amount =
+            BigDecimal::from_str(amount.trim()).map_err(|e| format!("amount {amount:?}: {e}"))?;
+        check_amount_bounds(&amount)?;
+        Ok(Self {
+            datetime: reading.datetime,
+            amount,
+            extra: None,
+        })
+    }
+}
+
+fn json_reading(value: Result<JsonReading, serde_json::Error>) -> Result<CSVRecord, String> {
+    value
+        .map_err(|e| e.to_string())
+        .and_then(CSVRecord::try_from)
+}
+
+/// Decodes readings laid out as `schema` describes, locating its columns by header name
+pub fn csv_stream<'a, R: io::Read + 'a>(
+    buffer: R,
+    schema: &'a CsvSchema,
+) -> impl Iterator<Item = Result<CSVRecord, RowError>> + 'a {
+    let mut reader = csv::ReaderBuilder::new()
         .has_headers(true)
         .trim(csv::Trim::All)
         .from_reader(buffer);
 
-    reader.into_deserialize::<CSVRecord>()
+    let columns = match reader.headers() {
+        Ok(headers) => schema
+            .columns(headers)
+            .map(|columns| (columns, schema.extra_columns(headers)))
+            .map_err(RowError::Header),
+        Err(e) => Err(RowError::Csv(e)),
+    };
+    let (rows, header_error) = match columns {
+        Ok(columns) => (Some((reader.into_records(), columns)), None),
+        Err(e) => (None, Some(e)),
+    };
+    header_error
+        .into_iter()
+        .map(Err)
+        .chain(rows.into_iter().flat_map(
+            move |(records, ((datetime_column, amount_column), extra_columns))| {
+                records.map(move |row| {
+                    let row = row?;
+                    let field = |message| RowError::Field {
+                        line: row.position().map_or(0, csv::Position::line),
+                        message,
+                    };
+                    let datetime = schema
+                        .parse_datetime(row.get(datetime_column).unwrap_or_default())
+                        .map_err(field)?;
+                    let amount = schema
+                        .parse_amount(row.get(amount_column).unwrap_or_default())
+                        .map_err(field)?;
+                    Ok(CSVRecord {
+                        datetime,
+                        amount,
+                        extra: CsvSchema::extra(&extra_columns, &row),
+                    })
+                })
+            },
+        ))
+}
+
+/// Decodes a JSON array of readings, the whole document is read before the first reading
+/// is returned
+pub fn json_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = Result<CSVRecord, RowError>> {
+    let (elements, document_error) =
+        match serde_json::from_reader::<_, Vec<serde_json::Value>>(buffer) {
+            Ok(elements) => (elements, None),
+            Err(e) => (Vec::new(), Some(RowError::Json(e))),
+        };
+    document_error
+        .into_iter()
+        .map(Err)
+        .chain(elements.into_iter().enumerate().map(|(index, value)| {
+            json_reading(serde_json::from_value(value))
+                .map_err(|message| RowError::Element { index, message })
+        }))
+}
+
+/// Decodes one JSON reading per line, skipping blank lines and stopping at the first
+/// read failure
+pub fn ndjson_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = Result<CSVRecord, RowError>> {
+    let mut lines = (1..).zip(io::BufReader::new(buffer).lines());
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        while !failed {
+            let (line, text) = lines.next()?;
+            match text {
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(RowError::Io(e)));
+                }
+                Ok(text) if text.trim().is_empty() => {}
+                Ok(text) => {
+                    return Some(
+                        json_reading(serde_json::from_str(&text))
+                            .map_err(|message| RowError::Field { line, message }),
+                    );
+                }
+            }
+        }
+        None
+    })
+}
+
+/// Passes reads through while hashing them, so a file is checksummed as it is decoded
+pub struct ChecksumReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: io::Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Hex SHA-256 of everything `inner` holds, reading on past where decoding stopped
+    pub fn finish(mut self) -> io::Result<String> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl<R: io::Read> io::Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Decodes every reading of a seed file, collecting the error of each rejected row
+/// (unparsable or out of range) instead of failing the whole file
+pub fn readings<R: io::Read>(
+    buffer: R,
+    format: ReadingsFormat,
+    schema: &CsvSchema,
+) -> (Vec<CSVRecord>, Vec<RowError>) {
+    let rows: Box<dyn Iterator<Item = Result<CSVRecord, RowError>> + '_> = match format {
+        ReadingsFormat::Csv => Box::new(csv_stream(buffer, schema)),
+        ReadingsFormat::Json => Box::new(json_stream(buffer)),
+        ReadingsFormat::Ndjson => Box::new(ndjson_stream(buffer)),
+    };
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for row in rows {
+        match row {
+            Ok(record) => accepted.push(record),
+            Err(e) => rejected.push(e),
+        }
+    }
+    (accepted, rejected)
+}
+
+/// Compression of a readings file, recognised from a `.gz` or `.zst` suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    const SUFFIXES: [(&str, Self); 2] = [(".gz", Self::Gzip), (".zst", Self::Zstd)];
+
+    /// Wraps `reader` so the file is decompressed as it is read
+    pub fn decoder<'a, R: io::Read + Send + 'a>(
+        self,
+        reader: R,
+    ) -> io::Result<Box<dyn io::Read + Send + 'a>> {
+        Ok(match self {
+            Self::Uncompressed => Box::new(reader),
+            Self::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+}
+
+/// Encoding of a readings file or upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingsFormat {
+    /// Columns described by [`CsvSchema`]
+    Csv,
+    /// Array of `{datetime, amount}` objects
+    Json,
+    /// One `{datetime, amount}` object per line
+    Ndjson,
+}
+
+impl ReadingsFormat {
+    const EXTENSIONS: [(&str, Self); 4] = [
+        (".csv", Self::Csv),
+        (".json", Self::Json),
+        (".ndjson", Self::Ndjson),
+        (".jsonl", Self::Ndjson),
+    ];
+
+    /// Format named by a `Content-Type`, ignoring parameters such as the charset
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next().unwrap_or_default().trim() {
+            "text/csv" => Some(Self::Csv),
+            "application/json" => Some(Self::Json),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(Self::Ndjson)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Format and compression of the file called `name`, e.g. `readings.ndjson.zst`, `None`
+/// when its extension is not a readings format
+pub fn file_kind(name: &str) -> Option<(ReadingsFormat, Compression)> {
+    let (stem, compression) = Compression::SUFFIXES
+        .into_iter()
+        .find_map(|(suffix, compression)| name.strip_suffix(suffix).map(|stem| (stem, compression)))
+        .unwrap_or((name, Compression::Uncompressed));
+    ReadingsFormat::EXTENSIONS
+        .into_iter()
+        .find(|(extension, _)| stem.ends_with(extension))
+        .map(|(_, format)| (format, compression))
+}
+
+/// Where a file of readings is read from
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileLocation {
+    Local(PathBuf),
+    /// `s3://`, `gs://`, `az://` or `https://` object, streamed rather than downloaded first
+    Remote(Url),
+}
+
+impl FileLocation {
+    pub fn parse(location: &str) -> Self {
+        match Url::parse(location) {
+            Ok(url) if matches!(url.scheme(), "s3" | "gs" | "az" | "https") => Self::Remote(url),
+            _ => Self::Local(PathBuf::from(location)),
+        }
+    }
+
+    /// Name recorded as the ingestion source
+    pub fn source(&self) -> String {
+        match self {
+            Self::Local(path) => path.display().to_string(),
+            Self::Remote(url) => url.to_string(),
+        }
+    }
+
+    /// Format and compression of the file, see [`file_kind`]
+    pub fn kind(&self) -> Option<(ReadingsFormat, Compression)> {
+        match self {
+            Self::Local(path) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(file_kind),
+            Self::Remote(url) => file_kind(url.path()),
+        }
+    }
+
+    pub fn is_readings_file(&self) -> bool {
+        self.kind().is_some()
+    }
+}
+
+/// Opens an object store URL as a blocking reader over its body stream, for use off the
+/// async runtime. Credentials and region come from the usual `AWS_*`, `GOOGLE_*` and
+/// `AZURE_*` environment variables, so URLs with a query string such as pre-signed links are refused
+/// rather than fetched without it.
+pub async fn open_remote(url: &Url) -> Result<impl io::Read + Send + 'static, object_store::Error> {
+    if url.query().is_some() {
+        return Err(object_store::Error::NotSupported {
+            source: "query strings are not sent to the object store".into(),
+        });
+    }
+    let store: Box<dyn ObjectStore> = match url.scheme() {
+        "s3" => Box::new(AmazonS3Builder::from_env().with_url(url.as_str()).build()?),
+        "gs" => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url.as_str())
+                .build()?,
+        ),
+        "az" => Box::new(
+            MicrosoftAzureBuilder::from_env()
+                .with_url(url.as_str())
+                .build()?,
+        ),
+        "https" => Box::new(
+            HttpBuilder::new()
+                .with_url(&url[..Position::BeforePath])
+                .build()?,
+        ),
+        scheme => {
+            return Err(object_store::Error::NotSupported {
+                source: format!("unsupported scheme {scheme}").into(),
+            });
+        }
+    };
+    let path = ObjectPath::from_url_path(url.path())?;
+    let body = store
+        .get(&path)
+        .await?
+        .into_stream()
+        .map(|chunk| chunk.map_err(io::Error::other));
+    Ok(SyncIoBridge::new(StreamReader::new(body)))
+}
+
+/// Decodes a bulk meter onboarding CSV, keeping per-row decoding failures
+pub fn meter_csv_rows<R: io::Read>(buffer: R) -> Vec<Result<MeterOnboarding, String>> {
+    csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(buffer)
+        .into_deserialize::<MeterCSVRecord>()
+        .map(|row| row.map(MeterOnboarding::from).map_err(|e| e.to_string()))
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use bigdecimal::{BigDecimal, FromPrimitive};
     use chrono::DateTime;
+    use test_case::test_case;
+
+    use super::{ChecksumReader, Compression, FileLocation, ReadingsFormat};
+    use crate::model::csv::{CsvSchema, EnergyUnit};
+
+    #[test]
+    fn test_checksum_covers_what_decoding_left_unread() {
+        use std::io::Read as _;
+
+        let mut reader = ChecksumReader::new(&b"abc"[..]);
+        let mut first = [0; 1];
+        reader.read_exact(&mut first).unwrap();
+        // SHA-256 of "abc"
+        assert_eq!(
+            reader.finish().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test_case("resources/Renewable_2025.csv", false, true)]
+    #[test_case("s3://meters/2025/readings.csv", true, true)]
+    #[test_case("gs://meters/readings.csv", true, true)]
+    #[test_case("az://readings/2025/readings.jsonl.gz", true, true)]
+    #[test_case("https://example.com/exports/readings.csv", true, true)]
+    #[test_case("http://example.com/readings.csv", false, true)]
+    #[test_case("s3://meters/readings.parquet", true, false)]
+    #[test_case("exports/readings.csv.gz", false, true)]
+    #[test_case("s3://meters/readings.csv.zst", true, true)]
+    #[test_case("exports/readings.gz", false, false)]
+    #[test_case("s3://meters/readings.ndjson", true, true)]
+    fn test_file_location(location: &str, remote: bool, readings_file: bool) {
+        let parsed = FileLocation::parse(location);
+        assert_eq!(matches!(parsed, FileLocation::Remote(_)), remote);
+        assert_eq!(parsed.is_readings_file(), readings_file);
+        if !remote {
+            assert_eq!(parsed, FileLocation::Local(PathBuf::from(location)));
+        }
+        assert_eq!(parsed.source(), location);
+    }
+
+    #[test_case("readings.csv", Some((ReadingsFormat::Csv, Compression::Uncompressed)))]
+    #[test_case("readings.json", Some((ReadingsFormat::Json, Compression::Uncompressed)))]
+    #[test_case("readings.ndjson.zst", Some((ReadingsFormat::Ndjson, Compression::Zstd)))]
+    #[test_case("readings.jsonl.gz", Some((ReadingsFormat::Ndjson, Compression::Gzip)))]
+    #[test_case("readings.gz", None)]
+    #[test_case("readings.csv.bz2", None)]
+    fn test_file_kind(name: &str, expected: Option<(ReadingsFormat, Compression)>) {
+        assert_eq!(super::file_kind(name), expected);
+    }
+
+    #[test_case("text/csv; charset=utf-8", Some(ReadingsFormat::Csv))]
+    #[test_case("application/json", Some(ReadingsFormat::Json))]
+    #[test_case("application/x-ndjson", Some(ReadingsFormat::Ndjson))]
+    #[test_case("text/plain", None)]
+    fn test_format_from_content_type(content_type: &str, expected: Option<ReadingsFormat>) {
+        assert_eq!(ReadingsFormat::from_content_type(content_type), expected);
+    }
+
+    #[test]
+    fn test_json_and_ndjson_decoding() {
+        let json = r#"[
+            {"datetime": "2025-01-01T00:00:00Z", "amount": 9000.5},
+            {"datetime": "2025-01-01T01:00:00Z", "amount": "12.25"},
+            {"datetime": "yesterday", "amount": 1},
+            {"datetime": "2025-01-01T03:00:00Z", "amount": "lots"}
+        ]"#;
+        let (accepted, rejected) =
+            super::readings(json.as_bytes(), ReadingsFormat::Json, &CsvSchema::default());
+        assert_eq!(accepted.len(), 2);
+        assert_eq!(accepted[0].amount, "9000.5".parse::<BigDecimal>().unwrap());
+        assert!(rejected[0].to_string().starts_with("readings[2]"));
+        assert!(rejected[1].to_string().starts_with("readings[3]"));
+
+        let ndjson = r#"{"datetime": "2025-01-01T00:00:00+01:00", "amount": 1}
+
+{"datetime": "2025-01-01T01:00:00Z", "amount": 12345678901234567890123}
+not json
+"#;
+        let (accepted, rejected) = super::readings(
+            ndjson.as_bytes(),
+            ReadingsFormat::Ndjson,
+            &CsvSchema::default(),
+        );
+        assert_eq!(
+            accepted[0].datetime,
+            DateTime::parse_from_rfc3339("2024-12-31T23:00:00Z").unwrap()
+        );
+        assert_eq!(rejected.len(), 2);
+        let reasons: Vec<String> = rejected.iter().map(ToString::to_string).collect();
+        assert!(reasons[0].starts_with("line: 3") && reasons[0].contains("exceeds NUMERIC"));
+        assert!(reasons[1].starts_with("line: 4"));
+
+        let (accepted, rejected) = super::readings(
+            ndjson.as_bytes(),
+            ReadingsFormat::Json,
+            &CsvSchema::default(),
+        );
+        assert!(accepted.is_empty());
+        assert_eq!(rejected.len(), 1);
+    }
 
     #[test]
     fn test_csv_decoding() {
@@ -27,7 +521,8 @@ mod test {
 1 Jan 2025 05:00,"9,000.000"
 1 Jan 2025 06:00,"9,000.000"
 "#;
-        let mut reader = super::csv_stream(test_data.as_bytes());
+        let schema = CsvSchema::default();
+        let mut reader = super::csv_stream(test_data.as_bytes(), &schema);
         let record = reader.next().unwrap().unwrap();
 
         let expected_dt = DateTime::parse_from_rfc3339("2025-01-01T00:00:00-00:00").unwrap();
@@ -36,4 +531,131 @@ mod test {
 
         assert_eq!(reader.count(), 6);
     }
+
+    #[test]
+    fn test_csv_columns_follow_the_schema() {
+        let test_data = r#"meter,Zeitstempel,Wirkarbeit Wh
+MTR-001,01.01.2025 00:15,"1.500,5"
+MTR-001,01.01.2025 00:30,"2.000"
+"#;
+        let schema = CsvSchema {
+            datetime_column: "Zeitstempel".to_string(),
+            amount_column: "Wirkarbeit Wh".to_string(),
+            datetime_format: "%d.%m.%Y %H:%M".to_string(),
+            decimal_separator: ',',
+            unit: EnergyUnit::Wh,
+            capture_extra: false,
+        };
+        let (accepted, rejected) =
+            super::readings(test_data.as_bytes(), ReadingsFormat::Csv, &schema);
+        assert!(rejected.is_empty(), "{rejected:?}");
+        assert_eq!(
+            accepted[0].datetime,
+            DateTime::parse_from_rfc3339("2025-01-01T00:15:00Z").unwrap()
+        );
+        assert_eq!(accepted[0].amount, "1.5005".parse::<BigDecimal>().unwrap());
+        assert_eq!(accepted[1].amount, BigDecimal::from(2));
+
+        let (accepted, rejected) = super::readings(
+            test_data.as_bytes(),
+            ReadingsFormat::Csv,
+            &CsvSchema::default(),
+        );
+        assert!(accepted.is_empty());
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].to_string(), "missing column \"Time (UTC)\"");
+        assert_eq!(rejected[0].line(), None);
+    }
+
+    #[test]
+    fn test_extra_columns_are_captured_when_enabled() {
+        let test_data = r#"Time (UTC),status,Quantity kWh,flag
+1 Jan 2025 00:00,OK,"9,000.000",E
+1 Jan 2025 01:00,,"9,000.000",
+"#;
+        let (accepted, _) = super::readings(
+            test_data.as_bytes(),
+            ReadingsFormat::Csv,
+            &CsvSchema::default(),
+        );
+        assert!(accepted.iter().all(|reading| reading.extra.is_none()));
+
+        let schema = CsvSchema {
+            capture_extra: true,
+            ..CsvSchema::default()
+        };
+        let (accepted, rejected) =
+            super::readings(test_data.as_bytes(), ReadingsFormat::Csv, &schema);
+        assert!(rejected.is_empty(), "{rejected:?}");
+        assert_eq!(
+            accepted[0].extra,
+            Some(serde_json::json!({"status": "OK", "flag": "E"}))
+        );
+        // Empty cells are left out, a row without any keeps no object
+        assert_eq!(accepted[1].extra, None);
+    }
+
+    #[test]
+    fn test_compressed_csv_is_decoded_transparently() {
+        use std::io::Write as _;
+
+        let test_data = "Time (UTC),Quantity kWh\n1 Jan 2025 00:00,\"9,000.000\"\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(test_data.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(test_data.as_bytes(), 0).unwrap();
+
+        for (compression, bytes) in [
+            (Compression::Uncompressed, test_data.as_bytes().to_vec()),
+            (Compression::Gzip, gzip),
+            (Compression::Zstd, zstd),
+        ] {
+            let reader = compression.decoder(bytes.as_slice()).unwrap();
+            let (accepted, rejected) =
+                super::readings(reader, ReadingsFormat::Csv, &CsvSchema::default());
+            assert_eq!(accepted.len(), 1, "{compression:?}");
+            assert!(rejected.is_empty(), "{compression:?}");
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_amounts_rejected() {
+        let test_data = r#"Time (UTC),Quantity kWh
+1 Jan 2025 00:00,"9,000.000"
+1 Jan 2025 01:00,12345678901234567890123
+1 Jan 2025 02:00,lots
+1 Jan 2025 03:00,1234567890123456789012.0000004
+"#;
+        let (accepted, rejected) = super::readings(
+            test_data.as_bytes(),
+            ReadingsFormat::Csv,
+            &CsvSchema::default(),
+        );
+        assert_eq!(accepted.len(), 2);
+        assert_eq!(rejected.len(), 2);
+        assert_eq!(rejected[0].line(), Some(3));
+        assert!(rejected[0].to_string().contains("exceeds NUMERIC(28, 6)"));
+        assert_eq!(rejected[1].line(), Some(4));
+    }
+
+    #[test]
+    fn test_meter_csv_decoding() {
+        let test_data = "meter_code,site_name,capacity_kw,series
+MTR-001,North Farm,1500.5,generation;export
+MTR-002,South Farm,,
+MTR-003,East Farm,lots,generation
+";
+        let rows = super::meter_csv_rows(test_data.as_bytes());
+        assert_eq!(rows.len(), 3);
+
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.meter_code, "MTR-001");
+        assert_eq!(first.capacity_kw, Some(1500.5));
+        assert_eq!(first.series, ["generation", "export"]);
+
+        let second = rows[1].as_ref().unwrap();
+        assert!(second.capacity_kw.is_none() && second.series.is_empty());
+
+        assert!(rows[2].is_err());
+    }
 }
@@ -0,0 +1,444 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions as _, Object,
+    Schema, SimpleObject,
+    dataloader::{DataLoader, Loader},
+};
+use axum::extract::FromRef as _;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth::ApiKey,
+    db::{
+        TimedPool,
+        query::{aggregate_ts_query, query_clock_drifts, query_ingestions, query_request_history},
+        with_statement_timeout,
+    },
+    deadline::Deadline,
+    error::ApiError,
+    model::{
+        api_request::{self, Aggregation, HistoryFilter, TimeSeriesRange},
+        api_response::{AggregationQueryRecord, IngestionSummary},
+        database::{IngestionClockDrift, QueryHistory},
+        validation::{Validate as _, ValidationLimits},
+    },
+    rounding,
+    route::check_bucket_estimate,
+    state::AppState,
+};
+
+pub type GraphQLSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Deepest selection accepted, no type nests far enough to need more
+const MAX_DEPTH: usize = 8;
+
+/// Built once, everything a request needs is attached as request data by [`execute`]
+static SCHEMA: LazyLock<GraphQLSchema> = LazyLock::new(|| {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+});
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(
+    name = "Aggregation",
+    remote = "crate::model::api_request::Aggregation"
+)]
+enum AggregationKind {
+    Hourly,
+    DayInMonth,
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(
+    name = "IngestionStatus",
+    remote = "crate::model::database::IngestionStatus"
+)]
+enum IngestionStatus {
+    Pending,
+    Complete,
+    Failed,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "QueryStatus", remote = "crate::model::database::QueryStatus")]
+enum QueryStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+#[graphql(name = "RangeEnd", remote = "crate::model::api_request::RangeEnd")]
+enum ToBound {
+    #[default]
+    Exclusive,
+    Inclusive,
+}
+
+/// Total of one bucket, `null` when the bucket holds no readings
+#[derive(SimpleObject, Clone)]
+struct Bucket {
+    datetime: DateTime<Utc>,
+    total_amount: Option<BigDecimal>,
+}
+
+impl From<AggregationQueryRecord> for Bucket {
+    fn from(record: AggregationQueryRecord) -> Self {
+        Self {
+            datetime: record.datetime,
+            total_amount: record
+                .total_amount
+                .map(|amount| rounding::current().apply(&amount)),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct Ingestion {
+    ingestion_id: i64,
+    source: String,
+    ingestion_datetime: DateTime<Utc>,
+    row_count: Option<i64>,
+    min_datetime: Option<DateTime<Utc>>,
+    max_datetime: Option<DateTime<Utc>>,
+    error_count: i64,
+    duration_ms: Option<i64>,
+    status: IngestionStatus,
+    /// Median daily mean reading over that of the series' earlier ingestions, set when
+    /// they are about a power of 1000 apart
+    unit_mismatch: Option<f64>,
+    /// Hex SHA-256 of the file the readings were read from
+    checksum: Option<String>,
+}
+
+impl From<IngestionSummary> for Ingestion {
+    fn from(summary: IngestionSummary) -> Self {
+        Self {
+            ingestion_id: summary.ingestion_id,
+            source: summary.source,
+            ingestion_datetime: summary.ingestion_datetime,
+            row_count: summary.row_count,
+            min_datetime: summary.min_datetime,
+            max_datetime: summary.max_datetime,
+            error_count: summary.error_count,
+            duration_ms: summary.duration_ms,
+            status: summary.status.into(),
+            unit_mismatch: summary.unit_mismatch,
+            checksum: summary.checksum,
+        }
+    }
+}
+
+#[ComplexObject]
+impl Ingestion {
+    /// Offsets of the ingestion's timestamps from the reading interval grid, loaded for every
+    /// listed ingestion in one query
+    async fn clock_drift(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ClockDrift>> {
+        let loader = ctx.data::<DataLoader<ClockDriftLoader>>()?;
+        loader.load_one(self.ingestion_id).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+struct ClockDrift {
+    interval_secs: i32,
+    readings: i64,
+    drifted_readings: i64,
+    max_offset_secs: i64,
+    snapped: bool,
+    collisions: i64,
+}
+
+impl From<IngestionClockDrift> for ClockDrift {
+    fn from(drift: IngestionClockDrift) -> Self {
+        Self {
+            interval_secs: drift.interval_secs,
+            readings: drift.readings,
+            drifted_readings: drift.drifted_readings,
+            max_offset_secs: drift.max_offset_secs,
+            snapped: drift.snapped,
+            collisions: drift.collisions,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct QueryHistoryEntry {
+    id: i64,
+    executed_at: DateTime<Utc>,
+    from_date: Option<DateTime<Utc>>,
+    /// Exclusive end of the range queried
+    to_date: Option<DateTime<Utc>>,
+    aggregation: AggregationKind,
+    api_key_id: Option<i64>,
+    duration_ms: Option<i64>,
+    /// Buckets returned, unset when the query failed or the count is not known
+    row_count: Option<i64>,
+    status: QueryStatus,
+}
+
+impl From<QueryHistory> for QueryHistoryEntry {
+    fn from(entry: QueryHistory) -> Self {
+        Self {
+            id: entry.id,
+            executed_at: entry.executed_at,
+            from_date: entry.from_date,
+            to_date: entry.to_date,
+            aggregation: entry.aggregation.into(),
+            api_key_id: entry.api_key_id,
+            duration_ms: entry.duration_ms,
+            row_count: entry.row_count,
+            status: entry.status.into(),
+        }
+    }
+}
+
+/// Renders a handler failure as a GraphQL error with the REST error code as an extension,
+/// hiding server side details as the REST API does
+fn graphql_error(error: ApiError) -> async_graphql::Error {
+    let (status, code) = error.status_and_code();
+    let message = if status.is_server_error() {
+        error!(code, "{error}");
+        "Internal Error".to_string()
+    } else {
+        warn!(code, "{error}");
+        error.to_string()
+    };
+    async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+/// Aggregation arguments with the range end already made exclusive
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct AggregationKey {
+    kind: Aggregation,
+    from_date: Option<DateTime<Utc>>,
+    to_date: Option<DateTime<Utc>>,
+}
+
+/// Evaluates every `aggregation` field of a document on one pooled connection, fields
+/// repeating the same arguments are evaluated once
+struct AggregationLoader {
+    pg_pool: TimedPool,
+    deadline: Deadline,
+}
+
+impl AggregationLoader {
+    async fn aggregate(
+        &self,
+        keys: Vec<AggregationKey>,
+    ) -> Result<HashMap<AggregationKey, Vec<Bucket>>, ApiError> {
+        let deadline = self.deadline;
+        let conn = self.pg_pool.get().await.map_err(ApiError::Pool)?;
+        conn.interact(move |conn| {
+            with_statement_timeout(conn, deadline.remaining(), |conn| {
+                keys.into_iter()
+                    .map(|key| {
+                        let records =
+                            aggregate_ts_query(key.kind, key.from_date, key.to_date, None, conn)?;
+                        Ok((key, records.into_iter().map(Bucket::from).collect()))
+                    })
+                    .collect()
+            })
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)
+    }
+}
+
+impl Loader<AggregationKey> for AggregationLoader {
+    type Value = Vec<Bucket>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[AggregationKey],
+    ) -> Result<HashMap<AggregationKey, Self::Value>, Self::Error> {
+        self.aggregate(keys.to_vec()).await.map_err(graphql_error)
+    }
+}
+
+/// Batches the `clockDrift` lookups of an ingestion listing into one query
+struct ClockDriftLoader {
+    pg_pool: TimedPool,
+}
+
+impl ClockDriftLoader {
+    async fn drifts(&self, ingestion_ids: Vec<i64>) -> Result<HashMap<i64, ClockDrift>, ApiError> {
+        let conn = self.pg_pool.get().await.map_err(ApiError::Pool)?;
+        let drifts = conn
+            .interact(move |conn| query_clock_drifts(&ingestion_ids, conn))
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Ok(drifts
+            .into_iter()
+            .map(|drift| (drift.ingestion_id, drift.into()))
+            .collect())
+    }
+}
+
+impl Loader<i64> for ClockDriftLoader {
+    type Value = ClockDrift;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[i64]) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+        self.drifts(keys.to_vec()).await.map_err(graphql_error)
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Totals per bucket of current readings from `from` up to, but excluding, `to`, or
+    /// including it with `toBound: INCLUSIVE`
+    async fn aggregation(
+        &self,
+        ctx: &Context<'_>,
+        kind: AggregationKind,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        #[graphql(default)] to_bound: ToBound,
+    ) -> async_graphql::Result<Vec<Bucket>> {
+        let state = ctx.data::<AppState>()?;
+        let ApiKey(api_key_id) = *ctx.data::<ApiKey>()?;
+
+        let range = TimeSeriesRange {
+            from_date: from,
+            to_date: to,
+            to_bound: api_request::RangeEnd::from(to_bound),
+            from_settlement: None,
+            to_settlement: None,
+        };
+        if let Some(violation) = range
+            .violations(ValidationLimits::from_ref(&state.config))
+            .first()
+        {
+            return Err(async_graphql::Error::new(format!(
+                "{} {}",
+                violation.field, violation.message
+            ))
+            .extend_with(|_, extensions| extensions.set("code", "validation_error")));
+        }
+
+        let kind = Aggregation::from(kind);
+        let (from_date, to_date) = range.half_open();
+        info!(aggregation_kind= ?kind, from_date= ?from_date, to_date= ?to_date, "Received GraphQL Time Series Query");
+        check_bucket_estimate(state, kind, from_date, to_date, false)
+            .await
+            .map_err(graphql_error)?;
+        if let Some(telemetry) = &state.telemetry {
+            telemetry.record_aggregation(kind);
+        }
+        let history = state
+            .history
+            .start(kind, from_date, to_date, Some(api_key_id));
+
+        let loader = ctx.data::<DataLoader<AggregationLoader>>()?;
+        let key = AggregationKey {
+            kind,
+            from_date,
+            to_date,
+        };
+        let buckets = loader.load_one(key).await?.unwrap_or_default();
+        history.succeeded(Some(buckets.len()));
+        Ok(buckets)
+    }
+
+    /// Loaded datasets, as `GET /timeseries/v1/ingestions`
+    async fn ingestions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Ingestion>> {
+        let state = ctx.data::<AppState>()?;
+        let ingestions = async {
+            let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+            conn.interact(query_ingestions)
+                .await
+                .map_err(ApiError::Interaction)?
+                .map_err(ApiError::Database)
+        }
+        .await
+        .map_err(graphql_error)?;
+        Ok(ingestions.into_iter().map(Ingestion::from).collect())
+    }
+
+    /// Most recent queries, as `GET /timeseries/v1/query/history`
+    async fn query_history(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<QueryHistoryEntry>> {
+        let state = ctx.data::<AppState>()?;
+        let limit = state.config.history_limit;
+        let entries = async {
+            let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+            conn.interact(move |conn| {
+                query_request_history(limit, None, &HistoryFilter::default(), conn)
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)
+        }
+        .await
+        .map_err(graphql_error)?;
+        Ok(entries.into_iter().map(QueryHistoryEntry::from).collect())
+    }
+}
+
+/// Runs `request` with fresh data loaders, so nothing loaded is shared between requests
+pub async fn execute(
+    state: AppState,
+    api_key: ApiKey,
+    deadline: Deadline,
+    request: async_graphql::Request,
+) -> async_graphql::Response {
+    let aggregations = DataLoader::new(
+        AggregationLoader {
+            pg_pool: state.db.read().clone(),
+            deadline,
+        },
+        tokio::spawn,
+    );
+    let clock_drifts = DataLoader::new(
+        ClockDriftLoader {
+            pg_pool: state.db.primary().clone(),
+        },
+        tokio::spawn,
+    );
+    let request = request
+        .data(state)
+        .data(api_key)
+        .data(aggregations)
+        .data(clock_drifts);
+    SCHEMA.execute(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::SCHEMA;
+
+    #[test]
+    fn test_schema_exposes_query_fields() {
+        let sdl = SCHEMA.sdl();
+        assert!(sdl.contains(
+            "aggregation(kind: Aggregation!, from: DateTime, to: DateTime, toBound: RangeEnd! = EXCLUSIVE): [Bucket!]!"
+        ));
+        assert!(sdl.contains("ingestions: [Ingestion!]!"));
+        assert!(sdl.contains("queryHistory: [QueryHistoryEntry!]!"));
+        assert!(sdl.contains("clockDrift: ClockDrift"));
+    }
+
+    #[tokio::test]
+    async fn test_deep_selections_are_rejected() {
+        let query = "{ __schema { types { fields { type { ofType { ofType { ofType { ofType { name } } } } } } } } }";
+        let response = SCHEMA.execute(query).await;
+        assert_eq!(response.errors.len(), 1);
+        assert!(response.errors[0].message.contains("nested too deep"));
+    }
+}
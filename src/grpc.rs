@@ -0,0 +1,602 @@
+use std::{net::SocketAddr, str::FromStr as _};
+
+use axum::{extract::FromRef as _, http::StatusCode};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use tonic::{
+    Code, Request, Response, Status,
+    transport::{Server, server::TcpIncoming},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth::{API_KEY_HEADER, resolve_api_key},
+    db::{
+        query::{aggregate_ts_query, find_source_ingestion, query_request_history},
+        seed_database::{PreparedReadings, insert_ingestion_with_drift},
+        with_statement_timeout,
+    },
+    decimal::{self, DecimalFormat},
+    drift::{self, DriftConfig},
+    error::ApiError,
+    issues, live,
+    model::{
+        api_request::{
+            Aggregation, AmountUnit, HistoryFilter, RangeEnd, TimeSeriesAggregationRequest,
+            TimeSeriesRange,
+        },
+        api_response::{AggregationQueryRecord, IngestionNotification},
+        check_amount_bounds,
+        csv::CSVRecord,
+        database::{QueryHistory, QueryStatus},
+        validation::{Validate as _, ValidationLimits},
+    },
+    rounding,
+    route::check_bucket_estimate,
+    state::AppState,
+};
+
+/// Metadata entry picking the [`DecimalFormat`] of returned amounts
+const DECIMALS_METADATA: &str = "decimals";
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/renewable.v1.TimeSeries.rs"));
+}
+
+pub use generated::{
+    time_series_client::TimeSeriesClient,
+    time_series_server::{TimeSeries, TimeSeriesServer},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum AggregationKind {
+    Unspecified = 0,
+    Hourly = 1,
+    DayInMonth = 2,
+    Weekly = 3,
+    Monthly = 4,
+    Quarterly = 5,
+    Yearly = 6,
+}
+
+impl From<Aggregation> for AggregationKind {
+    fn from(kind: Aggregation) -> Self {
+        match kind {
+            Aggregation::Hourly => Self::Hourly,
+            Aggregation::DayInMonth => Self::DayInMonth,
+            Aggregation::Weekly => Self::Weekly,
+            Aggregation::Monthly => Self::Monthly,
+            Aggregation::Quarterly => Self::Quarterly,
+            Aggregation::Yearly => Self::Yearly,
+        }
+    }
+}
+
+impl TryFrom<i32> for Aggregation {
+    type Error = Status;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match AggregationKind::try_from(value) {
+            Ok(AggregationKind::Hourly) => Ok(Self::Hourly),
+            Ok(AggregationKind::DayInMonth) => Ok(Self::DayInMonth),
+            Ok(AggregationKind::Weekly) => Ok(Self::Weekly),
+            Ok(AggregationKind::Monthly) => Ok(Self::Monthly),
+            Ok(AggregationKind::Quarterly) => Ok(Self::Quarterly),
+            Ok(AggregationKind::Yearly) => Ok(Self::Yearly),
+            Ok(AggregationKind::Unspecified) | Err(_) => Err(Status::invalid_argument(
+                "aggregation_kind must be specified",
+            )),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AggregationRequest {
+    #[prost(enumeration = "AggregationKind", tag = "1")]
+    pub aggregation_kind: i32,
+    #[prost(message, optional, tag = "2")]
+    pub from_date: Option<Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    pub to_date: Option<Timestamp>,
+    #[prost(bool, tag = "4")]
+    pub inclusive_end: bool,
+    #[prost(message, optional, tag = "5")]
+    pub as_recorded_by: Option<Timestamp>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Bucket {
+    #[prost(message, optional, tag = "1")]
+    pub datetime: Option<Timestamp>,
+    #[prost(string, optional, tag = "2")]
+    pub total_amount: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AggregationResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub buckets: Vec<Bucket>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HistoryRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HistoryEntry {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(message, optional, tag = "2")]
+    pub executed_at: Option<Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    pub from_date: Option<Timestamp>,
+    #[prost(message, optional, tag = "4")]
+    pub to_date: Option<Timestamp>,
+    #[prost(enumeration = "AggregationKind", tag = "5")]
+    pub aggregation_kind: i32,
+    #[prost(int64, optional, tag = "6")]
+    pub api_key_id: Option<i64>,
+    #[prost(int64, optional, tag = "7")]
+    pub duration_ms: Option<i64>,
+    #[prost(int64, optional, tag = "8")]
+    pub row_count: Option<i64>,
+    #[prost(bool, tag = "9")]
+    pub failed: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HistoryResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub entries: Vec<HistoryEntry>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Reading {
+    #[prost(message, optional, tag = "1")]
+    pub datetime: Option<Timestamp>,
+    #[prost(string, tag = "2")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IngestRequest {
+    #[prost(string, tag = "1")]
+    pub source: String,
+    #[prost(message, repeated, tag = "2")]
+    pub readings: Vec<Reading>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IngestResponse {
+    #[prost(int64, tag = "1")]
+    pub ingestion_id: i64,
+    #[prost(uint64, tag = "2")]
+    pub readings: u64,
+    #[prost(uint64, tag = "3")]
+    pub drifted_readings: u64,
+}
+
+fn timestamp(datetime: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: datetime.timestamp(),
+        nanos: i32::try_from(datetime.timestamp_subsec_nanos()).unwrap_or_default(),
+    }
+}
+
+fn datetime(field: &str, timestamp: Timestamp) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| DateTime::from_timestamp(timestamp.seconds, nanos))
+        .ok_or_else(|| Status::invalid_argument(format!("{field} is out of range")))
+}
+
+fn optional_datetime(
+    field: &str,
+    timestamp: Option<Timestamp>,
+) -> Result<Option<DateTime<Utc>>, Status> {
+    timestamp.map(|ts| datetime(field, ts)).transpose()
+}
+
+impl From<AggregationQueryRecord> for Bucket {
+    fn from(record: AggregationQueryRecord) -> Self {
+        Self {
+            datetime: Some(timestamp(record.datetime)),
+            total_amount: record.total_amount.map(|amount| {
+                DecimalFormat::current(DecimalFormat::Exact)
+                    .render(&rounding::current().apply(&amount))
+            }),
+        }
+    }
+}
+
+impl From<QueryHistory> for HistoryEntry {
+    fn from(entry: QueryHistory) -> Self {
+        Self {
+            id: entry.id,
+            executed_at: Some(timestamp(entry.executed_at)),
+            from_date: entry.from_date.map(timestamp),
+            to_date: entry.to_date.map(timestamp),
+            aggregation_kind: AggregationKind::from(entry.aggregation).into(),
+            api_key_id: entry.api_key_id,
+            duration_ms: entry.duration_ms,
+            row_count: entry.row_count,
+            failed: entry.status == QueryStatus::Failed,
+        }
+    }
+}
+
+impl TryFrom<AggregationRequest> for TimeSeriesAggregationRequest {
+    type Error = Status;
+
+    fn try_from(request: AggregationRequest) -> Result<Self, Self::Error> {
+        let to_bound = if request.inclusive_end {
+            RangeEnd::Inclusive
+        } else {
+            RangeEnd::Exclusive
+        };
+        Ok(Self {
+            aggregation_kind: Some(Aggregation::try_from(request.aggregation_kind)?),
+            datetime_filter: Some(TimeSeriesRange {
+                from_date: optional_datetime("from_date", request.from_date)?,
+                to_date: optional_datetime("to_date", request.to_date)?,
+                to_bound,
+                from_settlement: None,
+                to_settlement: None,
+            }),
+            fill_missing: None,
+            include_lineage: false,
+            include_completeness: false,
+            include_power: false,
+            include_settlement: false,
+            include_sources: false,
+            having: None,
+            group_by: Vec::new(),
+            unit: AmountUnit::default(),
+            timezone: None,
+            series_id: None,
+            series_name: None,
+            as_recorded_by: optional_datetime("as_recorded_by", request.as_recorded_by)?,
+        })
+    }
+}
+
+/// Decodes a reading with the same checks applied to seed file rows
+fn csv_record(index: usize, reading: Reading) -> Result<CSVRecord, Status> {
+    let datetime = reading
+        .datetime
+        .ok_or_else(|| Status::invalid_argument(format!("readings[{index}].datetime is required")))
+        .and_then(|ts| datetime(&format!("readings[{index}].datetime"), ts))?;
+    let amount = BigDecimal::from_str(reading.amount.trim())
+        .map_err(|e| Status::invalid_argument(format!("readings[{index}].amount {e}")))?;
+    check_amount_bounds(&amount)
+        .map_err(|e| Status::invalid_argument(format!("readings[{index}].{e}")))?;
+    Ok(CSVRecord {
+        datetime,
+        amount,
+        extra: None,
+    })
+}
+
+/// Maps handler failures onto gRPC codes, hiding server side details as the REST API does
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        let (status, code) = error.status_and_code();
+        if matches!(error, ApiError::ReadOnly) {
+            warn!(code, "{error}");
+            return Self::unavailable(error.to_string());
+        }
+        if status.is_server_error() {
+            error!(code, "{error}");
+        } else {
+            warn!(code, "{error}");
+        }
+        match status {
+            StatusCode::BAD_REQUEST => Self::invalid_argument(error.to_string()),
+            StatusCode::UNAUTHORIZED => Self::unauthenticated(error.to_string()),
+            StatusCode::FORBIDDEN => Self::permission_denied(error.to_string()),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Self::not_found(error.to_string()),
+            StatusCode::CONFLICT => Self::already_exists(error.to_string()),
+            StatusCode::PAYLOAD_TOO_LARGE => Self::resource_exhausted(error.to_string()),
+            StatusCode::SERVICE_UNAVAILABLE => Self::unavailable("Service Unavailable"),
+            StatusCode::GATEWAY_TIMEOUT => Self::deadline_exceeded("Deadline Exceeded"),
+            _ => Self::new(Code::Internal, "Internal Error"),
+        }
+    }
+}
+
+/// `TimeSeries` gRPC service, backed by the same queries and state as the REST routes
+#[derive(Clone)]
+pub struct TimeSeriesService {
+    state: AppState,
+    drift_config: DriftConfig,
+}
+
+impl TimeSeriesService {
+    pub fn new(state: AppState) -> Result<Self, String> {
+        let drift_config = DriftConfig::from_env(state.config.reading_interval())?;
+        Ok(Self {
+            state,
+            drift_config,
+        })
+    }
+
+    /// Decimal format of amounts sent as `decimals` metadata, `None` for exact strings
+    fn decimal_format<T>(request: &Request<T>) -> Result<Option<DecimalFormat>, Status> {
+        request
+            .metadata()
+            .get(DECIMALS_METADATA)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| Status::invalid_argument("decimals must be ASCII"))?
+                    .parse()
+                    .map_err(Status::invalid_argument)
+            })
+            .transpose()
+    }
+
+    /// Id of the API key sent as `x-api-key` metadata
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<i64, Status> {
+        let key = request
+            .metadata()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        Ok(resolve_api_key(self.state.db.primary(), key).await?)
+    }
+}
+
+#[tonic::async_trait]
+impl TimeSeries for TimeSeriesService {
+    async fn query_aggregation(
+        &self,
+        request: Request<AggregationRequest>,
+    ) -> Result<Response<AggregationResponse>, Status> {
+        let api_key_id = self.authenticate(&request).await?;
+        let decimals = Self::decimal_format(&request)?;
+        let query = TimeSeriesAggregationRequest::try_from(request.into_inner())?;
+        let errors = query.violations(ValidationLimits::from_ref(&self.state.config));
+        if !errors.is_empty() {
+            let details: Vec<_> = errors
+                .iter()
+                .map(|e| format!("{} {}", e.field, e.message))
+                .collect();
+            return Err(Status::invalid_argument(details.join(", ")));
+        }
+
+        let now = Utc::now();
+        let (aggregation_kind, from_date, to_date) =
+            query.resolve(self.state.config.default_query_start(now), now);
+        let as_recorded_by = query.as_recorded_by;
+        info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received gRPC Time Series Query");
+        check_bucket_estimate(&self.state, aggregation_kind, from_date, to_date, false).await?;
+        if let Some(telemetry) = &self.state.telemetry {
+            telemetry.record_aggregation(aggregation_kind);
+        }
+        let history =
+            self.state
+                .history
+                .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+
+        let timeout = self.state.config.query_timeout();
+        let conn = self.state.db.read().get().await.map_err(ApiError::Pool)?;
+        let records = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, Some(timeout), |conn| {
+                    aggregate_ts_query(aggregation_kind, from_date, to_date, as_recorded_by, conn)
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+
+        history.succeeded(Some(records.len()));
+        Ok(Response::new(AggregationResponse {
+            buckets: decimal::within(decimals, || records.into_iter().map(Bucket::from).collect()),
+        }))
+    }
+
+    async fn query_history(
+        &self,
+        request: Request<HistoryRequest>,
+    ) -> Result<Response<HistoryResponse>, Status> {
+        self.authenticate(&request).await?;
+
+        let limit = self.state.config.history_limit;
+        let conn = self
+            .state
+            .db
+            .primary()
+            .get()
+            .await
+            .map_err(ApiError::Pool)?;
+        let entries = conn
+            .interact(move |conn| {
+                query_request_history(limit, None, &HistoryFilter::default(), conn)
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+
+        Ok(Response::new(HistoryResponse {
+            entries: entries.into_iter().map(HistoryEntry::from).collect(),
+        }))
+    }
+
+    async fn ingest(
+        &self,
+        request: Request<IngestRequest>,
+    ) -> Result<Response<IngestResponse>, Status> {
+        self.authenticate(&request).await?;
+        self.state.read_only.ensure_writable()?;
+        let IngestRequest { source, readings } = request.into_inner();
+        if source.trim().is_empty() {
+            return Err(Status::invalid_argument("source must not be empty"));
+        }
+        if readings.is_empty() {
+            return Err(Status::invalid_argument("readings must not be empty"));
+        }
+        let readings = readings
+            .into_iter()
+            .enumerate()
+            .map(|(index, reading)| csv_record(index, reading))
+            .collect::<Result<Vec<_>, _>>()?;
+        info!(source, readings = readings.len(), "Received gRPC Ingestion");
+
+        let drift_config = self.drift_config;
+        let priority = self.state.config.source_priority(&source);
+        let issues = issues::inspect(&readings, drift_config.interval);
+        let (readings, report) = drift::analyse(readings, &drift_config);
+        let drifted_readings = report.drifted;
+        let span = live::reading_span(&readings);
+        let notified_source = source.clone();
+        let conn = self
+            .state
+            .db
+            .primary()
+            .get()
+            .await
+            .map_err(ApiError::Pool)?;
+        let received = readings.len();
+        let ingested = conn
+            .interact(move |conn| {
+                // Sources carry no unique constraint, a repeat would double every total
+                if find_source_ingestion(&source, conn)?.is_some() {
+                    return Ok(None);
+                }
+                let prepared = PreparedReadings {
+                    readings,
+                    report,
+                    rejected: 0,
+                    checksum: None,
+                    issues,
+                };
+                insert_ingestion_with_drift(source, None, priority, prepared, &drift_config, conn)
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        let Some((ingestion_id, inserted_rows)) = ingested else {
+            return Err(Status::already_exists("source has already been ingested"));
+        };
+        if let Some(cache) = &self.state.hot_cache
+            && let Err(e) = cache.refresh(self.state.db.primary()).await
+        {
+            error!("Unable to refresh hot cache after gRPC ingestion: {e}");
+        }
+        self.state.invalidate_response_cache().await;
+        if let Some((first_reading_at, last_reading_at)) = span {
+            self.state.ingestion_events.publish(IngestionNotification {
+                ingestion_id,
+                source: notified_source,
+                rows: inserted_rows,
+                updated_rows: 0,
+                skipped_rows: received.saturating_sub(inserted_rows),
+                first_reading_at,
+                last_reading_at,
+                unit_mismatch: None,
+            });
+        }
+
+        Ok(Response::new(IngestResponse {
+            ingestion_id,
+            readings: u64::try_from(inserted_rows).unwrap_or(u64::MAX),
+            drifted_readings: u64::try_from(drifted_readings).unwrap_or(u64::MAX),
+        }))
+    }
+}
+
+/// Binds `addr` up front so a taken port fails startup, then serves until shutdown
+pub fn serve(
+    addr: SocketAddr,
+    service: TimeSeriesService,
+) -> Result<impl Future<Output = Result<(), tonic::transport::Error>>, std::io::Error> {
+    // Queries and uploads share the server, each bounded by the longer of their timeouts
+    let timeout = service
+        .state
+        .config
+        .query_timeout()
+        .max(service.state.config.ingest_timeout());
+    let shutdown = service.state.shutdown.clone();
+    let incoming = TcpIncoming::bind(addr)?;
+    info!("gRPC listening on {addr}");
+    Ok(Server::builder()
+        .timeout(timeout)
+        .add_service(TimeSeriesServer::new(service))
+        .serve_with_incoming_shutdown(incoming, async move {
+            shutdown.requested().await;
+        }))
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone as _, Utc};
+    use prost::Message as _;
+    use tonic::Code;
+
+    use super::{AggregationKind, AggregationRequest, Reading, csv_record, datetime, timestamp};
+    use crate::model::api_request::{Aggregation, RangeEnd, TimeSeriesAggregationRequest};
+
+    #[test]
+    fn test_request_decodes_into_aggregation_query() {
+        let from = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let request = AggregationRequest {
+            aggregation_kind: AggregationKind::Weekly.into(),
+            from_date: Some(timestamp(from)),
+            to_date: None,
+            inclusive_end: true,
+            as_recorded_by: None,
+        };
+        let bytes = request.encode_to_vec();
+
+        let query =
+            TimeSeriesAggregationRequest::try_from(AggregationRequest::decode(&*bytes).unwrap())
+                .unwrap();
+        assert_eq!(query.aggregation_kind, Some(Aggregation::Weekly));
+        let range = query.datetime_filter.unwrap();
+        assert_eq!(range.from_date, Some(from));
+        assert_eq!(range.to_bound, RangeEnd::Inclusive);
+
+        let unspecified = AggregationRequest::default();
+        let status = TimeSeriesAggregationRequest::try_from(unspecified).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_timestamps_round_trip() {
+        let at = Utc.with_ymd_and_hms(2025, 6, 30, 23, 0, 0).unwrap()
+            + chrono::TimeDelta::microseconds(7);
+        assert_eq!(datetime("at", timestamp(at)).unwrap(), at);
+    }
+
+    #[test]
+    fn test_readings_are_checked_like_seed_rows() {
+        let at = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let reading = |amount: &str| Reading {
+            datetime: Some(timestamp(at)),
+            amount: amount.to_string(),
+        };
+
+        let record = csv_record(0, reading(" 12.5 ")).unwrap();
+        assert_eq!(record.amount, "12.5".parse::<BigDecimal>().unwrap());
+        assert_eq!(
+            csv_record(1, reading("lots")).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            csv_record(2, reading(&"9".repeat(30))).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        let undated = Reading {
+            datetime: None,
+            amount: "1".to_string(),
+        };
+        assert!(
+            csv_record(3, undated)
+                .unwrap_err()
+                .message()
+                .contains("readings[3].datetime")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use deadpool_diesel::Status;
+
+use crate::{
+    db::PoolWaits,
+    model::api_response::{CacheHealth, PoolHealth, ReplicationHealth},
+};
+
+/// Score at or above which the instance is reported as fully healthy
+pub const HEALTHY_SCORE: f64 = 0.75;
+/// Score below which the instance should be drained by load balancers
+pub const UNAVAILABLE_SCORE: f64 = 0.25;
+
+/// Replication lag tolerated before the replication score starts to degrade
+const LAG_GRACE_SECS: f64 = 5.0;
+/// Replication lag at which the replication score reaches zero
+const LAG_MAX_SECS: f64 = 60.0;
+
+#[allow(clippy::cast_precision_loss)]
+pub fn pool_health(status: Status, waits: PoolWaits) -> PoolHealth {
+    let in_use = status.size.saturating_sub(status.available);
+    let saturation = if status.max_size == 0 {
+        1.0
+    } else {
+        in_use as f64 / status.max_size as f64
+    };
+    // Requests queueing for a connection are worse than a busy but keeping-up pool
+    let waiting_penalty = if status.waiting > 0 { 0.5 } else { 1.0 };
+    let score = ((1.0 - saturation) * waiting_penalty).clamp(0.0, 1.0);
+
+    PoolHealth {
+        size: status.size,
+        available: status.available,
+        max_size: status.max_size,
+        waiting: status.waiting,
+        saturation,
+        score,
+        checkouts: waits.checkouts,
+        average_wait_ms: if waits.checkouts == 0 {
+            0.0
+        } else {
+            waits.total.as_secs_f64() * 1000.0 / waits.checkouts as f64
+        },
+        longest_wait_ms: waits.longest.as_secs_f64() * 1000.0,
+    }
+}
+
+pub fn replication_health(lag_seconds: Option<f64>) -> ReplicationHealth {
+    let score = match lag_seconds {
+        None => 1.0,
+        Some(lag) if lag <= LAG_GRACE_SECS => 1.0,
+        Some(lag) => (1.0 - (lag - LAG_GRACE_SECS) / (LAG_MAX_SECS - LAG_GRACE_SECS)).max(0.0),
+    };
+    ReplicationHealth { lag_seconds, score }
+}
+
+pub fn cache_health(enabled: bool, readings: usize) -> CacheHealth {
+    // A cold cache still serves correctly via Postgres, only slower
+    let score = if !enabled || readings > 0 { 1.0 } else { 0.5 };
+    CacheHealth {
+        enabled,
+        readings,
+        score,
+    }
+}
+
+/// Combines component scores, the weakest component dominates the overall score
+pub fn overall_score(
+    pool: &PoolHealth,
+    replication: &ReplicationHealth,
+    cache: &CacheHealth,
+) -> f64 {
+    pool.score.min(replication.score).min(cache.score)
+}
+
+pub fn status_label(score: f64) -> &'static str {
+    if score >= HEALTHY_SCORE {
+        "ok"
+    } else if score >= UNAVAILABLE_SCORE {
+        "degraded"
+    } else {
+        "unavailable"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use deadpool_diesel::Status;
+
+    use super::{cache_health, overall_score, pool_health, replication_health, status_label};
+    use crate::db::PoolWaits;
+
+    #[test]
+    fn test_pool_health_scores_saturation() {
+        let idle = pool_health(
+            Status {
+                max_size: 10,
+                size: 2,
+                available: 2,
+                waiting: 0,
+            },
+            PoolWaits::default(),
+        );
+        assert!((idle.score - 1.0).abs() < f64::EPSILON);
+
+        let busy = pool_health(
+            Status {
+                max_size: 10,
+                size: 10,
+                available: 2,
+                waiting: 0,
+            },
+            PoolWaits::default(),
+        );
+        assert!((busy.saturation - 0.8).abs() < 1e-9);
+
+        let queueing = pool_health(
+            Status {
+                max_size: 10,
+                size: 10,
+                available: 0,
+                waiting: 3,
+            },
+            PoolWaits::default(),
+        );
+        assert!(queueing.score.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_pool_health_reports_checkout_waits() {
+        let status = Status {
+            max_size: 10,
+            size: 4,
+            available: 4,
+            waiting: 0,
+        };
+        let idle = pool_health(status, PoolWaits::default());
+        assert_eq!((idle.checkouts, idle.average_wait_ms), (0, 0.0));
+
+        let waited = pool_health(
+            status,
+            PoolWaits {
+                checkouts: 4,
+                total: Duration::from_millis(10),
+                longest: Duration::from_millis(7),
+            },
+        );
+        assert!((waited.average_wait_ms - 2.5).abs() < 1e-9);
+        assert!((waited.longest_wait_ms - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_replication_and_cache_scores() {
+        assert!((replication_health(None).score - 1.0).abs() < f64::EPSILON);
+        assert!((replication_health(Some(2.0)).score - 1.0).abs() < f64::EPSILON);
+        assert!(replication_health(Some(120.0)).score.abs() < f64::EPSILON);
+
+        assert!((cache_health(false, 0).score - 1.0).abs() < f64::EPSILON);
+        assert!((cache_health(true, 0).score - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_overall_score_uses_weakest_component() {
+        let pool = pool_health(
+            Status {
+                max_size: 10,
+                size: 1,
+                available: 1,
+                waiting: 0,
+            },
+            PoolWaits::default(),
+        );
+        let replication = replication_health(Some(32.5));
+        let cache = cache_health(true, 10);
+
+        let score = overall_score(&pool, &replication, &cache);
+        assert!((score - 0.5).abs() < 1e-9);
+        assert_eq!(status_label(score), "degraded");
+        assert_eq!(status_label(0.9), "ok");
+        assert_eq!(status_label(0.1), "unavailable");
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tracing::{error, warn};
+
+use crate::{
+    db::{TimedPool, query::insert_query_history},
+    model::{
+        api_request::Aggregation,
+        database::{QueryHistory, QueryStatus},
+    },
+};
+
+/// Queues query history entries for a background task that writes them in batches,
+/// so each read costs a channel send rather than an insert.
+///
+/// Entries arriving while the buffer is full are dropped and counted.
+#[derive(Clone)]
+pub struct HistoryWriter {
+    sender: mpsc::Sender<QueryHistory>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl HistoryWriter {
+    /// Spawns the flush task writing queued entries every `flush_interval`
+    pub fn spawn(pg_pool: TimedPool, buffer: usize, flush_interval: Duration) -> Self {
+        let (writer, receiver) = Self::channel(buffer);
+        tokio::spawn(flush_loop(
+            pg_pool,
+            receiver,
+            Arc::clone(&writer.dropped),
+            flush_interval,
+        ));
+        writer
+    }
+
+    fn channel(buffer: usize) -> (Self, mpsc::Receiver<QueryHistory>) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let writer = Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (writer, receiver)
+    }
+
+    /// Starts timing a query, which is recorded once the returned guard is finished with
+    /// [`PendingQuery::succeeded`], or as failed when it is dropped first, e.g. by an error
+    /// returned with `?`
+    pub fn start(
+        &self,
+        aggregation_kind: Aggregation,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        api_key_id: Option<i64>,
+    ) -> PendingQuery {
+        PendingQuery {
+            writer: self.clone(),
+            entry: Some(QueryHistory::new(
+                from_date,
+                to_date,
+                aggregation_kind,
+                api_key_id,
+            )),
+            started: Instant::now(),
+        }
+    }
+
+    fn record(&self, entry: QueryHistory) {
+        if self.sender.try_send(entry).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Entries waiting for the next flush
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// Entries dropped because the buffer was full, since startup
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Query being answered, see [`HistoryWriter::start`]
+pub struct PendingQuery {
+    writer: HistoryWriter,
+    entry: Option<QueryHistory>,
+    started: Instant,
+}
+
+impl PendingQuery {
+    /// Records the query as answered with `rows` buckets, `None` when not known
+    pub fn succeeded(mut self, rows: Option<usize>) {
+        self.finish(QueryStatus::Succeeded, rows);
+    }
+
+    fn finish(&mut self, status: QueryStatus, rows: Option<usize>) {
+        if let Some(mut entry) = self.entry.take() {
+            let elapsed = self.started.elapsed().as_millis();
+            entry.duration_ms = Some(i64::try_from(elapsed).unwrap_or(i64::MAX));
+            entry.row_count = rows.map(|rows| i64::try_from(rows).unwrap_or(i64::MAX));
+            entry.status = status;
+            self.writer.record(entry);
+        }
+    }
+}
+
+impl Drop for PendingQuery {
+    fn drop(&mut self) {
+        self.finish(QueryStatus::Failed, None);
+    }
+}
+
+/// Drains everything currently queued, returning `false` once every writer is gone
+fn drain(receiver: &mut mpsc::Receiver<QueryHistory>, batch: &mut Vec<QueryHistory>) -> bool {
+    loop {
+        match receiver.try_recv() {
+            Ok(entry) => batch.push(entry),
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => return false,
+        }
+    }
+}
+
+async fn flush(pg_pool: &TimedPool, batch: Vec<QueryHistory>) {
+    let count = batch.len();
+    let conn = match pg_pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!(count, "Unable to write query history: {e}");
+            return;
+        }
+    };
+    match conn
+        .interact(move |conn| insert_query_history(&batch, conn))
+        .await
+    {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => error!(count, "Unable to write query history: {e}"),
+        Err(e) => error!(count, "Unable to write query history: {e}"),
+    }
+}
+
+async fn flush_loop(
+    pg_pool: TimedPool,
+    mut receiver: mpsc::Receiver<QueryHistory>,
+    dropped: Arc<AtomicU64>,
+    flush_interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(flush_interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut reported_dropped = 0;
+
+    loop {
+        ticker.tick().await;
+        let mut batch = Vec::new();
+        let open = drain(&mut receiver, &mut batch);
+        if !batch.is_empty() {
+            flush(&pg_pool, batch).await;
+        }
+
+        // Overflow is reported once per interval rather than once per dropped entry
+        let total = dropped.load(Ordering::Relaxed);
+        if total > reported_dropped {
+            warn!(
+                dropped = total - reported_dropped,
+                total, "Query history buffer full, entries dropped"
+            );
+            reported_dropped = total;
+        }
+        if !open {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HistoryWriter, drain};
+    use crate::model::{api_request::Aggregation, database::QueryStatus};
+
+    #[test]
+    fn test_full_buffer_drops_and_counts_entries() {
+        let (writer, mut receiver) = HistoryWriter::channel(2);
+        for _ in 0..5 {
+            writer
+                .start(Aggregation::Hourly, None, None, Some(1))
+                .succeeded(Some(3));
+        }
+        assert_eq!(writer.queued(), 2);
+        assert_eq!(writer.dropped(), 3);
+
+        let mut batch = Vec::new();
+        assert!(drain(&mut receiver, &mut batch));
+        assert_eq!(batch.len(), 2);
+        assert_eq!(writer.queued(), 0);
+
+        drop(writer);
+        assert!(!drain(&mut receiver, &mut batch));
+    }
+
+    #[test]
+    fn test_queries_dropped_unfinished_are_recorded_as_failed() {
+        let (writer, mut receiver) = HistoryWriter::channel(4);
+        writer
+            .start(Aggregation::Monthly, None, None, Some(1))
+            .succeeded(Some(12));
+        drop(writer.start(Aggregation::Yearly, None, None, Some(1)));
+
+        let mut batch = Vec::new();
+        drain(&mut receiver, &mut batch);
+        let outcomes: Vec<_> = batch
+            .iter()
+            .map(|entry| (entry.status, entry.row_count, entry.duration_ms.is_some()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (QueryStatus::Succeeded, Some(12), true),
+                (QueryStatus::Failed, None, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use std::{env, sync::Arc, sync::RwLock};
+
+use arrow_array::RecordBatch;
+use arrow_schema::ArrowError;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use datafusion::datasource::MemTable;
+use deadpool_diesel::postgres::Pool;
+use tracing::{error, info, warn};
+
+use crate::{
+    columnar::{totals_batch, totals_schema},
+    db::{PgError, query::load_recent_window},
+    engine::{self, READINGS},
+    model::{
+        api_request::{Aggregation, TotalFilter},
+        api_response::AggregationQueryRecord,
+    },
+};
+
+/// Columnar snapshot of the most recent readings, summed across ingestions per timestamp
+#[derive(Debug)]
+struct HotWindow {
+    start: Option<DateTime<Utc>>,
+    readings: RecordBatch,
+}
+
+impl Default for HotWindow {
+    fn default() -> Self {
+        Self {
+            start: None,
+            readings: RecordBatch::new_empty(totals_schema()),
+        }
+    }
+}
+
+/// Optional in-memory cache of the last N days of readings, used to answer
+/// Hourly queries over recent windows without a round trip to Postgres
+#[derive(Debug)]
+pub struct HotCache {
+    days: u32,
+    window: RwLock<HotWindow>,
+}
+
+impl HotCache {
+    pub fn new(days: u32) -> Self {
+        Self {
+            days,
+            window: RwLock::new(HotWindow::default()),
+        }
+    }
+
+    /// Builds the cache when `HOT_CACHE_DAYS` is set, otherwise the cache is disabled
+    pub fn from_env() -> Option<Self> {
+        let days = env::var("HOT_CACHE_DAYS").ok()?.parse::<u32>().ok()?;
+        (days > 0).then(|| Self::new(days))
+    }
+
+    /// Replaces the cached window, `rows` must be ordered by timestamp
+    pub fn replace(
+        &self,
+        start: DateTime<Utc>,
+        rows: Vec<(DateTime<Utc>, BigDecimal)>,
+    ) -> Result<(), ArrowError> {
+        let readings = totals_batch(&rows)?;
+        let mut window = self.window.write().expect("hot cache lock poisoned");
+        *window = HotWindow {
+            start: Some(start),
+            readings,
+        };
+        Ok(())
+    }
+
+    /// Number of readings currently held in the window
+    pub fn len(&self) -> usize {
+        self.window
+            .read()
+            .expect("hot cache lock poisoned")
+            .readings
+            .num_rows()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Earliest reading the window holds, `None` before the first refresh
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.window.read().expect("hot cache lock poisoned").start
+    }
+
+    /// Reloads the window ending at the latest stored reading, called after each ingestion.
+    /// A window too large for Arrow's decimals is dropped rather than served stale.
+    pub async fn refresh(&self, pg_pool: &Pool) -> Result<(), PgError> {
+        let days = self.days;
+        let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+        let loaded = conn
+            .interact(move |conn| load_recent_window(i64::from(days), conn))
+            .await
+            .map_err(PgError::InteractionError)?
+            .map_err(PgError::DieselError)?;
+
+        let clear =
+            || *self.window.write().expect("hot cache lock poisoned") = HotWindow::default();
+        match loaded {
+            Some((start, rows)) => {
+                let readings = rows.len();
+                match self.replace(start, rows) {
+                    Ok(()) => info!("Hot cache refreshed with {readings} readings"),
+                    Err(e) => {
+                        error!("Unable to hold the recent window in the hot cache: {e}");
+                        clear();
+                    }
+                }
+            }
+            None => clear(),
+        }
+        Ok(())
+    }
+
+    /// Returns hourly buckets when the requested range is fully covered by the window
+    pub async fn hourly(
+        &self,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+    ) -> Option<Vec<AggregationQueryRecord>> {
+        self.aggregate(
+            Aggregation::Hourly,
+            from_date,
+            to_date,
+            &TotalFilter::default(),
+            Tz::UTC,
+        )
+        .await
+    }
+
+    /// Returns the buckets of `kind` on the calendar of `zone` whose totals pass `having`,
+    /// when the requested range is fully covered by the window. The window is queried as
+    /// an in-memory table with the SQL the offline query mode runs over Parquet archives,
+    /// see [`engine::aggregate`].
+    pub async fn aggregate(
+        &self,
+        kind: Aggregation,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        having: &TotalFilter,
+        zone: Tz,
+    ) -> Option<Vec<AggregationQueryRecord>> {
+        let readings = {
+            let window = self.window.read().expect("hot cache lock poisoned");
+            if from_date? < window.start? {
+                return None;
+            }
+            window.readings.clone()
+        };
+
+        let ctx = engine::session();
+        let aggregated = async {
+            let table = MemTable::try_new(readings.schema(), vec![vec![readings]])?;
+            ctx.register_table(READINGS, Arc::new(table))?;
+            engine::aggregate(&ctx, kind, from_date, to_date, having, zone).await
+        };
+        aggregated
+            .await
+            .inspect_err(|e| warn!("Unable to aggregate the hot cache: {e}"))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Tz};
+
+    use super::HotCache;
+    use crate::model::api_request::{Aggregation, TotalFilter};
+
+    fn populated_cache() -> HotCache {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let rows = (0..8)
+            .map(|i| {
+                (
+                    start + TimeDelta::minutes(30 * i),
+                    BigDecimal::from(10 * (i + 1)),
+                )
+            })
+            .collect();
+        let cache = HotCache::new(1);
+        cache.replace(start, rows).unwrap();
+        cache
+    }
+
+    #[tokio::test]
+    async fn test_hourly_buckets_from_window() {
+        let cache = populated_cache();
+        let from = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2025, 1, 1, 2, 0, 0).unwrap();
+
+        // The reading at 02:00 sits on the exclusive end
+        let records = cache.hourly(Some(from), Some(to)).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].datetime, from);
+        assert_eq!(records[0].total_amount, Some(BigDecimal::from(30)));
+        assert_eq!(records[1].total_amount, Some(BigDecimal::from(70)));
+
+        let open_ended = cache.hourly(Some(from), None).await.unwrap();
+        assert_eq!(open_ended.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_zoned_buckets_from_window() {
+        let cache = populated_cache();
+        let from = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+
+        // The whole window falls on New Year's Eve in New York
+        let days = cache
+            .aggregate(
+                Aggregation::DayInMonth,
+                Some(from),
+                None,
+                &TotalFilter::default(),
+                New_York,
+            )
+            .await
+            .unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(
+            days[0].datetime,
+            Utc.with_ymd_and_hms(2024, 12, 31, 5, 0, 0).unwrap()
+        );
+        assert_eq!(days[0].total_amount, Some(BigDecimal::from(360)));
+
+        let having = TotalFilter {
+            gt: Some(30.0),
+            le: Some(110.0),
+            ..TotalFilter::default()
+        };
+        let hours = cache
+            .aggregate(Aggregation::Hourly, Some(from), None, &having, Tz::UTC)
+            .await
+            .unwrap();
+        let totals: Vec<_> = hours
+            .into_iter()
+            .filter_map(|hour| hour.total_amount)
+            .collect();
+        assert_eq!(totals, [BigDecimal::from(70), BigDecimal::from(110)]);
+    }
+
+    #[tokio::test]
+    async fn test_hourly_declines_uncovered_ranges() {
+        let cache = populated_cache();
+        let before_window = Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap();
+
+        assert!(cache.hourly(None, None).await.is_none());
+        assert!(cache.hourly(Some(before_window), None).await.is_none());
+        assert!(
+            HotCache::new(1)
+                .hourly(Some(before_window), None)
+                .await
+                .is_none()
+        );
+    }
+}
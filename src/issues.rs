@@ -0,0 +1,154 @@
+use bigdecimal::num_bigint::Sign;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    file_reader::RowError,
+    model::{
+        csv::CSVRecord,
+        database::{IngestionIssue, IssueKind},
+    },
+};
+
+/// Problem found in a file of readings, before it is attached to an ingestion
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub line: Option<u64>,
+    pub datetime: Option<DateTime<Utc>>,
+    pub detail: String,
+}
+
+impl Issue {
+    /// Row of the file that could not be decoded and was skipped
+    pub fn parse_failure(error: &RowError) -> Self {
+        Self {
+            kind: IssueKind::ParseFailure,
+            line: error.line(),
+            datetime: None,
+            detail: error.to_string(),
+        }
+    }
+
+    /// Interval converted from register readings that was skipped as out of range
+    pub fn rejected_delta(delta: &CSVRecord, reason: String) -> Self {
+        Self {
+            kind: IssueKind::RejectedDelta,
+            line: None,
+            datetime: Some(delta.datetime),
+            detail: reason,
+        }
+    }
+
+    pub fn into_record(self, ingestion_id: i64) -> IngestionIssue {
+        IngestionIssue {
+            id: 0,
+            ingestion_id,
+            kind: self.kind.as_str().to_string(),
+            line: self
+                .line
+                .map(|line| i64::try_from(line).unwrap_or(i64::MAX)),
+            datetime: self.datetime,
+            detail: self.detail,
+        }
+    }
+}
+
+/// Finds the readings a file holds that are stored but worth a second look: timestamps
+/// held by several readings, only one of which is stored, negative amounts, and
+/// gaps of at least one whole `interval` between consecutive timestamps.
+///
+/// Readings are looked at as received, before any clock drift is snapped away.
+pub fn inspect(readings: &[CSVRecord], interval: TimeDelta) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = readings
+        .iter()
+        .filter(|reading| reading.amount.sign() == Sign::Minus)
+        .map(|reading| Issue {
+            kind: IssueKind::NegativeValue,
+            line: None,
+            datetime: Some(reading.datetime),
+            detail: format!("amount {} is negative", reading.amount),
+        })
+        .collect();
+
+    let mut datetimes: Vec<DateTime<Utc>> = readings.iter().map(|r| r.datetime).collect();
+    datetimes.sort_unstable();
+    for run in datetimes
+        .chunk_by(|a, b| a == b)
+        .filter(|run| run.len() > 1)
+    {
+        issues.push(Issue {
+            kind: IssueKind::DuplicateTimestamp,
+            line: None,
+            datetime: Some(run[0]),
+            detail: format!("{} readings share the timestamp", run.len()),
+        });
+    }
+
+    datetimes.dedup();
+    let interval_secs = interval.num_seconds().max(1);
+    for pair in datetimes.windows(2) {
+        let missing = (pair[1] - pair[0]).num_seconds() / interval_secs - 1;
+        if missing > 0 {
+            issues.push(Issue {
+                kind: IssueKind::Gap,
+                line: None,
+                datetime: Some(pair[0] + interval),
+                detail: format!("{missing} readings missing before {}", pair[1]),
+            });
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.datetime);
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::inspect;
+    use crate::model::{csv::CSVRecord, database::IssueKind};
+
+    fn reading(hour: u32, minute: u32, amount: i32) -> CSVRecord {
+        CSVRecord {
+            datetime: Utc.with_ymd_and_hms(2025, 1, 1, hour, minute, 0).unwrap(),
+            amount: BigDecimal::from(amount),
+            extra: None,
+        }
+    }
+
+    #[test]
+    fn test_inspect_finds_duplicates_negatives_and_gaps() {
+        let readings = vec![
+            reading(0, 0, 1),
+            reading(1, 0, -2),
+            reading(1, 0, 3),
+            reading(4, 0, 4),
+        ];
+        let issues = inspect(&readings, TimeDelta::hours(1));
+
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.kind, issue.datetime.unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (IssueKind::NegativeValue, readings[1].datetime),
+                (IssueKind::DuplicateTimestamp, readings[1].datetime),
+                (IssueKind::Gap, reading(2, 0, 0).datetime),
+            ]
+        );
+        assert_eq!(
+            issues[2].detail,
+            "2 readings missing before 2025-01-01 04:00:00 UTC"
+        );
+    }
+
+    #[test]
+    fn test_inspect_ignores_clock_drift_within_an_interval() {
+        let readings = vec![reading(0, 0, 1), reading(1, 2, 2), reading(1, 58, 3)];
+        assert_eq!(inspect(&readings, TimeDelta::hours(1)), []);
+    }
+}
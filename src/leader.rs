@@ -0,0 +1,172 @@
+use std::{env, sync::Arc, thread, time::Duration};
+
+use chrono::Utc;
+use diesel::{
+    Connection as _, PgConnection, QueryResult, RunQueryDsl as _, dsl::sql, sql_types::Bool,
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{model::api_response::LeaderStatus, read_only::ReadOnlyMode};
+
+/// Interval between attempts to take leadership, and between checks that the connection
+/// holding it is alive, bounding how long the jobs pause after the leader goes away
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Elects the one instance among those sharing the database that runs the background
+/// jobs, partition maintenance, retention and the directory watcher, by holding a
+/// session advisory lock on a dedicated connection.
+///
+/// The lock goes with the session, so when the leader exits or loses its connection
+/// another instance takes over on its next attempt. Read-only instances step down and
+/// do not stand, since they skip the jobs.
+#[derive(Clone)]
+pub struct LeaderElection {
+    status: Arc<watch::Sender<LeaderStatus>>,
+}
+
+impl Default for LeaderElection {
+    fn default() -> Self {
+        let (status, _) = watch::channel(LeaderStatus {
+            leader: false,
+            since: None,
+        });
+        Self {
+            status: Arc::new(status),
+        }
+    }
+}
+
+impl LeaderElection {
+    pub fn status(&self) -> LeaderStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Whether this instance runs the background jobs
+    pub fn is_leader(&self) -> bool {
+        self.status.borrow().leader
+    }
+
+    /// Waits until this instance leads, so a job due while another instance led runs as
+    /// soon as leadership passes here
+    pub async fn leading(&self) {
+        // The sender lives as long as `self`, so waiting cannot fail
+        let _ = self
+            .status
+            .subscribe()
+            .wait_for(|status| status.leader)
+            .await;
+    }
+
+    fn set(&self, leader: bool) {
+        self.status.send_if_modified(|status| {
+            if status.leader == leader {
+                return false;
+            }
+            status.leader = leader;
+            status.since = Some(Utc::now());
+            if leader {
+                info!("Took leadership of the background jobs");
+            } else {
+                warn!("Gave up leadership of the background jobs");
+            }
+            true
+        });
+    }
+
+    /// Stands for election on a dedicated connection for the lifetime of the process
+    pub fn spawn(&self, read_only: ReadOnlyMode) -> Result<(), env::VarError> {
+        let database_url = env::var("DATABASE_URL")?;
+        let election = self.clone();
+        thread::spawn(move || election.campaign(&database_url, &read_only));
+        Ok(())
+    }
+
+    fn campaign(&self, database_url: &str, read_only: &ReadOnlyMode) {
+        loop {
+            match PgConnection::establish(database_url) {
+                Ok(mut conn) => {
+                    if let Err(e) = self.hold(&mut conn, read_only) {
+                        warn!("Leader election connection failed: {e}");
+                    }
+                }
+                Err(e) => warn!("Unable to connect for leader election: {e}"),
+            }
+            // The session, and the lock with it, is gone
+            self.set(false);
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Takes and keeps leadership on `conn` while the instance is writable, returning
+    /// once the connection fails
+    fn hold(&self, conn: &mut PgConnection, read_only: &ReadOnlyMode) -> QueryResult<()> {
+        loop {
+            let leader = self.is_leader();
+            if read_only.is_enabled() {
+                if leader {
+                    resign(conn)?;
+                    self.set(false);
+                }
+            } else if leader {
+                diesel::sql_query("SELECT 1").execute(conn)?;
+            } else {
+                self.set(try_lead(conn)?);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Takes the leader lock unless another session holds it
+fn try_lead(conn: &mut PgConnection) -> QueryResult<bool> {
+    diesel::select(sql::<Bool>(
+        "pg_try_advisory_lock(hashtext('renewable.leader'))",
+    ))
+    .get_result(conn)
+}
+
+fn resign(conn: &mut PgConnection) -> QueryResult<bool> {
+    diesel::select(sql::<Bool>(
+        "pg_advisory_unlock(hashtext('renewable.leader'))",
+    ))
+    .get_result(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, thread, time::Duration};
+
+    use diesel::{Connection as _, PgConnection};
+    use serial_test::serial;
+
+    use super::{resign, try_lead};
+
+    fn get_test_connection() -> PgConnection {
+        dotenvy::dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        PgConnection::establish(&database_url).expect("Failed to connect to database")
+    }
+
+    #[test]
+    #[serial]
+    fn test_one_session_leads_until_it_resigns_or_goes() {
+        let mut first = get_test_connection();
+        let mut second = get_test_connection();
+        assert!(try_lead(&mut first).unwrap());
+        assert!(!try_lead(&mut second).unwrap());
+
+        assert!(resign(&mut first).unwrap());
+        assert!(try_lead(&mut second).unwrap());
+        assert!(!try_lead(&mut first).unwrap());
+
+        // Closing the session releases the lock, once the server notices
+        drop(second);
+        let taken = (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(20));
+            try_lead(&mut first).unwrap()
+        });
+        assert!(taken);
+        assert!(resign(&mut first).unwrap());
+    }
+}
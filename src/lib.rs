@@ -1,9 +1,62 @@
+pub mod auth;
+pub mod bucket;
+pub mod build_info;
+#[cfg(feature = "redis-cache")]
+pub mod cache;
+pub mod calendar;
+pub mod chain;
+pub mod columnar;
+pub mod config;
+pub mod cursor;
 pub mod db;
+pub mod deadline;
+pub mod decimal;
+pub mod delta_block;
+pub mod detect;
+pub mod diff;
+pub mod drift;
+pub mod dsl;
+pub mod encryption;
+pub mod engine;
+pub mod error;
+pub mod export;
 pub mod file_reader;
+pub mod graphql;
+pub mod grpc;
+pub mod health;
+pub mod history;
+pub mod hot_cache;
+pub mod issues;
+pub mod leader;
+pub mod listener;
+pub mod live;
 pub mod logger;
 pub mod model;
+pub mod negotiate;
+pub mod notify;
+pub mod offline;
+pub mod openapi;
+pub mod partitions;
+pub mod power;
+pub mod read_only;
+pub mod register;
+pub mod response_cache;
+pub mod retention;
+pub mod rounding;
 pub mod route;
+pub mod schema_check;
+pub mod selftest;
+pub mod settlement;
+pub mod shadow;
 pub mod shutdown;
+pub mod state;
+pub mod storage_stats;
+pub mod telemetry;
+pub mod unit_check;
+pub mod upload;
+pub mod variance;
+pub mod watcher;
+pub mod watermark;
 
 #[allow(clippy::wildcard_imports)]
 pub mod schema;
@@ -0,0 +1,282 @@
+use std::{
+    env,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use axum::Router;
+use axum_server::{Handle, Server, accept::NoDelayAcceptor};
+use hyper_util::{
+    rt::{TokioExecutor, TokioTimer},
+    server::conn::auto::Builder,
+};
+use listenfd::ListenFd;
+use tokio::net::{TcpListener, UnixListener};
+use tracing::info;
+
+use crate::shutdown::Shutdown;
+
+const DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT_SECS: u64 = 20;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ListenerError {
+    #[error("invalid LISTEN_ADDR {0}")]
+    Address(String),
+
+    #[error("no socket passed via systemd socket activation")]
+    NoActivatedSocket,
+
+    #[error("invalid {0}")]
+    Tuning(&'static str),
+
+    #[error("unable to bind listener {0}")]
+    IoError(std::io::Error),
+}
+
+/// Where the server accepts connections, parsed from the configured `listen_addr`.
+///
+/// Accepts a TCP socket address, `unix:<path>` for a Unix domain socket, or `systemd`
+/// to adopt the first socket passed via `LISTEN_FDS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenerConfig {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+    Systemd,
+}
+
+impl FromStr for ListenerConfig {
+    type Err = ListenerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "systemd" {
+            return Ok(Self::Systemd);
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(ListenerError::Address(s.to_string()));
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        s.parse()
+            .map(Self::Tcp)
+            .map_err(|_| ListenerError::Address(s.to_string()))
+    }
+}
+
+impl ListenerConfig {
+    pub async fn bind(&self) -> Result<BoundListener, ListenerError> {
+        match self {
+            Self::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .map_err(ListenerError::IoError)?;
+                info!("listening on {addr}");
+                Ok(BoundListener::Tcp(listener))
+            }
+            Self::Unix(path) => bind_unix(path).map(BoundListener::Unix),
+            Self::Systemd => adopt_activated(),
+        }
+    }
+}
+
+fn bind_unix(path: &Path) -> Result<UnixListener, ListenerError> {
+    // A socket file left behind by an unclean exit would otherwise fail the bind
+    if path.exists() {
+        std::fs::remove_file(path).map_err(ListenerError::IoError)?;
+    }
+    let listener = UnixListener::bind(path).map_err(ListenerError::IoError)?;
+    info!("listening on unix:{}", path.display());
+    Ok(listener)
+}
+
+fn adopt_activated() -> Result<BoundListener, ListenerError> {
+    let mut fds = ListenFd::from_env();
+
+    if let Ok(Some(listener)) = fds.take_tcp_listener(0) {
+        listener
+            .set_nonblocking(true)
+            .map_err(ListenerError::IoError)?;
+        let listener = TcpListener::from_std(listener).map_err(ListenerError::IoError)?;
+        info!("listening on activated socket {:?}", listener.local_addr());
+        return Ok(BoundListener::Tcp(listener));
+    }
+
+    let listener = fds
+        .take_unix_listener(0)
+        .map_err(ListenerError::IoError)?
+        .ok_or(ListenerError::NoActivatedSocket)?;
+    listener
+        .set_nonblocking(true)
+        .map_err(ListenerError::IoError)?;
+    let listener = UnixListener::from_std(listener).map_err(ListenerError::IoError)?;
+    info!("listening on activated unix socket");
+    Ok(BoundListener::Unix(listener))
+}
+
+fn env_parse<T: FromStr>(name: &'static str) -> Result<Option<T>, ListenerError> {
+    env::var(name)
+        .ok()
+        .map(|value| value.parse().map_err(|_| ListenerError::Tuning(name)))
+        .transpose()
+}
+
+/// Connection level settings applied to the HTTP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerTuning {
+    /// Accept HTTP/2 alongside HTTP/1.1, read from `HTTP2_ENABLED`
+    pub http2: bool,
+    /// Read from `HTTP2_MAX_CONCURRENT_STREAMS`, unlimited by default
+    pub http2_max_concurrent_streams: Option<u32>,
+    /// Interval between HTTP/2 pings on idle connections, read from `HTTP2_KEEP_ALIVE_INTERVAL_SECS`
+    pub http2_keep_alive_interval: Option<Duration>,
+    /// Time to wait for a ping acknowledgement, read from `HTTP2_KEEP_ALIVE_TIMEOUT_SECS`
+    pub http2_keep_alive_timeout: Duration,
+    /// Reuse HTTP/1.1 connections between requests, read from `HTTP1_KEEP_ALIVE`
+    pub http1_keep_alive: bool,
+    /// Time allowed for a client to send request headers, read from `HTTP1_HEADER_READ_TIMEOUT_SECS`
+    pub http1_header_read_timeout: Option<Duration>,
+    /// Disable Nagle's algorithm on accepted TCP connections, read from `TCP_NODELAY`
+    pub tcp_nodelay: bool,
+}
+
+impl Default for ServerTuning {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            http2_max_concurrent_streams: None,
+            http2_keep_alive_interval: None,
+            http2_keep_alive_timeout: Duration::from_secs(DEFAULT_HTTP2_KEEP_ALIVE_TIMEOUT_SECS),
+            http1_keep_alive: true,
+            http1_header_read_timeout: None,
+            tcp_nodelay: true,
+        }
+    }
+}
+
+impl ServerTuning {
+    pub fn from_env() -> Result<Self, ListenerError> {
+        let defaults = Self::default();
+        Ok(Self {
+            http2: env_parse("HTTP2_ENABLED")?.unwrap_or(defaults.http2),
+            http2_max_concurrent_streams: env_parse("HTTP2_MAX_CONCURRENT_STREAMS")?,
+            http2_keep_alive_interval: env_parse("HTTP2_KEEP_ALIVE_INTERVAL_SECS")?
+                .map(Duration::from_secs),
+            http2_keep_alive_timeout: env_parse("HTTP2_KEEP_ALIVE_TIMEOUT_SECS")?
+                .map_or(defaults.http2_keep_alive_timeout, Duration::from_secs),
+            http1_keep_alive: env_parse("HTTP1_KEEP_ALIVE")?.unwrap_or(defaults.http1_keep_alive),
+            http1_header_read_timeout: env_parse("HTTP1_HEADER_READ_TIMEOUT_SECS")?
+                .map(Duration::from_secs),
+            tcp_nodelay: env_parse("TCP_NODELAY")?.unwrap_or(defaults.tcp_nodelay),
+        })
+    }
+
+    fn apply(&self, builder: &mut Builder<TokioExecutor>) {
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .keep_alive(self.http1_keep_alive)
+            .header_read_timeout(self.http1_header_read_timeout);
+        builder
+            .http2()
+            .timer(TokioTimer::new())
+            .max_concurrent_streams(self.http2_max_concurrent_streams)
+            .keep_alive_interval(self.http2_keep_alive_interval)
+            .keep_alive_timeout(self.http2_keep_alive_timeout);
+    }
+
+    fn configure<A: axum_server::Address>(&self, server: Server<A>) -> Server<A> {
+        let mut server = if self.http2 {
+            server
+        } else {
+            server.http1_only()
+        };
+        self.apply(server.http_builder());
+        server
+    }
+}
+
+/// Stops accepting connections once shutdown is requested, giving in-flight requests
+/// the rest of the grace period
+fn shutdown_on_request<A: axum_server::Address + Send + Sync + 'static>(
+    handle: Handle<A>,
+    shutdown: &Shutdown,
+) {
+    let shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown.requested().await;
+        handle.graceful_shutdown(Some(shutdown.remaining()));
+    });
+}
+
+pub enum BoundListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl BoundListener {
+    /// Serves `app` with the given tuning until shutdown is requested
+    pub async fn serve(
+        self,
+        app: Router,
+        tuning: &ServerTuning,
+        shutdown: &Shutdown,
+    ) -> std::io::Result<()> {
+        let service = app.into_make_service();
+        match self {
+            Self::Tcp(listener) => {
+                let handle = Handle::<SocketAddr>::new();
+                shutdown_on_request(handle.clone(), shutdown);
+                let server = tuning.configure(Server::from_listener(listener).handle(handle));
+                if tuning.tcp_nodelay {
+                    server.acceptor(NoDelayAcceptor).serve(service).await
+                } else {
+                    server.serve(service).await
+                }
+            }
+            Self::Unix(listener) => {
+                let handle = Handle::<std::os::unix::net::SocketAddr>::new();
+                shutdown_on_request(handle.clone(), shutdown);
+                tuning
+                    .configure(Server::from_listener(listener).handle(handle))
+                    .serve(service)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, path::PathBuf};
+
+    use super::{ListenerConfig, ServerTuning};
+
+    #[test]
+    fn test_parse_listener_config() {
+        assert_eq!(
+            "0.0.0.0:8000".parse::<ListenerConfig>().unwrap(),
+            ListenerConfig::Tcp(SocketAddr::from(([0, 0, 0, 0], 8000)))
+        );
+        assert_eq!(
+            "unix:/run/renewable/api.sock"
+                .parse::<ListenerConfig>()
+                .unwrap(),
+            ListenerConfig::Unix(PathBuf::from("/run/renewable/api.sock"))
+        );
+        assert_eq!(
+            "systemd".parse::<ListenerConfig>().unwrap(),
+            ListenerConfig::Systemd
+        );
+        assert!("unix:".parse::<ListenerConfig>().is_err());
+        assert!("localhost".parse::<ListenerConfig>().is_err());
+    }
+
+    #[test]
+    fn test_default_server_tuning() {
+        let tuning = ServerTuning::default();
+        assert!(tuning.http2 && tuning.http1_keep_alive && tuning.tcp_nodelay);
+        assert!(tuning.http2_max_concurrent_streams.is_none());
+    }
+}
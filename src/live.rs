@@ -0,0 +1,132 @@
+use axum::extract::ws::{Message, WebSocket};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::model::{api_response::IngestionNotification, csv::CSVRecord};
+
+/// Notifications held for subscribers that fall behind before they are told they lagged
+const LIVE_UPDATE_BUFFER: usize = 256;
+
+/// Fan-out of new ingestions to live subscribers, published to by the ingestion path
+#[derive(Clone)]
+pub struct IngestionEvents {
+    sender: broadcast::Sender<IngestionNotification>,
+}
+
+impl Default for IngestionEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(LIVE_UPDATE_BUFFER);
+        Self { sender }
+    }
+}
+
+impl IngestionEvents {
+    /// Publishes a stored ingestion, a no-op while nobody is subscribed
+    pub fn publish(&self, notification: IngestionNotification) {
+        let _ = self.sender.send(notification);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<IngestionNotification> {
+        self.sender.subscribe()
+    }
+}
+
+/// Timestamps of the first and last of `readings`, `None` when there are none
+pub fn reading_span(readings: &[CSVRecord]) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let first = readings.iter().map(|reading| reading.datetime).min()?;
+    let last = readings.iter().map(|reading| reading.datetime).max()?;
+    Some((first, last))
+}
+
+/// Message pushed to WebSocket subscribers
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveUpdate {
+    Ingestion(IngestionNotification),
+    /// The subscriber fell behind and missed `skipped` notifications, a full refresh is due
+    Lagged {
+        skipped: u64,
+    },
+}
+
+/// Forwards notifications to `socket` until either side goes away
+pub async fn stream_ingestions(mut socket: WebSocket, events: IngestionEvents) {
+    let mut receiver = events.subscribe();
+    loop {
+        let update = tokio::select! {
+            notification = receiver.recv() => match notification {
+                Ok(notification) => LiveUpdate::Ingestion(notification),
+                Err(RecvError::Lagged(skipped)) => LiveUpdate::Lagged { skipped },
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                // Pings are answered by axum, anything else from the client is ignored
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        let text = match serde_json::to_string(&update) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Unable to encode live update: {e}");
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+    debug!("Live update subscriber disconnected");
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone as _, Utc};
+
+    use super::{IngestionEvents, LiveUpdate, reading_span};
+    use crate::model::{api_response::IngestionNotification, csv::CSVRecord};
+
+    #[tokio::test]
+    async fn test_published_ingestions_reach_subscribers() {
+        let events = IngestionEvents::default();
+        let readings: Vec<CSVRecord> = [3, 1, 2]
+            .into_iter()
+            .map(|hour| CSVRecord {
+                datetime: Utc.with_ymd_and_hms(2025, 1, 1, hour, 0, 0).unwrap(),
+                amount: BigDecimal::from(1),
+                extra: None,
+            })
+            .collect();
+        let (first_reading_at, last_reading_at) = reading_span(&readings).unwrap();
+        let notification = IngestionNotification {
+            ingestion_id: 7,
+            source: "feed".to_string(),
+            rows: readings.len(),
+            updated_rows: 0,
+            skipped_rows: 0,
+            first_reading_at,
+            last_reading_at,
+            unit_mismatch: None,
+        };
+
+        // Nobody is listening yet, publishing must not fail
+        events.publish(notification.clone());
+        let mut receiver = events.subscribe();
+        events.publish(notification);
+
+        let received = receiver.recv().await.unwrap();
+        assert_eq!(received.ingestion_id, 7);
+        assert_eq!(received.first_reading_at, readings[1].datetime);
+        assert_eq!(received.last_reading_at, readings[0].datetime);
+
+        let json = serde_json::to_value(LiveUpdate::Ingestion(received)).unwrap();
+        assert_eq!(json["type"], "ingestion");
+        assert_eq!(json["rows"], 3);
+        let json = serde_json::to_value(LiveUpdate::Lagged { skipped: 4 }).unwrap();
+        assert_eq!(json["type"], "lagged");
+    }
+}
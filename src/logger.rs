@@ -1,12 +1,90 @@
-use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+use std::{env, io, str::FromStr};
+
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt as _,
+    util::SubscriberInitExt as _,
+};
+
+/// Output format for log lines, read from `LOG_FORMAT`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LogFormat {
+    /// Human readable single line output, the default
+    #[default]
+    Full,
+    /// Newline delimited JSON including span fields, for log shippers
+    Json,
+    /// Multi-line output intended for local development
+    Pretty,
+    /// Abbreviated single line output
+    Compact,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            "compact" => Ok(Self::Compact),
+            other => Err(format!("unknown log format {other}")),
+        }
+    }
+}
 
 /// Enables the tracing crate for all logging and tracing functionality
 pub fn init_logging() {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("{}=debug", env!("CARGO_CRATE_NAME")).into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    init_logging_to(io::stdout);
+}
+
+/// As [`init_logging`], writing log lines to `writer` instead of stdout
+pub fn init_logging_to<W>(writer: W)
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let format = match env::var("LOG_FORMAT") {
+        Ok(format) => format.parse().unwrap_or_else(|e| {
+            eprintln!("{e}, falling back to the default log format");
+            LogFormat::default()
+        }),
+        Err(_) => LogFormat::default(),
+    };
+
+    let registry = tracing_subscriber::registry().with(
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| format!("{}=debug", env!("CARGO_CRATE_NAME")).into()),
+    );
+    match format {
+        LogFormat::Full => registry.with(fmt::layer().with_writer(writer)).init(),
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .with_writer(writer)
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .init(),
+        LogFormat::Pretty => registry
+            .with(fmt::layer().with_writer(writer).pretty())
+            .init(),
+        LogFormat::Compact => registry
+            .with(fmt::layer().with_writer(writer).compact())
+            .init(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogFormat;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("Pretty".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert_eq!("compact".parse::<LogFormat>().unwrap(), LogFormat::Compact);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
 }
@@ -1,4 +1,6 @@
-use chrono::{DateTime, Utc};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use chrono_tz::Tz;
 use diesel::{
     AsExpression,
     deserialize::{FromSql, FromSqlRow},
@@ -7,13 +9,30 @@ use diesel::{
 };
 use serde::{Deserialize, Serialize};
 use std::io::Write;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, PartialEq, Eq, FromSqlRow, AsExpression, Deserialize, Serialize, Clone, Copy)]
+use crate::{model::database::QueryStatus, settlement};
+
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    FromSqlRow,
+    AsExpression,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    ToSchema,
+)]
 #[diesel(sql_type = crate::renewable_schema::sql_types::AggregationKind)]
 pub enum Aggregation {
     Hourly,
     DayInMonth,
+    Weekly,
     Monthly,
+    Quarterly,
     Yearly,
 }
 
@@ -22,7 +41,9 @@ impl FromSql<crate::renewable_schema::sql_types::AggregationKind, Pg> for Aggreg
         match bytes.as_bytes() {
             b"Hourly" => Ok(Self::Hourly),
             b"DayInMonth" => Ok(Self::DayInMonth),
+            b"Weekly" => Ok(Self::Weekly),
             b"Monthly" => Ok(Self::Monthly),
+            b"Quarterly" => Ok(Self::Quarterly),
             b"Yearly" => Ok(Self::Yearly),
             _ => Err("Unrecognized enum variant".into()),
         }
@@ -34,7 +55,9 @@ impl ToSql<crate::renewable_schema::sql_types::AggregationKind, Pg> for Aggregat
         let s = match self {
             Self::Hourly => "Hourly",
             Self::DayInMonth => "DayInMonth",
+            Self::Weekly => "Weekly",
             Self::Monthly => "Monthly",
+            Self::Quarterly => "Quarterly",
             Self::Yearly => "Yearly",
         };
         out.write_all(s.as_bytes())?;
@@ -47,20 +70,679 @@ impl From<Aggregation> for &str {
         match kind {
             Aggregation::Hourly => "hour",
             Aggregation::DayInMonth => "day",
+            Aggregation::Weekly => "week",
             Aggregation::Monthly => "month",
+            Aggregation::Quarterly => "quarter",
             Aggregation::Yearly => "year",
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl Aggregation {
+    /// Aggregation of a request without an `aggregation_kind`, the finest keeping a range
+    /// spanning `span` to at most about 120 buckets, monthly for ranges without a start
+    pub fn for_span(span: Option<TimeDelta>) -> Self {
+        let Some(span) = span else {
+            return Self::Monthly;
+        };
+        match span.num_days() {
+            ..=4 => Self::Hourly,
+            5..=92 => Self::DayInMonth,
+            93..=731 => Self::Weekly,
+            732..=3653 => Self::Monthly,
+            _ => Self::Yearly,
+        }
+    }
+
+    /// Most buckets a range spanning `span` touches, months, quarters and years taken at
+    /// their shortest so the estimate errs high
+    pub fn estimated_buckets(self, span: TimeDelta) -> i64 {
+        let width = match self {
+            Self::Hourly => TimeDelta::hours(1),
+            Self::DayInMonth => TimeDelta::days(1),
+            Self::Weekly => TimeDelta::weeks(1),
+            Self::Monthly => TimeDelta::days(28),
+            Self::Quarterly => TimeDelta::days(89),
+            Self::Yearly => TimeDelta::days(365),
+        };
+        if span <= TimeDelta::zero() {
+            return 0;
+        }
+        // A range not aligned to the buckets reaches into one more
+        (span.num_seconds() + width.num_seconds() - 1) / width.num_seconds() + 1
+    }
+}
+
+/// Whether a reading stamped exactly at `to_date` falls inside the range
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RangeEnd {
+    /// `[from_date, to_date)`, so back to back ranges never count a boundary reading twice
+    #[default]
+    Exclusive,
+    /// `[from_date, to_date]`
+    Inclusive,
+}
+
+impl RangeEnd {
+    /// Exclusive upper bound every query filters on. Postgres stores timestamps to the
+    /// microsecond, so an inclusive end becomes the next representable instant.
+    pub fn exclusive_end(self, to_date: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Exclusive => to_date,
+            Self::Inclusive => to_date.map(|to_date| to_date + TimeDelta::microseconds(1)),
+        }
+    }
+}
+
+/// Half hour of a GB settlement day, numbered from 1 at midnight UK local time
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, ToSchema)]
+pub struct SettlementPeriod {
+    pub settlement_date: NaiveDate,
+    /// 1 to 48, or to 46 and 50 on the days the clocks change
+    pub settlement_period: u32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TimeSeriesRange {
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to_bound: RangeEnd,
+    /// Start of this settlement period, in place of `from_date`
+    #[serde(default)]
+    pub from_settlement: Option<SettlementPeriod>,
+    /// End of this settlement period, in place of `to_date`, so the period is included
+    #[serde(default)]
+    pub to_settlement: Option<SettlementPeriod>,
+}
+
+impl TimeSeriesRange {
+    /// `(from_date, end)` with `end` exclusive whatever bound was requested, settlement
+    /// periods resolved to the instants they start and end
+    pub fn half_open(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let from_date = match &self.from_settlement {
+            Some(period) => settlement::period_start(period),
+            None => self.from_date,
+        };
+        let to_date = match &self.to_settlement {
+            Some(period) => settlement::period_end(period),
+            None => self.to_bound.exclusive_end(self.to_date),
+        };
+        (from_date, to_date)
+    }
+}
+
+/// Dimension an aggregation can be broken down by within each bucket
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// Fuel type of the series the readings belong to
+    FuelType,
+}
+
+/// Unit energy totals are returned in, converted from the kWh they are stored in
+#[derive(Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy, ToSchema)]
+pub enum AmountUnit {
+    #[default]
+    #[serde(rename = "kWh")]
+    KWh,
+    MWh,
+    GWh,
+    /// Joules
+    J,
+}
+
+impl AmountUnit {
+    /// Converts an amount in kWh into this unit
+    pub fn from_kwh(self, amount: BigDecimal) -> BigDecimal {
+        match self {
+            Self::KWh => amount,
+            Self::MWh => amount / 1_000,
+            Self::GWh => amount / 1_000_000,
+            Self::J => amount * 3_600_000,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// How buckets without any readings are represented in aggregation responses
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Clone, Copy, ToSchema)]
+pub enum FillMissing {
+    Null,
+    Zero,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TimeSeriesAggregationRequest {
+    /// Picked from the length of the range when omitted, see [`Aggregation::for_span`]
+    #[serde(default)]
+    pub aggregation_kind: Option<Aggregation>,
+    /// The last `default_query_days` days when omitted, `{}` for every reading
+    #[serde(default)]
+    pub datetime_filter: Option<TimeSeriesRange>,
+    #[serde(default)]
+    pub fill_missing: Option<FillMissing>,
+    #[serde(default)]
+    pub include_lineage: bool,
+    /// Report how many readings each bucket holds against the native reading interval
+    #[serde(default)]
+    pub include_completeness: bool,
+    /// Derive average and peak power per bucket alongside the energy totals, from the
+    /// same pass over the readings
+    #[serde(default)]
+    pub include_power: bool,
+    /// Label each bucket with the GB settlement date and period it starts in
+    #[serde(default)]
+    pub include_settlement: bool,
+    /// Name the sources each bucket's total was taken from, those of the highest priority
+    /// ingestion of a series holding each timestamp
+    #[serde(default)]
+    pub include_sources: bool,
+    /// Only return buckets whose total passes these bounds, screened by the database
+    #[serde(default)]
+    pub having: Option<TotalFilter>,
+    /// Also return each bucket's total split by these dimensions, e.g. the generation mix
+    /// by fuel type
+    #[serde(default)]
+    pub group_by: Vec<GroupBy>,
+    /// Unit of the returned totals, `having` bounds are still compared against kWh
+    #[serde(default)]
+    pub unit: AmountUnit,
+    /// IANA timezone whose local calendar buckets follow, e.g. `Europe/London` for daily
+    /// totals from local midnight. JSON and streamed bucket starts carry the zone's offset,
+    /// CSV and Arrow stay in UTC. Defaults to UTC.
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "Europe/London")]
+    pub timezone: Option<Tz>,
+    /// Only consider readings of this series
+    #[serde(default)]
+    pub series_id: Option<i64>,
+    /// Only consider readings of the series with this name, instead of `series_id`
+    #[serde(default)]
+    pub series_name: Option<String>,
+    /// Only consider readings recorded at or before this instant, each at the revision
+    /// current then, also accepted as `as_of`
+    #[serde(default, alias = "as_of")]
+    pub as_recorded_by: Option<DateTime<Utc>>,
+}
+
+impl TimeSeriesAggregationRequest {
+    /// Aggregation and half-open range to answer with, an omitted range starting at
+    /// `default_start` and open ended, and an omitted aggregation picked from the span of
+    /// the range up to `now`
+    pub fn resolve(
+        &self,
+        default_start: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> (Aggregation, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let (from_date, to_date) = match &self.datetime_filter {
+            Some(range) => range.half_open(),
+            None => (Some(default_start), None),
+        };
+        let aggregation_kind = self.aggregation_kind.unwrap_or_else(|| {
+            Aggregation::for_span(from_date.map(|from| to_date.unwrap_or(now) - from))
+        });
+        (aggregation_kind, from_date, to_date)
+    }
+}
+
+/// Bounds on a bucket's total amount, every bound given must hold
+#[derive(Debug, Default, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TotalFilter {
+    /// Total strictly above
+    pub gt: Option<f64>,
+    /// Total at or above
+    pub ge: Option<f64>,
+    /// Total strictly below
+    pub lt: Option<f64>,
+    /// Total at or below
+    pub le: Option<f64>,
+}
+
+impl TotalFilter {
+    /// The `gt`, `ge`, `lt` and `le` bounds as exact decimals, using the shortest
+    /// representation of each so `0.1` is not widened to its binary expansion
+    pub fn bounds(&self) -> [Option<BigDecimal>; 4] {
+        [self.gt, self.ge, self.lt, self.le]
+            .map(|bound| bound.and_then(|value| value.to_string().parse().ok()))
+    }
+
+    /// Whether `total` passes every bound, as the database screens bucket totals
+    pub fn admits(&self, total: &BigDecimal) -> bool {
+        let [gt, ge, lt, le] = self.bounds();
+        gt.is_none_or(|bound| *total > bound)
+            && ge.is_none_or(|bound| *total >= bound)
+            && lt.is_none_or(|bound| *total < bound)
+            && le.is_none_or(|bound| *total <= bound)
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportDownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Selects the series written by the Parquet export, raw readings when no aggregation is given
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ParquetExportParams {
+    #[param(inline)]
+    pub aggregation_kind: Option<Aggregation>,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[param(inline)]
+    pub to_bound: RangeEnd,
+}
+
+/// Average and peak power per bucket, derived from the energy readings
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PowerQueryRequest {
+    pub aggregation_kind: Aggregation,
+    pub datetime_filter: TimeSeriesRange,
+    /// Only consider readings recorded at or before this instant, each at the revision
+    /// current then
+    #[serde(default)]
+    pub as_recorded_by: Option<DateTime<Utc>>,
+}
+
+/// Aggregates several ranges in one query, such as the same month across several years,
+/// for seasonal comparisons
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MultiRangeQueryRequest {
+    pub aggregation_kind: Aggregation,
+    pub ranges: Vec<TimeSeriesRange>,
+    /// Only return buckets whose total passes these bounds, screened by the database
+    #[serde(default)]
+    pub having: Option<TotalFilter>,
+    /// IANA timezone whose local calendar buckets follow, bucket starts carry its offset.
+    /// Defaults to UTC.
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "Europe/London")]
+    pub timezone: Option<Tz>,
+    /// Only consider readings of this series
+    #[serde(default)]
+    pub series_id: Option<i64>,
+    /// Only consider readings recorded at or before this instant, each at the revision
+    /// current then
+    #[serde(default)]
+    pub as_recorded_by: Option<DateTime<Utc>>,
+}
+
+/// Compares an aggregation as recorded by two points in time
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SnapshotDiffRequest {
     pub aggregation_kind: Aggregation,
     pub datetime_filter: TimeSeriesRange,
+    pub baseline_recorded_by: DateTime<Utc>,
+    /// Defaults to the time of the request
+    #[serde(default)]
+    pub compare_recorded_by: Option<DateTime<Utc>>,
+}
+
+/// A meter to onboard along with the series created for it
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MeterOnboarding {
+    pub meter_code: String,
+    pub site_name: String,
+    #[serde(default)]
+    pub capacity_kw: Option<f64>,
+    /// Channels to create as series of the meter, named `<meter_code>/<channel>` and
+    /// measured in kWh
+    #[serde(default)]
+    pub series: Vec<String>,
+}
+
+impl MeterOnboarding {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.meter_code.trim().is_empty() {
+            return Err("meter_code must not be empty".to_string());
+        }
+        if self.site_name.trim().is_empty() {
+            return Err("site_name must not be empty".to_string());
+        }
+        if let Some(capacity) = self.capacity_kw
+            && !(capacity.is_finite() && capacity >= 0.0)
+        {
+            return Err("capacity_kw must be a non-negative number".to_string());
+        }
+        if self.series.iter().any(|name| name.trim().is_empty()) {
+            return Err("series names must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Expected generation for a calendar month, P90 being the conservative estimate
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProfileMonth {
+    pub month: u32,
+    pub p50_kwh: f64,
+    pub p90_kwh: f64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MeterProfileUpload {
+    pub months: Vec<ProfileMonth>,
+}
+
+impl MeterProfileUpload {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = [false; 12];
+        for entry in &self.months {
+            if !(1..=12).contains(&entry.month) {
+                return Err(format!("month {} must be between 1 and 12", entry.month));
+            }
+            let slot = &mut seen[(entry.month - 1) as usize];
+            if *slot {
+                return Err(format!("month {} is listed more than once", entry.month));
+            }
+            *slot = true;
+
+            let valid = |value: f64| value.is_finite() && value >= 0.0;
+            if !(valid(entry.p50_kwh) && valid(entry.p90_kwh)) {
+                return Err(format!(
+                    "month {} must have non-negative P50 and P90",
+                    entry.month
+                ));
+            }
+            if entry.p90_kwh > entry.p50_kwh {
+                return Err(format!("month {} has a P90 above its P50", entry.month));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VarianceParams {
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[param(inline)]
+    pub to_bound: RangeEnd,
+}
+
+/// Switches the instance in or out of read-only mode, e.g. around a database failover
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReadOnlyToggle {
+    pub read_only: bool,
+    /// Reported back by the admin API and `/readyz`
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Sets the public key the exports of an API key are encrypted to, or clears it
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExportRecipientUpdate {
+    /// An `age1...` recipient or an ASCII armored PGP public key block
+    #[schema(example = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p")]
+    pub recipient: Option<String>,
+}
+
+/// Places an ingestion under legal hold
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LegalHoldRequest {
+    /// Why the readings are held, e.g. the dispute reference
+    #[schema(example = "Dispute 2025-014 with the grid operator")]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogParams {
+    /// Only the entries of this ingestion
+    pub ingestion_id: Option<i64>,
+    /// Entries returned, newest first, defaults to 100 and at most 1000
+    pub limit: Option<i64>,
+}
+
+impl AuditLogParams {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(100).clamp(1, 1000)
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StorageParams {
+    /// Days of daily samples growth is measured over, defaults to 30 and at most 365
+    pub days: Option<u32>,
+}
+
+impl StorageParams {
+    pub fn days(&self) -> u32 {
+        self.days.unwrap_or(30).clamp(1, 365)
+    }
+}
+
+/// Aggregation a saved view holds, answered as `POST /timeseries/v1/query` answers it
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SavedViewDefinition {
+    pub aggregation_kind: Aggregation,
+    /// Every reading when omitted, open ended ranges follow new readings
+    #[serde(default)]
+    pub datetime_filter: Option<TimeSeriesRange>,
+    #[serde(default)]
+    pub series_id: Option<i64>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "Europe/London")]
+    pub timezone: Option<Tz>,
+    #[serde(default)]
+    pub having: Option<TotalFilter>,
+    /// Store the rows as a materialized view, computed when saved rather than when read
+    #[serde(default)]
+    pub materialized: bool,
+}
+
+impl SavedViewDefinition {
+    /// Longest view name, so that the prefixed relation name fits Postgres identifiers
+    pub const MAX_NAME_LEN: usize = 48;
+
+    /// Whether `name` can be used as is in the view's relation name: lowercase ASCII
+    /// letters, digits and underscores starting with a letter
+    pub fn valid_name(name: &str) -> bool {
+        name.len() <= Self::MAX_NAME_LEN
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryPageParams {
+    /// `x-next-cursor` of the previous page, the newest queries when unset
+    pub cursor: Option<String>,
+}
+
+/// Narrows the query history to the entries matching every filter set
+#[derive(Debug, Default, Clone, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryFilter {
+    pub aggregation: Option<Aggregation>,
+    /// Queries executed at or after this instant
+    pub executed_from: Option<DateTime<Utc>>,
+    /// Queries executed before this instant
+    pub executed_to: Option<DateTime<Utc>>,
+    pub status: Option<QueryStatus>,
+}
+
+impl HistoryFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.executed_from, self.executed_to) {
+            (Some(from), Some(to)) if from >= to => {
+                Err("executed_from must be before executed_to".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DetectFormatParams {
+    /// Data rows inspected from the start of the file, defaults to 100
+    pub sample_rows: Option<usize>,
+}
+
+/// What an upload does when its source was already ingested
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionMode {
+    /// Refuse the upload with a 409
+    #[default]
+    Error,
+    /// Add the readings at timestamps the earlier ingestion does not hold, keeping the rest
+    Skip,
+    /// Add new readings and replace those at timestamps already held
+    Overwrite,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IngestionUploadParams {
+    /// Name the upload is recorded under, each source is only ingested once
+    pub source: String,
+    /// Series the readings belong to
+    pub series_id: Option<i64>,
+    /// Merges an upload of a source already ingested into its ingestion, e.g. a
+    /// corrected file, rather than refusing it
+    #[serde(default)]
+    #[param(inline)]
+    pub mode: IngestionMode,
+}
+
+/// File a pre-signed upload URL is asked for
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UploadUrlRequest {
+    /// Name of the file, whose extensions give its format and compression as they do for
+    /// the seed file
+    #[schema(example = "readings-2025.csv.zst")]
+    pub file_name: String,
+}
+
+/// Tells the service a file finished uploading through its pre-signed URL, to be
+/// ingested from the bucket
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UploadedFile {
+    /// `location` returned with the upload URL, also recorded as the ingestion source
+    #[schema(example = "s3://meters/uploads/7/1748772000000000-readings-2025.csv.zst")]
+    pub location: String,
+    /// Series the readings belong to
+    pub series_id: Option<i64>,
+    /// Merges the file into the ingestion of an earlier file at the same location rather
+    /// than refusing it
+    #[serde(default)]
+    pub mode: IngestionMode,
+}
+
+/// Series created by `POST` or replaced by `PUT`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SeriesDefinition {
+    pub name: String,
+    /// Defaults to kWh
+    #[serde(default = "default_series_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub fuel_type: Option<String>,
+    #[serde(default)]
+    pub site: Option<String>,
+}
+
+fn default_series_unit() -> String {
+    "kWh".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::TimeDelta;
+    use test_case::test_case;
+
+    use super::{Aggregation, AmountUnit, SavedViewDefinition, TimeSeriesAggregationRequest};
+
+    #[test_case("\"kWh\"", "1500.5", "1500.5")]
+    #[test_case("\"MWh\"", "1500.5", "1.5005")]
+    #[test_case("\"GWh\"", "1500.5", "0.0015005")]
+    #[test_case("\"J\"", "0.5", "1800000")]
+    fn test_amounts_convert_from_kwh(unit: &str, kwh: &str, expected: &str) {
+        let unit: AmountUnit = serde_json::from_str(unit).unwrap();
+        assert_eq!(
+            unit.from_kwh(kwh.parse().unwrap()),
+            expected.parse::<BigDecimal>().unwrap()
+        );
+    }
+
+    #[test_case(Aggregation::Hourly, 1, 25)]
+    #[test_case(Aggregation::DayInMonth, 0, 0)]
+    #[test_case(Aggregation::Weekly, 365, 54)]
+    #[test_case(Aggregation::Monthly, 365, 15)]
+    #[test_case(Aggregation::Yearly, 3653, 12)]
+    fn test_bucket_estimate_errs_high(kind: Aggregation, days: i64, buckets: i64) {
+        assert_eq!(kind.estimated_buckets(TimeDelta::days(days)), buckets);
+    }
+
+    #[test_case("daily_site_a", true)]
+    #[test_case("a1", true)]
+    #[test_case("", false)]
+    #[test_case("1st", false)]
+    #[test_case("Daily", false)]
+    #[test_case("daily; drop", false)]
+    #[test_case(&"a".repeat(49), false)]
+    fn test_view_names_are_plain_identifiers(name: &str, valid: bool) {
+        assert_eq!(SavedViewDefinition::valid_name(name), valid);
+    }
+
+    #[test]
+    fn test_omitted_range_and_aggregation_are_defaulted() {
+        let now = "2025-03-31T10:45:00Z".parse().unwrap();
+        let default_start = "2025-03-01T10:00:00Z".parse().unwrap();
+        let resolve = |json: &str| {
+            serde_json::from_str::<TimeSeriesAggregationRequest>(json)
+                .unwrap()
+                .resolve(default_start, now)
+        };
+
+        assert_eq!(
+            resolve("{}"),
+            (Aggregation::DayInMonth, Some(default_start), None)
+        );
+        assert_eq!(
+            resolve(r#"{"aggregation_kind": "Hourly"}"#).0,
+            Aggregation::Hourly
+        );
+        // An empty filter still asks for every reading
+        assert_eq!(
+            resolve(r#"{"datetime_filter": {}}"#),
+            (Aggregation::Monthly, None, None)
+        );
+        let from = |from_date: &str| {
+            resolve(&format!(r#"{{"datetime_filter": {{"from_date": "{from_date}", "to_date": "2025-03-31T00:00:00Z"}}}}"#)).0
+        };
+        assert_eq!(from("2025-03-29T00:00:00Z"), Aggregation::Hourly);
+        assert_eq!(from("2024-12-31T00:00:00Z"), Aggregation::DayInMonth);
+        assert_eq!(from("2024-01-01T00:00:00Z"), Aggregation::Weekly);
+        assert_eq!(from("2020-01-01T00:00:00Z"), Aggregation::Monthly);
+        assert_eq!(from("2000-01-01T00:00:00Z"), Aggregation::Yearly);
+    }
+
+    #[test]
+    fn test_as_of_is_another_name_for_as_recorded_by() {
+        let parse = |json: &str| serde_json::from_str::<TimeSeriesAggregationRequest>(json);
+        let request = parse(r#"{"as_of": "2026-01-31T00:00:00Z"}"#).unwrap();
+        assert_eq!(
+            request.as_recorded_by,
+            Some("2026-01-31T00:00:00Z".parse().unwrap())
+        );
+        assert!(
+            parse(r#"{"as_of": "2026-01-31T00:00:00Z", "as_recorded_by": "2026-01-31T00:00:00Z"}"#)
+                .is_err()
+        );
+    }
 }
@@ -1,16 +1,624 @@
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Debug, diesel::Queryable, Serialize)]
+use crate::{
+    model::{
+        api_request::AmountUnit,
+        database::{IngestionStatus, JobStatus},
+    },
+    unit_check::UnitMismatch,
+};
+
+#[derive(Debug, Clone, diesel::Queryable, diesel::QueryableByName, Serialize, ToSchema)]
 pub struct AggregationQueryRecord {
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
     pub datetime: DateTime<Utc>,
-    #[serde(serialize_with = "super::serialize_opt_bigdecimal")]
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Numeric>)]
+    pub total_amount: Option<BigDecimal>,
+}
+
+impl AggregationQueryRecord {
+    /// The record with its total converted from kWh into `unit`
+    pub fn in_unit(self, unit: AmountUnit) -> Self {
+        Self {
+            total_amount: self.total_amount.map(|total| unit.from_kwh(total)),
+            ..self
+        }
+    }
+
+    /// The record with its bucket start given at the offset `timezone` has at that instant
+    pub fn in_zone(self, timezone: Tz) -> ZonedAggregationRecord {
+        ZonedAggregationRecord {
+            datetime: self.datetime.with_timezone(&timezone).fixed_offset(),
+            total_amount: self.total_amount,
+        }
+    }
+}
+
+/// [`AggregationQueryRecord`] as returned to clients, with the bucket start in the
+/// requested timezone, `Z` for UTC
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct ZonedAggregationRecord {
+    pub datetime: DateTime<FixedOffset>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
     pub total_amount: Option<BigDecimal>,
 }
 
-#[derive(Debug, Serialize)]
+/// Ingestion that contributed readings to an aggregation result
+#[derive(Debug, Clone, diesel::QueryableByName, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::ts_metadata)]
+pub struct IngestionLineage {
+    pub ingestion_id: i64,
+    pub source: String,
+    pub ingestion_datetime: DateTime<Utc>,
+}
+
+/// Readings held by a bucket against the number its span should hold at the native
+/// reading interval, so partially covered buckets are not mistaken for low generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BucketCompleteness {
+    pub datetime: DateTime<Utc>,
+    pub expected_points: i64,
+    /// Distinct reading timestamps within the bucket
+    pub actual_points: i64,
+    pub complete: bool,
+}
+
+/// GB settlement date and period a bucket starts in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BucketSettlement {
+    pub datetime: DateTime<Utc>,
+    pub settlement_date: NaiveDate,
+    pub settlement_period: u32,
+}
+
+/// Power in kW over a bucket, derived from its kWh readings at the native reading interval
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct BucketPower {
+    pub datetime: DateTime<Utc>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub energy_kwh: Option<BigDecimal>,
+    /// Mean power over the intervals holding a reading
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub average_kw: Option<BigDecimal>,
+    /// Mean power over the bucket's highest interval
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub max_kw: Option<BigDecimal>,
+    pub readings: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PowerResponse {
+    pub executed_at: DateTime<Utc>,
+    pub interval_minutes: i64,
+    pub buckets: Vec<BucketPower>,
+}
+
+/// Buckets of one range of a multi-range query
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RangeRecords {
+    /// Position of the range within the request
+    pub range_index: usize,
+    pub from_date: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub to_date: Option<DateTime<Utc>>,
+    pub records: Vec<ZonedAggregationRecord>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MultiRangeResponse {
+    pub executed_at: DateTime<Utc>,
+    /// One entry per requested range, in request order
+    pub ranges: Vec<RangeRecords>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct QueryResponse {
     pub executed_at: DateTime<Utc>,
-    pub records: Vec<AggregationQueryRecord>,
+    /// Unit of every `total_amount`, power stays in kW
+    pub unit: AmountUnit,
+    pub records: Vec<ZonedAggregationRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Vec<IngestionLineage>>,
+    /// One entry per record, in the same order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completeness: Option<Vec<BucketCompleteness>>,
+    /// One entry per record, in the same order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power: Option<Vec<BucketPower>>,
+    /// One entry per record, in the same order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<Vec<BucketSettlement>>,
+    /// One entry per bucket and fuel type, ordered by bucket
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<Vec<FuelTypeRecord>>,
+    /// One entry per bucket and winning source, ordered by bucket
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<BucketSource>>,
+}
+
+/// Share of a bucket's total generated by one fuel type
+#[derive(Debug, Clone, PartialEq, diesel::QueryableByName, Serialize, ToSchema)]
+pub struct FuelTypeRecord {
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    pub datetime: DateTime<Utc>,
+    /// `null` for readings outside any series or of a series without a fuel type
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub fuel_type: Option<String>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Numeric>)]
+    pub total_amount: Option<BigDecimal>,
+}
+
+/// A source whose readings went into a bucket's total, having the highest priority among
+/// the ingestions of its series holding each of those timestamps
+#[derive(Debug, Clone, PartialEq, Eq, diesel::QueryableByName, Serialize, ToSchema)]
+pub struct BucketSource {
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    pub datetime: DateTime<Utc>,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub source: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub priority: i32,
+    /// Readings of the bucket taken from this source
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub readings: i64,
+}
+
+impl FuelTypeRecord {
+    /// As [`AggregationQueryRecord::in_unit`]
+    pub fn in_unit(self, unit: AmountUnit) -> Self {
+        Self {
+            total_amount: self.total_amount.map(|total| unit.from_kwh(total)),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportJobResponse {
+    pub id: i64,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub download_url: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryJobResponse {
+    pub id: i64,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    /// Body `POST /timeseries/v1/query` answers the aggregation with as JSON, once complete
+    #[schema(value_type = Option<QueryResponse>)]
+    pub result: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeletedIngestion {
+    pub ingestion_id: i64,
+    pub deleted_metadata: usize,
+    pub deleted_readings: usize,
+}
+
+#[derive(Debug, diesel::Queryable, Serialize, ToSchema)]
+pub struct IngestionSummary {
+    pub ingestion_id: i64,
+    pub source: String,
+    pub ingestion_datetime: DateTime<Utc>,
+    /// Readings stored by the ingestion, `None` until it completes
+    pub row_count: Option<i64>,
+    pub min_datetime: Option<DateTime<Utc>>,
+    pub max_datetime: Option<DateTime<Utc>>,
+    /// Rows of the source skipped as invalid
+    pub error_count: i64,
+    /// Time taken to store the readings
+    pub duration_ms: Option<i64>,
+    pub status: IngestionStatus,
+    /// Median daily mean reading over that of the series' earlier ingestions, set when
+    /// they are about a power of 1000 apart, suggesting the amounts are in another unit
+    pub unit_mismatch: Option<f64>,
+    /// Hex SHA-256 of the file the readings were read from
+    pub checksum: Option<String>,
+}
+
+/// Bucket whose total differs between two as-of evaluations
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BucketChange {
+    pub datetime: DateTime<Utc>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub baseline_amount: Option<BigDecimal>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub compare_amount: Option<BigDecimal>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub difference: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SnapshotDiffResponse {
+    pub executed_at: DateTime<Utc>,
+    pub baseline_recorded_by: DateTime<Utc>,
+    pub compare_recorded_by: DateTime<Utc>,
+    pub changes: Vec<BucketChange>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolHealth {
+    pub size: usize,
+    pub available: usize,
+    pub max_size: usize,
+    pub waiting: usize,
+    pub saturation: f64,
+    pub score: f64,
+    /// Connections checked out since startup
+    pub checkouts: u64,
+    /// Average and longest time a checkout waited for a connection since startup
+    pub average_wait_ms: f64,
+    pub longest_wait_ms: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReplicationHealth {
+    /// Seconds since the pool answering aggregations last replayed a transaction, `None`
+    /// when it is a primary
+    pub lag_seconds: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheHealth {
+    pub enabled: bool,
+    pub readings: usize,
+    pub score: f64,
+}
+
+/// Query history entries awaiting their batched insert, informational only
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryHealth {
+    pub queued: usize,
+    /// Entries dropped since startup because the buffer was full
+    pub dropped: u64,
+}
+
+/// Aggregation results cached in process, informational only
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ResponseCacheHealth {
+    pub enabled: bool,
+    pub entries: usize,
+    pub capacity: usize,
+    /// Queries answered from the cache since startup
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Outcome of the retention job, informational only
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct RetentionHealth {
+    pub enabled: bool,
+    /// Rows due to go are only counted
+    pub dry_run: bool,
+    pub last_run: Option<DateTime<Utc>>,
+    /// Rows from before this instant were due to go on the last run
+    pub last_cutoff: Option<DateTime<Utc>>,
+    /// Readings purged on the last run, or due to be in a dry run
+    pub last_readings: u64,
+    pub last_query_history: u64,
+    /// Expired readings kept on the last run for ingestions under legal hold
+    pub last_held: u64,
+    /// Readings purged since startup
+    pub readings_purged: u64,
+    pub query_history_purged: u64,
+    pub partitions_dropped: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthChecks {
+    pub pool: PoolHealth,
+    /// Pool of the read replica answering aggregations, when `DATABASE_READ_URL` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_pool: Option<PoolHealth>,
+    pub replication: ReplicationHealth,
+    pub cache: CacheHealth,
+    pub response_cache: ResponseCacheHealth,
+    pub history: HistoryHealth,
+    pub retention: RetentionHealth,
+    pub leader: LeaderStatus,
+    pub read_only: ReadOnlyStatus,
+}
+
+/// Average daily growth between the oldest sample in the window and now
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct StorageGrowth {
+    pub since: NaiveDate,
+    pub bytes_per_day: f64,
+    pub rows_per_day: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RelationStorage {
+    pub relation: String,
+    /// Partitioned table the relation is a partition of
+    pub parent: Option<String>,
+    /// Table, TOAST and index bytes, a partitioned table's adding up its partitions
+    pub total_bytes: i64,
+    pub table_bytes: i64,
+    pub index_bytes: i64,
+    /// The planner's row estimate, 0 before the relation was first analyzed
+    pub row_estimate: i64,
+    /// `None` without an earlier sample in the window
+    pub growth: Option<StorageGrowth>,
+}
+
+/// Sizes of the tables and partitions of the `renewable` schema and their growth, for
+/// capacity planning
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StorageResponse {
+    pub sampled_at: DateTime<Utc>,
+    /// Size of the whole database, including what lies outside the `renewable` schema
+    pub database_bytes: i64,
+    /// Size of the tables of the `renewable` schema together
+    pub schema_bytes: i64,
+    /// Growth of the schema together, partitions dropped by retention counting against it
+    pub growth: Option<StorageGrowth>,
+    /// Largest first
+    pub relations: Vec<RelationStorage>,
+}
+
+/// Ingestion whose readings no longer match those its latest chain entry recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AlteredIngestion {
+    pub ingestion_id: i64,
+    /// Sequence number of the ingestion's latest chain entry
+    pub seq: i64,
+    pub expected_readings: i64,
+    pub readings: i64,
+    pub expected_hash: String,
+    pub hash: String,
+}
+
+/// Outcome of checking the hash chain over ingestions and the readings it covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ChainVerification {
+    pub verified_at: DateTime<Utc>,
+    /// Whether every link holds and no ingestion was altered
+    pub intact: bool,
+    pub entries: usize,
+    /// Hash of the latest entry. Kept by an auditor, it shows the history up to it was not
+    /// rewritten when a later verification still links back to it.
+    pub head: Option<String>,
+    /// Sequence number of the first entry whose hash, or link to the entry before, fails
+    pub broken_at: Option<i64>,
+    pub altered: Vec<AlteredIngestion>,
+    /// Complete ingestions without a chain entry, stored before the chain was kept
+    pub unchained: i64,
+}
+
+/// Whether the instance runs the background jobs shared by every replica
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LeaderStatus {
+    pub leader: bool,
+    /// When leadership was last taken or given up, `None` before the first election
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Whether the instance rejects writes, queries are served either way
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadOnlyStatus {
+    pub read_only: bool,
+    /// Operator supplied explanation, e.g. the maintenance window
+    pub reason: Option<String>,
+    /// When the mode last changed
+    pub since: DateTime<Utc>,
+}
+
+/// Encryption applied to the exports of an API key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportRecipientStatus {
+    pub api_key_id: i64,
+    /// `age` or `gpg`, exports are written in plain text when absent
+    pub encryption: Option<&'static str>,
+}
+
+/// View created or refreshed for a saved aggregation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SavedView {
+    pub name: String,
+    /// Schema qualified relation BI tools query, with `datetime` and `total_amount` columns
+    pub relation: String,
+    pub materialized: bool,
+    /// `SELECT` the view runs
+    pub definition: String,
+}
+
+/// Readiness detail used by orchestrators to drain degraded instances
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub status: &'static str,
+    pub score: f64,
+    pub checks: HealthChecks,
+}
+
+/// Outcome of onboarding a single row of a bulk meter request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MeterOnboardingResult {
+    /// Zero based position of the row in the request
+    pub row: usize,
+    pub meter_code: Option<String>,
+    pub meter_id: Option<i64>,
+    pub series_created: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MeterOnboardingResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub results: Vec<MeterOnboardingResult>,
+}
+
+/// Where a month's actual generation falls against its expected profile
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, ToSchema)]
+pub enum ProfileBand {
+    AboveP50,
+    BetweenP90AndP50,
+    BelowP90,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MonthlyVariance {
+    pub month: DateTime<Utc>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub actual_kwh: Option<BigDecimal>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub p50_kwh: Option<BigDecimal>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub p90_kwh: Option<BigDecimal>,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub variance_to_p50_kwh: Option<BigDecimal>,
+    pub variance_to_p50_pct: Option<f64>,
+    pub band: Option<ProfileBand>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VarianceResponse {
+    pub meter_code: String,
+    pub months: Vec<MonthlyVariance>,
+}
+
+/// Totals of one month of a [`CalendarResponse`]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarMonth {
+    pub month: u32,
+    #[serde(serialize_with = "crate::decimal::serialize_opt")]
+    #[schema(value_type = Option<f64>)]
+    pub total_amount: Option<BigDecimal>,
+    pub days_with_readings: u32,
+    /// Day with the largest total
+    pub peak_day: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarResponse {
+    pub year: i32,
+    /// Total of every UTC day from 1 January, `null` for days without readings
+    #[serde(serialize_with = "crate::decimal::serialize_opt_seq")]
+    #[schema(value_type = Vec<Option<f64>>)]
+    pub days: Vec<Option<BigDecimal>>,
+    pub months: Vec<CalendarMonth>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MeterProfileStored {
+    pub meter_code: String,
+    pub months_stored: usize,
+}
+
+/// What the running binary was built from, compiled in at build time
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, `unknown` outside a git checkout
+    pub git_sha: &'static str,
+    pub built_at: DateTime<Utc>,
+    /// Enabled Cargo features
+    pub features: Vec<&'static str>,
+    /// Newest embedded migration, the schema level the instance migrates to at startup
+    pub schema_migration: &'static str,
+}
+
+/// Pre-signed URL a readings file is uploaded to directly, rather than through the API
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadUrlResponse {
+    /// `PUT` the file's bytes to this URL before it expires
+    pub upload_url: String,
+    /// Object the file is stored as, sent to `/timeseries/v1/uploads/ingest` once uploaded
+    pub location: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Pushed to live update subscribers when an ingestion is stored
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IngestionNotification {
+    pub ingestion_id: i64,
+    pub source: String,
+    /// Readings inserted
+    pub rows: usize,
+    /// Readings replacing those an earlier upload of the source stored
+    pub updated_rows: usize,
+    /// Readings left out, their timestamps being already held or repeated in the upload
+    pub skipped_rows: usize,
+    pub first_reading_at: DateTime<Utc>,
+    pub last_reading_at: DateTime<Utc>,
+    /// Set when the readings look to be in another unit than those of their series
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_mismatch: Option<UnitMismatch>,
+}
+
+/// Detected value with the share of sampled values supporting it, from 0 to 1
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct DetectedCandidate {
+    pub value: String,
+    pub confidence: f64,
+}
+
+/// Meaning a column of an uploaded file may carry
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnRole {
+    Datetime,
+    Amount,
+}
+
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct RoleCandidate {
+    pub role: ColumnRole,
+    pub confidence: f64,
+}
+
+/// Roles a column may be mapped to, most likely first
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct ColumnMapping {
+    /// Zero based position of the column
+    pub index: usize,
+    pub header: Option<String>,
+    pub candidates: Vec<RoleCandidate>,
+}
+
+/// Layout detected from the first rows of an uploaded file, each list most likely first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FormatDetection {
+    /// Data rows inspected, excluding the header
+    pub sampled_rows: usize,
+    pub has_header: bool,
+    pub delimiters: Vec<DetectedCandidate>,
+    /// `chrono` format strings matching the datetime column
+    pub datetime_formats: Vec<DetectedCandidate>,
+    /// `point` for `1,234.5` or `comma` for `1.234,5`
+    pub decimal_conventions: Vec<DetectedCandidate>,
+    pub columns: Vec<ColumnMapping>,
+    /// Whether the sampled rows can be ingested as they are, with the configured CSV columns
+    pub ingestible: bool,
 }
@@ -1,16 +1,239 @@
+use std::str::FromStr as _;
+
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
+use chrono::{
+    DateTime, NaiveDateTime, Utc,
+    format::{Item, StrftimeItems},
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(serde::Deserialize, Debug)]
+use crate::{
+    config::AppConfig,
+    model::{DATETIME_FORMAT, api_request::MeterOnboarding, check_amount_bounds},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CSVRecord {
-    #[serde(
-        rename = "Time (UTC)",
-        deserialize_with = "super::deserialize_datetime"
-    )]
     pub datetime: DateTime<Utc>,
-    #[serde(
-        rename = "Quantity kWh",
-        deserialize_with = "super::deserialize_decimal"
-    )]
+    /// Energy in kWh
     pub amount: BigDecimal,
+    /// Other columns of the row keyed by header, when the schema captures them
+    pub extra: Option<serde_json::Value>,
+}
+
+/// Unit of the amount column, converted to kWh on ingestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyUnit {
+    Wh,
+    #[default]
+    Kwh,
+    Mwh,
+}
+
+impl EnergyUnit {
+    fn to_kwh(self, amount: BigDecimal) -> BigDecimal {
+        match self {
+            Self::Wh => amount / 1000,
+            Self::Kwh => amount,
+            Self::Mwh => amount * 1000,
+        }
+    }
+}
+
+/// How the columns of a readings CSV are named and formatted, so files from different
+/// utilities can be ingested without code changes. Defaults to the seed file layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvSchema {
+    pub datetime_column: String,
+    pub amount_column: String,
+    /// `chrono` format of the datetime column, read as UTC
+    pub datetime_format: String,
+    /// `.` or `,`, the other being accepted as a thousands separator
+    pub decimal_separator: char,
+    pub unit: EnergyUnit,
+    /// Keep the columns beyond the datetime and amount as [`CSVRecord::extra`] rather than
+    /// discarding them
+    pub capture_extra: bool,
+}
+
+impl Default for CsvSchema {
+    fn default() -> Self {
+        Self {
+            datetime_column: "Time (UTC)".to_string(),
+            amount_column: "Quantity kWh".to_string(),
+            datetime_format: DATETIME_FORMAT.to_string(),
+            decimal_separator: '.',
+            unit: EnergyUnit::Kwh,
+            capture_extra: false,
+        }
+    }
+}
+
+impl From<&AppConfig> for CsvSchema {
+    fn from(config: &AppConfig) -> Self {
+        Self {
+            datetime_column: config.csv_datetime_column.clone(),
+            amount_column: config.csv_amount_column.clone(),
+            datetime_format: config.csv_datetime_format.clone(),
+            decimal_separator: config.csv_decimal_separator,
+            unit: config.csv_unit,
+            capture_extra: config.csv_capture_extra,
+        }
+    }
+}
+
+impl CsvSchema {
+    /// Whether `format` is a datetime format `chrono` can parse with
+    pub fn is_valid_format(format: &str) -> bool {
+        !format.is_empty() && !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+    }
+
+    /// Positions of the datetime and amount columns within `headers`
+    pub fn columns(&self, headers: &csv::StringRecord) -> Result<(usize, usize), String> {
+        let position = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| format!("missing column {name:?}"))
+        };
+        Ok((
+            position(&self.datetime_column)?,
+            position(&self.amount_column)?,
+        ))
+    }
+
+    /// Positions and names of the columns captured as [`CSVRecord::extra`], none unless
+    /// [`Self::capture_extra`] is set
+    pub fn extra_columns(&self, headers: &csv::StringRecord) -> Vec<(usize, String)> {
+        if !self.capture_extra {
+            return Vec::new();
+        }
+        headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| *header != self.datetime_column && *header != self.amount_column)
+            .map(|(position, header)| (position, header.to_string()))
+            .collect()
+    }
+
+    /// Object of the non-empty `columns` of `row` as strings, `None` when every one is empty
+    pub fn extra(
+        columns: &[(usize, String)],
+        row: &csv::StringRecord,
+    ) -> Option<serde_json::Value> {
+        let extra: serde_json::Map<_, _> = columns
+            .iter()
+            .filter_map(|(position, header)| {
+                row.get(*position)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| (header.clone(), serde_json::Value::from(value)))
+            })
+            .collect();
+        (!extra.is_empty()).then_some(serde_json::Value::Object(extra))
+    }
+
+    pub fn parse_datetime(&self, value: &str) -> Result<DateTime<Utc>, String> {
+        NaiveDateTime::parse_from_str(value, &self.datetime_format)
+            .map(|naive| naive.and_utc())
+            .map_err(|e| format!("{}: {e}", self.datetime_column))
+    }
+
+    /// Parses an amount in the configured unit and decimal separator into kWh, rejecting
+    /// amounts the store cannot hold
+    pub fn parse_amount(&self, value: &str) -> Result<BigDecimal, String> {
+        let thousands = if self.decimal_separator == ',' {
+            '.'
+        } else {
+            ','
+        };
+        let cleaned: String = value
+            .trim()
+            .trim_matches('"')
+            .chars()
+            .filter(|c| *c != thousands && !c.is_whitespace())
+            .map(|c| if c == self.decimal_separator { '.' } else { c })
+            .collect();
+        if cleaned.is_empty() {
+            return Err(format!("{}: empty amount", self.amount_column));
+        }
+        let amount = BigDecimal::from_str(&cleaned)
+            .map(|amount| self.unit.to_kwh(amount))
+            .map_err(|e| format!("{}: {e}", self.amount_column))?;
+        check_amount_bounds(&amount)?;
+        Ok(amount)
+    }
+}
+
+/// Row of a bulk meter onboarding CSV, `series` holds `;` separated names
+#[derive(serde::Deserialize, Debug)]
+pub struct MeterCSVRecord {
+    pub meter_code: String,
+    pub site_name: String,
+    pub capacity_kw: Option<f64>,
+    #[serde(default)]
+    pub series: String,
+}
+
+impl From<MeterCSVRecord> for MeterOnboarding {
+    fn from(record: MeterCSVRecord) -> Self {
+        Self {
+            meter_code: record.meter_code,
+            site_name: record.site_name,
+            capacity_kw: record.capacity_kw,
+            series: record
+                .series
+                .split(';')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone as _, Utc};
+    use test_case::test_case;
+
+    use super::{CsvSchema, EnergyUnit};
+
+    #[test_case('.', EnergyUnit::Kwh, "\"9,000.500\"", "9000.5")]
+    #[test_case(',', EnergyUnit::Kwh, "9.000,5", "9000.5")]
+    #[test_case(',', EnergyUnit::Kwh, "1 234,5", "1234.5")]
+    #[test_case('.', EnergyUnit::Wh, "1500", "1.5")]
+    #[test_case('.', EnergyUnit::Mwh, "0.25", "250")]
+    fn test_amounts_are_read_in_kwh(
+        decimal_separator: char,
+        unit: EnergyUnit,
+        value: &str,
+        kwh: &str,
+    ) {
+        let schema = CsvSchema {
+            decimal_separator,
+            unit,
+            ..CsvSchema::default()
+        };
+        assert_eq!(
+            schema.parse_amount(value).unwrap(),
+            kwh.parse::<BigDecimal>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_datetime_format_is_configurable() {
+        let schema = CsvSchema {
+            datetime_format: "%Y-%m-%dT%H:%M:%S".to_string(),
+            ..CsvSchema::default()
+        };
+        assert_eq!(
+            schema.parse_datetime("2025-01-01T00:30:00").unwrap(),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 30, 0).unwrap()
+        );
+        assert!(schema.parse_datetime("1 Jan 2025 00:30").is_err());
+        assert!(CsvSchema::is_valid_format("%d/%m/%Y %H:%M"));
+        assert!(!CsvSchema::is_valid_format("%Q"));
+    }
 }
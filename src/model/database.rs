@@ -1,15 +1,41 @@
+use std::io::Write as _;
+
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
-use diesel::{Insertable, Queryable, QueryableByName, Selectable};
-use serde::Serialize;
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::{
+    AsExpression, Insertable, Queryable, QueryableByName, Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    pg::{Pg, PgValue},
+    serialize::{IsNull, Output, ToSql},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::model::{api_request::Aggregation, csv::CSVRecord};
+use crate::model::{
+    api_request::{Aggregation, SeriesDefinition},
+    csv::CSVRecord,
+};
 
 #[derive(Queryable, Insertable, QueryableByName, Debug)]
 #[diesel(table_name = crate::renewable_schema::ts_metadata)]
 pub struct TSMetadata {
     pub ingestion_datetime: DateTime<Utc>,
     pub source: String,
+    /// Series every reading of the ingestion belongs to
+    pub series_id: Option<i64>,
+    /// Rank of the source, see [`crate::config::AppConfig::source_priority`]
+    pub priority: i32,
+    /// Readings stored by the ingestion, set once it completes
+    pub row_count: Option<i64>,
+    pub min_datetime: Option<DateTime<Utc>>,
+    pub max_datetime: Option<DateTime<Utc>>,
+    /// Rows of the source skipped as invalid
+    pub error_count: i64,
+    /// Time taken to store the readings, set once the ingestion completes or fails
+    pub duration_ms: Option<i64>,
+    pub status: IngestionStatus,
+    /// Hex SHA-256 of the file read, a second ingestion of the same content being skipped
+    pub checksum: Option<String>,
 }
 
 impl TSMetadata {
@@ -17,29 +43,193 @@ impl TSMetadata {
         Self {
             ingestion_datetime: Utc::now(),
             source,
+            series_id: None,
+            priority: 0,
+            row_count: None,
+            min_datetime: None,
+            max_datetime: None,
+            error_count: 0,
+            duration_ms: None,
+            status: IngestionStatus::Pending,
+            checksum: None,
+        }
+    }
+}
+
+/// Progress of an ingestion, recorded before its readings are stored so that ingestions
+/// which never finish remain visible
+#[derive(Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Clone, Copy, ToSchema)]
+#[diesel(sql_type = crate::renewable_schema::sql_types::IngestionStatus)]
+pub enum IngestionStatus {
+    Pending,
+    Complete,
+    Failed,
+}
+
+impl FromSql<crate::renewable_schema::sql_types::IngestionStatus, Pg> for IngestionStatus {
+    fn from_sql(bytes: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"Pending" => Ok(Self::Pending),
+            b"Complete" => Ok(Self::Complete),
+            b"Failed" => Ok(Self::Failed),
+            _ => Err("Unrecognized enum variant".into()),
         }
     }
 }
 
+impl ToSql<crate::renewable_schema::sql_types::IngestionStatus, Pg> for IngestionStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let s = match self {
+            Self::Pending => "Pending",
+            Self::Complete => "Complete",
+            Self::Failed => "Failed",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
 #[derive(Queryable, Insertable, QueryableByName, Debug)]
 #[diesel(table_name = crate::renewable_schema::ts_store)]
 pub struct TSStore {
     pub ingestion_id: i64,
     pub datetime: DateTime<Utc>,
     pub amount: BigDecimal,
+    pub recorded_at: DateTime<Utc>,
+    /// Columns of the source row beyond the datetime and amount, see [`CSVRecord::extra`]
+    pub extra: Option<serde_json::Value>,
+}
+
+/// Readings of one ingestion on one UTC day, packed by [`crate::delta_block::encode`]
+#[derive(Queryable, Insertable, Selectable, Debug)]
+#[diesel(table_name = crate::renewable_schema::ts_compressed_blocks)]
+pub struct CompressedBlock {
+    pub ingestion_id: i64,
+    pub day: NaiveDate,
+    pub first_datetime: DateTime<Utc>,
+    pub last_datetime: DateTime<Utc>,
+    pub readings: i32,
+    /// Earliest `recorded_at` of the packed rows, given to every reading decoded from it
+    pub recorded_at: DateTime<Utc>,
+    pub block: Vec<u8>,
+}
+
+/// Storage taken by an ingestion's rows against its compressed blocks, Postgres row
+/// overheads included on both sides
+#[derive(Debug, Serialize)]
+pub struct CompressionReport {
+    pub ingestion_id: i64,
+    pub blocks: usize,
+    pub readings: usize,
+    pub row_bytes: i64,
+    pub block_bytes: i64,
 }
 
 impl From<(i64, CSVRecord)> for TSStore {
-    fn from((ingestion_id, CSVRecord { datetime, amount }): (i64, CSVRecord)) -> Self {
+    fn from(
+        (
+            ingestion_id,
+            CSVRecord {
+                datetime,
+                amount,
+                extra,
+            },
+        ): (i64, CSVRecord),
+    ) -> Self {
         Self {
             ingestion_id,
             datetime,
             amount,
+            recorded_at: Utc::now(),
+            extra,
+        }
+    }
+}
+
+/// Energy held by a bucket, with readings at the same timestamp from several ingestions
+/// summed into one interval reading before the peak and count are taken
+#[derive(QueryableByName, Debug, Clone, PartialEq)]
+pub struct BucketEnergy {
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    pub datetime: DateTime<Utc>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Numeric>)]
+    pub total_amount: Option<BigDecimal>,
+    /// Largest single interval reading within the bucket
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Numeric>)]
+    pub peak_amount: Option<BigDecimal>,
+    /// Distinct reading timestamps within the bucket
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub readings: i64,
+}
+
+/// Bucket of one range of a multi-range query
+#[derive(QueryableByName, Debug, Clone, PartialEq)]
+pub struct RangeBucket {
+    /// Position of the range within the request
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub range_index: i64,
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    pub datetime: DateTime<Utc>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Numeric>)]
+    pub total_amount: Option<BigDecimal>,
+}
+
+/// Offsets of an ingestion's timestamps from the expected reading interval grid
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::ingestion_clock_drift)]
+pub struct IngestionClockDrift {
+    pub ingestion_id: i64,
+    pub interval_secs: i32,
+    pub readings: i64,
+    /// Readings whose timestamp was not on the grid
+    pub drifted_readings: i64,
+    /// Signed offset of the furthest drifted reading from its nearest grid point
+    pub max_offset_secs: i64,
+    /// Whether drifted readings were stored snapped to the grid rather than as received
+    pub snapped: bool,
+    /// Snapped readings discarded because a closer reading held the same grid point
+    pub collisions: i64,
+}
+
+/// Kind of problem found in an ingestion's file, see [`crate::issues`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    ParseFailure,
+    RejectedDelta,
+    DuplicateTimestamp,
+    NegativeValue,
+    Gap,
+}
+
+impl IssueKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ParseFailure => "parse_failure",
+            Self::RejectedDelta => "rejected_delta",
+            Self::DuplicateTimestamp => "duplicate_timestamp",
+            Self::NegativeValue => "negative_value",
+            Self::Gap => "gap",
         }
     }
 }
 
-#[derive(Queryable, Insertable, QueryableByName, Debug, Selectable, Serialize)]
+/// Problem found in an ingestion's file as it was ingested
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::ingestion_issues)]
+pub struct IngestionIssue {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub ingestion_id: i64,
+    /// `parse_failure`, `rejected_delta`, `duplicate_timestamp`, `negative_value` or `gap`
+    pub kind: String,
+    /// Line of the file, for rows that could not be parsed
+    pub line: Option<i64>,
+    /// Timestamp of the reading, or the first one missing from a gap
+    pub datetime: Option<DateTime<Utc>>,
+    pub detail: String,
+}
+
+#[derive(Queryable, Insertable, QueryableByName, Debug, Selectable, Serialize, ToSchema)]
 #[diesel(table_name = crate::renewable_schema::query_history)]
 pub struct QueryHistory {
     #[diesel(skip_insertion)]
@@ -48,6 +238,14 @@ pub struct QueryHistory {
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
     pub aggregation: Aggregation,
+    /// Key that issued the query, `None` for internal queries
+    pub api_key_id: Option<i64>,
+    /// Time taken to answer, `None` for queries recorded before queries were timed
+    pub duration_ms: Option<i64>,
+    /// Buckets returned, `None` when the query failed or the count is not known, as for
+    /// export jobs and responses served by another replica
+    pub row_count: Option<i64>,
+    pub status: QueryStatus,
 }
 
 impl QueryHistory {
@@ -55,6 +253,7 @@ impl QueryHistory {
         from_date: Option<DateTime<Utc>>,
         to_date: Option<DateTime<Utc>>,
         aggregation: Aggregation,
+        api_key_id: Option<i64>,
     ) -> Self {
         Self {
             id: 0,
@@ -62,6 +261,443 @@ impl QueryHistory {
             from_date,
             to_date,
             aggregation,
+            api_key_id,
+            duration_ms: None,
+            row_count: None,
+            status: QueryStatus::Succeeded,
+        }
+    }
+}
+
+/// Whether a query recorded in the history was answered
+#[derive(
+    Debug, PartialEq, Eq, FromSqlRow, AsExpression, Deserialize, Serialize, Clone, Copy, ToSchema,
+)]
+#[diesel(sql_type = crate::renewable_schema::sql_types::QueryStatus)]
+pub enum QueryStatus {
+    Succeeded,
+    Failed,
+}
+
+impl FromSql<crate::renewable_schema::sql_types::QueryStatus, Pg> for QueryStatus {
+    fn from_sql(bytes: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"Succeeded" => Ok(Self::Succeeded),
+            b"Failed" => Ok(Self::Failed),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl ToSql<crate::renewable_schema::sql_types::QueryStatus, Pg> for QueryStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let s = match self {
+            Self::Succeeded => "Succeeded",
+            Self::Failed => "Failed",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Clone, Copy, ToSchema)]
+#[diesel(sql_type = crate::renewable_schema::sql_types::JobStatus)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Complete,
+    Failed,
+}
+
+impl FromSql<crate::renewable_schema::sql_types::JobStatus, Pg> for JobStatus {
+    fn from_sql(bytes: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"Pending" => Ok(Self::Pending),
+            b"Running" => Ok(Self::Running),
+            b"Complete" => Ok(Self::Complete),
+            b"Failed" => Ok(Self::Failed),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl ToSql<crate::renewable_schema::sql_types::JobStatus, Pg> for JobStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let s = match self {
+            Self::Pending => "Pending",
+            Self::Running => "Running",
+            Self::Complete => "Complete",
+            Self::Failed => "Failed",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, Selectable)]
+#[diesel(table_name = crate::renewable_schema::export_jobs)]
+pub struct ExportJob {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub status: JobStatus,
+    pub aggregation: Aggregation,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    pub file_path: Option<String>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Key that created the job, whose watermark the file carries
+    pub api_key_id: Option<i64>,
+}
+
+impl ExportJob {
+    pub fn new(
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        aggregation: Aggregation,
+        api_key_id: Option<i64>,
+    ) -> Self {
+        Self {
+            id: 0,
+            created_at: Utc::now(),
+            status: JobStatus::Pending,
+            aggregation,
+            from_date,
+            to_date,
+            file_path: None,
+            row_count: None,
+            error: None,
+            completed_at: None,
+            api_key_id,
+        }
+    }
+}
+
+/// Aggregation answered in the background, see `POST /timeseries/v1/query/async`
+#[derive(Queryable, Insertable, Debug, Selectable)]
+#[diesel(table_name = crate::renewable_schema::query_jobs)]
+pub struct QueryJob {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub status: JobStatus,
+    pub aggregation: Aggregation,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    /// Key that submitted the job
+    pub api_key_id: Option<i64>,
+    /// The JSON response of the aggregation, once complete
+    pub result: Option<serde_json::Value>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl QueryJob {
+    pub fn new(
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        aggregation: Aggregation,
+        api_key_id: Option<i64>,
+    ) -> Self {
+        Self {
+            id: 0,
+            created_at: Utc::now(),
+            status: JobStatus::Pending,
+            aggregation,
+            from_date,
+            to_date,
+            api_key_id,
+            result: None,
+            row_count: None,
+            error: None,
+            completed_at: None,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, Selectable)]
+#[diesel(table_name = crate::renewable_schema::meters)]
+pub struct Meter {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub meter_code: String,
+    pub site_name: String,
+    pub capacity_kw: Option<BigDecimal>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Meter {
+    pub fn new(meter_code: String, site_name: String, capacity_kw: Option<BigDecimal>) -> Self {
+        Self {
+            id: 0,
+            meter_code,
+            site_name,
+            capacity_kw,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Named stream of readings such as one meter or asset
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::series)]
+pub struct Series {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub name: String,
+    pub unit: String,
+    pub fuel_type: Option<String>,
+    pub site: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Meter the series is a channel of, when created by onboarding one
+    pub meter_id: Option<i64>,
+}
+
+impl Series {
+    /// Channel `channel` of `meter`, named after the meter's code, e.g. `MTR-001/generation`,
+    /// and measured in kWh
+    pub fn meter_channel(meter_id: i64, meter: &Meter, channel: &str) -> Self {
+        Self {
+            id: 0,
+            name: format!("{}/{channel}", meter.meter_code),
+            unit: "kWh".to_string(),
+            fuel_type: None,
+            site: Some(meter.site_name.clone()),
+            created_at: Utc::now(),
+            meter_id: Some(meter_id),
+        }
+    }
+}
+
+impl From<SeriesDefinition> for Series {
+    fn from(definition: SeriesDefinition) -> Self {
+        Self {
+            id: 0,
+            name: definition.name,
+            unit: definition.unit,
+            fuel_type: definition.fuel_type,
+            site: definition.site,
+            created_at: Utc::now(),
+            meter_id: None,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, Selectable)]
+#[diesel(table_name = crate::renewable_schema::api_keys)]
+pub struct ApiKeyRecord {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub name: String,
+    pub key_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub export_recipient: Option<String>,
+}
+
+impl ApiKeyRecord {
+    pub fn new(name: String, key_hash: String) -> Self {
+        Self {
+            id: 0,
+            name,
+            key_hash,
+            created_at: Utc::now(),
+            revoked_at: None,
+            export_recipient: None,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, Selectable)]
+#[diesel(table_name = crate::renewable_schema::meter_profiles)]
+pub struct MeterProfile {
+    pub meter_id: i64,
+    pub month: i16,
+    pub p50_kwh: BigDecimal,
+    pub p90_kwh: BigDecimal,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Column of a `renewable` table as the database catalog describes it
+#[derive(QueryableByName, Debug, Clone, PartialEq, Eq)]
+pub struct LiveColumn {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub table_name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub column_name: String,
+    /// Postgres type name, e.g. `int8`, `_text` for arrays or `job_status` for enums
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub udt_name: String,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub nullable: bool,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub has_default: bool,
+}
+
+/// Index of a `renewable` table other than those backing a primary key, unique or
+/// exclusion constraint
+#[derive(QueryableByName, Debug, Clone, PartialEq, Eq)]
+pub struct LiveIndex {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub index_name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub table_name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub definition: String,
+}
+
+/// Month partition of `ts_store` ending on or before a retention cutoff
+#[derive(QueryableByName, Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredPartition {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    pub lower_bound: DateTime<Utc>,
+}
+
+/// Size of a table, partition or materialized view of the `renewable` schema on a day,
+/// sampled daily into `storage_stats`
+#[derive(QueryableByName, Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = crate::renewable_schema::storage_stats)]
+pub struct StorageSample {
+    pub sampled_on: NaiveDate,
+    pub relation: String,
+    /// Partitioned table the relation is a partition of
+    pub parent: Option<String>,
+    /// Table, TOAST and index bytes, a partitioned table's adding up its partitions
+    pub total_bytes: i64,
+    pub index_bytes: i64,
+    /// The planner's row estimate, 0 before the relation was first analyzed
+    pub row_estimate: i64,
+}
+
+/// Rows older than a retention cutoff, purged or in a dry run due to be
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPurge {
+    pub readings: u64,
+    pub query_history: u64,
+    /// Month partitions of `ts_store` dropped whole rather than deleted from
+    pub partitions: u64,
+    /// Expired readings kept because their ingestion is under legal hold
+    pub held: u64,
+}
+
+/// Readings of an upload merged into the earlier ingestion of its source
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IngestionCorrection {
+    pub ingestion_id: i64,
+    /// Readings at timestamps the ingestion did not hold
+    pub inserted: usize,
+    /// Readings replacing those held, with `mode=overwrite`
+    pub updated: usize,
+    /// Readings left out, at timestamps held with `mode=skip` or repeated in the upload
+    pub skipped: usize,
+}
+
+/// Ingestion kept from retention and deletion while a dispute is open
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::legal_holds)]
+pub struct LegalHold {
+    pub ingestion_id: i64,
+    pub placed_at: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// What an audit log entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    LegalHoldPlaced,
+    LegalHoldReleased,
+    DeleteIngestion,
+    CorrectIngestion,
+    RetentionPurge,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LegalHoldPlaced => "legal_hold_placed",
+            Self::LegalHoldReleased => "legal_hold_released",
+            Self::DeleteIngestion => "delete_ingestion",
+            Self::CorrectIngestion => "correct_ingestion",
+            Self::RetentionPurge => "retention_purge",
+        }
+    }
+}
+
+/// Change to the readings of an ingestion recorded in the hash chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainEvent {
+    Ingested,
+    Corrected,
+    Deleted,
+    Purged,
+}
+
+impl ChainEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ingested => "ingested",
+            Self::Corrected => "corrected",
+            Self::Deleted => "deleted",
+            Self::Purged => "purged",
+        }
+    }
+}
+
+/// Entry of the hash chain over ingestions, see [`crate::chain`]
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::ingestion_chain)]
+pub struct ChainEntry {
+    #[diesel(skip_insertion)]
+    pub seq: i64,
+    pub recorded_at: DateTime<Utc>,
+    /// `ingested`, `corrected`, `deleted` or `purged`
+    pub event: String,
+    pub ingestion_id: i64,
+    /// Readings the ingestion held after the event
+    pub readings: i64,
+    /// Hex SHA-256 of those readings, see [`crate::chain::content_line`]
+    pub content_hash: String,
+    /// Hash of the entry before, `None` for the first
+    pub previous_hash: Option<String>,
+    /// Hex SHA-256 over the previous hash and this entry, see [`crate::chain::link_hash`]
+    pub hash: String,
+}
+
+/// Entry of the audit log, kept after its ingestion is gone
+#[derive(Queryable, Insertable, Selectable, Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[diesel(table_name = crate::renewable_schema::audit_log)]
+pub struct AuditEntry {
+    #[diesel(skip_insertion)]
+    pub id: i64,
+    pub recorded_at: DateTime<Utc>,
+    /// `legal_hold_placed`, `legal_hold_released`, `delete_ingestion`, `correct_ingestion`
+    /// or `retention_purge`
+    pub action: String,
+    pub ingestion_id: Option<i64>,
+    /// False when the action was refused
+    pub permitted: bool,
+    pub detail: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        action: AuditAction,
+        ingestion_id: Option<i64>,
+        permitted: bool,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: 0,
+            recorded_at: Utc::now(),
+            action: action.as_str().to_string(),
+            ingestion_id,
+            permitted,
+            detail: Some(detail.into()),
         }
     }
 }
@@ -0,0 +1,544 @@
+use axum::{
+    Json,
+    extract::{
+        FromRef, FromRequest, FromRequestParts, Query, Request,
+        rejection::{JsonRejection, QueryRejection},
+    },
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+use chrono::TimeDelta;
+use serde::{Serialize, de::DeserializeOwned};
+use utoipa::ToSchema;
+
+use crate::{
+    config::AppConfig,
+    detect::MAX_SAMPLE_ROWS,
+    model::api_request::{
+        DetectFormatParams, IngestionUploadParams, MultiRangeQueryRequest, ParquetExportParams,
+        PowerQueryRequest, SeriesDefinition, SettlementPeriod, SnapshotDiffRequest,
+        TimeSeriesAggregationRequest, TimeSeriesRange, VarianceParams,
+    },
+    settlement,
+};
+
+/// A single rejected field of a request
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Dotted path to the offending field, `body` when the payload could not be decoded
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Structured body returned for requests that fail decoding or validation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidationErrorResponse {
+    pub errors: Vec<FieldError>,
+}
+
+/// Rejected request, rendered as a [`ValidationErrorResponse`] with `status`
+#[derive(Debug)]
+pub struct ValidationRejection {
+    pub status: StatusCode,
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationRejection {
+    pub fn unprocessable(errors: Vec<FieldError>) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            errors,
+        }
+    }
+}
+
+impl IntoResponse for ValidationRejection {
+    fn into_response(self) -> Response {
+        let body = ValidationErrorResponse {
+            errors: self.errors,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Bounds applied to every request range, see [`AppConfig::max_query_span_days`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidationLimits {
+    pub max_span: Option<TimeDelta>,
+}
+
+impl FromRef<AppConfig> for ValidationLimits {
+    fn from_ref(config: &AppConfig) -> Self {
+        Self {
+            max_span: config.max_query_span_days.map(TimeDelta::days),
+        }
+    }
+}
+
+/// Most ranges a multi-range query may aggregate in one statement
+pub const MAX_QUERY_RANGES: usize = 32;
+
+/// Semantic checks run once a request has been decoded
+pub trait Validate {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError>;
+
+    fn validate(&self, limits: ValidationLimits) -> Result<(), ValidationRejection> {
+        let errors = self.violations(limits);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationRejection::unprocessable(errors))
+        }
+    }
+}
+
+/// Checks `period` names a period of its settlement day
+fn settlement_violation(field: &str, period: &SettlementPeriod) -> Option<FieldError> {
+    let periods = settlement::periods_in_day(period.settlement_date);
+    (!(1..=periods).contains(&period.settlement_period)).then(|| {
+        FieldError::new(
+            &format!("{field}.settlement_period"),
+            format!(
+                "must be between 1 and {periods} on {}",
+                period.settlement_date
+            ),
+        )
+    })
+}
+
+/// Rejects inverted ranges and closed ranges wider than `limits.max_span`, open ended
+/// ranges are left to the request timeout
+fn range_violations(
+    prefix: &str,
+    range: &TimeSeriesRange,
+    limits: ValidationLimits,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    for (date, period, name) in [
+        (range.from_date, range.from_settlement, "from"),
+        (range.to_date, range.to_settlement, "to"),
+    ] {
+        let Some(period) = period else {
+            continue;
+        };
+        let field = format!("{prefix}{name}_settlement");
+        if date.is_some() {
+            errors.push(FieldError::new(
+                &field,
+                format!("must not be combined with {name}_date"),
+            ));
+        }
+        errors.extend(settlement_violation(&field, &period));
+    }
+    if !errors.is_empty() {
+        return errors;
+    }
+
+    let from_date = range
+        .from_settlement
+        .as_ref()
+        .map_or(range.from_date, settlement::period_start);
+    let to_date = range
+        .to_settlement
+        .as_ref()
+        .map_or(range.to_date, settlement::period_end);
+    let (Some(from_date), Some(to_date)) = (from_date, to_date) else {
+        return Vec::new();
+    };
+    let end = if range.to_settlement.is_some() {
+        "to_settlement"
+    } else {
+        "to_date"
+    };
+    let field = format!("{prefix}{end}");
+    if to_date < from_date {
+        return vec![FieldError::new(&field, "must not be before from_date")];
+    }
+    match limits.max_span {
+        Some(max_span) if to_date - from_date > max_span => vec![FieldError::new(
+            &field,
+            format!("range must not span more than {} days", max_span.num_days()),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+impl Validate for TimeSeriesRange {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        range_violations("", self, limits)
+    }
+}
+
+impl Validate for TimeSeriesAggregationRequest {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        let mut errors = match &self.datetime_filter {
+            Some(range) => range_violations("datetime_filter.", range, limits),
+            None => Vec::new(),
+        };
+        // Filled buckets would put back the buckets the filter removed
+        if self.having.is_some() && self.fill_missing.is_some() {
+            errors.push(FieldError::new(
+                "having",
+                "cannot be combined with fill_missing",
+            ));
+        }
+        // The breakdown splits every bucket, screened or not
+        if self.having.is_some() && !self.group_by.is_empty() {
+            errors.push(FieldError::new(
+                "group_by",
+                "cannot be combined with having",
+            ));
+        }
+        if self.having.is_some() && self.include_sources {
+            errors.push(FieldError::new(
+                "include_sources",
+                "cannot be combined with having",
+            ));
+        }
+        if self.series_id.is_some() && self.series_name.is_some() {
+            errors.push(FieldError::new(
+                "series_name",
+                "cannot be combined with series_id",
+            ));
+        }
+        // Missing buckets and their expected readings are worked out on the UTC calendar
+        if self.timezone.is_some() && (self.fill_missing.is_some() || self.include_completeness) {
+            errors.push(FieldError::new(
+                "timezone",
+                "cannot be combined with fill_missing or include_completeness",
+            ));
+        }
+        errors
+    }
+}
+
+impl Validate for PowerQueryRequest {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        range_violations("datetime_filter.", &self.datetime_filter, limits)
+    }
+}
+
+impl Validate for MultiRangeQueryRequest {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        if self.ranges.is_empty() || self.ranges.len() > MAX_QUERY_RANGES {
+            return vec![FieldError::new(
+                "ranges",
+                format!("must hold between 1 and {MAX_QUERY_RANGES} ranges"),
+            )];
+        }
+        self.ranges
+            .iter()
+            .enumerate()
+            .flat_map(|(index, range)| {
+                range_violations(&format!("ranges[{index}]."), range, limits)
+            })
+            .collect()
+    }
+}
+
+impl Validate for SnapshotDiffRequest {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        let mut errors = range_violations("datetime_filter.", &self.datetime_filter, limits);
+        if self
+            .compare_recorded_by
+            .is_some_and(|compare| compare < self.baseline_recorded_by)
+        {
+            errors.push(FieldError::new(
+                "compare_recorded_by",
+                "must not be before baseline_recorded_by",
+            ));
+        }
+        errors
+    }
+}
+
+impl Validate for ParquetExportParams {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        let range = TimeSeriesRange {
+            from_date: self.from_date,
+            to_date: self.to_date,
+            to_bound: self.to_bound,
+            from_settlement: None,
+            to_settlement: None,
+        };
+        range_violations("", &range, limits)
+    }
+}
+
+impl Validate for VarianceParams {
+    fn violations(&self, limits: ValidationLimits) -> Vec<FieldError> {
+        let range = TimeSeriesRange {
+            from_date: self.from_date,
+            to_date: self.to_date,
+            to_bound: self.to_bound,
+            from_settlement: None,
+            to_settlement: None,
+        };
+        range_violations("", &range, limits)
+    }
+}
+
+impl Validate for DetectFormatParams {
+    fn violations(&self, _limits: ValidationLimits) -> Vec<FieldError> {
+        match self.sample_rows {
+            Some(rows) if !(1..=MAX_SAMPLE_ROWS).contains(&rows) => vec![FieldError::new(
+                "sample_rows",
+                format!("must be between 1 and {MAX_SAMPLE_ROWS}"),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Validate for IngestionUploadParams {
+    fn violations(&self, _limits: ValidationLimits) -> Vec<FieldError> {
+        if self.source.trim().is_empty() {
+            vec![FieldError::new("source", "must not be empty")]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Validate for SeriesDefinition {
+    fn violations(&self, _limits: ValidationLimits) -> Vec<FieldError> {
+        [("name", &self.name), ("unit", &self.unit)]
+            .into_iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(field, _)| FieldError::new(field, "must not be empty"))
+            .collect()
+    }
+}
+
+/// Query string extractor reporting decoding failures and [`Validate`] violations as a
+/// [`ValidationErrorResponse`]
+#[derive(Debug)]
+pub struct ValidQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    type Rejection = ValidationRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await.map_err(
+            |rejection: QueryRejection| ValidationRejection {
+                status: rejection.status(),
+                errors: vec![FieldError::new("query", rejection.body_text())],
+            },
+        )?;
+        let config = AppConfig::from_ref(state);
+        value.validate(ValidationLimits::from_ref(&config))?;
+        Ok(Self(value))
+    }
+}
+
+/// JSON body extractor that reports decoding failures, such as an unknown aggregation
+/// kind, and [`Validate`] violations as a [`ValidationErrorResponse`]
+#[derive(Debug)]
+pub struct ValidJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    type Rejection = ValidationRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) =
+            Json::<T>::from_request(req, state)
+                .await
+                .map_err(|rejection: JsonRejection| ValidationRejection {
+                    status: rejection.status(),
+                    errors: vec![FieldError::new("body", rejection.body_text())],
+                })?;
+        let config = AppConfig::from_ref(state);
+        value.validate(ValidationLimits::from_ref(&config))?;
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+    use test_case::test_case;
+
+    use super::{Validate as _, ValidationLimits};
+    use crate::model::api_request::{
+        Aggregation, AmountUnit, FillMissing, GroupBy, MultiRangeQueryRequest, RangeEnd,
+        SettlementPeriod, TimeSeriesAggregationRequest, TimeSeriesRange, TotalFilter,
+    };
+
+    fn range(from_date: &str, to_date: &str) -> TimeSeriesRange {
+        request(from_date, to_date).datetime_filter.unwrap()
+    }
+
+    fn request(from_date: &str, to_date: &str) -> TimeSeriesAggregationRequest {
+        let parse = |s: &str| (!s.is_empty()).then(|| s.parse::<DateTime<Utc>>().unwrap());
+        TimeSeriesAggregationRequest {
+            aggregation_kind: Some(Aggregation::Monthly),
+            datetime_filter: Some(TimeSeriesRange {
+                from_date: parse(from_date),
+                to_date: parse(to_date),
+                to_bound: RangeEnd::Exclusive,
+                from_settlement: None,
+                to_settlement: None,
+            }),
+            fill_missing: None,
+            include_lineage: false,
+            include_completeness: false,
+            include_power: false,
+            having: None,
+            group_by: Vec::new(),
+            unit: AmountUnit::KWh,
+            timezone: None,
+            series_id: None,
+            series_name: None,
+            include_settlement: false,
+            include_sources: false,
+            as_recorded_by: None,
+        }
+    }
+
+    #[test_case("2025-01-01T00:00:00Z", "2025-02-01T00:00:00Z", 0; "ordered range")]
+    #[test_case("2025-01-01T00:00:00Z", "2025-01-01T00:00:00Z", 0; "empty range")]
+    #[test_case("2025-02-01T00:00:00Z", "2025-01-01T00:00:00Z", 1; "inverted range")]
+    #[test_case("2024-01-01T00:00:00Z", "2025-06-01T00:00:00Z", 1; "range too wide")]
+    #[test_case("2020-01-01T00:00:00Z", "", 0; "open ended range")]
+    fn test_range_validation(from_date: &str, to_date: &str, expected: usize) {
+        let limits = ValidationLimits {
+            max_span: Some(TimeDelta::days(366)),
+        };
+        let errors = request(from_date, to_date).violations(limits);
+        assert_eq!(errors.len(), expected);
+        assert!(
+            errors
+                .iter()
+                .all(|error| error.field == "datetime_filter.to_date")
+        );
+    }
+
+    #[test]
+    fn test_having_excludes_fill_missing() {
+        let limits = ValidationLimits { max_span: None };
+        let mut screened = request("", "");
+        screened.having = Some(TotalFilter {
+            gt: Some(1.0),
+            ..TotalFilter::default()
+        });
+        assert!(screened.violations(limits).is_empty());
+
+        screened.fill_missing = Some(FillMissing::Zero);
+        assert_eq!(screened.violations(limits)[0].field, "having");
+
+        screened.fill_missing = None;
+        screened.group_by = vec![GroupBy::FuelType];
+        assert_eq!(screened.violations(limits)[0].field, "group_by");
+    }
+
+    #[test]
+    fn test_series_filter_takes_id_or_name() {
+        let limits = ValidationLimits { max_span: None };
+        let mut filtered = request("", "");
+        filtered.series_name = Some("north-array".to_string());
+        assert!(filtered.violations(limits).is_empty());
+
+        filtered.series_id = Some(1);
+        assert_eq!(filtered.violations(limits)[0].field, "series_name");
+    }
+
+    #[test]
+    fn test_timezone_excludes_utc_calendar_options() {
+        let limits = ValidationLimits { max_span: None };
+        let mut zoned = request("", "");
+        zoned.timezone = Some(chrono_tz::Europe::London);
+        zoned.include_power = true;
+        assert!(zoned.violations(limits).is_empty());
+
+        zoned.include_completeness = true;
+        assert_eq!(zoned.violations(limits)[0].field, "timezone");
+
+        zoned.include_completeness = false;
+        zoned.fill_missing = Some(FillMissing::Null);
+        assert_eq!(zoned.violations(limits)[0].field, "timezone");
+    }
+
+    #[test]
+    fn test_multi_range_validation() {
+        let limits = ValidationLimits { max_span: None };
+        let mut multi = MultiRangeQueryRequest {
+            aggregation_kind: Aggregation::Monthly,
+            ranges: vec![
+                range("2024-01-01T00:00:00Z", "2024-02-01T00:00:00Z"),
+                range("2025-02-01T00:00:00Z", "2025-01-01T00:00:00Z"),
+            ],
+            having: None,
+            timezone: None,
+            series_id: None,
+            as_recorded_by: None,
+        };
+        assert_eq!(multi.violations(limits)[0].field, "ranges[1].to_date");
+
+        multi.ranges.clear();
+        assert_eq!(multi.violations(limits)[0].field, "ranges");
+    }
+
+    #[test]
+    fn test_settlement_range_validation() {
+        let period = |d: u32, settlement_period: u32| SettlementPeriod {
+            settlement_date: NaiveDate::from_ymd_opt(2025, 10, d).unwrap(),
+            settlement_period,
+        };
+        let limits = ValidationLimits { max_span: None };
+
+        let mut long_day = request("", "");
+        long_day.datetime_filter.as_mut().unwrap().from_settlement = Some(period(26, 1));
+        long_day.datetime_filter.as_mut().unwrap().to_settlement = Some(period(26, 50));
+        assert!(long_day.violations(limits).is_empty());
+        assert_eq!(
+            long_day.datetime_filter.as_ref().unwrap().half_open(),
+            (
+                Some("2025-10-25T23:00:00Z".parse().unwrap()),
+                Some("2025-10-27T00:00:00Z".parse().unwrap())
+            )
+        );
+
+        let mut invalid = request("2025-10-01T00:00:00Z", "");
+        invalid.datetime_filter.as_mut().unwrap().from_settlement = Some(period(27, 1));
+        invalid.datetime_filter.as_mut().unwrap().to_settlement = Some(period(27, 50));
+        let fields: Vec<_> = invalid
+            .violations(limits)
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "datetime_filter.from_settlement",
+                "datetime_filter.to_settlement.settlement_period"
+            ]
+        );
+
+        let mut inverted = request("", "");
+        inverted.datetime_filter.as_mut().unwrap().from_settlement = Some(period(27, 3));
+        inverted.datetime_filter.as_mut().unwrap().to_settlement = Some(period(27, 1));
+        assert_eq!(
+            inverted.violations(limits)[0].field,
+            "datetime_filter.to_settlement"
+        );
+    }
+}
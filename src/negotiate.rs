@@ -0,0 +1,88 @@
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{HeaderMap, header, request::Parts},
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::ApiError;
+
+/// Media type of an Arrow IPC stream
+pub const ARROW_STREAM: &str = "application/vnd.apache.arrow.stream";
+
+/// Representation of a query result chosen by the client
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Csv,
+    /// Arrow IPC stream of record batches
+    Arrow,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FormatParams {
+    /// Takes precedence over the `Accept` header
+    pub format: Option<ResponseFormat>,
+}
+
+/// Picks `?format=` when given, otherwise the first of `text/csv` or
+/// [`ARROW_STREAM`] listed in `Accept`, falling back to JSON
+fn negotiate(format: Option<ResponseFormat>, headers: &HeaderMap) -> ResponseFormat {
+    format.unwrap_or_else(|| {
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(
+                |media| match media.split(';').next().unwrap_or_default().trim() {
+                    "text/csv" => Some(ResponseFormat::Csv),
+                    ARROW_STREAM => Some(ResponseFormat::Arrow),
+                    _ => None,
+                },
+            )
+            .unwrap_or_default()
+    })
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(FormatParams { format }) = Query::from_request_parts(parts, state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        Ok(negotiate(format, &parts.headers))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::{HeaderMap, HeaderValue, header};
+    use test_case::test_case;
+
+    use super::{ResponseFormat, negotiate};
+
+    #[test_case(None, None, ResponseFormat::Json; "defaults to json")]
+    #[test_case(None, Some("text/csv"), ResponseFormat::Csv; "accept csv")]
+    #[test_case(None, Some("application/json;q=0.9, text/csv;q=1.0"), ResponseFormat::Csv; "accept list")]
+    #[test_case(None, Some("text/html"), ResponseFormat::Json; "accept other")]
+    #[test_case(Some(ResponseFormat::Json), Some("text/csv"), ResponseFormat::Json; "param wins")]
+    #[test_case(Some(ResponseFormat::Csv), None, ResponseFormat::Csv; "param csv")]
+    #[test_case(None, Some("application/vnd.apache.arrow.stream"), ResponseFormat::Arrow; "accept arrow")]
+    #[test_case(Some(ResponseFormat::Arrow), Some("text/csv"), ResponseFormat::Arrow; "param arrow")]
+    fn test_negotiate(
+        format: Option<ResponseFormat>,
+        accept: Option<&'static str>,
+        expected: ResponseFormat,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+        }
+        assert_eq!(negotiate(format, &headers), expected);
+    }
+}
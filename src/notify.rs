@@ -0,0 +1,172 @@
+use std::{env, sync::Arc, thread, time::Duration};
+
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::{Connection as _, PgConnection, QueryResult, RunQueryDsl as _, sql_types::Text};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, info, warn};
+
+use crate::state::AppState;
+
+/// Postgres channel announcing that stored readings changed
+pub const INGESTION_CHANNEL: &str = "ingestion_complete";
+/// Interval between checks for notifications received by the listening connection
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Wait before reconnecting a listener whose connection failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Tells the other instances sharing the database that readings changed, and listens
+/// for them doing the same, so each drops its per-instance caches after writes made
+/// through any of them.
+///
+/// Writers `NOTIFY ingestion_complete` with their instance id as payload, so listeners
+/// skip the changes of their own instance, whose caches the write path already dropped.
+#[derive(Clone)]
+pub struct ChangeFeed {
+    instance: Arc<str>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        let instance = format!("{}-{}", std::process::id(), Utc::now().timestamp_micros());
+        Self {
+            instance: instance.into(),
+        }
+    }
+}
+
+impl ChangeFeed {
+    /// Notifies every other instance that readings changed
+    pub async fn announce(&self, pg_pool: &Pool) {
+        let instance = Arc::clone(&self.instance);
+        let result = match pg_pool.get().await {
+            Ok(conn) => conn
+                .interact(move |conn| notify(&instance, conn))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result.map_err(|e| e.to_string())),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            error!("Unable to notify other instances of changed readings: {e}");
+        }
+    }
+
+    /// Listens on a dedicated connection for the changes of other instances, dropping
+    /// the response cache and refreshing the hot cache of `state` after each. Nothing
+    /// is spawned when the instance caches nothing.
+    pub fn spawn_listener(&self, state: AppState) -> Result<(), env::VarError> {
+        if state.response_cache.is_none() && state.hot_cache.is_none() {
+            return Ok(());
+        }
+        let database_url = env::var("DATABASE_URL")?;
+        // Changes arriving while the caches are being refreshed coalesce into one refresh
+        let (sender, mut receiver) = mpsc::channel(1);
+        let instance = Arc::clone(&self.instance);
+        thread::spawn(move || listen(&database_url, &instance, &sender));
+        tokio::spawn(async move {
+            while receiver.recv().await.is_some() {
+                if let Some(cache) = &state.response_cache {
+                    cache.invalidate();
+                }
+                if let Some(cache) = &state.hot_cache
+                    && let Err(e) = cache.refresh(state.db.primary()).await
+                {
+                    error!("Unable to refresh hot cache after a change elsewhere: {e}");
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+fn notify(instance: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(INGESTION_CHANNEL)
+        .bind::<Text, _>(instance)
+        .execute(conn)
+}
+
+/// Whether another instance announced a change since the last call, consuming the
+/// notifications received so far without waiting for more
+fn changed_elsewhere(instance: &str, conn: &mut PgConnection) -> QueryResult<bool> {
+    let mut changed = false;
+    for notification in conn.notifications_iter() {
+        changed |= notification?.payload != instance;
+    }
+    Ok(changed)
+}
+
+/// Polls a listening connection until the receiving task has gone, reconnecting when
+/// the connection fails
+fn listen(database_url: &str, instance: &str, sender: &mpsc::Sender<()>) {
+    loop {
+        let mut conn = match PgConnection::establish(database_url) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Unable to connect the change listener: {e}");
+                thread::sleep(RECONNECT_DELAY);
+                continue;
+            }
+        };
+        if let Err(e) = diesel::sql_query(format!("LISTEN {INGESTION_CHANNEL}")).execute(&mut conn)
+        {
+            warn!("Unable to listen for changes: {e}");
+            thread::sleep(RECONNECT_DELAY);
+            continue;
+        }
+        info!("Listening for changes made through other instances");
+        // Changes made while no connection was listening went unseen
+        let mut changed = true;
+        loop {
+            if changed && let Err(TrySendError::Closed(())) = sender.try_send(()) {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+            changed = match changed_elsewhere(instance, &mut conn) {
+                Ok(changed) => changed,
+                Err(e) => {
+                    warn!("Change listener connection failed: {e}");
+                    break;
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, thread, time::Duration};
+
+    use diesel::{Connection as _, PgConnection, RunQueryDsl as _};
+
+    use super::{INGESTION_CHANNEL, changed_elsewhere, notify};
+
+    fn get_test_connection() -> PgConnection {
+        dotenvy::dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        PgConnection::establish(&database_url).expect("Failed to connect to database")
+    }
+
+    #[test]
+    fn test_listeners_skip_changes_of_their_own_instance() {
+        let mut listener = get_test_connection();
+        let mut writer = get_test_connection();
+        diesel::sql_query(format!("LISTEN {INGESTION_CHANNEL}"))
+            .execute(&mut listener)
+            .unwrap();
+        assert!(!changed_elsewhere("a", &mut listener).unwrap());
+
+        // Notifications reach the listening connection shortly after they are sent
+        let delivery = Duration::from_millis(200);
+        notify("a", &mut writer).unwrap();
+        thread::sleep(delivery);
+        assert!(!changed_elsewhere("a", &mut listener).unwrap());
+
+        notify("b", &mut writer).unwrap();
+        notify("a", &mut writer).unwrap();
+        thread::sleep(delivery);
+        assert!(changed_elsewhere("a", &mut listener).unwrap());
+        assert!(!changed_elsewhere("a", &mut listener).unwrap());
+    }
+}
@@ -0,0 +1,306 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use datafusion::{
+    error::DataFusionError,
+    prelude::{ParquetReadOptions, SessionContext},
+};
+
+use crate::{
+    bucket,
+    engine::{self, READINGS},
+    model::{
+        api_request::TimeSeriesAggregationRequest,
+        api_response::QueryResponse,
+        validation::{Validate as _, ValidationLimits},
+    },
+    settlement,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum OfflineError {
+    #[error("query engine error {0}")]
+    Engine(#[from] DataFusionError),
+
+    #[error("not a raw readings export: {0}")]
+    Archive(String),
+
+    #[error("invalid request: {0}")]
+    Request(String),
+}
+
+/// Session holding the readings of raw Parquet exports, as served by
+/// `/timeseries/v1/export/parquet` without `aggregation_kind`, as its
+/// [`READINGS`](engine::READINGS). Each reading carries its ingestion's series and
+/// priority, exports made before those were included read as unranked.
+pub async fn open_archives(archives: &[PathBuf]) -> Result<SessionContext, OfflineError> {
+    let paths = archives
+        .iter()
+        .map(|archive| {
+            archive.to_str().map(str::to_string).ok_or_else(|| {
+                OfflineError::Archive(format!("{} is not a UTF-8 path", archive.display()))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let ctx = engine::session();
+    // Archives are taken whatever their name, not only those ending in `.parquet`
+    let options = ParquetReadOptions {
+        file_extension: "",
+        ..ParquetReadOptions::default()
+    };
+    let readings = ctx.read_parquet(paths, options).await?;
+    for column in ["datetime", "amount"] {
+        if readings
+            .schema()
+            .field_with_unqualified_name(column)
+            .is_err()
+        {
+            return Err(OfflineError::Archive(format!("no {column} column")));
+        }
+    }
+    ctx.register_table(READINGS, readings.into_view())?;
+    Ok(ctx)
+}
+
+/// Answers `request` from the archives of `ctx`, see [`open_archives`], as the query
+/// endpoint answers it from the database, an omitted range starting at `default_start`.
+/// Options needing more than the readings, the series, lineage, sources, completeness,
+/// power, breakdowns and `as_recorded_by`, which needs the revisions readings were
+/// overwritten from, are rejected.
+pub async fn answer(
+    request: TimeSeriesAggregationRequest,
+    ctx: &SessionContext,
+    default_start: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<QueryResponse, OfflineError> {
+    if let Err(rejection) = request.validate(ValidationLimits::default()) {
+        let errors: Vec<_> = rejection
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect();
+        return Err(OfflineError::Request(errors.join(", ")));
+    }
+    let unsupported = [
+        ("series_id", request.series_id.is_some()),
+        ("series_name", request.series_name.is_some()),
+        ("include_lineage", request.include_lineage),
+        ("include_sources", request.include_sources),
+        ("include_completeness", request.include_completeness),
+        ("include_power", request.include_power),
+        ("group_by", !request.group_by.is_empty()),
+        ("as_recorded_by", request.as_recorded_by.is_some()),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, requested)| *requested) {
+        return Err(OfflineError::Request(format!(
+            "{option} is not available offline"
+        )));
+    }
+
+    let (aggregation_kind, from_date, to_date) = request.resolve(default_start, now);
+    let zone = request.timezone.unwrap_or(Tz::UTC);
+    let records = engine::aggregate(
+        ctx,
+        aggregation_kind,
+        from_date,
+        to_date,
+        &request.having.unwrap_or_default(),
+        zone,
+    )
+    .await?;
+    let records = match request.fill_missing {
+        Some(fill) => bucket::fill_missing(aggregation_kind, from_date, to_date, records, fill),
+        None => records,
+    };
+    let settlement = request
+        .include_settlement
+        .then(|| settlement::label(&records));
+    Ok(QueryResponse {
+        executed_at: now,
+        unit: request.unit,
+        records: records
+            .into_iter()
+            .map(|record| record.in_unit(request.unit).in_zone(zone))
+            .collect(),
+        lineage: None,
+        completeness: None,
+        power: None,
+        settlement,
+        breakdown: None,
+        sources: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, path::PathBuf};
+
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+    use chrono_tz::Europe::London;
+
+    use super::{OfflineError, answer, open_archives};
+    use crate::{
+        bucket,
+        columnar::{self, IngestionRanks},
+        engine,
+        model::{
+            api_request::{Aggregation, TimeSeriesAggregationRequest, TotalFilter},
+            database::TSStore,
+        },
+    };
+
+    /// Raw export of `readings` written to a scratch file named after `name`
+    fn archive(name: &str, readings: &[TSStore], ranks: &IngestionRanks) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.parquet", std::process::id()));
+        columnar::write_parquet(
+            File::create(&path).unwrap(),
+            columnar::readings_schema(),
+            readings,
+            |readings| columnar::readings_batch(readings, ranks),
+        )
+        .unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_archives_aggregate_as_postgres_buckets_them() {
+        let start = Utc.with_ymd_and_hms(2024, 10, 26, 22, 0, 0).unwrap();
+        let readings: Vec<_> = (0..6)
+            .map(|i| TSStore {
+                ingestion_id: 1,
+                datetime: start + TimeDelta::hours(i),
+                amount: (100 * (i + 1)).into(),
+                recorded_at: start + TimeDelta::days(i),
+                extra: None,
+            })
+            .collect();
+        let path = archive("offline-buckets", &readings, &IngestionRanks::new());
+        let ctx = open_archives(&[path]).await.unwrap();
+
+        // London left summer time at 02:00 local on the 27th, 01:00 repeating
+        let days = engine::aggregate(
+            &ctx,
+            Aggregation::DayInMonth,
+            None,
+            None,
+            &TotalFilter::default(),
+            London,
+        )
+        .await
+        .unwrap();
+        let totals: Vec<_> = days
+            .iter()
+            .map(|day| (day.datetime, day.total_amount.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                (
+                    Utc.with_ymd_and_hms(2024, 10, 25, 23, 0, 0).unwrap(),
+                    100.into()
+                ),
+                (
+                    Utc.with_ymd_and_hms(2024, 10, 26, 23, 0, 0).unwrap(),
+                    2000.into()
+                ),
+            ]
+        );
+        assert_eq!(
+            bucket::truncate_in(Aggregation::Hourly, start + TimeDelta::hours(2), London),
+            Utc.with_ymd_and_hms(2024, 10, 27, 1, 0, 0).unwrap()
+        );
+
+        let having = TotalFilter {
+            ge: Some(300.0),
+            lt: Some(500.0),
+            ..TotalFilter::default()
+        };
+        let hours = engine::aggregate(
+            &ctx,
+            Aggregation::Hourly,
+            Some(start + TimeDelta::hours(1)),
+            Some(start + TimeDelta::hours(5)),
+            &having,
+            chrono_tz::UTC,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            hours.iter().map(|hour| hour.datetime).collect::<Vec<_>>(),
+            [start + TimeDelta::hours(2), start + TimeDelta::hours(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_outranked_readings_are_left_out_as_the_database_supersedes_them() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let readings: Vec<_> = [(1, 0, 10), (1, 1, 10), (2, 1, 7), (3, 1, 5), (4, 0, 2)]
+            .into_iter()
+            .map(|(ingestion_id, hour, amount)| TSStore {
+                ingestion_id,
+                datetime: start + TimeDelta::hours(hour),
+                amount: amount.into(),
+                recorded_at: start,
+                extra: None,
+            })
+            .collect();
+        // 2 and 3 outrank 1 on series 9 and share a priority, 4 is another series
+        let ranks = IngestionRanks::from([
+            (1, (Some(9), 0)),
+            (2, (Some(9), 5)),
+            (3, (Some(9), 5)),
+            (4, (Some(8), 0)),
+        ]);
+        // Split across two archives, the readings are ranked together
+        let paths = [
+            archive("offline-ranked-first", &readings[..2], &ranks),
+            archive("offline-ranked-rest", &readings[2..], &ranks),
+        ];
+        let ctx = open_archives(&paths).await.unwrap();
+
+        let hours = engine::aggregate(
+            &ctx,
+            Aggregation::Hourly,
+            None,
+            None,
+            &TotalFilter::default(),
+            chrono_tz::UTC,
+        )
+        .await
+        .unwrap();
+        let totals: Vec<_> = hours
+            .iter()
+            .map(|hour| (hour.datetime, hour.total_amount.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                (start, BigDecimal::from(12)),
+                (start + TimeDelta::hours(1), BigDecimal::from(12)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_answers_reject_options_needing_the_database() {
+        let ctx = engine::session();
+        let request: TimeSeriesAggregationRequest =
+            serde_json::from_str(r#"{"datetime_filter": {}, "include_lineage": true}"#).unwrap();
+        let now = Utc::now();
+        assert!(matches!(
+            answer(request, &ctx, now, now).await,
+            Err(OfflineError::Request(message)) if message.contains("include_lineage")
+        ));
+
+        let request: TimeSeriesAggregationRequest = serde_json::from_str(
+            r#"{"datetime_filter": {}, "as_recorded_by": "2024-03-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            answer(request, &ctx, now, now).await,
+            Err(OfflineError::Request(message)) if message.contains("as_recorded_by")
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+};
+
+use crate::{
+    error::ErrorBody,
+    live::LiveUpdate,
+    model::{
+        api_request::{
+            Aggregation, AmountUnit, ExportRecipientUpdate, FillMissing, GroupBy, LegalHoldRequest,
+            MeterOnboarding, MeterProfileUpload, MultiRangeQueryRequest, PowerQueryRequest,
+            ProfileMonth, RangeEnd, ReadOnlyToggle, SavedViewDefinition, SeriesDefinition,
+            SettlementPeriod, SnapshotDiffRequest, TimeSeriesAggregationRequest, TimeSeriesRange,
+            TotalFilter, UploadUrlRequest, UploadedFile,
+        },
+        api_response::{
+            AggregationQueryRecord, AlteredIngestion, BucketChange, BucketCompleteness,
+            BucketPower, BucketSettlement, BucketSource, BuildInfo, CacheHealth, CalendarMonth,
+            CalendarResponse, ChainVerification, ColumnMapping, ColumnRole, DeletedIngestion,
+            DetectedCandidate, ExportJobResponse, ExportRecipientStatus, FormatDetection,
+            FuelTypeRecord, HealthChecks, HistoryHealth, IngestionLineage, IngestionNotification,
+            IngestionSummary, LeaderStatus, MeterOnboardingResponse, MeterOnboardingResult,
+            MeterProfileStored, MonthlyVariance, MultiRangeResponse, PoolHealth, PowerResponse,
+            ProfileBand, QueryJobResponse, QueryResponse, RangeRecords, ReadOnlyStatus,
+            ReadinessResponse, RelationStorage, ReplicationHealth, ResponseCacheHealth,
+            RetentionHealth, RoleCandidate, SavedView, SnapshotDiffResponse, StorageGrowth,
+            StorageResponse, UploadUrlResponse, VarianceResponse, ZonedAggregationRecord,
+        },
+        database::{
+            AuditEntry, IngestionClockDrift, IngestionIssue, IngestionStatus, JobStatus, LegalHold,
+            QueryHistory, QueryStatus, Series,
+        },
+        validation::{FieldError, ValidationErrorResponse},
+    },
+    negotiate::ResponseFormat,
+    route,
+    unit_check::UnitMismatch,
+};
+
+/// Machine-readable contract for the REST API, served at `/api-doc/openapi.json`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Renewable Time Series API"),
+    modifiers(&ApiKeySecurity),
+    paths(
+        route::get_healthz,
+        route::get_readyz,
+        route::get_version,
+        route::post_query_ts,
+        route::post_query_dsl,
+        route::post_query_stream,
+        route::post_query_diff,
+        route::post_query_power,
+        route::post_query_ranges,
+        route::get_calendar,
+        route::get_query_history,
+        route::get_ingestions,
+        route::post_ingestion,
+        route::post_upload_url,
+        route::post_uploaded_file,
+        route::get_ingestion_clock_drift,
+        route::get_ingestion_issues,
+        route::delete_ingestion_by_id,
+        route::get_series_list,
+        route::post_series,
+        route::get_series_by_id,
+        route::put_series,
+        route::delete_series_by_id,
+        route::post_detect_format,
+        route::post_meters_bulk,
+        route::put_meter_profile,
+        route::get_meter_variance,
+        route::post_export,
+        route::get_export,
+        route::download_export,
+        route::get_parquet_export,
+        route::get_live_updates,
+        route::post_graphql,
+        route::get_graphql_playground,
+        route::get_read_only,
+        route::put_read_only,
+        route::put_export_recipient,
+        route::put_legal_hold,
+        route::delete_legal_hold,
+        route::get_audit_log,
+        route::get_storage,
+        route::get_chain_verification,
+        route::put_saved_view,
+        route::post_query_async,
+        route::get_query_job,
+    ),
+    components(schemas(
+        Aggregation,
+        FillMissing,
+        RangeEnd,
+        SettlementPeriod,
+        TimeSeriesRange,
+        TotalFilter,
+        GroupBy,
+        AmountUnit,
+        TimeSeriesAggregationRequest,
+        AggregationQueryRecord,
+        ZonedAggregationRecord,
+        IngestionLineage,
+        BucketCompleteness,
+        BucketSettlement,
+        FuelTypeRecord,
+        BucketSource,
+        QueryResponse,
+        SnapshotDiffRequest,
+        BucketChange,
+        SnapshotDiffResponse,
+        PowerQueryRequest,
+        BucketPower,
+        PowerResponse,
+        MultiRangeQueryRequest,
+        RangeRecords,
+        MultiRangeResponse,
+        CalendarMonth,
+        CalendarResponse,
+        QueryHistory,
+        IngestionSummary,
+        IngestionStatus,
+        IngestionClockDrift,
+        IngestionIssue,
+        DeletedIngestion,
+        SeriesDefinition,
+        Series,
+        DetectedCandidate,
+        ColumnRole,
+        RoleCandidate,
+        ColumnMapping,
+        FormatDetection,
+        MeterOnboarding,
+        MeterOnboardingResult,
+        MeterOnboardingResponse,
+        ProfileMonth,
+        MeterProfileUpload,
+        MeterProfileStored,
+        ProfileBand,
+        MonthlyVariance,
+        VarianceResponse,
+        JobStatus,
+        QueryStatus,
+        ExportJobResponse,
+        QueryJobResponse,
+        PoolHealth,
+        ReplicationHealth,
+        CacheHealth,
+        ResponseCacheHealth,
+        HistoryHealth,
+        RetentionHealth,
+        LeaderStatus,
+        ReadOnlyStatus,
+        ReadOnlyToggle,
+        ExportRecipientUpdate,
+        ExportRecipientStatus,
+        LegalHoldRequest,
+        LegalHold,
+        AuditEntry,
+        StorageResponse,
+        RelationStorage,
+        StorageGrowth,
+        ChainVerification,
+        AlteredIngestion,
+        SavedViewDefinition,
+        SavedView,
+        HealthChecks,
+        ReadinessResponse,
+        BuildInfo,
+        FieldError,
+        ValidationErrorResponse,
+        ErrorBody,
+        ResponseFormat,
+        IngestionNotification,
+        UploadUrlRequest,
+        UploadedFile,
+        UploadUrlResponse,
+        UnitMismatch,
+        LiveUpdate,
+    ))
+)]
+pub struct ApiDoc;
+
+/// Registers the `X-Api-Key` header scheme referenced by authenticated routes and the
+/// `X-Admin-Token` scheme of the admin routes
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+            );
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Admin-Token"))),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use utoipa::OpenApi as _;
+
+    use super::ApiDoc;
+
+    #[test]
+    fn test_openapi_documents_routes() {
+        let doc = ApiDoc::openapi();
+
+        for path in [
+            "/healthz",
+            "/readyz",
+            "/version",
+            "/timeseries/v1/query",
+            "/timeseries/v1/query/dsl",
+            "/timeseries/v1/query/stream",
+            "/timeseries/v1/query/diff",
+            "/timeseries/v1/query/power",
+            "/timeseries/v1/query/ranges",
+            "/timeseries/v1/analytics/calendar/{year}",
+            "/timeseries/v1/query/history",
+            "/timeseries/v1/ingestions",
+            "/timeseries/v1/ingestions/{id}",
+            "/timeseries/v1/ingestions/{id}/clock-drift",
+            "/timeseries/v1/uploads",
+            "/timeseries/v1/uploads/ingest",
+            "/timeseries/v1/series",
+            "/timeseries/v1/series/{id}",
+            "/timeseries/v1/ingest/detect-format",
+            "/timeseries/v1/meters/bulk",
+            "/timeseries/v1/meters/{meter_code}/profile",
+            "/timeseries/v1/meters/{meter_code}/variance",
+            "/timeseries/v1/exports",
+            "/timeseries/v1/exports/{id}",
+            "/timeseries/v1/exports/{id}/download",
+            "/timeseries/v1/export/parquet",
+            "/timeseries/v1/ws",
+            "/graphql",
+            "/admin/v1/read-only",
+            "/admin/v1/api-keys/{id}/export-recipient",
+            "/admin/v1/ingestions/{id}/legal-hold",
+            "/admin/v1/audit-log",
+            "/admin/v1/storage",
+            "/admin/v1/chain/verify",
+            "/admin/v1/views/{name}",
+            "/timeseries/v1/query/async",
+            "/timeseries/v1/jobs/{id}",
+        ] {
+            assert!(doc.paths.paths.contains_key(path), "{path} is undocumented");
+        }
+        let components = doc.components.unwrap();
+        assert!(
+            components
+                .schemas
+                .contains_key("TimeSeriesAggregationRequest")
+        );
+        assert!(components.security_schemes.contains_key("api_key"));
+        assert!(components.security_schemes.contains_key("admin_token"));
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::{db::partitions::ensure_ts_store_partitions, state::AppState};
+
+/// Interval between checks that the coming months have their partitions
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Spawns the task creating the `ts_store` partitions of the current UTC month and the
+/// `months_ahead` after it, at startup and daily after, so readings land in their month's
+/// partition rather than the default one. Maintenance waits for this instance to lead
+/// and pauses while it is read-only.
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    let months_ahead = state.config.partition_months_ahead;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(MAINTENANCE_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            state.leader.leading().await;
+            if state.read_only.is_enabled() {
+                debug!("Read-only, skipping partition maintenance");
+                continue;
+            }
+            match maintain(state.db.primary(), months_ahead).await {
+                Ok(0) => debug!("Partitions of the coming months already exist"),
+                Ok(created) => info!(created, "Created partitions of the coming months"),
+                Err(e) => error!("Unable to create partitions of the coming months: {e}"),
+            }
+        }
+    })
+}
+
+async fn maintain(pg_pool: &Pool, months_ahead: u32) -> Result<usize, String> {
+    let conn = pg_pool.get().await.map_err(|e| e.to_string())?;
+    conn.interact(move |conn| ensure_ts_store_partitions(Utc::now(), months_ahead, conn))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use chrono::TimeDelta;
+
+use crate::model::{
+    api_response::{AggregationQueryRecord, BucketPower},
+    database::BucketEnergy,
+};
+
+const SECONDS_PER_HOUR: i64 = 3600;
+
+/// Converts kWh held by one reading interval into the mean kW over that interval
+fn interval_power(energy: &BigDecimal, interval: TimeDelta) -> BigDecimal {
+    energy * BigDecimal::from(SECONDS_PER_HOUR) / BigDecimal::from(interval.num_seconds().max(1))
+}
+
+/// Derives average and peak power for each bucket from its energy at the native
+/// `interval`. The average only spans the intervals holding a reading, so a partially
+/// covered bucket reports the power generated while it was measured.
+pub fn bucket_power(buckets: Vec<BucketEnergy>, interval: TimeDelta) -> Vec<BucketPower> {
+    buckets
+        .into_iter()
+        .map(|bucket| {
+            let average_kw = bucket
+                .total_amount
+                .as_ref()
+                .filter(|_| bucket.readings > 0)
+                .map(|total| interval_power(total, interval) / BigDecimal::from(bucket.readings));
+            BucketPower {
+                datetime: bucket.datetime,
+                average_kw,
+                max_kw: bucket
+                    .peak_amount
+                    .as_ref()
+                    .map(|peak| interval_power(peak, interval)),
+                energy_kwh: bucket.total_amount,
+                readings: bucket.readings,
+            }
+        })
+        .collect()
+}
+
+/// Energy totals of the buckets, as an aggregation query returns them
+pub fn energy_records(power: &[BucketPower]) -> Vec<AggregationQueryRecord> {
+    power
+        .iter()
+        .map(|bucket| AggregationQueryRecord {
+            datetime: bucket.datetime,
+            total_amount: bucket.energy_kwh.clone(),
+        })
+        .collect()
+}
+
+/// Lines `power` up with `records`, one entry per record in the same order. Buckets
+/// added when filling missing buckets hold no readings and so report no power.
+pub fn per_record(records: &[AggregationQueryRecord], power: Vec<BucketPower>) -> Vec<BucketPower> {
+    let mut power: HashMap<_, _> = power
+        .into_iter()
+        .map(|bucket| (bucket.datetime, bucket))
+        .collect();
+    records
+        .iter()
+        .map(|record| {
+            power
+                .remove(&record.datetime)
+                .unwrap_or_else(|| BucketPower {
+                    datetime: record.datetime,
+                    energy_kwh: record.total_amount.clone(),
+                    average_kw: None,
+                    max_kw: None,
+                    readings: 0,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::{bucket_power, energy_records, per_record};
+    use crate::model::{
+        api_request::{Aggregation, FillMissing},
+        database::BucketEnergy,
+    };
+
+    fn energy(total: i64, peak: i64, readings: i64) -> BucketEnergy {
+        BucketEnergy {
+            datetime: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            total_amount: Some(BigDecimal::from(total)),
+            peak_amount: Some(BigDecimal::from(peak)),
+            readings,
+        }
+    }
+
+    #[test]
+    fn test_power_uses_the_native_reading_interval() {
+        // Four half-hourly readings of 10, 20, 30 and 40 kWh
+        let power = bucket_power(vec![energy(100, 40, 4)], TimeDelta::minutes(30));
+        assert_eq!(power[0].energy_kwh, Some(BigDecimal::from(100)));
+        assert_eq!(power[0].average_kw, Some(BigDecimal::from(50)));
+        assert_eq!(power[0].max_kw, Some(BigDecimal::from(80)));
+
+        let hourly = bucket_power(vec![energy(100, 40, 4)], TimeDelta::hours(1));
+        assert_eq!(hourly[0].average_kw, Some(BigDecimal::from(25)));
+        assert_eq!(hourly[0].max_kw, Some(BigDecimal::from(40)));
+    }
+
+    #[test]
+    fn test_power_is_empty_without_readings() {
+        let mut empty = energy(0, 0, 0);
+        empty.total_amount = None;
+        empty.peak_amount = None;
+
+        let power = bucket_power(vec![empty], TimeDelta::hours(1));
+        assert_eq!(power[0].average_kw, None);
+        assert_eq!(power[0].max_kw, None);
+    }
+
+    #[test]
+    fn test_power_lines_up_with_filled_records() {
+        let day = |d| Utc.with_ymd_and_hms(2025, 1, d, 0, 0, 0).unwrap();
+        let mut later = energy(48, 4, 24);
+        later.datetime = day(3);
+        let mut first = energy(24, 2, 24);
+        first.datetime = day(1);
+
+        let power = bucket_power(vec![first, later], TimeDelta::hours(1));
+        let records = crate::bucket::fill_missing(
+            Aggregation::DayInMonth,
+            Some(day(1)),
+            Some(day(4)),
+            energy_records(&power),
+            FillMissing::Zero,
+        );
+        let aligned = per_record(&records, power);
+
+        let summary: Vec<_> = aligned
+            .iter()
+            .map(|bucket| (bucket.datetime, bucket.average_kw.clone(), bucket.readings))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (day(1), Some(BigDecimal::from(1)), 24),
+                (day(2), None, 0),
+                (day(3), Some(BigDecimal::from(2)), 24),
+            ]
+        );
+        assert_eq!(aligned[1].energy_kwh, Some(BigDecimal::from(0)));
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+use chrono::Utc;
+use tracing::warn;
+
+use crate::{error::ApiError, model::api_response::ReadOnlyStatus};
+
+/// Instance wide switch that rejects ingestion and other writes while queries keep being
+/// served, flipped around database failovers and maintenance windows
+#[derive(Clone)]
+pub struct ReadOnlyMode {
+    status: Arc<RwLock<ReadOnlyStatus>>,
+}
+
+impl ReadOnlyMode {
+    pub fn new(read_only: bool) -> Self {
+        let status = ReadOnlyStatus {
+            read_only,
+            reason: read_only.then(|| "read_only set in config".to_string()),
+            since: Utc::now(),
+        };
+        Self {
+            status: Arc::new(RwLock::new(status)),
+        }
+    }
+
+    pub fn status(&self) -> ReadOnlyStatus {
+        self.status.read().expect("read-only lock poisoned").clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status
+            .read()
+            .expect("read-only lock poisoned")
+            .read_only
+    }
+
+    /// Switches the mode, `since` only moves when the mode actually changes
+    pub fn set(&self, read_only: bool, reason: Option<String>) -> ReadOnlyStatus {
+        let mut status = self.status.write().expect("read-only lock poisoned");
+        if status.read_only != read_only {
+            status.since = Utc::now();
+        }
+        status.read_only = read_only;
+        status.reason = reason;
+        warn!(read_only, reason = ?status.reason, "Read-only mode changed");
+        status.clone()
+    }
+
+    pub fn ensure_writable(&self) -> Result<(), ApiError> {
+        if self.is_enabled() {
+            return Err(ApiError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
+/// Rejects the request with 503 while the instance is read-only
+pub async fn reject_writes(
+    State(mode): State<ReadOnlyMode>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match mode.ensure_writable() {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReadOnlyMode;
+
+    #[test]
+    fn test_toggle_keeps_since_until_mode_changes() {
+        let mode = ReadOnlyMode::new(false);
+        assert!(mode.ensure_writable().is_ok());
+
+        let enabled = mode.set(true, Some("failover".to_string()));
+        assert!(mode.is_enabled());
+        assert!(mode.ensure_writable().is_err());
+
+        let updated = mode.set(true, Some("failover, step 2".to_string()));
+        assert_eq!(updated.since, enabled.since);
+        assert_eq!(mode.status().reason.as_deref(), Some("failover, step 2"));
+
+        let disabled = mode.set(false, None);
+        assert!(disabled.since >= enabled.since);
+        assert!(mode.ensure_writable().is_ok());
+    }
+}
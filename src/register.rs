@@ -0,0 +1,178 @@
+use std::{env, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::model::csv::CSVRecord;
+
+/// Whether a seed file holds energy per interval or ever-increasing register readings
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ReadingKind {
+    #[default]
+    Interval,
+    Cumulative,
+}
+
+impl FromStr for ReadingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "interval" => Ok(Self::Interval),
+            "cumulative" => Ok(Self::Cumulative),
+            other => Err(format!("unknown reading kind {other}")),
+        }
+    }
+}
+
+/// Register settings for a seed file, read from `SEED_READING_KIND` and `SEED_REGISTER_ROLLOVER`
+#[derive(Debug, Clone, Default)]
+pub struct RegisterConfig {
+    pub reading_kind: ReadingKind,
+    pub rollover_at: Option<BigDecimal>,
+}
+
+impl RegisterConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let reading_kind = match env::var("SEED_READING_KIND") {
+            Ok(kind) => kind.parse()?,
+            Err(_) => ReadingKind::default(),
+        };
+        let rollover_at = match env::var("SEED_REGISTER_ROLLOVER") {
+            Ok(value) => Some(
+                BigDecimal::from_str(&value)
+                    .map_err(|e| format!("invalid SEED_REGISTER_ROLLOVER: {e}"))?,
+            ),
+            Err(_) => None,
+        };
+        Ok(Self {
+            reading_kind,
+            rollover_at,
+        })
+    }
+}
+
+/// Discontinuity detected while converting register readings
+#[derive(Debug, PartialEq, Eq)]
+pub enum RegisterEvent {
+    /// The register wrapped past its maximum value back through zero
+    Rollover(DateTime<Utc>),
+    /// The register dropped without wrapping, e.g. after a meter replacement
+    Reset(DateTime<Utc>),
+}
+
+/// Converts cumulative register readings into interval deltas.
+///
+/// Readings are ordered by time and the first reading only establishes the baseline.
+/// A drop in value is treated as a rollover when `rollover_at` is known and the wrapped
+/// delta is smaller than half the register range, otherwise as a reset where the new
+/// reading is the energy accumulated since the meter restarted from zero.
+pub fn to_interval(
+    mut readings: Vec<CSVRecord>,
+    rollover_at: Option<&BigDecimal>,
+) -> (Vec<CSVRecord>, Vec<RegisterEvent>) {
+    readings.sort_by_key(|reading| reading.datetime);
+
+    let mut deltas = Vec::with_capacity(readings.len().saturating_sub(1));
+    let mut events = Vec::new();
+    let mut readings = readings.into_iter();
+    let Some(mut previous) = readings.next() else {
+        return (deltas, events);
+    };
+
+    for reading in readings {
+        if reading.datetime == previous.datetime {
+            warn!(datetime = %reading.datetime, "Skipping duplicate register reading");
+            continue;
+        }
+
+        let amount = if reading.amount >= previous.amount {
+            &reading.amount - &previous.amount
+        } else {
+            let wrapped = rollover_at
+                .map(|limit| (limit, limit - &previous.amount + &reading.amount))
+                .filter(|(limit, wrapped)| wrapped * BigDecimal::from(2) < **limit)
+                .map(|(_, wrapped)| wrapped);
+            if let Some(wrapped) = wrapped {
+                warn!(datetime = %reading.datetime, "Register rollover detected");
+                events.push(RegisterEvent::Rollover(reading.datetime));
+                wrapped
+            } else {
+                warn!(datetime = %reading.datetime, "Register reset detected");
+                events.push(RegisterEvent::Reset(reading.datetime));
+                reading.amount.clone()
+            }
+        };
+
+        deltas.push(CSVRecord {
+            datetime: reading.datetime,
+            amount,
+            extra: reading.extra.clone(),
+        });
+        previous = reading;
+    }
+
+    (deltas, events)
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{RegisterEvent, to_interval};
+    use crate::model::csv::CSVRecord;
+
+    fn hour(h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, h, 0, 0).unwrap()
+    }
+
+    fn readings(values: &[i64]) -> Vec<CSVRecord> {
+        values
+            .iter()
+            .zip(0..)
+            .map(|(value, h)| CSVRecord {
+                datetime: hour(h),
+                amount: BigDecimal::from(*value),
+                extra: None,
+            })
+            .collect()
+    }
+
+    fn amounts(records: &[CSVRecord]) -> Vec<BigDecimal> {
+        records.iter().map(|r| r.amount.clone()).collect()
+    }
+
+    #[test]
+    fn test_monotonic_register_to_deltas() {
+        let mut input = readings(&[100, 150, 150, 190]);
+        input.reverse();
+
+        let (deltas, events) = to_interval(input, None);
+        assert_eq!(amounts(&deltas), [50, 0, 40].map(BigDecimal::from));
+        assert_eq!(deltas[0].datetime, hour(1));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_register_rollover() {
+        let limit = BigDecimal::from(1000);
+        let (deltas, events) = to_interval(readings(&[980, 995, 10, 30]), Some(&limit));
+
+        assert_eq!(amounts(&deltas), [15, 15, 20].map(BigDecimal::from));
+        assert_eq!(events, vec![RegisterEvent::Rollover(hour(2))]);
+    }
+
+    #[test]
+    fn test_register_reset() {
+        let limit = BigDecimal::from(1000);
+        let (deltas, events) = to_interval(readings(&[400, 450, 5, 25]), Some(&limit));
+        assert_eq!(amounts(&deltas), [50, 5, 20].map(BigDecimal::from));
+        assert_eq!(events, vec![RegisterEvent::Reset(hour(2))]);
+
+        let (deltas, events) = to_interval(readings(&[980, 995, 10]), None);
+        assert_eq!(amounts(&deltas), [15, 10].map(BigDecimal::from));
+        assert_eq!(events, vec![RegisterEvent::Reset(hour(2))]);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use lru::LruCache;
+
+use crate::{
+    config::AppConfig,
+    model::{
+        api_request::{Aggregation, AmountUnit, FillMissing, GroupBy},
+        api_response::{
+            AggregationQueryRecord, BucketCompleteness, BucketPower, BucketSettlement,
+            BucketSource, FuelTypeRecord, IngestionLineage, ResponseCacheHealth,
+        },
+    },
+};
+
+/// Everything an aggregation response depends on besides the stored readings, with the
+/// range resolved to its half-open bounds and a series name to its id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryKey {
+    pub aggregation_kind: Aggregation,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    pub fill_missing: Option<FillMissing>,
+    pub include_lineage: bool,
+    pub include_completeness: bool,
+    pub include_power: bool,
+    pub include_settlement: bool,
+    pub include_sources: bool,
+    /// `having` bounds as exact decimals, as they are compared
+    pub having: Option<[Option<BigDecimal>; 4]>,
+    pub group_by: Vec<GroupBy>,
+    pub unit: AmountUnit,
+    pub timezone: Option<Tz>,
+    pub series_id: Option<i64>,
+    pub as_recorded_by: Option<DateTime<Utc>>,
+}
+
+/// An aggregation and the extras it asked for, ready to render in any response format
+#[derive(Debug, Clone)]
+pub struct AggregationResult {
+    pub executed_at: DateTime<Utc>,
+    /// Bucket totals in the requested unit, starting in UTC
+    pub records: Vec<AggregationQueryRecord>,
+    pub lineage: Option<Vec<IngestionLineage>>,
+    pub completeness: Option<Vec<BucketCompleteness>>,
+    pub power: Option<Vec<BucketPower>>,
+    pub settlement: Option<Vec<BucketSettlement>>,
+    pub breakdown: Option<Vec<FuelTypeRecord>>,
+    pub sources: Option<Vec<BucketSource>>,
+}
+
+/// Whether a response was served from the [`ResponseCache`], sent as `X-Cache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entries {
+    results: LruCache<QueryKey, (Instant, Arc<AggregationResult>)>,
+    /// Bumped on every invalidation, so a query that started before new readings were
+    /// written does not cache its now stale result
+    generation: u64,
+}
+
+/// In-process cache of aggregation results, least recently used results evicted beyond
+/// its capacity. Results live for a TTL and every result is dropped when readings are
+/// ingested or deleted through this instance, the TTL bounding how stale results may be
+/// after writes through other instances sharing the database.
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(Entries {
+                results: LruCache::new(capacity),
+                generation: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Builds the cache when `response_cache_entries` is set, otherwise it is disabled
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        let capacity = NonZeroUsize::new(config.response_cache_entries)?;
+        Some(Self::new(capacity, config.response_cache_ttl()))
+    }
+
+    /// The cached result for `key` unless it has expired, counted as a hit or a miss
+    pub fn get(&self, key: &QueryKey) -> Option<Arc<AggregationResult>> {
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        let result = match entries.results.get(key) {
+            Some((cached_at, result)) if cached_at.elapsed() < self.ttl => Some(Arc::clone(result)),
+            Some(_) => {
+                entries.results.pop(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if result.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Generation to hand back to [`Self::insert`] with a result computed after this call
+    pub fn generation(&self) -> u64 {
+        self.entries
+            .lock()
+            .expect("response cache lock poisoned")
+            .generation
+    }
+
+    /// Caches `result` unless the cache was invalidated since `generation` was taken
+    pub fn insert(&self, key: QueryKey, generation: u64, result: Arc<AggregationResult>) {
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        if entries.generation == generation {
+            entries.results.put(key, (Instant::now(), result));
+        }
+    }
+
+    /// Drops every cached result, called whenever stored readings change
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        entries.results.clear();
+        entries.generation += 1;
+    }
+
+    pub fn health(&self) -> ResponseCacheHealth {
+        let entries = self.entries.lock().expect("response cache lock poisoned");
+        ResponseCacheHealth {
+            enabled: true,
+            entries: entries.results.len(),
+            capacity: entries.results.cap().get(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+    use chrono::Utc;
+
+    use super::{AggregationResult, QueryKey, ResponseCache};
+    use crate::model::api_request::{Aggregation, AmountUnit};
+
+    fn key(aggregation_kind: Aggregation) -> QueryKey {
+        QueryKey {
+            aggregation_kind,
+            from_date: None,
+            to_date: None,
+            fill_missing: None,
+            include_lineage: false,
+            include_completeness: false,
+            include_power: false,
+            include_settlement: false,
+            include_sources: false,
+            having: None,
+            group_by: Vec::new(),
+            unit: AmountUnit::KWh,
+            timezone: None,
+            series_id: None,
+            as_recorded_by: None,
+        }
+    }
+
+    fn result() -> Arc<AggregationResult> {
+        Arc::new(AggregationResult {
+            executed_at: Utc::now(),
+            records: Vec::new(),
+            lineage: None,
+            completeness: None,
+            power: None,
+            settlement: None,
+            breakdown: None,
+            sources: None,
+        })
+    }
+
+    fn cache(capacity: usize, ttl: Duration) -> ResponseCache {
+        ResponseCache::new(NonZeroUsize::new(capacity).unwrap(), ttl)
+    }
+
+    #[test]
+    fn test_results_are_cached_until_invalidated() {
+        let cache = cache(2, Duration::from_secs(60));
+        assert!(cache.get(&key(Aggregation::Hourly)).is_none());
+
+        let generation = cache.generation();
+        let hourly = result();
+        cache.insert(key(Aggregation::Hourly), generation, Arc::clone(&hourly));
+        assert!(Arc::ptr_eq(
+            &cache.get(&key(Aggregation::Hourly)).unwrap(),
+            &hourly
+        ));
+        assert!(cache.get(&key(Aggregation::Monthly)).is_none());
+
+        cache.invalidate();
+        assert!(cache.get(&key(Aggregation::Hourly)).is_none());
+        // Computed before the invalidation, so possibly missing the new readings
+        cache.insert(key(Aggregation::Hourly), generation, result());
+        assert!(cache.get(&key(Aggregation::Hourly)).is_none());
+
+        let health = cache.health();
+        assert_eq!((health.hits, health.misses, health.entries), (1, 4, 0));
+    }
+
+    #[test]
+    fn test_results_expire_and_are_evicted() {
+        let expired = cache(2, Duration::ZERO);
+        expired.insert(key(Aggregation::Hourly), 0, result());
+        assert!(expired.get(&key(Aggregation::Hourly)).is_none());
+        assert_eq!(expired.health().entries, 0);
+
+        let full = cache(2, Duration::from_secs(60));
+        for kind in [
+            Aggregation::Hourly,
+            Aggregation::Weekly,
+            Aggregation::Monthly,
+        ] {
+            full.insert(key(kind), 0, result());
+        }
+        assert!(full.get(&key(Aggregation::Hourly)).is_none());
+        assert!(full.get(&key(Aggregation::Monthly)).is_some());
+        assert_eq!(full.health().entries, 2);
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use deadpool_diesel::postgres::Pool;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info};
+
+use crate::{
+    db::retention::purge_before,
+    model::{api_response::RetentionHealth, database::RetentionPurge},
+    state::AppState,
+};
+
+/// Purges readings and query history older than `retention_days` on an interval,
+/// dropping whole month partitions of `ts_store` where it can, and keeps the outcome for
+/// `/readyz`. With `retention_dry_run` the rows due to go are only counted and logged.
+#[derive(Clone, Default)]
+pub struct RetentionJob {
+    health: Arc<RwLock<RetentionHealth>>,
+}
+
+impl RetentionJob {
+    pub fn health(&self) -> RetentionHealth {
+        self.health.read().expect("retention lock poisoned").clone()
+    }
+
+    /// Spawns the purge task of `state`, nothing is spawned without `retention_days`.
+    /// Purges wait for this instance to lead and pause while it is read-only.
+    pub fn spawn(&self, state: AppState) {
+        let Some(days) = state.config.retention_days else {
+            return;
+        };
+        let dry_run = state.config.retention_dry_run;
+        {
+            let mut health = self.health.write().expect("retention lock poisoned");
+            health.enabled = true;
+            health.dry_run = dry_run;
+        }
+        let interval = state.config.retention_interval();
+        info!(
+            days,
+            dry_run,
+            interval_secs = interval.as_secs(),
+            "Retention enabled"
+        );
+
+        let job = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                state.leader.leading().await;
+                if state.read_only.is_enabled() {
+                    debug!("Read-only, skipping retention");
+                    continue;
+                }
+                job.run(&state, days, dry_run).await;
+            }
+        });
+    }
+
+    async fn run(&self, state: &AppState, days: u32, dry_run: bool) {
+        let now = Utc::now();
+        let cutoff = now - TimeDelta::days(i64::from(days));
+        let purge = match purge(state.db.primary(), cutoff, dry_run).await {
+            Ok(purge) => purge,
+            Err(e) => {
+                error!(%cutoff, "Unable to purge expired rows: {e}");
+                return;
+            }
+        };
+
+        if dry_run {
+            info!(
+                %cutoff,
+                readings = purge.readings,
+                query_history = purge.query_history,
+                held = purge.held,
+                "Retention dry run, rows due to be purged"
+            );
+        } else {
+            info!(
+                %cutoff,
+                readings = purge.readings,
+                query_history = purge.query_history,
+                partitions = purge.partitions,
+                held = purge.held,
+                "Purged expired rows"
+            );
+            if purge.readings > 0 {
+                state.invalidate_response_cache().await;
+            }
+        }
+
+        let mut health = self.health.write().expect("retention lock poisoned");
+        health.last_run = Some(now);
+        health.last_cutoff = Some(cutoff);
+        health.last_readings = purge.readings;
+        health.last_query_history = purge.query_history;
+        health.last_held = purge.held;
+        if !dry_run {
+            health.readings_purged += purge.readings;
+            health.query_history_purged += purge.query_history;
+            health.partitions_dropped += purge.partitions;
+        }
+    }
+}
+
+/// Purges, or with `dry_run` counts, readings and query history older than `cutoff`
+pub async fn purge(
+    pg_pool: &Pool,
+    cutoff: DateTime<Utc>,
+    dry_run: bool,
+) -> Result<RetentionPurge, String> {
+    let conn = pg_pool.get().await.map_err(|e| e.to_string())?;
+    conn.interact(move |conn| purge_before(cutoff, dry_run, conn))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,97 @@
+use std::sync::OnceLock;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+static POLICY: OnceLock<RoundingPolicy> = OnceLock::new();
+
+/// How decimal amounts are rounded to the configured scale
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Round half to even, also known as banker's rounding
+    #[default]
+    HalfEven,
+    HalfUp,
+    HalfDown,
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+}
+
+impl From<RoundingMode> for bigdecimal::RoundingMode {
+    fn from(mode: RoundingMode) -> Self {
+        match mode {
+            RoundingMode::HalfEven => Self::HalfEven,
+            RoundingMode::HalfUp => Self::HalfUp,
+            RoundingMode::HalfDown => Self::HalfDown,
+            RoundingMode::Up => Self::Up,
+            RoundingMode::Down => Self::Down,
+            RoundingMode::Ceiling => Self::Ceiling,
+            RoundingMode::Floor => Self::Floor,
+        }
+    }
+}
+
+/// Rounding applied to every amount leaving the service and to calculations derived
+/// from them, so reported figures reconcile with billing systems
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct RoundingPolicy {
+    pub mode: RoundingMode,
+    /// Decimal places to keep, amounts are left untouched when `None`
+    pub scale: Option<i64>,
+}
+
+impl RoundingPolicy {
+    pub fn apply(&self, value: &BigDecimal) -> BigDecimal {
+        match self.scale {
+            Some(scale) => value.with_scale_round(scale, self.mode.into()),
+            None => value.clone(),
+        }
+    }
+}
+
+/// Sets the process wide policy, only the first call takes effect
+pub fn install(policy: RoundingPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// The installed policy, or no rounding when none has been installed
+pub fn current() -> RoundingPolicy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr as _;
+
+    use bigdecimal::BigDecimal;
+    use test_case::test_case;
+
+    use super::{RoundingMode, RoundingPolicy};
+
+    #[test_case(RoundingMode::HalfEven, "2.125", "2.12")]
+    #[test_case(RoundingMode::HalfEven, "2.135", "2.14")]
+    #[test_case(RoundingMode::HalfUp, "2.125", "2.13")]
+    #[test_case(RoundingMode::HalfDown, "2.125", "2.12")]
+    #[test_case(RoundingMode::Up, "2.121", "2.13")]
+    #[test_case(RoundingMode::Down, "2.129", "2.12")]
+    #[test_case(RoundingMode::Ceiling, "-2.129", "-2.12")]
+    #[test_case(RoundingMode::Floor, "-2.121", "-2.13")]
+    fn test_rounding_modes(mode: RoundingMode, value: &str, expected: &str) {
+        let policy = RoundingPolicy {
+            mode,
+            scale: Some(2),
+        };
+        let rounded = policy.apply(&BigDecimal::from_str(value).unwrap());
+        assert_eq!(rounded, BigDecimal::from_str(expected).unwrap());
+        assert_eq!(rounded.to_string(), expected);
+    }
+
+    #[test]
+    fn test_policy_without_scale_is_identity() {
+        let value = BigDecimal::from_str("9000.12345").unwrap();
+        assert_eq!(RoundingPolicy::default().apply(&value), value);
+    }
+}
@@ -1,72 +1,3225 @@
+use std::{convert::Infallible, fmt::Display, io, sync::Arc};
+
+#[cfg(feature = "redis-cache")]
+use crate::cache::{CacheError, SharedCache};
+#[cfg(feature = "compressed-storage")]
+use crate::db::compressed_storage::stream_readings;
+#[cfg(not(feature = "compressed-storage"))]
+use crate::db::query::stream_readings;
+#[cfg(feature = "redis-cache")]
+use axum::http::HeaderValue;
+
 use crate::{
-    db::query::{aggregate_ts_query, query_request_history},
+    auth::ApiKey,
+    bucket,
+    build_info::build_info,
+    calendar, columnar,
+    config::AppConfig,
+    db::{
+        Pools, TimedPool,
+        api_keys::{export_recipient, set_export_recipient},
+        chain::verify_chain,
+        export_jobs::{create_export_job, get_export_job},
+        health::replication_lag_seconds,
+        legal_holds::{audit_entries, find_hold, place_hold, record_audit, release_hold},
+        meters::{load_meter_profile, onboard_meters, replace_meter_profile},
+        query::{
+            aggregate_ts_query, aggregate_ts_query_having, bucket_energy, bucket_point_counts,
+            bucket_sources, delete_ingestion, diff_ts_query, find_source_ingestion,
+            fuel_type_breakdown, has_ranked_ingestions, ingestion_ranks, monthly_actuals,
+            multi_range_ts_query, query_clock_drift, query_ingestion_issues, query_ingestions,
+            query_lineage, query_request_history, reading_extent, scan_ts_query, stream_ts_query,
+        },
+        query_jobs::{
+            self, create_query_job, mark_query_complete, mark_query_failed, mark_query_running,
+        },
+        seed_database::{
+            check_units, correct_ingestion, flag_unit_mismatch, insert_ingestion_with_drift,
+            prepare_readings, record_issues,
+        },
+        series::{
+            create_series, delete_series, find_series_id, get_series, list_series, update_series,
+        },
+        storage_stats::{database_size, relation_sizes, storage_samples_since},
+        views::save_view,
+        with_statement_timeout,
+    },
+    deadline::Deadline,
+    decimal, detect, diff, dsl,
+    encryption::ExportRecipient,
+    error::{ApiError, ErrorBody},
+    export::{self, ExportQuery, run_export_job},
+    file_reader::{self, ReadingsFormat, meter_csv_rows},
+    graphql, health,
+    history::HistoryWriter,
+    live::{self, IngestionEvents, LiveUpdate},
     model::{
-        api_request::{TimeSeriesAggregationRequest, TimeSeriesRange},
-        api_response::QueryResponse,
+        api_request::{
+            Aggregation, AmountUnit, AuditLogParams, DetectFormatParams, ExportDownloadParams,
+            ExportRecipientUpdate, FillMissing, GroupBy, HistoryFilter, HistoryPageParams,
+            IngestionMode, IngestionUploadParams, LegalHoldRequest, MeterOnboarding,
+            MeterProfileUpload, MultiRangeQueryRequest, ParquetExportParams, PowerQueryRequest,
+            ReadOnlyToggle, SavedViewDefinition, SeriesDefinition, SnapshotDiffRequest,
+            StorageParams, TimeSeriesAggregationRequest, TimeSeriesRange, TotalFilter,
+            UploadUrlRequest, UploadedFile, VarianceParams,
+        },
+        api_response::{
+            AggregationQueryRecord, BuildInfo, CalendarResponse, ChainVerification,
+            DeletedIngestion, ExportJobResponse, ExportRecipientStatus, FormatDetection,
+            HealthChecks, HistoryHealth, IngestionNotification, IngestionSummary,
+            MeterOnboardingResponse, MeterProfileStored, MultiRangeResponse, PowerResponse,
+            QueryJobResponse, QueryResponse, RangeRecords, ReadOnlyStatus, ReadinessResponse,
+            ResponseCacheHealth, SavedView, SnapshotDiffResponse, StorageResponse,
+            UploadUrlResponse, VarianceResponse,
+        },
+        csv::CsvSchema,
+        database::{
+            AuditAction, AuditEntry, IngestionClockDrift, IngestionCorrection, IngestionIssue,
+            JobStatus, LegalHold, QueryHistory, Series,
+        },
+        validation::{
+            ValidJson, ValidQuery, Validate as _, ValidationErrorResponse, ValidationLimits,
+        },
     },
+    negotiate::{ARROW_STREAM, FormatParams, ResponseFormat},
+    power,
+    read_only::ReadOnlyMode,
+    response_cache::{AggregationResult, CacheStatus, QueryKey},
+    rounding, settlement, shadow,
+    state::AppState,
+    storage_stats, variance,
 };
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
-use chrono::Utc;
-use deadpool_diesel::postgres::Pool;
-use serde_json::json;
-use tracing::{error, info};
+use async_graphql::http::{GraphQLPlaygroundConfig, playground_source};
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{FromRef as _, Path, Query, State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode, header},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use diesel::result::DatabaseErrorKind;
+use tokio::{io::DuplexStream, sync::mpsc};
+use tokio_stream::{StreamExt as _, wrappers::ReceiverStream};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use tracing::{debug, error, info, warn};
+
+/// Bytes buffered between a blocking encoder and the response body
+const ENCODER_PIPE_BYTES: usize = 64 * 1024;
+/// Events buffered between a streamed query and a slow client before the query waits
+const STREAM_BUFFER_EVENTS: usize = 1024;
+/// Reports whether an aggregation was answered from the response cache
+const X_CACHE: &str = "x-cache";
+/// Marks an aggregation answered by the hot cache while Postgres was unavailable
+const X_DEGRADED: &str = "x-degraded";
+/// Earliest reading a degraded answer could draw on
+const X_DEGRADED_WINDOW_START: &str = "x-degraded-window-start";
+/// Cursor resuming a paginated listing after its last entry, sent when more may follow
+const X_NEXT_CURSOR: &str = "x-next-cursor";
+/// [`crate::cursor::CursorSigner`] scope of the query history listing
+const HISTORY_CURSOR: &str = "query_history";
+
+#[utoipa::path(
+    get,
+    path = "/admin/v1/read-only",
+    security(("admin_token" = [])),
+    tag = "admin",
+    responses(
+        (status = 200, description = "Current read-only mode", body = ReadOnlyStatus),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+    )
+)]
+pub async fn get_read_only(State(read_only): State<ReadOnlyMode>) -> Json<ReadOnlyStatus> {
+    Json(read_only.status())
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/v1/read-only",
+    security(("admin_token" = [])),
+    tag = "admin",
+    request_body = ReadOnlyToggle,
+    responses(
+        (status = 200, description = "Read-only mode after the change", body = ReadOnlyStatus),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+    )
+)]
+pub async fn put_read_only(
+    State(read_only): State<ReadOnlyMode>,
+    Json(toggle): Json<ReadOnlyToggle>,
+) -> Json<ReadOnlyStatus> {
+    Json(read_only.set(toggle.read_only, toggle.reason))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/v1/api-keys/{id}/export-recipient",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(("id" = i64, Path, description = "API key id")),
+    request_body = ExportRecipientUpdate,
+    responses(
+        (status = 200, description = "Encryption of the key's exports after the change", body = ExportRecipientStatus),
+        (status = 400, description = "Recipient is not an age or PGP public key", body = ErrorBody),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 404, description = "Unknown API key", body = ErrorBody),
+    )
+)]
+pub async fn put_export_recipient(
+    State(state): State<AppState>,
+    Path(api_key_id): Path<i64>,
+    Json(update): Json<ExportRecipientUpdate>,
+) -> Result<Json<ExportRecipientStatus>, ApiError> {
+    let recipient = update
+        .recipient
+        .as_deref()
+        .map(ExportRecipient::parse)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+    let encryption = recipient.as_ref().map(ExportRecipient::extension);
+
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+    let updated = conn
+        .interact(move |conn| {
+            let key = recipient.as_ref().map(ExportRecipient::key);
+            set_export_recipient(api_key_id, key, conn)
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    if !updated {
+        return Err(ApiError::NotFound("API key"));
+    }
+    info!(api_key_id, encryption, "Export recipient changed");
+    Ok(Json(ExportRecipientStatus {
+        api_key_id,
+        encryption,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/v1/ingestions/{id}/legal-hold",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(("id" = i64, Path, description = "Ingestion id")),
+    request_body = LegalHoldRequest,
+    responses(
+        (status = 200, description = "Hold placed, or its reason replaced", body = LegalHold),
+        (status = 400, description = "Missing reason", body = ErrorBody),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 404, description = "Unknown ingestion", body = ErrorBody),
+    )
+)]
+pub async fn put_legal_hold(
+    State(db): State<Pools>,
+    Path(ingestion_id): Path<i64>,
+    Json(request): Json<LegalHoldRequest>,
+) -> Result<Json<LegalHold>, ApiError> {
+    let reason = request.reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(ApiError::BadRequest(
+            "a legal hold needs a reason".to_string(),
+        ));
+    }
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+    let hold = conn
+        .interact(move |conn| place_hold(ingestion_id, &reason, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("ingestion"))?;
+    info!(ingestion_id, reason = hold.reason, "Legal hold placed");
+    Ok(Json(hold))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/v1/ingestions/{id}/legal-hold",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(("id" = i64, Path, description = "Ingestion id")),
+    responses(
+        (status = 200, description = "The hold released", body = LegalHold),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 404, description = "Ingestion not under legal hold", body = ErrorBody),
+    )
+)]
+pub async fn delete_legal_hold(
+    State(db): State<Pools>,
+    Path(ingestion_id): Path<i64>,
+) -> Result<Json<LegalHold>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+    let hold = conn
+        .interact(move |conn| release_hold(ingestion_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("legal hold"))?;
+    info!(ingestion_id, "Legal hold released");
+    Ok(Json(hold))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/v1/audit-log",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(AuditLogParams),
+    responses(
+        (status = 200, description = "Legal holds placed and released, and the deletions and purges they refused, newest first", body = [AuditEntry]),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+    )
+)]
+pub async fn get_audit_log(
+    State(db): State<Pools>,
+    Query(params): Query<AuditLogParams>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let limit = params.limit();
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+    let entries = conn
+        .interact(move |conn| audit_entries(params.ingestion_id, limit, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    Ok(Json(entries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/v1/storage",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(StorageParams),
+    responses(
+        (status = 200, description = "Sizes, row estimates and daily growth of every table and partition", body = StorageResponse),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_storage(
+    State(db): State<Pools>,
+    Query(params): Query<StorageParams>,
+) -> Result<Json<StorageResponse>, ApiError> {
+    let now = Utc::now();
+    let since = now.date_naive() - TimeDelta::days(i64::from(params.days()));
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+    let (database_bytes, sizes, samples) = conn
+        .interact(move |conn| {
+            Ok::<_, diesel::result::Error>((
+                database_size(conn)?,
+                relation_sizes(now.date_naive(), conn)?,
+                storage_samples_since(since, conn)?,
+            ))
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    Ok(Json(storage_stats::report(
+        now,
+        database_bytes,
+        sizes,
+        &samples,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/v1/chain/verify",
+    security(("admin_token" = [])),
+    tag = "admin",
+    responses(
+        (status = 200, description = "Whether the hash chain over ingestions links up and matches the readings held", body = ChainVerification),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_chain_verification(
+    State(db): State<Pools>,
+) -> Result<Json<ChainVerification>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+    let verification = conn
+        .interact(verify_chain)
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    Ok(Json(verification))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/v1/views/{name}",
+    security(("admin_token" = [])),
+    tag = "admin",
+    params(("name" = String, Path, description = "Lowercase letters, digits and underscores, the view is `renewable.saved_{name}`")),
+    request_body = SavedViewDefinition,
+    responses(
+        (status = 200, description = "View created, or replaced and refreshed", body = SavedView),
+        (status = 400, description = "Invalid view name", body = ErrorBody),
+        (status = 401, description = "Missing or invalid admin token", body = ErrorBody),
+        (status = 403, description = "Admin API disabled", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn put_saved_view(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(definition): Json<SavedViewDefinition>,
+) -> Result<Json<SavedView>, ApiError> {
+    state.read_only.ensure_writable()?;
+    if !SavedViewDefinition::valid_name(&name) {
+        return Err(ApiError::BadRequest(format!(
+            "view names are up to {} lowercase letters, digits and underscores starting with a letter",
+            SavedViewDefinition::MAX_NAME_LEN
+        )));
+    }
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let view = conn
+        .interact(move |conn| save_view(&name, &definition, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    info!(
+        relation = view.relation,
+        materialized = view.materialized,
+        "Saved view"
+    );
+    Ok(Json(view))
+}
 
 pub async fn handler_404() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "")
 }
 
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "Process is alive"))
+)]
+pub async fn get_healthz() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "health",
+    responses((status = 200, description = "Build the instance is running", body = BuildInfo))
+)]
+pub async fn get_version() -> Json<BuildInfo> {
+    Json(build_info())
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready, possibly degraded", body = ReadinessResponse),
+        (status = 503, description = "Not ready to serve traffic", body = ReadinessResponse),
+    )
+)]
+pub async fn get_readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let pool = health::pool_health(state.db.primary().status(), state.db.primary().waits());
+    let read_pool = state
+        .db
+        .replica()
+        .map(|replica| health::pool_health(replica.status(), replica.waits()));
+    let cache = health::cache_health(
+        state.hot_cache.is_some(),
+        state.hot_cache.as_ref().map_or(0, |cache| cache.len()),
+    );
+
+    let lag = match state.db.read().get().await {
+        Ok(conn) => match conn.interact(replication_lag_seconds).await {
+            Ok(Ok(lag)) => Ok(lag),
+            Ok(Err(e)) => {
+                error!("Readiness replication check failed: {e}");
+                Err(())
+            }
+            Err(_) => {
+                error!("Readiness replication check failed");
+                Err(())
+            }
+        },
+        Err(e) => {
+            error!("Readiness unable to get connection: {e}");
+            Err(())
+        }
+    };
+
+    let reachable = lag.is_ok();
+    let replication = health::replication_health(lag.ok().flatten());
+    let score = if reachable {
+        let read_score = read_pool.as_ref().map_or(1.0, |read_pool| read_pool.score);
+        health::overall_score(&pool, &replication, &cache).min(read_score)
+    } else {
+        0.0
+    };
+    let status = health::status_label(score);
+    let response = ReadinessResponse {
+        status,
+        score,
+        checks: HealthChecks {
+            pool,
+            read_pool,
+            replication,
+            cache,
+            response_cache: state
+                .response_cache
+                .as_ref()
+                .map_or_else(ResponseCacheHealth::default, |cache| cache.health()),
+            history: HistoryHealth {
+                queued: state.history.queued(),
+                dropped: state.history.dropped(),
+            },
+            retention: state.retention.health(),
+            leader: state.leader.status(),
+            read_only: state.read_only.status(),
+        },
+    };
+
+    let code = if score < health::UNAVAILABLE_SCORE {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    params(FormatParams),
+    request_body = TimeSeriesAggregationRequest,
+    responses(
+        (status = 200, description = "Aggregated time series, as CSV or an Arrow IPC stream when negotiated", content(
+            (QueryResponse = "application/json"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.apache.arrow.stream"),
+        ), headers(
+            ("x-cache" = String, description = "`HIT` or `MISS` when the response cache is enabled"),
+            ("x-degraded" = String, description = "`hot-cache` when answered in process while Postgres was unavailable, see `query_fallback`"),
+            ("x-degraded-window-start" = String, description = "Earliest reading a degraded answer drew on"),
+        )),
+        (status = 503, description = "Database unavailable, and the hot cache unable to answer", body = ErrorBody),
+        (status = 400, description = "Unknown format, or a decimal format other than exact for arrow", body = ErrorBody),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 413, description = "Estimated to return more than `max_query_buckets` buckets", body = ErrorBody),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
 pub async fn post_query_ts(
-    State(pg_pool): State<Pool>,
-    Json(request): Json<TimeSeriesAggregationRequest>,
-) -> impl IntoResponse {
-    let Ok(conn) = pg_pool.get().await else {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response();
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    format: ResponseFormat,
+    ValidJson(request): ValidJson<TimeSeriesAggregationRequest>,
+) -> Result<Response, ApiError> {
+    aggregation_response(&state, api_key_id, deadline, format, request).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/dsl",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    params(FormatParams),
+    request_body(
+        description = "Query text such as `sum(amount) by month where series='North Farm' from 2024-01 to 2024-12 in MWh`, see the README for the grammar",
+        content = String,
+        content_type = "text/plain",
+    ),
+    responses(
+        (status = 200, description = "Aggregated time series as returned by `/timeseries/v1/query`", content(
+            (QueryResponse = "application/json"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.apache.arrow.stream"),
+        )),
+        (status = 400, description = "Query text does not parse, or unknown format", body = ErrorBody),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 413, description = "Estimated to return more than `max_query_buckets` buckets", body = ErrorBody),
+        (status = 422, description = "Invalid range", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn post_query_dsl(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    format: ResponseFormat,
+    query: String,
+) -> Result<Response, ApiError> {
+    info!(query, "Received DSL Query");
+    let request = dsl::parse(&query)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?
+        .into_request();
+    if let Err(rejection) = request.validate(ValidationLimits::from_ref(&state.config)) {
+        return Ok(rejection.into_response());
+    }
+    aggregation_response(&state, api_key_id, deadline, format, request).await
+}
+
+/// Answers a validated aggregation request in the negotiated format, from the response
+/// cache when it holds the result
+async fn aggregation_response(
+    state: &AppState,
+    api_key_id: i64,
+    deadline: Deadline,
+    format: ResponseFormat,
+    request: TimeSeriesAggregationRequest,
+) -> Result<Response, ApiError> {
+    if format == ResponseFormat::Arrow {
+        decimal::require_exact("Arrow")?;
+    }
+    let (key, having) = query_key(state, request).await?;
+    let QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        unit,
+        timezone,
+        ..
+    } = key;
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Time Series Query");
+
+    let history = state
+        .history
+        .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+    let cached = state
+        .response_cache
+        .as_ref()
+        .map(|cache| (cache, cache.get(&key)));
+    // Results missing here may have been computed by another replica
+    #[cfg(feature = "redis-cache")]
+    let shared = match cached {
+        Some((_, Some(_))) => None,
+        _ => SharedResponse::open(state, format, &key).await,
+    };
+    #[cfg(feature = "redis-cache")]
+    if let Some(response) = shared.as_ref().and_then(SharedResponse::hit) {
+        history.succeeded(None);
+        return Ok(response);
+    }
+    let (result, cache_status, degraded) = match cached {
+        Some((_, Some(result))) => (result, Some(CacheStatus::Hit), None),
+        Some((cache, None)) => {
+            let generation = cache.generation();
+            let (result, degraded) = run_or_degrade(state, deadline, &key, having).await?;
+            let result = Arc::new(result);
+            // Degraded answers miss readings outside the window, so are never cached
+            if degraded.is_none() {
+                cache.insert(key, generation, Arc::clone(&result));
+            }
+            (result, Some(CacheStatus::Miss), degraded)
+        }
+        None => {
+            let (result, degraded) = run_or_degrade(state, deadline, &key, having).await?;
+            (Arc::new(result), None, degraded)
+        }
+    };
+    history.succeeded(Some(result.records.len()));
+
+    let zone = timezone.unwrap_or(Tz::UTC);
+    let response = match format {
+        ResponseFormat::Csv => csv_response(&result.records)?,
+        ResponseFormat::Arrow => arrow_response(result.records.clone()),
+        ResponseFormat::Json => {
+            Json(query_response(Arc::unwrap_or_clone(result), unit, zone)).into_response()
+        }
+    };
+    if let Some(window_start) = degraded {
+        let headers = [
+            (X_DEGRADED, "hot-cache".to_string()),
+            (X_DEGRADED_WINDOW_START, window_start.to_rfc3339()),
+        ];
+        return Ok((headers, response).into_response());
+    }
+    #[cfg(feature = "redis-cache")]
+    let (response, cache_status) = match shared {
+        Some(shared) => (shared.store(response).await?, Some(CacheStatus::Miss)),
+        None => (response, cache_status),
     };
+    Ok(match cache_status {
+        Some(status) => ([(X_CACHE, status.as_str())], response).into_response(),
+        None => response,
+    })
+}
 
+/// The query an aggregation request asks for and the bounds its totals must pass, with
+/// the range resolved and a series name to its id
+async fn query_key(
+    state: &AppState,
+    request: TimeSeriesAggregationRequest,
+) -> Result<(QueryKey, TotalFilter), ApiError> {
+    let now = Utc::now();
+    let (aggregation_kind, from_date, to_date) =
+        request.resolve(state.config.default_query_start(now), now);
     let TimeSeriesAggregationRequest {
-        aggregation_kind,
-        datetime_filter: TimeSeriesRange { from_date, to_date },
+        aggregation_kind: _,
+        datetime_filter: _,
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        having,
+        group_by,
+        unit,
+        timezone,
+        series_id,
+        series_name,
+        as_recorded_by,
     } = request;
-    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Time Series Query");
-    let query_result = conn
-        .interact(move |conn| aggregate_ts_query(aggregation_kind, from_date, to_date, conn))
-        .await;
+    let series_id = resolve_series(state.db.read(), series_id, series_name).await?;
+    check_bucket_estimate(
+        state,
+        aggregation_kind,
+        from_date,
+        to_date,
+        fill_missing.is_some(),
+    )
+    .await?;
+    let key = QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        having: having.as_ref().map(TotalFilter::bounds),
+        group_by,
+        unit,
+        timezone,
+        series_id,
+        as_recorded_by,
+    };
+    if let Some(telemetry) = &state.telemetry {
+        telemetry.record_query(&key);
+    }
+    Ok((key, having.unwrap_or_default()))
+}
+
+/// Refuses an aggregation estimated to return more than `max_query_buckets` buckets
+/// before it reaches Postgres. Open ends are taken at the earliest and latest readings
+/// held, and so are ends beyond them unless `fill_missing` pads the range, the summaries
+/// only being read when the range as requested is over the limit.
+pub(crate) async fn check_bucket_estimate(
+    state: &AppState,
+    aggregation_kind: Aggregation,
+    from_date: Option<DateTime<Utc>>,
+    to_date: Option<DateTime<Utc>>,
+    fill_missing: bool,
+) -> Result<(), ApiError> {
+    let Some(max) = state.config.max_query_buckets else {
+        return Ok(());
+    };
+    if let (Some(from), Some(to)) = (from_date, to_date)
+        && aggregation_kind.estimated_buckets(to - from) <= max
+    {
+        return Ok(());
+    }
 
-    let Ok(query_result) = query_result else {
-        error!("Error executing aggregate query");
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response();
+    let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+    let extent = conn
+        .interact(reading_extent)
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    let Some((first, last)) = extent else {
+        return Ok(());
+    };
+    let (from, to) = if fill_missing {
+        (from_date.unwrap_or(first), to_date.unwrap_or(last))
+    } else {
+        (
+            from_date.map_or(first, |from| from.max(first)),
+            to_date.map_or(last, |to| to.min(last)),
+        )
     };
+    let estimated = aggregation_kind.estimated_buckets(to - from);
+    if estimated > max {
+        return Err(ApiError::TooManyBuckets { estimated, max });
+    }
+    Ok(())
+}
+
+/// JSON body of an aggregation response, bucket starts carrying the offset of `zone`
+fn query_response(result: AggregationResult, unit: AmountUnit, zone: Tz) -> QueryResponse {
+    let AggregationResult {
+        executed_at,
+        records,
+        lineage,
+        completeness,
+        power,
+        settlement,
+        breakdown,
+        sources,
+    } = result;
+    QueryResponse {
+        executed_at,
+        unit,
+        records: records
+            .into_iter()
+            .map(|record| record.in_zone(zone))
+            .collect(),
+        lineage,
+        completeness,
+        power,
+        settlement,
+        breakdown,
+        sources,
+    }
+}
+
+/// A JSON or CSV aggregation response in the [`SharedCache`] every replica reads, keyed by
+/// the format, decimal format and query. Arrow responses are streamed as they are encoded so are not shared.
+#[cfg(feature = "redis-cache")]
+struct SharedResponse<'a> {
+    cache: &'a SharedCache,
+    generation: u64,
+    key: String,
+    ttl: std::time::Duration,
+    /// Content type, content disposition and body, one line each before the body
+    cached: Option<Vec<u8>>,
+}
 
-    match query_result {
-        Ok(records) => {
-            let response = QueryResponse {
-                executed_at: Utc::now(),
-                records,
+#[cfg(feature = "redis-cache")]
+impl<'a> SharedResponse<'a> {
+    /// Looks the response up, `None` when no shared cache is configured or it cannot be
+    /// reached, in which case the response is computed without it
+    async fn open(state: &'a AppState, format: ResponseFormat, key: &QueryKey) -> Option<Self> {
+        let cache = state.shared_cache.as_ref()?;
+        if format == ResponseFormat::Arrow {
+            return None;
+        }
+        // Amounts are rendered in the decimal format the client asked for
+        let key = format!("{format:?} {:?} {key:?}", decimal::requested());
+        let lookup = async {
+            let generation = cache.generation().await?;
+            let cached = cache.get(generation, &key).await?;
+            Ok::<_, CacheError>((generation, cached))
+        };
+        let (generation, cached) = lookup
+            .await
+            .inspect_err(|e| warn!("Unable to read the shared cache: {e}"))
+            .ok()?;
+        Some(Self {
+            cache,
+            generation,
+            key,
+            ttl: state.config.response_cache_ttl(),
+            cached,
+        })
+    }
+
+    /// The cached response, when another request computed it
+    fn hit(&self) -> Option<Response> {
+        let mut lines = self.cached.as_deref()?.splitn(3, |byte| *byte == b'\n');
+        let (content_type, disposition, body) = (lines.next()?, lines.next()?, lines.next()?);
+        let mut response = Body::from(body.to_vec()).into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_bytes(content_type).ok()?,
+        );
+        if !disposition.is_empty() {
+            headers.insert(
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_bytes(disposition).ok()?,
+            );
+        }
+        headers.insert(X_CACHE, HeaderValue::from_static(CacheStatus::Hit.as_str()));
+        Some(response)
+    }
+
+    /// Caches a freshly rendered `response` for the other replicas, unless the readings
+    /// changed since it was looked up
+    async fn store(self, response: Response) -> Result<Response, ApiError> {
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(ApiError::Body)?;
+        if parts.status.is_success() {
+            let header = |name| {
+                parts
+                    .headers
+                    .get(name)
+                    .map_or(&[][..], HeaderValue::as_bytes)
             };
-            Json(response).into_response()
+            let cached = [
+                header(header::CONTENT_TYPE),
+                b"\n",
+                header(header::CONTENT_DISPOSITION),
+                b"\n",
+                &body,
+            ]
+            .concat();
+            if let Err(e) = self
+                .cache
+                .set(self.generation, &self.key, &cached, self.ttl)
+                .await
+            {
+                warn!("Unable to write the shared cache: {e}");
+            }
         }
-        Err(e) => {
-            error!("Error executing aggregate query: {e}");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+/// Runs the aggregation `key` describes and every extra it asks for, with totals
+/// converted into the requested unit
+async fn run_aggregation(
+    state: &AppState,
+    deadline: Deadline,
+    key: &QueryKey,
+    having: TotalFilter,
+) -> Result<AggregationResult, ApiError> {
+    let QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        ref group_by,
+        unit,
+        timezone,
+        series_id,
+        as_recorded_by,
+        ..
+    } = *key;
+    let zone = timezone.unwrap_or(Tz::UTC);
+
+    // Recent Hourly windows over current data can be answered from the hot cache
+    let cached = match &state.hot_cache {
+        Some(cache)
+            if aggregation_kind == Aggregation::Hourly
+                && as_recorded_by.is_none()
+                && !include_power
+                && key.having.is_none()
+                && series_id.is_none()
+                && timezone.is_none() =>
+        {
+            cache.hourly(from_date, to_date).await
+        }
+        _ => None,
+    };
+
+    let (records, power) = if let Some(records) = cached {
+        (records, None)
+    } else if include_power {
+        // Energy totals are taken from the power query rather than aggregated again
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let buckets = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    bucket_energy(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        &having,
+                        zone,
+                        conn,
+                    )
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        let power = power::bucket_power(buckets, state.config.reading_interval());
+        (power::energy_records(&power), Some(power))
+    } else {
+        let filter = having.clone();
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let records = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    aggregate_ts_query_having(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        &filter,
+                        zone,
+                        conn,
+                    )
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        // Only UTC buckets of a day or more over current readings come from the summaries
+        if let Some(sampler) = &state.shadow
+            && aggregation_kind != Aggregation::Hourly
+            && as_recorded_by.is_none()
+            && timezone.is_none()
+            && sampler.sample()
+        {
+            shadow_aggregation(state, key, having.clone(), records.clone());
+        }
+        (records, None)
+    };
+
+    let lineage = if include_lineage {
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let lineage = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    query_lineage(from_date, to_date, as_recorded_by, series_id, conn)
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Some(lineage)
+    } else {
+        None
+    };
+
+    let breakdown = if group_by.contains(&GroupBy::FuelType) {
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let breakdown = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    fuel_type_breakdown(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        zone,
+                        conn,
+                    )
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Some(breakdown)
+    } else {
+        None
+    };
+
+    let sources = if include_sources {
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let sources = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    bucket_sources(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        zone,
+                        conn,
+                    )
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Some(sources)
+    } else {
+        None
+    };
+
+    let records = match fill_missing {
+        Some(fill) => bucket::fill_missing(aggregation_kind, from_date, to_date, records, fill),
+        None => records,
+    };
+    let power = power.map(|power| power::per_record(&records, power));
+    let settlement = include_settlement.then(|| settlement::label(&records));
+
+    let completeness = if include_completeness {
+        let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+        let counts = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    bucket_point_counts(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        conn,
+                    )
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Some(bucket::completeness(
+            aggregation_kind,
+            &records,
+            counts,
+            state.config.reading_interval(),
+        ))
+    } else {
+        None
+    };
+
+    let records: Vec<_> = records
+        .into_iter()
+        .map(|record| record.in_unit(unit))
+        .collect();
+    let breakdown = breakdown.map(|breakdown| {
+        breakdown
+            .into_iter()
+            .map(|record| record.in_unit(unit))
+            .collect()
+    });
+
+    Ok(AggregationResult {
+        executed_at: Utc::now(),
+        records,
+        lineage,
+        completeness,
+        power,
+        settlement,
+        breakdown,
+        sources,
+    })
+}
+
+/// [`run_aggregation`], answered from the hot cache instead when Postgres is unavailable
+/// and `query_fallback` is set, along with the start of the window a degraded answer drew on
+async fn run_or_degrade(
+    state: &AppState,
+    deadline: Deadline,
+    key: &QueryKey,
+    having: TotalFilter,
+) -> Result<(AggregationResult, Option<DateTime<Utc>>), ApiError> {
+    let error = match run_aggregation(state, deadline, key, having.clone()).await {
+        Ok(result) => return Ok((result, None)),
+        Err(e) => e,
+    };
+    if !state.config.query_fallback || !error.is_database_unavailable() {
+        return Err(error);
+    }
+    match degraded_aggregation(state, key, &having).await {
+        Some((result, window_start)) => {
+            warn!("Answering from the hot cache, Postgres is unavailable: {error}");
+            Ok((result, Some(window_start)))
         }
+        None => Err(error),
     }
 }
 
-pub async fn get_query_history(State(pg_pool): State<Pool>) -> impl IntoResponse {
-    let Ok(conn) = pg_pool.get().await else {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response();
+/// Runs the aggregation `key` describes again over `ts_store` in the background and logs
+/// the buckets whose totals differ from those `served` from the summaries, with the
+/// request's parameters. Nothing is compared once a ranked ingestion has the served
+/// aggregation scan `ts_store` too.
+fn shadow_aggregation(
+    state: &AppState,
+    key: &QueryKey,
+    having: TotalFilter,
+    served: Vec<AggregationQueryRecord>,
+) {
+    let db = state.db.clone();
+    let timeout = state.config.query_timeout();
+    let QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        series_id,
+        ..
+    } = *key;
+    state.shutdown.spawn("shadow query", async move {
+        let shadow = async {
+            let conn = db.read().get().await.map_err(ApiError::Pool)?;
+            let having = having.clone();
+            conn.interact(move |conn| {
+                with_statement_timeout(conn, Some(timeout), |conn| {
+                    if has_ranked_ingestions(conn)? {
+                        return Ok(None);
+                    }
+                    scan_ts_query(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        series_id,
+                        &having,
+                        conn,
+                    )
+                    .map(Some)
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)
+        };
+        match shadow.await {
+            Ok(Some(shadow)) => {
+                let discrepancies = shadow::compare(&served, &shadow);
+                if let Some(first) = discrepancies.first() {
+                    warn!(
+                        aggregation_kind = ?aggregation_kind,
+                        from_date = ?from_date,
+                        to_date = ?to_date,
+                        series_id = ?series_id,
+                        having = ?having,
+                        buckets = discrepancies.len(),
+                        first = %first,
+                        "Shadow query over ts_store disagrees with the summaries"
+                    );
+                } else {
+                    debug!(aggregation_kind = ?aggregation_kind, buckets = served.len(), "Shadow query agrees with the summaries");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Unable to run shadow query: {e}"),
+        }
+    });
+}
+
+/// The aggregation `key` describes computed in process over the hot cache, when its
+/// window covers the range and nothing asked for needs more than the summed readings.
+/// DataFusion queries the cache's columns, see
+/// [`HotCache::aggregate`](crate::hot_cache::HotCache::aggregate).
+async fn degraded_aggregation(
+    state: &AppState,
+    key: &QueryKey,
+    having: &TotalFilter,
+) -> Option<(AggregationResult, DateTime<Utc>)> {
+    let QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        ref group_by,
+        unit,
+        timezone,
+        series_id,
+        as_recorded_by,
+        ..
+    } = *key;
+    if include_lineage
+        || include_completeness
+        || include_power
+        || include_sources
+        || !group_by.is_empty()
+        || series_id.is_some()
+        || as_recorded_by.is_some()
+    {
+        return None;
+    }
+    let cache = state.hot_cache.as_ref()?;
+    let records = cache
+        .aggregate(
+            aggregation_kind,
+            from_date,
+            to_date,
+            having,
+            timezone.unwrap_or(Tz::UTC),
+        )
+        .await?;
+    let records = match fill_missing {
+        Some(fill) => bucket::fill_missing(aggregation_kind, from_date, to_date, records, fill),
+        None => records,
+    };
+    let settlement = include_settlement.then(|| settlement::label(&records));
+    let result = AggregationResult {
+        executed_at: Utc::now(),
+        records: records
+            .into_iter()
+            .map(|record| record.in_unit(unit))
+            .collect(),
+        lineage: None,
+        completeness: None,
+        power: None,
+        settlement,
+        breakdown: None,
+        sources: None,
     };
+    Some((result, cache.start()?))
+}
+
+/// Id of the series a query is limited to, looked up by name when given one
+async fn resolve_series(
+    pg_pool: &TimedPool,
+    series_id: Option<i64>,
+    series_name: Option<String>,
+) -> Result<Option<i64>, ApiError> {
+    if series_id.is_none() && series_name.is_none() {
+        return Ok(None);
+    }
+    let conn = pg_pool.get().await.map_err(ApiError::Pool)?;
+    conn.interact(move |conn| match series_name {
+        Some(name) => find_series_id(&name, conn),
+        None => get_series(series_id.unwrap_or_default(), conn)
+            .map(|series| series.map(|series| series.id)),
+    })
+    .await
+    .map_err(ApiError::Interaction)?
+    .map_err(ApiError::Database)?
+    .ok_or(ApiError::NotFound("series"))
+    .map(Some)
+}
+
+/// Streams aggregation records as Arrow IPC record batches
+fn arrow_response(records: Vec<AggregationQueryRecord>) -> Response {
+    let body = streamed_body(move |writer| {
+        columnar::write_ipc_stream(
+            writer,
+            columnar::aggregation_schema(),
+            &records,
+            columnar::aggregation_batch,
+        )
+    });
+    ([(header::CONTENT_TYPE, ARROW_STREAM)], body).into_response()
+}
+
+/// Renders aggregation records as a CSV attachment, lineage, completeness, power and
+/// settlement labels are only available as JSON
+fn csv_response(records: &[AggregationQueryRecord]) -> Result<Response, ApiError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    export::write_records(&mut writer, records).map_err(ApiError::Csv)?;
+    let body = writer
+        .into_inner()
+        .map_err(|e| ApiError::Csv(e.into_error().into()))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"query.csv\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/stream",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    params(FormatParams),
+    request_body = TimeSeriesAggregationRequest,
+    responses(
+        (status = 200, description = "Buckets in bucket order as the database cursor yields them, at most `max_stream_rows`. As Server-Sent Events: a `bucket` event per `ZonedAggregationRecord`, then `done` with the bucket count, or `error` with an `ErrorBody`. As CSV: the rows of a query CSV, the body aborted rather than ended on failure", content(
+            (String = "text/event-stream"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Option or format not supported when streaming", body = ErrorBody),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn post_query_stream(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    format: ResponseFormat,
+    ValidJson(request): ValidJson<TimeSeriesAggregationRequest>,
+) -> Result<Response, ApiError> {
+    let now = Utc::now();
+    let (aggregation_kind, from_date, to_date) =
+        request.resolve(state.config.default_query_start(now), now);
+    let TimeSeriesAggregationRequest {
+        aggregation_kind: _,
+        datetime_filter: _,
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        having,
+        group_by,
+        unit,
+        timezone,
+        series_id,
+        series_name,
+        as_recorded_by,
+    } = request;
+    if fill_missing.is_some()
+        || include_lineage
+        || include_completeness
+        || include_power
+        || include_settlement
+        || include_sources
+        || !group_by.is_empty()
+    {
+        return Err(ApiError::BadRequest(
+            "fill_missing, include_lineage, include_completeness, include_power, include_settlement, include_sources and group_by are not supported when streaming"
+                .to_string(),
+        ));
+    }
+    if format == ResponseFormat::Arrow {
+        return Err(ApiError::BadRequest(
+            "arrow is not supported when streaming, use json or csv".to_string(),
+        ));
+    }
+    let having = having.unwrap_or_default();
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Streamed Time Series Query");
+    let series_id = resolve_series(state.db.read(), series_id, series_name).await?;
+    let zone = timezone.unwrap_or(Tz::UTC);
 
-    let Ok(history_result) = conn.interact(query_request_history).await else {
-        error!("Error executing Query History");
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response();
+    let history = state
+        .history
+        .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+    // Taken up front so an exhausted pool fails the request rather than the stream
+    let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+    let max_rows = state.config.max_stream_rows;
+    // Buckets are written from a blocking thread, outside the request's task
+    let decimals = decimal::requested();
+
+    let (mut sink, end, response) = match format {
+        ResponseFormat::Csv => {
+            let (sender, receiver) = mpsc::channel(STREAM_BUFFER_EVENTS);
+            let writer = Box::new(csv::Writer::from_writer(BodyWriter {
+                sender: sender.clone(),
+                buffer: Vec::new(),
+            }));
+            let response = (
+                [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+                Body::from_stream(ReceiverStream::new(receiver)),
+            )
+                .into_response();
+            (BucketSink::Csv(writer), StreamEnd::Body(sender), response)
+        }
+        _ => {
+            let (sender, receiver) = mpsc::channel(STREAM_BUFFER_EVENTS);
+            let response = Sse::new(ReceiverStream::new(receiver).map(Ok::<_, Infallible>))
+                .keep_alive(KeepAlive::default())
+                .into_response();
+            (
+                BucketSink::Events(sender.clone(), zone),
+                StreamEnd::Events(sender),
+                response,
+            )
+        }
     };
 
-    match history_result {
-        Ok(records) => Json(json!(records)).into_response(),
-        Err(e) => {
-            error!("Error executing Query History: {e}");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+    state.shutdown.spawn("streamed query", async move {
+        let streamed = conn
+            .interact(move |conn| decimal::within(decimals, || {
+                sink.start().map_err(ApiError::Csv)?;
+                let (mut sent, mut exceeded) = (0, false);
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    stream_ts_query(
+                        aggregation_kind,
+                        from_date,
+                        to_date,
+                        as_recorded_by,
+                        series_id,
+                        &having,
+                        zone,
+                        conn,
+                        |record| {
+                            // The bucket past the limit is fetched but never sent
+                            exceeded = sent == max_rows;
+                            if exceeded || !sink.send(record.in_unit(unit)) {
+                                return false;
+                            }
+                            sent += 1;
+                            true
+                        },
+                    )
+                })
+                .map_err(ApiError::Database)?;
+                if exceeded {
+                    return Err(ApiError::BadRequest(format!(
+                        "result exceeds max_stream_rows of {max_rows} buckets, narrow the range or use a coarser aggregation"
+                    )));
+                }
+                sink.flush();
+                Ok(sent)
+            }))
+            .await
+            .map_err(ApiError::Interaction)
+            .flatten();
+        if let Ok(sent) = streamed {
+            history.succeeded(Some(sent));
+        }
+        end.finish(streamed).await;
+    });
+
+    Ok(response)
+}
+
+/// Where the buckets of a streamed query go, written from the blocking database thread
+enum BucketSink {
+    /// Server-Sent Events with bucket starts in the requested timezone
+    Events(mpsc::Sender<Event>, Tz),
+    /// Rows of a query CSV, in UTC as CSV query results are
+    Csv(Box<csv::Writer<BodyWriter>>),
+}
+
+impl BucketSink {
+    /// Writes the CSV header, before any bucket
+    fn start(&mut self) -> csv::Result<()> {
+        match self {
+            Self::Events(..) => Ok(()),
+            Self::Csv(writer) => writer.write_record(export::CSV_HEADER),
+        }
+    }
+
+    /// Hands over a bucket, `false` once the client has gone, which ends the query early
+    fn send(&mut self, record: AggregationQueryRecord) -> bool {
+        match self {
+            Self::Events(sender, zone) => {
+                match Event::default()
+                    .event("bucket")
+                    .json_data(record.in_zone(*zone))
+                {
+                    Ok(event) => sender.blocking_send(event).is_ok(),
+                    Err(e) => {
+                        error!("Unable to encode streamed bucket: {e}");
+                        false
+                    }
+                }
+            }
+            Self::Csv(writer) => export::write_record(writer, &record).is_ok(),
+        }
+    }
+
+    /// Sends any rows still buffered once every bucket has been handed over
+    fn flush(&mut self) {
+        if let Self::Csv(writer) = self {
+            // Only fails once the client has gone
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Last word of a streamed query, sent once the query has finished
+enum StreamEnd {
+    Events(mpsc::Sender<Event>),
+    Body(mpsc::Sender<io::Result<Bytes>>),
+}
+
+impl StreamEnd {
+    /// A `done` event with the bucket count or an `error` event, while a body is aborted
+    /// on failure so a client cannot mistake a partial CSV for the whole result
+    async fn finish(self, streamed: Result<usize, ApiError>) {
+        match (self, streamed) {
+            (Self::Events(sender), streamed) => {
+                let last = match streamed {
+                    Ok(sent) => Event::default().event("done").data(sent.to_string()),
+                    Err(e) => error_event(e),
+                };
+                let _ = sender.send(last).await;
+            }
+            (Self::Body(_), Ok(_)) => {}
+            (Self::Body(sender), Err(e)) => {
+                error!("Streamed query failed: {e}");
+                let _ = sender.send(Err(io::Error::other(e.to_string()))).await;
+            }
+        }
+    }
+}
+
+/// Blocking writer sending its output to a response body in chunks of about
+/// [`ENCODER_PIPE_BYTES`], failing once the client has gone
+struct BodyWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl io::Write for BodyWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        if self.buffer.len() >= ENCODER_PIPE_BYTES {
+            self.flush()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
         }
+        let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
     }
 }
+
+/// Ends a stream whose successful status has already been sent
+fn error_event(error: ApiError) -> Event {
+    let (_, body) = error.into_body();
+    Event::default()
+        .event("error")
+        .json_data(body)
+        .unwrap_or_else(|_| Event::default().event("error"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/diff",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    request_body = SnapshotDiffRequest,
+    responses(
+        (status = 200, description = "Buckets changed between the two snapshots", body = SnapshotDiffResponse),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn post_query_diff(
+    State(db): State<Pools>,
+    State(history): State<HistoryWriter>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    ValidJson(request): ValidJson<SnapshotDiffRequest>,
+) -> Result<Json<SnapshotDiffResponse>, ApiError> {
+    let conn = db.read().get().await.map_err(ApiError::Pool)?;
+
+    let SnapshotDiffRequest {
+        aggregation_kind,
+        datetime_filter,
+        baseline_recorded_by,
+        compare_recorded_by,
+    } = request;
+    let (from_date, to_date) = datetime_filter.half_open();
+    let compare_recorded_by = compare_recorded_by.unwrap_or_else(Utc::now);
+    info!(aggregation_kind= ?aggregation_kind, baseline_recorded_by= ?baseline_recorded_by, compare_recorded_by= ?compare_recorded_by, "Received Snapshot Diff Query");
+    let history = history.start(aggregation_kind, from_date, to_date, Some(api_key_id));
+
+    let (baseline, compare) = conn
+        .interact(move |conn| {
+            with_statement_timeout(conn, deadline.remaining(), |conn| {
+                diff_ts_query(
+                    aggregation_kind,
+                    from_date,
+                    to_date,
+                    baseline_recorded_by,
+                    compare_recorded_by,
+                    conn,
+                )
+            })
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    let changes = diff::diff_buckets(baseline, compare);
+    history.succeeded(Some(changes.len()));
+    Ok(Json(SnapshotDiffResponse {
+        executed_at: Utc::now(),
+        baseline_recorded_by,
+        compare_recorded_by,
+        changes,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/power",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    request_body = PowerQueryRequest,
+    responses(
+        (status = 200, description = "Average and peak power per bucket in kW", body = PowerResponse),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn post_query_power(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    ValidJson(request): ValidJson<PowerQueryRequest>,
+) -> Result<Json<PowerResponse>, ApiError> {
+    let PowerQueryRequest {
+        aggregation_kind,
+        datetime_filter,
+        as_recorded_by,
+    } = request;
+    let (from_date, to_date) = datetime_filter.half_open();
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Power Query");
+    let history = state
+        .history
+        .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+
+    let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+    let buckets = conn
+        .interact(move |conn| {
+            with_statement_timeout(conn, deadline.remaining(), |conn| {
+                bucket_energy(
+                    aggregation_kind,
+                    from_date,
+                    to_date,
+                    as_recorded_by,
+                    None,
+                    &TotalFilter::default(),
+                    Tz::UTC,
+                    conn,
+                )
+            })
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    history.succeeded(Some(buckets.len()));
+    Ok(Json(PowerResponse {
+        executed_at: Utc::now(),
+        interval_minutes: state.config.reading_interval_minutes,
+        buckets: power::bucket_power(buckets, state.config.reading_interval()),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/ranges",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    request_body = MultiRangeQueryRequest,
+    responses(
+        (status = 200, description = "Buckets of each range, tagged by its position in the request", body = MultiRangeResponse),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn post_query_ranges(
+    State(db): State<Pools>,
+    State(history): State<HistoryWriter>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    ValidJson(request): ValidJson<MultiRangeQueryRequest>,
+) -> Result<Json<MultiRangeResponse>, ApiError> {
+    let MultiRangeQueryRequest {
+        aggregation_kind,
+        ranges,
+        having,
+        timezone,
+        series_id,
+        as_recorded_by,
+    } = request;
+    let zone = timezone.unwrap_or(Tz::UTC);
+    let ranges: Vec<_> = ranges.iter().map(TimeSeriesRange::half_open).collect();
+    info!(aggregation_kind= ?aggregation_kind, ranges = ranges.len(), "Received Multi-Range Query");
+    let pending: Vec<_> = ranges
+        .iter()
+        .map(|&(from_date, to_date)| {
+            history.start(aggregation_kind, from_date, to_date, Some(api_key_id))
+        })
+        .collect();
+
+    let conn = db.read().get().await.map_err(ApiError::Pool)?;
+    let query_ranges = ranges.clone();
+    let buckets = conn
+        .interact(move |conn| {
+            with_statement_timeout(conn, deadline.remaining(), |conn| {
+                multi_range_ts_query(
+                    aggregation_kind,
+                    &query_ranges,
+                    as_recorded_by,
+                    series_id,
+                    &having.unwrap_or_default(),
+                    zone,
+                    conn,
+                )
+            })
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    let mut ranges: Vec<_> = ranges
+        .into_iter()
+        .enumerate()
+        .map(|(range_index, (from_date, to_date))| RangeRecords {
+            range_index,
+            from_date,
+            to_date,
+            records: Vec::new(),
+        })
+        .collect();
+    for bucket in buckets {
+        if let Some(range) = usize::try_from(bucket.range_index)
+            .ok()
+            .and_then(|index| ranges.get_mut(index))
+        {
+            range.records.push(
+                AggregationQueryRecord {
+                    datetime: bucket.datetime,
+                    total_amount: bucket.total_amount,
+                }
+                .in_zone(zone),
+            );
+        }
+    }
+
+    for (history, range) in pending.into_iter().zip(&ranges) {
+        history.succeeded(Some(range.records.len()));
+    }
+    Ok(Json(MultiRangeResponse {
+        executed_at: Utc::now(),
+        ranges,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/analytics/calendar/{year}",
+    security(("api_key" = [])),
+    tag = "analytics",
+    params(("year" = i32, Path, description = "Calendar year")),
+    responses(
+        (status = 200, description = "Total of every UTC day of the year with monthly summaries", body = CalendarResponse),
+        (status = 400, description = "Unsupported year", body = ErrorBody),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_calendar(
+    State(db): State<Pools>,
+    State(history): State<HistoryWriter>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    Path(year): Path<i32>,
+) -> Result<Json<CalendarResponse>, ApiError> {
+    let (from_date, to_date) = calendar::year_range(year)
+        .ok_or_else(|| ApiError::BadRequest(format!("unsupported year {year}")))?;
+    info!(year, "Received Calendar Query");
+    let history = history.start(
+        Aggregation::DayInMonth,
+        Some(from_date),
+        Some(to_date),
+        Some(api_key_id),
+    );
+
+    // Whole days are summed from the per-day summaries rather than the readings
+    let conn = db.read().get().await.map_err(ApiError::Pool)?;
+    let daily = conn
+        .interact(move |conn| {
+            with_statement_timeout(conn, deadline.remaining(), |conn| {
+                aggregate_ts_query(
+                    Aggregation::DayInMonth,
+                    Some(from_date),
+                    Some(to_date),
+                    None,
+                    conn,
+                )
+            })
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    history.succeeded(Some(daily.len()));
+    Ok(Json(calendar::calendar(year, daily)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/query/history",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    params(HistoryPageParams, HistoryFilter),
+    responses(
+        (status = 200, description = "Most recent queries matching the filters, newest first", body = [QueryHistory], headers(
+            ("x-next-cursor" = String, description = "Sent as `cursor` with the same filters for the page of older queries, absent on the last page"),
+        )),
+        (status = 400, description = "Cursor not issued by this service for these filters, or an empty execution window", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_query_history(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryPageParams>,
+    Query(filter): Query<HistoryFilter>,
+) -> Result<Response, ApiError> {
+    filter.validate().map_err(ApiError::BadRequest)?;
+    let after = params
+        .cursor
+        .map(|cursor| state.cursor_signer.verify(HISTORY_CURSOR, &filter, &cursor))
+        .transpose()
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let limit = state.config.history_limit;
+    let signed_filter = filter.clone();
+    let records = conn
+        .interact(move |conn| query_request_history(limit, after, &filter, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    let next_cursor = match records.last() {
+        Some(last) if records.len() as i64 == limit => Some(state.cursor_signer.issue(
+            HISTORY_CURSOR,
+            &signed_filter,
+            &(last.executed_at, last.id),
+        )),
+        _ => None,
+    };
+    Ok(match next_cursor {
+        Some(cursor) => ([(X_NEXT_CURSOR, cursor)], Json(records)).into_response(),
+        None => Json(records).into_response(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/query/async",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    request_body = TimeSeriesAggregationRequest,
+    responses(
+        (status = 202, description = "Query job created, poll `/timeseries/v1/jobs/{id}` for its result", body = QueryJobResponse),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 413, description = "Estimated to return more than `max_query_buckets` buckets", body = ErrorBody),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_query_async(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    ValidJson(request): ValidJson<TimeSeriesAggregationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (key, having) = query_key(&state, request).await?;
+    let QueryKey {
+        aggregation_kind,
+        from_date,
+        to_date,
+        ..
+    } = key;
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Asynchronous Query");
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+    let job = conn
+        .interact(move |conn| {
+            create_query_job(aggregation_kind, from_date, to_date, api_key_id, conn)
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    let shutdown = state.shutdown.clone();
+    shutdown.spawn(
+        format!("query job {}", job.id),
+        decimal::carry(run_query_job(state, job.id, api_key_id, key, having)),
+    );
+    let response = QueryJobResponse {
+        id: job.id,
+        status: job.status,
+        created_at: job.created_at,
+        completed_at: None,
+        row_count: None,
+        error: None,
+        result: None,
+    };
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+/// Worker spawned for each query job, answering it without the request deadline and
+/// recording the JSON response or the failure against the job
+async fn run_query_job(
+    state: AppState,
+    job_id: i64,
+    api_key_id: i64,
+    key: QueryKey,
+    having: TotalFilter,
+) {
+    let history = state.history.start(
+        key.aggregation_kind,
+        key.from_date,
+        key.to_date,
+        Some(api_key_id),
+    );
+    let (unit, zone) = (key.unit, key.timezone.unwrap_or(Tz::UTC));
+    let answered = async {
+        let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+        conn.interact(move |conn| mark_query_running(job_id, conn))
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        let result = run_aggregation(&state, Deadline::default(), &key, having).await?;
+        let row_count = i64::try_from(result.records.len()).unwrap_or(i64::MAX);
+        let response = serde_json::to_value(query_response(result, unit, zone))
+            .expect("query responses serialize");
+        conn.interact(move |conn| mark_query_complete(job_id, &response, row_count, conn))
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        Ok::<_, ApiError>(row_count)
+    };
+    let Err(e) = answered.await.map(|row_count| {
+        history.succeeded(usize::try_from(row_count).ok());
+        info!(job_id, row_count, "Query job complete");
+    }) else {
+        return;
+    };
+
+    error!(job_id, "Query job failed: {e}");
+    let message = e.to_string();
+    let recorded = match state.db.primary().get().await {
+        Ok(conn) => conn
+            .interact(move |conn| mark_query_failed(job_id, &message, conn))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = recorded {
+        error!(job_id, "Unable to record query job failure: {e}");
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/jobs/{id}",
+    security(("api_key" = [])),
+    tag = "timeseries",
+    params(("id" = i64, Path, description = "Query job id")),
+    responses(
+        (status = 200, description = "Query job status, with the response once complete", body = QueryJobResponse),
+        (status = 404, description = "Unknown query job, or one submitted by another key", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_query_job(
+    State(db): State<Pools>,
+    ApiKey(api_key_id): ApiKey,
+    Path(job_id): Path<i64>,
+) -> Result<Json<QueryJobResponse>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let job = conn
+        .interact(move |conn| query_jobs::get_query_job(job_id, api_key_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("query job"))?;
+
+    Ok(Json(QueryJobResponse {
+        id: job.id,
+        status: job.status,
+        created_at: job.created_at,
+        completed_at: job.completed_at,
+        row_count: job.row_count,
+        error: job.error,
+        result: job.result,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/exports",
+    security(("api_key" = [])),
+    tag = "exports",
+    request_body = TimeSeriesAggregationRequest,
+    responses(
+        (status = 202, description = "Export job created", body = ExportJobResponse),
+        (status = 400, description = "`series_id`, `series_name`, `fill_missing`, `include_lineage`, `include_completeness`, `include_power`, `include_settlement`, `include_sources`, `group_by` and units other than kWh are not supported by exports", body = ErrorBody),
+        (status = 422, description = "Invalid request", body = ValidationErrorResponse),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_export(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    ValidJson(request): ValidJson<TimeSeriesAggregationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let now = Utc::now();
+    let (aggregation_kind, from_date, to_date) =
+        request.resolve(state.config.default_query_start(now), now);
+    let TimeSeriesAggregationRequest {
+        fill_missing,
+        include_lineage,
+        include_completeness,
+        include_power,
+        include_settlement,
+        include_sources,
+        having,
+        group_by,
+        unit,
+        timezone,
+        series_id,
+        series_name,
+        as_recorded_by,
+        ..
+    } = request;
+    // Export jobs cover the whole store and write bucket totals alone, rather than silently
+    // exporting every series or leaving out what else was asked for
+    let unsupported = [
+        ("series_id", series_id.is_some()),
+        ("series_name", series_name.is_some()),
+        ("fill_missing", fill_missing.is_some()),
+        ("include_lineage", include_lineage),
+        ("include_completeness", include_completeness),
+        ("include_power", include_power),
+        ("include_settlement", include_settlement),
+        ("include_sources", include_sources),
+        ("group_by", !group_by.is_empty()),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, requested)| *requested) {
+        return Err(ApiError::BadRequest(format!(
+            "{option} is not supported by exports"
+        )));
+    }
+    if unit != AmountUnit::KWh {
+        return Err(ApiError::BadRequest(
+            "exports are written in kWh, unit is not supported".to_string(),
+        ));
+    }
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Export Request");
+    // Recorded once the job has run, as failed should it not be created
+    let history = state
+        .history
+        .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+    let (job, recipient) = conn
+        .interact(move |conn| {
+            // Exports of a key with a recipient are never written in plain text, so one
+            // that no longer parses refuses the export rather than falling back
+            let recipient = export_recipient(api_key_id, conn)
+                .map_err(ApiError::Database)?
+                .map(|recipient| ExportRecipient::parse(&recipient))
+                .transpose()
+                .map_err(|e| ApiError::BadRequest(format!("invalid export recipient, {e}")))?;
+            let job = create_export_job(aggregation_kind, from_date, to_date, api_key_id, conn)
+                .map_err(ApiError::Database)?;
+            Ok((job, recipient))
+        })
+        .await
+        .map_err(ApiError::Interaction)??;
+    state.shutdown.spawn(
+        format!("export job {}", job.id),
+        decimal::carry(run_export_job(
+            state.db.primary().clone(),
+            state.export_config.clone(),
+            job.id,
+            ExportQuery {
+                aggregation_kind,
+                from_date,
+                to_date,
+                as_recorded_by,
+                having: having.unwrap_or_default(),
+                timezone: timezone.unwrap_or(Tz::UTC),
+            },
+            recipient,
+            state.export_config.watermark_for(api_key_id),
+            history,
+        )),
+    );
+    let response = ExportJobResponse {
+        id: job.id,
+        status: job.status,
+        created_at: job.created_at,
+        completed_at: None,
+        row_count: None,
+        error: None,
+        download_url: None,
+        expires_at: None,
+    };
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/exports/{id}",
+    security(("api_key" = [])),
+    tag = "exports",
+    params(("id" = i64, Path, description = "Export job id")),
+    responses(
+        (status = 200, description = "Export job status", body = ExportJobResponse),
+        (status = 404, description = "Unknown export job, or one created by another key", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_export(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    Path(job_id): Path<i64>,
+) -> Result<Json<ExportJobResponse>, ApiError> {
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    // The file carries its creator's watermark, so no other key is handed its download URL
+    let job = conn
+        .interact(move |conn| get_export_job(job_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .filter(|job| job.api_key_id == Some(api_key_id))
+        .ok_or(ApiError::NotFound("export job"))?;
+
+    let (download_url, expires_at) = if job.status == JobStatus::Complete {
+        let expires_at =
+            Utc::now() + TimeDelta::from_std(state.export_config.url_ttl).unwrap_or(TimeDelta::MAX);
+        let url = state.export_config.signed_download_url(job.id, expires_at);
+        (Some(url), Some(expires_at))
+    } else {
+        (None, None)
+    };
+    Ok(Json(ExportJobResponse {
+        id: job.id,
+        status: job.status,
+        created_at: job.created_at,
+        completed_at: job.completed_at,
+        row_count: job.row_count,
+        error: job.error,
+        download_url,
+        expires_at,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/exports/{id}/download",
+    tag = "exports",
+    params(("id" = i64, Path, description = "Export job id"), ExportDownloadParams),
+    responses(
+        (status = 200, description = "Exported CSV file, encrypted to the recipient of the key that created the job when it has one", content(
+            (String = "text/csv"),
+            (Vec<u8> = "application/octet-stream"),
+        )),
+        (status = 403, description = "Invalid Signature", body = ErrorBody),
+        (status = 404, description = "Unknown export job or file", body = ErrorBody),
+        (status = 410, description = "Download Link Expired", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn download_export(
+    State(state): State<AppState>,
+    Path(job_id): Path<i64>,
+    Query(params): Query<ExportDownloadParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !export::verify(
+        &state.export_config.signing_key,
+        job_id,
+        params.expires,
+        &params.signature,
+    ) {
+        return Err(ApiError::Forbidden("Invalid Signature"));
+    }
+    if params.expires < Utc::now().timestamp() {
+        return Err(ApiError::Gone("Download Link Expired"));
+    }
+
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+    let job = conn
+        .interact(move |conn| get_export_job(job_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("export job"))?;
+    let file_path = job.file_path.ok_or(ApiError::NotFound("export file"))?;
+
+    let Ok(file) = tokio::fs::File::open(&file_path).await else {
+        error!("Export file {file_path} is missing");
+        return Err(ApiError::NotFound("export file"));
+    };
+    // Encrypted exports carry the extension of their scheme after `.csv`
+    let file_name = std::path::Path::new(&file_path).file_name().map_or_else(
+        || format!("export-{job_id}.csv"),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let content_type = if file_name.ends_with(".csv") {
+        "text/csv"
+    } else {
+        "application/octet-stream"
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/export/parquet",
+    security(("api_key" = [])),
+    tag = "exports",
+    params(ParquetExportParams),
+    responses(
+        (status = 200, description = "Raw readings, or aggregated buckets when `aggregation_kind` is given, as a Parquet file. Raw readings are streamed as the database cursor yields them, at most `max_stream_rows`, the body aborted rather than ended on failure", content_type = "application/vnd.apache.parquet"),
+        (status = 400, description = "Decimal format other than exact", body = ErrorBody),
+        (status = 422, description = "Invalid range", body = ValidationErrorResponse),
+        (status = 504, description = "Request deadline exceeded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_parquet_export(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    deadline: Deadline,
+    ValidQuery(params): ValidQuery<ParquetExportParams>,
+) -> Result<Response, ApiError> {
+    let ParquetExportParams {
+        aggregation_kind,
+        from_date,
+        to_date,
+        to_bound,
+    } = params;
+    decimal::require_exact("Parquet")?;
+    let to_date = to_bound.exclusive_end(to_date);
+    let watermark = state.export_config.watermark_for(api_key_id);
+    info!(aggregation_kind= ?aggregation_kind, from_date= ?from_date, to_date= ?to_date, "Received Parquet Export Request");
+    let conn = state.db.read().get().await.map_err(ApiError::Pool)?;
+
+    let body = if let Some(aggregation_kind) = aggregation_kind {
+        let history = state
+            .history
+            .start(aggregation_kind, from_date, to_date, Some(api_key_id));
+        let records = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), |conn| {
+                    aggregate_ts_query(aggregation_kind, from_date, to_date, None, conn)
+                })
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        history.succeeded(Some(records.len()));
+        streamed_body(move |writer| {
+            columnar::write_parquet(
+                writer,
+                columnar::aggregation_schema(),
+                &records,
+                |records| columnar::watermarked_aggregation_batch(records, watermark.as_ref()),
+            )
+        })
+    } else {
+        let ranks = conn
+            .interact(move |conn| {
+                with_statement_timeout(conn, deadline.remaining(), ingestion_ranks)
+            })
+            .await
+            .map_err(ApiError::Interaction)?
+            .map_err(ApiError::Database)?;
+        let max_rows = state.config.max_stream_rows;
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER_EVENTS);
+        let end = StreamEnd::Body(sender.clone());
+        let writer = BodyWriter {
+            sender,
+            buffer: Vec::new(),
+        };
+        // Readings are encoded from the blocking database thread as the cursor yields them
+        state.shutdown.spawn("parquet export", async move {
+            let streamed = conn
+                .interact(move |conn| {
+                    let mut parquet = columnar::ParquetStream::try_new(
+                        writer,
+                        columnar::readings_schema(),
+                        |readings: &[_]| {
+                            columnar::watermarked_readings_batch(
+                                readings,
+                                &ranks,
+                                watermark.as_ref(),
+                            )
+                        },
+                    )
+                    .map_err(ApiError::Parquet)?;
+                    let (mut sent, mut exceeded, mut failed) = (0, false, None);
+                    with_statement_timeout(conn, deadline.remaining(), |conn| {
+                        stream_readings(from_date, to_date, conn, |reading| {
+                            // The reading past the limit is fetched but never written
+                            exceeded = sent == max_rows;
+                            if exceeded {
+                                return false;
+                            }
+                            if let Err(e) = parquet.push(reading) {
+                                failed = Some(e);
+                                return false;
+                            }
+                            sent += 1;
+                            true
+                        })
+                    })
+                    .map_err(ApiError::Database)?;
+                    if let Some(e) = failed {
+                        return Err(ApiError::Parquet(e));
+                    }
+                    if exceeded {
+                        return Err(ApiError::BadRequest(format!(
+                            "export exceeds max_stream_rows of {max_rows} readings, narrow the range"
+                        )));
+                    }
+                    parquet.finish().map_err(ApiError::Parquet)?;
+                    Ok(sent)
+                })
+                .await
+                .map_err(ApiError::Interaction)
+                .flatten();
+            end.finish(streamed).await;
+        });
+        Body::from_stream(ReceiverStream::new(receiver))
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.apache.parquet"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"timeseries.parquet\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Runs a blocking encoder, streaming its output to the client as it is written.
+/// Failures after the first bytes are sent can only truncate the body, so are logged.
+fn streamed_body<E: Display>(
+    encode: impl FnOnce(SyncIoBridge<DuplexStream>) -> Result<(), E> + Send + 'static,
+) -> Body {
+    let (reader, writer) = tokio::io::duplex(ENCODER_PIPE_BYTES);
+    let writer = SyncIoBridge::new(writer);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = encode(writer) {
+            error!("Unable to encode streamed response: {e}");
+        }
+    });
+    Body::from_stream(ReaderStream::new(reader))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/ingestions",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    responses(
+        (status = 200, description = "Loaded datasets", body = [IngestionSummary]),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_ingestions(
+    State(db): State<Pools>,
+) -> Result<Json<Vec<IngestionSummary>>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let records = conn
+        .interact(query_ingestions)
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    Ok(Json(records))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/ingestions",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    params(IngestionUploadParams),
+    request_body(
+        description = "Readings as a CSV in the configured layout, a JSON array or one JSON object per line, each object holding datetime and amount",
+        content(
+            (String = "text/csv"),
+            (String = "application/json"),
+            (String = "application/x-ndjson"),
+        )
+    ),
+    responses(
+        (status = 200, description = "Upload merged into the earlier ingestion of its source", body = IngestionNotification),
+        (status = 201, description = "Stored ingestion", body = IngestionNotification),
+        (status = 400, description = "Unsupported content type or no valid readings", body = ErrorBody),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 409, description = "Source has already been ingested and mode is error, or into another series, or a file of the same content has", body = ErrorBody),
+        (status = 422, description = "Invalid source, or readings about a power of 1000 off those of the series while unit mismatches are rejected", body = ValidationErrorResponse),
+        (status = 423, description = "Merging into an ingestion under legal hold", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_ingestion(
+    State(state): State<AppState>,
+    ValidQuery(params): ValidQuery<IngestionUploadParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<IngestionNotification>), ApiError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let format = ReadingsFormat::from_content_type(content_type).ok_or_else(|| {
+        ApiError::BadRequest(format!("unsupported content type {content_type:?}"))
+    })?;
+
+    let IngestionUploadParams {
+        source,
+        series_id,
+        mode,
+    } = params;
+    info!(source, bytes = body.len(), format = ?format, "Received Readings Upload");
+    ingest_readings(
+        &state,
+        source,
+        series_id,
+        mode,
+        format,
+        io::Cursor::new(body),
+    )
+    .await
+}
+
+/// Stores the readings decoded from `file` as an ingestion of `source`, or merges them
+/// into the earlier ingestion of the source as `mode` says, telling live subscribers
+async fn ingest_readings(
+    state: &AppState,
+    source: String,
+    series_id: Option<i64>,
+    mode: IngestionMode,
+    format: ReadingsFormat,
+    file: impl io::Read + Send + 'static,
+) -> Result<(StatusCode, Json<IngestionNotification>), ApiError> {
+    let csv_schema = CsvSchema::from(&state.config);
+    let register_config = state.register_config.clone();
+    let drift_config = state.drift_config;
+    let priority = state.config.source_priority(&source);
+    let reject_unit_mismatch = state.config.reject_unit_mismatch();
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let notified_source = source.clone();
+    let ((first_reading_at, last_reading_at), stored, unit_mismatch, corrected) = conn
+        .interact(move |conn| {
+            // An upload of a source already ingested is merged into its ingestion
+            let earlier = match find_source_ingestion(&source, conn).map_err(ApiError::Database)? {
+                None => None,
+                Some(_) if mode == IngestionMode::Error => {
+                    return Err(ApiError::Conflict("source has already been ingested"));
+                }
+                Some((_, ingested_series))
+                    if series_id.is_some_and(|id| Some(id) != ingested_series) =>
+                {
+                    return Err(ApiError::Conflict(
+                        "source was ingested into another series",
+                    ));
+                }
+                Some(earlier) => Some(earlier),
+            };
+            let series_id = series_id.or(earlier.and_then(|(_, series_id)| series_id));
+            if let Some(id) = series_id
+                && get_series(id, conn).map_err(ApiError::Database)?.is_none()
+            {
+                return Err(ApiError::NotFound("series"));
+            }
+            let prepared =
+                prepare_readings(file, format, &csv_schema, &register_config, &drift_config);
+            let span = live::reading_span(&prepared.readings).ok_or_else(|| {
+                ApiError::BadRequest("upload holds no valid readings".to_string())
+            })?;
+            let unit_mismatch = match series_id {
+                Some(id) => {
+                    check_units(id, &prepared.readings, conn).map_err(ApiError::Database)?
+                }
+                None => None,
+            };
+            if let Some(mismatch) = unit_mismatch
+                && reject_unit_mismatch
+            {
+                return Err(ApiError::UnitMismatch(mismatch));
+            }
+            let received = prepared.readings.len();
+            let stored = if let Some((ingestion_id, _)) = earlier {
+                let overwrite = mode == IngestionMode::Overwrite;
+                // Any merge changes the totals a hold keeps as they are, the refusal is
+                // audited by the correction
+                let corrected = correct_ingestion(ingestion_id, overwrite, prepared.readings, conn)
+                    .map_err(ApiError::Database)?
+                    .map_err(|_| ApiError::LegalHold(ingestion_id))?;
+                record_issues(ingestion_id, prepared.issues, conn).map_err(ApiError::Database)?;
+                corrected
+            } else {
+                let (ingestion_id, inserted) = insert_ingestion_with_drift(
+                    source,
+                    series_id,
+                    priority,
+                    prepared,
+                    &drift_config,
+                    conn,
+                )
+                .map_err(ApiError::Database)?
+                .ok_or(ApiError::Conflict("file has already been ingested"))?;
+                IngestionCorrection {
+                    ingestion_id,
+                    inserted,
+                    updated: 0,
+                    skipped: received - inserted,
+                }
+            };
+            if let Some(mismatch) = unit_mismatch {
+                flag_unit_mismatch(stored.ingestion_id, mismatch, conn)
+                    .map_err(ApiError::Database)?;
+            }
+            Ok((span, stored, unit_mismatch, earlier.is_some()))
+        })
+        .await
+        .map_err(ApiError::Interaction)??;
+    drop(conn);
+    let ingestion_id = stored.ingestion_id;
+    if corrected {
+        info!(
+            ingestion_id,
+            inserted = stored.inserted,
+            updated = stored.updated,
+            skipped = stored.skipped,
+            "Merged upload into the earlier ingestion of its source"
+        );
+    }
+    if let Some(mismatch) = unit_mismatch {
+        warn!(
+            ingestion_id,
+            ratio = mismatch.ratio,
+            "Readings are about {}x those of the series, check their unit",
+            mismatch.factor
+        );
+    }
+
+    if let Some(cache) = &state.hot_cache
+        && let Err(e) = cache.refresh(state.db.primary()).await
+    {
+        error!("Unable to refresh hot cache after upload: {e}");
+    }
+    state.invalidate_response_cache().await;
+    let notification = IngestionNotification {
+        ingestion_id,
+        source: notified_source,
+        rows: stored.inserted,
+        updated_rows: stored.updated,
+        skipped_rows: stored.skipped,
+        first_reading_at,
+        last_reading_at,
+        unit_mismatch,
+    };
+    state.ingestion_events.publish(notification.clone());
+    let status = if corrected {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    Ok((status, Json(notification)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/uploads",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    request_body = UploadUrlRequest,
+    responses(
+        (status = 201, description = "Pre-signed URL to PUT the file to", body = UploadUrlResponse),
+        (status = 400, description = "File name is not that of a readings file or holds characters other than letters, digits, ., _ and -", body = ErrorBody),
+        (status = 404, description = "No upload bucket is configured", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_upload_url(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    Json(request): Json<UploadUrlRequest>,
+) -> Result<(StatusCode, Json<UploadUrlResponse>), ApiError> {
+    let uploads = state
+        .uploads
+        .as_ref()
+        .ok_or(ApiError::NotFound("upload bucket"))?;
+    let issued = uploads
+        .issue(api_key_id, &request.file_name, Utc::now())
+        .await
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "{:?} is not a plain .csv, .json, .ndjson or .jsonl file name",
+                request.file_name
+            ))
+        })?
+        .map_err(ApiError::ObjectStore)?;
+    info!(api_key_id, location = %issued.location, "Issued pre-signed upload URL");
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadUrlResponse {
+            upload_url: issued.upload_url.to_string(),
+            location: issued.location.to_string(),
+            expires_at: issued.expires_at,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/uploads/ingest",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    request_body = UploadedFile,
+    responses(
+        (status = 200, description = "File merged into the earlier ingestion of its location", body = IngestionNotification),
+        (status = 201, description = "Stored ingestion", body = IngestionNotification),
+        (status = 400, description = "Location was not issued to this key, the file could not be decompressed or holds no valid readings", body = ErrorBody),
+        (status = 404, description = "No upload bucket is configured, nothing was uploaded to the location, or unknown series", body = ErrorBody),
+        (status = 409, description = "Location has already been ingested and mode is error, or into another series, or a file of the same content has", body = ErrorBody),
+        (status = 422, description = "Readings about a power of 1000 off those of the series while unit mismatches are rejected", body = ErrorBody),
+        (status = 423, description = "Merging into an ingestion under legal hold", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_uploaded_file(
+    State(state): State<AppState>,
+    ApiKey(api_key_id): ApiKey,
+    Json(uploaded): Json<UploadedFile>,
+) -> Result<(StatusCode, Json<IngestionNotification>), ApiError> {
+    let uploads = state
+        .uploads
+        .as_ref()
+        .ok_or(ApiError::NotFound("upload bucket"))?;
+    let (url, (format, compression)) = uploads
+        .uploaded_by(&uploaded.location, api_key_id)
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "{:?} is not an upload location issued to this key",
+                uploaded.location
+            ))
+        })?;
+    let file = file_reader::open_remote(&url).await.map_err(|e| match e {
+        object_store::Error::NotFound { .. } => ApiError::NotFound("uploaded file"),
+        e => ApiError::ObjectStore(e),
+    })?;
+    let file = compression
+        .decoder(file)
+        .map_err(|e| ApiError::BadRequest(format!("unable to decompress upload: {e}")))?;
+
+    let source = url.to_string();
+    info!(api_key_id, source, format = ?format, "Ingesting uploaded file from the bucket");
+    ingest_readings(
+        &state,
+        source,
+        uploaded.series_id,
+        uploaded.mode,
+        format,
+        file,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/ingestions/{id}/clock-drift",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    params(("id" = i64, Path, description = "Ingestion id")),
+    responses(
+        (status = 200, description = "Offsets of the ingestion's readings from the interval grid", body = IngestionClockDrift),
+        (status = 404, description = "Unknown ingestion or no drift analysis recorded", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_ingestion_clock_drift(
+    State(db): State<Pools>,
+    Path(ingestion_id): Path<i64>,
+) -> Result<Json<IngestionClockDrift>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let drift = conn
+        .interact(move |conn| query_clock_drift(ingestion_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("clock drift report"))?;
+    Ok(Json(drift))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/ingestions/{id}/issues",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    params(("id" = i64, Path, description = "Ingestion id")),
+    responses(
+        (status = 200, description = "Rows skipped and readings worth a second look, in the order they were found", body = Vec<IngestionIssue>),
+        (status = 404, description = "Unknown ingestion", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_ingestion_issues(
+    State(db): State<Pools>,
+    Path(ingestion_id): Path<i64>,
+) -> Result<Json<Vec<IngestionIssue>>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let issues = conn
+        .interact(move |conn| query_ingestion_issues(ingestion_id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("ingestion"))?;
+    Ok(Json(issues))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/timeseries/v1/ingestions/{id}",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    params(("id" = i64, Path, description = "Ingestion id")),
+    responses(
+        (status = 200, description = "Deleted row counts", body = DeletedIngestion),
+        (status = 404, description = "Unknown ingestion", body = ErrorBody),
+        (status = 423, description = "Ingestion under legal hold", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn delete_ingestion_by_id(
+    State(state): State<AppState>,
+    Path(ingestion_id): Path<i64>,
+) -> Result<Json<DeletedIngestion>, ApiError> {
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(ingestion_id, "Received Delete Ingestion Request");
+    let deleted = conn
+        .interact(move |conn| {
+            // Refusals are audited, the hold's foreign key backs the check up
+            if let Some(hold) = find_hold(ingestion_id, conn).map_err(ApiError::Database)? {
+                let refusal = AuditEntry::new(
+                    AuditAction::DeleteIngestion,
+                    Some(ingestion_id),
+                    false,
+                    format!("refused under legal hold: {}", hold.reason),
+                );
+                record_audit(refusal, conn).map_err(ApiError::Database)?;
+                return Err(ApiError::LegalHold(ingestion_id));
+            }
+            delete_ingestion(ingestion_id, conn).map_err(ApiError::Database)
+        })
+        .await
+        .map_err(ApiError::Interaction)??
+        .ok_or(ApiError::NotFound("ingestion"))?;
+    drop(conn);
+
+    if let Some(cache) = &state.hot_cache
+        && let Err(e) = cache.refresh(state.db.primary()).await
+    {
+        error!("Unable to refresh hot cache after delete: {e}");
+    }
+    state.invalidate_response_cache().await;
+    Ok(Json(deleted))
+}
+
+/// Reports a taken name or a series still referenced by ingestions as a conflict
+fn series_error(error: diesel::result::Error) -> ApiError {
+    match error {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            ApiError::Conflict("series name already exists")
+        }
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            ApiError::Conflict("series still has ingestions")
+        }
+        other => ApiError::Database(other),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/series",
+    security(("api_key" = [])),
+    tag = "series",
+    responses(
+        (status = 200, description = "Every series ordered by name", body = [Series]),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_series_list(State(db): State<Pools>) -> Result<Json<Vec<Series>>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let series = conn
+        .interact(list_series)
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+    Ok(Json(series))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/series",
+    security(("api_key" = [])),
+    tag = "series",
+    request_body = SeriesDefinition,
+    responses(
+        (status = 201, description = "Created series", body = Series),
+        (status = 409, description = "Series name already exists", body = ErrorBody),
+        (status = 422, description = "Invalid series", body = ValidationErrorResponse),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_series(
+    State(db): State<Pools>,
+    ValidJson(definition): ValidJson<SeriesDefinition>,
+) -> Result<(StatusCode, Json<Series>), ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(name = definition.name, "Received Create Series Request");
+    let series = conn
+        .interact(move |conn| create_series(definition, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(series_error)?;
+    Ok((StatusCode::CREATED, Json(series)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/series/{id}",
+    security(("api_key" = [])),
+    tag = "series",
+    params(("id" = i64, Path, description = "Series id")),
+    responses(
+        (status = 200, description = "Series", body = Series),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_series_by_id(
+    State(db): State<Pools>,
+    Path(id): Path<i64>,
+) -> Result<Json<Series>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    let series = conn
+        .interact(move |conn| get_series(id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("series"))?;
+    Ok(Json(series))
+}
+
+#[utoipa::path(
+    put,
+    path = "/timeseries/v1/series/{id}",
+    security(("api_key" = [])),
+    tag = "series",
+    params(("id" = i64, Path, description = "Series id")),
+    request_body = SeriesDefinition,
+    responses(
+        (status = 200, description = "Replaced series", body = Series),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 409, description = "Series name already exists", body = ErrorBody),
+        (status = 422, description = "Invalid series", body = ValidationErrorResponse),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn put_series(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(definition): ValidJson<SeriesDefinition>,
+) -> Result<Json<Series>, ApiError> {
+    let conn = state.db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(id, name = definition.name, "Received Update Series Request");
+    let series = conn
+        .interact(move |conn| update_series(id, definition, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(series_error)?
+        .ok_or(ApiError::NotFound("series"))?;
+    // Breakdowns group by the series' fuel type
+    state.invalidate_response_cache().await;
+    Ok(Json(series))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/timeseries/v1/series/{id}",
+    security(("api_key" = [])),
+    tag = "series",
+    params(("id" = i64, Path, description = "Series id")),
+    responses(
+        (status = 200, description = "Deleted series", body = Series),
+        (status = 404, description = "Unknown series", body = ErrorBody),
+        (status = 409, description = "Ingestions still reference the series", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn delete_series_by_id(
+    State(db): State<Pools>,
+    Path(id): Path<i64>,
+) -> Result<Json<Series>, ApiError> {
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(id, "Received Delete Series Request");
+    let series = conn
+        .interact(move |conn| delete_series(id, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(series_error)?
+        .ok_or(ApiError::NotFound("series"))?;
+    Ok(Json(series))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/meters/bulk",
+    security(("api_key" = [])),
+    tag = "meters",
+    request_body(
+        description = "Meters as a JSON array or a CSV with meter_code, site_name, capacity_kw and `;` separated series columns",
+        content(
+            ([MeterOnboarding] = "application/json"),
+            (String = "text/csv"),
+        )
+    ),
+    responses(
+        (status = 200, description = "Per-row onboarding outcome", body = MeterOnboardingResponse),
+        (status = 400, description = "Malformed request body", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn post_meters_bulk(
+    State(db): State<Pools>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<MeterOnboardingResponse>, ApiError> {
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/csv"));
+    let rows = if is_csv {
+        meter_csv_rows(body.as_ref())
+    } else {
+        serde_json::from_slice::<Vec<MeterOnboarding>>(&body)
+            .map_err(|e| ApiError::BadRequest(e.to_string()))?
+            .into_iter()
+            .map(Ok)
+            .collect()
+    };
+
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(rows = rows.len(), "Received Bulk Meter Onboarding Request");
+    let results = conn
+        .interact(move |conn| onboard_meters(rows, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?;
+
+    let created = results.iter().filter(|r| r.error.is_none()).count();
+    Ok(Json(MeterOnboardingResponse {
+        created,
+        failed: results.len() - created,
+        results,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeseries/v1/ingest/detect-format",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    params(DetectFormatParams),
+    request_body(content = String, description = "Start of the file to inspect", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Detected layout with confidence scores", body = FormatDetection),
+        (status = 422, description = "Invalid sample size", body = ValidationErrorResponse),
+    )
+)]
+pub async fn post_detect_format(
+    State(config): State<AppConfig>,
+    ValidQuery(params): ValidQuery<DetectFormatParams>,
+    body: Bytes,
+) -> Json<FormatDetection> {
+    let sample_rows = params.sample_rows.unwrap_or(detect::DEFAULT_SAMPLE_ROWS);
+    info!(
+        bytes = body.len(),
+        sample_rows, "Received Format Detection Request"
+    );
+    Json(detect::detect_format(
+        &body,
+        sample_rows,
+        &CsvSchema::from(&config),
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/timeseries/v1/meters/{meter_code}/profile",
+    security(("api_key" = [])),
+    tag = "meters",
+    params(("meter_code" = String, Path, description = "Meter code")),
+    request_body = MeterProfileUpload,
+    responses(
+        (status = 200, description = "Profile replaced", body = MeterProfileStored),
+        (status = 400, description = "Invalid profile", body = ErrorBody),
+        (status = 404, description = "Unknown meter", body = ErrorBody),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+        (status = 503, description = "Instance is read-only", body = ErrorBody),
+    )
+)]
+pub async fn put_meter_profile(
+    State(db): State<Pools>,
+    Path(meter_code): Path<String>,
+    Json(upload): Json<MeterProfileUpload>,
+) -> Result<Json<MeterProfileStored>, ApiError> {
+    upload.validate().map_err(ApiError::BadRequest)?;
+
+    let conn = db.primary().get().await.map_err(ApiError::Pool)?;
+
+    info!(meter_code, "Received Meter Profile Upload");
+    let code = meter_code.clone();
+    let months_stored = conn
+        .interact(move |conn| replace_meter_profile(&code, upload.months, conn))
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("meter"))?;
+
+    Ok(Json(MeterProfileStored {
+        meter_code,
+        months_stored,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/meters/{meter_code}/variance",
+    security(("api_key" = [])),
+    tag = "meters",
+    params(("meter_code" = String, Path, description = "Meter code"), VarianceParams),
+    responses(
+        (status = 200, description = "Monthly actuals of the meter's series against the P50/P90 profile", body = VarianceResponse),
+        (status = 404, description = "Unknown meter", body = ErrorBody),
+        (status = 422, description = "Invalid range", body = ValidationErrorResponse),
+        (status = 500, description = "Internal Error", body = ErrorBody),
+    )
+)]
+pub async fn get_meter_variance(
+    State(db): State<Pools>,
+    Path(meter_code): Path<String>,
+    ValidQuery(VarianceParams {
+        from_date,
+        to_date,
+        to_bound,
+    }): ValidQuery<VarianceParams>,
+) -> Result<Json<VarianceResponse>, ApiError> {
+    let to_date = to_bound.exclusive_end(to_date);
+    let conn = db.read().get().await.map_err(ApiError::Pool)?;
+
+    let code = meter_code.clone();
+    let (profile, actuals) = conn
+        .interact(move |conn| {
+            let Some(profile) = load_meter_profile(&code, conn)? else {
+                return Ok(None);
+            };
+            let actuals = monthly_actuals(&code, from_date, to_date, conn)?;
+            Ok::<_, diesel::result::Error>(Some((profile, actuals)))
+        })
+        .await
+        .map_err(ApiError::Interaction)?
+        .map_err(ApiError::Database)?
+        .ok_or(ApiError::NotFound("meter"))?;
+
+    let actuals = bucket::fill_missing(
+        Aggregation::Monthly,
+        from_date,
+        to_date,
+        actuals,
+        FillMissing::Null,
+    );
+    Ok(Json(VarianceResponse {
+        meter_code,
+        months: variance::monthly_variance(&profile, actuals, rounding::current()),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    security(("api_key" = [])),
+    tag = "graphql",
+    request_body(content = Object, description = "GraphQL request with `query`, and optionally `variables` and `operationName`"),
+    responses(
+        (status = 200, description = "GraphQL response, failed fields are reported under `errors`", body = Object),
+        (status = 400, description = "Decimal format other than exact", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+    )
+)]
+pub async fn post_graphql(
+    State(state): State<AppState>,
+    api_key: ApiKey,
+    deadline: Deadline,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Json<async_graphql::Response>, ApiError> {
+    decimal::require_exact("GraphQL")?;
+    Ok(Json(
+        graphql::execute(state, api_key, deadline, request).await,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/timeseries/v1/ws",
+    security(("api_key" = [])),
+    tag = "ingestions",
+    responses(
+        (status = 101, description = "WebSocket of `LiveUpdate` JSON messages, one per new ingestion", body = LiveUpdate),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+    )
+)]
+pub async fn get_live_updates(
+    ws: WebSocketUpgrade,
+    State(events): State<IngestionEvents>,
+) -> Response {
+    ws.on_upgrade(move |socket| live::stream_ingestions(socket, events))
+}
+
+#[utoipa::path(
+    get,
+    path = "/graphql",
+    tag = "graphql",
+    responses((status = 200, description = "GraphQL Playground", content_type = "text/html", body = String))
+)]
+pub async fn get_graphql_playground() -> Html<String> {
+    Html(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
@@ -5,11 +5,130 @@ pub mod renewable {
         #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
         #[diesel(postgres_type(name = "aggregation_kind", schema = "renewable"))]
         pub struct AggregationKind;
+
+        #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "ingestion_status", schema = "renewable"))]
+        pub struct IngestionStatus;
+
+        #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "job_status", schema = "renewable"))]
+        pub struct JobStatus;
+
+        #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "query_status", schema = "renewable"))]
+        pub struct QueryStatus;
+    }
+
+    diesel::table! {
+        renewable.api_keys (id) {
+            id -> Int8,
+            name -> Text,
+            key_hash -> Text,
+            created_at -> Timestamptz,
+            revoked_at -> Nullable<Timestamptz>,
+            export_recipient -> Nullable<Text>,
+        }
+    }
+
+    diesel::table! {
+        renewable.audit_log (id) {
+            id -> Int8,
+            recorded_at -> Timestamptz,
+            action -> Text,
+            ingestion_id -> Nullable<Int8>,
+            permitted -> Bool,
+            detail -> Nullable<Text>,
+        }
     }
 
     diesel::table! {
         use diesel::sql_types::*;
         use super::sql_types::AggregationKind;
+        use super::sql_types::JobStatus;
+
+        renewable.export_jobs (id) {
+            id -> Int8,
+            created_at -> Timestamptz,
+            status -> JobStatus,
+            aggregation -> AggregationKind,
+            from_date -> Nullable<Timestamptz>,
+            to_date -> Nullable<Timestamptz>,
+            file_path -> Nullable<Text>,
+            row_count -> Nullable<Int8>,
+            error -> Nullable<Text>,
+            completed_at -> Nullable<Timestamptz>,
+            api_key_id -> Nullable<Int8>,
+        }
+    }
+
+    diesel::table! {
+        renewable.ingestion_clock_drift (ingestion_id) {
+            ingestion_id -> Int8,
+            interval_secs -> Int4,
+            readings -> Int8,
+            drifted_readings -> Int8,
+            max_offset_secs -> Int8,
+            snapped -> Bool,
+            collisions -> Int8,
+        }
+    }
+
+    diesel::table! {
+        renewable.ingestion_chain (seq) {
+            seq -> Int8,
+            recorded_at -> Timestamptz,
+            event -> Text,
+            ingestion_id -> Int8,
+            readings -> Int8,
+            content_hash -> Text,
+            previous_hash -> Nullable<Text>,
+            hash -> Text,
+        }
+    }
+
+    diesel::table! {
+        renewable.ingestion_issues (id) {
+            id -> Int8,
+            ingestion_id -> Int8,
+            kind -> Text,
+            line -> Nullable<Int8>,
+            datetime -> Nullable<Timestamptz>,
+            detail -> Text,
+        }
+    }
+
+    diesel::table! {
+        renewable.legal_holds (ingestion_id) {
+            ingestion_id -> Int8,
+            placed_at -> Timestamptz,
+            reason -> Text,
+        }
+    }
+
+    diesel::table! {
+        renewable.meter_profiles (meter_id, month) {
+            meter_id -> Int8,
+            month -> Int2,
+            p50_kwh -> Numeric,
+            p90_kwh -> Numeric,
+            uploaded_at -> Timestamptz,
+        }
+    }
+
+    diesel::table! {
+        renewable.meters (id) {
+            id -> Int8,
+            meter_code -> Text,
+            site_name -> Text,
+            capacity_kw -> Nullable<Numeric>,
+            created_at -> Timestamptz,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::AggregationKind;
+        use super::sql_types::QueryStatus;
 
         renewable.query_history (id) {
             id -> Int8,
@@ -17,14 +136,106 @@ pub mod renewable {
             from_date -> Nullable<Timestamptz>,
             to_date -> Nullable<Timestamptz>,
             aggregation -> AggregationKind,
+            api_key_id -> Nullable<Int8>,
+            duration_ms -> Nullable<Int8>,
+            row_count -> Nullable<Int8>,
+            status -> QueryStatus,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::AggregationKind;
+        use super::sql_types::JobStatus;
+
+        renewable.query_jobs (id) {
+            id -> Int8,
+            created_at -> Timestamptz,
+            status -> JobStatus,
+            aggregation -> AggregationKind,
+            from_date -> Nullable<Timestamptz>,
+            to_date -> Nullable<Timestamptz>,
+            api_key_id -> Nullable<Int8>,
+            result -> Nullable<Jsonb>,
+            row_count -> Nullable<Int8>,
+            error -> Nullable<Text>,
+            completed_at -> Nullable<Timestamptz>,
+        }
+    }
+
+    diesel::table! {
+        renewable.series (id) {
+            id -> Int8,
+            name -> Text,
+            unit -> Text,
+            fuel_type -> Nullable<Text>,
+            site -> Nullable<Text>,
+            created_at -> Timestamptz,
+            meter_id -> Nullable<Int8>,
+        }
+    }
+
+    diesel::table! {
+        renewable.storage_stats (sampled_on, relation) {
+            sampled_on -> Date,
+            relation -> Text,
+            parent -> Nullable<Text>,
+            total_bytes -> Int8,
+            index_bytes -> Int8,
+            row_estimate -> Int8,
         }
     }
 
     diesel::table! {
+        renewable.ts_compressed_blocks (ingestion_id, day) {
+            ingestion_id -> Int8,
+            day -> Date,
+            first_datetime -> Timestamptz,
+            last_datetime -> Timestamptz,
+            readings -> Int4,
+            recorded_at -> Timestamptz,
+            block -> Bytea,
+        }
+    }
+
+    diesel::table! {
+        renewable.ts_daily_summary (ingestion_id, day) {
+            ingestion_id -> Int8,
+            day -> Timestamptz,
+            first_datetime -> Timestamptz,
+            last_datetime -> Timestamptz,
+            total_amount -> Numeric,
+            readings -> Int8,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::IngestionStatus;
+
         renewable.ts_metadata (ingestion_id) {
             ingestion_id -> Int8,
             ingestion_datetime -> Timestamptz,
             source -> Text,
+            series_id -> Nullable<Int8>,
+            priority -> Int4,
+            row_count -> Nullable<Int8>,
+            min_datetime -> Nullable<Timestamptz>,
+            max_datetime -> Nullable<Timestamptz>,
+            error_count -> Int8,
+            duration_ms -> Nullable<Int8>,
+            status -> IngestionStatus,
+            unit_mismatch -> Nullable<Float8>,
+            checksum -> Nullable<Text>,
+        }
+    }
+
+    diesel::table! {
+        renewable.ts_monthly_summary (ingestion_id, month) {
+            ingestion_id -> Int8,
+            month -> Timestamptz,
+            total_amount -> Numeric,
+            readings -> Int8,
         }
     }
 
@@ -33,10 +244,57 @@ pub mod renewable {
             ingestion_id -> Int8,
             datetime -> Timestamptz,
             amount -> Numeric,
+            recorded_at -> Timestamptz,
+            extra -> Nullable<Jsonb>,
+        }
+    }
+
+    diesel::table! {
+        renewable.ts_store_revisions (ingestion_id, datetime, revision) {
+            ingestion_id -> Int8,
+            datetime -> Timestamptz,
+            revision -> Int4,
+            amount -> Numeric,
+            extra -> Nullable<Jsonb>,
+            valid_from -> Timestamptz,
+            valid_to -> Timestamptz,
         }
     }
 
+    diesel::joinable!(export_jobs -> api_keys (api_key_id));
+    diesel::joinable!(ingestion_clock_drift -> ts_metadata (ingestion_id));
+    diesel::joinable!(ingestion_issues -> ts_metadata (ingestion_id));
+    diesel::joinable!(legal_holds -> ts_metadata (ingestion_id));
+    diesel::joinable!(meter_profiles -> meters (meter_id));
+    diesel::joinable!(query_history -> api_keys (api_key_id));
+    diesel::joinable!(query_jobs -> api_keys (api_key_id));
+    diesel::joinable!(series -> meters (meter_id));
+    diesel::joinable!(ts_compressed_blocks -> ts_metadata (ingestion_id));
+    diesel::joinable!(ts_daily_summary -> ts_metadata (ingestion_id));
+    diesel::joinable!(ts_metadata -> series (series_id));
+    diesel::joinable!(ts_monthly_summary -> ts_metadata (ingestion_id));
     diesel::joinable!(ts_store -> ts_metadata (ingestion_id));
+    diesel::joinable!(ts_store_revisions -> ts_metadata (ingestion_id));
 
-    diesel::allow_tables_to_appear_in_same_query!(query_history, ts_metadata, ts_store,);
+    diesel::allow_tables_to_appear_in_same_query!(
+        api_keys,
+        audit_log,
+        export_jobs,
+        ingestion_chain,
+        ingestion_clock_drift,
+        ingestion_issues,
+        legal_holds,
+        meter_profiles,
+        meters,
+        query_history,
+        query_jobs,
+        series,
+        storage_stats,
+        ts_compressed_blocks,
+        ts_daily_summary,
+        ts_metadata,
+        ts_monthly_summary,
+        ts_store,
+        ts_store_revisions,
+    );
 }
@@ -0,0 +1,701 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use deadpool_diesel::postgres::Pool;
+use diesel::{PgConnection, QueryResult};
+
+use crate::{
+    db::{
+        PgError,
+        catalog::{live_columns, live_indexes},
+    },
+    model::database::{LiveColumn, LiveIndex},
+};
+
+/// Diesel schema the queries of this build are compiled against
+const DIESEL_SCHEMA: &str = include_str!("schema.rs");
+
+/// Name and `up.sql` of every embedded migration, in the order they are applied
+const MIGRATIONS: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+const SCHEMA: &str = "renewable";
+
+/// Column declared by the Diesel schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedColumn {
+    pub table: String,
+    pub column: String,
+    /// Postgres type name as the catalog reports it, see [`LiveColumn::udt_name`]
+    pub udt_name: String,
+    pub nullable: bool,
+}
+
+/// Index left behind by the migrations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedIndex {
+    pub table: String,
+    /// `CREATE INDEX` statement of the migration creating it
+    pub statement: String,
+}
+
+/// Tables, columns and indexes this build expects the database to hold
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub columns: Vec<ExpectedColumn>,
+    pub indexes: BTreeMap<String, ExpectedIndex>,
+}
+
+/// Difference between the live database and the [`Snapshot`], e.g. a hand-made index or a
+/// column type changed outside the migrations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+        udt_name: String,
+        nullable: bool,
+    },
+    ColumnType {
+        table: String,
+        column: String,
+        expected: String,
+        found: String,
+    },
+    Nullability {
+        table: String,
+        column: String,
+        /// Whether the Diesel schema expects the column to be nullable
+        nullable: bool,
+    },
+    /// Column the Diesel schema does not know, `required` when it is `NOT NULL` without a
+    /// default so every insert fails
+    ExtraColumn {
+        table: String,
+        column: String,
+        required: bool,
+    },
+    MissingIndex {
+        name: String,
+        table: String,
+        statement: String,
+    },
+    ExtraIndex {
+        name: String,
+        table: String,
+        definition: String,
+    },
+}
+
+fn not_null(nullable: bool) -> &'static str {
+    if nullable { "nullable" } else { "NOT NULL" }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTable { table } => write!(f, "table {SCHEMA}.{table} is missing"),
+            Self::MissingColumn { table, column, .. } => {
+                write!(f, "column {SCHEMA}.{table}.{column} is missing")
+            }
+            Self::ColumnType {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "column {SCHEMA}.{table}.{column} is {found}, expected {expected}"
+            ),
+            Self::Nullability {
+                table,
+                column,
+                nullable,
+            } => write!(
+                f,
+                "column {SCHEMA}.{table}.{column} is {}, expected {}",
+                not_null(!nullable),
+                not_null(*nullable)
+            ),
+            Self::ExtraColumn {
+                table,
+                column,
+                required,
+            } => {
+                write!(
+                    f,
+                    "column {SCHEMA}.{table}.{column} is not in the Diesel schema"
+                )?;
+                if *required {
+                    write!(f, ", inserts fail as it is NOT NULL without a default")?;
+                }
+                Ok(())
+            }
+            Self::MissingIndex { name, table, .. } => {
+                write!(f, "index {SCHEMA}.{name} on {table} is missing")
+            }
+            Self::ExtraIndex { name, table, .. } => write!(
+                f,
+                "index {SCHEMA}.{name} on {table} was not created by a migration"
+            ),
+        }
+    }
+}
+
+impl Drift {
+    /// SQL that would bring the database back in line, to be reviewed before it is run
+    pub fn fix(&self) -> String {
+        match self {
+            Self::MissingTable { table } => {
+                format!("-- restore {SCHEMA}.{table} from a backup or migrate an empty database")
+            }
+            Self::MissingColumn {
+                table,
+                column,
+                udt_name,
+                nullable,
+            } => format!(
+                "ALTER TABLE {SCHEMA}.{table} ADD COLUMN {column} {udt_name}{};",
+                if *nullable { "" } else { " NOT NULL" }
+            ),
+            Self::ColumnType {
+                table,
+                column,
+                expected,
+                ..
+            } => format!(
+                "ALTER TABLE {SCHEMA}.{table} ALTER COLUMN {column} TYPE {expected} USING {column}::{expected};"
+            ),
+            Self::Nullability {
+                table,
+                column,
+                nullable,
+            } => format!(
+                "ALTER TABLE {SCHEMA}.{table} ALTER COLUMN {column} {} NOT NULL;",
+                if *nullable { "DROP" } else { "SET" }
+            ),
+            Self::ExtraColumn {
+                table,
+                column,
+                required: true,
+            } => format!("ALTER TABLE {SCHEMA}.{table} ALTER COLUMN {column} DROP NOT NULL;"),
+            Self::ExtraColumn { table, column, .. } => {
+                format!("ALTER TABLE {SCHEMA}.{table} DROP COLUMN {column};")
+            }
+            Self::MissingIndex { statement, .. } => format!("{statement};"),
+            Self::ExtraIndex { name, .. } => format!("DROP INDEX {SCHEMA}.{name};"),
+        }
+    }
+}
+
+/// Value of a `Wrapper<..>` type, e.g. `Text` of `Nullable<Text>`
+fn type_argument<'a>(diesel_type: &'a str, wrapper: &str) -> Option<&'a str> {
+    diesel_type
+        .strip_prefix(wrapper)?
+        .strip_prefix('<')?
+        .strip_suffix('>')
+        .map(str::trim)
+}
+
+/// Catalog name of a non-null Diesel SQL type: `Int8` is `int8`, `Array<Text>` is `_text`
+/// and an enum such as `JobStatus` is `job_status`
+fn udt_name(diesel_type: &str) -> String {
+    if let Some(element) = type_argument(diesel_type, "Array") {
+        let element = type_argument(element, "Nullable").unwrap_or(element);
+        return format!("_{}", udt_name(element));
+    }
+    let name = diesel_type.rsplit("::").next().unwrap_or(diesel_type);
+    let mut snake = String::with_capacity(name.len() + 2);
+    for (position, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if position > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Columns of the `table!` blocks of a Diesel schema file
+fn parse_schema(schema: &str) -> Vec<ExpectedColumn> {
+    let mut columns = Vec::new();
+    let mut table = None;
+    let mut sql_name = None;
+    for line in schema.lines().map(str::trim) {
+        if let Some(declaration) = line.strip_prefix(&format!("{SCHEMA}.")) {
+            table = declaration
+                .split([' ', '('])
+                .next()
+                .map(ToString::to_string);
+        } else if line == "}" {
+            table = None;
+        } else if let Some(name) = line
+            .strip_prefix("#[sql_name = \"")
+            .and_then(|name| name.strip_suffix("\"]"))
+        {
+            sql_name = Some(name.to_string());
+        } else if let (Some(table), Some((column, diesel_type))) = (&table, line.split_once("->")) {
+            let diesel_type = diesel_type.trim().trim_end_matches(',');
+            let (diesel_type, nullable) = match type_argument(diesel_type, "Nullable") {
+                Some(inner) => (inner, true),
+                None => (diesel_type, false),
+            };
+            columns.push(ExpectedColumn {
+                table: table.clone(),
+                column: sql_name
+                    .take()
+                    .unwrap_or_else(|| column.trim().trim_start_matches("r#").to_string()),
+                udt_name: udt_name(diesel_type),
+                nullable,
+            });
+        }
+    }
+    columns
+}
+
+/// Statements of a migration, leaving `;` within quotes and `$$` bodies alone and
+/// dropping `--` comments
+fn statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let (mut in_string, mut in_body) = (false, false);
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !in_body => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            '$' if !in_string && chars.peek() == Some(&'$') => {
+                chars.next();
+                in_body = !in_body;
+                current.push_str("$$");
+            }
+            '-' if !in_string && !in_body && chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        current.push(c);
+                        break;
+                    }
+                }
+            }
+            ';' if !in_string && !in_body => {
+                statements.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    statements.push(current.trim().to_string());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+/// Identifier as Postgres stores it, unquoted, unqualified and folded to lower case
+fn identifier(word: &str) -> String {
+    let word = word.split('(').next().unwrap_or_default();
+    let word = word.trim_end_matches(',');
+    let word = word.strip_prefix(&format!("{SCHEMA}.")).unwrap_or(word);
+    if word.starts_with('"') {
+        word.trim_matches('"').to_string()
+    } else {
+        word.to_lowercase()
+    }
+}
+
+/// Applies the index changes of one statement
+fn replay(statement: &str, indexes: &mut BTreeMap<String, ExpectedIndex>) {
+    let words: Vec<&str> = statement.split_whitespace().collect();
+    let keywords: Vec<String> = words.iter().map(|w| w.to_ascii_uppercase()).collect();
+    let keyword = |position: usize, expected: &str| {
+        keywords
+            .get(position)
+            .is_some_and(|keyword| keyword == expected)
+    };
+    let mut position = 2;
+    let mut skip = |words: &[&str]| {
+        if words
+            .iter()
+            .enumerate()
+            .all(|(offset, word)| keyword(position + offset, word))
+        {
+            position += words.len();
+        }
+    };
+
+    if keyword(0, "CREATE") && (keyword(1, "INDEX") || keyword(2, "INDEX")) {
+        skip(&["INDEX"]);
+        skip(&["CONCURRENTLY"]);
+        skip(&["IF", "NOT", "EXISTS"]);
+        // Unnamed indexes are named by Postgres, so they cannot be compared
+        if keyword(position, "ON") || !keyword(position + 1, "ON") {
+            return;
+        }
+        let table_position = position + if keyword(position + 2, "ONLY") { 3 } else { 2 };
+        if let (Some(name), Some(table)) = (words.get(position), words.get(table_position)) {
+            indexes.insert(
+                identifier(name),
+                ExpectedIndex {
+                    table: identifier(table),
+                    statement: statement.split_whitespace().collect::<Vec<_>>().join(" "),
+                },
+            );
+        }
+    } else if keyword(0, "DROP") && keyword(1, "INDEX") {
+        skip(&["CONCURRENTLY"]);
+        skip(&["IF", "EXISTS"]);
+        for name in &words[position.min(words.len())..] {
+            indexes.remove(&identifier(name));
+        }
+    } else if keyword(0, "DROP") && keyword(1, "TABLE") {
+        skip(&["IF", "EXISTS"]);
+        let dropped: BTreeSet<_> = words[position.min(words.len())..]
+            .iter()
+            .map(|table| identifier(table))
+            .collect();
+        indexes.retain(|_, index| !dropped.contains(&index.table));
+    } else if keyword(0, "ALTER") && (keyword(1, "INDEX") || keyword(1, "TABLE")) {
+        skip(&["IF", "EXISTS"]);
+        if !(keyword(position + 1, "RENAME") && keyword(position + 2, "TO")) {
+            return;
+        }
+        let (Some(from), Some(to)) = (words.get(position), words.get(position + 3)) else {
+            return;
+        };
+        let (from, to) = (identifier(from), identifier(to));
+        if keyword(1, "INDEX") {
+            if let Some(index) = indexes.remove(&from) {
+                indexes.insert(to, index);
+            }
+        } else {
+            for index in indexes.values_mut().filter(|index| index.table == from) {
+                index.table.clone_from(&to);
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Snapshot of this build, its Diesel schema and embedded migrations
+    pub fn expected() -> Self {
+        Self::parse(DIESEL_SCHEMA, MIGRATIONS)
+    }
+
+    /// Snapshot of a Diesel schema file and the `(name, up.sql)` migrations, in order
+    pub fn parse(schema: &str, migrations: &[(&str, &str)]) -> Self {
+        let mut indexes = BTreeMap::new();
+        for (_, sql) in migrations {
+            for statement in statements(sql) {
+                replay(&statement, &mut indexes);
+            }
+        }
+        Self {
+            columns: parse_schema(schema),
+            indexes,
+        }
+    }
+
+    /// Differences of the catalog from the snapshot, tables the Diesel schema does not
+    /// declare are left alone
+    pub fn compare(&self, columns: &[LiveColumn], indexes: &[LiveIndex]) -> Vec<Drift> {
+        let live: BTreeMap<_, _> = columns
+            .iter()
+            .map(|column| {
+                (
+                    (column.table_name.as_str(), column.column_name.as_str()),
+                    column,
+                )
+            })
+            .collect();
+        let live_tables: BTreeSet<_> = columns
+            .iter()
+            .map(|column| column.table_name.as_str())
+            .collect();
+        let expected_tables: BTreeSet<_> = self
+            .columns
+            .iter()
+            .map(|column| column.table.as_str())
+            .collect();
+        let present = |table: &str| expected_tables.contains(table) && live_tables.contains(table);
+
+        let mut drift: Vec<_> = expected_tables
+            .difference(&live_tables)
+            .map(|table| Drift::MissingTable {
+                table: (*table).to_string(),
+            })
+            .collect();
+        for expected in self.columns.iter().filter(|column| present(&column.table)) {
+            let table = expected.table.clone();
+            let column = expected.column.clone();
+            match live.get(&(expected.table.as_str(), expected.column.as_str())) {
+                None => drift.push(Drift::MissingColumn {
+                    table,
+                    column,
+                    udt_name: expected.udt_name.clone(),
+                    nullable: expected.nullable,
+                }),
+                Some(found) if found.udt_name != expected.udt_name => {
+                    drift.push(Drift::ColumnType {
+                        table,
+                        column,
+                        expected: expected.udt_name.clone(),
+                        found: found.udt_name.clone(),
+                    });
+                }
+                Some(found) if found.nullable != expected.nullable => {
+                    drift.push(Drift::Nullability {
+                        table,
+                        column,
+                        nullable: expected.nullable,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        let declared: BTreeSet<_> = self
+            .columns
+            .iter()
+            .map(|column| (column.table.as_str(), column.column.as_str()))
+            .collect();
+        drift.extend(
+            columns
+                .iter()
+                .filter(|column| present(&column.table_name))
+                .filter(|column| {
+                    !declared.contains(&(column.table_name.as_str(), column.column_name.as_str()))
+                })
+                .map(|column| Drift::ExtraColumn {
+                    table: column.table_name.clone(),
+                    column: column.column_name.clone(),
+                    required: !column.nullable && !column.has_default,
+                }),
+        );
+
+        let live_indexes: BTreeSet<_> = indexes
+            .iter()
+            .map(|index| index.index_name.as_str())
+            .collect();
+        drift.extend(
+            self.indexes
+                .iter()
+                .filter(|(name, index)| {
+                    present(&index.table) && !live_indexes.contains(name.as_str())
+                })
+                .map(|(name, index)| Drift::MissingIndex {
+                    name: name.clone(),
+                    table: index.table.clone(),
+                    statement: index.statement.clone(),
+                }),
+        );
+        drift.extend(
+            indexes
+                .iter()
+                .filter(|index| present(&index.table_name))
+                .filter(|index| !self.indexes.contains_key(&index.index_name))
+                .map(|index| Drift::ExtraIndex {
+                    name: index.index_name.clone(),
+                    table: index.table_name.clone(),
+                    definition: index.definition.clone(),
+                }),
+        );
+        drift
+    }
+}
+
+/// Compares the connected database against the snapshot of this build
+pub fn check(conn: &mut PgConnection) -> QueryResult<Vec<Drift>> {
+    let columns = live_columns(conn)?;
+    let indexes = live_indexes(conn)?;
+    Ok(Snapshot::expected().compare(&columns, &indexes))
+}
+
+/// [`check`] on a pooled connection
+pub async fn check_pool(pg_pool: &Pool) -> Result<Vec<Drift>, PgError> {
+    let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+    conn.interact(check)
+        .await
+        .map_err(PgError::InteractionError)?
+        .map_err(PgError::DieselError)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{Drift, Snapshot, statements, udt_name};
+    use crate::model::database::{LiveColumn, LiveIndex};
+
+    const DIESEL_SCHEMA: &str = r#"
+pub mod renewable {
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::JobStatus;
+
+        renewable.jobs (id) {
+            id -> Int8,
+            status -> JobStatus,
+            #[sql_name = "type"]
+            type_ -> Nullable<Text>,
+        }
+    }
+
+    diesel::table! {
+        renewable.readings (id) {
+            id -> Int8,
+            amount -> Numeric,
+        }
+    }
+}
+"#;
+
+    const MIGRATIONS: [(&str, &str); 2] = [
+        (
+            "create",
+            "-- Readings; one per row
+            CREATE TABLE renewable.readings (id BIGINT PRIMARY KEY, amount NUMERIC NOT NULL);
+            CREATE INDEX idx_readings_amount ON renewable.readings(amount);
+            CREATE UNIQUE INDEX IF NOT EXISTS Idx_Old ON renewable.old_readings USING btree (id);
+            CREATE FUNCTION touch() RETURNS trigger AS $$
+            BEGIN
+                NEW.amount = 0; -- ignored
+                RETURN NEW;
+            END;
+            $$ LANGUAGE plpgsql;
+            COMMENT ON TABLE renewable.readings IS 'a; b';",
+        ),
+        (
+            "rename",
+            "ALTER INDEX idx_readings_amount RENAME TO idx_readings_by_amount;
+            DROP TABLE renewable.old_readings;
+            CREATE INDEX idx_readings_id ON renewable.readings(id);
+            DROP INDEX IF EXISTS renewable.idx_readings_id",
+        ),
+    ];
+
+    fn column(table: &str, column: &str, udt_name: &str, nullable: bool) -> LiveColumn {
+        LiveColumn {
+            table_name: table.to_string(),
+            column_name: column.to_string(),
+            udt_name: udt_name.to_string(),
+            nullable,
+            has_default: false,
+        }
+    }
+
+    fn index(name: &str, table: &str) -> LiveIndex {
+        LiveIndex {
+            index_name: name.to_string(),
+            table_name: table.to_string(),
+            definition: format!("CREATE INDEX {name} ON renewable.{table} USING btree (id)"),
+        }
+    }
+
+    #[test_case("Int8", "int8")]
+    #[test_case("Timestamptz", "timestamptz")]
+    #[test_case("Array<Nullable<Text>>", "_text")]
+    #[test_case("JobStatus", "job_status")]
+    #[test_case("super::sql_types::AggregationKind", "aggregation_kind")]
+    fn test_diesel_types_map_to_catalog_names(diesel_type: &str, expected: &str) {
+        assert_eq!(udt_name(diesel_type), expected);
+    }
+
+    #[test]
+    fn test_statements_keep_quoted_semicolons() {
+        let statements = statements(MIGRATIONS[0].1);
+        assert_eq!(statements.len(), 5);
+        assert!(statements[3].ends_with("$$ LANGUAGE plpgsql"));
+        assert!(statements[4].ends_with("'a; b'"));
+    }
+
+    #[test]
+    fn test_snapshot_replays_migrations() {
+        let snapshot = Snapshot::parse(DIESEL_SCHEMA, &MIGRATIONS);
+        assert_eq!(
+            snapshot
+                .columns
+                .iter()
+                .map(|c| (
+                    c.table.as_str(),
+                    c.column.as_str(),
+                    c.udt_name.as_str(),
+                    c.nullable
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("jobs", "id", "int8", false),
+                ("jobs", "status", "job_status", false),
+                ("jobs", "type", "text", true),
+                ("readings", "id", "int8", false),
+                ("readings", "amount", "numeric", false),
+            ]
+        );
+        // Renamed indexes are followed, dropped ones and those of dropped tables forgotten
+        assert_eq!(
+            snapshot.indexes.keys().collect::<Vec<_>>(),
+            ["idx_readings_by_amount"]
+        );
+        assert_eq!(
+            snapshot.indexes["idx_readings_by_amount"].statement,
+            "CREATE INDEX idx_readings_amount ON renewable.readings(amount)"
+        );
+    }
+
+    #[test]
+    fn test_compare_reports_each_difference() {
+        let snapshot = Snapshot::parse(DIESEL_SCHEMA, &MIGRATIONS);
+        let matching = [
+            column("readings", "id", "int8", false),
+            column("readings", "amount", "numeric", false),
+        ];
+        assert_eq!(
+            snapshot.compare(&matching, &[index("idx_readings_by_amount", "readings")]),
+            [Drift::MissingTable {
+                table: "jobs".to_string()
+            }]
+        );
+
+        let columns = [
+            column("jobs", "id", "int8", false),
+            column("jobs", "status", "text", false),
+            column("jobs", "type", "text", false),
+            column("readings", "id", "int8", false),
+            column("readings", "note", "text", false),
+            // Tables outside the Diesel schema are not compared
+            column("staging", "id", "int8", false),
+        ];
+        let indexes = [
+            index("hand_made", "readings"),
+            index("staging_id", "staging"),
+        ];
+        let drift = snapshot.compare(&columns, &indexes);
+        assert_eq!(
+            drift.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "column renewable.jobs.status is text, expected job_status",
+                "column renewable.jobs.type is NOT NULL, expected nullable",
+                "column renewable.readings.amount is missing",
+                "column renewable.readings.note is not in the Diesel schema, inserts fail as it is NOT NULL without a default",
+                "index renewable.idx_readings_by_amount on readings is missing",
+                "index renewable.hand_made on readings was not created by a migration",
+            ]
+        );
+        assert_eq!(
+            drift.iter().map(Drift::fix).collect::<Vec<_>>(),
+            [
+                "ALTER TABLE renewable.jobs ALTER COLUMN status TYPE job_status USING status::job_status;",
+                "ALTER TABLE renewable.jobs ALTER COLUMN type DROP NOT NULL;",
+                "ALTER TABLE renewable.readings ADD COLUMN amount numeric NOT NULL;",
+                "ALTER TABLE renewable.readings ALTER COLUMN note DROP NOT NULL;",
+                "CREATE INDEX idx_readings_amount ON renewable.readings(amount);",
+                "DROP INDEX renewable.hand_made;",
+            ]
+        );
+    }
+}
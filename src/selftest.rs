@@ -0,0 +1,317 @@
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use bigdecimal::{BigDecimal, ToPrimitive as _};
+use chrono::{DateTime, TimeDelta, TimeZone as _, Utc};
+use deadpool_diesel::postgres::Pool;
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::info;
+
+use crate::{
+    auth::API_KEY_HEADER,
+    db::{PgError, seed_database::insert_ingestion},
+    model::csv::CSVRecord,
+};
+
+/// Hourly readings generated for each run, spanning two calendar days
+const READINGS: i64 = 48;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const EXPORT_POLL_ATTEMPTS: u32 = 120;
+const HISTORY_POLL_ATTEMPTS: u32 = 20;
+
+/// Target instance exercised by the `selftest` subcommand
+#[derive(Debug, Clone)]
+pub struct SelfTestConfig {
+    pub base_url: String,
+    pub api_key: String,
+}
+
+impl SelfTestConfig {
+    /// Reads `SELFTEST_URL` (default `http://127.0.0.1:8000`) and `SELFTEST_API_KEY`,
+    /// falling back to `BOOTSTRAP_API_KEY`
+    pub fn from_env() -> Result<Self, String> {
+        let base_url =
+            env::var("SELFTEST_URL").unwrap_or_else(|_| "http://127.0.0.1:8000".to_string());
+        let api_key = env::var("SELFTEST_API_KEY")
+            .or_else(|_| env::var("BOOTSTRAP_API_KEY"))
+            .map_err(|_| "SELFTEST_API_KEY or BOOTSTRAP_API_KEY must be set".to_string())?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+}
+
+/// Outcome of a single step of the flow
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Machine-readable result printed by the `selftest` subcommand
+#[derive(Debug, Serialize)]
+pub struct Verdict {
+    pub passed: bool,
+    pub duration_ms: u128,
+    pub checks: Vec<Check>,
+}
+
+/// First reading of the generated data, far enough ahead not to overlap real readings
+fn window_start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// Hourly readings of 1, 2, .. [`READINGS`] kWh starting at [`window_start`]
+fn generated_readings() -> Vec<CSVRecord> {
+    (0..READINGS)
+        .map(|i| CSVRecord {
+            datetime: window_start() + TimeDelta::hours(i),
+            amount: BigDecimal::from(i + 1),
+            extra: None,
+        })
+        .collect()
+}
+
+/// Expected `DayInMonth` totals of [`generated_readings`]
+fn expected_daily_totals() -> Vec<f64> {
+    generated_readings()
+        .chunk_by(|a, b| a.datetime.date_naive() == b.datetime.date_naive())
+        .map(|day| {
+            day.iter()
+                .map(|reading| reading.amount.to_f64().unwrap_or_default())
+                .sum()
+        })
+        .collect()
+}
+
+struct Run {
+    client: reqwest::Client,
+    config: SelfTestConfig,
+    checks: Vec<Check>,
+}
+
+impl Run {
+    fn record(&mut self, name: &'static str, outcome: Result<String, String>) -> bool {
+        let passed = outcome.is_ok();
+        let detail = outcome.unwrap_or_else(|e| e);
+        info!(name, passed, detail, "Self test check");
+        self.checks.push(Check {
+            name,
+            passed,
+            detail,
+        });
+        passed
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, String> {
+        let response = request
+            .header(API_KEY_HEADER, &self.config.api_key)
+            .send()
+            .await
+            .map_err(|e| format!("request failed: {e}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("unexpected status {status}: {body}"));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| format!("invalid response body: {e}"))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.config.base_url)
+    }
+
+    fn query_body(&self) -> Value {
+        json!({
+            "aggregation_kind": "DayInMonth",
+            "datetime_filter": {
+                "from_date": window_start(),
+                "to_date": window_start() + TimeDelta::hours(READINGS),
+            },
+        })
+    }
+
+    async fn query(&self) -> Result<String, String> {
+        let response = self
+            .send(
+                self.client
+                    .post(self.url("/timeseries/v1/query"))
+                    .json(&self.query_body()),
+            )
+            .await?;
+        let totals: Vec<f64> = response["records"]
+            .as_array()
+            .ok_or("response has no records")?
+            .iter()
+            .map(|record| record["total_amount"].as_f64().unwrap_or_default())
+            .collect();
+        let expected = expected_daily_totals();
+        if totals == expected {
+            Ok(format!("daily totals {totals:?}"))
+        } else {
+            Err(format!("daily totals {totals:?}, expected {expected:?}"))
+        }
+    }
+
+    /// History is written in batches, so the entry may take a flush interval to appear
+    async fn history(&self) -> Result<String, String> {
+        let from_date = json!(window_start());
+        for _ in 0..HISTORY_POLL_ATTEMPTS {
+            let response = self
+                .send(self.client.get(self.url("/timeseries/v1/query/history")))
+                .await?;
+            let recorded = response
+                .as_array()
+                .ok_or("history is not a list")?
+                .iter()
+                .find(|entry| {
+                    entry["aggregation"] == "DayInMonth" && entry["from_date"] == from_date
+                })
+                .map(|entry| format!("recorded as history entry {}", entry["id"]));
+            match recorded {
+                Some(detail) => return Ok(detail),
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        Err("query missing from history".to_string())
+    }
+
+    async fn export(&self) -> Result<String, String> {
+        let job = self
+            .send(
+                self.client
+                    .post(self.url("/timeseries/v1/exports"))
+                    .json(&self.query_body()),
+            )
+            .await?;
+        let job_url = self.url(&format!("/timeseries/v1/exports/{}", job["id"]));
+
+        for _ in 0..EXPORT_POLL_ATTEMPTS {
+            let job = self.send(self.client.get(&job_url)).await?;
+            match job["status"].as_str() {
+                Some("Complete") => {
+                    let download_url = job["download_url"]
+                        .as_str()
+                        .ok_or("completed export has no download url")?;
+                    let csv = self
+                        .client
+                        .get(self.url(download_url))
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status)
+                        .map_err(|e| format!("download failed: {e}"))?
+                        .text()
+                        .await
+                        .map_err(|e| format!("download failed: {e}"))?;
+                    let rows = csv.lines().skip(1).count();
+                    let expected = expected_daily_totals().len();
+                    return if job["row_count"] == json!(expected) && rows == expected {
+                        Ok(format!("exported {rows} rows"))
+                    } else {
+                        Err(format!(
+                            "exported {rows} rows ({} reported), expected {expected}",
+                            job["row_count"]
+                        ))
+                    };
+                }
+                Some("Failed") => return Err(format!("export failed: {}", job["error"])),
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        Err("export did not complete in time".to_string())
+    }
+
+    async fn cleanup(&self, ingestion_id: i64) -> Result<String, String> {
+        let deleted = self
+            .send(
+                self.client
+                    .delete(self.url(&format!("/timeseries/v1/ingestions/{ingestion_id}"))),
+            )
+            .await?;
+        if deleted["deleted_readings"] == json!(READINGS) {
+            Ok(format!("removed ingestion {ingestion_id}"))
+        } else {
+            Err(format!(
+                "removed {} readings, expected {READINGS}",
+                deleted["deleted_readings"]
+            ))
+        }
+    }
+}
+
+/// Runs the ingest, query, history and export flow against the target instance.
+///
+/// Generated readings are written straight to the instance's database as a new ingestion,
+/// then read back through the API and finally removed through the ingestions endpoint.
+pub async fn run(pg_pool: &Pool, config: SelfTestConfig) -> Result<Verdict, PgError> {
+    let started = Instant::now();
+    let mut run = Run {
+        client: reqwest::Client::new(),
+        config,
+        checks: Vec::new(),
+    };
+
+    let source = format!(
+        "selftest-{}",
+        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    );
+    let conn = pg_pool.get().await.map_err(PgError::ConnectionError)?;
+    let ingested = conn
+        .interact(move |conn| insert_ingestion(source, None, 0, generated_readings(), 0, conn))
+        .await
+        .map_err(PgError::InteractionError)?
+        .map_err(PgError::DieselError)?;
+
+    let ingestion_id = match ingested {
+        Some((ingestion_id, rows)) => {
+            run.record(
+                "ingest",
+                Ok(format!("ingestion {ingestion_id} with {rows} readings")),
+            );
+            Some(ingestion_id)
+        }
+        None => {
+            run.record("ingest", Err("source was already ingested".to_string()));
+            None
+        }
+    };
+
+    if let Some(ingestion_id) = ingestion_id {
+        let outcome = run.query().await;
+        if run.record("query", outcome) {
+            let outcome = run.history().await;
+            run.record("history", outcome);
+        }
+        let outcome = run.export().await;
+        run.record("export", outcome);
+        let outcome = run.cleanup(ingestion_id).await;
+        run.record("cleanup", outcome);
+    }
+
+    Ok(Verdict {
+        passed: run.checks.iter().all(|check| check.passed),
+        duration_ms: started.elapsed().as_millis(),
+        checks: run.checks,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{READINGS, expected_daily_totals, generated_readings, window_start};
+
+    #[test]
+    fn test_generated_readings_span_two_days() {
+        let readings = generated_readings();
+        assert_eq!(readings.len() as i64, READINGS);
+        assert_eq!(readings[0].datetime, window_start());
+        assert_eq!(expected_daily_totals(), [300.0, 876.0]);
+    }
+}
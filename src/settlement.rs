@@ -0,0 +1,127 @@
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, TimeDelta, TimeZone as _, Utc};
+use chrono_tz::Europe::London;
+
+use crate::model::{
+    api_request::SettlementPeriod,
+    api_response::{AggregationQueryRecord, BucketSettlement},
+};
+
+/// Length of a GB settlement period
+pub const PERIOD: TimeDelta = TimeDelta::minutes(30);
+
+/// Start of the settlement day, midnight UK local time, which is never skipped or repeated
+/// by a clock change
+fn day_start(date: NaiveDate) -> Option<DateTime<Utc>> {
+    London
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+}
+
+/// Settlement periods in `date`, 46 when the clocks go forward and 50 when they go back
+pub fn periods_in_day(date: NaiveDate) -> u32 {
+    let next = date.checked_add_days(Days::new(1));
+    match (day_start(date), next.and_then(day_start)) {
+        (Some(start), Some(end)) => {
+            u32::try_from((end - start).num_minutes() / PERIOD.num_minutes()).unwrap_or_default()
+        }
+        _ => 0,
+    }
+}
+
+/// First instant of `period`, `None` when the day does not have that many periods
+pub fn period_start(period: &SettlementPeriod) -> Option<DateTime<Utc>> {
+    if !(1..=periods_in_day(period.settlement_date)).contains(&period.settlement_period) {
+        return None;
+    }
+    day_start(period.settlement_date)
+        .map(|start| start + PERIOD * (period.settlement_period - 1).cast_signed())
+}
+
+/// Instant `period` ends, exclusive
+pub fn period_end(period: &SettlementPeriod) -> Option<DateTime<Utc>> {
+    period_start(period).map(|start| start + PERIOD)
+}
+
+/// Settlement date and period holding `datetime`
+pub fn settlement_period(datetime: DateTime<Utc>) -> SettlementPeriod {
+    let settlement_date = datetime.with_timezone(&London).date_naive();
+    let elapsed = day_start(settlement_date).map_or(0, |start| (datetime - start).num_minutes());
+    SettlementPeriod {
+        settlement_date,
+        settlement_period: u32::try_from(elapsed / PERIOD.num_minutes() + 1).unwrap_or(1),
+    }
+}
+
+/// Labels each record with the settlement period its bucket starts in
+pub fn label(records: &[AggregationQueryRecord]) -> Vec<BucketSettlement> {
+    records
+        .iter()
+        .map(|record| {
+            let SettlementPeriod {
+                settlement_date,
+                settlement_period,
+            } = settlement_period(record.datetime);
+            BucketSettlement {
+                datetime: record.datetime,
+                settlement_date,
+                settlement_period,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, TimeZone as _, Utc};
+    use test_case::test_case;
+
+    use super::{period_end, period_start, periods_in_day, settlement_period};
+    use crate::model::api_request::SettlementPeriod;
+
+    fn period(y: i32, m: u32, d: u32, settlement_period: u32) -> SettlementPeriod {
+        SettlementPeriod {
+            settlement_date: NaiveDate::from_ymd_opt(y, m, d).unwrap(),
+            settlement_period,
+        }
+    }
+
+    #[test_case(2025, 3, 30, 46; "clocks go forward")]
+    #[test_case(2025, 6, 1, 48; "summer")]
+    #[test_case(2025, 10, 26, 50; "clocks go back")]
+    #[test_case(2025, 12, 1, 48; "winter")]
+    fn test_periods_in_day(y: i32, m: u32, d: u32, expected: u32) {
+        assert_eq!(
+            periods_in_day(NaiveDate::from_ymd_opt(y, m, d).unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_periods_follow_uk_local_time() {
+        // Settlement day starts at 23:00 UTC the evening before during BST
+        let summer = period(2025, 6, 1, 1);
+        assert_eq!(
+            period_start(&summer),
+            Some(Utc.with_ymd_and_hms(2025, 5, 31, 23, 0, 0).unwrap())
+        );
+        assert_eq!(
+            settlement_period(Utc.with_ymd_and_hms(2025, 5, 31, 23, 15, 0).unwrap()),
+            summer
+        );
+
+        // The 50th period of the long day ends at midnight UTC
+        let last = period(2025, 10, 26, 50);
+        assert_eq!(
+            period_end(&last),
+            Some(Utc.with_ymd_and_hms(2025, 10, 27, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            settlement_period(Utc.with_ymd_and_hms(2025, 10, 26, 23, 30, 0).unwrap()),
+            last
+        );
+
+        assert_eq!(period_start(&period(2025, 3, 30, 47)), None);
+        assert_eq!(period_start(&period(2025, 3, 30, 0)), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+
+use crate::model::api_response::AggregationQueryRecord;
+
+/// Picks the aggregations answered from the daily and monthly summaries that are run a
+/// second time over `ts_store`, one in every `shadow_query_one_in`, so a summary drifting
+/// from the readings it rolls up shows in the logs before a client notices
+#[derive(Clone, Debug)]
+pub struct ShadowQueries {
+    one_in: u64,
+    seen: Arc<AtomicU64>,
+}
+
+impl ShadowQueries {
+    pub fn new(one_in: u64) -> Self {
+        Self {
+            one_in: one_in.max(1),
+            seen: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Whether the next eligible aggregation is shadowed, the first and every
+    /// `one_in`th after it
+    pub fn sample(&self) -> bool {
+        self.seen
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.one_in)
+    }
+}
+
+/// A bucket whose total differs between the two runs of an aggregation, `None` where
+/// one of them returned no such bucket
+#[derive(Debug, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub datetime: DateTime<Utc>,
+    pub served: Option<BigDecimal>,
+    pub shadow: Option<BigDecimal>,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = |total: &Option<BigDecimal>| {
+            total
+                .as_ref()
+                .map_or_else(|| "no bucket".to_string(), BigDecimal::to_string)
+        };
+        write!(
+            f,
+            "{} served {}, shadow {}",
+            self.datetime,
+            total(&self.served),
+            total(&self.shadow)
+        )
+    }
+}
+
+/// Buckets of `served` and `shadow` whose totals disagree, in bucket order. Totals are
+/// compared as exact decimals, trailing zeros aside.
+pub fn compare(
+    served: &[AggregationQueryRecord],
+    shadow: &[AggregationQueryRecord],
+) -> Vec<Discrepancy> {
+    let mut buckets: BTreeMap<_, (Option<BigDecimal>, Option<BigDecimal>)> = BTreeMap::new();
+    for record in served {
+        buckets.entry(record.datetime).or_default().0 = record.total_amount.clone();
+    }
+    for record in shadow {
+        buckets.entry(record.datetime).or_default().1 = record.total_amount.clone();
+    }
+    buckets
+        .into_iter()
+        .filter(|(_, (served, shadow))| served != shadow)
+        .map(|(datetime, (served, shadow))| Discrepancy {
+            datetime,
+            served,
+            shadow,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::{Discrepancy, ShadowQueries, compare};
+    use crate::model::api_response::AggregationQueryRecord;
+
+    #[test]
+    fn test_compare_reports_buckets_that_disagree() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let record = |day: i64, total: &str| AggregationQueryRecord {
+            datetime: start + TimeDelta::days(day),
+            total_amount: Some(total.parse().unwrap()),
+        };
+        let served = [record(0, "1.50"), record(1, "2"), record(2, "3")];
+        let shadow = [
+            record(2, "3"),
+            record(0, "1.5"),
+            record(1, "2.5"),
+            record(3, "1"),
+        ];
+
+        assert_eq!(
+            compare(&served, &shadow),
+            [
+                Discrepancy {
+                    datetime: start + TimeDelta::days(1),
+                    served: Some(BigDecimal::from(2)),
+                    shadow: Some("2.5".parse().unwrap()),
+                },
+                Discrepancy {
+                    datetime: start + TimeDelta::days(3),
+                    served: None,
+                    shadow: Some(BigDecimal::from(1)),
+                },
+            ]
+        );
+        assert!(compare(&served, &served).is_empty());
+    }
+
+    #[test]
+    fn test_one_in_n_aggregations_are_sampled() {
+        let shadow = ShadowQueries::new(3);
+        let sampled: Vec<_> = (0..7).map(|_| shadow.sample()).collect();
+        assert_eq!(sampled, [true, false, false, true, false, false, true]);
+    }
+}
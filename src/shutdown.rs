@@ -1,7 +1,21 @@
-use std::time::Duration;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use tokio::signal;
-use tracing::info;
+use tokio::{signal, task::JoinHandle, time::Instant};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+use crate::db::Pools;
+
+/// Interval at which a draining pool is checked for connections still in use
+const POOL_DRAIN_POLL: Duration = Duration::from_millis(50);
 
 /// Graceful shutdown signal handling
 pub async fn shutdown_signal() {
@@ -30,3 +44,195 @@ pub async fn shutdown_signal() {
     info!("Received termination signal shutting down");
     tokio::time::sleep(Duration::from_secs(11)).await;
 }
+
+/// Coordinates a graceful shutdown: once requested the servers stop accepting
+/// connections, and background work spawned through [`Shutdown::spawn`] and connections
+/// checked out of the pool are given until the grace period ends before the pool is
+/// closed, whatever is still running being logged as cancelled.
+#[derive(Clone)]
+pub struct Shutdown {
+    grace: Duration,
+    requested: CancellationToken,
+    deadline: Arc<OnceLock<Instant>>,
+    tasks: TaskTracker,
+    running: Arc<Mutex<BTreeMap<u64, String>>>,
+    next_task: Arc<AtomicU64>,
+}
+
+/// Removes a tracked task from the running ones once it finishes or is dropped
+struct RunningTask {
+    id: u64,
+    running: Arc<Mutex<BTreeMap<u64, String>>>,
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        self.running
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+/// What was left running when the grace period ended
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Cancelled {
+    /// Names of the tracked tasks still running
+    pub tasks: Vec<String>,
+    /// Connections still checked out of the pool, i.e. `interact` calls in flight
+    pub connections: usize,
+}
+
+impl Shutdown {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            requested: CancellationToken::new(),
+            deadline: Arc::new(OnceLock::new()),
+            tasks: TaskTracker::new(),
+            running: Arc::new(Mutex::new(BTreeMap::new())),
+            next_task: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Requests shutdown on the first termination signal
+    pub fn listen(&self) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            shutdown.request();
+        });
+    }
+
+    /// Starts the grace period, a no-op once shutdown was requested
+    pub fn request(&self) {
+        self.deadline.get_or_init(|| Instant::now() + self.grace);
+        self.requested.cancel();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.is_cancelled()
+    }
+
+    /// Resolves once shutdown is requested
+    pub async fn requested(&self) {
+        self.requested.cancelled().await;
+    }
+
+    /// Time left of the grace period, all of it before shutdown is requested
+    pub fn remaining(&self) -> Duration {
+        self.deadline.get().map_or(self.grace, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        })
+    }
+
+    /// Spawns `task` as background work shutdown waits for, logged under `name` when the
+    /// grace period ends before it does
+    pub fn spawn<F>(&self, name: impl Into<String>, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let id = self.next_task.fetch_add(1, Ordering::Relaxed);
+        self.running
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(id, name.into());
+        let guard = RunningTask {
+            id,
+            running: Arc::clone(&self.running),
+        };
+        self.tasks.spawn(async move {
+            let _guard = guard;
+            task.await
+        })
+    }
+
+    /// Waits for tracked tasks, then for connections in use to be returned to `pools`,
+    /// until the grace period ends, and closes the pools, returning what was cut short
+    pub async fn drain(&self, pools: &Pools) -> Cancelled {
+        self.request();
+        self.tasks.close();
+        let deadline = Instant::now() + self.remaining();
+
+        let tasks_done = tokio::time::timeout_at(deadline, self.tasks.wait())
+            .await
+            .is_ok();
+        let mut connections = in_use(pools);
+        while connections > 0 && Instant::now() < deadline {
+            tokio::time::sleep(POOL_DRAIN_POLL.min(deadline - Instant::now())).await;
+            connections = in_use(pools);
+        }
+        pools.close();
+
+        let tasks: Vec<_> = if tasks_done {
+            Vec::new()
+        } else {
+            self.running
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .values()
+                .cloned()
+                .collect()
+        };
+        let cancelled = Cancelled { tasks, connections };
+        if cancelled == Cancelled::default() {
+            info!("Drained background work and database connections");
+        } else {
+            warn!(
+                tasks = ?cancelled.tasks,
+                connections = cancelled.connections,
+                "Grace period ended, cancelling background work still running"
+            );
+        }
+        cancelled
+    }
+}
+
+fn in_use(pools: &Pools) -> usize {
+    std::iter::once(pools.primary())
+        .chain(pools.replica())
+        .map(|pool| {
+            let status = pool.status();
+            status.size.saturating_sub(status.available)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use deadpool_diesel::{
+        Runtime,
+        postgres::{Manager, Pool},
+    };
+
+    use super::{Cancelled, Shutdown};
+    use crate::db::{Pools, TimedPool};
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tasks_within_the_grace_period() {
+        let manager = Manager::new("postgres://localhost/unused", Runtime::Tokio1);
+        let pool = Pool::builder(manager).build().unwrap();
+        let shutdown = Shutdown::new(Duration::from_millis(300));
+        assert_eq!(shutdown.remaining(), Duration::from_millis(300));
+
+        shutdown.spawn("ingestion", tokio::time::sleep(Duration::from_millis(20)));
+        shutdown.spawn("export job 7", tokio::time::sleep(Duration::from_secs(60)));
+        let cancelled = shutdown
+            .drain(&Pools::new(TimedPool::new(pool.clone()), None))
+            .await;
+
+        assert!(shutdown.is_requested());
+        assert_eq!(shutdown.remaining(), Duration::ZERO);
+        assert_eq!(
+            cancelled,
+            Cancelled {
+                tasks: vec!["export job 7".to_string()],
+                connections: 0,
+            }
+        );
+        assert!(pool.is_closed());
+    }
+}
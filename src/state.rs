@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+
+#[cfg(feature = "redis-cache")]
+use crate::cache::SharedCache;
+use crate::{
+    config::AppConfig, cursor::CursorSigner, db::Pools, drift::DriftConfig, export::ExportConfig,
+    history::HistoryWriter, hot_cache::HotCache, leader::LeaderElection, live::IngestionEvents,
+    notify::ChangeFeed, read_only::ReadOnlyMode, register::RegisterConfig,
+    response_cache::ResponseCache, retention::RetentionJob, shadow::ShadowQueries,
+    shutdown::Shutdown, telemetry::Telemetry, upload::UploadBucket,
+};
+
+/// Shared state handed to every route handler
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Pools,
+    pub config: AppConfig,
+    pub export_config: ExportConfig,
+    /// Signs the cursors of paginated listings
+    pub cursor_signer: CursorSigner,
+    /// How uploaded readings files are decoded, as the seed file is
+    pub register_config: RegisterConfig,
+    pub drift_config: DriftConfig,
+    /// Issues pre-signed upload URLs, when `upload_bucket` is set
+    pub uploads: Option<UploadBucket>,
+    pub hot_cache: Option<Arc<HotCache>>,
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// Responses and counters shared with the other replicas, when `redis_url` is set
+    #[cfg(feature = "redis-cache")]
+    pub shared_cache: Option<SharedCache>,
+    pub history: HistoryWriter,
+    pub read_only: ReadOnlyMode,
+    pub ingestion_events: IngestionEvents,
+    /// Tells the other instances sharing the database that readings changed
+    pub change_feed: ChangeFeed,
+    pub retention: RetentionJob,
+    /// Whether this instance runs the background jobs shared by every replica
+    pub leader: LeaderElection,
+    /// Background work drained on shutdown
+    pub shutdown: Shutdown,
+    /// Anonymous usage counts, when `telemetry_dir` opts into them
+    pub telemetry: Option<Telemetry>,
+    /// Aggregations checked against a scan of `ts_store`, when `shadow_query_one_in` is set
+    pub shadow: Option<ShadowQueries>,
+}
+
+impl AppState {
+    /// Drops cached aggregation results after stored readings change, on every replica
+    /// when a shared cache is configured, and has the other instances drop theirs
+    pub async fn invalidate_response_cache(&self) {
+        if let Some(cache) = &self.response_cache {
+            cache.invalidate();
+        }
+        #[cfg(feature = "redis-cache")]
+        if let Some(shared) = &self.shared_cache
+            && let Err(e) = shared.invalidate().await
+        {
+            tracing::error!("Unable to invalidate the shared cache: {e}");
+        }
+        self.change_feed.announce(self.db.primary()).await;
+    }
+}
+
+impl FromRef<AppState> for AppConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Pools {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for ReadOnlyMode {
+    fn from_ref(state: &AppState) -> Self {
+        state.read_only.clone()
+    }
+}
+
+impl FromRef<AppState> for IngestionEvents {
+    fn from_ref(state: &AppState) -> Self {
+        state.ingestion_events.clone()
+    }
+}
+
+impl FromRef<AppState> for HistoryWriter {
+    fn from_ref(state: &AppState) -> Self {
+        state.history.clone()
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use deadpool_diesel::postgres::Pool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::{
+    db::storage_stats::record_storage_sample,
+    model::{
+        api_response::{RelationStorage, StorageGrowth, StorageResponse},
+        database::StorageSample,
+    },
+    state::AppState,
+};
+
+/// Interval between samples of the size of every table and partition
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Spawns the task sampling the size of every table and partition into `storage_stats`,
+/// at startup and daily after, so `/admin/v1/storage` can report growth. Sampling waits
+/// for this instance to lead and pauses while it is read-only.
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            state.leader.leading().await;
+            if state.read_only.is_enabled() {
+                debug!("Read-only, skipping the storage sample");
+                continue;
+            }
+            match sample(state.db.primary()).await {
+                Ok(relations) => info!(relations, "Sampled table and partition sizes"),
+                Err(e) => error!("Unable to sample table and partition sizes: {e}"),
+            }
+        }
+    })
+}
+
+async fn sample(pg_pool: &Pool) -> Result<usize, String> {
+    let conn = pg_pool.get().await.map_err(|e| e.to_string())?;
+    conn.interact(|conn| record_storage_sample(Utc::now().date_naive(), conn))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Reports `sizes`, taken at `now`, with their growth since the oldest of `samples`
+/// holding them, `samples` being ordered oldest first
+pub fn report(
+    now: DateTime<Utc>,
+    database_bytes: i64,
+    sizes: Vec<StorageSample>,
+    samples: &[StorageSample],
+) -> StorageResponse {
+    let today = now.date_naive();
+    let tables = |day: NaiveDate, rows: &[StorageSample]| {
+        rows.iter()
+            .filter(|size| size.sampled_on == day && size.parent.is_none())
+            .fold((0, 0), |(bytes, count), size| {
+                (bytes + size.total_bytes, count + size.row_estimate)
+            })
+    };
+    let (schema_bytes, schema_rows) = tables(today, &sizes);
+    let growth = samples.first().and_then(|oldest| {
+        let (bytes, rows) = tables(oldest.sampled_on, samples);
+        growth_since(
+            oldest.sampled_on,
+            bytes,
+            rows,
+            today,
+            schema_bytes,
+            schema_rows,
+        )
+    });
+
+    let mut relations: Vec<_> = sizes
+        .into_iter()
+        .map(|size| {
+            let growth = samples
+                .iter()
+                .find(|sample| sample.relation == size.relation)
+                .and_then(|oldest| {
+                    growth_since(
+                        oldest.sampled_on,
+                        oldest.total_bytes,
+                        oldest.row_estimate,
+                        today,
+                        size.total_bytes,
+                        size.row_estimate,
+                    )
+                });
+            RelationStorage {
+                table_bytes: size.total_bytes - size.index_bytes,
+                relation: size.relation,
+                parent: size.parent,
+                total_bytes: size.total_bytes,
+                index_bytes: size.index_bytes,
+                row_estimate: size.row_estimate,
+                growth,
+            }
+        })
+        .collect();
+    relations.sort_by(|a, b| {
+        b.total_bytes
+            .cmp(&a.total_bytes)
+            .then_with(|| a.relation.cmp(&b.relation))
+    });
+
+    StorageResponse {
+        sampled_at: now,
+        database_bytes,
+        schema_bytes,
+        growth,
+        relations,
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn growth_since(
+    since: NaiveDate,
+    bytes_then: i64,
+    rows_then: i64,
+    today: NaiveDate,
+    bytes: i64,
+    rows: i64,
+) -> Option<StorageGrowth> {
+    let days = (today - since).num_days();
+    (days > 0).then(|| StorageGrowth {
+        since,
+        bytes_per_day: (bytes - bytes_then) as f64 / days as f64,
+        rows_per_day: (rows - rows_then) as f64 / days as f64,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, TimeZone as _, Utc};
+
+    use super::report;
+    use crate::model::{api_response::StorageGrowth, database::StorageSample};
+
+    fn size(day: u32, relation: &str, parent: Option<&str>, total: i64) -> StorageSample {
+        StorageSample {
+            sampled_on: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+            relation: relation.to_string(),
+            parent: parent.map(str::to_string),
+            total_bytes: total,
+            index_bytes: total / 4,
+            row_estimate: total / 100,
+        }
+    }
+
+    #[test]
+    fn test_growth_is_measured_from_the_oldest_sample() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 11, 6, 0, 0).unwrap();
+        let samples = vec![
+            size(1, "ts_store", None, 1000),
+            size(1, "ts_store_2025_03", Some("ts_store"), 1000),
+            size(6, "query_history", None, 400),
+            size(6, "ts_store", None, 1500),
+        ];
+        let sizes = vec![
+            size(11, "query_history", None, 600),
+            size(11, "ts_store", None, 3000),
+            size(11, "ts_store_2025_03", Some("ts_store"), 3000),
+        ];
+
+        let response = report(now, 10_000, sizes, &samples);
+        assert_eq!(response.schema_bytes, 3600);
+        // Tables absent from the oldest sample count as new growth
+        assert_eq!(
+            response.growth,
+            Some(StorageGrowth {
+                since: NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+                bytes_per_day: 260.0,
+                rows_per_day: 2.6,
+            })
+        );
+
+        let names: Vec<_> = response.relations.iter().map(|r| &r.relation[..]).collect();
+        assert_eq!(names, ["ts_store", "ts_store_2025_03", "query_history"]);
+        assert_eq!(response.relations[0].table_bytes, 2250);
+        assert!(
+            (response.relations[2].growth.as_ref().unwrap().bytes_per_day - 40.0).abs()
+                < f64::EPSILON
+        );
+    }
+
+    #[test]
+    fn test_growth_needs_an_earlier_sample() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 11, 6, 0, 0).unwrap();
+        let sizes = vec![size(11, "ts_store", None, 3000)];
+        let response = report(now, 10_000, sizes.clone(), &sizes);
+        assert_eq!(response.growth, None);
+        assert_eq!(response.relations[0].growth, None);
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+use crate::{
+    build_info::build_info, config::AppConfig, model::api_request::Aggregation,
+    response_cache::QueryKey,
+};
+
+/// Anonymous counts of the features an instance is asked for, written as a JSON report
+/// every `telemetry_interval_secs` when `telemetry_dir` opts into it. Only route
+/// templates, aggregation kinds and which query options were set are counted, never
+/// paths, parameter values, keys or readings, and nothing is sent anywhere.
+#[derive(Clone)]
+pub struct Telemetry {
+    usage: Arc<Mutex<Usage>>,
+    configured: Vec<&'static str>,
+}
+
+#[derive(Default)]
+struct Usage {
+    since: DateTime<Utc>,
+    endpoints: BTreeMap<String, u64>,
+    aggregation_kinds: BTreeMap<String, u64>,
+    query_options: BTreeMap<&'static str, u64>,
+}
+
+/// Usage of one instance over a period, see [`Telemetry`]
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct UsageReport {
+    pub version: &'static str,
+    /// Cargo features the binary was built with
+    pub build_features: Vec<&'static str>,
+    /// Optional features switched on by the configuration
+    pub configured_features: Vec<&'static str>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    /// Requests per method and route template of the REST API
+    pub endpoints: BTreeMap<String, u64>,
+    /// Aggregations answered per kind, over REST, GraphQL and gRPC
+    pub aggregation_kinds: BTreeMap<String, u64>,
+    /// REST aggregation requests setting each option
+    pub query_options: BTreeMap<&'static str, u64>,
+}
+
+/// Optional features `config` switches on, with `hot_cache` for `HOT_CACHE_DAYS`
+pub fn configured_features(config: &AppConfig, hot_cache: bool) -> Vec<&'static str> {
+    [
+        ("grpc", config.grpc_listen_addr.is_some()),
+        (
+            "read_replica",
+            std::env::var_os("DATABASE_READ_URL").is_some(),
+        ),
+        ("hot_cache", hot_cache),
+        ("query_fallback", config.query_fallback),
+        ("shadow_queries", config.shadow_query_one_in.is_some()),
+        ("response_cache", config.response_cache_entries > 0),
+        ("redis_cache", config.redis_url.is_some()),
+        ("read_only", config.read_only),
+        ("retention", config.retention_days.is_some()),
+        ("source_priorities", !config.source_priorities.is_empty()),
+        ("reject_unit_mismatch", config.reject_unit_mismatch),
+        ("max_query_buckets", config.max_query_buckets.is_some()),
+        (
+            "statement_timeout",
+            config.db_statement_timeout_ms.is_some(),
+        ),
+        ("watch_dir", config.watch_dir.is_some()),
+        ("upload_bucket", config.upload_bucket.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+impl Telemetry {
+    pub fn new(configured: Vec<&'static str>, now: DateTime<Utc>) -> Self {
+        Self {
+            usage: Arc::new(Mutex::new(Usage {
+                since: now,
+                ..Usage::default()
+            })),
+            configured,
+        }
+    }
+
+    fn usage(&self) -> std::sync::MutexGuard<'_, Usage> {
+        self.usage
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub fn record_endpoint(&self, method: &str, route: &str) {
+        *self
+            .usage()
+            .endpoints
+            .entry(format!("{method} {route}"))
+            .or_default() += 1;
+    }
+
+    pub fn record_aggregation(&self, kind: Aggregation) {
+        *self
+            .usage()
+            .aggregation_kinds
+            .entry(format!("{kind:?}"))
+            .or_default() += 1;
+    }
+
+    /// Counts the kind of an aggregation request and each option it set
+    pub fn record_query(&self, key: &QueryKey) {
+        self.record_aggregation(key.aggregation_kind);
+        let options = [
+            ("fill_missing", key.fill_missing.is_some()),
+            ("include_lineage", key.include_lineage),
+            ("include_completeness", key.include_completeness),
+            ("include_power", key.include_power),
+            ("include_settlement", key.include_settlement),
+            ("include_sources", key.include_sources),
+            ("having", key.having.is_some()),
+            ("group_by", !key.group_by.is_empty()),
+            ("unit", key.unit != Default::default()),
+            ("timezone", key.timezone.is_some()),
+            ("series", key.series_id.is_some()),
+            ("as_recorded_by", key.as_recorded_by.is_some()),
+        ];
+        let mut usage = self.usage();
+        for (option, set) in options {
+            if set {
+                *usage.query_options.entry(option).or_default() += 1;
+            }
+        }
+    }
+
+    /// Report of the usage counted since the last one, starting a new period at `now`
+    pub fn take_report(&self, now: DateTime<Utc>) -> UsageReport {
+        let usage = std::mem::replace(
+            &mut *self.usage(),
+            Usage {
+                since: now,
+                ..Usage::default()
+            },
+        );
+        let build = build_info();
+        UsageReport {
+            version: build.version,
+            build_features: build.features,
+            configured_features: self.configured.clone(),
+            period_start: usage.since,
+            period_end: now,
+            endpoints: usage.endpoints,
+            aggregation_kinds: usage.aggregation_kinds,
+            query_options: usage.query_options,
+        }
+    }
+
+    /// Spawns the task writing a report into `dir` every `interval`, the first once a
+    /// full interval has passed
+    pub fn spawn(&self, dir: PathBuf, interval: Duration) {
+        let telemetry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let report = telemetry.take_report(Utc::now());
+                match write_report(&dir, &report) {
+                    Ok(path) => info!(path = %path.display(), "Wrote usage report"),
+                    Err(e) => error!("Unable to write usage report: {e}"),
+                }
+            }
+        });
+    }
+}
+
+/// Writes `report` as `usage-<period end>.json` in `dir`, renamed into place once
+/// complete so collectors never pick up a partial file
+pub fn write_report(dir: &Path, report: &UsageReport) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = format!("usage-{}.json", report.period_end.format("%Y%m%dT%H%M%SZ"));
+    let path = dir.join(name);
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_vec_pretty(report)?)?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Counts each request by its method and the template of the route it matched
+pub async fn count_usage(
+    State(telemetry): State<Telemetry>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(route) = request.extensions().get::<MatchedPath>() {
+        telemetry.record_endpoint(request.method().as_str(), route.as_str());
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::{Telemetry, write_report};
+    use crate::model::api_request::Aggregation;
+
+    #[test]
+    fn test_report_holds_the_usage_of_its_period() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let telemetry = Telemetry::new(vec!["response_cache"], start);
+        telemetry.record_endpoint("POST", "/timeseries/v1/query");
+        telemetry.record_endpoint("POST", "/timeseries/v1/query");
+        telemetry.record_aggregation(Aggregation::Hourly);
+
+        let end = start + TimeDelta::days(1);
+        let report = telemetry.take_report(end);
+        assert_eq!(report.period_start, start);
+        assert_eq!(report.configured_features, ["response_cache"]);
+        assert_eq!(report.endpoints["POST /timeseries/v1/query"], 2);
+        assert_eq!(report.aggregation_kinds["Hourly"], 1);
+
+        // The next period starts empty
+        let report = telemetry.take_report(end + TimeDelta::days(1));
+        assert_eq!(report.period_start, end);
+        assert!(report.endpoints.is_empty());
+
+        let dir = std::env::temp_dir().join(format!("usage-{}", std::process::id()));
+        let path = write_report(&dir, &report).unwrap();
+        assert!(path.ends_with("usage-20250103T000000Z.json"));
+        assert!(!path.with_extension("json.partial").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, ToPrimitive as _, Zero as _};
+use chrono::NaiveDate;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::model::csv::CSVRecord;
+
+/// Orders of magnitude between an ingestion and its series' history from which they are
+/// taken to be in different units, about 316×, halfway to a factor of 1000 on a log scale
+const MISMATCH_ORDERS: f64 = 2.5;
+
+/// An ingestion whose readings are about a power of 1000 larger or smaller than those
+/// its series held before, such as MWh uploaded as kWh
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct UnitMismatch {
+    /// Median daily mean reading of the ingestion over that of the series' history
+    pub ratio: f64,
+    /// Nearest power of 1000 to the ratio, the factor the amounts appear to be off by
+    pub factor: f64,
+}
+
+impl UnitMismatch {
+    /// Compares the typical reading of an ingestion with that of its series' history,
+    /// both the median of the daily mean readings
+    pub fn between(ingestion: f64, history: f64) -> Option<Self> {
+        if !(ingestion > 0.0 && history > 0.0) {
+            return None;
+        }
+        let ratio = ingestion / history;
+        let orders = ratio.log10();
+        (orders.abs() >= MISMATCH_ORDERS).then(|| Self {
+            ratio,
+            factor: 1000f64.powf((orders / 3.0).round()),
+        })
+    }
+}
+
+/// Median over the UTC days of the readings of the absolute mean reading of each day,
+/// as the series' history is summarized from `ts_daily_summary`. Days netting to zero
+/// are left out, `None` when no day is left.
+pub fn median_daily_reading(readings: &[CSVRecord]) -> Option<f64> {
+    let mut days: BTreeMap<NaiveDate, (BigDecimal, u32)> = BTreeMap::new();
+    for reading in readings {
+        let (total, count) = days.entry(reading.datetime.date_naive()).or_default();
+        *total += &reading.amount;
+        *count += 1;
+    }
+    let mut means: Vec<f64> = days
+        .into_values()
+        .filter(|(total, _)| !total.is_zero())
+        .filter_map(|(total, count)| (total / BigDecimal::from(count)).abs().to_f64())
+        .collect();
+    if means.is_empty() {
+        return None;
+    }
+    means.sort_by(f64::total_cmp);
+    let middle = means.len() / 2;
+    Some(if means.len().is_multiple_of(2) {
+        (means[middle - 1] + means[middle]) / 2.0
+    } else {
+        means[middle]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+    use test_case::test_case;
+
+    use super::{UnitMismatch, median_daily_reading};
+    use crate::model::csv::CSVRecord;
+
+    #[test_case(2.0, 2.1, None; "same unit")]
+    #[test_case(40.0, 2.0, None; "a windy month")]
+    #[test_case(2000.0, 2.0, Some(1000.0); "Wh uploaded as kWh")]
+    #[test_case(0.0021, 2.0, Some(0.001); "MWh uploaded as kWh")]
+    #[test_case(2.0, 0.0, None; "no history")]
+    fn test_mismatch_is_about_a_power_of_1000(ingestion: f64, history: f64, factor: Option<f64>) {
+        assert_eq!(
+            UnitMismatch::between(ingestion, history).map(|mismatch| mismatch.factor),
+            factor
+        );
+    }
+
+    #[test]
+    fn test_median_daily_reading() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let readings: Vec<_> = [1, 3, 10, 30, 5, -5]
+            .into_iter()
+            .enumerate()
+            .map(|(index, amount)| CSVRecord {
+                // Two readings a day
+                datetime: start + TimeDelta::hours(12 * i64::try_from(index).unwrap()),
+                amount: BigDecimal::from(amount),
+                extra: None,
+            })
+            .collect();
+        // Daily means of 2 and 20, the third day netting to zero
+        assert_eq!(median_daily_reading(&readings), Some(11.0));
+        assert_eq!(median_daily_reading(&readings[4..]), None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use object_store::{
+    aws::AmazonS3Builder, azure::MicrosoftAzureBuilder, gcp::GoogleCloudStorageBuilder,
+    path::Path as ObjectPath, signer::Signer,
+};
+use url::Url;
+
+use crate::{
+    config::AppConfig,
+    file_reader::{Compression, ReadingsFormat, file_kind},
+};
+
+/// Schemes of the buckets uploads can be signed for
+const BUCKET_SCHEMES: [&str; 3] = ["s3", "gs", "az"];
+
+/// Whether `url` names a bucket, and optional prefix, pre-signed uploads can target
+pub fn is_bucket_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| {
+        BUCKET_SCHEMES.contains(&url.scheme()) && url.has_host() && url.query().is_none()
+    })
+}
+
+/// A pre-signed `PUT` URL and the object it uploads
+#[derive(Debug)]
+pub struct IssuedUpload {
+    pub upload_url: Url,
+    pub location: Url,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Bucket clients upload large readings files to directly, through URLs signed with the
+/// service's credentials, so their bytes never pass through the API. Each API key uploads
+/// under a prefix of its own and can only have the files there ingested.
+#[derive(Clone, Debug)]
+pub struct UploadBucket {
+    signer: Arc<dyn Signer>,
+    /// `upload_bucket` without a trailing slash
+    root: String,
+    url_ttl: Duration,
+}
+
+impl UploadBucket {
+    /// Signs with the credentials of the usual `AWS_*`, `GOOGLE_*` or `AZURE_*`
+    /// environment variables, `None` when `upload_bucket` is unset
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>, object_store::Error> {
+        let Some(bucket) = &config.upload_bucket else {
+            return Ok(None);
+        };
+        let signer: Arc<dyn Signer> = match bucket.split_once("://") {
+            Some(("s3", _)) => Arc::new(AmazonS3Builder::from_env().with_url(bucket).build()?),
+            Some(("gs", _)) => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_url(bucket)
+                    .build()?,
+            ),
+            Some(("az", _)) => {
+                Arc::new(MicrosoftAzureBuilder::from_env().with_url(bucket).build()?)
+            }
+            _ => {
+                return Err(object_store::Error::NotSupported {
+                    source: format!("unsupported upload bucket {bucket}").into(),
+                });
+            }
+        };
+        Ok(Some(Self::new(signer, bucket, config.upload_url_ttl())))
+    }
+
+    pub fn new(signer: Arc<dyn Signer>, bucket: &str, url_ttl: Duration) -> Self {
+        Self {
+            signer,
+            root: bucket.trim_end_matches('/').to_string(),
+            url_ttl,
+        }
+    }
+
+    /// Signs a `PUT` of `file_name` under the prefix of `api_key_id`, stamped with `now`
+    /// so uploads of the same name never overwrite each other. `None` when the name is not
+    /// that of a readings file or holds characters other than letters, digits, `.`, `_`
+    /// and `-`.
+    pub async fn issue(
+        &self,
+        api_key_id: i64,
+        file_name: &str,
+        now: DateTime<Utc>,
+    ) -> Option<Result<IssuedUpload, object_store::Error>> {
+        let plain = file_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !plain || file_kind(file_name).is_none() {
+            return None;
+        }
+        let location = format!(
+            "{}/{api_key_id}/{}-{file_name}",
+            self.root,
+            now.timestamp_micros()
+        );
+        Some(self.sign(&location, now).await)
+    }
+
+    async fn sign(
+        &self,
+        location: &str,
+        now: DateTime<Utc>,
+    ) -> Result<IssuedUpload, object_store::Error> {
+        let location = Url::parse(location).map_err(|e| object_store::Error::Generic {
+            store: "upload bucket",
+            source: Box::new(e),
+        })?;
+        let path = ObjectPath::from_url_path(location.path())?;
+        let upload_url = self
+            .signer
+            .signed_url(Method::PUT, &path, self.url_ttl)
+            .await?;
+        Ok(IssuedUpload {
+            upload_url,
+            location,
+            expires_at: now + self.url_ttl,
+        })
+    }
+
+    /// `location` and the format of its file when it is a readings file `api_key_id` could
+    /// have been issued an upload URL for, directly under its prefix
+    pub fn uploaded_by(
+        &self,
+        location: &str,
+        api_key_id: i64,
+    ) -> Option<(Url, (ReadingsFormat, Compression))> {
+        let name = location.strip_prefix(&format!("{}/{api_key_id}/", self.root))?;
+        if name.contains(['/', '?', '#']) {
+            return None;
+        }
+        Some((Url::parse(location).ok()?, file_kind(name)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use object_store::aws::AmazonS3Builder;
+    use test_case::test_case;
+
+    use super::UploadBucket;
+    use crate::file_reader::{Compression, ReadingsFormat};
+
+    fn bucket() -> UploadBucket {
+        let s3 = AmazonS3Builder::new()
+            .with_url("s3://meters/uploads")
+            .with_region("eu-west-2")
+            .with_access_key_id("AKIDEXAMPLE")
+            .with_secret_access_key("secret")
+            .build()
+            .unwrap();
+        UploadBucket::new(
+            Arc::new(s3),
+            "s3://meters/uploads/",
+            Duration::from_secs(600),
+        )
+    }
+
+    #[test_case("s3://meters/uploads", true)]
+    #[test_case("gs://meters", true)]
+    #[test_case("az://readings/uploads", true)]
+    #[test_case("https://example.com/uploads", false)]
+    #[test_case("s3://meters/uploads?versionId=1", false)]
+    #[test_case("uploads", false)]
+    fn test_is_bucket_url(url: &str, expected: bool) {
+        assert_eq!(super::is_bucket_url(url), expected);
+    }
+
+    #[tokio::test]
+    async fn test_uploads_are_signed_under_the_prefix_of_their_key() {
+        let bucket = bucket();
+        let now = "2025-06-01T10:00:00Z".parse().unwrap();
+        let issued = bucket
+            .issue(7, "readings-2025.csv.zst", now)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            issued.location.as_str(),
+            "s3://meters/uploads/7/1748772000000000-readings-2025.csv.zst"
+        );
+        assert_eq!(issued.expires_at, now + Duration::from_secs(600));
+        assert_eq!(
+            issued.upload_url.path(),
+            "/meters/uploads/7/1748772000000000-readings-2025.csv.zst"
+        );
+        let query = issued.upload_url.query().unwrap();
+        assert!(query.contains("X-Amz-Expires=600"));
+        assert!(query.contains("X-Amz-Signature="));
+
+        let (location, kind) = bucket.uploaded_by(issued.location.as_str(), 7).unwrap();
+        assert_eq!(location, issued.location);
+        assert_eq!(kind, (ReadingsFormat::Csv, Compression::Zstd));
+        assert_eq!(bucket.uploaded_by(issued.location.as_str(), 8), None);
+    }
+
+    #[test_case("readings.parquet")]
+    #[test_case("../readings.csv")]
+    #[test_case("daily readings.csv")]
+    #[tokio::test]
+    async fn test_only_plain_readings_file_names_are_signed(file_name: &str) {
+        let now = "2025-06-01T10:00:00Z".parse().unwrap();
+        assert!(bucket().issue(7, file_name, now).await.is_none());
+    }
+
+    #[test_case("s3://meters/uploads/7/1-readings.csv", true)]
+    #[test_case("s3://meters/uploads/7/nested/readings.csv", false)]
+    #[test_case("s3://meters/uploads/7/1-readings.csv?versionId=2", false)]
+    #[test_case("s3://meters/uploads/7/1-readings.parquet", false)]
+    #[test_case("s3://meters/uploads/70/1-readings.csv", false)]
+    #[test_case("s3://meters/other/7/1-readings.csv", false)]
+    #[test_case("gs://meters/uploads/7/1-readings.csv", false)]
+    fn test_only_files_under_the_prefix_of_the_key_are_ingested(location: &str, owned: bool) {
+        assert_eq!(bucket().uploaded_by(location, 7).is_some(), owned);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, ToPrimitive as _, Zero as _};
+use chrono::Datelike as _;
+
+use crate::{
+    model::{
+        api_response::{AggregationQueryRecord, MonthlyVariance, ProfileBand},
+        database::MeterProfile,
+    },
+    rounding::RoundingPolicy,
+};
+
+/// Places an actual monthly total within the expected generation bands
+pub fn band(actual: &BigDecimal, p50: &BigDecimal, p90: &BigDecimal) -> ProfileBand {
+    if actual >= p50 {
+        ProfileBand::AboveP50
+    } else if actual >= p90 {
+        ProfileBand::BetweenP90AndP50
+    } else {
+        ProfileBand::BelowP90
+    }
+}
+
+/// Compares monthly actuals against the calendar month P50/P90 profile, months without a
+/// profile entry or without readings are reported without a variance. Actuals are rounded
+/// by `rounding` first so the band matches the figures reported to the caller.
+pub fn monthly_variance(
+    profile: &[MeterProfile],
+    actuals: Vec<AggregationQueryRecord>,
+    rounding: RoundingPolicy,
+) -> Vec<MonthlyVariance> {
+    let by_month: HashMap<u32, &MeterProfile> = profile
+        .iter()
+        .filter_map(|entry| u32::try_from(entry.month).ok().map(|month| (month, entry)))
+        .collect();
+
+    actuals
+        .into_iter()
+        .map(|record| {
+            let expected = by_month.get(&record.datetime.month());
+            let actual_kwh = record.total_amount.map(|actual| rounding.apply(&actual));
+            let p50_kwh = expected.map(|entry| rounding.apply(&entry.p50_kwh));
+            let p90_kwh = expected.map(|entry| rounding.apply(&entry.p90_kwh));
+
+            let (variance_to_p50_kwh, variance_to_p50_pct, band) =
+                match (&actual_kwh, &p50_kwh, &p90_kwh) {
+                    (Some(actual), Some(p50), Some(p90)) => {
+                        let variance = actual - p50;
+                        let pct = (!p50.is_zero())
+                            .then(|| (&variance / p50).to_f64())
+                            .flatten()
+                            .map(|ratio| ratio * 100.0);
+                        (Some(variance), pct, Some(band(actual, p50, p90)))
+                    }
+                    _ => (None, None, None),
+                };
+
+            MonthlyVariance {
+                month: record.datetime,
+                actual_kwh,
+                p50_kwh,
+                p90_kwh,
+                variance_to_p50_kwh,
+                variance_to_p50_pct,
+                band,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone, Utc};
+
+    use super::monthly_variance;
+    use crate::{
+        model::{
+            api_response::{AggregationQueryRecord, ProfileBand},
+            database::MeterProfile,
+        },
+        rounding::{RoundingMode, RoundingPolicy},
+    };
+
+    fn profile(month: i16, p50: i64, p90: i64) -> MeterProfile {
+        MeterProfile {
+            meter_id: 1,
+            month,
+            p50_kwh: BigDecimal::from(p50),
+            p90_kwh: BigDecimal::from(p90),
+            uploaded_at: Utc::now(),
+        }
+    }
+
+    fn actual(month: u32, total: Option<i64>) -> AggregationQueryRecord {
+        AggregationQueryRecord {
+            datetime: Utc.with_ymd_and_hms(2025, month, 1, 0, 0, 0).unwrap(),
+            total_amount: total.map(BigDecimal::from),
+        }
+    }
+
+    #[test]
+    fn test_monthly_variance_bands() {
+        let profile = [
+            profile(1, 100, 80),
+            profile(2, 100, 80),
+            profile(3, 100, 80),
+        ];
+        let actuals = vec![
+            actual(1, Some(110)),
+            actual(2, Some(90)),
+            actual(3, Some(50)),
+            actual(4, Some(10)),
+            actual(5, None),
+        ];
+
+        let variance = monthly_variance(&profile, actuals, RoundingPolicy::default());
+        let bands: Vec<_> = variance.iter().map(|month| month.band).collect();
+        assert_eq!(
+            bands,
+            [
+                Some(ProfileBand::AboveP50),
+                Some(ProfileBand::BetweenP90AndP50),
+                Some(ProfileBand::BelowP90),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(variance[0].variance_to_p50_kwh, Some(BigDecimal::from(10)));
+        assert!((variance[2].variance_to_p50_pct.unwrap() + 50.0).abs() < 1e-9);
+        assert!(variance[3].p50_kwh.is_none());
+    }
+
+    #[test]
+    fn test_monthly_variance_bands_rounded_actuals() {
+        let profile = [profile(1, 100, 80)];
+        let actuals = vec![AggregationQueryRecord {
+            datetime: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            total_amount: Some("99.5".parse().unwrap()),
+        }];
+        let rounding = RoundingPolicy {
+            mode: RoundingMode::HalfEven,
+            scale: Some(0),
+        };
+
+        let variance = monthly_variance(&profile, actuals, rounding);
+        assert_eq!(variance[0].actual_kwh, Some(BigDecimal::from(100)));
+        assert_eq!(variance[0].band, Some(ProfileBand::AboveP50));
+    }
+}
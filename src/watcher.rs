@@ -0,0 +1,318 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use deadpool_diesel::postgres::Pool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::AppConfig,
+    db::{
+        query::source_ingested,
+        seed_database::{insert_ingestion_with_drift, prepare_readings},
+    },
+    drift::DriftConfig,
+    file_reader::{Compression, FileLocation, ReadingsFormat},
+    live,
+    model::{api_response::IngestionNotification, csv::CsvSchema},
+    register::RegisterConfig,
+    state::AppState,
+};
+
+/// Size and modification time of a file, compared between polls so files still being
+/// copied into the directory are left alone
+type Fingerprint = (u64, SystemTime);
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatchError {
+    #[error("unable to read file {0}")]
+    Io(io::Error),
+
+    #[error("invalid configuration {0}")]
+    Config(String),
+
+    #[error("unable to get a database connection {0}")]
+    Pool(deadpool_diesel::PoolError),
+
+    #[error("database interaction failed {0}")]
+    Interaction(deadpool_diesel::InteractError),
+
+    #[error("database error {0}")]
+    Database(diesel::result::Error),
+}
+
+impl WatchError {
+    /// Connection failures are retried on the next poll, anything else would fail again
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::Pool(_) | Self::Interaction(_))
+    }
+}
+
+pub enum Outcome {
+    Ingested(IngestionNotification),
+    /// The file was ingested before, under the same path
+    Duplicate,
+    /// The file holds no valid readings
+    Empty,
+}
+
+/// Polls a directory for new readings files and ingests each once, as the seed file is, using
+/// the file path as the ingestion source so files already ingested are skipped
+struct DirWatcher {
+    state: AppState,
+    dir: PathBuf,
+    csv_schema: CsvSchema,
+    register_config: RegisterConfig,
+    drift_config: DriftConfig,
+    /// Files seen changing on the previous poll
+    pending: HashMap<PathBuf, Fingerprint>,
+    /// Files ingested, already ingested or failed since startup
+    settled: HashSet<PathBuf>,
+}
+
+/// Spawns the watcher polling `dir` every `interval`
+pub fn spawn(state: AppState, dir: PathBuf, interval: Duration) -> Result<JoinHandle<()>, String> {
+    let register_config = RegisterConfig::from_env()?;
+    let drift_config = DriftConfig::from_env(state.config.reading_interval())?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    info!(dir = %dir.display(), interval_secs = interval.as_secs(), "Watching for readings files");
+
+    let mut watcher = DirWatcher {
+        csv_schema: CsvSchema::from(&state.config),
+        state,
+        dir,
+        register_config,
+        drift_config,
+        pending: HashMap::new(),
+        settled: HashSet::new(),
+    };
+    // A poll under way when shutdown is requested finishes ingesting its file first
+    let shutdown = watcher.state.shutdown.clone();
+    Ok(shutdown
+        .clone()
+        .spawn("watched directory ingestion", async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    () = shutdown.requested() => break,
+                    _ = ticker.tick() => watcher.poll().await,
+                }
+            }
+        }))
+}
+
+/// CSV, JSON and NDJSON files, compressed or not, directly inside `dir`, ordered by name
+fn readings_files(dir: &Path) -> io::Result<Vec<(PathBuf, Fingerprint)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !FileLocation::Local(path.clone()).is_readings_file() {
+            continue;
+        }
+        let metadata = fs::metadata(&path)?;
+        if metadata.is_file() {
+            files.push((path, (metadata.len(), metadata.modified()?)));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+impl DirWatcher {
+    async fn poll(&mut self) {
+        if self.state.read_only.is_enabled() || !self.state.leader.is_leader() {
+            debug!("Not leading or read-only, skipping watched directory poll");
+            return;
+        }
+        let files = match readings_files(&self.dir) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!(dir = %self.dir.display(), "Unable to list watched directory: {e}");
+                return;
+            }
+        };
+        self.pending
+            .retain(|path, _| files.iter().any(|(file, _)| file == path));
+
+        for (path, fingerprint) in files {
+            if self.settled.contains(&path) {
+                continue;
+            }
+            // Only ingest once the file is unchanged since the previous poll
+            if self.pending.insert(path.clone(), fingerprint) != Some(fingerprint) {
+                continue;
+            }
+            self.pending.remove(&path);
+
+            let source = path.display().to_string();
+            match self.ingest(&path).await {
+                Ok(Outcome::Ingested(notification)) => {
+                    info!(
+                        source,
+                        ingestion_id = notification.ingestion_id,
+                        rows = notification.rows,
+                        "Ingested watched file"
+                    );
+                    self.refresh_hot_cache().await;
+                    self.state.invalidate_response_cache().await;
+                    self.state.ingestion_events.publish(notification);
+                }
+                Ok(Outcome::Duplicate) => info!(source, "Watched file already ingested"),
+                Ok(Outcome::Empty) => warn!(source, "Watched file has no valid readings"),
+                Err(e) if e.is_transient() => {
+                    warn!(source, "Unable to ingest watched file, retrying: {e}");
+                    continue;
+                }
+                Err(e) => error!(source, "Unable to ingest watched file: {e}"),
+            }
+            self.settled.insert(path);
+        }
+    }
+
+    async fn ingest(&self, path: &Path) -> Result<Outcome, WatchError> {
+        ingest(
+            self.state.db.primary(),
+            path,
+            self.csv_schema.clone(),
+            self.register_config.clone(),
+            self.drift_config,
+            self.state
+                .config
+                .source_priority(&path.display().to_string()),
+        )
+        .await
+    }
+
+    async fn refresh_hot_cache(&self) {
+        if let Some(cache) = &self.state.hot_cache
+            && let Err(e) = cache.refresh(self.state.db.primary()).await
+        {
+            error!("Unable to refresh hot cache after watched ingestion: {e}");
+        }
+    }
+}
+
+/// Ingests the readings file at `path` once, as a watched file is, for one-off
+/// ingestions without a running server
+pub async fn ingest_file(
+    config: &AppConfig,
+    pg_pool: &Pool,
+    path: &Path,
+) -> Result<Outcome, WatchError> {
+    let register_config = RegisterConfig::from_env().map_err(WatchError::Config)?;
+    let drift_config =
+        DriftConfig::from_env(config.reading_interval()).map_err(WatchError::Config)?;
+    ingest(
+        pg_pool,
+        path,
+        CsvSchema::from(config),
+        register_config,
+        drift_config,
+        config.source_priority(&path.display().to_string()),
+    )
+    .await
+}
+
+async fn ingest(
+    pg_pool: &Pool,
+    path: &Path,
+    csv_schema: CsvSchema,
+    register_config: RegisterConfig,
+    drift_config: DriftConfig,
+    priority: i32,
+) -> Result<Outcome, WatchError> {
+    let source = path.display().to_string();
+    let path = path.to_path_buf();
+
+    let conn = pg_pool.get().await.map_err(WatchError::Pool)?;
+    conn.interact(move |conn| {
+        if source_ingested(&source, conn).map_err(WatchError::Database)? {
+            return Ok(Outcome::Duplicate);
+        }
+        let (format, compression) = FileLocation::Local(path.clone())
+            .kind()
+            .unwrap_or((ReadingsFormat::Csv, Compression::Uncompressed));
+        let file = File::open(&path).map_err(WatchError::Io)?;
+        let reader = compression.decoder(file).map_err(WatchError::Io)?;
+        let prepared = prepare_readings(
+            BufReader::new(reader),
+            format,
+            &csv_schema,
+            &register_config,
+            &drift_config,
+        );
+        let Some((first_reading_at, last_reading_at)) = live::reading_span(&prepared.readings)
+        else {
+            return Ok(Outcome::Empty);
+        };
+        let received = prepared.readings.len();
+
+        let ingested = insert_ingestion_with_drift(
+            source.clone(),
+            None,
+            priority,
+            prepared,
+            &drift_config,
+            conn,
+        )
+        .map_err(WatchError::Database)?;
+        Ok(match ingested {
+            Some((ingestion_id, rows)) => Outcome::Ingested(IngestionNotification {
+                ingestion_id,
+                source,
+                rows,
+                updated_rows: 0,
+                skipped_rows: received.saturating_sub(rows),
+                first_reading_at,
+                last_reading_at,
+                unit_mismatch: None,
+            }),
+            None => Outcome::Duplicate,
+        })
+    })
+    .await
+    .map_err(WatchError::Interaction)?
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::readings_files;
+
+    #[test]
+    fn test_readings_files_lists_only_readings_files_in_name_order() {
+        let dir = std::env::temp_dir().join(format!("watcher-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested.csv")).unwrap();
+        fs::write(dir.join("b.csv"), "datetime,amount\n").unwrap();
+        fs::write(dir.join("a.csv"), "datetime,amount\n").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        fs::write(dir.join("c.csv.gz"), "").unwrap();
+        fs::write(dir.join("d.ndjson"), "{}\n").unwrap();
+
+        let names: Vec<_> = readings_files(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|(path, (len, _))| (path.file_name().unwrap().to_owned(), len))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            names,
+            [
+                ("a.csv".into(), 16),
+                ("b.csv".into(), 16),
+                ("c.csv.gz".into(), 0),
+                ("d.ndjson".into(), 3)
+            ]
+        );
+    }
+}
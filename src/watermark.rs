@@ -0,0 +1,204 @@
+use std::{fs::File, io, path::Path};
+
+use arrow_array::{Array as _, Decimal128Array, TimestampMicrosecondArray};
+use bigdecimal::{
+    BigDecimal, Signed as _,
+    num_bigint::{BigInt, Sign},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac as _};
+use parquet::{arrow::arrow_reader::ParquetRecordBatchReaderBuilder, errors::ParquetError};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{model::AMOUNT_SCALE, rounding};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatermarkError {
+    #[error("io error {0}")]
+    Io(#[from] io::Error),
+
+    #[error("csv error {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("parquet error {0}")]
+    Parquet(#[from] ParquetError),
+
+    #[error("arrow error {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    #[error("not an export: {0}")]
+    Format(String),
+}
+
+/// Marks the amounts of one API key's exports by replacing the digit at [`AMOUNT_SCALE`],
+/// the millionth of a kWh, with one derived from the key, the bucket and a secret, so a
+/// leaked export can be traced back to the key with [`trace`]
+#[derive(Clone, Debug)]
+pub struct Watermark {
+    secret: Vec<u8>,
+    api_key_id: i64,
+}
+
+impl Watermark {
+    pub fn new(secret: &[u8], api_key_id: i64) -> Self {
+        Self {
+            secret: secret.to_vec(),
+            api_key_id,
+        }
+    }
+
+    /// Digit carried by the amount of the bucket or reading at `datetime`
+    fn digit(&self, datetime: DateTime<Utc>) -> u8 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC can take key of any size");
+        mac.update(format!("{}:{}", self.api_key_id, datetime.timestamp_micros()).as_bytes());
+        mac.finalize().into_bytes()[0] % 10
+    }
+
+    /// `amount` as exported at `datetime`, rounded by the policy then marked
+    pub fn mark(&self, datetime: DateTime<Utc>, amount: &BigDecimal) -> BigDecimal {
+        let rounded = rounding::current()
+            .apply(amount)
+            .with_scale_round(AMOUNT_SCALE, bigdecimal::RoundingMode::HalfEven);
+        let (units, _) = rounded.as_bigint_and_exponent();
+        let magnitude = units.magnitude() / 10u8 * 10u8 + self.digit(datetime);
+        let sign = if units.sign() == Sign::Minus {
+            Sign::Minus
+        } else {
+            Sign::Plus
+        };
+        BigDecimal::new(BigInt::from_biguint(sign, magnitude), AMOUNT_SCALE)
+    }
+
+    fn carried_by(&self, datetime: DateTime<Utc>, amount: &BigDecimal) -> bool {
+        let (units, _) = amount.with_scale(AMOUNT_SCALE).as_bigint_and_exponent();
+        units.abs() % 10u8 == BigInt::from(self.digit(datetime))
+    }
+}
+
+/// How many of an export's amounts carry the watermark of one key
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct TraceMatch {
+    pub api_key_id: i64,
+    pub matched: usize,
+    pub amounts: usize,
+}
+
+/// Scores each of `api_key_ids` against the amounts of an export, best match first. An
+/// export of a key matches all of its amounts, any other about one in ten.
+pub fn trace(
+    secret: &[u8],
+    api_key_ids: &[i64],
+    amounts: &[(DateTime<Utc>, BigDecimal)],
+) -> Vec<TraceMatch> {
+    let mut matches: Vec<_> = api_key_ids
+        .iter()
+        .map(|&api_key_id| {
+            let watermark = Watermark::new(secret, api_key_id);
+            TraceMatch {
+                api_key_id,
+                matched: amounts
+                    .iter()
+                    .filter(|(datetime, amount)| watermark.carried_by(*datetime, amount))
+                    .count(),
+                amounts: amounts.len(),
+            }
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.matched
+            .cmp(&a.matched)
+            .then(a.api_key_id.cmp(&b.api_key_id))
+    });
+    matches
+}
+
+/// Timestamps and amounts of a CSV export, or of a Parquet export of readings or
+/// buckets, skipping empty buckets
+pub fn read_export(path: &Path) -> Result<Vec<(DateTime<Utc>, BigDecimal)>, WatermarkError> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "parquet")
+    {
+        return read_parquet(File::open(path)?);
+    }
+    let mut amounts = Vec::new();
+    for row in csv::Reader::from_path(path)?.records() {
+        let row = row?;
+        let (Some(datetime), Some(amount)) = (row.get(0), row.get(1)) else {
+            return Err(WatermarkError::Format(
+                "expected datetime and amount".to_string(),
+            ));
+        };
+        if amount.is_empty() {
+            continue;
+        }
+        let datetime = DateTime::parse_from_rfc3339(datetime)
+            .map_err(|e| WatermarkError::Format(format!("{datetime}: {e}")))?;
+        let amount = amount
+            .parse()
+            .map_err(|e| WatermarkError::Format(format!("{amount}: {e}")))?;
+        amounts.push((datetime.to_utc(), amount));
+    }
+    Ok(amounts)
+}
+
+fn read_parquet(file: File) -> Result<Vec<(DateTime<Utc>, BigDecimal)>, WatermarkError> {
+    let mut amounts = Vec::new();
+    for batch in ParquetRecordBatchReaderBuilder::try_new(file)?.build()? {
+        let batch = batch?;
+        let datetimes = batch
+            .column_by_name("datetime")
+            .and_then(|column| column.as_any().downcast_ref::<TimestampMicrosecondArray>())
+            .ok_or_else(|| WatermarkError::Format("no datetime column".to_string()))?;
+        let totals = ["amount", "total_amount"]
+            .into_iter()
+            .find_map(|name| batch.column_by_name(name))
+            .and_then(|column| column.as_any().downcast_ref::<Decimal128Array>())
+            .ok_or_else(|| WatermarkError::Format("no decimal amount column".to_string()))?;
+        let scale = i64::from(totals.scale());
+        for row in (0..batch.num_rows()).filter(|&row| totals.is_valid(row)) {
+            let datetime = DateTime::from_timestamp_micros(datetimes.value(row))
+                .ok_or_else(|| WatermarkError::Format(format!("row {row} out of range")))?;
+            amounts.push((
+                datetime,
+                BigDecimal::new(BigInt::from(totals.value(row)), scale),
+            ));
+        }
+    }
+    Ok(amounts)
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeDelta, TimeZone as _, Utc};
+
+    use super::{Watermark, trace};
+
+    #[test]
+    fn test_marked_exports_trace_back_to_their_key() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let watermark = Watermark::new(b"secret", 7);
+        let amounts: Vec<_> = (0..40)
+            .map(|hour| {
+                let datetime = start + TimeDelta::hours(hour);
+                let amount = BigDecimal::from(hour * 1000 - 1500) / BigDecimal::from(7);
+                let marked = watermark.mark(datetime, &amount);
+                // Only the millionth of a kWh is touched
+                assert!((&marked - &amount).abs() < BigDecimal::new(1.into(), 5));
+                (datetime, marked)
+            })
+            .collect();
+
+        let matches = trace(b"secret", &[3, 7, 11], &amounts);
+        assert_eq!(matches[0].api_key_id, 7);
+        assert_eq!(matches[0].matched, 40);
+        assert!(matches[1].matched < 20);
+        // Without the secret the watermark cannot be attributed
+        assert!(trace(b"guess", &[7], &amounts)[0].matched < 20);
+    }
+}